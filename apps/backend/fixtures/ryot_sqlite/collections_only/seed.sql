@@ -0,0 +1,5 @@
+INSERT INTO "user" ("name", "password", "lot", "preferences", "sink_integrations") VALUES ('demo', 'not-a-hash', 'N', '{}', '[]');
+INSERT INTO "user" ("name", "password", "lot", "preferences", "sink_integrations") VALUES ('other', 'not-a-hash', 'N', '{}', '[]');
+INSERT INTO "collection" ("name", "description", "user_id") VALUES ('Watchlist', 'Things I want to watch in the future.', 1);
+INSERT INTO "collection" ("name", "user_id", "visibility") VALUES ('Favorites', 1, 'PU');
+INSERT INTO "collection" ("name", "user_id") VALUES ('Not mine', 2);
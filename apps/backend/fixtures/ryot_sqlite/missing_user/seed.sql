@@ -0,0 +1 @@
+-- A backup of an instance without any users
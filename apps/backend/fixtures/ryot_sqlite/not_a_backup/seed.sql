@@ -0,0 +1,2 @@
+-- A SQLite database that was not created by Ryot
+DROP TABLE seaql_migrations;
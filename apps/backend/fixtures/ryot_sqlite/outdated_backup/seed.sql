@@ -0,0 +1,2 @@
+-- A backup of an instance that has not run the latest migration
+DELETE FROM seaql_migrations WHERE version = 'm20230812_000041_add_import_item_ids';
@@ -1,17 +1,284 @@
-use std::sync::Arc;
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    sync::{Arc, Mutex},
+};
 
-use apalis::prelude::{Job, JobContext, JobError};
+use apalis::{
+    prelude::{Job, JobContext, JobError, Storage},
+    sqlite::SqliteStorage,
+};
+use async_graphql::SimpleObject;
+use chrono::{Duration, Utc};
 use sea_orm::prelude::DateTimeUtc;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sqlx::SqlitePool;
 
 use crate::{
     entities::metadata,
     fitness::exercise::resolver::ExerciseService,
     importer::{DeployImportJobInput, ImporterService},
-    miscellaneous::resolver::MiscellaneousService,
-    models::fitness::Exercise,
+    miscellaneous::resolver::{BulkMediaActionInput, BulkMediaFilter, MiscellaneousService},
+    models::{
+        fitness::Exercise,
+        media::{ImportOrExportItem, ImportOrExportItemIdentifier},
+    },
+    monitoring::{count_provider_requests, record_job_finished},
 };
 
+// Job Registry
+
+/// The status of a running job
+#[derive(Debug, Clone, SimpleObject)]
+pub struct JobStatus {
+    pub id: String,
+    pub name: String,
+    pub user_id: Option<i32>,
+    pub started_on: DateTimeUtc,
+    /// The number of items processed, for jobs that report their progress.
+    pub processed: Option<usize>,
+    pub total: Option<usize>,
+    /// When the job is expected to finish, for jobs that can estimate it.
+    pub estimate: Option<JobEstimate>,
+    /// Whether the job stops early when it is cancelled
+    pub cancellable: bool,
+    pub cancel_requested: bool,
+}
+
+/// When a job is expected to finish, based on the time its items took so far
+#[derive(Debug, Clone, PartialEq, Eq, SimpleObject, Serialize, Deserialize)]
+pub struct JobEstimate {
+    pub finish_on: DateTimeUtc,
+    /// Whether too few items were processed for the estimate to be reliable
+    pub low_confidence: bool,
+}
+
+/// Whether the workers of this instance are picking up new jobs
+#[derive(Debug, Clone, SimpleObject)]
+pub struct WorkerStatus {
+    pub paused: bool,
+    pub paused_on: Option<DateTimeUtc>,
+    /// The number of jobs that are running.
+    pub running: usize,
+}
+
+/// Keeps track of the long running jobs on this instance so that they can
+/// report their progress and be cancelled.
+#[derive(Debug, Default)]
+pub struct JobRegistry {
+    jobs: Mutex<HashMap<String, JobStatus>>,
+    paused_on: Mutex<Option<DateTimeUtc>>,
+}
+
+/// Registers a job with the registry until it is dropped.
+pub struct JobHandle {
+    registry: Arc<JobRegistry>,
+    id: String,
+}
+
+impl JobHandle {
+    pub fn is_cancelled(&self) -> bool {
+        self.registry
+            .jobs
+            .lock()
+            .unwrap()
+            .get(&self.id)
+            .map_or(false, |j| j.cancel_requested)
+    }
+
+    pub fn set_progress(&self, processed: usize, total: usize) {
+        if let Some(job) = self.registry.jobs.lock().unwrap().get_mut(&self.id) {
+            job.processed = Some(processed);
+            job.total = Some(total);
+        }
+    }
+
+    pub fn set_estimate(&self, estimate: JobEstimate) {
+        if let Some(job) = self.registry.jobs.lock().unwrap().get_mut(&self.id) {
+            job.estimate = Some(estimate);
+        }
+    }
+}
+
+impl Drop for JobHandle {
+    fn drop(&mut self) {
+        self.registry.jobs.lock().unwrap().remove(&self.id);
+    }
+}
+
+impl JobRegistry {
+    pub fn register(
+        self: &Arc<Self>,
+        ctx: &JobContext,
+        name: &str,
+        user_id: Option<i32>,
+        cancellable: bool,
+    ) -> JobHandle {
+        let id = ctx.id().to_string();
+        self.jobs.lock().unwrap().insert(
+            id.clone(),
+            JobStatus {
+                id: id.clone(),
+                name: name.to_owned(),
+                user_id,
+                started_on: Utc::now(),
+                processed: None,
+                total: None,
+                estimate: None,
+                cancellable,
+                cancel_requested: false,
+            },
+        );
+        JobHandle {
+            registry: self.clone(),
+            id,
+        }
+    }
+
+    /// Request a job to be cancelled. Returns `false` if it is not running or
+    /// can not be cancelled.
+    pub fn cancel(&self, id: &str) -> bool {
+        match self.jobs.lock().unwrap().get_mut(id) {
+            Some(job) if job.cancellable => {
+                job.cancel_requested = true;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<JobStatus> {
+        self.jobs.lock().unwrap().get(id).cloned()
+    }
+
+    pub fn statuses(&self) -> Vec<JobStatus> {
+        let mut jobs = self
+            .jobs
+            .lock()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+        jobs.sort_by_key(|j| j.started_on);
+        jobs
+    }
+
+    /// Stop new jobs from starting. Returns `false` if already paused.
+    pub fn pause(&self) -> bool {
+        let mut paused_on = self.paused_on.lock().unwrap();
+        if paused_on.is_some() {
+            return false;
+        }
+        *paused_on = Some(Utc::now());
+        true
+    }
+
+    /// Let the jobs held back by `pause` run. Returns `false` if not paused.
+    pub fn resume(&self) -> bool {
+        self.paused_on.lock().unwrap().take().is_some()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused_on.lock().unwrap().is_some()
+    }
+
+    pub fn worker_status(&self) -> WorkerStatus {
+        let paused_on = *self.paused_on.lock().unwrap();
+        WorkerStatus {
+            paused: paused_on.is_some(),
+            paused_on,
+            running: self.jobs.lock().unwrap().len(),
+        }
+    }
+}
+
+/// How long a job that was picked up while the workers are paused stays in the
+/// queue before it is picked up again.
+const PAUSED_JOB_DELAY_SECONDS: i64 = 60;
+
+/// Put a job that was picked up while the workers are paused or the instance is
+/// shutting down back in the queue, where it stays pending until the workers
+/// are resumed. Returns the job if it should run.
+async fn requeue_while_paused<T>(job: T, ctx: &JobContext) -> Result<Option<T>, JobError>
+where
+    T: Job + Serialize + DeserializeOwned + Send + Sync + Unpin + 'static,
+{
+    let service = ctx.data::<Arc<MiscellaneousService>>().unwrap();
+    if !service.jobs.is_paused() && !service.shutdown.is_requested() {
+        return Ok(Some(job));
+    }
+    // DEV: the job is stored again before this one is marked as done, so it is
+    // not lost if the instance stops while the workers are paused. Jobs are
+    // held back the same way once a shutdown is requested, so that an import
+    // that puts itself back in the queue is not interrupted again.
+    let mut storage = ctx.data::<SqliteStorage<T>>().unwrap().clone();
+    storage
+        .schedule(
+            job,
+            Utc::now() + Duration::seconds(PAUSED_JOB_DELAY_SECONDS),
+        )
+        .await
+        .map_err(|e| JobError::Failed(Box::new(e)))?;
+    tracing::debug!(
+        "Put a {} job back in the queue since the workers are paused or stopping",
+        T::NAME
+    );
+    Ok(None)
+}
+
+/// Record the outcome of a job. Failed jobs are returned as errors so that the
+/// worker marks them as failed.
+fn complete_job<T, E: Debug>(name: &'static str, result: Result<T, E>) -> Result<(), JobError> {
+    record_job_finished(name, result.is_ok());
+    if let Err(e) = result {
+        tracing::error!("The {name} job failed: {e:?}");
+        return Err(JobError::Failed(format!("{e:?}").into()));
+    }
+    Ok(())
+}
+
+/// The type of the job that has waited the longest to be picked up by a worker
+/// and the number of seconds it has waited. Jobs that are scheduled to run
+/// later are not counted.
+pub async fn oldest_pending_job(pool: &SqlitePool) -> sqlx::Result<Option<(String, i64)>> {
+    let now = Utc::now().timestamp();
+    // DEV: apalis keeps its queue in the `Jobs` table of the scheduler database
+    let oldest: Option<(String, i64)> = sqlx::query_as(
+        "SELECT job_type, run_at FROM Jobs WHERE status = 'Pending' AND run_at <= ? \
+        ORDER BY run_at LIMIT 1",
+    )
+    .bind(now)
+    .fetch_optional(pool)
+    .await?;
+    Ok(oldest.map(|(job, run_at)| (job, now - run_at)))
+}
+
+/// The number of jobs that are waiting in the queue, including the ones that
+/// were put back while the workers are paused.
+pub async fn pending_job_count(pool: &SqlitePool) -> sqlx::Result<i64> {
+    let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM Jobs WHERE status = 'Pending'")
+        .fetch_one(pool)
+        .await?;
+    Ok(count)
+}
+
+/// The number of imports left in the queue whose input has an encrypted
+/// secret, which can only be read with the key that it was sealed with.
+pub async fn queued_imports_with_secrets(pool: &SqlitePool) -> sqlx::Result<usize> {
+    // DEV: failed jobs are picked up again until they run out of attempts
+    let jobs: Vec<(String,)> = sqlx::query_as(
+        "SELECT job FROM Jobs WHERE job_type = ? AND status NOT IN ('Done', 'Killed')",
+    )
+    .bind(ImportMedia::NAME)
+    .fetch_all(pool)
+    .await?;
+    Ok(jobs
+        .iter()
+        .filter_map(|(job,)| serde_json::from_str::<ImportMedia>(job).ok())
+        .filter(|j| j.input.has_encrypted_secret())
+        .count())
+}
+
 // Cron Jobs
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -27,22 +294,56 @@ impl Job for ScheduledJob {
     const NAME: &'static str = "apalis::ScheduledJob";
 }
 
+/// Take the lease on a cron job for half of its interval so that other
+/// instances firing at the same time skip it.
+/// Runs that fall while the workers are paused or stopping are skipped.
+async fn acquire_cron_lease(ctx: &JobContext, name: &str, every_hours: i32) -> bool {
+    let service = ctx.data::<Arc<MiscellaneousService>>().unwrap();
+    if service.jobs.is_paused() || service.shutdown.is_requested() {
+        tracing::debug!("Skipping {name} since the workers are paused or stopping");
+        return false;
+    }
+    let acquired = service
+        .acquire_job_lease(name, Duration::minutes(i64::from(every_hours) * 30))
+        .await
+        .unwrap();
+    if !acquired {
+        tracing::debug!("Skipping {name} since another instance is running it");
+    }
+    acquired
+}
+
 pub async fn general_media_cleanup_jobs(
     _information: ScheduledJob,
     ctx: JobContext,
 ) -> Result<(), JobError> {
+    if !acquire_cron_lease(&ctx, "general_media_cleanup_jobs", 24).await {
+        return Ok(());
+    }
     tracing::trace!("Invalidating invalid media import jobs");
     ctx.data::<Arc<ImporterService>>()
         .unwrap()
         .invalidate_import_jobs()
         .await
         .unwrap();
+    tracing::trace!("Deleting import reports past their retention");
+    ctx.data::<Arc<ImporterService>>()
+        .unwrap()
+        .delete_expired_import_reports()
+        .await
+        .unwrap();
     tracing::trace!("Cleaning up media items without associated user activities");
     ctx.data::<Arc<MiscellaneousService>>()
         .unwrap()
         .cleanup_metadata_with_associated_user_activities()
         .await
         .unwrap();
+    tracing::trace!("Removing expired entries from the provider lookup cache");
+    ctx.data::<Arc<MiscellaneousService>>()
+        .unwrap()
+        .cleanup_provider_lookup_cache()
+        .await
+        .unwrap();
     Ok(())
 }
 
@@ -50,12 +351,33 @@ pub async fn general_user_cleanup(
     _information: ScheduledJob,
     ctx: JobContext,
 ) -> Result<(), JobError> {
+    let every = ctx
+        .data::<Arc<MiscellaneousService>>()
+        .unwrap()
+        .config
+        .scheduler
+        .user_cleanup_every;
+    if !acquire_cron_lease(&ctx, "general_user_cleanup", every).await {
+        return Ok(());
+    }
+    tracing::trace!("Purging deleted seen items and reviews from the trash");
+    ctx.data::<Arc<MiscellaneousService>>()
+        .unwrap()
+        .purge_trash()
+        .await
+        .unwrap();
     tracing::trace!("Cleaning up user and metadata association");
     ctx.data::<Arc<MiscellaneousService>>()
         .unwrap()
         .cleanup_user_and_metadata_association()
         .await
         .unwrap();
+    tracing::trace!("Marking untouched in progress seen items as stale");
+    ctx.data::<Arc<MiscellaneousService>>()
+        .unwrap()
+        .mark_stale_seen_items()
+        .await
+        .unwrap();
     tracing::trace!("Removing old user summaries and regenerating them");
     ctx.data::<Arc<MiscellaneousService>>()
         .unwrap()
@@ -75,6 +397,15 @@ pub async fn yank_integrations_data(
     _information: ScheduledJob,
     ctx: JobContext,
 ) -> Result<(), JobError> {
+    let every = ctx
+        .data::<Arc<MiscellaneousService>>()
+        .unwrap()
+        .config
+        .integration
+        .pull_every;
+    if !acquire_cron_lease(&ctx, "yank_integrations_data", every).await {
+        return Ok(());
+    }
     tracing::trace!("Getting data from yanked integrations for all users");
     ctx.data::<Arc<MiscellaneousService>>()
         .unwrap()
@@ -84,12 +415,85 @@ pub async fn yank_integrations_data(
     Ok(())
 }
 
+pub async fn send_media_reminders(
+    _information: ScheduledJob,
+    ctx: JobContext,
+) -> Result<(), JobError> {
+    if !acquire_cron_lease(&ctx, "send_media_reminders", 24).await {
+        return Ok(());
+    }
+    tracing::trace!("Sending reminders that are due");
+    ctx.data::<Arc<MiscellaneousService>>()
+        .unwrap()
+        .send_due_media_reminders()
+        .await
+        .unwrap();
+    Ok(())
+}
+
+pub async fn refresh_media_recommendations(
+    _information: ScheduledJob,
+    ctx: JobContext,
+) -> Result<(), JobError> {
+    if !acquire_cron_lease(&ctx, "refresh_media_recommendations", 24).await {
+        return Ok(());
+    }
+    tracing::trace!("Refreshing media recommendations of all users");
+    ctx.data::<Arc<MiscellaneousService>>()
+        .unwrap()
+        .refresh_all_media_recommendations()
+        .await
+        .unwrap();
+    Ok(())
+}
+
+pub async fn refresh_podcasts(_information: ScheduledJob, ctx: JobContext) -> Result<(), JobError> {
+    let every = ctx
+        .data::<Arc<MiscellaneousService>>()
+        .unwrap()
+        .config
+        .podcasts
+        .refresh_every;
+    if !acquire_cron_lease(&ctx, "refresh_podcasts", every).await {
+        return Ok(());
+    }
+    tracing::trace!("Refreshing podcasts that are in a library");
+    ctx.data::<Arc<MiscellaneousService>>()
+        .unwrap()
+        .refresh_library_podcasts()
+        .await
+        .unwrap();
+    Ok(())
+}
+
+pub async fn refresh_instance_statistics(
+    _information: ScheduledJob,
+    ctx: JobContext,
+) -> Result<(), JobError> {
+    let service = ctx.data::<Arc<MiscellaneousService>>().unwrap();
+    // DEV: the statistics are cached in memory, so every instance computes its
+    // own and no lease is taken
+    if !service.config.server.public_statistics || service.jobs.is_paused() {
+        return Ok(());
+    }
+    tracing::trace!("Refreshing the statistics of the instance");
+    service.refresh_instance_statistics().await.unwrap();
+    Ok(())
+}
+
 // Application Jobs
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ImportMedia {
     pub user_id: i32,
     pub input: DeployImportJobInput,
+    /// The report of an interrupted import that should be continued
+    #[serde(default)]
+    pub resume_report_id: Option<i32>,
+    /// The items of the interrupted import, so that its source is not fetched
+    /// again
+    #[serde(default)]
+    pub resume_items: Option<Vec<ImportOrExportItem<ImportOrExportItemIdentifier>>>,
 }
 
 impl Job for ImportMedia {
@@ -97,13 +501,65 @@ impl Job for ImportMedia {
 }
 
 pub async fn import_media(information: ImportMedia, ctx: JobContext) -> Result<(), JobError> {
+    let Some(information) = requeue_while_paused(information, &ctx).await? else {
+        return Ok(());
+    };
     tracing::trace!("Importing media");
-    ctx.data::<Arc<ImporterService>>()
-        .unwrap()
-        .import_from_source(information.user_id, information.input)
-        .await
-        .unwrap();
-    Ok(())
+    let service = ctx.data::<Arc<ImporterService>>().unwrap();
+    let job = service.media_service.jobs.register(
+        &ctx,
+        ImportMedia::NAME,
+        Some(information.user_id),
+        true,
+    );
+    let result = count_provider_requests(service.import_from_source(
+        information.user_id,
+        information.input,
+        information.resume_report_id,
+        information.resume_items,
+        &job,
+    ))
+    .await;
+    complete_job(ImportMedia::NAME, result)
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BulkMediaActionJob {
+    pub user_id: i32,
+    pub report_id: i32,
+    pub action: BulkMediaActionInput,
+    pub filter: BulkMediaFilter,
+}
+
+impl Job for BulkMediaActionJob {
+    const NAME: &'static str = "apalis::BulkMediaActionJob";
+}
+
+pub async fn bulk_media_action_job(
+    information: BulkMediaActionJob,
+    ctx: JobContext,
+) -> Result<(), JobError> {
+    let Some(information) = requeue_while_paused(information, &ctx).await? else {
+        return Ok(());
+    };
+    tracing::trace!("Performing bulk action for user {:?}", information.user_id);
+    let service = ctx.data::<Arc<MiscellaneousService>>().unwrap();
+    let job = service.jobs.register(
+        &ctx,
+        BulkMediaActionJob::NAME,
+        Some(information.user_id),
+        true,
+    );
+    let result = service
+        .perform_bulk_media_action(
+            information.user_id,
+            information.report_id,
+            information.action,
+            information.filter,
+            &job,
+        )
+        .await;
+    complete_job(BulkMediaActionJob::NAME, result)
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -119,12 +575,13 @@ pub async fn user_created_job(
     information: UserCreatedJob,
     ctx: JobContext,
 ) -> Result<(), JobError> {
+    let Some(information) = requeue_while_paused(information, &ctx).await? else {
+        return Ok(());
+    };
     tracing::trace!("Running jobs after user creation");
     let service = ctx.data::<Arc<MiscellaneousService>>().unwrap();
-    service
-        .user_created_job(&information.user_id)
-        .await
-        .unwrap();
+    let result = service.user_created_job(&information.user_id).await;
+    complete_job(UserCreatedJob::NAME, result)?;
     service
         .calculate_user_media_summary(&information.user_id)
         .await
@@ -135,6 +592,9 @@ pub async fn user_created_job(
 #[derive(Debug, Deserialize, Serialize)]
 pub struct RecalculateUserSummaryJob {
     pub user_id: i32,
+    /// The import report to flag if the calculation fails
+    #[serde(default)]
+    pub import_report_id: Option<i32>,
 }
 
 impl Job for RecalculateUserSummaryJob {
@@ -145,12 +605,30 @@ pub async fn recalculate_user_summary_job(
     information: RecalculateUserSummaryJob,
     ctx: JobContext,
 ) -> Result<(), JobError> {
+    let Some(information) = requeue_while_paused(information, &ctx).await? else {
+        return Ok(());
+    };
     tracing::trace!("Calculating summary for user {:?}", information.user_id);
-    ctx.data::<Arc<MiscellaneousService>>()
-        .unwrap()
+    let service = ctx.data::<Arc<MiscellaneousService>>().unwrap();
+    let _job = service.jobs.register(
+        &ctx,
+        RecalculateUserSummaryJob::NAME,
+        Some(information.user_id),
+        false,
+    );
+    let result = service
         .calculate_user_media_summary(&information.user_id)
-        .await
-        .unwrap();
+        .await;
+    if let (Err(e), Some(report_id)) = (&result, information.import_report_id) {
+        service
+            .flag_import_summary_stale(
+                report_id,
+                format!("The summary could not be recalculated: {}", e.message),
+            )
+            .await
+            .ok();
+    }
+    complete_job(RecalculateUserSummaryJob::NAME, result)?;
     tracing::trace!(
         "Summary calculation complete for user {:?}",
         information.user_id
@@ -171,12 +649,41 @@ pub async fn update_metadata_job(
     information: UpdateMetadataJob,
     ctx: JobContext,
 ) -> Result<(), JobError> {
-    ctx.data::<Arc<MiscellaneousService>>()
-        .unwrap()
-        .update_metadata(information.metadata)
-        .await
-        .unwrap();
-    Ok(())
+    let Some(information) = requeue_while_paused(information, &ctx).await? else {
+        return Ok(());
+    };
+    let service = ctx.data::<Arc<MiscellaneousService>>().unwrap();
+    let metadata_id = information.metadata.id;
+    let result = service.update_metadata(information.metadata).await;
+    if let Err(e) = service.release_metadata_refresh(metadata_id).await {
+        tracing::warn!("Could not release the refresh of {metadata_id}: {e:?}");
+    }
+    complete_job(UpdateMetadataJob::NAME, result)
+}
+
+/// Refresh the details of many media in one job, for eg the media of an import
+/// whose details were deferred.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct UpdateMetadataBatchJob {
+    pub metadata_ids: Vec<i32>,
+}
+
+impl Job for UpdateMetadataBatchJob {
+    const NAME: &'static str = "apalis::UpdateMetadataBatchJob";
+}
+
+pub async fn update_metadata_batch_job(
+    information: UpdateMetadataBatchJob,
+    ctx: JobContext,
+) -> Result<(), JobError> {
+    let Some(information) = requeue_while_paused(information, &ctx).await? else {
+        return Ok(());
+    };
+    let service = ctx.data::<Arc<MiscellaneousService>>().unwrap();
+    let result = service
+        .update_metadata_batch(information.metadata_ids)
+        .await;
+    complete_job(UpdateMetadataBatchJob::NAME, result)
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -192,11 +699,57 @@ pub async fn update_exercise_job(
     information: UpdateExerciseJob,
     ctx: JobContext,
 ) -> Result<(), JobError> {
+    let Some(information) = requeue_while_paused(information, &ctx).await? else {
+        return Ok(());
+    };
     tracing::trace!("Updating {:?}", information.exercise.name);
-    ctx.data::<Arc<ExerciseService>>()
+    let result = ctx
+        .data::<Arc<ExerciseService>>()
         .unwrap()
         .update_exercise(information.exercise)
-        .await
-        .unwrap();
-    Ok(())
+        .await;
+    complete_job(UpdateExerciseJob::NAME, result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pause_and_resume() {
+        let registry = JobRegistry::default();
+        assert!(!registry.resume());
+        assert!(registry.pause());
+        assert!(!registry.pause());
+        assert!(registry.is_paused());
+        assert!(registry.worker_status().paused_on.is_some());
+        assert!(registry.resume());
+        assert!(!registry.is_paused());
+    }
+
+    #[test]
+    fn test_cancel_only_cancellable_jobs() {
+        let registry = JobRegistry::default();
+        for (id, cancellable) in [("import", true), ("summary", false)] {
+            registry.jobs.lock().unwrap().insert(
+                id.to_owned(),
+                JobStatus {
+                    id: id.to_owned(),
+                    name: id.to_owned(),
+                    user_id: Some(1),
+                    started_on: Utc::now(),
+                    processed: None,
+                    total: None,
+                    estimate: None,
+                    cancellable,
+                    cancel_requested: false,
+                },
+            );
+        }
+        assert!(registry.cancel("import"));
+        assert!(registry.get("import").unwrap().cancel_requested);
+        assert!(!registry.cancel("summary"));
+        assert!(!registry.get("summary").unwrap().cancel_requested);
+        assert!(!registry.cancel("finished"));
+    }
 }
@@ -1,10 +1,12 @@
-use std::path::PathBuf;
+use std::{collections::HashMap, path::PathBuf};
 
 use anyhow::Result;
 use schematic::{derive_enum, Config, ConfigEnum, ConfigLoader, ValidateError};
 use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
 
 use crate::{
+    migrator::MediaImportSource,
     providers::{audible::AudibleService, itunes::ITunesService, tmdb::TmdbService},
     traits::{IsFeatureEnabled, MediaProviderLanguages},
     utils::PROJECT_NAME,
@@ -128,9 +130,60 @@ pub struct ExerciseConfig {
     pub db: FreeExerciseDbConfig,
 }
 
+fn validate_import_source_cooldown_minutes(
+    value: &HashMap<String, i64>,
+    _partial: &PartialMediaConfig,
+    _context: &(),
+) -> Result<(), ValidateError> {
+    let sources = MediaImportSource::iter()
+        .map(|s| format!("{:?}", s))
+        .collect::<Vec<_>>();
+    if let Some(source) = value.keys().find(|k| !sources.contains(k)) {
+        return Err(ValidateError::new(format!(
+            "{:?} is not an import source, expected one of {:?}",
+            source, sources
+        )));
+    }
+    Ok(())
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Config)]
 #[config(rename_all = "snake_case", env_prefix = "MEDIA_")]
-pub struct MediaConfig {}
+pub struct MediaConfig {
+    /// The number of hours for which details fetched from a provider are
+    /// reused when the same media is committed again. Set to `0` to disable
+    /// the cache.
+    #[setting(default = 168)]
+    pub provider_cache_ttl_hours: i64,
+    /// The number of days for which import reports are kept. They are also the
+    /// source of the import audit log. Set to `0` to keep them forever.
+    #[setting(default = 0)]
+    pub import_report_retention_days: i64,
+    /// The number of minutes after a successful import before the user can
+    /// import from the same source again. Set to `0` to disable the cooldown.
+    #[setting(default = 0)]
+    pub import_cooldown_minutes: i64,
+    /// The cooldown of specific sources, for eg `{ "Trakt": 60 }`, used
+    /// instead of `import_cooldown_minutes`. Can only be set in the config
+    /// file.
+    #[setting(validate = validate_import_source_cooldown_minutes)]
+    pub import_source_cooldown_minutes: HashMap<String, i64>,
+    /// Regular expressions removed from titles before imported items are
+    /// matched by their title, in addition to the defaults of each source. They
+    /// are separated by semicolons when set using an environment variable.
+    #[setting(default = vec![], parse_env = schematic::env::split_semicolon)]
+    pub import_title_strip_patterns: Vec<String>,
+    /// The number of seconds an imported item can take to be fetched from its
+    /// provider before it fails.
+    #[setting(default = 30)]
+    pub import_item_timeout: u64,
+    /// The number of items in a row that can fail to be fetched from the same
+    /// provider, because it could not be reached, responded with a server error
+    /// or timed out, before the remaining items from it are skipped. Set to `0`
+    /// to never skip them.
+    #[setting(default = 5)]
+    pub import_provider_failure_threshold: usize,
+}
 
 fn validate_tmdb_locale(value: &str) -> Result<(), ValidateError> {
     if !TmdbService::supported_languages().contains(&value.to_owned()) {
@@ -212,6 +265,20 @@ pub struct ITunesConfig {
     pub locale: String,
 }
 
+fn validate_podcast_refresh_every(
+    value: &i32,
+    _partial: &PartialPodcastConfig,
+    _context: &(),
+) -> Result<(), ValidateError> {
+    if !(1..=23).contains(value) {
+        return Err(ValidateError::new(format!(
+            "{} is not a valid number of hours, expected a value between 1 and 23",
+            value
+        )));
+    }
+    Ok(())
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Config)]
 pub struct PodcastConfig {
     /// Settings related to Listennotes.
@@ -220,6 +287,10 @@ pub struct PodcastConfig {
     /// Settings related to iTunes.
     #[setting(nested)]
     pub itunes: ITunesConfig,
+    /// Refresh the episodes of podcasts that are in a library every `n` hours.
+    /// Must be between `1` and `23`.
+    #[setting(validate = validate_podcast_refresh_every, default = 6)]
+    pub refresh_every: i32,
 }
 
 impl IsFeatureEnabled for PodcastConfig {}
@@ -331,6 +402,7 @@ pub struct IntegrationConfig {
     #[setting(default = 2)]
     pub minimum_progress_limit: i32,
     /// The maximum progress limit after which a media is considered to be completed.
+    /// Users can change this with their `completion_threshold_percent` preference.
     #[setting(default = 95)]
     pub maximum_progress_limit: i32,
 }
@@ -348,6 +420,48 @@ impl IsFeatureEnabled for FileStorageConfig {
     }
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, Config)]
+#[config(rename_all = "snake_case", env_prefix = "METRICS_")]
+pub struct MetricsConfig {
+    /// Whether the Prometheus metrics will be exposed at `/metrics`.
+    #[setting(default = true)]
+    pub enabled: bool,
+    /// If set, requests to `/metrics` must include this as a bearer token.
+    pub token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Config)]
+#[config(rename_all = "snake_case", env_prefix = "RATE_LIMIT_")]
+pub struct RateLimitConfig {
+    /// The maximum number of media a single collection refresh will queue. The
+    /// ones that were updated the longest time ago are picked first.
+    #[setting(default = 100)]
+    pub collection_refresh_max_items: u64,
+    /// The number of collection refreshes a user can perform every minute. Set
+    /// to `0` to disable the limit.
+    #[setting(default = 1)]
+    pub collection_refresh_per_minute: i32,
+    /// Whether admins are exempt from all rate limits.
+    #[setting(default = true)]
+    pub exempt_admins: bool,
+    /// The number of exports a user can perform every minute. Set to `0` to
+    /// disable the limit.
+    #[setting(default = 2)]
+    pub export_per_minute: i32,
+    /// The number of import jobs a user can deploy every minute. Set to `0` to
+    /// disable the limit.
+    #[setting(default = 2)]
+    pub import_per_minute: i32,
+    /// The number of metadata refresh jobs a user can deploy every minute. Set
+    /// to `0` to disable the limit.
+    #[setting(default = 10)]
+    pub metadata_refresh_per_minute: i32,
+    /// The number of searches a user can perform every minute. Users that are
+    /// not logged in share this limit. Set to `0` to disable the limit.
+    #[setting(default = 30)]
+    pub search_per_minute: i32,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Config)]
 #[config(rename_all = "snake_case", env_prefix = "SCHEDULER_")]
 pub struct SchedulerConfig {
@@ -391,15 +505,45 @@ pub struct ServerConfig {
     /// Whether default credentials will be populated on the login page of the
     /// instance.
     pub default_credentials: bool,
+    /// Whether introspection queries will be rejected. Introspection is always
+    /// allowed when running in development mode.
+    pub disable_introspection: bool,
     /// This will make auth cookies insecure and should be set to `true` if you
     /// are running the server on `localhost`.
     /// [More information](https://github.com/IgnisDa/ryot/issues/23)
     pub insecure_cookie: bool,
+    /// The path to a JSON file containing an array of SHA-256 hashes of the
+    /// GraphQL operations that are allowed to be executed. All operations are
+    /// allowed if this is empty or when running in development mode.
+    pub persisted_queries_path: String,
     /// The hours in which a media can be marked as seen again for a user. This
     /// is used so that the same media can not be used marked as started when
     /// it has been already marked as seen in the last `n` hours.
     #[setting(default = 2)]
     pub progress_update_threshold: i64,
+    /// Whether anonymous statistics about the instance can be queried without
+    /// logging in.
+    pub public_statistics: bool,
+    /// The number of seconds that a background job can wait to be picked up
+    /// by a worker before the instance is marked as not ready. Set to `0` to
+    /// disable this check.
+    #[setting(default = 3600)]
+    pub readiness_max_job_wait: i64,
+    /// Whether the readiness check will also make sure that the providers
+    /// (TMDB) are reachable.
+    pub readiness_probe_provider: bool,
+    /// Whether a failing provider probe will mark the instance as not ready.
+    /// Otherwise it is only reported as a warning.
+    pub readiness_requires_provider: bool,
+    /// The key used to encrypt the secrets that are stored in the database,
+    /// for eg the tokens of integrations and the provider credentials of
+    /// users. Secrets are stored unencrypted and provider credentials can not
+    /// be stored if this is empty.
+    pub secrets_key: String,
+    /// The number of seconds to wait for running background jobs to finish
+    /// their current item when the server is shutting down.
+    #[setting(default = 30)]
+    pub shutdown_grace_period: i64,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Config)]
@@ -432,12 +576,18 @@ pub struct AppConfig {
     /// Settings related to media.
     #[setting(nested)]
     pub media: MediaConfig,
+    /// Settings related to the metrics endpoint.
+    #[setting(nested)]
+    pub metrics: MetricsConfig,
     /// Settings related to movies.
     #[setting(nested)]
     pub movies: MovieConfig,
     /// Settings related to podcasts.
     #[setting(nested)]
     pub podcasts: PodcastConfig,
+    /// Settings related to rate limiting expensive operations.
+    #[setting(nested)]
+    pub rate_limit: RateLimitConfig,
     /// Settings related to scheduler.
     #[setting(nested)]
     pub scheduler: SchedulerConfig,
@@ -468,6 +618,7 @@ impl AppConfig {
         cl.file_storage.s3_secret_access_key = gt();
         cl.file_storage.s3_url = gt();
         cl.integration.hasher_salt = gt();
+        cl.metrics.token = gt();
         cl.movies.tmdb.access_token = gt();
         cl.podcasts.listennotes.api_token = gt();
         cl.shows.tmdb.access_token = gt();
@@ -476,6 +627,7 @@ impl AppConfig {
         cl.video_games.twitch.client_secret = gt();
         cl.server.config_dump_path = gt();
         cl.server.cors_origins = vec![gt()];
+        cl.server.secrets_key = gt();
         cl
     }
 }
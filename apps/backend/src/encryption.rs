@@ -0,0 +1,126 @@
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Key, Nonce,
+};
+use async_graphql::{Error, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use sha2::{Digest, Sha256};
+
+/// The prefix of the values encrypted by a `SecretsCipher`
+const ENCRYPTED_PREFIX: &str = "enc:v1:";
+const NONCE_LENGTH: usize = 12;
+
+/// Encrypts the secrets that are stored in the database with AES-GCM, using a
+/// key derived from the `server.secrets_key` config.
+#[derive(Clone)]
+pub struct SecretsCipher {
+    cipher: Option<Aes256Gcm>,
+}
+
+impl SecretsCipher {
+    pub fn new(key: &str) -> Self {
+        let cipher = match key {
+            "" => None,
+            key => {
+                let key = Sha256::digest(key.as_bytes());
+                Some(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)))
+            }
+        };
+        Self { cipher }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.cipher.is_some()
+    }
+
+    pub fn is_encrypted(value: &str) -> bool {
+        value.starts_with(ENCRYPTED_PREFIX)
+    }
+
+    /// Encrypt a secret if a key is configured, otherwise keep it as it is.
+    pub fn seal(&self, value: &str) -> Result<String> {
+        if !self.is_enabled() || Self::is_encrypted(value) {
+            return Ok(value.to_owned());
+        }
+        self.encrypt(value)
+    }
+
+    /// Decrypt a secret that was passed through `seal`.
+    pub fn open(&self, value: &str) -> Result<String> {
+        if Self::is_encrypted(value) {
+            self.decrypt(value)
+        } else {
+            Ok(value.to_owned())
+        }
+    }
+
+    fn cipher(&self) -> Result<&Aes256Gcm> {
+        self.cipher.as_ref().ok_or_else(|| {
+            Error::new("The `server.secrets_key` config must be set to store secrets")
+        })
+    }
+
+    pub fn encrypt(&self, value: &str) -> Result<String> {
+        let cipher = self.cipher()?;
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let mut data = nonce.to_vec();
+        data.extend(
+            cipher
+                .encrypt(&nonce, value.as_bytes())
+                .map_err(|_| Error::new("Could not encrypt the secret"))?,
+        );
+        Ok(format!("{ENCRYPTED_PREFIX}{}", STANDARD.encode(data)))
+    }
+
+    pub fn decrypt(&self, value: &str) -> Result<String> {
+        let Some(encoded) = value.strip_prefix(ENCRYPTED_PREFIX) else {
+            return Err(Error::new("The secret is not encrypted"));
+        };
+        let cipher = self.cipher()?;
+        let invalid = || Error::new("The secret could not be decrypted with the configured key");
+        let data = STANDARD.decode(encoded).map_err(|_| invalid())?;
+        if data.len() < NONCE_LENGTH {
+            return Err(invalid());
+        }
+        let (nonce, encrypted) = data.split_at(NONCE_LENGTH);
+        let decrypted = cipher
+            .decrypt(Nonce::from_slice(nonce), encrypted)
+            .map_err(|_| invalid())?;
+        String::from_utf8(decrypted).map_err(|_| invalid())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_round_trip() {
+        let cipher = SecretsCipher::new("key");
+        let encrypted = cipher.encrypt("token").unwrap();
+        assert!(encrypted.starts_with(ENCRYPTED_PREFIX));
+        assert_ne!(encrypted, cipher.encrypt("token").unwrap());
+        assert_eq!(cipher.decrypt(&encrypted).unwrap(), "token");
+        assert!(SecretsCipher::new("other").decrypt(&encrypted).is_err());
+    }
+
+    #[test]
+    fn test_encrypt_without_key() {
+        let cipher = SecretsCipher::new("");
+        assert!(!cipher.is_enabled());
+        assert!(cipher.encrypt("token").is_err());
+        assert_eq!(cipher.seal("token").unwrap(), "token");
+        assert_eq!(cipher.open("token").unwrap(), "token");
+    }
+
+    #[test]
+    fn test_seal_and_open() {
+        let cipher = SecretsCipher::new("key");
+        let sealed = cipher.seal("token").unwrap();
+        assert!(SecretsCipher::is_encrypted(&sealed));
+        assert_eq!(cipher.seal(&sealed).unwrap(), sealed);
+        assert_eq!(cipher.open(&sealed).unwrap(), "token");
+        assert_eq!(cipher.open("plain").unwrap(), "plain");
+        assert!(SecretsCipher::new("").open(&sealed).is_err());
+    }
+}
@@ -22,6 +22,8 @@ pub struct Model {
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::import_analytics::Entity")]
+    ImportAnalytics,
     #[sea_orm(
         belongs_to = "super::user::Entity",
         from = "Column::UserId",
@@ -32,6 +34,12 @@ pub enum Relation {
     User,
 }
 
+impl Related<super::import_analytics::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ImportAnalytics.def()
+    }
+}
+
 impl Related<super::user::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::User.def()
@@ -6,7 +6,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     migrator::{MetadataLot, MetadataSource},
-    miscellaneous::{MediaSpecifics, MetadataCreators, MetadataImages},
+    miscellaneous::{MediaSpecifics, MetadataAlternativeTitles, MetadataCreators, MetadataImages},
 };
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize, Default)]
@@ -27,6 +27,12 @@ pub struct Model {
     pub creators: MetadataCreators,
     pub source: MetadataSource,
     pub specifics: MediaSpecifics,
+    /// The number of refreshes from the provider that have failed in a row
+    #[serde(default)]
+    pub refresh_failures: i32,
+    /// Other titles the media is known by, for eg in other languages
+    #[serde(default)]
+    pub alternative_titles: MetadataAlternativeTitles,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -80,4 +86,17 @@ impl Related<super::collection::Entity> for Entity {
     }
 }
 
+impl Related<super::metadata_group::Entity> for Entity {
+    fn to() -> RelationDef {
+        super::metadata_to_metadata_group::Relation::MetadataGroup.def()
+    }
+    fn via() -> Option<RelationDef> {
+        Some(
+            super::metadata_to_metadata_group::Relation::Metadata
+                .def()
+                .rev(),
+        )
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -2,15 +2,25 @@
 
 pub mod prelude;
 
+pub mod bulk_media_action_report;
 pub mod collection;
 pub mod exercise;
 pub mod genre;
+pub mod import_analytics;
+pub mod job_lease;
 pub mod media_import_report;
+pub mod media_reminder;
 pub mod metadata;
+pub mod metadata_group;
+pub mod metadata_title;
 pub mod metadata_to_collection;
 pub mod metadata_to_genre;
+pub mod metadata_to_metadata_group;
+pub mod provider_lookup_cache;
 pub mod review;
 pub mod seen;
 pub mod summary;
 pub mod user;
+pub mod user_provider_credential;
+pub mod user_recommendation;
 pub mod user_to_metadata;
@@ -1,14 +1,24 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.11.3
 
+pub use super::bulk_media_action_report::Entity as BulkMediaActionReport;
 pub use super::collection::Entity as Collection;
 pub use super::exercise::Entity as Exercise;
 pub use super::genre::Entity as Genre;
+pub use super::import_analytics::Entity as ImportAnalytics;
+pub use super::job_lease::Entity as JobLease;
 pub use super::media_import_report::Entity as MediaImportReport;
+pub use super::media_reminder::Entity as MediaReminder;
 pub use super::metadata::Entity as Metadata;
+pub use super::metadata_group::Entity as MetadataGroup;
+pub use super::metadata_title::Entity as MetadataTitle;
 pub use super::metadata_to_collection::Entity as MetadataToCollection;
 pub use super::metadata_to_genre::Entity as MetadataToGenre;
+pub use super::metadata_to_metadata_group::Entity as MetadataToMetadataGroup;
+pub use super::provider_lookup_cache::Entity as ProviderLookupCache;
 pub use super::review::Entity as Review;
 pub use super::seen::Entity as Seen;
 pub use super::summary::Entity as Summary;
 pub use super::user::Entity as User;
+pub use super::user_provider_credential::Entity as UserProviderCredential;
+pub use super::user_recommendation::Entity as UserRecommendation;
 pub use super::user_to_metadata::Entity as UserToMetadata;
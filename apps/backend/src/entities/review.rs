@@ -7,7 +7,9 @@ use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    miscellaneous::SeenOrReviewExtraInformation, models::media::Visibility,
+    migrator::{MediaImportSource, Provenance},
+    miscellaneous::SeenOrReviewExtraInformation,
+    models::media::Visibility,
     utils::associate_user_with_metadata,
 };
 
@@ -28,6 +30,13 @@ pub struct Model {
     pub metadata_id: i32,
     #[graphql(skip)]
     pub extra_information: Option<SeenOrReviewExtraInformation>,
+    pub provenance: Provenance,
+    /// The source of the import that created this review
+    pub import_source: Option<MediaImportSource>,
+    /// The report of the import that created this review
+    pub import_report_id: Option<i32>,
+    /// Set when the review was deleted. It can be restored until it is purged.
+    pub deleted_at: Option<DateTimeUtc>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -1,6 +1,6 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.11.2
 
-use async_graphql::SimpleObject;
+use async_graphql::{ComplexObject, SimpleObject};
 use async_trait::async_trait;
 use chrono::{NaiveDate, Utc};
 use sea_orm::{entity::prelude::*, ActiveValue};
@@ -9,9 +9,10 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     entities::{prelude::UserToMetadata, user_to_metadata},
-    migrator::SeenState,
+    migrator::{MediaImportSource, Provenance, SeenDatePrecision, SeenState},
     miscellaneous::{
         SeenOrReviewExtraInformation, SeenPodcastExtraInformation, SeenShowExtraInformation,
+        ShowProgress,
     },
     utils::associate_user_with_metadata,
 };
@@ -20,7 +21,7 @@ use crate::{
 // - remove from watchlist if it was in there
 // - add to in progress
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize, SimpleObject)]
-#[graphql(name = "Seen")]
+#[graphql(name = "Seen", complex)]
 #[sea_orm(table_name = "seen")]
 pub struct Model {
     #[sea_orm(primary_key)]
@@ -32,6 +33,19 @@ pub struct Model {
     pub user_id: i32,
     pub metadata_id: i32,
     pub state: SeenState,
+    /// The number of times the media was consumed, for sources that only
+    /// report a count without individual dates.
+    pub plays: i32,
+    pub provenance: Provenance,
+    /// The source of the import that created this item
+    pub import_source: Option<MediaImportSource>,
+    /// The report of the import that created this item
+    pub import_report_id: Option<i32>,
+    /// How precisely the dates are known. Dates that are only known to their
+    /// month or year are stored as the first day of it.
+    pub date_precision: SeenDatePrecision,
+    /// Set when the item was deleted. It can be restored until it is purged.
+    pub deleted_at: Option<DateTimeUtc>,
     #[graphql(skip)]
     #[serde(skip)]
     pub extra_information: Option<SeenOrReviewExtraInformation>,
@@ -41,6 +55,19 @@ pub struct Model {
     pub podcast_information: Option<SeenPodcastExtraInformation>,
 }
 
+#[ComplexObject]
+impl Model {
+    /// The date the media was started on, without the parts that are not known.
+    async fn started_on_label(&self) -> Option<String> {
+        self.started_on.map(|d| self.date_precision.format(d))
+    }
+
+    /// The date the media was finished on, without the parts that are not known.
+    async fn finished_on_label(&self) -> Option<String> {
+        self.finished_on.map(|d| self.date_precision.format(d))
+    }
+}
+
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
     #[sea_orm(
@@ -103,6 +130,10 @@ impl ActiveModelBehavior for ActiveModel {
                 user_to_metadata::Column::LastUpdatedOn,
                 Expr::value(Utc::now()),
             )
+            .col_expr(
+                user_to_metadata::Column::ShowProgress,
+                Expr::value(Option::<ShowProgress>::None),
+            )
             .exec(db)
             .await?;
         Ok(model)
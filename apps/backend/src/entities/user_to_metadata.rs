@@ -3,6 +3,8 @@
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 
+use crate::{migrator::MediaState, miscellaneous::ShowProgress};
+
 #[derive(
     Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize, PartialOrd, Ord,
 )]
@@ -13,6 +15,11 @@ pub struct Model {
     #[sea_orm(primary_key, auto_increment = false)]
     pub metadata_id: i32,
     pub last_updated_on: DateTimeUtc,
+    pub monitored: bool,
+    pub ignored: bool,
+    /// Cached progress through a show, cleared whenever it might have changed
+    pub show_progress: Option<ShowProgress>,
+    pub state: Option<MediaState>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -17,10 +17,34 @@ pub enum DoneSetPersonalBest {
     Volume,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, Eq, PartialEq)]
+pub enum DoneSetKind {
+    #[default]
+    Normal,
+    DropSet,
+    WarmUp,
+    Failure,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromJsonQueryResult, Eq, PartialEq)]
 pub struct DoneSetRecord {
     pub statistic: DoneSetStatistic,
     pub personal_bests: Vec<DoneSetPersonalBest>,
+    #[serde(default)]
+    pub kind: DoneSetKind,
+    /// Sets that share the same group (for eg: the sets of a drop set) will
+    /// have the same identifier. Supersets group exercises instead, see
+    /// `DoneWorkout.supersets`.
+    #[serde(default)]
+    pub group_id: Option<u16>,
+}
+
+impl DoneSetRecord {
+    /// Whether this set should be considered when calculating the volume and
+    /// personal bests
+    pub fn counts_towards_statistics(&self) -> bool {
+        self.kind != DoneSetKind::WarmUp
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromJsonQueryResult, Eq, PartialEq)]
@@ -31,6 +55,32 @@ pub struct DoneTotal {
     pub active_duration: u32,
 }
 
+impl DoneTotal {
+    /// Add up the sets that count towards the statistics.
+    pub fn from_sets(sets: &[DoneSetRecord]) -> Self {
+        let mut total = Self {
+            personal_bests: 0,
+            weight: 0,
+            reps: 0,
+            active_duration: 0,
+        };
+        for set in sets.iter().filter(|s| s.counts_towards_statistics()) {
+            total.personal_bests += set.personal_bests.len() as u16;
+            match set.statistic {
+                DoneSetStatistic::Duration(duration)
+                | DoneSetStatistic::DistanceAndDuration(_, duration) => {
+                    total.active_duration += u32::from(duration)
+                }
+                DoneSetStatistic::RepsAndWeight(reps, weight) => {
+                    total.reps += u32::from(reps);
+                    total.weight += u32::from(reps) * u32::from(weight);
+                }
+            }
+        }
+        total
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromJsonQueryResult, Eq, PartialEq)]
 pub struct DoneExercise {
     pub idx: u16,
@@ -80,3 +130,25 @@ impl DoneWorkout {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_total_skips_warm_up_sets() {
+        let set = |kind, reps, weight| DoneSetRecord {
+            statistic: DoneSetStatistic::RepsAndWeight(reps, weight),
+            personal_bests: vec![],
+            kind,
+            group_id: None,
+        };
+        let total = DoneTotal::from_sets(&[
+            set(DoneSetKind::WarmUp, 10, 20),
+            set(DoneSetKind::Normal, 5, 60),
+            set(DoneSetKind::DropSet, 8, 40),
+        ]);
+        assert_eq!(total.reps, 13);
+        assert_eq!(total.weight, 620);
+    }
+}
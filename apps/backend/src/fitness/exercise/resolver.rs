@@ -1,6 +1,9 @@
 use std::{env, ffi::OsStr, path::Path, sync::Arc};
 
-use apalis::{prelude::Storage, sqlite::SqliteStorage};
+use apalis::{
+    prelude::{Job, Storage},
+    sqlite::SqliteStorage,
+};
 use async_graphql::{Context, Error, InputObject, Object, Result};
 use sea_orm::{
     ActiveModelTrait, ActiveValue, ColumnTrait, DatabaseConnection, EntityTrait, PaginatorTrait,
@@ -18,6 +21,7 @@ use crate::{
         fitness::{Exercise as GithubExercise, ExerciseAttributes},
         SearchResults,
     },
+    monitoring::record_job_enqueued,
     utils::{get_case_insensitive_like_query, PAGE_LIMIT},
 };
 
@@ -175,6 +179,7 @@ impl ExerciseService {
         let mut job_ids = vec![];
         for exercise in exercises {
             let job = storage.push(UpdateExerciseJob { exercise }).await?;
+            record_job_enqueued(UpdateExerciseJob::NAME);
             job_ids.push(job.to_string());
         }
         Ok(job_ids.len().try_into().unwrap())
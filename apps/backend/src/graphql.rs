@@ -1,12 +1,30 @@
-use async_graphql::{EmptySubscription, MergedObject, Schema};
+use std::{
+    collections::HashSet,
+    path::PathBuf,
+    sync::{Arc, OnceLock},
+};
+
+use async_graphql::{
+    extensions::{Extension, ExtensionContext, ExtensionFactory, NextPrepareRequest},
+    parser::{
+        parse_query, parse_schema,
+        types::{Selection, TypeKind, TypeSystemDefinition},
+    },
+    EmptySubscription, ErrorExtensionValues, MergedObject, Request, Schema, ServerError,
+    ServerResult,
+};
+use sha2::{Digest, Sha256};
 
 use crate::{
     fitness::exercise::resolver::{ExerciseMutation, ExerciseQuery},
     importer::{ImporterMutation, ImporterQuery},
     miscellaneous::resolver::{MiscellaneousMutation, MiscellaneousQuery},
-    utils::AppServices,
+    utils::{read_file_to_json, AppServices},
 };
 
+/// The error code returned when an operation is not present in the allow-list
+pub const PERSISTED_QUERY_NOT_ALLOWED: &str = "PERSISTED_QUERY_NOT_ALLOWED";
+
 #[derive(MergedObject, Default)]
 pub struct QueryRoot(MiscellaneousQuery, ImporterQuery, ExerciseQuery);
 
@@ -15,14 +33,141 @@ pub struct MutationRoot(MiscellaneousMutation, ImporterMutation, ExerciseMutatio
 
 pub type GraphqlSchema = Schema<QueryRoot, MutationRoot, EmptySubscription>;
 
+/// Rejects all operations whose SHA-256 hash is not present in the allow-list
+struct PersistedQueries {
+    hashes: Arc<HashSet<String>>,
+}
+
+impl ExtensionFactory for PersistedQueries {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(PersistedQueriesExtension {
+            hashes: self.hashes.clone(),
+        })
+    }
+}
+
+struct PersistedQueriesExtension {
+    hashes: Arc<HashSet<String>>,
+}
+
+#[async_trait::async_trait]
+impl Extension for PersistedQueriesExtension {
+    async fn prepare_request(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        request: Request,
+        next: NextPrepareRequest<'_>,
+    ) -> ServerResult<Request> {
+        let hash = format!("{:x}", Sha256::digest(request.query.as_bytes()));
+        if !self.hashes.contains(&hash) {
+            let mut error = ServerError::new("This operation is not allowed", None);
+            let mut extensions = ErrorExtensionValues::default();
+            extensions.set("code", PERSISTED_QUERY_NOT_ALLOWED);
+            error.extensions = Some(extensions);
+            return Err(error);
+        }
+        next.run(ctx, request).await
+    }
+}
+
 pub async fn get_schema(app_services: &AppServices) -> GraphqlSchema {
-    Schema::build(
+    let config = &app_services.media_service.config.server;
+    let mut schema = Schema::build(
         QueryRoot::default(),
         MutationRoot::default(),
         EmptySubscription,
     )
     .data(app_services.media_service.clone())
     .data(app_services.importer_service.clone())
-    .data(app_services.exercise_service.clone())
-    .finish()
+    .data(app_services.exercise_service.clone());
+    if !cfg!(debug_assertions) {
+        if config.disable_introspection {
+            schema = schema.disable_introspection();
+        }
+        if !config.persisted_queries_path.is_empty() {
+            let hashes = read_file_to_json::<HashSet<String>>(&PathBuf::from(
+                &config.persisted_queries_path,
+            ))
+            .expect("Could not read the persisted queries file");
+            tracing::info!("Loaded {} persisted queries", hashes.len());
+            schema = schema.extension(PersistedQueries {
+                hashes: Arc::new(hashes),
+            });
+        }
+    }
+    schema.finish()
+}
+
+/// The names of the fields of the query and mutation roots of a schema.
+fn root_fields(sdl: &str) -> HashSet<String> {
+    let Ok(document) = parse_schema(sdl) else {
+        return HashSet::new();
+    };
+    document
+        .definitions
+        .into_iter()
+        .filter_map(|d| match d {
+            TypeSystemDefinition::Type(t) => Some(t.node),
+            _ => None,
+        })
+        .filter(|t| ["QueryRoot", "MutationRoot"].contains(&t.name.node.as_str()))
+        .flat_map(|t| match t.kind {
+            TypeKind::Object(o) => o.fields,
+            _ => vec![],
+        })
+        .map(|f| f.node.name.node.to_string())
+        .collect()
+}
+
+/// The first root field of the operation that a request runs.
+fn root_field(request: &Request) -> Option<String> {
+    let document = parse_query(&request.query).ok()?;
+    let (_, operation) = document.operations.iter().find(|(name, _)| {
+        request.operation_name.is_none()
+            || name.map(|n| n.as_str()) == request.operation_name.as_deref()
+    })?;
+    operation
+        .node
+        .selection_set
+        .node
+        .items
+        .iter()
+        .find_map(|s| match &s.node {
+            Selection::Field(f) => Some(f.node.name.node.to_string()),
+            _ => None,
+        })
+}
+
+/// The root field that a request runs, to be used as a metrics label. Since the
+/// query comes from the client, any field that is not in the schema (and any
+/// query that can not be parsed) is labelled "other" to keep the number of
+/// labels bounded.
+pub fn operation_label(schema: &GraphqlSchema, request: &Request) -> String {
+    static ROOT_FIELDS: OnceLock<HashSet<String>> = OnceLock::new();
+    let known = ROOT_FIELDS.get_or_init(|| root_fields(&schema.sdl()));
+    root_field(request)
+        .filter(|f| known.contains(f))
+        .unwrap_or_else(|| "other".to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_root_field_of_the_requested_operation() {
+        let known = root_fields(
+            "type QueryRoot { coreDetails: Int }\ntype MutationRoot { commitMedia: Int }",
+        );
+        assert_eq!(
+            known,
+            HashSet::from(["coreDetails".to_owned(), "commitMedia".to_owned()])
+        );
+        let query = "query Details { coreDetails } mutation Commit { commitMedia }";
+        let request = Request::new(query).operation_name("Commit");
+        assert_eq!(root_field(&request).as_deref(), Some("commitMedia"));
+        let request = Request::new("{ ...on QueryRoot { coreDetails } }");
+        assert_eq!(root_field(&request), None);
+        assert_eq!(root_field(&Request::new("not a query")), None);
+    }
 }
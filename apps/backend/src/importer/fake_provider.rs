@@ -0,0 +1,74 @@
+//! A provider for the tests of the importers, which never makes requests.
+
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+
+use crate::{
+    migrator::MetadataLot,
+    models::{
+        media::{MediaDetails, MediaSearchItem},
+        SearchResults,
+    },
+    traits::{IsbnProvider, MediaProvider},
+};
+
+/// ISBNs and queries that are listed resolve to the listed items. Of the rest,
+/// ISBNs starting with `000` and queries containing "Unfindable" are not found,
+/// and everything else resolves to an identifier derived from the query.
+/// Details are never found.
+#[derive(Debug, Default)]
+pub struct FakeProvider {
+    pub isbns: HashMap<&'static str, &'static str>,
+    /// The identifiers and titles found for each query
+    pub searches: HashMap<&'static str, Vec<(&'static str, &'static str)>>,
+}
+
+#[async_trait]
+impl IsbnProvider for FakeProvider {
+    async fn id_from_isbn(&self, isbn: &str) -> Option<String> {
+        match self.isbns.get(isbn) {
+            Some(identifier) => Some(identifier.to_string()),
+            None if isbn.starts_with("000") => None,
+            None => Some(format!("isbn:{isbn}")),
+        }
+    }
+}
+
+#[async_trait]
+impl MediaProvider for FakeProvider {
+    async fn search(
+        &self,
+        query: &str,
+        _page: Option<i32>,
+    ) -> anyhow::Result<SearchResults<MediaSearchItem>> {
+        let found = match self.searches.get(query) {
+            Some(found) => found
+                .iter()
+                .map(|(identifier, title)| (identifier.to_string(), title.to_string()))
+                .collect(),
+            None if query.contains("Unfindable") => vec![],
+            None => vec![(format!("search:{query}"), query.to_owned())],
+        };
+        let items: Vec<_> = found
+            .into_iter()
+            .map(|(identifier, title)| MediaSearchItem {
+                identifier,
+                lot: MetadataLot::Book,
+                title,
+                image: None,
+                publish_year: None,
+                popularity: None,
+            })
+            .collect();
+        Ok(SearchResults {
+            total: items.len() as i32,
+            items,
+            next_page: None,
+        })
+    }
+
+    async fn details(&self, identifier: &str) -> anyhow::Result<MediaDetails> {
+        anyhow::bail!("The details of {identifier} are not fetched in this test")
+    }
+}
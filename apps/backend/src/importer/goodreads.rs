@@ -1,5 +1,6 @@
-use async_graphql::Result;
+use async_graphql::{Error, Result};
 use chrono::{DateTime, Utc};
+use convert_case::{Case, Casing};
 use itertools::Itertools;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
@@ -7,9 +8,11 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     importer::{
-        DeployGoodreadsImportInput, ImportOrExportItem, ImportOrExportItemIdentifier, ImportResult,
+        csv_records, resolve_by_title, DeployGoodreadsImportInput, ImportFailStep,
+        ImportFailedItem, ImportOrExportItem, ImportOrExportItemIdentifier, ImportResult,
+        ImportWarningItem, ResolvedIdentifier, TitleMatching,
     },
-    migrator::{MetadataImageLot, MetadataLot, MetadataSource},
+    migrator::{MediaState, MetadataImageLot, MetadataLot, MetadataSource},
     miscellaneous::{
         DefaultCollection, MediaSpecifics, MetadataCreator, MetadataImage, MetadataImageUrl,
     },
@@ -17,8 +20,17 @@ use crate::{
         BookSpecifics, ImportOrExportItemRating, ImportOrExportItemReview, ImportOrExportItemSeen,
         MediaDetails,
     },
+    traits::{IsbnProvider, MediaProvider},
+    utils::{convert_naive_to_utc, convert_string_to_fuzzy_date},
 };
 
+/// An example of the CSV export generated by Goodreads
+pub const CSV_TEMPLATE: &str = r#"Book Id,Title,Author,ISBN13,My Rating,My Review,Date Read,Bookshelves,Read Count
+234225,Dune,Frank Herbert,"=""9780441172719""",5,A classic.<br/><br/>Would read again.,2023/01/15,"science-fiction, favourites",2
+54493401,Project Hail Mary,Andy Weir,"=""9780593135204""",0,,,currently-reading,0
+5907,The Hobbit,J.R.R. Tolkien,"=""9780547928227""",0,,,to-read,0
+"#;
+
 #[derive(Debug, Serialize, Deserialize)]
 struct RssBookDetails {
     #[serde(default)]
@@ -51,14 +63,45 @@ struct RssDetail {
     channel: RssChannel,
 }
 
-pub async fn import(input: DeployGoodreadsImportInput) -> Result<ImportResult> {
-    let content = surf::get(input.rss_url)
-        .await
-        .unwrap()
-        .body_string()
+#[derive(Debug, Serialize, Deserialize)]
+struct CsvItem {
+    #[serde(rename = "Title")]
+    title: String,
+    #[serde(rename = "Author")]
+    author: Option<String>,
+    #[serde(rename = "ISBN13")]
+    isbn: Option<String>,
+    #[serde(rename = "My Rating")]
+    rating: Option<Decimal>,
+    #[serde(rename = "My Review")]
+    review: Option<String>,
+    #[serde(rename = "Date Read")]
+    date_read: Option<String>,
+    #[serde(rename = "Bookshelves")]
+    bookshelves: Option<String>,
+    #[serde(rename = "Read Count")]
+    read_count: Option<usize>,
+    #[serde(rename = "Original Publication Year", default)]
+    publication_year: Option<i32>,
+}
+
+pub async fn import(
+    input: DeployGoodreadsImportInput,
+    provider: &(impl MediaProvider + IsbnProvider + Sync),
+    matching: &TitleMatching,
+) -> Result<ImportResult> {
+    if let Some(csv_export) = input.csv_export {
+        return import_csv(&csv_export, provider, matching).await;
+    }
+    let rss_url = input
+        .rss_url
+        .ok_or_else(|| Error::new("Either the RSS url or the CSV export is required".to_owned()))?;
+    let content = surf::get(rss_url)
+        .recv_string()
         .await
-        .unwrap();
-    let books: RssDetail = quick_xml::de::from_str(&content).unwrap();
+        .map_err(|e| Error::new(format!("Could not download the RSS feed: {e}")))?;
+    let books: RssDetail = quick_xml::de::from_str(&content)
+        .map_err(|e| Error::new(format!("The RSS feed could not be parsed: {e}")))?;
     let books = books.channel.item.into_iter().collect_vec();
     Ok(ImportResult {
         media: books
@@ -77,6 +120,7 @@ pub async fn import(input: DeployGoodreadsImportInput) -> Result<ImportResult> {
                         date: None,
                         spoiler: Some(false),
                         text: Some(d.user_review),
+                        visibility: None,
                     });
                 };
                 if !d.user_rating.is_empty() {
@@ -100,6 +144,10 @@ pub async fn import(input: DeployGoodreadsImportInput) -> Result<ImportResult> {
                         show_season_number: None,
                         show_episode_number: None,
                         podcast_episode_number: None,
+                        podcast_episode_guid: None,
+                        progress: None,
+                        plays: None,
+                        date_precision: None,
                     });
                 }
 
@@ -110,6 +158,7 @@ pub async fn import(input: DeployGoodreadsImportInput) -> Result<ImportResult> {
 
                 ImportOrExportItem {
                     source_id: d.book_id.to_string(),
+                    title: Some(d.title.clone()),
                     source: MetadataSource::Custom,
                     lot: MetadataLot::Book,
                     identifier: ImportOrExportItemIdentifier::AlreadyFilled(Box::new(
@@ -134,15 +183,265 @@ pub async fn import(input: DeployGoodreadsImportInput) -> Result<ImportResult> {
                             specifics: MediaSpecifics::Book(BookSpecifics {
                                 pages: d.book.num_pages.parse().ok(),
                             }),
+                            groups: vec![],
+                            alternative_titles: vec![],
                         },
                     )),
                     seen_history,
                     collections: default_collections,
                     reviews,
+                    state: shelf_state(&d.user_shelves),
                 }
             })
             .collect(),
         failed_items: vec![],
+        warnings: vec![],
+        pending_matches: vec![],
         collections: vec![],
+        unsupported: 0,
     })
 }
+
+/// The media state that a shelf corresponds to, if any.
+fn shelf_state(shelf: &str) -> Option<MediaState> {
+    match shelf {
+        "to-read" => Some(MediaState::PlanTo),
+        "currently-reading" => Some(MediaState::InProgress),
+        "did-not-finish" => Some(MediaState::Dropped),
+        _ => None,
+    }
+}
+
+/// Goodreads exports the ISBN as a spreadsheet formula (`="9780441172719"`).
+fn clean_isbn(isbn: &str) -> Option<String> {
+    let isbn = isbn.trim_start_matches('=').trim_matches('"').trim();
+    if isbn.is_empty() {
+        None
+    } else {
+        Some(isbn.to_owned())
+    }
+}
+
+/// Reviews in the export contain HTML line breaks.
+fn clean_review(review: &str) -> String {
+    review
+        .replace("<br/>", "\n")
+        .replace("<br />", "\n")
+        .replace("<br>", "\n")
+        .trim()
+        .to_owned()
+}
+
+async fn import_csv(
+    csv_export: &str,
+    provider: &(impl MediaProvider + IsbnProvider + Sync),
+    matching: &TitleMatching,
+) -> Result<ImportResult> {
+    let lot = MetadataLot::Book;
+    let source = MetadataSource::Openlibrary;
+    let mut result = ImportResult::default();
+    let records = csv_records(csv_export);
+    let total = records.len();
+    for (idx, (line, record)) in records.into_iter().enumerate() {
+        let record: CsvItem = match record {
+            Ok(r) => r,
+            Err(e) => {
+                result.failed_items.push(
+                    ImportFailedItem::new(
+                        lot,
+                        ImportFailStep::InputTransformation,
+                        idx.to_string(),
+                        Some(e.to_string()),
+                    )
+                    .at_line(line),
+                );
+                continue;
+            }
+        };
+        tracing::debug!(
+            "Getting details for {title:?} ({idx}/{total})",
+            title = record.title
+        );
+        let isbn = record.isbn.as_deref().and_then(clean_isbn);
+        let isbn_identifier = match isbn.as_ref() {
+            Some(isbn) => provider.id_from_isbn(isbn).await,
+            None => None,
+        };
+        let resolved = match isbn_identifier {
+            Some(i) => Ok(ResolvedIdentifier::Found(i, None)),
+            None => match resolve_by_title(
+                provider,
+                lot,
+                source,
+                &record.title,
+                record.author.as_deref(),
+                record.publication_year,
+                matching,
+            )
+            .await
+            {
+                Ok(Some(resolution)) => match matching.pending_match(
+                    &resolution,
+                    lot,
+                    source,
+                    &record.title,
+                    record.publication_year,
+                    Some(line),
+                ) {
+                    Some(p) => Ok(ResolvedIdentifier::Pending(p)),
+                    None => {
+                        let warning = resolution.warning(lot, &record.title);
+                        Ok(ResolvedIdentifier::Found(
+                            resolution.chosen.identifier,
+                            warning,
+                        ))
+                    }
+                },
+                found => {
+                    let error = match isbn {
+                        Some(isbn) => format!(
+                            "Could not convert ISBN: {} to Openlibrary ID or find it by title",
+                            isbn
+                        ),
+                        None => "No ISBN found and could not find it by title".to_owned(),
+                    };
+                    Err(match found {
+                        Err(e) => format!("{error}: {e}"),
+                        _ => error,
+                    })
+                }
+            },
+        };
+        let shelves = record
+            .bookshelves
+            .map(|s| {
+                s.split(',')
+                    .map(|s| s.trim().to_owned())
+                    .filter(|s| !s.is_empty())
+                    .collect_vec()
+            })
+            .unwrap_or_default();
+        let mut read_count = record.read_count.unwrap_or_default();
+        if read_count == 0 && record.date_read.is_some() {
+            read_count = 1;
+        }
+        // DEV: only the last date is known, so the rest are recorded as plays
+        // instead of creating undated entries
+        let mut seen_history = vec![];
+        if read_count > 0 {
+            seen_history.push(ImportOrExportItemSeen {
+                started_on: None,
+                ended_on: None,
+                show_season_number: None,
+                show_episode_number: None,
+                podcast_episode_number: None,
+                podcast_episode_guid: None,
+                progress: None,
+                plays: Some(read_count as i32),
+                date_precision: None,
+            });
+        }
+        if let Some(w) = record.date_read {
+            // DEV: dates entered by hand can be as vague as "Jan 2015", which
+            // are kept with that precision instead of inventing a day
+            match convert_string_to_fuzzy_date(&w, "%Y/%m/%d") {
+                Some((d, precision)) => {
+                    if let Some(s) = seen_history.first_mut() {
+                        s.ended_on = Some(convert_naive_to_utc(d));
+                        s.date_precision = Some(precision);
+                    }
+                }
+                None => result.warnings.push(ImportWarningItem {
+                    lot,
+                    identifier: record.title.clone(),
+                    warning: format!("Could not parse read date: {:?}", w),
+                    alternatives: vec![],
+                }),
+            }
+        }
+        let state = shelves.iter().find_map(|s| shelf_state(s));
+        let collections = shelves
+            .into_iter()
+            .filter(|s| s != "read" && shelf_state(s) != Some(MediaState::Dropped))
+            .map(|s| match s.as_str() {
+                "to-read" => DefaultCollection::Watchlist.to_string(),
+                "currently-reading" => DefaultCollection::InProgress.to_string(),
+                _ => s.to_case(Case::Title),
+            })
+            .collect_vec();
+        let mut reviews = vec![];
+        let rating = record
+            .rating
+            .filter(|r| *r != dec!(0))
+            // DEV: Rates items out of 5
+            .map(|r| r.saturating_mul(dec!(20)));
+        let review = record
+            .review
+            .map(|r| clean_review(&r))
+            .filter(|r| !r.is_empty())
+            .map(|r| ImportOrExportItemReview {
+                date: None,
+                spoiler: Some(false),
+                text: Some(r),
+                visibility: None,
+            });
+        if rating.is_some() || review.is_some() {
+            reviews.push(ImportOrExportItemRating {
+                rating,
+                review,
+                show_season_number: None,
+                show_episode_number: None,
+                podcast_episode_number: None,
+            });
+        }
+        let mut item = ImportOrExportItem {
+            source_id: record.title.clone(),
+            title: Some(record.title),
+            lot,
+            source,
+            identifier: ImportOrExportItemIdentifier::NeedsDetails(String::new()),
+            seen_history,
+            reviews,
+            collections,
+            state,
+        };
+        if let Some(identifier) =
+            result.resolved_identifier(resolved, &item, record.publication_year, line)
+        {
+            item.identifier = ImportOrExportItemIdentifier::NeedsDetails(identifier);
+            result.media.push(item);
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("=\"9780441172719\"", Some("9780441172719"))]
+    #[case("9780441172719", Some("9780441172719"))]
+    #[case("=\"\"", None)]
+    fn test_clean_isbn(#[case] input: &str, #[case] expected: Option<&str>) {
+        assert_eq!(clean_isbn(input).as_deref(), expected);
+    }
+
+    #[rstest]
+    #[case("to-read", Some(MediaState::PlanTo))]
+    #[case("currently-reading", Some(MediaState::InProgress))]
+    #[case("did-not-finish", Some(MediaState::Dropped))]
+    #[case("favorites", None)]
+    fn test_shelf_state(#[case] shelf: &str, #[case] expected: Option<MediaState>) {
+        assert_eq!(shelf_state(shelf), expected);
+    }
+
+    #[test]
+    fn test_clean_review() {
+        assert_eq!(
+            clean_review("Loved it.<br/><br/>Would read again. "),
+            "Loved it.\n\nWould read again."
+        );
+    }
+}
@@ -0,0 +1,646 @@
+//! Runs the importers against the fixtures in `fixtures/<source>/<case>` and
+//! compares what they produce with the `snapshot.json` of each case. Sources
+//! that are fetched over HTTP are served from the `http` directory of the case,
+//! where `/users/demo/lists?page=1` is read from `http/users/demo/lists@page=1.json`.
+//! GraphQL requests are read by their operation and variables, where a
+//! `GetWatchlist` operation with `{"first": 100}` sent to `/graphql` is read from
+//! `http/graphql@first=100&operation=GetWatchlist.json`.
+//!
+//! Run the tests with `UPDATE_SNAPSHOTS=1` to write the snapshots again after
+//! an intended change.
+
+use std::{
+    future::Future,
+    net::TcpListener,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use apalis::prelude::{JobContext, JobId};
+use aws_sdk_s3::config::Region;
+use axum::{
+    http::{StatusCode, Uri},
+    Router, Server,
+};
+use darkbird::{Options, Storage as MemoryStorage, StorageType};
+use itertools::Itertools;
+use schematic::ConfigLoader;
+use sea_orm::{ConnectionTrait, Database, PaginatorTrait, Statement};
+use sea_orm_migration::MigratorTrait;
+use serde_json::{json, Value};
+use sqlx::SqlitePool;
+
+use super::{fake_provider::FakeProvider, *};
+use crate::{
+    background::import_media,
+    config::AppConfig,
+    entities::user,
+    migrator::UserLot,
+    miscellaneous::resolver::Provider,
+    users::{UserPreferences, UserSinkIntegrations},
+    utils::{create_app_services, AppServices},
+};
+
+const UPDATE_ENV: &str = "UPDATE_SNAPSHOTS";
+
+/// The operation and the variables that are set of a GraphQL request, in the
+/// form of a query string.
+fn graphql_query(body: &str) -> Option<String> {
+    let body: Value = serde_json::from_str(body).ok()?;
+    let mut params = vec![format!("operation={}", body["operationName"].as_str()?)];
+    if let Some(variables) = body["variables"].as_object() {
+        for (name, value) in variables {
+            match value {
+                Value::Null => {}
+                Value::String(v) => params.push(format!("{name}={v}")),
+                v => params.push(format!("{name}={v}")),
+            }
+        }
+    }
+    Some(params.join("&"))
+}
+
+/// Serve the `http` directory of a case and return its base url.
+fn serve_fixture(case: &Path) -> String {
+    let root = case.join("http");
+    let app = Router::new().fallback(move |uri: Uri, body: String| {
+        let root = root.clone();
+        async move {
+            let mut file = uri.path().trim_start_matches('/').to_owned();
+            if let Some(query) = uri
+                .query()
+                .map(String::from)
+                .or_else(|| graphql_query(&body))
+            {
+                file = format!("{file}@{}", query.split('&').sorted().join("&"));
+            }
+            if Path::new(uri.path()).extension().is_none() {
+                file.push_str(".json");
+            }
+            match std::fs::read_to_string(root.join(file)) {
+                Ok(body) => (StatusCode::OK, body),
+                Err(_) => (StatusCode::NOT_FOUND, "Not Found".to_owned()),
+            }
+        }
+    });
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(
+        Server::from_tcp(listener)
+            .unwrap()
+            .serve(app.into_make_service()),
+    );
+    url
+}
+
+fn read(case: &Path, name: &str) -> String {
+    std::fs::read_to_string(case.join(name)).unwrap()
+}
+
+fn read_optional(case: &Path, name: &str) -> Option<String> {
+    std::fs::read_to_string(case.join(name)).ok()
+}
+
+/// The parts of a result that matter to the user. The identifiers of custom
+/// media are random, so they are replaced by their title.
+fn snapshot(result: &ImportResult) -> Value {
+    let media = result
+        .media
+        .iter()
+        .map(|m| {
+            let identifier = match &m.identifier {
+                ImportOrExportItemIdentifier::NeedsDetails(i) => i.clone(),
+                ImportOrExportItemIdentifier::AlreadyFilled(d) => format!("custom:{}", d.title),
+            };
+            let seen = m
+                .seen_history
+                .iter()
+                .map(|s| {
+                    let mut seen = s.ended_on.map_or_else(
+                        || "undated".to_owned(),
+                        |d| s.date_precision.unwrap_or_default().format(d.date_naive()),
+                    );
+                    if let (Some(season), Some(episode)) =
+                        (s.show_season_number, s.show_episode_number)
+                    {
+                        seen.push_str(&format!(" S{season}E{episode}"));
+                    }
+                    if let Some(progress) = s.progress {
+                        seen.push_str(&format!(" {progress}%"));
+                    }
+                    if let Some(plays) = s.plays {
+                        seen.push_str(&format!(" x{plays}"));
+                    }
+                    seen
+                })
+                .collect_vec();
+            let ratings = m
+                .reviews
+                .iter()
+                .map(|r| {
+                    let review = r.review.as_ref().and_then(|r| r.text.clone());
+                    let mut rating = json!({
+                        "rating": r.rating.map(|r| r.normalize().to_string()),
+                        "review": review.filter(|t| !t.is_empty()),
+                    });
+                    // DEV: most sources do not have a visibility, so it is
+                    // only listed when it is known
+                    if let Some(v) = r.review.as_ref().and_then(|r| r.visibility) {
+                        rating["visibility"] = json!(format!("{v:?}"));
+                    }
+                    rating
+                })
+                .collect_vec();
+            json!({
+                "lot": format!("{:?}", m.lot),
+                "source": format!("{:?}", m.source),
+                "identifier": identifier,
+                "seen": seen,
+                "ratings": ratings,
+                "collections": m.collections,
+                "state": m.state.map(|s| format!("{s:?}")),
+            })
+        })
+        .collect_vec();
+    let failed = result
+        .failed_items
+        .iter()
+        .map(|f| {
+            json!({
+                "step": format!("{:?}", f.step),
+                "identifier": f.identifier,
+                "line": f.line,
+            })
+        })
+        .collect_vec();
+    let warnings = result
+        .warnings
+        .iter()
+        .map(|w| json!({ "identifier": w.identifier, "warning": w.warning }))
+        .collect_vec();
+    json!({
+        "collections": result.collections.iter().map(|c| &c.name).collect_vec(),
+        "media": media,
+        "failed": failed,
+        "warnings": warnings,
+        "unsupported": result.unsupported,
+    })
+}
+
+/// Run the importer on every case of the source and compare the results with
+/// their snapshots.
+async fn check_fixtures<F, Fut>(source: &str, import: F)
+where
+    F: Fn(PathBuf) -> Fut,
+    Fut: Future<Output = Result<ImportResult>>,
+{
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("fixtures")
+        .join(source);
+    let cases = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.is_dir())
+        .sorted()
+        .collect_vec();
+    assert!(cases.len() >= 2, "{source} needs at least two fixtures");
+    let update = std::env::var(UPDATE_ENV).is_ok();
+    let mut mismatches = vec![];
+    for case in cases {
+        let actual = match import(case.clone()).await {
+            Ok(result) => snapshot(&result),
+            Err(e) => json!({ "error": e.message }),
+        };
+        let path = case.join("snapshot.json");
+        if update {
+            let content = serde_json::to_string_pretty(&actual).unwrap();
+            std::fs::write(&path, format!("{content}\n")).unwrap();
+            continue;
+        }
+        let expected: Value = read_optional(&case, "snapshot.json")
+            .map(|s| serde_json::from_str(&s).unwrap())
+            .unwrap_or(Value::Null);
+        if actual != expected {
+            mismatches.push(format!(
+                "{}:\n{}",
+                case.display(),
+                serde_json::to_string_pretty(&actual).unwrap()
+            ));
+        }
+    }
+    assert!(
+        mismatches.is_empty(),
+        "The results do not match the snapshots, run with {UPDATE_ENV}=1 to update them.\n{}",
+        mismatches.join("\n")
+    );
+}
+
+#[tokio::test]
+async fn test_media_json_fixtures() {
+    check_fixtures("media_json", |case| async move {
+        media_json::import(DeployMediaJsonImportInput {
+            export: read(&case, "export.json"),
+        })
+        .await
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_movary_fixtures() {
+    check_fixtures("movary", |case| async move {
+        let input = DeployMovaryImportInput {
+            history: read(&case, "history.csv"),
+            ratings: read(&case, "ratings.csv"),
+            watchlist: read_optional(&case, "watchlist.csv"),
+            date_format: None,
+        };
+        movary::import(input, &FakeProvider::default(), &TitleMatching::default()).await
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_story_graph_fixtures() {
+    check_fixtures("story_graph", |case| async move {
+        let input = DeployStoryGraphImportInput {
+            export: read(&case, "export.csv"),
+            date_format: None,
+        };
+        story_graph::import(input, &FakeProvider::default(), &TitleMatching::default()).await
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_goodreads_fixtures() {
+    check_fixtures("goodreads", |case| async move {
+        let input = DeployGoodreadsImportInput {
+            rss_url: Some(format!("{}/review/list_rss/1.xml", serve_fixture(&case))),
+            csv_export: read_optional(&case, "export.csv"),
+        };
+        goodreads::import(input, &FakeProvider::default(), &TitleMatching::default()).await
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_trakt_fixtures() {
+    check_fixtures("trakt", |case| async move {
+        let input = DeployTraktImportInput {
+            username: "demo".to_owned(),
+            list: None,
+        };
+        trakt::import_from(&serve_fixture(&case), input).await
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_trakt_estimate_size() {
+    let case = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/trakt/profile");
+    let input = DeployTraktImportInput {
+        username: "demo".to_owned(),
+        list: None,
+    };
+    let size = trakt::estimate_size_from(&serve_fixture(&case), &input)
+        .await
+        .unwrap();
+    assert_eq!(size, 5);
+}
+
+#[tokio::test]
+async fn test_justwatch_fixtures() {
+    check_fixtures("justwatch", |case| async move {
+        let input = DeployJustWatchImportInput {
+            token: "token".to_owned(),
+            region: read_optional(&case, "region.txt").map(|s| s.trim().to_owned()),
+        };
+        justwatch::import_from(&serve_fixture(&case), input).await
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_justwatch_estimate_size() {
+    let case = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/justwatch/lists");
+    let input = DeployJustWatchImportInput {
+        token: "token".to_owned(),
+        region: None,
+    };
+    let size = justwatch::estimate_size_from(&serve_fixture(&case), &input)
+        .await
+        .unwrap();
+    assert_eq!(size, 8);
+}
+
+#[tokio::test]
+async fn test_media_tracker_fixtures() {
+    check_fixtures("media_tracker", |case| async move {
+        media_tracker::import(DeployMediaTrackerImportInput {
+            api_url: serve_fixture(&case),
+            api_key: "token".to_owned(),
+            page_size: read_optional(&case, "page_size.txt").map(|s| s.trim().parse().unwrap()),
+        })
+        .await
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_media_tracker_estimate_size() {
+    let case = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/media_tracker/basic");
+    let input = DeployMediaTrackerImportInput {
+        api_url: serve_fixture(&case),
+        api_key: "token".to_owned(),
+        page_size: None,
+    };
+    assert_eq!(media_tracker::estimate_size(&input).await.unwrap(), 2);
+}
+
+/// An import that was interrupted by a shutdown is saved with its items, which
+/// it continues with once it is picked up again instead of fetching its source.
+#[tokio::test]
+async fn test_media_tracker_import_resumes_from_the_saved_items() {
+    let case = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/media_tracker/basic");
+    let input = DeployMediaTrackerImportInput {
+        api_url: serve_fixture(&case),
+        api_key: "token".to_owned(),
+        page_size: None,
+    };
+    let fetched = media_tracker::import(input.clone()).await.unwrap();
+    // DEV: the job storage keeps the jobs as JSON
+    let job = serde_json::to_string(&json!({
+        "user_id": 1,
+        "input": { "source": "MediaTracker", "media_tracker": input },
+        "resume_report_id": 1,
+        "resume_items": fetched.media,
+    }))
+    .unwrap();
+    let job: ImportMedia = serde_json::from_str(&job).unwrap();
+    let (resumed, _, _) = resumed_or_fetched(job.resume_items, async {
+        Err(Error::new("The source was fetched again"))
+    })
+    .await
+    .unwrap();
+    assert_eq!(snapshot(&resumed)["media"], snapshot(&fetched)["media"]);
+}
+
+/// The services of an instance whose jobs are queued in `pool`. The provider
+/// configurations that are otherwise fetched when the services are created are
+/// written beforehand, so that no requests are made.
+async fn instance_services(
+    db: &DatabaseConnection,
+    pool: &SqlitePool,
+    shutdown: Arc<ShutdownSignal>,
+) -> (AppServices, SqliteStorage<ImportMedia>) {
+    let cached = [
+        (
+            "tmdb-config.json",
+            r#"{"images":{"secure_base_url":"https://image.tmdb.org/t/p/"}}"#,
+        ),
+        ("listennotes.json", "{}"),
+    ];
+    for (name, contents) in cached {
+        let path = std::env::temp_dir().join(name);
+        if !path.exists() {
+            std::fs::write(path, contents).unwrap();
+        }
+    }
+    async fn storage<T: Job>(pool: &SqlitePool) -> SqliteStorage<T> {
+        let storage = SqliteStorage::new(pool.clone());
+        storage.setup().await.unwrap();
+        storage
+    }
+    let config = ConfigLoader::<AppConfig>::new().load().unwrap().config;
+    let auth_dir = std::env::temp_dir().display().to_string();
+    let auth_db = Arc::new(
+        MemoryStorage::open(Options::new(
+            &auth_dir,
+            &format!("ryot-auth-{}", uuid::Uuid::new_v4()),
+            1000,
+            StorageType::RamCopies,
+            true,
+        ))
+        .await
+        .unwrap(),
+    );
+    let s3_client = aws_sdk_s3::Client::from_conf(
+        aws_sdk_s3::Config::builder()
+            .region(Region::new("us-east-1"))
+            .build(),
+    );
+    let import_media_storage = storage(pool).await;
+    let services = create_app_services(
+        db.clone(),
+        auth_db,
+        s3_client,
+        Arc::new(config),
+        &import_media_storage,
+        &storage(pool).await,
+        &storage(pool).await,
+        &storage(pool).await,
+        &storage(pool).await,
+        &storage(pool).await,
+        &storage(pool).await,
+        shutdown,
+    )
+    .await;
+    (services, import_media_storage)
+}
+
+/// The context that a worker runs an import job with.
+fn import_job_context(services: &AppServices, storage: &SqliteStorage<ImportMedia>) -> JobContext {
+    let mut ctx = JobContext::new(JobId::new());
+    ctx.insert(services.importer_service.clone());
+    ctx.insert(services.media_service.clone());
+    ctx.insert(storage.clone());
+    ctx
+}
+
+/// The import jobs waiting in the queue, along with when they are due.
+async fn queued_imports(pool: &SqlitePool) -> Vec<(ImportMedia, i64)> {
+    let jobs: Vec<(String, i64)> = sqlx::query_as(
+        "SELECT job, run_at FROM Jobs WHERE job_type = ? AND status = 'Pending' ORDER BY run_at",
+    )
+    .bind(ImportMedia::NAME)
+    .fetch_all(pool)
+    .await
+    .unwrap();
+    jobs.into_iter()
+        .map(|(job, run_at)| (serde_json::from_str(&job).unwrap(), run_at))
+        .collect()
+}
+
+/// An import that is interrupted by a shutdown saves where it stopped and puts
+/// itself back in the queue, where the workers leave it until the instance
+/// starts again. It then continues with the items it was not done with.
+#[tokio::test]
+async fn test_import_interrupted_by_shutdown_resumes_after_restart() {
+    let dir = std::env::temp_dir();
+    let id = uuid::Uuid::new_v4();
+    let db_path = dir.join(format!("ryot-shutdown-{id}.db"));
+    let db = Database::connect(format!("sqlite://{}?mode=rwc", db_path.display()))
+        .await
+        .unwrap();
+    crate::migrator::Migrator::up(&db, None).await.unwrap();
+    let jobs_path = dir.join(format!("ryot-shutdown-jobs-{id}.db"));
+    let pool = SqlitePool::connect(&format!("sqlite://{}?mode=rwc", jobs_path.display()))
+        .await
+        .unwrap();
+    let user = user::ActiveModel {
+        name: ActiveValue::Set("diana".to_owned()),
+        password: ActiveValue::Set("password".to_owned()),
+        lot: ActiveValue::Set(UserLot::Normal),
+        preferences: ActiveValue::Set(UserPreferences::default()),
+        sink_integrations: ActiveValue::Set(UserSinkIntegrations(vec![])),
+        ..Default::default()
+    }
+    .insert(&db)
+    .await
+    .unwrap();
+    let case = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/media_tracker/basic");
+    let source = DeployMediaTrackerImportInput {
+        api_url: serve_fixture(&case),
+        api_key: "token".to_owned(),
+        page_size: None,
+    };
+    // DEV: shows need their details before their history can be imported, so
+    // they are left out to keep the providers from being requested
+    let lots = [
+        MetadataLot::Movie,
+        MetadataLot::Book,
+        MetadataLot::VideoGame,
+    ];
+    let expected_seen: usize = media_tracker::import(source.clone())
+        .await
+        .unwrap()
+        .media
+        .iter()
+        .filter(|m| lots.contains(&m.lot))
+        .map(|m| m.seen_history.len())
+        .sum();
+    let job: ImportMedia = serde_json::from_value(json!({
+        "user_id": user.id,
+        "input": {
+            "source": "MediaTracker",
+            "media_tracker": source,
+            "metadata_enrichment": "Skip",
+            "lots": lots,
+        },
+    }))
+    .unwrap();
+
+    let shutdown = Arc::new(ShutdownSignal::default());
+    let (services, storage) = instance_services(&db, &pool, shutdown.clone()).await;
+    let jobs = services.media_service.jobs.clone();
+    let interrupted = async {
+        // DEV: the shutdown is requested while the second item is imported
+        while !jobs.statuses().iter().any(|j| j.processed >= Some(1)) {
+            tokio::task::yield_now().await;
+        }
+        shutdown.request();
+    };
+    let (result, _) = tokio::time::timeout(Duration::from_secs(30), async {
+        tokio::join!(
+            import_media(job, import_job_context(&services, &storage)),
+            interrupted
+        )
+    })
+    .await
+    .unwrap();
+    result.unwrap();
+    let report = MediaImportReport::find().one(&db).await.unwrap().unwrap();
+    let details = report.details.clone().unwrap();
+    let cursor = details.resume_cursor.unwrap();
+    assert!(cursor > 0 && cursor < lots.len());
+    assert_eq!(details.succeeded_items.len(), cursor);
+    assert_eq!(report.finished_on, None);
+    let mut queued = queued_imports(&pool).await;
+    assert_eq!(queued.len(), 1);
+    let (resumed, _) = queued.remove(0);
+    assert_eq!(resumed.resume_report_id, Some(report.id));
+    assert_eq!(
+        resumed.resume_items.as_ref().map(Vec::len),
+        Some(lots.len())
+    );
+
+    // DEV: a worker that picks the job up before the instance stops puts it
+    // back for later instead of running it
+    let repeated: ImportMedia =
+        serde_json::from_value(serde_json::to_value(&resumed).unwrap()).unwrap();
+    import_media(repeated, import_job_context(&services, &storage))
+        .await
+        .unwrap();
+    let report = MediaImportReport::find().one(&db).await.unwrap().unwrap();
+    assert_eq!(report.details.unwrap().resume_cursor, Some(cursor));
+    let queued = queued_imports(&pool).await;
+    assert!(queued.last().unwrap().1 > Utc::now().timestamp());
+
+    let (services, storage) =
+        instance_services(&db, &pool, Arc::new(ShutdownSignal::default())).await;
+    import_media(resumed, import_job_context(&services, &storage))
+        .await
+        .unwrap();
+    let reports = MediaImportReport::find().all(&db).await.unwrap();
+    assert_eq!(reports.len(), 1);
+    let details = reports[0].details.clone().unwrap();
+    assert!(reports[0].finished_on.is_some());
+    assert_eq!(details.resume_cursor, None);
+    assert_eq!(details.succeeded_items.len(), lots.len());
+    assert_eq!(
+        Metadata::find().count(&db).await.unwrap(),
+        lots.len() as u64
+    );
+    assert_eq!(Seen::find().count(&db).await.unwrap(), expected_seen as u64);
+    db.close().await.unwrap();
+    pool.close().await;
+    std::fs::remove_file(db_path).ok();
+    std::fs::remove_file(jobs_path).ok();
+}
+
+#[tokio::test]
+async fn test_xlsx_fixtures() {
+    check_fixtures("xlsx", |case| async move {
+        xlsx::import_workbook(
+            std::fs::read(case.join("sheet.xlsx")).unwrap(),
+            read_optional(&case, "sheet_name.txt").map(|s| s.trim().to_owned()),
+            None,
+            None,
+            |_, _| async { Ok(Box::new(FakeProvider::default()) as Provider) },
+            &TitleMatching::default(),
+        )
+        .await
+    })
+    .await;
+}
+
+/// The cases are SQL scripts that are run on a freshly migrated database, whose
+/// user named "demo" is then imported.
+#[tokio::test]
+async fn test_ryot_sqlite_fixtures() {
+    check_fixtures("ryot_sqlite", |case| async move {
+        let path = std::env::temp_dir().join(format!("ryot-fixture-{}.db", uuid::Uuid::new_v4()));
+        let db = Database::connect(format!("sqlite://{}?mode=rwc", path.display()))
+            .await
+            .unwrap();
+        crate::migrator::Migrator::up(&db, None).await.unwrap();
+        let seed = read(&case, "seed.sql")
+            .lines()
+            .filter(|l| !l.starts_with("--"))
+            .join("\n");
+        for sql in seed.split(';').map(|s| s.trim()).filter(|s| !s.is_empty()) {
+            db.execute(Statement::from_string(
+                db.get_database_backend(),
+                sql.to_owned(),
+            ))
+            .await
+            .unwrap();
+        }
+        db.close().await.unwrap();
+        let result = ryot_sqlite::import_backup(&path, "demo").await;
+        std::fs::remove_file(&path).ok();
+        result
+    })
+    .await;
+}
@@ -0,0 +1,368 @@
+use std::collections::HashMap;
+
+use async_graphql::{Error, Result};
+use sea_orm::prelude::DateTimeUtc;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use surf::{http::headers::AUTHORIZATION, Client};
+
+use crate::{
+    importer::{
+        DeployJustWatchImportInput, ImportFailStep, ImportFailedItem, ImportOrExportItem,
+        ImportOrExportItemIdentifier, ImportResult,
+    },
+    migrator::{MediaState, MetadataLot, MetadataSource},
+    miscellaneous::DefaultCollection,
+    models::media::ImportOrExportItemSeen,
+    utils::get_base_http_client,
+};
+
+const API_URL: &str = "https://apis.justwatch.com";
+/// The catalog that is used if the input does not have a region
+const DEFAULT_REGION: &str = "US";
+const LANGUAGE: &str = "en";
+const PAGE_SIZE: usize = 100;
+
+const TITLES_FRAGMENT: &str = "
+    totalCount
+    pageInfo { hasNextPage endCursor }
+    edges {
+        createdAt
+        node {
+            id
+            objectType
+            content(country: $country, language: $language) {
+                title
+                externalIds { tmdbId }
+            }
+        }
+    }";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TitleList {
+    Seenlist,
+    Watchlist,
+}
+
+impl TitleList {
+    fn operation_name(&self) -> &'static str {
+        match self {
+            Self::Seenlist => "GetSeenlist",
+            Self::Watchlist => "GetWatchlist",
+        }
+    }
+
+    fn query(&self) -> String {
+        let field = match self {
+            Self::Seenlist => "seenlistTitles",
+            Self::Watchlist => "watchlistTitles",
+        };
+        format!(
+            "query {}($country: Country!, $language: Language!, $first: Int!, $after: String) {{
+                viewer {{
+                    titles: {}(country: $country, first: $first, after: $after) {{ {} }}
+                }}
+            }}",
+            self.operation_name(),
+            field,
+            TITLES_FRAGMENT
+        )
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExternalIds {
+    tmdb_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Content {
+    title: String,
+    external_ids: ExternalIds,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+enum ObjectType {
+    Movie,
+    Show,
+    #[serde(other)]
+    Unsupported,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Node {
+    id: String,
+    object_type: ObjectType,
+    /// Not set if the title is not a part of the catalog of the region
+    content: Option<Content>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Edge {
+    created_at: Option<DateTimeUtc>,
+    node: Node,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PageInfo {
+    has_next_page: bool,
+    end_cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Titles {
+    total_count: usize,
+    page_info: PageInfo,
+    edges: Vec<Edge>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Viewer {
+    titles: Titles,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ResponseData {
+    viewer: Option<Viewer>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ResponseError {
+    message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Response {
+    data: Option<ResponseData>,
+    #[serde(default)]
+    errors: Vec<ResponseError>,
+}
+
+fn get_client(api_url: &str, token: &str) -> Client {
+    get_base_http_client(
+        &format!("{}/", api_url),
+        vec![(AUTHORIZATION, format!("Bearer {}", token))],
+    )
+}
+
+/// The region of the catalog, for eg "US", checked to be a country code.
+fn region(input: &DeployJustWatchImportInput) -> Result<String> {
+    let region = input
+        .region
+        .as_deref()
+        .unwrap_or(DEFAULT_REGION)
+        .trim()
+        .to_uppercase();
+    if region.len() != 2 || !region.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(Error::new(format!(
+            "{region:?} is not a two letter country code"
+        )));
+    }
+    Ok(region)
+}
+
+async fn fetch_page(
+    client: &Client,
+    list: TitleList,
+    region: &str,
+    first: usize,
+    after: Option<&str>,
+) -> Result<Titles> {
+    let body = json!({
+        "operationName": list.operation_name(),
+        "query": list.query(),
+        "variables": {
+            "country": region,
+            "language": LANGUAGE,
+            "first": first,
+            "after": after,
+        },
+    });
+    let mut rsp = client
+        .post("graphql")
+        .body_json(&body)?
+        .await
+        .map_err(|e| Error::new(e.to_string()))?;
+    let data: Response = rsp
+        .body_json()
+        .await
+        .map_err(|e| Error::new(format!("Could not get the JustWatch {list:?}: {e}")))?;
+    if let Some(e) = data.errors.first() {
+        return Err(Error::new(format!(
+            "JustWatch responded with: {}",
+            e.message
+        )));
+    }
+    data.data
+        .and_then(|d| d.viewer)
+        .map(|v| v.titles)
+        .ok_or_else(|| Error::new("The JustWatch token was not accepted"))
+}
+
+pub async fn import(input: DeployJustWatchImportInput) -> Result<ImportResult> {
+    import_from(API_URL, input).await
+}
+
+pub async fn estimate_size(input: &DeployJustWatchImportInput) -> Result<usize> {
+    estimate_size_from(API_URL, input).await
+}
+
+/// The number of titles on the seenlist and the watchlist.
+pub async fn estimate_size_from(
+    api_url: &str,
+    input: &DeployJustWatchImportInput,
+) -> Result<usize> {
+    let client = get_client(api_url, &input.token);
+    let region = region(input)?;
+    let mut total = 0;
+    for list in [TitleList::Seenlist, TitleList::Watchlist] {
+        total += fetch_page(&client, list, &region, 1, None)
+            .await?
+            .total_count;
+    }
+    Ok(total)
+}
+
+/// Import the seenlist and watchlist of a JustWatch account from the catalog
+/// of the region.
+pub async fn import_from(api_url: &str, input: DeployJustWatchImportInput) -> Result<ImportResult> {
+    let client = get_client(api_url, &input.token);
+    let region = region(&input)?;
+    let mut media: Vec<ImportOrExportItem<ImportOrExportItemIdentifier>> = vec![];
+    let mut positions = HashMap::new();
+    let mut failed_items = vec![];
+    let mut unsupported = 0;
+    for list in [TitleList::Seenlist, TitleList::Watchlist] {
+        let mut after = None;
+        loop {
+            let page = fetch_page(&client, list, &region, PAGE_SIZE, after.as_deref()).await?;
+            tracing::trace!(
+                "Got {count} of {total} titles of the {list:?}",
+                count = page.edges.len(),
+                total = page.total_count
+            );
+            for edge in page.edges {
+                if edge.node.object_type == ObjectType::Unsupported {
+                    unsupported += 1;
+                    continue;
+                }
+                match process_edge(edge, list, &region) {
+                    Ok(item) => match positions.get(&(item.lot, item.source_id.clone())) {
+                        Some(&idx) => merge_item(&mut media[idx], item),
+                        None => {
+                            positions.insert((item.lot, item.source_id.clone()), media.len());
+                            media.push(item);
+                        }
+                    },
+                    Err(f) => failed_items.push(f),
+                }
+            }
+            match page.page_info.end_cursor {
+                Some(cursor) if page.page_info.has_next_page => after = Some(cursor),
+                _ => break,
+            }
+        }
+    }
+    Ok(ImportResult {
+        media,
+        failed_items,
+        unsupported,
+        ..Default::default()
+    })
+}
+
+fn merge_item(
+    existing: &mut ImportOrExportItem<ImportOrExportItemIdentifier>,
+    item: ImportOrExportItem<ImportOrExportItemIdentifier>,
+) {
+    existing.seen_history.extend(item.seen_history);
+    existing.collections.extend(item.collections);
+    existing.state = existing.state.or(item.state);
+}
+
+fn process_edge(
+    edge: Edge,
+    list: TitleList,
+    region: &str,
+) -> std::result::Result<ImportOrExportItem<ImportOrExportItemIdentifier>, ImportFailedItem> {
+    let lot = match edge.node.object_type {
+        ObjectType::Show => MetadataLot::Show,
+        _ => MetadataLot::Movie,
+    };
+    let failed = |error: String| {
+        ImportFailedItem::new(
+            lot,
+            ImportFailStep::ItemDetailsFromSource,
+            edge.node.id.clone(),
+            Some(error),
+        )
+    };
+    let Some(content) = edge.node.content.as_ref() else {
+        return Err(failed(format!(
+            "The title is not a part of the {region} catalog"
+        )));
+    };
+    let Some(tmdb_id) = content.external_ids.tmdb_id.clone() else {
+        return Err(failed(format!(
+            "{:?} does not have an associated TMDB id",
+            content.title
+        )));
+    };
+    let mut item = ImportOrExportItem {
+        source_id: tmdb_id.clone(),
+        title: Some(content.title.clone()),
+        lot,
+        source: MetadataSource::Tmdb,
+        identifier: ImportOrExportItemIdentifier::NeedsDetails(tmdb_id),
+        seen_history: vec![],
+        reviews: vec![],
+        collections: vec![],
+        state: None,
+    };
+    match (list, lot) {
+        (TitleList::Watchlist, _) => item
+            .collections
+            .push(DefaultCollection::Watchlist.to_string()),
+        // DEV: JustWatch does not say which episodes of a show were seen, so
+        // the whole show is marked as completed instead
+        (TitleList::Seenlist, MetadataLot::Show) => item.state = Some(MediaState::Completed),
+        (TitleList::Seenlist, _) => item.seen_history.push(ImportOrExportItemSeen {
+            started_on: None,
+            ended_on: edge.created_at,
+            show_season_number: None,
+            show_episode_number: None,
+            podcast_episode_number: None,
+            podcast_episode_guid: None,
+            progress: None,
+            plays: None,
+            date_precision: None,
+        }),
+    }
+    Ok(item)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(None, Some("US"))]
+    #[case(Some(" de "), Some("DE"))]
+    #[case(Some("Germany"), None)]
+    #[case(Some("1A"), None)]
+    fn test_region(#[case] region: Option<&str>, #[case] expected: Option<&str>) {
+        let input = DeployJustWatchImportInput {
+            token: "token".to_owned(),
+            region: region.map(String::from),
+        };
+        assert_eq!(super::region(&input).ok().as_deref(), expected);
+    }
+}
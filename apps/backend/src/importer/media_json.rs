@@ -1,12 +1,221 @@
-use async_graphql::Result;
+use async_graphql::{Error, Result};
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
 
-use crate::importer::{DeployMediaJsonImportInput, ImportResult};
+use crate::{
+    importer::{DeployMediaJsonImportInput, ImportFailStep, ImportFailedItem, ImportResult},
+    migrator::{MediaState, MetadataLot, MetadataSource},
+    models::media::{
+        ImportOrExportItem, ImportOrExportItemIdentifier, ImportOrExportItemRating,
+        ImportOrExportItemSeen,
+    },
+};
+
+/// The largest export that can be imported, in bytes
+pub const MAX_EXPORT_SIZE: usize = 50 * 1024 * 1024;
+
+/// An example of a media JSON export containing a single item
+pub const EXPORT_TEMPLATE: &str = r#"[
+  {
+    "source_id": "Inception",
+    "lot": "Movie",
+    "source": "Tmdb",
+    "identifier": "27205",
+    "seen_history": [
+      {
+        "started_on": null,
+        "ended_on": "2023-01-15T00:00:00Z",
+        "show_season_number": null,
+        "show_episode_number": null,
+        "podcast_episode_number": null
+      }
+    ],
+    "reviews": [
+      {
+        "review": {
+          "date": "2023-01-15T00:00:00Z",
+          "spoiler": false,
+          "text": "Watched with friends",
+          "visibility": "Private"
+        },
+        "rating": "90",
+        "show_season_number": null,
+        "show_episode_number": null,
+        "podcast_episode_number": null
+      }
+    ],
+    "collections": ["Watchlist"]
+  }
+]
+"#;
+
+fn check_size(export: &str) -> Result<()> {
+    if export.len() > MAX_EXPORT_SIZE {
+        return Err(Error::new(format!(
+            "The export is {size} bytes, which is more than the maximum of {MAX_EXPORT_SIZE} bytes",
+            size = export.len()
+        )));
+    }
+    Ok(())
+}
+
+/// Reject exports that are too large before an import job is deployed for them.
+pub fn check_export_size(input: &DeployMediaJsonImportInput) -> Result<()> {
+    check_size(&input.export)
+}
+
+fn is_invalid<T: DeserializeOwned>(value: &Value) -> bool {
+    serde_json::from_value::<T>(value.clone()).is_err()
+}
+
+/// Find the field of an object that could not be deserialized. All the fields
+/// of `T` must be optional so that each of them can be checked on its own.
+fn invalid_field_pointer<T: DeserializeOwned>(value: &Value, pointer: String) -> String {
+    value
+        .as_object()
+        .and_then(|o| {
+            o.iter()
+                .find(|(k, v)| {
+                    is_invalid::<T>(&Value::Object(Map::from_iter([(
+                        k.to_string(),
+                        (*v).clone(),
+                    )])))
+                })
+                .map(|(k, _)| format!("{pointer}/{k}"))
+        })
+        .unwrap_or(pointer)
+}
+
+/// Find the element of an array that could not be deserialized.
+fn invalid_element_pointer<T: DeserializeOwned>(value: &Value, pointer: String) -> String {
+    value
+        .as_array()
+        .and_then(|a| {
+            a.iter()
+                .enumerate()
+                .find(|(_, e)| is_invalid::<T>(e))
+                .map(|(idx, e)| invalid_field_pointer::<T>(e, format!("{pointer}/{idx}")))
+        })
+        .unwrap_or(pointer)
+}
+
+/// Get the JSON pointer to the part of an item that could not be deserialized.
+fn invalid_item_pointer(item: &Value, pointer: String) -> String {
+    let Some(object) = item.as_object() else {
+        return pointer;
+    };
+    for field in ["source_id", "lot", "source", "identifier"] {
+        if !object.contains_key(field) {
+            return format!("{pointer}/{field}");
+        }
+    }
+    let field = |k: &str| format!("{pointer}/{k}");
+    let value = |k: &str| object.get(k).cloned().unwrap_or(Value::Null);
+    if is_invalid::<String>(&value("source_id")) {
+        field("source_id")
+    } else if is_invalid::<MetadataLot>(&value("lot")) {
+        field("lot")
+    } else if is_invalid::<MetadataSource>(&value("source")) {
+        field("source")
+    } else if is_invalid::<ImportOrExportItemIdentifier>(&value("identifier")) {
+        field("identifier")
+    } else if is_invalid::<Vec<ImportOrExportItemSeen>>(&value("seen_history")) {
+        invalid_element_pointer::<ImportOrExportItemSeen>(
+            &value("seen_history"),
+            field("seen_history"),
+        )
+    } else if is_invalid::<Vec<ImportOrExportItemRating>>(&value("reviews")) {
+        invalid_element_pointer::<ImportOrExportItemRating>(&value("reviews"), field("reviews"))
+    } else if is_invalid::<Vec<String>>(&value("collections")) {
+        field("collections")
+    } else if is_invalid::<Option<MediaState>>(&value("state")) {
+        field("state")
+    } else {
+        pointer
+    }
+}
+
+/// Deserialize the export one item at a time so that a malformed item does not
+/// prevent the rest of them from being imported.
+fn parse_export(
+    export: &str,
+) -> Result<(
+    Vec<ImportOrExportItem<ImportOrExportItemIdentifier>>,
+    Vec<ImportFailedItem>,
+)> {
+    check_size(export)?;
+    let items: Vec<Value> = serde_json::from_str(export)
+        .map_err(|e| Error::new(format!("The export is not a JSON array: {e}")))?;
+    let mut media = vec![];
+    let mut failed_items = vec![];
+    for (idx, item) in items.into_iter().enumerate() {
+        match serde_json::from_value(item.clone()) {
+            Ok(m) => media.push(m),
+            Err(e) => failed_items.push(ImportFailedItem::new(
+                // DEV: the lot might be the reason the item is invalid
+                serde_json::from_value(item["lot"].clone()).unwrap_or_default(),
+                ImportFailStep::InputTransformation,
+                item["source_id"]
+                    .as_str()
+                    .map_or_else(|| idx.to_string(), |s| s.to_owned()),
+                Some(format!(
+                    "{pointer}: {e}",
+                    pointer = invalid_item_pointer(&item, format!("/{idx}"))
+                )),
+            )),
+        }
+    }
+    Ok((media, failed_items))
+}
 
 pub async fn import(input: DeployMediaJsonImportInput) -> Result<ImportResult> {
-    let media = serde_json::from_str(&input.export).unwrap();
+    let (media, failed_items) = parse_export(&input.export)?;
     Ok(ImportResult {
         collections: vec![],
         media,
-        failed_items: vec![],
+        failed_items,
+        warnings: vec![],
+        pending_matches: vec![],
+        unsupported: 0,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIXED_EXPORT: &str = r#"[
+      { "source_id": "Inception", "lot": "Movie", "source": "Tmdb", "identifier": "27205",
+        "seen_history": [], "reviews": [], "collections": [] },
+      { "source_id": "Blue Eye Samurai", "lot": "Cartoon", "source": "Tmdb", "identifier": "1",
+        "seen_history": [], "reviews": [], "collections": [] },
+      { "source_id": "Dune", "lot": "Book", "source": "Openlibrary", "identifier": "OL1M",
+        "seen_history": [{ "ended_on": "yesterday" }], "reviews": [], "collections": [] },
+      { "source_id": "Arcane", "lot": "Show", "source": "Tmdb",
+        "seen_history": [], "reviews": [], "collections": [] }
+    ]"#;
+
+    #[test]
+    fn test_parse_export_reports_invalid_items() {
+        let (media, failed_items) = parse_export(MIXED_EXPORT).unwrap();
+        assert_eq!(media.len(), 1);
+        assert_eq!(media[0].source_id, "Inception");
+        let errors = failed_items
+            .iter()
+            .map(|f| (f.identifier.as_str(), f.error.clone().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(errors.len(), 3);
+        assert_eq!(errors[0].0, "Blue Eye Samurai");
+        assert!(errors[0].1.starts_with("/1/lot: "));
+        assert_eq!(errors[1].0, "Dune");
+        assert!(errors[1].1.starts_with("/2/seen_history/0/ended_on: "));
+        assert_eq!(errors[2].0, "Arcane");
+        assert!(errors[2].1.starts_with("/3/identifier: missing field"));
+    }
+
+    #[test]
+    fn test_parse_export_rejects_large_payloads() {
+        let export = " ".repeat(MAX_EXPORT_SIZE + 1);
+        assert!(parse_export(&export).is_err());
+    }
+}
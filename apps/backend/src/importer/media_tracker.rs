@@ -1,12 +1,19 @@
 // Responsible for importing from https://github.com/bonukai/MediaTracker.
 
-use async_graphql::Result;
-use rust_decimal::Decimal;
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+
+use async_graphql::{Error, Result};
+use itertools::Itertools;
+use rust_decimal::{prelude::ToPrimitive, Decimal};
 use rust_decimal_macros::dec;
 use sea_orm::prelude::DateTimeUtc;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
 use serde_with::{formats::Flexible, serde_as, TimestampMilliSeconds};
-use surf::{http::headers::USER_AGENT, Client, Config, Url};
+use surf::{http::headers::USER_AGENT, Client, Config, StatusCode, Url};
 use uuid::Uuid;
 
 use crate::{
@@ -14,21 +21,23 @@ use crate::{
         DeployMediaTrackerImportInput, ImportFailStep, ImportFailedItem, ImportOrExportItem,
         ImportResult,
     },
-    migrator::{MetadataLot, MetadataSource},
+    migrator::{MediaState, MetadataLot, MetadataSource},
     miscellaneous::{MediaSpecifics, MetadataCreator},
-    models::{
-        media::{
-            BookSpecifics, CreateOrUpdateCollectionInput, ImportOrExportItemIdentifier,
-            ImportOrExportItemRating, ImportOrExportItemReview, ImportOrExportItemSeen,
-            MediaDetails, Visibility,
-        },
-        IdObject,
+    models::media::{
+        BookSpecifics, CreateOrUpdateCollectionInput, ImportOrExportItemIdentifier,
+        ImportOrExportItemRating, ImportOrExportItemReview, ImportOrExportItemSeen, MediaDetails,
+        Visibility,
     },
     providers::openlibrary::get_key,
     utils::USER_AGENT_STR,
 };
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// The number of items requested at a time when the import does not set it.
+const DEFAULT_PAGE_SIZE: i32 = 100;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_REQUEST_ATTEMPTS: u32 = 3;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 enum MediaType {
     Book,
@@ -36,16 +45,20 @@ enum MediaType {
     Tv,
     VideoGame,
     Audiobook,
+    /// A media type that Ryot does not support
+    #[serde(other)]
+    Unsupported,
 }
 
-impl From<MediaType> for MetadataLot {
-    fn from(value: MediaType) -> Self {
-        match value {
-            MediaType::Book => Self::Book,
-            MediaType::Movie => Self::Movie,
-            MediaType::Tv => Self::Show,
-            MediaType::VideoGame => Self::VideoGame,
-            MediaType::Audiobook => Self::AudioBook,
+impl MediaType {
+    fn lot(&self) -> Option<MetadataLot> {
+        match self {
+            Self::Book => Some(MetadataLot::Book),
+            Self::Movie => Some(MetadataLot::Movie),
+            Self::Tv => Some(MetadataLot::Show),
+            Self::VideoGame => Some(MetadataLot::VideoGame),
+            Self::Audiobook => Some(MetadataLot::AudioBook),
+            Self::Unsupported => None,
         }
     }
 }
@@ -57,6 +70,23 @@ enum ListPrivacy {
     Public,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UserResponse {
+    id: i32,
+    /// Whether the reviews of the user are shown to the other users
+    public_reviews: Option<bool>,
+}
+
+impl UserResponse {
+    fn review_visibility(&self) -> Option<Visibility> {
+        self.public_reviews.map(|public| match public {
+            true => Visibility::Public,
+            false => Visibility::Private,
+        })
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct ListResponse {
@@ -66,6 +96,9 @@ struct ListResponse {
     items: Vec<ListItemResponse>,
     description: Option<String>,
     privacy: ListPrivacy,
+    /// Set for the watchlist that MediaTracker creates for every user
+    #[serde(default)]
+    is_watchlist: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -74,11 +107,21 @@ struct ListItemResponse {
     media_item: Item,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 struct Item {
     id: i32,
     media_type: MediaType,
+    /// The fraction of the item that has been consumed, if it is in progress
+    #[serde(default)]
+    progress: Option<Decimal>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ItemsPage {
+    data: Vec<Item>,
+    total_number_of_pages: i32,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -113,7 +156,7 @@ struct ItemSeen {
     episode_id: Option<i32>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 #[serde(rename_all = "camelCase")]
 struct ItemDetails {
     seen_history: Vec<ItemSeen>,
@@ -130,28 +173,91 @@ struct ItemDetails {
     number_of_pages: Option<i32>,
 }
 
-pub async fn import(input: DeployMediaTrackerImportInput) -> Result<ImportResult> {
-    let client: Client = Config::new()
+/// Get the identifier of an item using the external ids that MediaTracker has
+/// already matched it with. Books that only exist on Goodreads can not be
+/// fetched from any provider.
+fn external_identifier(
+    media_type: &MediaType,
+    details: &ItemDetails,
+) -> Option<(String, MetadataSource)> {
+    match media_type {
+        MediaType::Book => details
+            .openlibrary_id
+            .as_ref()
+            .map(|i| (get_key(i), MetadataSource::Openlibrary)),
+        MediaType::Movie | MediaType::Tv => details
+            .tmdb_id
+            .map(|i| (i.to_string(), MetadataSource::Tmdb)),
+        MediaType::VideoGame => details
+            .igdb_id
+            .map(|i| (i.to_string(), MetadataSource::Igdb)),
+        MediaType::Audiobook => details
+            .audible_id
+            .clone()
+            .map(|i| (i, MetadataSource::Audible)),
+        MediaType::Unsupported => None,
+    }
+}
+
+/// The state of an item in MediaTracker. Whether a show is completed depends on
+/// its episodes, so that is left to the seen history.
+fn item_state(
+    lot: MetadataLot,
+    seen: bool,
+    progress: Option<Decimal>,
+    in_watchlist: bool,
+) -> Option<MediaState> {
+    let in_progress = progress.map_or(false, |p| p > Decimal::ZERO && p < Decimal::ONE);
+    if in_progress {
+        Some(MediaState::InProgress)
+    } else if seen {
+        (lot != MetadataLot::Show).then_some(MediaState::Completed)
+    } else if in_watchlist {
+        Some(MediaState::PlanTo)
+    } else {
+        None
+    }
+}
+
+fn get_client(input: &DeployMediaTrackerImportInput) -> Client {
+    Config::new()
         .add_header(USER_AGENT, USER_AGENT_STR)
         .unwrap()
-        .add_header("Access-Token", input.api_key)
+        .add_header("Access-Token", input.api_key.as_str())
         .unwrap()
         .set_base_url(Url::parse(&format!("{}/api/", input.api_url)).unwrap())
+        .set_timeout(Some(REQUEST_TIMEOUT))
         .try_into()
-        .unwrap();
+        .unwrap()
+}
 
-    let mut rsp = client.get("user").await.unwrap();
-    let data: IdObject = rsp.body_json().await.unwrap();
+/// The number of items in the MediaTracker account, read from a page with a
+/// single item so that no details are fetched.
+pub async fn estimate_size(input: &DeployMediaTrackerImportInput) -> Result<usize> {
+    let client = get_client(input);
+    let data: ItemsPage = get_json(
+        &client,
+        "items/paginated",
+        Some(json!({ "page": 1, "numberOfItemsPerPage": 1 })),
+    )
+    .await
+    .map_err(|e| Error::new(format!("Could not get the MediaTracker items: {e}")))?;
+    Ok(data.total_number_of_pages as usize)
+}
 
-    let user_id: i32 = data.id;
+pub async fn import(input: DeployMediaTrackerImportInput) -> Result<ImportResult> {
+    let client = get_client(&input);
+    let page_size = input.page_size.unwrap_or(DEFAULT_PAGE_SIZE).max(1);
 
-    let mut rsp = client
-        .get("lists")
-        .query(&serde_json::json!({ "userId": user_id }))
-        .unwrap()
+    let data: UserResponse = get_json(&client, "user", None)
         .await
-        .unwrap();
-    let mut lists: Vec<ListResponse> = rsp.body_json().await.unwrap();
+        .map_err(|e| Error::new(format!("Could not get the MediaTracker user: {e}")))?;
+    let user_id: i32 = data.id;
+
+    let mut lists: Vec<ListResponse> =
+        get_json(&client, "lists", Some(json!({ "userId": user_id })))
+            .await
+            .map_err(|e| Error::new(format!("Could not get the MediaTracker lists: {e}")))?;
 
     let all_collections = lists
         .iter()
@@ -169,92 +275,302 @@ pub async fn import(input: DeployMediaTrackerImportInput) -> Result<ImportResult
         })
         .collect();
     for list in lists.iter_mut() {
-        let mut rsp = client
-            .get("list/items")
-            .query(&serde_json::json!({ "listId": list.id }))
-            .unwrap()
+        list.items = get_json(&client, "list/items", Some(json!({ "listId": list.id })))
             .await
-            .unwrap();
-        let items: Vec<ListItemResponse> = rsp.body_json().await.unwrap();
-        list.items = items;
+            .map_err(|e| Error::new(format!("Could not get the list {:?}: {e}", list.name)))?;
     }
+    tracing::trace!("Loaded data for {total:?} lists", total = lists.len());
 
-    let mut failed_items = vec![];
+    let mut result = ImportResult {
+        collections: all_collections,
+        ..Default::default()
+    };
 
+    let mut in_progress = HashMap::new();
+    let mut pages = ItemPages::new(&client, page_size, true);
+    while let Some(items) = pages.next(&mut result).await? {
+        in_progress.extend(items.into_iter().map(|i| (i.id, i)));
+    }
+
+    let importer = ItemImporter {
+        client: &client,
+        lists: &lists,
+        in_progress: &in_progress,
+        review_visibility: data.review_visibility(),
+    };
+    let mut imported = HashSet::new();
     // all items returned here are seen atleast once
-    let mut rsp = client.get("items").await.unwrap();
-    let mut data: Vec<Item> = rsp.body_json().await.unwrap();
-
-    // There are a few items that are added to lists but have not been seen, so will
-    // add them manually.
-    lists.iter().for_each(|l| {
-        l.items.iter().for_each(|i| {
-            data.push(Item {
-                id: i.media_item.id,
-                media_type: i.media_item.media_type.clone(),
-            })
-        })
-    });
+    let mut pages = ItemPages::new(&client, page_size, false);
+    while let Some(items) = pages.next(&mut result).await? {
+        let items = items
+            .into_iter()
+            .filter(|i| imported.insert(i.id))
+            .collect_vec();
+        importer.import_all(items, &mut result).await;
+    }
 
-    tracing::trace!("Loaded data for {total:?} lists", total = lists.len());
+    // There are a few items that are added to lists or are in progress but have
+    // not been seen, so will add them manually.
+    let extra_items = lists
+        .iter()
+        .flat_map(|l| l.items.iter().map(|i| i.media_item.clone()))
+        .chain(in_progress.values().sorted_by_key(|i| i.id).cloned())
+        .filter(|i| imported.insert(i.id))
+        .collect_vec();
+    importer.import_all(extra_items, &mut result).await;
+
+    if result.unsupported > 0 {
+        tracing::warn!(
+            "Skipped {total} items of types that are not supported",
+            total = result.unsupported
+        );
+    }
+    Ok(result)
+}
 
-    let data_len = data.len();
-
-    let mut final_data = vec![];
-    // TODO: Technically this can be done in parallel, by executing requests in
-    // batches. Example: https://users.rust-lang.org/t/can-tokio-semaphore-be-used-to-limit-spawned-tasks/59899.
-    for (idx, d) in data.into_iter().enumerate() {
-        let lot = MetadataLot::from(d.media_type.clone());
-        let mut rsp = client.get(format!("details/{}", d.id)).await.unwrap();
-        let details: ItemDetails = match rsp.body_json().await {
-            Ok(s) => s,
-            Err(e) => {
-                tracing::error!("Encountered error for id = {id:?}: {e:?}", id = d.id);
-                failed_items.push(ImportFailedItem {
-                    lot,
-                    step: ImportFailStep::ItemDetailsFromSource,
-                    identifier: d.id.to_string(),
-                    error: Some(e.to_string()),
-                });
+/// Make a request to MediaTracker and parse its response. Requests that fail,
+/// time out or get an error from the server are tried again with an
+/// exponential backoff.
+async fn get_json<T: DeserializeOwned>(
+    client: &Client,
+    path: &str,
+    query: Option<Value>,
+) -> std::result::Result<T, String> {
+    let mut attempt = 1;
+    loop {
+        let mut request = client.get(path);
+        if let Some(query) = &query {
+            request = request.query(query).map_err(|e| e.to_string())?;
+        }
+        let error = match request.await {
+            Ok(mut rsp) if rsp.status().is_success() => {
+                return rsp.body_json().await.map_err(|e| e.to_string());
+            }
+            Ok(rsp)
+                if !rsp.status().is_server_error()
+                    && rsp.status() != StatusCode::TooManyRequests =>
+            {
+                return Err(format!("MediaTracker responded with {}", rsp.status()));
+            }
+            Ok(rsp) => format!("MediaTracker responded with {}", rsp.status()),
+            Err(e) => e.to_string(),
+        };
+        if attempt >= MAX_REQUEST_ATTEMPTS {
+            return Err(error);
+        }
+        tracing::warn!("Request to {path:?} failed (attempt {attempt}): {error}");
+        tokio::time::sleep(Duration::from_secs(2_u64.pow(attempt - 1))).await;
+        attempt += 1;
+    }
+}
+
+/// Fetches the items one page at a time, so that each page can be imported
+/// before the next one is requested.
+struct ItemPages<'a> {
+    client: &'a Client,
+    page_size: i32,
+    only_with_progress: bool,
+    page: i32,
+    total_pages: i32,
+}
+
+impl<'a> ItemPages<'a> {
+    fn new(client: &'a Client, page_size: i32, only_with_progress: bool) -> Self {
+        Self {
+            client,
+            page_size,
+            only_with_progress,
+            page: 1,
+            total_pages: 1,
+        }
+    }
+
+    async fn fetch(&self, page: i32, page_size: i32) -> std::result::Result<ItemsPage, String> {
+        let mut query = json!({ "page": page, "numberOfItemsPerPage": page_size });
+        if self.only_with_progress {
+            query["onlyWithProgress"] = json!(true);
+        }
+        get_json(self.client, "items/paginated", Some(query)).await
+    }
+
+    /// The items of the next page. The import fails without the first page,
+    /// since it tells how many pages there are. The items of a later page that
+    /// can not be fetched are requested one at a time, and the ones that still
+    /// fail are added to the failed items.
+    async fn next(&mut self, result: &mut ImportResult) -> Result<Option<Vec<Item>>> {
+        if self.page > self.total_pages {
+            return Ok(None);
+        }
+        let page = self.page;
+        self.page += 1;
+        let error = match self.fetch(page, self.page_size).await {
+            Ok(data) => {
+                self.total_pages = data.total_number_of_pages;
+                tracing::trace!("Got page {page}/{total} of items", total = self.total_pages);
+                return Ok(Some(data.data));
+            }
+            Err(e) if page == 1 => {
+                return Err(Error::new(format!(
+                    "Could not get the MediaTracker items: {e}"
+                )));
+            }
+            Err(e) => e,
+        };
+        tracing::error!("Could not get page {page} of items, getting them one at a time: {error}");
+        let mut items = vec![];
+        let first = (page - 1) * self.page_size + 1;
+        for position in first..first + self.page_size {
+            match self.fetch(position, 1).await {
+                // DEV: the last page might not be full
+                Ok(data) if data.data.is_empty() => break,
+                Ok(data) => items.extend(data.data),
+                Err(e) => result.failed_items.push(ImportFailedItem::new(
+                    MetadataLot::default(),
+                    ImportFailStep::ItemDetailsFromSource,
+                    format!("item {position}"),
+                    Some(e),
+                )),
+            }
+        }
+        Ok(Some(items))
+    }
+}
+
+struct ItemImporter<'a> {
+    client: &'a Client,
+    lists: &'a [ListResponse],
+    in_progress: &'a HashMap<i32, Item>,
+    review_visibility: Option<Visibility>,
+}
+
+impl ItemImporter<'_> {
+    async fn import_all(&self, items: Vec<Item>, result: &mut ImportResult) {
+        // TODO: Technically this can be done in parallel, by executing requests in
+        // batches. Example: https://users.rust-lang.org/t/can-tokio-semaphore-be-used-to-limit-spawned-tasks/59899.
+        for item in items {
+            let Some(lot) = item.media_type.lot() else {
+                result.unsupported += 1;
                 continue;
+            };
+            match self.import_one(item, lot).await {
+                Ok(item) => result.media.push(item),
+                Err(item) => result.failed_items.push(item),
             }
+        }
+    }
+
+    async fn import_one(
+        &self,
+        d: Item,
+        lot: MetadataLot,
+    ) -> std::result::Result<ImportOrExportItem<ImportOrExportItemIdentifier>, ImportFailedItem>
+    {
+        let fail = |error: String| {
+            ImportFailedItem::new(
+                lot,
+                ImportFailStep::ItemDetailsFromSource,
+                d.id.to_string(),
+                Some(error),
+            )
         };
-        let (identifier, source) = match d.media_type.clone() {
-            MediaType::Book => {
-                if let Some(_g_id) = details.goodreads_id {
-                    (Uuid::new_v4().to_string(), MetadataSource::Custom)
-                } else {
-                    (
-                        get_key(&details.openlibrary_id.clone().unwrap()),
-                        MetadataSource::Openlibrary,
-                    )
-                }
+        let details: ItemDetails = get_json(self.client, &format!("details/{}", d.id), None)
+            .await
+            .map_err(|e| {
+                tracing::error!("Encountered error for id = {id:?}: {e:?}", id = d.id);
+                fail(e)
+            })?;
+        let external = external_identifier(&d.media_type, &details);
+        let need_details = external.is_some();
+        let (identifier, source) = match external {
+            Some(e) => e,
+            None if d.media_type == MediaType::Book => {
+                (Uuid::new_v4().to_string(), MetadataSource::Custom)
+            }
+            None => {
+                return Err(fail(
+                    "MediaTracker has no external id for this item".to_owned(),
+                ))
             }
-            MediaType::Movie => (details.tmdb_id.unwrap().to_string(), MetadataSource::Tmdb),
-            MediaType::Tv => (details.tmdb_id.unwrap().to_string(), MetadataSource::Tmdb),
-            MediaType::VideoGame => (details.igdb_id.unwrap().to_string(), MetadataSource::Igdb),
-            MediaType::Audiobook => (details.audible_id.clone().unwrap(), MetadataSource::Audible),
         };
         tracing::trace!(
-            "Got details for {type:?}: {id} ({idx}/{total})",
+            "Got details for {type:?}: {id}",
             type = d.media_type,
             id = d.id,
-            idx = idx,
-            total = data_len
         );
-        let need_details = details.goodreads_id.is_none();
+        let progress = d
+            .progress
+            .or_else(|| self.in_progress.get(&d.id).and_then(|i| i.progress));
 
         let mut collections = vec![];
-        for list in lists.iter() {
+        let mut in_watchlist = false;
+        for list in self.lists.iter() {
             for item in list.items.iter() {
                 if item.media_item.id == d.id {
                     collections.push(list.name.clone());
+                    in_watchlist |= list.is_watchlist;
+                }
+            }
+        }
+        let state = item_state(
+            lot,
+            !details.seen_history.is_empty(),
+            progress,
+            in_watchlist,
+        );
+
+        let mut seen_history = details
+            .seen_history
+            .iter()
+            .map(|s| {
+                let (season_number, episode_number) = if let Some(c) = s.episode_id {
+                    let episode = details
+                        .seasons
+                        .iter()
+                        .flat_map(|e| e.episodes.to_owned())
+                        .find(|e| e.id == c)
+                        .unwrap();
+                    (Some(episode.season_number), Some(episode.episode_number))
+                } else {
+                    (None, None)
+                };
+                ImportOrExportItemSeen {
+                    started_on: None,
+                    ended_on: s.date,
+                    show_season_number: season_number,
+                    show_episode_number: episode_number,
+                    // DEV: Since this source does not support podcasts
+                    podcast_episode_number: None,
+                    podcast_episode_guid: None,
+                    progress: None,
+                    plays: None,
+                    date_precision: None,
                 }
+            })
+            .collect_vec();
+        // DEV: the progress of shows is tracked per episode, which MediaTracker
+        // does not expose
+        if let Some(progress) = progress
+            .filter(|_| lot != MetadataLot::Show)
+            .and_then(|p| (p * dec!(100)).round().to_i32())
+        {
+            if progress > 0 && progress < 100 {
+                seen_history.push(ImportOrExportItemSeen {
+                    started_on: None,
+                    ended_on: None,
+                    show_season_number: None,
+                    show_episode_number: None,
+                    podcast_episode_number: None,
+                    podcast_episode_guid: None,
+                    progress: Some(progress),
+                    plays: None,
+                    date_precision: None,
+                });
             }
         }
 
         let item = ImportOrExportItem {
             source_id: d.id.to_string(),
+            title: Some(details.title.clone()),
             source,
             lot,
             collections,
@@ -282,6 +598,8 @@ pub async fn import(input: DeployMediaTrackerImportInput) -> Result<ImportResult
                     specifics: MediaSpecifics::Book(BookSpecifics {
                         pages: details.number_of_pages,
                     }),
+                    groups: vec![],
+                    alternative_titles: vec![],
                 })),
                 true => ImportOrExportItemIdentifier::NeedsDetails(identifier),
             },
@@ -297,49 +615,75 @@ pub async fn import(input: DeployMediaTrackerImportInput) -> Result<ImportResult
                         date: None,
                         spoiler: Some(false),
                         text: r.review,
+                        visibility: None,
                     })
                 };
                 ImportOrExportItemRating {
-                    review,
+                    review: review.map(|r| ImportOrExportItemReview {
+                        visibility: self.review_visibility,
+                        ..r
+                    }),
                     rating: r.rating.map(|d| d.saturating_mul(dec!(20))),
                     show_season_number: None,
                     show_episode_number: None,
                     podcast_episode_number: None,
                 }
             })),
-            seen_history: details
-                .seen_history
-                .iter()
-                .map(|s| {
-                    let (season_number, episode_number) = if let Some(c) = s.episode_id {
-                        let episode = details
-                            .seasons
-                            .iter()
-                            .flat_map(|e| e.episodes.to_owned())
-                            .find(|e| e.id == c)
-                            .unwrap();
-                        (Some(episode.season_number), Some(episode.episode_number))
-                    } else {
-                        (None, None)
-                    };
-                    ImportOrExportItemSeen {
-                        started_on: None,
-                        ended_on: s.date,
-                        show_season_number: season_number,
-                        show_episode_number: episode_number,
-                        // DEV: Since this source does not support podcasts
-                        podcast_episode_number: None,
-                    }
-                })
-                .collect(),
+            seen_history,
+            state,
         };
-        final_data.push(item);
+        Ok(item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(MediaType::Book, Some(("OL1M".to_owned(), MetadataSource::Openlibrary)))]
+    #[case(MediaType::Movie, Some(("27205".to_owned(), MetadataSource::Tmdb)))]
+    #[case(MediaType::Tv, Some(("27205".to_owned(), MetadataSource::Tmdb)))]
+    #[case(MediaType::VideoGame, None)]
+    #[case(MediaType::Audiobook, Some(("B00".to_owned(), MetadataSource::Audible)))]
+    #[case(MediaType::Unsupported, None)]
+    fn test_external_identifier(
+        #[case] media_type: MediaType,
+        #[case] expected: Option<(String, MetadataSource)>,
+    ) {
+        let details = ItemDetails {
+            openlibrary_id: Some("/books/OL1M".to_owned()),
+            goodreads_id: Some(1),
+            tmdb_id: Some(27205),
+            audible_id: Some("B00".to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(external_identifier(&media_type, &details), expected);
+    }
+
+    #[rstest]
+    #[case(MetadataLot::Movie, true, None, false, Some(MediaState::Completed))]
+    #[case(MetadataLot::Show, true, None, false, None)]
+    #[case(MetadataLot::Book, false, Some(dec!(0.4)), true, Some(MediaState::InProgress))]
+    #[case(MetadataLot::Book, false, Some(dec!(0)), true, Some(MediaState::PlanTo))]
+    #[case(MetadataLot::Movie, false, None, false, None)]
+    fn test_item_state(
+        #[case] lot: MetadataLot,
+        #[case] seen: bool,
+        #[case] progress: Option<Decimal>,
+        #[case] in_watchlist: bool,
+        #[case] expected: Option<MediaState>,
+    ) {
+        assert_eq!(item_state(lot, seen, progress, in_watchlist), expected);
+    }
+
+    #[test]
+    fn test_unknown_media_type_is_unsupported() {
+        let item: Item = serde_json::from_str(r#"{"id": 1, "mediaType": "podcast"}"#).unwrap();
+        assert_eq!(item.media_type, MediaType::Unsupported);
+        assert!(item.media_type.lot().is_none());
     }
-    Ok(ImportResult {
-        media: final_data,
-        failed_items,
-        collections: all_collections,
-    })
 }
 
 pub mod utils {
@@ -370,6 +714,7 @@ pub mod utils {
                 date: Some(date),
                 spoiler: Some(spoiler),
                 text: Some(text),
+                visibility: None,
             })
         } else {
             None
@@ -1,34 +1,84 @@
-use std::sync::Arc;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    future::Future,
+    iter,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
 
-use apalis::{prelude::Storage, sqlite::SqliteStorage};
-use async_graphql::{Context, Enum, InputObject, Object, Result, SimpleObject};
-use chrono::{Duration, Utc};
+use apalis::{
+    prelude::{Job, Storage},
+    sqlite::SqliteStorage,
+};
+use async_graphql::{
+    Context, Enum, Error, ErrorExtensions, InputObject, Object, Result, SimpleObject,
+};
+use chrono::{DateTime, Duration, Utc};
+use futures::{AsyncRead, AsyncReadExt};
 use itertools::Itertools;
+use nanoid::nanoid;
+use regex::Regex;
+use rust_decimal::Decimal;
 use sea_orm::{
-    ActiveModelTrait, ActiveValue, ColumnTrait, DatabaseConnection, EntityTrait,
-    FromJsonQueryResult, QueryFilter,
+    ActiveModelTrait, ActiveValue, ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr,
+    EntityTrait, FromJsonQueryResult, FromQueryResult, ModelTrait, QueryFilter, QueryOrder,
+    QuerySelect, TransactionTrait,
 };
-use serde::{Deserialize, Serialize};
+use sea_query::Expr;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use strum::IntoEnumIterator;
 
 use crate::{
-    background::ImportMedia,
-    entities::{media_import_report, prelude::MediaImportReport},
-    migrator::{MediaImportSource, MetadataLot},
-    miscellaneous::resolver::MiscellaneousService,
-    models::media::{
-        AddMediaToCollection, CreateOrUpdateCollectionInput, ImportOrExportItem,
-        ImportOrExportItemIdentifier, PostReviewInput, ProgressUpdateInput,
+    background::{ImportMedia, JobEstimate, JobHandle},
+    encryption::SecretsCipher,
+    entities::{
+        collection, import_analytics, media_import_report, metadata, metadata_title,
+        metadata_to_collection,
+        prelude::{
+            Collection, ImportAnalytics, MediaImportReport, Metadata, MetadataGroup, MetadataTitle,
+            MetadataToCollection, Review, Seen, User,
+        },
+        review, seen,
+    },
+    migrator::{
+        MediaImportSource, MediaState, MetadataLot, MetadataSource, SeenDatePrecision, SeenState,
     },
-    traits::AuthProvider,
-    utils::MemoryDatabase,
+    miscellaneous::{
+        resolver::{sort_media_candidates, MiscellaneousService, SeenBatch},
+        DefaultCollection, MediaSpecifics,
+    },
+    models::{
+        media::{
+            AddMediaToCollection, CreateOrUpdateCollectionInput, CreateSeenEntryInput,
+            ImportOrExportItem, ImportOrExportItemIdentifier, ImportOrExportItemRating,
+            ImportOrExportItemSeen, ImportProvenance, MediaDetails, MediaSearchItem,
+            PodcastEpisode, PostReviewInput, ProgressUpdateInput, Visibility,
+        },
+        IdObject,
+    },
+    monitoring::{
+        provider_failure_count, provider_request_count, record_import, record_job_enqueued,
+    },
+    notification::{send_webhook, ImportNotification},
+    rate_limiter::RateLimitedOperation,
+    shutdown::ShutdownSignal,
+    traits::{AuthProvider, MediaProvider},
+    utils::{associate_user_with_metadata, MemoryDatabase, MAX_RATING, PAGE_LIMIT},
 };
 
+#[cfg(test)]
+mod fake_provider;
 mod goodreads;
+#[cfg(test)]
+mod harness;
+mod justwatch;
 mod media_json;
 mod media_tracker;
 mod movary;
+mod ryot_sqlite;
 mod story_graph;
 mod trakt;
+mod xlsx;
 
 #[derive(Debug, InputObject, Serialize, Deserialize, Clone)]
 pub struct DeployMediaTrackerImportInput {
@@ -36,18 +86,43 @@ pub struct DeployMediaTrackerImportInput {
     api_url: String,
     /// An application token generated by an admin
     api_key: String,
+    /// The number of items requested at a time. Defaults to 100.
+    page_size: Option<i32>,
 }
 
 #[derive(Debug, InputObject, Serialize, Deserialize, Clone)]
 pub struct DeployGoodreadsImportInput {
     // The RSS url that can be found from the user's profile
-    rss_url: String,
+    rss_url: Option<String>,
+    // The contents of the CSV export. It contains the full history, unlike the
+    // RSS feed, and is preferred if provided.
+    csv_export: Option<String>,
+}
+
+#[derive(Debug, InputObject, Serialize, Deserialize, Clone)]
+pub struct DeployTraktListImportInput {
+    // The slug of the list (for eg: `username/lists/slug` or just `slug`). A
+    // list of another profile needs the username or the url of the list.
+    slug: String,
+    // The name of the collection the items will be added to. Defaults to the
+    // name of the list.
+    collection: Option<String>,
 }
 
 #[derive(Debug, InputObject, Serialize, Deserialize, Clone)]
 pub struct DeployTraktImportInput {
     // The public username in Trakt.
     username: String,
+    // Import only this list instead of the whole profile.
+    list: Option<DeployTraktListImportInput>,
+}
+
+#[derive(Debug, InputObject, Serialize, Deserialize, Clone)]
+pub struct DeployJustWatchImportInput {
+    /// The access token of a logged in JustWatch session.
+    token: String,
+    /// The country whose catalog is used, for eg "GB". Defaults to "US".
+    region: Option<String>,
 }
 
 #[derive(Debug, InputObject, Serialize, Deserialize, Clone)]
@@ -56,12 +131,43 @@ pub struct DeployMovaryImportInput {
     history: String,
     // The CSV contents of the ratings file.
     ratings: String,
+    // The CSV contents of the watchlist file.
+    watchlist: Option<String>,
+    // The format of the dates in the CSV files (for eg: `DD/MM/YYYY`).
+    // Defaults to `YYYY-MM-DD`, which Movary uses in its exports.
+    date_format: Option<String>,
 }
 
 #[derive(Debug, InputObject, Serialize, Deserialize, Clone)]
 pub struct DeployStoryGraphImportInput {
     // The CSV contents of the export file.
     export: String,
+    // The format of the dates in the CSV file (for eg: `DD/MM/YYYY`).
+    // Defaults to `YYYY/MM/DD`, which StoryGraph uses in its exports.
+    date_format: Option<String>,
+}
+
+#[derive(Debug, InputObject, Serialize, Deserialize, Clone)]
+pub struct DeployRyotSqliteImportInput {
+    // The key of the SQLite database file returned by the upload endpoint.
+    backup_key: String,
+    // The name of the user in the instance the backup was taken from.
+    username: String,
+}
+
+#[derive(Debug, InputObject, Serialize, Deserialize, Clone)]
+pub struct DeployXlsxImportInput {
+    // The key of the spreadsheet returned by the upload endpoint.
+    file_key: String,
+    // The sheet to import. The first sheet is used if not provided.
+    sheet_name: Option<String>,
+    // A column with a rating from another rating system, for eg "Stars". It
+    // is kept in the text of the review since Ryot has a single scale.
+    secondary_rating_column: Option<String>,
+    // The format of the dates that are written as text (for eg: `DD/MM/YYYY`).
+    // If not provided, the format that fits the most dates of the sheet is
+    // used for all of them.
+    date_format: Option<String>,
 }
 
 #[derive(Debug, InputObject, Serialize, Deserialize, Clone)]
@@ -70,15 +176,198 @@ pub struct DeployMediaJsonImportInput {
     export: String,
 }
 
+/// How the metadata of imported media should be fetched from the providers
+#[derive(Debug, Enum, PartialEq, Eq, Copy, Clone, Serialize, Deserialize, Default)]
+pub enum ImportMetadataEnrichment {
+    /// Fetch the details of each item while importing it
+    #[default]
+    Immediate,
+    /// Commit items with minimal data and refresh all of them once the import
+    /// is complete, in batches of up to 100 media per job
+    Deferred,
+    /// Commit items with minimal data and never refresh them automatically
+    Skip,
+}
+
+/// How an import changes the state of media that the user has already
+/// started or finished
+#[derive(Debug, Enum, PartialEq, Eq, Copy, Clone, Serialize, Deserialize, Default)]
+pub enum ImportStateOverwrite {
+    /// Keep the existing state if the source has the media less far along, for
+    /// eg in progress when it is already completed
+    #[default]
+    NeverRegress,
+    /// Always use the state from the source
+    SourceAuthoritative,
+}
+
 #[derive(Debug, InputObject, Serialize, Deserialize, Clone)]
 pub struct DeployImportJobInput {
     pub source: MediaImportSource,
+    pub metadata_enrichment: Option<ImportMetadataEnrichment>,
+    /// Append the results to the last successful report of this source instead
+    /// of creating a new one.
+    pub merge_with_last_report: Option<bool>,
+    /// Do not import seen history for media that already has some.
+    pub skip_existing_seen: Option<bool>,
+    /// The visibility of imported reviews whose source does not specify one.
+    /// Defaults to private.
+    pub review_visibility: Option<Visibility>,
+    /// Mark all imported reviews as containing spoilers. Reviews that the
+    /// source flags as spoilers are always marked.
+    pub mark_imported_reviews_spoiler: Option<bool>,
+    /// Only import media of these types.
+    pub lots: Option<Vec<MetadataLot>>,
+    /// The collection that items from watchlist-like shelves are added to.
+    pub watchlist_collection: Option<String>,
+    /// Record seen history without an end date as in progress instead of
+    /// completed.
+    pub undated_seen_in_progress: Option<bool>,
+    /// Do not recalculate the user's summary once the import is complete.
+    pub skip_summary_recalculation: Option<bool>,
+    /// Items resolved by their title with a confidence (between 0 and 1) below
+    /// this are treated as unmatched.
+    pub min_match_confidence: Option<Decimal>,
+    /// Items matched by their title with a lower confidence level than this
+    /// are not imported, but listed in the report to be confirmed or rejected.
+    pub confirm_matches_below: Option<ImportMatchLevel>,
+    /// Only create collections for the source's tags or shelves in this list.
+    /// All of them are created if not provided.
+    pub tag_collection_allowlist: Option<Vec<String>>,
+    /// Only import the latest rating of each media item instead of one review
+    /// per dated rating.
+    pub latest_rating_only: Option<bool>,
+    /// Suffix the collections created by the import with the source, date and
+    /// report id, for eg "Watchlist (Trakt 2023-08-02 #12)".
+    pub namespace_collections: Option<bool>,
+    /// Save seen history in batches of this size instead of one item at a
+    /// time, which is faster for large imports. At most 500.
+    pub seen_batch_size: Option<usize>,
+    /// Add the items that could not be matched by their title as custom media
+    /// to the "Needs Review" collection instead of failing them.
+    pub keep_unmatched: Option<bool>,
+    /// Regular expressions removed from titles before items are matched by
+    /// their title. Replaces the defaults of the source, which are returned by
+    /// `importTitleStripPatterns`.
+    pub title_strip_patterns: Option<Vec<String>>,
+    /// Whether the source can move media that already has a state back, for
+    /// eg from completed to in progress. Defaults to never.
+    pub state_overwrite: Option<ImportStateOverwrite>,
+    /// Add the items to a collection for each group they are a part of at
+    /// their provider, for eg "The Matrix Collection" on TMDB.
+    pub group_collections: Option<bool>,
+    /// Log the media that each imported item resolved to, at the debug level
+    /// of the `ryot::import_audit` target.
+    pub log_succeeded_items: Option<bool>,
+    /// Import the items in the order they appear in the source instead of
+    /// starting with the ones that have the most activity.
+    pub preserve_source_order: Option<bool>,
     pub media_tracker: Option<DeployMediaTrackerImportInput>,
     pub goodreads: Option<DeployGoodreadsImportInput>,
     pub trakt: Option<DeployTraktImportInput>,
     pub movary: Option<DeployMovaryImportInput>,
     pub story_graph: Option<DeployStoryGraphImportInput>,
     pub media_json: Option<DeployMediaJsonImportInput>,
+    pub ryot_sqlite: Option<DeployRyotSqliteImportInput>,
+    pub xlsx: Option<DeployXlsxImportInput>,
+    pub just_watch: Option<DeployJustWatchImportInput>,
+}
+
+impl DeployImportJobInput {
+    /// Whether a secret of the source was sealed before the job was queued.
+    pub fn has_encrypted_secret(&self) -> bool {
+        let api_key = self.media_tracker.as_ref().map(|s| &s.api_key);
+        let token = self.just_watch.as_ref().map(|s| &s.token);
+        api_key
+            .into_iter()
+            .chain(token)
+            .any(|s| SecretsCipher::is_encrypted(s))
+    }
+}
+
+/// The options that were used for an import job after applying the defaults
+/// from the user's preferences
+#[derive(Debug, SimpleObject, Serialize, Deserialize, Eq, PartialEq, Clone)]
+pub struct ImportOptions {
+    pub metadata_enrichment: ImportMetadataEnrichment,
+    pub merge_with_last_report: bool,
+    pub skip_existing_seen: bool,
+    pub review_visibility: Option<Visibility>,
+    pub lots: Vec<MetadataLot>,
+    pub watchlist_collection: String,
+    #[serde(default)]
+    pub undated_seen_in_progress: bool,
+    #[serde(default)]
+    pub skip_summary_recalculation: bool,
+    #[serde(default)]
+    pub min_match_confidence: Option<Decimal>,
+    #[serde(default)]
+    pub tag_collection_allowlist: Option<Vec<String>>,
+    #[serde(default)]
+    pub latest_rating_only: bool,
+    /// Appended to the name of every collection the import creates
+    #[serde(default)]
+    pub collection_suffix: Option<String>,
+    #[serde(default)]
+    pub seen_batch_size: Option<usize>,
+    #[serde(default)]
+    pub title_strip_patterns: Option<Vec<String>>,
+    #[serde(default)]
+    pub keep_unmatched: bool,
+    #[serde(default)]
+    pub state_overwrite: ImportStateOverwrite,
+    #[serde(default)]
+    pub group_collections: bool,
+    #[serde(default)]
+    pub log_succeeded_items: bool,
+    #[serde(default)]
+    pub preserve_source_order: bool,
+    #[serde(default)]
+    pub mark_imported_reviews_spoiler: bool,
+    #[serde(default)]
+    pub confirm_matches_below: Option<ImportMatchLevel>,
+}
+
+impl ImportOptions {
+    fn collection_name(&self, name: &str) -> String {
+        match &self.collection_suffix {
+            Some(suffix) => format!("{name} ({suffix})"),
+            None => name.to_owned(),
+        }
+    }
+}
+
+impl From<&DeployImportJobInput> for ImportOptions {
+    fn from(input: &DeployImportJobInput) -> Self {
+        Self {
+            metadata_enrichment: input.metadata_enrichment.unwrap_or_default(),
+            merge_with_last_report: input.merge_with_last_report.unwrap_or_default(),
+            skip_existing_seen: input.skip_existing_seen.unwrap_or_default(),
+            // DEV: the same default as the review table, so that the report
+            // shows the visibility that was actually used
+            review_visibility: Some(input.review_visibility.unwrap_or(Visibility::Private)),
+            lots: input.lots.clone().unwrap_or_default(),
+            watchlist_collection: input
+                .watchlist_collection
+                .clone()
+                .unwrap_or_else(|| DefaultCollection::Watchlist.to_string()),
+            undated_seen_in_progress: input.undated_seen_in_progress.unwrap_or_default(),
+            skip_summary_recalculation: input.skip_summary_recalculation.unwrap_or_default(),
+            min_match_confidence: input.min_match_confidence,
+            tag_collection_allowlist: input.tag_collection_allowlist.clone(),
+            latest_rating_only: input.latest_rating_only.unwrap_or_default(),
+            collection_suffix: None,
+            seen_batch_size: input.seen_batch_size,
+            title_strip_patterns: input.title_strip_patterns.clone(),
+            keep_unmatched: input.keep_unmatched.unwrap_or_default(),
+            state_overwrite: input.state_overwrite.unwrap_or_default(),
+            group_collections: input.group_collections.unwrap_or_default(),
+            log_succeeded_items: input.log_succeeded_items.unwrap_or_default(),
+            preserve_source_order: input.preserve_source_order.unwrap_or_default(),
+            mark_imported_reviews_spoiler: input.mark_imported_reviews_spoiler.unwrap_or_default(),
+            confirm_matches_below: input.confirm_matches_below,
+        }
+    }
 }
 
 /// The various steps in which media importing can fail
@@ -90,316 +379,4814 @@ pub enum ImportFailStep {
     MediaDetailsFromProvider,
     /// Failed to transform the data into the required format
     InputTransformation,
+    /// Failed to find the item at the provider using its title
+    TitleMatch,
     /// Failed to save a seen history item
     SeenHistoryConversion,
     /// Failed to save a review/rating item
     ReviewConversion,
+    /// Skipped since the provider failed for too many items in a row
+    ProviderUnavailable,
+    /// Failed to add the item to a collection
+    CollectionConversion,
+}
+
+/// The number of items of an import that failed at each step
+#[derive(
+    Debug, SimpleObject, FromJsonQueryResult, Serialize, Deserialize, Eq, PartialEq, Clone, Default,
+)]
+pub struct ImportStepFailures {
+    pub item_details_from_source: usize,
+    pub media_details_from_provider: usize,
+    pub input_transformation: usize,
+    pub title_match: usize,
+    pub seen_history_conversion: usize,
+    pub review_conversion: usize,
+    pub provider_unavailable: usize,
+    #[serde(default)]
+    pub collection_conversion: usize,
+}
+
+impl ImportStepFailures {
+    fn new(items: &[ImportFailedItem]) -> Self {
+        let mut failures = Self::default();
+        for item in items {
+            let count = match item.step {
+                ImportFailStep::ItemDetailsFromSource => &mut failures.item_details_from_source,
+                ImportFailStep::MediaDetailsFromProvider => {
+                    &mut failures.media_details_from_provider
+                }
+                ImportFailStep::InputTransformation => &mut failures.input_transformation,
+                ImportFailStep::TitleMatch => &mut failures.title_match,
+                ImportFailStep::SeenHistoryConversion => &mut failures.seen_history_conversion,
+                ImportFailStep::ReviewConversion => &mut failures.review_conversion,
+                ImportFailStep::ProviderUnavailable => &mut failures.provider_unavailable,
+                ImportFailStep::CollectionConversion => &mut failures.collection_conversion,
+            };
+            *count += 1;
+        }
+        failures
+    }
 }
 
 #[derive(
     Debug, SimpleObject, FromJsonQueryResult, Serialize, Deserialize, Eq, PartialEq, Clone,
 )]
 pub struct ImportFailedItem {
+    /// Identifies the item within its report
+    #[serde(default)]
+    id: String,
     lot: MetadataLot,
     step: ImportFailStep,
     identifier: String,
     error: Option<String>,
+    /// The line in the source file that the item was read from.
+    #[serde(default)]
+    line: Option<u64>,
+    /// The year the source had for the item, if it was matched by its title
+    #[serde(default)]
+    year: Option<i32>,
+    /// The item as it was read from the source, which is imported when the
+    /// item is retried
+    #[graphql(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    item: Option<serde_json::Value>,
+}
+
+impl ImportFailedItem {
+    pub fn new(
+        lot: MetadataLot,
+        step: ImportFailStep,
+        identifier: String,
+        error: Option<String>,
+    ) -> Self {
+        Self {
+            id: nanoid!(),
+            lot,
+            step,
+            identifier,
+            error,
+            line: None,
+            year: None,
+            item: None,
+        }
+    }
+
+    pub fn at_line(mut self, line: u64) -> Self {
+        self.line = Some(line);
+        self
+    }
+
+    /// Keep the item that failed, so that it can be imported with all of its
+    /// data when it is retried.
+    pub fn with_item(mut self, item: &ImportOrExportItem<ImportOrExportItemIdentifier>) -> Self {
+        self.item = serde_json::to_value(item).ok();
+        self
+    }
+
+    /// The title to search for the item with when it is retried. Only items
+    /// that could not be matched are identified by their title in the source.
+    fn search_title(&self) -> Option<String> {
+        if self.step == ImportFailStep::TitleMatch {
+            return Some(self.identifier.clone());
+        }
+        match stored_item(&self.item)?.identifier {
+            ImportOrExportItemIdentifier::AlreadyFilled(a) => Some(a.title),
+            ImportOrExportItemIdentifier::NeedsDetails(_) => None,
+        }
+    }
+}
+
+/// The item to import for a failed item or pending match once its identifier
+/// is known. Items that were not stored only add the media to the library.
+fn item_to_import(
+    stored: &Option<serde_json::Value>,
+    lot: MetadataLot,
+    source_id: &str,
+    source: MetadataSource,
+    identifier: String,
+) -> ImportOrExportItem<ImportOrExportItemIdentifier> {
+    let mut item = stored_item(stored).unwrap_or_else(|| ImportOrExportItem {
+        source_id: source_id.to_owned(),
+        title: None,
+        lot,
+        source,
+        identifier: ImportOrExportItemIdentifier::NeedsDetails(String::new()),
+        seen_history: vec![],
+        reviews: vec![],
+        collections: vec![],
+        state: None,
+    });
+    item.source = source;
+    item.identifier = ImportOrExportItemIdentifier::NeedsDetails(identifier);
+    item
+}
+
+/// The item that was stored along with a failed item or pending match.
+fn stored_item(
+    item: &Option<serde_json::Value>,
+) -> Option<ImportOrExportItem<ImportOrExportItemIdentifier>> {
+    serde_json::from_value(item.clone()?).ok()
+}
+
+/// An item that was imported, but some of its data could not be parsed
+#[derive(
+    Debug, SimpleObject, FromJsonQueryResult, Serialize, Deserialize, Eq, PartialEq, Clone,
+)]
+pub struct ImportWarningItem {
+    lot: MetadataLot,
+    identifier: String,
+    warning: String,
+    /// The other candidates that could have been chosen for this item
+    #[serde(default)]
+    alternatives: Vec<MediaSearchItem>,
+}
+
+/// An item that was imported
+#[derive(Debug, SimpleObject, Serialize, Deserialize, Eq, PartialEq, Clone)]
+pub struct ImportSucceededItem {
+    pub lot: MetadataLot,
+    pub source: MetadataSource,
+    /// The identifier of the media at its provider
+    pub identifier: String,
+    pub metadata_id: i32,
+}
+
+/// A provider whose remaining items were skipped after it failed for too many
+/// items in a row
+#[derive(Debug, SimpleObject, Serialize, Deserialize, Eq, PartialEq, Clone)]
+pub struct ImportUnavailableProvider {
+    pub lot: MetadataLot,
+    pub source: MetadataSource,
+    /// The number of items that were skipped because of it
+    pub skipped: usize,
+}
+
+/// A failed item of an import report to look up again at another provider
+#[derive(Debug, InputObject)]
+pub struct RetryFailedImportItemInput {
+    pub report_id: i32,
+    /// The id of the item in the `failed_items` of the report.
+    pub id: String,
+    /// The provider to look the item up at.
+    pub source: MetadataSource,
+    /// The identifier of the item at the provider. If not provided, the item
+    /// is searched for using its title in the source, if it is known.
+    pub identifier: Option<String>,
+}
+
+/// A pending match of an import report that the user agrees with
+#[derive(Debug, InputObject)]
+pub struct ConfirmImportMatchInput {
+    pub report_id: i32,
+    /// The id of the item in the `pending_matches` of the report.
+    pub id: String,
+    /// The identifier to import the item with instead of the matched one.
+    pub identifier: Option<String>,
+}
+
+/// The seen items and reviews that were removed along with an import
+#[derive(Debug, SimpleObject, Eq, PartialEq)]
+pub struct DeletedImportResults {
+    pub seen: u64,
+    pub reviews: u64,
+}
+
+/// How the items of a lot differ between two import reports
+#[derive(Debug, SimpleObject, Eq, PartialEq)]
+pub struct ImportReportLotComparison {
+    pub lot: MetadataLot,
+    pub only_in_new: Vec<ImportSucceededItem>,
+    pub only_in_old: Vec<ImportSucceededItem>,
+    pub in_both: Vec<ImportSucceededItem>,
 }
 
 #[derive(Debug, SimpleObject, Serialize, Deserialize, Eq, PartialEq, Clone)]
 pub struct ImportDetails {
     pub total: usize,
+    /// The number of items that were skipped because Ryot does not support
+    /// their type
+    #[serde(default)]
+    pub unsupported: usize,
 }
 
-#[derive(Debug)]
-pub struct ImportResult {
-    collections: Vec<CreateOrUpdateCollectionInput>,
-    media: Vec<ImportOrExportItem<ImportOrExportItemIdentifier>>,
-    failed_items: Vec<ImportFailedItem>,
+/// The outcome of the items of a single lot
+#[derive(Debug, SimpleObject, Serialize, Deserialize, Eq, PartialEq, Clone)]
+pub struct ImportLotSummary {
+    pub lot: MetadataLot,
+    /// Items that failed at the source or whose media the import tried to
+    /// commit
+    pub attempted: usize,
+    /// Items whose media was committed
+    pub succeeded: usize,
+    /// Items that failed at the source or whose media could not be committed
+    pub failed: usize,
+    pub seen_created: usize,
+    pub reviews_created: usize,
+    /// Seen items that were not started since their media is already further
+    /// along
+    #[serde(default)]
+    pub seen_skipped: usize,
 }
 
-#[derive(
-    Debug, SimpleObject, Serialize, Deserialize, FromJsonQueryResult, Eq, PartialEq, Clone,
-)]
-pub struct ImportResultResponse {
-    pub source: MediaImportSource,
-    pub import: ImportDetails,
-    pub failed_items: Vec<ImportFailedItem>,
+impl ImportLotSummary {
+    fn new(lot: MetadataLot) -> Self {
+        Self {
+            lot,
+            attempted: 0,
+            succeeded: 0,
+            failed: 0,
+            seen_created: 0,
+            reviews_created: 0,
+            seen_skipped: 0,
+        }
+    }
+
+    /// The summary of the lot, which is added if it is not there yet.
+    fn of(lots: &mut Vec<Self>, lot: MetadataLot) -> &mut Self {
+        match lots.iter().position(|l| l.lot == lot) {
+            Some(idx) => &mut lots[idx],
+            None => {
+                lots.push(Self::new(lot));
+                lots.last_mut().unwrap()
+            }
+        }
+    }
+
+    fn add(&mut self, other: &Self) {
+        self.attempted += other.attempted;
+        self.succeeded += other.succeeded;
+        self.failed += other.failed;
+        self.seen_created += other.seen_created;
+        self.reviews_created += other.reviews_created;
+        self.seen_skipped += other.seen_skipped;
+    }
 }
 
-#[derive(Default)]
-pub struct ImporterQuery;
+/// Accounts for every item that was read from the source in exactly one bucket
+#[derive(Debug, SimpleObject, Serialize, Deserialize, Eq, PartialEq, Clone, Default)]
+pub struct ImportReconciliation {
+    /// The number of items that were read from the source
+    pub source_total: usize,
+    /// Items whose media was committed
+    pub imported: usize,
+    /// Items that could not be read from the source or whose media could not
+    /// be committed
+    pub failed: usize,
+    /// Items that referred to the same media as an earlier item and were
+    /// merged into it, or that refer to media the user has ignored
+    pub skipped: usize,
+    /// Items that were excluded by the lots filter or whose type is not
+    /// supported
+    pub filtered: usize,
+    /// Items whose match has to be confirmed by the user
+    #[serde(default)]
+    pub pending: usize,
+}
 
-#[Object]
-impl ImporterQuery {
-    /// Get all the import jobs deployed by the user
-    async fn media_import_reports(
-        &self,
-        gql_ctx: &Context<'_>,
-    ) -> Result<Vec<media_import_report::Model>> {
-        let service = gql_ctx.data_unchecked::<Arc<ImporterService>>();
-        let user_id = service.user_id_from_ctx(gql_ctx).await?;
-        service.media_import_reports(user_id).await
+impl ImportReconciliation {
+    fn merge(self, other: Self) -> Self {
+        Self {
+            source_total: self.source_total + other.source_total,
+            imported: self.imported + other.imported,
+            failed: self.failed + other.failed,
+            skipped: self.skipped + other.skipped,
+            filtered: self.filtered + other.filtered,
+            pending: self.pending + other.pending,
+        }
     }
 }
 
-#[derive(Default)]
-pub struct ImporterMutation;
+/// The maximum number of alternatives recorded for an ambiguous title match
+const MAX_TITLE_ALTERNATIVES: usize = 5;
 
-#[Object]
-impl ImporterMutation {
-    /// Add job to import data from various sources.
-    async fn deploy_import_job(
-        &self,
-        gql_ctx: &Context<'_>,
-        input: DeployImportJobInput,
-    ) -> Result<String> {
-        let service = gql_ctx.data_unchecked::<Arc<ImporterService>>();
-        let user_id = service.user_id_from_ctx(gql_ctx).await?;
-        service.deploy_import_job(user_id, input).await
+/// How sure an item matched by its title is to be the right media
+#[derive(Debug, Enum, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum ImportMatchLevel {
+    /// The titles are only similar
+    Low,
+    /// The titles are the same, but the year is different or unknown
+    Medium,
+    /// The titles and years are the same
+    High,
+}
+
+fn match_level(
+    confidence: Decimal,
+    expected_year: Option<i32>,
+    year: Option<i32>,
+) -> ImportMatchLevel {
+    if confidence < Decimal::ONE {
+        ImportMatchLevel::Low
+    } else if expected_year.is_some() && expected_year == year {
+        ImportMatchLevel::High
+    } else {
+        ImportMatchLevel::Medium
     }
 }
 
-pub struct ImporterService {
-    db: DatabaseConnection,
-    media_service: Arc<MiscellaneousService>,
-    import_media: SqliteStorage<ImportMedia>,
+/// An item whose match has to be confirmed before it is imported
+#[derive(Debug, SimpleObject, Serialize, Deserialize, Eq, PartialEq, Clone)]
+pub struct ImportPendingMatch {
+    /// Identifies the match within its report
+    #[serde(default)]
+    pub id: String,
+    pub lot: MetadataLot,
+    pub source: MetadataSource,
+    /// The title of the item in the source
+    pub title: String,
+    /// The line in the source file that the item was read from.
+    pub line: Option<u64>,
+    pub level: ImportMatchLevel,
+    pub confidence: Decimal,
+    pub chosen: MediaSearchItem,
+    pub alternatives: Vec<MediaSearchItem>,
+    /// The item as it was read from the source, which is imported when the
+    /// match is confirmed
+    #[graphql(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub item: Option<serde_json::Value>,
 }
 
-impl AuthProvider for ImporterService {
-    fn get_auth_db(&self) -> &MemoryDatabase {
-        self.media_service.get_auth_db()
+impl ImportPendingMatch {
+    /// Keep the item whose match has to be confirmed, so that it can be
+    /// imported with all of its data.
+    pub fn with_item(mut self, item: &ImportOrExportItem<ImportOrExportItemIdentifier>) -> Self {
+        self.item = serde_json::to_value(item).ok();
+        self
     }
 }
 
-impl ImporterService {
-    #[allow(clippy::too_many_arguments)]
-    pub fn new(
-        db: &DatabaseConnection,
-        media_service: Arc<MiscellaneousService>,
-        import_media: &SqliteStorage<ImportMedia>,
-    ) -> Self {
-        Self {
-            db: db.clone(),
-            media_service,
-            import_media: import_media.clone(),
+/// What was added to the user's library for an item of an import
+#[derive(Debug, Default)]
+struct ItemWrite {
+    seen_created: usize,
+    reviews_created: usize,
+    seen_skipped: usize,
+    /// The seen history and reviews of the item that could not be added
+    failed_items: Vec<ImportFailedItem>,
+}
+
+/// The result of resolving an item using its title
+#[derive(Debug)]
+pub struct TitleResolution {
+    pub chosen: MediaSearchItem,
+    /// How closely the title of the chosen candidate matches, between 0 and 1
+    pub confidence: Decimal,
+    /// The other candidates that were returned by the provider
+    pub alternatives: Vec<MediaSearchItem>,
+}
+
+impl TitleResolution {
+    /// The confidence level of the match, given the year the item is expected
+    /// to be from.
+    pub fn level(&self, year: Option<i32>) -> ImportMatchLevel {
+        match_level(self.confidence, year, self.chosen.publish_year)
+    }
+    /// A warning that should be recorded if more than one candidate was found
+    pub fn warning(&self, lot: MetadataLot, identifier: &str) -> Option<ImportWarningItem> {
+        if self.alternatives.is_empty() {
+            return None;
         }
+        Some(ImportWarningItem {
+            lot,
+            identifier: identifier.to_owned(),
+            warning: format!(
+                "Matched to {:?} ({}) with confidence {} out of {} candidates",
+                self.chosen.title,
+                self.chosen.identifier,
+                self.confidence,
+                self.alternatives.len() + 1
+            ),
+            alternatives: self.alternatives.clone(),
+        })
     }
+}
 
-    pub async fn deploy_import_job(
+/// The identifier an item was resolved to, unless its match has to be
+/// confirmed by the user first
+pub enum ResolvedIdentifier {
+    Found(String, Option<ImportWarningItem>),
+    Pending(ImportPendingMatch),
+}
+
+/// The patterns removed from the titles of a source by default. Release years
+/// and edition markers are removed for all of them.
+pub fn default_title_strip_patterns(source: MediaImportSource) -> Vec<String> {
+    let mut patterns = vec![
+        r"\s*\[[^\]]*\]",
+        r"\s*\((19|20)\d{2}\)\s*$",
+        r"(?i)\s*[-:(]?\s*\b(director's|extended|special|collector's|anniversary|unrated|theatrical|deluxe)\s+(cut|edition)\)?",
+    ];
+    match source {
+        // DEV: Goodreads and StoryGraph add the series to the title, for eg
+        // "Leviathan Wakes (The Expanse, #1)"
+        MediaImportSource::Goodreads | MediaImportSource::StoryGraph => {
+            patterns.push(r"\s*\([^)]*#\d+(\.\d+)?\)\s*$");
+            patterns.push(r"(?i)\s*:\s*a novel\s*$");
+        }
+        MediaImportSource::Movary => patterns.push(r"(?i)\s*\b(4k|uhd|blu-?ray|dvd)\b"),
+        _ => {}
+    }
+    patterns.into_iter().map(String::from).collect()
+}
+
+/// A media item that is already in the database
+#[derive(Debug, FromQueryResult)]
+struct KnownMedia {
+    identifier: String,
+    title: String,
+    publish_year: Option<i32>,
+}
+
+/// The media in the database that is known by a title, either as its main
+/// title or as one of its alternative titles.
+async fn known_media_by_title<C: ConnectionTrait>(
+    db: &C,
+    lot: MetadataLot,
+    source: MetadataSource,
+    title: &str,
+) -> std::result::Result<Vec<KnownMedia>, DbErr> {
+    // DEV: the titles are stored the way `save_metadata_titles` normalizes them
+    MetadataTitle::find()
+        .select_only()
+        .column(metadata::Column::Identifier)
+        .column(metadata::Column::Title)
+        .column(metadata::Column::PublishYear)
+        .inner_join(Metadata)
+        .filter(metadata_title::Column::Lot.eq(lot))
+        .filter(metadata_title::Column::Title.eq(title.trim().to_lowercase()))
+        .filter(metadata::Column::Source.eq(source))
+        .into_model::<KnownMedia>()
+        .all(db)
+        .await
+}
+
+/// Whether media from `year` can be what an item from `expected` refers to.
+/// Either of them not being known does not rule it out.
+fn same_year(expected: Option<i32>, year: Option<i32>) -> bool {
+    match (expected, year) {
+        (Some(e), Some(y)) => e == y,
+        _ => true,
+    }
+}
+
+/// How items without an identifier are matched by their title.
+#[derive(Debug, Default)]
+pub struct TitleMatching {
+    /// Candidates with a confidence below this are treated as unmatched
+    pub min_confidence: Option<Decimal>,
+    strip_patterns: Vec<Regex>,
+    /// The database to look up the media that is already known in
+    known_media: Option<DatabaseConnection>,
+    /// The media that items of this import were matched to by their title, so
+    /// that an item listed more than once is only searched for once
+    matched_titles: Mutex<HashMap<(MetadataLot, MetadataSource, String), MediaSearchItem>>,
+    /// Matches with a lower level than this have to be confirmed by the user
+    confirm_below: Option<ImportMatchLevel>,
+}
+
+impl TitleMatching {
+    pub fn new(min_confidence: Option<Decimal>, strip_patterns: &[String]) -> Result<Self> {
+        let strip_patterns = strip_patterns
+            .iter()
+            .map(|p| {
+                Regex::new(p).map_err(|e| Error::new(format!("Invalid title pattern {p:?}: {e}")))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            min_confidence,
+            strip_patterns,
+            known_media: None,
+            matched_titles: Mutex::new(HashMap::new()),
+            confirm_below: None,
+        })
+    }
+
+    /// Hold back the matches with a lower level than this for the user to
+    /// confirm.
+    pub fn confirming_below(mut self, level: Option<ImportMatchLevel>) -> Self {
+        self.confirm_below = level;
+        self
+    }
+
+    /// The match to list in the report instead of importing the item, if it
+    /// has to be confirmed.
+    pub fn pending_match(
         &self,
-        user_id: i32,
-        mut input: DeployImportJobInput,
-    ) -> Result<String> {
-        let mut storage = self.import_media.clone();
-        if let Some(s) = input.media_tracker.as_mut() {
-            s.api_url = s.api_url.trim_end_matches('/').to_owned()
+        resolution: &TitleResolution,
+        lot: MetadataLot,
+        source: MetadataSource,
+        title: &str,
+        year: Option<i32>,
+        line: Option<u64>,
+    ) -> Option<ImportPendingMatch> {
+        let level = resolution.level(year);
+        if self.confirm_below.map_or(true, |min| level >= min) {
+            return None;
         }
-        let job = storage.push(ImportMedia { user_id, input }).await.unwrap();
-        Ok(job.to_string())
+        Some(ImportPendingMatch {
+            id: nanoid!(),
+            lot,
+            source,
+            title: title.to_owned(),
+            line,
+            level,
+            confidence: resolution.confidence,
+            chosen: resolution.chosen.clone(),
+            alternatives: resolution.alternatives.clone(),
+            item: None,
+        })
     }
 
-    pub async fn invalidate_import_jobs(&self) -> Result<()> {
-        let all_jobs = MediaImportReport::find()
-            .filter(media_import_report::Column::Success.is_null())
-            .all(&self.db)
-            .await?;
-        for job in all_jobs {
-            if Utc::now() - job.started_on > Duration::hours(24) {
-                tracing::trace!("Invalidating job with id = {id}", id = job.id);
-                let mut job: media_import_report::ActiveModel = job.into();
-                job.success = ActiveValue::Set(Some(false));
-                job.save(&self.db).await?;
-            }
+    /// Match items to the media that is already in the database by their
+    /// title, before the provider is searched.
+    pub fn with_known_media(mut self, db: DatabaseConnection) -> Self {
+        self.known_media = Some(db);
+        self
+    }
+
+    /// The media that is known by this title, if there is exactly one. Media
+    /// from a different year than the item is not considered.
+    async fn known_media(
+        &self,
+        lot: MetadataLot,
+        source: MetadataSource,
+        title: &str,
+        year: Option<i32>,
+    ) -> anyhow::Result<Option<MediaSearchItem>> {
+        let matched = self
+            .matched_titles
+            .lock()
+            .unwrap()
+            .get(&(lot, source, self.title_key(title)))
+            .cloned();
+        if let Some(m) = matched.filter(|m| same_year(year, m.publish_year)) {
+            return Ok(Some(m));
         }
-        Ok(())
+        let Some(db) = &self.known_media else {
+            return Ok(None);
+        };
+        let known = known_media_by_title(db, lot, source, title)
+            .await?
+            .into_iter()
+            .filter(|m| same_year(year, m.publish_year))
+            .unique_by(|m| m.identifier.clone())
+            .collect_vec();
+        // DEV: a title shared by different media can not tell them apart
+        Ok(match known.as_slice() {
+            [m] => Some(MediaSearchItem {
+                identifier: m.identifier.clone(),
+                lot,
+                title: m.title.clone(),
+                image: None,
+                publish_year: m.publish_year,
+                popularity: None,
+            }),
+            _ => None,
+        })
     }
 
-    pub async fn media_import_reports(
+    /// Remember the media an item was matched to, for the items with the same
+    /// title that come after it.
+    fn remember_match(
         &self,
-        user_id: i32,
-    ) -> Result<Vec<media_import_report::Model>> {
-        self.media_service.media_import_reports(user_id).await
+        lot: MetadataLot,
+        source: MetadataSource,
+        title: &str,
+        item: &MediaSearchItem,
+    ) {
+        self.matched_titles
+            .lock()
+            .unwrap()
+            .insert((lot, source, self.title_key(title)), item.clone());
     }
 
-    pub async fn import_from_source(
+    /// The match to the media that is known by this title. Media that is only
+    /// known by it as an alternative title is a less sure match, and is not
+    /// used if it is below the minimum confidence.
+    async fn known_resolution(
         &self,
-        user_id: i32,
-        input: DeployImportJobInput,
-    ) -> Result<()> {
-        let db_import_job = self
-            .media_service
-            .start_import_job(user_id, input.source)
-            .await?;
-        let mut import = match input.source {
-            MediaImportSource::MediaTracker => {
-                media_tracker::import(input.media_tracker.unwrap()).await?
-            }
-            MediaImportSource::MediaJson => media_json::import(input.media_json.unwrap()).await?,
-            MediaImportSource::Goodreads => goodreads::import(input.goodreads.unwrap()).await?,
-            MediaImportSource::Trakt => trakt::import(input.trakt.unwrap()).await?,
-            MediaImportSource::Movary => movary::import(input.movary.unwrap()).await?,
-            MediaImportSource::StoryGraph => {
-                story_graph::import(
-                    input.story_graph.unwrap(),
-                    &self.media_service.openlibrary_service,
-                )
-                .await?
-            }
+        lot: MetadataLot,
+        source: MetadataSource,
+        title: &str,
+        year: Option<i32>,
+    ) -> anyhow::Result<Option<TitleResolution>> {
+        let Some(known) = self.known_media(lot, source, title, year).await? else {
+            return Ok(None);
         };
-        import.media = import
-            .media
+        let confidence = self.confidence(title, &known.title);
+        if self.min_confidence.map_or(false, |m| confidence < m) {
+            return Ok(None);
+        }
+        Ok(Some(TitleResolution {
+            chosen: known,
+            confidence,
+            alternatives: vec![],
+        }))
+    }
+
+    fn title_key(&self, title: &str) -> String {
+        normalize_title(&self.normalize(title))
             .into_iter()
-            .sorted_unstable_by_key(|m| {
-                m.seen_history.len() + m.reviews.len() + m.collections.len()
-            })
-            .rev()
-            .collect_vec();
-        for col_details in import.collections.into_iter() {
-            self.media_service
-                .create_or_update_collection(&user_id, col_details)
-                .await?;
+            .collect()
+    }
+
+    /// Remove the decorations from a title. The original is kept if nothing
+    /// would be left of it.
+    pub fn normalize(&self, title: &str) -> String {
+        let mut normalized = title.to_owned();
+        for pattern in self.strip_patterns.iter() {
+            normalized = pattern.replace_all(&normalized, "").into_owned();
         }
-        for (idx, item) in import.media.iter().enumerate() {
-            tracing::debug!(
-                "Importing media with identifier = {iden}",
-                iden = item.source_id
-            );
-            let data = match &item.identifier {
-                ImportOrExportItemIdentifier::NeedsDetails(i) => {
-                    self.media_service
-                        .commit_media(item.lot, item.source, i)
-                        .await
-                }
-                ImportOrExportItemIdentifier::AlreadyFilled(a) => {
-                    self.media_service.commit_media_internal(*a.clone()).await
+        match normalized.trim() {
+            "" => title.trim().to_owned(),
+            n => n.to_owned(),
+        }
+    }
+
+    pub fn confidence(&self, expected: &str, candidate: &str) -> Decimal {
+        title_confidence(&self.normalize(expected), &self.normalize(candidate))
+    }
+}
+
+/// Lowercase a title and keep only its words so that punctuation and spacing
+/// do not affect matching.
+fn normalize_title(title: &str) -> Vec<char> {
+    title
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .join(" ")
+        .chars()
+        .collect()
+}
+
+/// The similarity of two titles based on their normalized Levenshtein
+/// distance, between 0 (nothing in common) and 1 (identical).
+fn title_confidence(expected: &str, candidate: &str) -> Decimal {
+    let a = normalize_title(expected);
+    let b = normalize_title(candidate);
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return Decimal::ONE;
+    }
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    let distance = previous[b.len()];
+    (Decimal::from(longest - distance) / Decimal::from(longest)).round_dp(2)
+}
+
+/// Add a rating from another rating system of the source to the text of a
+/// review, on a line of its own so that it can be told apart from the text.
+pub fn review_text_with_secondary_rating(text: Option<String>, name: &str, rating: &str) -> String {
+    let line = format!("Secondary rating ({name}): {rating}");
+    match text.filter(|t| !t.trim().is_empty()) {
+        Some(text) => format!("{text}\n\n{line}"),
+        None => line,
+    }
+}
+
+/// Read the whole body of a downloaded `file`, failing as soon as it is larger
+/// than `limit` so that large files are not kept in memory.
+async fn read_limited(body: impl AsyncRead + Unpin, limit: usize, file: &str) -> Result<Vec<u8>> {
+    let mut data = vec![];
+    body.take(limit as u64 + 1)
+        .read_to_end(&mut data)
+        .await
+        .map_err(|e| Error::new(format!("Could not download the {file}: {e}")))?;
+    if data.len() > limit {
+        return Err(Error::new(format!(
+            "The {file} is more than the maximum of {limit} bytes"
+        )));
+    }
+    Ok(data)
+}
+
+/// Whether a review rates the media as a whole instead of an episode.
+fn is_media_rating(review: &ImportOrExportItemRating) -> bool {
+    review.rating.is_some()
+        && review.show_season_number.is_none()
+        && review.show_episode_number.is_none()
+        && review.podcast_episode_number.is_none()
+}
+
+/// Bring the ratings that a buggy source scaled out of the valid range back
+/// within it. Returns the original values of the ones that were changed.
+fn clamp_ratings(reviews: &mut [ImportOrExportItemRating]) -> Vec<Decimal> {
+    let mut clamped = vec![];
+    for rating in reviews.iter_mut().filter_map(|r| r.rating.as_mut()) {
+        let valid = (*rating).clamp(Decimal::ZERO, MAX_RATING);
+        if valid != *rating {
+            clamped.push(*rating);
+            *rating = valid;
+        }
+    }
+    clamped
+}
+
+fn rating_date(review: &ImportOrExportItemRating) -> Option<DateTime<Utc>> {
+    review.review.as_ref().and_then(|r| r.date)
+}
+
+/// Date each dated rating with the watch it was given for, which is the latest
+/// one that ended on or before it and was not already rated.
+fn tie_ratings_to_seen(
+    reviews: &mut [ImportOrExportItemRating],
+    history: &[ImportOrExportItemSeen],
+) {
+    let mut watches = history
+        .iter()
+        .filter(|s| s.show_episode_number.is_none() && s.podcast_episode_number.is_none())
+        .filter_map(|s| s.ended_on)
+        .sorted()
+        .collect_vec();
+    let mut rated = reviews
+        .iter_mut()
+        .filter(|r| is_media_rating(r) && rating_date(r).is_some())
+        .collect_vec();
+    rated.sort_by_key(|r| rating_date(r));
+    for review in rated.into_iter().rev() {
+        let date = rating_date(review).unwrap();
+        if let Some(pos) = watches.iter().rposition(|w| *w <= date) {
+            review.review.as_mut().unwrap().date = Some(watches.remove(pos));
+        }
+    }
+}
+
+/// Remove all but the latest rating of the media as a whole. Undated ratings
+/// are considered older than dated ones.
+fn keep_latest_rating(reviews: Vec<ImportOrExportItemRating>) -> Vec<ImportOrExportItemRating> {
+    let latest = reviews
+        .iter()
+        .enumerate()
+        .filter(|(_, r)| is_media_rating(r))
+        .max_by_key(|(idx, r)| (rating_date(r), *idx))
+        .map(|(idx, _)| idx);
+    reviews
+        .into_iter()
+        .enumerate()
+        .filter(|(idx, r)| !is_media_rating(r) || Some(*idx) == latest)
+        .map(|(_, r)| r)
+        .collect()
+}
+
+/// Order the seen history of an item chronologically and remove exact
+/// duplicates. Entries without a date can not be told apart from rewatches,
+/// so they are all kept. Only the episodes present in the source are kept,
+/// any gaps between them are never filled in.
+fn normalize_seen_history(history: Vec<ImportOrExportItemSeen>) -> Vec<ImportOrExportItemSeen> {
+    let mut dated = HashSet::new();
+    history
+        .into_iter()
+        .filter(|s| {
+            s.ended_on.is_none()
+                || dated.insert((
+                    s.show_season_number,
+                    s.show_episode_number,
+                    s.podcast_episode_number,
+                    s.ended_on,
+                ))
+        })
+        .sorted_by_key(|s| {
+            (
+                s.ended_on,
+                s.show_season_number,
+                s.show_episode_number,
+                s.podcast_episode_number,
+            )
+        })
+        .collect()
+}
+
+/// Separate the seen history entries that have been started from the ones
+/// with zero progress. Returns the started entries and whether the item has
+/// only been planned.
+fn partition_seen_history(
+    history: &[ImportOrExportItemSeen],
+) -> (Vec<&ImportOrExportItemSeen>, bool) {
+    let (planned, started): (Vec<_>, Vec<_>) = history.iter().partition(|s| s.progress == Some(0));
+    let only_planned = started.is_empty() && !planned.is_empty();
+    (started, only_planned)
+}
+
+/// How far along media in a state is. Dropped and held media was started but
+/// not finished.
+fn state_progress(state: MediaState) -> u8 {
+    match state {
+        MediaState::PlanTo => 0,
+        MediaState::InProgress | MediaState::Dropped | MediaState::OnHold | MediaState::OnAHold => {
+            1
+        }
+        MediaState::Completed => 2,
+    }
+}
+
+/// Move the seen items and reviews created by an import to the trash, and take
+/// the media that is no longer in progress out of the "In Progress" collection.
+/// Returns the media whose seen items were deleted.
+async fn trash_import_results<C: ConnectionTrait>(
+    db: &C,
+    user_id: i32,
+    report_id: i32,
+) -> Result<(DeletedImportResults, Vec<i32>)> {
+    let deleted_seen: Vec<(i32, i32)> = Seen::find()
+        .select_only()
+        .column(seen::Column::MetadataId)
+        .column(seen::Column::Progress)
+        .filter(seen::Column::UserId.eq(user_id))
+        .filter(seen::Column::ImportReportId.eq(report_id))
+        .filter(seen::Column::DeletedAt.is_null())
+        .into_tuple()
+        .all(db)
+        .await?;
+    // DEV: these go to the trash like the ones deleted by hand, so that they
+    // can be restored if the import was undone by mistake
+    let deleted_at = Utc::now();
+    let seen = Seen::update_many()
+        .col_expr(seen::Column::DeletedAt, Expr::value(deleted_at))
+        .filter(seen::Column::UserId.eq(user_id))
+        .filter(seen::Column::ImportReportId.eq(report_id))
+        .filter(seen::Column::DeletedAt.is_null())
+        .exec(db)
+        .await?
+        .rows_affected;
+    let reviews = Review::update_many()
+        .col_expr(review::Column::DeletedAt, Expr::value(deleted_at))
+        .filter(review::Column::UserId.eq(user_id))
+        .filter(review::Column::ImportReportId.eq(report_id))
+        .filter(review::Column::DeletedAt.is_null())
+        .exec(db)
+        .await?
+        .rows_affected;
+    let started = deleted_seen
+        .iter()
+        .filter(|(_, progress)| *progress < 100)
+        .map(|(metadata_id, _)| *metadata_id)
+        .unique()
+        .collect_vec();
+    // DEV: media that is still in progress through other seen items, for eg
+    // ones added by hand, stays in the collection
+    let still_started: Vec<i32> = Seen::find()
+        .select_only()
+        .column(seen::Column::MetadataId)
+        .filter(seen::Column::UserId.eq(user_id))
+        .filter(seen::Column::MetadataId.is_in(started.clone()))
+        .filter(seen::Column::State.eq(SeenState::InProgress))
+        .filter(seen::Column::DeletedAt.is_null())
+        .into_tuple()
+        .all(db)
+        .await?;
+    let stopped = started
+        .into_iter()
+        .filter(|id| !still_started.contains(id))
+        .collect_vec();
+    let in_progress = Collection::find()
+        .filter(collection::Column::UserId.eq(user_id))
+        .filter(collection::Column::Name.eq(DefaultCollection::InProgress.to_string()))
+        .one(db)
+        .await?;
+    if let (Some(collection), false) = (in_progress, stopped.is_empty()) {
+        MetadataToCollection::delete_many()
+            .filter(metadata_to_collection::Column::CollectionId.eq(collection.id))
+            .filter(metadata_to_collection::Column::MetadataId.is_in(stopped))
+            .exec(db)
+            .await?;
+    }
+    let metadata_ids = deleted_seen
+        .into_iter()
+        .map(|(metadata_id, _)| metadata_id)
+        .unique()
+        .collect();
+    Ok((DeletedImportResults { seen, reviews }, metadata_ids))
+}
+
+/// The state that the seen history of the user puts media in. Seen episodes do
+/// not complete a show or podcast, so only the items seen as a whole count.
+fn seen_history_state(history: &[seen::Model]) -> Option<MediaState> {
+    let whole = history
+        .iter()
+        .filter(|s| s.extra_information.is_none())
+        .map(|s| s.state)
+        .collect_vec();
+    if whole.contains(&SeenState::Completed) {
+        Some(MediaState::Completed)
+    } else if whole.contains(&SeenState::InProgress) {
+        Some(MediaState::InProgress)
+    } else {
+        None
+    }
+}
+
+/// Whether moving media from its existing state to the new one goes back.
+fn state_regresses(existing: Option<MediaState>, new: MediaState) -> bool {
+    existing.map_or(false, |e| state_progress(new) < state_progress(e))
+}
+
+/// Stops fetching from a provider once it has failed for a number of items in a
+/// row, so that an outage does not make every remaining item wait for its
+/// timeout.
+#[derive(Debug, Default)]
+struct ProviderBreaker {
+    threshold: usize,
+    failures: HashMap<(MetadataLot, MetadataSource), usize>,
+    unavailable: Vec<ImportUnavailableProvider>,
+}
+
+impl ProviderBreaker {
+    fn new(threshold: usize, unavailable: Vec<ImportUnavailableProvider>) -> Self {
+        Self {
+            threshold,
+            unavailable,
+            ..Default::default()
+        }
+    }
+
+    /// Whether the items of the provider are being skipped, in which case this
+    /// one is counted as skipped too.
+    fn skip(&mut self, lot: MetadataLot, source: MetadataSource) -> bool {
+        match self
+            .unavailable
+            .iter_mut()
+            .find(|p| p.lot == lot && p.source == source)
+        {
+            Some(provider) => {
+                provider.skipped += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn record(&mut self, lot: MetadataLot, source: MetadataSource, success: bool) {
+        if success {
+            self.failures.remove(&(lot, source));
+            return;
+        }
+        let failures = self.failures.entry((lot, source)).or_default();
+        *failures += 1;
+        if self.threshold > 0 && *failures >= self.threshold {
+            tracing::warn!(
+                "Skipping the remaining {lot} items from {source:?} after {failures} failures in a row"
+            );
+            self.failures.remove(&(lot, source));
+            self.unavailable.push(ImportUnavailableProvider {
+                lot,
+                source,
+                skipped: 0,
+            });
+        }
+    }
+}
+
+/// The number of recent items whose timings are averaged to estimate how long
+/// an import will take
+const IMPORT_TIMING_WINDOW: usize = 50;
+/// The estimate of an import is not reliable until this many items are timed
+const IMPORT_ESTIMATE_MIN_ITEMS: usize = 20;
+/// How often the progress of a running import is saved to its report, in
+/// seconds
+const IMPORT_PROGRESS_SAVE_INTERVAL: u64 = 30;
+
+/// How far a running import has got
+#[derive(Debug, SimpleObject, Serialize, Deserialize, Eq, PartialEq, Clone)]
+pub struct ImportProgress {
+    pub processed: usize,
+    pub total: usize,
+    pub estimate: Option<JobEstimate>,
+    pub saved_on: DateTime<Utc>,
+}
+
+/// The average time the items of an import took, in milliseconds
+#[derive(Debug, SimpleObject, Serialize, Deserialize, Eq, PartialEq, Clone, Default)]
+pub struct ImportThroughput {
+    /// The number of items that were timed
+    pub items: usize,
+    /// The time taken to find the media of an item
+    pub resolution_ms: u64,
+    /// The time taken to save the seen history, reviews and collections of
+    /// an item
+    pub write_ms: u64,
+}
+
+/// Keeps a rolling average of the time taken by the items of an import,
+/// separately for resolving their media and writing their data.
+#[derive(Debug, Default)]
+struct ImportTimer {
+    /// When the current item was started and resolved
+    current: Option<(Instant, Option<Instant>)>,
+    resolution: VecDeque<std::time::Duration>,
+    write: VecDeque<std::time::Duration>,
+    items: usize,
+}
+
+impl ImportTimer {
+    /// Start timing the next item, which finishes the previous one.
+    fn start_item(&mut self) {
+        self.finish_item();
+        self.current = Some((Instant::now(), None));
+    }
+
+    fn resolved(&mut self) {
+        if let Some((_, resolved)) = self.current.as_mut() {
+            *resolved = Some(Instant::now());
+        }
+    }
+
+    fn finish_item(&mut self) {
+        if let Some((started, resolved)) = self.current.take() {
+            let now = Instant::now();
+            let resolved = resolved.unwrap_or(now);
+            self.record(resolved - started, now - resolved);
+        }
+    }
+
+    /// Items that failed before being resolved are recorded without any write
+    /// time, so that the average is per item read from the source.
+    fn record(&mut self, resolution: std::time::Duration, write: std::time::Duration) {
+        for (window, time) in [(&mut self.resolution, resolution), (&mut self.write, write)] {
+            if window.len() == IMPORT_TIMING_WINDOW {
+                window.pop_front();
+            }
+            window.push_back(time);
+        }
+        self.items += 1;
+    }
+
+    fn average(window: &VecDeque<std::time::Duration>) -> std::time::Duration {
+        match window.len() {
+            0 => std::time::Duration::ZERO,
+            len => window.iter().sum::<std::time::Duration>() / len as u32,
+        }
+    }
+
+    /// When the remaining items are expected to be imported, once an item has
+    /// been timed.
+    fn estimate(&self, remaining: usize, now: DateTime<Utc>) -> Option<JobEstimate> {
+        if self.items == 0 {
+            return None;
+        }
+        let per_item = Self::average(&self.resolution) + Self::average(&self.write);
+        let left = Duration::from_std(per_item.checked_mul(remaining as u32)?).ok()?;
+        Some(JobEstimate {
+            finish_on: now + left,
+            low_confidence: self.items < IMPORT_ESTIMATE_MIN_ITEMS,
+        })
+    }
+
+    fn throughput(&self) -> ImportThroughput {
+        ImportThroughput {
+            items: self.items,
+            resolution_ms: Self::average(&self.resolution).as_millis() as u64,
+            write_ms: Self::average(&self.write).as_millis() as u64,
+        }
+    }
+}
+
+/// The order in which the items of an import are processed. Unless the order of
+/// the source is preserved, the items with the most activity come first so that
+/// problems with them show up early.
+fn order_import_items(
+    media: Vec<ImportOrExportItem<ImportOrExportItemIdentifier>>,
+    preserve_source_order: bool,
+) -> Vec<ImportOrExportItem<ImportOrExportItemIdentifier>> {
+    if preserve_source_order {
+        return media;
+    }
+    media
+        .into_iter()
+        .sorted_by_key(|m| m.seen_history.len() + m.reviews.len() + m.collections.len())
+        .rev()
+        .collect()
+}
+
+/// The items of an import, along with how they are accounted for.
+type FetchedImport = (ImportResult, ImportReconciliation, Vec<ImportLotSummary>);
+
+/// An interrupted import continues with the items it was fetched with instead
+/// of fetching its source again. The rest of its results are restored from its
+/// report.
+async fn resumed_or_fetched(
+    resume_items: Option<Vec<ImportOrExportItem<ImportOrExportItemIdentifier>>>,
+    fetch: impl Future<Output = Result<FetchedImport>>,
+) -> Result<FetchedImport> {
+    match resume_items {
+        Some(media) => Ok((
+            ImportResult {
+                media,
+                ..Default::default()
+            },
+            ImportReconciliation::default(),
+            vec![],
+        )),
+        None => fetch.await,
+    }
+}
+
+/// Merge items that refer to the same media into the first of them. Returns the
+/// remaining items and the number of items that were merged away.
+fn merge_duplicate_items(
+    media: Vec<ImportOrExportItem<ImportOrExportItemIdentifier>>,
+) -> (Vec<ImportOrExportItem<ImportOrExportItemIdentifier>>, usize) {
+    let mut merged: Vec<ImportOrExportItem<ImportOrExportItemIdentifier>> = vec![];
+    let mut skipped = 0;
+    for item in media {
+        let existing = match &item.identifier {
+            ImportOrExportItemIdentifier::NeedsDetails(identifier) => merged.iter_mut().find(|m| {
+                m.lot == item.lot
+                    && m.source == item.source
+                    && matches!(
+                        &m.identifier,
+                        ImportOrExportItemIdentifier::NeedsDetails(i) if i == identifier
+                    )
+            }),
+            ImportOrExportItemIdentifier::AlreadyFilled(_) => None,
+        };
+        match existing {
+            Some(m) => {
+                m.seen_history.extend(item.seen_history);
+                m.reviews.extend(item.reviews);
+                for col in item.collections {
+                    if !m.collections.contains(&col) {
+                        m.collections.push(col);
+                    }
+                }
+                skipped += 1;
+            }
+            None => merged.push(item),
+        }
+    }
+    (merged, skipped)
+}
+
+/// The collection that items which could not be matched are added to
+pub const NEEDS_REVIEW_COLLECTION: &str = "Needs Review";
+
+/// A custom media item standing in for an item that could not be matched, so
+/// that it can be resolved by hand later. It keeps the seen history, reviews
+/// and collections the item had in the source.
+fn unmatched_stub(
+    item: &ImportFailedItem,
+    source: MediaImportSource,
+) -> ImportOrExportItem<ImportOrExportItemIdentifier> {
+    let specifics = match item.lot {
+        MetadataLot::AudioBook => MediaSpecifics::AudioBook(Default::default()),
+        MetadataLot::Book => MediaSpecifics::Book(Default::default()),
+        MetadataLot::Movie => MediaSpecifics::Movie(Default::default()),
+        MetadataLot::Podcast => MediaSpecifics::Podcast(Default::default()),
+        MetadataLot::Show => MediaSpecifics::Show(Default::default()),
+        MetadataLot::VideoGame => MediaSpecifics::VideoGame(Default::default()),
+        MetadataLot::Anime => MediaSpecifics::Anime(Default::default()),
+        MetadataLot::Manga => MediaSpecifics::Manga(Default::default()),
+    };
+    // DEV: the identifier only depends on the item, so that importing the same
+    // file again reuses the stub. The year tells apart remakes with the same
+    // title.
+    let mut identifier = format!(
+        "unmatched-{:?}-{}",
+        item.lot,
+        item.identifier.trim().to_lowercase()
+    );
+    if let Some(year) = item.year {
+        identifier.push_str(&format!("-{year}"));
+    }
+    let stored = stored_item(&item.item);
+    let mut collections = stored
+        .as_ref()
+        .map(|s| s.collections.clone())
+        .unwrap_or_default();
+    if !collections.iter().any(|c| c == NEEDS_REVIEW_COLLECTION) {
+        collections.push(NEEDS_REVIEW_COLLECTION.to_owned());
+    }
+    ImportOrExportItem {
+        source_id: item.identifier.clone(),
+        title: Some(item.identifier.clone()),
+        lot: item.lot,
+        source: MetadataSource::Custom,
+        identifier: ImportOrExportItemIdentifier::AlreadyFilled(Box::new(MediaDetails {
+            identifier,
+            title: item.identifier.clone(),
+            description: Some(format!(
+                "Imported from {source:?}, but no match was found: {}",
+                item.error.as_deref().unwrap_or_default()
+            )),
+            lot: item.lot,
+            source: MetadataSource::Custom,
+            creators: vec![],
+            genres: vec![],
+            images: vec![],
+            publish_year: item.year,
+            publish_date: None,
+            specifics,
+            groups: vec![],
+            alternative_titles: vec![],
+        })),
+        seen_history: stored
+            .as_ref()
+            .map(|s| s.seen_history.clone())
+            .unwrap_or_default(),
+        reviews: stored
+            .as_ref()
+            .map(|s| s.reviews.clone())
+            .unwrap_or_default(),
+        collections,
+        state: stored.and_then(|s| s.state),
+    }
+}
+
+/// Whether a collection from the source should be created. Ryot's default
+/// collections are always allowed.
+fn is_collection_allowed(name: &str, allowlist: &Option<Vec<String>>) -> bool {
+    match allowlist {
+        None => true,
+        Some(allowed) => {
+            name == NEEDS_REVIEW_COLLECTION
+                || DefaultCollection::iter().any(|d| d.to_string() == name)
+                || allowed.iter().any(|a| a.eq_ignore_ascii_case(name))
+        }
+    }
+}
+
+/// Apply the options of an import to an item as it was read from the source.
+fn prepare_item(
+    mut item: ImportOrExportItem<ImportOrExportItemIdentifier>,
+    options: &ImportOptions,
+    warnings: &mut Vec<ImportWarningItem>,
+) -> ImportOrExportItem<ImportOrExportItemIdentifier> {
+    for rating in clamp_ratings(&mut item.reviews) {
+        warnings.push(ImportWarningItem {
+            lot: item.lot,
+            identifier: item.source_id.clone(),
+            warning: format!(
+                "Rating {rating} is outside of the valid range and was changed to {}",
+                rating.clamp(Decimal::ZERO, MAX_RATING)
+            ),
+            alternatives: vec![],
+        });
+    }
+    item.seen_history = normalize_seen_history(item.seen_history);
+    tie_ratings_to_seen(&mut item.reviews, &item.seen_history);
+    if options.latest_rating_only {
+        item.reviews = keep_latest_rating(item.reviews);
+    }
+    item.collections = map_collections(item.collections, options);
+    item
+}
+
+/// Drop the collections that are not allowed and add items from the source's
+/// watchlist to the user's chosen collection.
+fn map_collections(collections: Vec<String>, options: &ImportOptions) -> Vec<String> {
+    collections
+        .into_iter()
+        .filter(|c| is_collection_allowed(c, &options.tag_collection_allowlist))
+        .map(|c| {
+            if c == DefaultCollection::Watchlist.to_string() {
+                options.collection_name(&options.watchlist_collection)
+            } else {
+                options.collection_name(&c)
+            }
+        })
+        .unique()
+        .collect()
+}
+
+/// Group the items of two reports by lot. Items are matched on their provider
+/// and identifier, so reports of different sources can be compared.
+fn compare_succeeded_items(
+    old: &[ImportSucceededItem],
+    new: &[ImportSucceededItem],
+) -> Vec<ImportReportLotComparison> {
+    MetadataLot::iter()
+        .filter_map(|lot| {
+            let of_lot = |items: &[ImportSucceededItem]| {
+                items
+                    .iter()
+                    .filter(|i| i.lot == lot)
+                    .unique_by(|i| (i.source, i.identifier.clone()))
+                    .cloned()
+                    .collect_vec()
+            };
+            let (old, new) = (of_lot(old), of_lot(new));
+            if old.is_empty() && new.is_empty() {
+                return None;
+            }
+            let old_keys: HashSet<_> = old
+                .iter()
+                .map(|i| (i.source, i.identifier.clone()))
+                .collect();
+            let new_keys: HashSet<_> = new
+                .iter()
+                .map(|i| (i.source, i.identifier.clone()))
+                .collect();
+            let (in_both, only_in_new) = new
+                .into_iter()
+                .partition(|i| old_keys.contains(&(i.source, i.identifier.clone())));
+            let only_in_old = old
+                .into_iter()
+                .filter(|i| !new_keys.contains(&(i.source, i.identifier.clone())))
+                .collect();
+            Some(ImportReportLotComparison {
+                lot,
+                only_in_new,
+                only_in_old,
+                in_both,
+            })
+        })
+        .collect()
+}
+
+/// The number of the podcast episode with this GUID in the feed. Some providers
+/// use the GUID as the id of the episode.
+fn podcast_episode_number_by_guid(episodes: &[PodcastEpisode], guid: &str) -> Option<i32> {
+    episodes
+        .iter()
+        .find(|e| e.guid.as_deref() == Some(guid) || e.id == guid)
+        .map(|e| e.number)
+}
+
+/// The progress to record for a seen item. Items without a progress are
+/// considered completed, unless they have no end date and the user asked for
+/// those to be left in progress.
+fn seen_progress(seen: &ImportOrExportItemSeen, undated_in_progress: bool) -> i32 {
+    match seen.progress {
+        Some(p) => p,
+        None if undated_in_progress && seen.ended_on.is_none() => 0,
+        None => 100,
+    }
+}
+
+/// Deserialize all the rows of a CSV file along with the line each of them
+/// starts at, so that failures can point to the offending row.
+pub fn csv_records<T: DeserializeOwned>(content: &str) -> Vec<(u64, csv::Result<T>)> {
+    // DEV: files saved by Excel start with a byte order mark, which would
+    // otherwise become part of the first header
+    let content = content.trim_start_matches('\u{feff}');
+    let mut reader = csv::Reader::from_reader(content.as_bytes());
+    let headers = reader.headers().cloned().unwrap_or_default();
+    reader
+        .records()
+        .map(|r| match r {
+            Ok(r) => (
+                r.position().map_or(0, |p| p.line()),
+                r.deserialize(Some(&headers)),
+            ),
+            Err(e) => (e.position().map_or(0, |p| p.line()), Err(e)),
+        })
+        .collect()
+}
+
+/// Resolve an item by searching for its title using the provider. The author
+/// is included in the query to tell apart items with the same title, and the
+/// year keeps known media from another year from being used. Fails if the best
+/// candidate's title is not close enough to the expected one.
+pub async fn resolve_by_title(
+    provider: &(impl MediaProvider + Sync + ?Sized),
+    lot: MetadataLot,
+    source: MetadataSource,
+    title: &str,
+    author: Option<&str>,
+    year: Option<i32>,
+    matching: &TitleMatching,
+) -> anyhow::Result<Option<TitleResolution>> {
+    let title = matching.normalize(title);
+    if let Some(resolution) = matching.known_resolution(lot, source, &title, year).await? {
+        return Ok(Some(resolution));
+    }
+    let query = match author {
+        Some(a) => format!("{} {}", title, a),
+        None => title.clone(),
+    };
+    let candidates = provider.search(&query, None).await?.items;
+    let mut candidates = sort_media_candidates(candidates, lot, &title);
+    if candidates.is_empty() {
+        return Ok(None);
+    }
+    let chosen = candidates.remove(0);
+    let confidence = matching.confidence(&title, &chosen.title);
+    if let Some(min) = matching.min_confidence.filter(|m| confidence < *m) {
+        anyhow::bail!(
+            "Best match {:?} has confidence {} which is below {}",
+            chosen.title,
+            confidence,
+            min
+        );
+    }
+    matching.remember_match(lot, source, &title, &chosen);
+    candidates.truncate(MAX_TITLE_ALTERNATIVES);
+    Ok(Some(TitleResolution {
+        chosen,
+        confidence,
+        alternatives: candidates,
+    }))
+}
+
+#[derive(Debug, Default)]
+pub struct ImportResult {
+    collections: Vec<CreateOrUpdateCollectionInput>,
+    media: Vec<ImportOrExportItem<ImportOrExportItemIdentifier>>,
+    failed_items: Vec<ImportFailedItem>,
+    warnings: Vec<ImportWarningItem>,
+    /// The items that were matched by their title and have to be confirmed
+    pending_matches: Vec<ImportPendingMatch>,
+    /// The number of items that were skipped because Ryot does not support
+    /// their type
+    unsupported: usize,
+}
+
+impl ImportResult {
+    /// The identifier an item was resolved to by its title. An item that could
+    /// not be matched or whose match has to be confirmed is recorded along
+    /// with its failure or pending match instead, so that it can be imported
+    /// later.
+    fn resolved_identifier(
+        &mut self,
+        resolved: std::result::Result<ResolvedIdentifier, String>,
+        item: &ImportOrExportItem<ImportOrExportItemIdentifier>,
+        year: Option<i32>,
+        line: u64,
+    ) -> Option<String> {
+        match resolved {
+            Ok(ResolvedIdentifier::Found(identifier, warning)) => {
+                self.warnings.extend(warning);
+                Some(identifier)
+            }
+            Ok(ResolvedIdentifier::Pending(p)) => {
+                self.pending_matches.push(p.with_item(item));
+                None
+            }
+            Err(error) => {
+                let mut failed = ImportFailedItem::new(
+                    item.lot,
+                    ImportFailStep::TitleMatch,
+                    item.source_id.clone(),
+                    Some(error),
+                )
+                .at_line(line)
+                .with_item(item);
+                failed.year = year;
+                self.failed_items.push(failed);
+                None
+            }
+        }
+    }
+}
+
+#[derive(
+    Debug, SimpleObject, Serialize, Deserialize, FromJsonQueryResult, Eq, PartialEq, Clone,
+)]
+pub struct ImportResultResponse {
+    pub source: MediaImportSource,
+    pub import: ImportDetails,
+    pub failed_items: Vec<ImportFailedItem>,
+    #[serde(default)]
+    pub succeeded_items: Vec<ImportSucceededItem>,
+    #[serde(default)]
+    pub warnings: Vec<ImportWarningItem>,
+    /// The items that were not imported since their match has to be confirmed
+    #[serde(default)]
+    pub pending_matches: Vec<ImportPendingMatch>,
+    /// The index of the next item to import if the import was interrupted
+    #[serde(default)]
+    pub resume_cursor: Option<usize>,
+    /// The options the import was run with
+    #[serde(default)]
+    pub options: Option<ImportOptions>,
+    #[serde(default)]
+    pub reconciliation: Option<ImportReconciliation>,
+    /// Set when the user's summary could not be recalculated after the import,
+    /// in which case their statistics might be stale
+    #[serde(default)]
+    pub summary_warning: Option<String>,
+    /// The collections that did not exist before the import
+    #[serde(default)]
+    pub created_collections: Vec<String>,
+    /// The providers that were skipped for the rest of the import
+    #[serde(default)]
+    pub unavailable_providers: Vec<ImportUnavailableProvider>,
+    /// How long the items of the import took on average
+    #[serde(default)]
+    pub throughput: Option<ImportThroughput>,
+    /// Set while the import is running, along with the results so far. It is
+    /// saved every `IMPORT_PROGRESS_SAVE_INTERVAL` seconds.
+    #[serde(default)]
+    pub progress: Option<ImportProgress>,
+    /// The outcome of the items of each lot. Unlike `import.total`, it
+    /// includes the items that failed.
+    #[serde(default)]
+    pub lots: Vec<ImportLotSummary>,
+}
+
+impl ImportResultResponse {
+    /// Combine the results of another import of the same source into this one.
+    pub fn merge(&mut self, other: Self) {
+        self.import.total += other.import.total;
+        self.import.unsupported += other.import.unsupported;
+        self.failed_items.extend(other.failed_items);
+        self.succeeded_items.extend(other.succeeded_items);
+        self.warnings.extend(other.warnings);
+        self.pending_matches.extend(other.pending_matches);
+        self.unavailable_providers
+            .extend(other.unavailable_providers);
+        self.options = other.options;
+        self.summary_warning = other.summary_warning;
+        self.throughput = other.throughput.or(self.throughput.take());
+        self.progress = other.progress;
+        for lot in other.lots.iter() {
+            ImportLotSummary::of(&mut self.lots, lot.lot).add(lot);
+        }
+        for name in other.created_collections {
+            if !self.created_collections.contains(&name) {
+                self.created_collections.push(name);
+            }
+        }
+        self.reconciliation = match (self.reconciliation.take(), other.reconciliation) {
+            (Some(a), Some(b)) => Some(a.merge(b)),
+            (a, b) => b.or(a),
+        };
+    }
+
+    /// Move a failed item to the succeeded ones once it has been imported by
+    /// hand. Returns the failed item, if there was one with the id.
+    fn resolve_failed_item(
+        &mut self,
+        id: &str,
+        item: ImportSucceededItem,
+    ) -> Option<ImportFailedItem> {
+        let index = self.failed_items.iter().position(|f| f.id == id)?;
+        let failed = self.failed_items.remove(index);
+        self.succeeded_items.push(item);
+        if let Some(r) = self.reconciliation.as_mut() {
+            r.failed = r.failed.saturating_sub(1);
+            r.imported += 1;
+        }
+        Some(failed)
+    }
+
+    /// Remove a pending match once the user has confirmed or rejected it. A
+    /// confirmed match is moved to the succeeded items and a rejected one to
+    /// the failed items, so that it can be retried with another identifier.
+    fn resolve_pending_match(
+        &mut self,
+        id: &str,
+        item: Option<ImportSucceededItem>,
+    ) -> Option<ImportPendingMatch> {
+        let index = self.pending_matches.iter().position(|p| p.id == id)?;
+        let pending = self.pending_matches.remove(index);
+        let r = self.reconciliation.as_mut();
+        match item {
+            Some(item) => {
+                self.succeeded_items.push(item);
+                if let Some(r) = r {
+                    r.pending = r.pending.saturating_sub(1);
+                    r.imported += 1;
+                }
+            }
+            None => {
+                self.failed_items.push(ImportFailedItem {
+                    id: nanoid!(),
+                    lot: pending.lot,
+                    step: ImportFailStep::TitleMatch,
+                    identifier: pending.title.clone(),
+                    error: Some(format!(
+                        "The match to {:?} ({}) was rejected",
+                        pending.chosen.title, pending.chosen.identifier
+                    )),
+                    line: pending.line,
+                    year: None,
+                    item: pending.item.clone(),
+                });
+                if let Some(r) = r {
+                    r.pending = r.pending.saturating_sub(1);
+                    r.failed += 1;
+                }
+            }
+        }
+        Some(pending)
+    }
+
+    /// Give an id to the failed items and pending matches of a report that was
+    /// saved before they had one. Returns whether any of them was missing one.
+    pub fn assign_missing_ids(&mut self) -> bool {
+        let ids = self
+            .failed_items
+            .iter_mut()
+            .map(|f| &mut f.id)
+            .chain(self.pending_matches.iter_mut().map(|p| &mut p.id))
+            .filter(|id| id.is_empty())
+            .collect_vec();
+        let assigned = !ids.is_empty();
+        for id in ids {
+            *id = nanoid!();
+        }
+        assigned
+    }
+}
+
+/// Aggregated import statistics for a single source
+#[derive(Debug, SimpleObject, Clone)]
+pub struct ImportSourceStats {
+    pub source: MediaImportSource,
+    /// The number of import jobs deployed for this source
+    pub total_imports: usize,
+    /// The number of import jobs that completed successfully
+    pub successful_imports: usize,
+    pub total_items_imported: usize,
+    pub total_items_failed: usize,
+    /// The most recent import job for this source
+    pub last_import: Option<media_import_report::Model>,
+}
+
+/// The success rate of a single finished import job
+#[derive(Debug, SimpleObject, Clone)]
+pub struct ImportSuccessRateTrendItem {
+    pub report_id: i32,
+    pub source: MediaImportSource,
+    pub started_on: DateTime<Utc>,
+    /// Percentage of items that were imported without any failures
+    pub success_rate: f64,
+}
+
+#[derive(Debug, SimpleObject, Clone)]
+pub struct MediaImportStats {
+    pub total_items_imported: usize,
+    pub total_items_failed: usize,
+    /// Percentage of all items that were imported without any failures
+    pub success_rate: f64,
+    pub sources: Vec<ImportSourceStats>,
+    /// The success rate of each finished import, ordered from oldest to newest
+    pub success_rate_trend: Vec<ImportSuccessRateTrendItem>,
+}
+
+fn get_success_rate(imported: usize, failed: usize) -> f64 {
+    let total = imported + failed;
+    if total == 0 {
+        return 0.0;
+    }
+    (imported as f64 / total as f64) * 100.0
+}
+
+/// The error code returned when an import is deployed during the cooldown of
+/// its source
+pub const IMPORT_COOLDOWN: &str = "IMPORT_COOLDOWN";
+
+/// When the next import is allowed after a successful one that finished at
+/// `finished_on`, if that is still in the future.
+fn next_import_allowed_on(
+    finished_on: Option<DateTime<Utc>>,
+    cooldown: Duration,
+    now: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+    finished_on
+        .map(|f| f + cooldown)
+        .filter(|allowed_on| *allowed_on > now)
+}
+
+fn import_cooldown_error(source: MediaImportSource, allowed_on: DateTime<Utc>) -> Error {
+    Error::new(format!(
+        "An import from {source:?} was completed recently, the next one is allowed on {}",
+        allowed_on.to_rfc3339()
+    ))
+    .extend_with(|_, e| {
+        e.set("code", IMPORT_COOLDOWN);
+        e.set("allowedOn", allowed_on.to_rfc3339());
+    })
+}
+
+#[derive(Debug, InputObject)]
+pub struct ImportAuditLogInput {
+    pub user_id: Option<i32>,
+    pub source: Option<MediaImportSource>,
+    pub page: Option<i32>,
+}
+
+#[derive(Debug, InputObject)]
+pub struct ImportAnalyticsInput {
+    pub source: Option<MediaImportSource>,
+    /// Only include the imports that started after this date
+    pub since: Option<DateTime<Utc>>,
+}
+
+/// The metrics of a finished import, before it is merged with another report
+fn import_analytics_model(
+    details: &ImportResultResponse,
+    started_on: DateTime<Utc>,
+    finished_on: DateTime<Utc>,
+    success: bool,
+    api_calls: Option<usize>,
+) -> import_analytics::ActiveModel {
+    let reconciliation = details.reconciliation.clone().unwrap_or_default();
+    import_analytics::ActiveModel {
+        source: ActiveValue::Set(details.source),
+        started_on: ActiveValue::Set(started_on),
+        finished_on: ActiveValue::Set(finished_on),
+        duration_ms: ActiveValue::Set((finished_on - started_on).num_milliseconds()),
+        success: ActiveValue::Set(success),
+        source_total: ActiveValue::Set(reconciliation.source_total as i32),
+        imported: ActiveValue::Set(reconciliation.imported as i32),
+        failed: ActiveValue::Set(reconciliation.failed as i32),
+        skipped: ActiveValue::Set(reconciliation.skipped as i32),
+        filtered: ActiveValue::Set(reconciliation.filtered as i32),
+        pending: ActiveValue::Set(reconciliation.pending as i32),
+        failures_by_step: ActiveValue::Set(ImportStepFailures::new(&details.failed_items)),
+        api_calls: ActiveValue::Set(api_calls.map(|c| c as i32)),
+        ..Default::default()
+    }
+}
+
+/// The metrics of an import that stopped with an error before it had any
+/// results.
+fn failed_import_analytics_model(
+    source: MediaImportSource,
+    started_on: DateTime<Utc>,
+    finished_on: DateTime<Utc>,
+    api_calls: Option<usize>,
+) -> import_analytics::ActiveModel {
+    import_analytics::ActiveModel {
+        source: ActiveValue::Set(source),
+        started_on: ActiveValue::Set(started_on),
+        finished_on: ActiveValue::Set(finished_on),
+        duration_ms: ActiveValue::Set((finished_on - started_on).num_milliseconds()),
+        success: ActiveValue::Set(false),
+        source_total: ActiveValue::Set(0),
+        imported: ActiveValue::Set(0),
+        failed: ActiveValue::Set(0),
+        skipped: ActiveValue::Set(0),
+        filtered: ActiveValue::Set(0),
+        pending: ActiveValue::Set(0),
+        failures_by_step: ActiveValue::Set(ImportStepFailures::default()),
+        api_calls: ActiveValue::Set(api_calls.map(|c| c as i32)),
+        ..Default::default()
+    }
+}
+
+/// An import deployed by a user, as seen by an admin
+#[derive(Debug, SimpleObject, Clone)]
+pub struct ImportAuditLogEntry {
+    pub report_id: i32,
+    pub user_id: i32,
+    pub username: String,
+    pub source: MediaImportSource,
+    pub started_on: DateTime<Utc>,
+    pub finished_on: Option<DateTime<Utc>>,
+    pub success: Option<bool>,
+    pub items_imported: Option<usize>,
+    pub items_failed: Option<usize>,
+}
+
+/// An example file showing the shape expected by a file based importer
+#[derive(Debug, SimpleObject, Clone)]
+pub struct ImportTemplate {
+    pub source: MediaImportSource,
+    /// The input field the contents of this file should be sent as
+    pub field: String,
+    pub file_name: String,
+    pub mime_type: String,
+    pub content: String,
+}
+
+fn get_import_templates() -> Vec<ImportTemplate> {
+    let template =
+        |source, field: &str, file_name: &str, mime_type: &str, content: &str| ImportTemplate {
+            source,
+            field: field.to_owned(),
+            file_name: file_name.to_owned(),
+            mime_type: mime_type.to_owned(),
+            content: content.to_owned(),
+        };
+    vec![
+        template(
+            MediaImportSource::Goodreads,
+            "csvExport",
+            "goodreads_library_export.csv",
+            "text/csv",
+            goodreads::CSV_TEMPLATE,
+        ),
+        template(
+            MediaImportSource::MediaJson,
+            "export",
+            "media.json",
+            "application/json",
+            media_json::EXPORT_TEMPLATE,
+        ),
+        template(
+            MediaImportSource::Movary,
+            "history",
+            "history.csv",
+            "text/csv",
+            movary::HISTORY_TEMPLATE,
+        ),
+        template(
+            MediaImportSource::Movary,
+            "ratings",
+            "ratings.csv",
+            "text/csv",
+            movary::RATINGS_TEMPLATE,
+        ),
+        template(
+            MediaImportSource::Movary,
+            "watchlist",
+            "watchlist.csv",
+            "text/csv",
+            movary::WATCHLIST_TEMPLATE,
+        ),
+        template(
+            MediaImportSource::StoryGraph,
+            "export",
+            "storygraph.csv",
+            "text/csv",
+            story_graph::EXPORT_TEMPLATE,
+        ),
+    ]
+}
+
+#[derive(Default)]
+pub struct ImporterQuery;
+
+#[Object]
+impl ImporterQuery {
+    /// Get all the import jobs deployed by the user
+    async fn media_import_reports(
+        &self,
+        gql_ctx: &Context<'_>,
+    ) -> Result<Vec<media_import_report::Model>> {
+        let service = gql_ctx.data_unchecked::<Arc<ImporterService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.media_import_reports(user_id).await
+    }
+
+    /// Get aggregated statistics about all the imports done by the user
+    async fn media_import_stats(&self, gql_ctx: &Context<'_>) -> Result<MediaImportStats> {
+        let service = gql_ctx.data_unchecked::<Arc<ImporterService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.media_import_stats(user_id).await
+    }
+
+    /// Get the items that a newer import added or did not import compared to
+    /// an older one, grouped by lot. Reports created before imported items
+    /// were recorded have no items to compare.
+    async fn compare_import_reports(
+        &self,
+        gql_ctx: &Context<'_>,
+        old_report_id: i32,
+        new_report_id: i32,
+    ) -> Result<Vec<ImportReportLotComparison>> {
+        let service = gql_ctx.data_unchecked::<Arc<ImporterService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service
+            .compare_import_reports(user_id, old_report_id, new_report_id)
+            .await
+    }
+
+    /// Get the imports deployed by all users of this instance, newest first. The
+    /// account must be an `Admin`.
+    async fn import_audit_log(
+        &self,
+        gql_ctx: &Context<'_>,
+        input: ImportAuditLogInput,
+    ) -> Result<Vec<ImportAuditLogEntry>> {
+        let service = gql_ctx.data_unchecked::<Arc<ImporterService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.media_service.admin_account_guard(user_id).await?;
+        service.import_audit_log(input).await
+    }
+
+    /// Get the metrics of the imports finished on this instance, oldest first.
+    /// They are kept after the reports are deleted. The account must be an
+    /// `Admin`.
+    async fn import_analytics(
+        &self,
+        gql_ctx: &Context<'_>,
+        input: ImportAnalyticsInput,
+    ) -> Result<Vec<import_analytics::Model>> {
+        let service = gql_ctx.data_unchecked::<Arc<ImporterService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.media_service.admin_account_guard(user_id).await?;
+        service.import_analytics(input).await
+    }
+
+    /// Get the items of an import that were not imported since their match has
+    /// to be confirmed.
+    async fn import_pending_matches(
+        &self,
+        gql_ctx: &Context<'_>,
+        report_id: i32,
+    ) -> Result<Vec<ImportPendingMatch>> {
+        let service = gql_ctx.data_unchecked::<Arc<ImporterService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        let (_, details) = service.finished_import_report(user_id, report_id).await?;
+        Ok(details.pending_matches)
+    }
+
+    /// Get the number of items an import from an API-based source is expected
+    /// to have, using a single request to the source.
+    async fn estimate_import_size(
+        &self,
+        gql_ctx: &Context<'_>,
+        input: DeployImportJobInput,
+    ) -> Result<usize> {
+        let service = gql_ctx.data_unchecked::<Arc<ImporterService>>();
+        service.user_id_from_ctx(gql_ctx).await?;
+        service.estimate_import_size(input).await
+    }
+
+    /// Get the patterns removed from titles before the items of a source are
+    /// matched by their title.
+    async fn import_title_strip_patterns(
+        &self,
+        gql_ctx: &Context<'_>,
+        source: MediaImportSource,
+    ) -> Vec<String> {
+        gql_ctx
+            .data_unchecked::<Arc<ImporterService>>()
+            .title_strip_patterns(source)
+    }
+
+    /// Get example files for all the importers that accept file uploads
+    async fn import_templates(&self) -> Vec<ImportTemplate> {
+        get_import_templates()
+    }
+}
+
+#[derive(Default)]
+pub struct ImporterMutation;
+
+#[Object]
+impl ImporterMutation {
+    /// Add job to import data from various sources.
+    async fn deploy_import_job(
+        &self,
+        gql_ctx: &Context<'_>,
+        input: DeployImportJobInput,
+    ) -> Result<String> {
+        let service = gql_ctx.data_unchecked::<Arc<ImporterService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.deploy_import_job(user_id, input).await
+    }
+
+    /// Delete the seen items and reviews created by an import. Items created
+    /// manually or by other imports are kept.
+    async fn delete_import_results(
+        &self,
+        gql_ctx: &Context<'_>,
+        report_id: i32,
+    ) -> Result<DeletedImportResults> {
+        let service = gql_ctx.data_unchecked::<Arc<ImporterService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.delete_import_results(user_id, report_id).await
+    }
+
+    /// Import a single failed item of a report again using another provider or
+    /// a corrected identifier. On success, the media is added to the user's
+    /// library and the item is moved to the succeeded items of the report.
+    async fn retry_failed_import_item(
+        &self,
+        gql_ctx: &Context<'_>,
+        input: RetryFailedImportItemInput,
+    ) -> Result<ImportSucceededItem> {
+        let service = gql_ctx.data_unchecked::<Arc<ImporterService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.retry_failed_import_item(user_id, input).await
+    }
+
+    /// Import a pending match of a report. The media is added to the user's
+    /// library and the item is moved to the succeeded items of the report.
+    async fn confirm_import_match(
+        &self,
+        gql_ctx: &Context<'_>,
+        input: ConfirmImportMatchInput,
+    ) -> Result<ImportSucceededItem> {
+        let service = gql_ctx.data_unchecked::<Arc<ImporterService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.confirm_import_match(user_id, input).await
+    }
+
+    /// Reject a pending match of a report. The item is moved to the failed
+    /// items, from where it can be retried with another identifier.
+    async fn reject_import_match(
+        &self,
+        gql_ctx: &Context<'_>,
+        report_id: i32,
+        id: String,
+    ) -> Result<bool> {
+        let service = gql_ctx.data_unchecked::<Arc<ImporterService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.reject_import_match(user_id, report_id, id).await
+    }
+}
+
+pub struct ImporterService {
+    db: DatabaseConnection,
+    pub media_service: Arc<MiscellaneousService>,
+    import_media: SqliteStorage<ImportMedia>,
+    shutdown: Arc<ShutdownSignal>,
+}
+
+impl AuthProvider for ImporterService {
+    fn get_auth_db(&self) -> &MemoryDatabase {
+        self.media_service.get_auth_db()
+    }
+}
+
+impl ImporterService {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        db: &DatabaseConnection,
+        media_service: Arc<MiscellaneousService>,
+        import_media: &SqliteStorage<ImportMedia>,
+        shutdown: Arc<ShutdownSignal>,
+    ) -> Self {
+        Self {
+            db: db.clone(),
+            media_service,
+            import_media: import_media.clone(),
+            shutdown,
+        }
+    }
+
+    pub async fn deploy_import_job(
+        &self,
+        user_id: i32,
+        mut input: DeployImportJobInput,
+    ) -> Result<String> {
+        self.media_service
+            .rate_limit_guard(Some(user_id), RateLimitedOperation::Import)
+            .await?;
+        self.import_cooldown_guard(user_id, input.source).await?;
+        let mut storage = self.import_media.clone();
+        if let Some(s) = input.media_tracker.as_mut() {
+            s.api_url = s.api_url.trim_end_matches('/').to_owned();
+            // DEV: the input is stored in the job queue until the job runs
+            s.api_key = self.media_service.secrets.seal(&s.api_key)?;
+        }
+        if let Some(s) = input.just_watch.as_mut() {
+            s.token = self.media_service.secrets.seal(&s.token)?;
+        }
+        if let Some(s) = input.media_json.as_ref() {
+            media_json::check_export_size(s)?;
+        }
+        let defaults = self
+            .media_service
+            .user_preferences(user_id)
+            .await?
+            .import_defaults;
+        input.metadata_enrichment = input
+            .metadata_enrichment
+            .or(Some(defaults.metadata_enrichment));
+        input.skip_existing_seen = input
+            .skip_existing_seen
+            .or(Some(defaults.skip_existing_seen));
+        input.review_visibility = input.review_visibility.or(defaults.review_visibility);
+        input.mark_imported_reviews_spoiler = input
+            .mark_imported_reviews_spoiler
+            .or(Some(defaults.mark_imported_reviews_spoiler));
+        input.lots = input.lots.or(Some(defaults.lots));
+        input.watchlist_collection = input.watchlist_collection.or(defaults.watchlist_collection);
+        input.undated_seen_in_progress = input
+            .undated_seen_in_progress
+            .or(Some(defaults.undated_seen_in_progress));
+        input.min_match_confidence = input.min_match_confidence.or(defaults.min_match_confidence);
+        if input
+            .min_match_confidence
+            .map_or(false, |c| c < Decimal::ZERO || c > Decimal::ONE)
+        {
+            return Err(Error::new("The match confidence must be between 0 and 1"));
+        }
+        if let Some(patterns) = input.title_strip_patterns.as_ref() {
+            TitleMatching::new(None, patterns)?;
+        }
+        let job = storage
+            .push(ImportMedia {
+                user_id,
+                input,
+                resume_report_id: None,
+                resume_items: None,
+            })
+            .await
+            .unwrap();
+        record_job_enqueued(ImportMedia::NAME);
+        Ok(job.to_string())
+    }
+
+    /// The cooldown configured for imports from the source, if any.
+    fn import_cooldown(&self, source: MediaImportSource) -> Option<Duration> {
+        let config = &self.media_service.config.media;
+        let minutes = config
+            .import_source_cooldown_minutes
+            .get(&format!("{:?}", source))
+            .copied()
+            .unwrap_or(config.import_cooldown_minutes);
+        (minutes > 0).then(|| Duration::minutes(minutes))
+    }
+
+    /// Reject the import if the user completed another one from the same
+    /// source during its cooldown. Imports that failed do not count.
+    async fn import_cooldown_guard(&self, user_id: i32, source: MediaImportSource) -> Result<()> {
+        let Some(cooldown) = self.import_cooldown(source) else {
+            return Ok(());
+        };
+        let last_import = MediaImportReport::find()
+            .filter(media_import_report::Column::UserId.eq(user_id))
+            .filter(media_import_report::Column::Source.eq(source))
+            .filter(media_import_report::Column::Success.eq(true))
+            .order_by_desc(media_import_report::Column::FinishedOn)
+            .one(&self.db)
+            .await?;
+        match next_import_allowed_on(
+            last_import.and_then(|r| r.finished_on),
+            cooldown,
+            Utc::now(),
+        ) {
+            Some(allowed_on) => Err(import_cooldown_error(source, allowed_on)),
+            None => Ok(()),
+        }
+    }
+
+    pub async fn estimate_import_size(&self, input: DeployImportJobInput) -> Result<usize> {
+        let missing = || Error::new("The details of the source were not provided");
+        match input.source {
+            MediaImportSource::MediaTracker => {
+                let mut s = input.media_tracker.ok_or_else(missing)?;
+                s.api_url = s.api_url.trim_end_matches('/').to_owned();
+                media_tracker::estimate_size(&s).await
+            }
+            MediaImportSource::Trakt => {
+                trakt::estimate_size(input.trakt.as_ref().ok_or_else(missing)?).await
+            }
+            MediaImportSource::JustWatch => {
+                justwatch::estimate_size(input.just_watch.as_ref().ok_or_else(missing)?).await
+            }
+            _ => Err(Error::new(
+                "The size can only be estimated for imports from an API",
+            )),
+        }
+    }
+
+    /// The default patterns of the source followed by the ones configured for
+    /// the instance.
+    fn title_strip_patterns(&self, source: MediaImportSource) -> Vec<String> {
+        let mut patterns = default_title_strip_patterns(source);
+        patterns.extend(
+            self.media_service
+                .config
+                .media
+                .import_title_strip_patterns
+                .iter()
+                .cloned(),
+        );
+        patterns
+    }
+
+    pub async fn invalidate_import_jobs(&self) -> Result<()> {
+        let all_jobs = MediaImportReport::find()
+            .filter(media_import_report::Column::Success.is_null())
+            .all(&self.db)
+            .await?;
+        for job in all_jobs {
+            if Utc::now() - job.started_on > Duration::hours(24) {
+                tracing::trace!("Invalidating job with id = {id}", id = job.id);
+                let mut job: media_import_report::ActiveModel = job.into();
+                job.success = ActiveValue::Set(Some(false));
+                job.save(&self.db).await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn delete_expired_import_reports(&self) -> Result<()> {
+        let days = self.media_service.config.media.import_report_retention_days;
+        if days <= 0 {
+            return Ok(());
+        }
+        let deleted = MediaImportReport::delete_many()
+            .filter(media_import_report::Column::StartedOn.lt(Utc::now() - Duration::days(days)))
+            .filter(media_import_report::Column::Success.is_not_null())
+            .exec(&self.db)
+            .await?;
+        tracing::trace!("Deleted {} expired import reports", deleted.rows_affected);
+        Ok(())
+    }
+
+    pub async fn compare_import_reports(
+        &self,
+        user_id: i32,
+        old_report_id: i32,
+        new_report_id: i32,
+    ) -> Result<Vec<ImportReportLotComparison>> {
+        let succeeded_items = |report_id: i32| async move {
+            let report = MediaImportReport::find_by_id(report_id)
+                .filter(media_import_report::Column::UserId.eq(user_id))
+                .one(&self.db)
+                .await?
+                .ok_or_else(|| Error::new(format!("No import report with id {report_id}")))?;
+            Ok::<_, Error>(
+                report
+                    .details
+                    .map(|d| d.succeeded_items)
+                    .unwrap_or_default(),
+            )
+        };
+        let old = succeeded_items(old_report_id).await?;
+        let new = succeeded_items(new_report_id).await?;
+        Ok(compare_succeeded_items(&old, &new))
+    }
+
+    pub async fn delete_import_results(
+        &self,
+        user_id: i32,
+        report_id: i32,
+    ) -> Result<DeletedImportResults> {
+        let report = MediaImportReport::find_by_id(report_id)
+            .filter(media_import_report::Column::UserId.eq(user_id))
+            .one(&self.db)
+            .await?
+            .ok_or_else(|| Error::new(format!("No import report with id {report_id}")))?;
+        let txn = self.db.begin().await?;
+        let (deleted, metadata_ids) = trash_import_results(&txn, user_id, report.id).await?;
+        txn.commit().await?;
+        // DEV: the same cleanup as deleting a seen item by hand
+        for metadata_id in metadata_ids {
+            self.media_service
+                .invalidate_show_progress(metadata_id, Some(user_id))
+                .await
+                .ok();
+        }
+        self.media_service
+            .deploy_recalculate_summary_job(user_id)
+            .await?;
+        tracing::debug!(
+            "Deleted {} seen items and {} reviews of import {}",
+            deleted.seen,
+            deleted.reviews,
+            report.id
+        );
+        Ok(deleted)
+    }
+
+    /// The report of an import of the user, along with its details.
+    async fn finished_import_report(
+        &self,
+        user_id: i32,
+        report_id: i32,
+    ) -> Result<(media_import_report::Model, ImportResultResponse)> {
+        let report = MediaImportReport::find_by_id(report_id)
+            .filter(media_import_report::Column::UserId.eq(user_id))
+            .one(&self.db)
+            .await?
+            .ok_or_else(|| Error::new(format!("No import report with id {report_id}")))?;
+        let details = report
+            .details
+            .clone()
+            .filter(|d| d.progress.is_none())
+            .ok_or_else(|| Error::new("The import has not finished yet"))?;
+        Ok((report, details))
+    }
+
+    /// Import an item that was left out of an import along with its seen
+    /// history, reviews and collections, and add it to the user's library.
+    /// The item is moved to the succeeded items by the caller.
+    async fn import_item_again(
+        &self,
+        user_id: i32,
+        report: &media_import_report::Model,
+        details: &mut ImportResultResponse,
+        item: ImportOrExportItem<ImportOrExportItemIdentifier>,
+    ) -> Result<ImportSucceededItem> {
+        let metadata = self.commit_item(user_id, &item, false, None).await?;
+        associate_user_with_metadata(&user_id, &metadata.id, &self.db).await?;
+        // DEV: reports from before the options were saved did not keep their
+        // items either, so there is nothing else to import for them
+        if let Some(options) = details.options.clone() {
+            let item = prepare_item(item.clone(), &options, &mut details.warnings);
+            let provenance = ImportProvenance {
+                source: report.source,
+                report_id: report.id,
+            };
+            let written = self
+                .write_item(user_id, metadata.id, &item, &options, provenance, None)
+                .await?;
+            let summary = ImportLotSummary::of(&mut details.lots, item.lot);
+            summary.seen_created += written.seen_created;
+            summary.reviews_created += written.reviews_created;
+            summary.seen_skipped += written.seen_skipped;
+            details.failed_items.extend(written.failed_items);
+        }
+        self.succeeded_item(metadata.id).await
+    }
+
+    /// The item as it was committed, which can differ from what the source
+    /// had, eg when a title was matched at a provider.
+    async fn succeeded_item(&self, metadata_id: i32) -> Result<ImportSucceededItem> {
+        let metadata = Metadata::find_by_id(metadata_id)
+            .one(&self.db)
+            .await?
+            .ok_or_else(|| Error::new(format!("No media with id {metadata_id}")))?;
+        Ok(ImportSucceededItem {
+            lot: metadata.lot,
+            source: metadata.source,
+            identifier: metadata.identifier,
+            metadata_id: metadata.id,
+        })
+    }
+
+    pub async fn retry_failed_import_item(
+        &self,
+        user_id: i32,
+        input: RetryFailedImportItemInput,
+    ) -> Result<ImportSucceededItem> {
+        let (report, mut details) = self
+            .finished_import_report(user_id, input.report_id)
+            .await?;
+        let failed = details
+            .failed_items
+            .iter()
+            .find(|f| f.id == input.id)
+            .cloned()
+            .ok_or_else(|| Error::new(format!("No failed item with id {}", input.id)))?;
+        let identifier = match input.identifier {
+            Some(i) => i,
+            None => {
+                let title = failed.search_title().ok_or_else(|| {
+                    Error::new(format!(
+                        "The title of {:?} is not known, an identifier is required",
+                        failed.identifier
+                    ))
+                })?;
+                let options = details.options.as_ref();
+                let strip_patterns = options
+                    .and_then(|o| o.title_strip_patterns.clone())
+                    .unwrap_or_else(|| self.title_strip_patterns(report.source));
+                let matching = TitleMatching::new(
+                    options.and_then(|o| o.min_match_confidence),
+                    &strip_patterns,
+                )?;
+                let provider = self
+                    .media_service
+                    .get_user_provider(user_id, failed.lot, input.source)
+                    .await?;
+                let not_found =
+                    || Error::new(format!("Could not find {title:?} at {:?}", input.source));
+                let resolution = resolve_by_title(
+                    &*provider,
+                    failed.lot,
+                    input.source,
+                    &title,
+                    None,
+                    failed.year,
+                    &matching,
+                )
+                .await
+                .map_err(|e| Error::new(e.to_string()))?
+                .ok_or_else(not_found)?;
+                let candidates = iter::once(resolution.chosen)
+                    .chain(resolution.alternatives)
+                    .collect();
+                let (chosen, _) = self
+                    .media_service
+                    .commit_media_candidate(
+                        Some(user_id),
+                        failed.lot,
+                        input.source,
+                        &matching.normalize(&title),
+                        candidates,
+                    )
+                    .await?
+                    .ok_or_else(not_found)?;
+                chosen.identifier
+            }
+        };
+        let item = item_to_import(
+            &failed.item,
+            failed.lot,
+            &failed.identifier,
+            input.source,
+            identifier,
+        );
+        let succeeded = self
+            .import_item_again(user_id, &report, &mut details, item)
+            .await?;
+        details.resolve_failed_item(&input.id, succeeded.clone());
+        self.media_service
+            .save_import_job_progress(report, details)
+            .await?;
+        tracing::debug!(
+            "Imported failed item {:?} of import {} again",
+            failed.identifier,
+            input.report_id
+        );
+        Ok(succeeded)
+    }
+
+    pub async fn confirm_import_match(
+        &self,
+        user_id: i32,
+        input: ConfirmImportMatchInput,
+    ) -> Result<ImportSucceededItem> {
+        let (report, mut details) = self
+            .finished_import_report(user_id, input.report_id)
+            .await?;
+        let pending = details
+            .pending_matches
+            .iter()
+            .find(|p| p.id == input.id)
+            .cloned()
+            .ok_or_else(|| Error::new(format!("No pending match with id {}", input.id)))?;
+        let identifier = input
+            .identifier
+            .unwrap_or_else(|| pending.chosen.identifier.clone());
+        let item = item_to_import(
+            &pending.item,
+            pending.lot,
+            &pending.title,
+            pending.source,
+            identifier,
+        );
+        let succeeded = self
+            .import_item_again(user_id, &report, &mut details, item)
+            .await?;
+        details.resolve_pending_match(&input.id, Some(succeeded.clone()));
+        self.media_service
+            .save_import_job_progress(report, details)
+            .await?;
+        tracing::debug!(
+            "Confirmed the match of {:?} of import {}",
+            pending.title,
+            input.report_id
+        );
+        Ok(succeeded)
+    }
+
+    pub async fn reject_import_match(
+        &self,
+        user_id: i32,
+        report_id: i32,
+        id: String,
+    ) -> Result<bool> {
+        let (report, mut details) = self.finished_import_report(user_id, report_id).await?;
+        details
+            .resolve_pending_match(&id, None)
+            .ok_or_else(|| Error::new(format!("No pending match with id {id}")))?;
+        self.media_service
+            .save_import_job_progress(report, details)
+            .await?;
+        Ok(true)
+    }
+
+    pub async fn import_audit_log(
+        &self,
+        input: ImportAuditLogInput,
+    ) -> Result<Vec<ImportAuditLogEntry>> {
+        let page = input.page.unwrap_or(1).max(1);
+        let mut query = MediaImportReport::find()
+            .find_also_related(User)
+            .order_by_desc(media_import_report::Column::StartedOn);
+        if let Some(user_id) = input.user_id {
+            query = query.filter(media_import_report::Column::UserId.eq(user_id));
+        }
+        if let Some(source) = input.source {
+            query = query.filter(media_import_report::Column::Source.eq(source));
+        }
+        let reports = query
+            .limit(PAGE_LIMIT as u64)
+            .offset(((page - 1) * PAGE_LIMIT) as u64)
+            .all(&self.db)
+            .await?;
+        Ok(reports
+            .into_iter()
+            .map(|(report, user)| ImportAuditLogEntry {
+                report_id: report.id,
+                user_id: report.user_id,
+                username: user.map(|u| u.name).unwrap_or_default(),
+                source: report.source,
+                started_on: report.started_on,
+                finished_on: report.finished_on,
+                success: report.success,
+                items_imported: report.details.as_ref().map(|d| d.import.total),
+                items_failed: report.details.as_ref().map(|d| d.failed_items.len()),
+            })
+            .collect())
+    }
+
+    pub async fn media_import_reports(
+        &self,
+        user_id: i32,
+    ) -> Result<Vec<media_import_report::Model>> {
+        self.media_service.media_import_reports(user_id).await
+    }
+
+    pub async fn import_analytics(
+        &self,
+        input: ImportAnalyticsInput,
+    ) -> Result<Vec<import_analytics::Model>> {
+        let mut query = ImportAnalytics::find().order_by_asc(import_analytics::Column::StartedOn);
+        if let Some(source) = input.source {
+            query = query.filter(import_analytics::Column::Source.eq(source));
+        }
+        if let Some(since) = input.since {
+            query = query.filter(import_analytics::Column::StartedOn.gte(since));
+        }
+        Ok(query.all(&self.db).await?)
+    }
+
+    /// Save the results of a running import so far. A failure to do so does not
+    /// fail the import.
+    async fn save_import_progress(&self, report_id: i32, details: ImportResultResponse) {
+        let model = media_import_report::ActiveModel {
+            id: ActiveValue::Unchanged(report_id),
+            details: ActiveValue::Set(Some(details)),
+            ..Default::default()
+        };
+        if let Err(e) = model.update(&self.db).await {
+            tracing::warn!("Could not save the progress of import {report_id}: {e:?}");
+        }
+    }
+
+    /// Store the metrics of a finished import. A failure to do so does not
+    /// fail the import.
+    async fn save_import_analytics(&self, report_id: i32, model: import_analytics::ActiveModel) {
+        let model = import_analytics::ActiveModel {
+            report_id: ActiveValue::Set(Some(report_id)),
+            ..model
+        };
+        if let Err(e) = model.insert(&self.db).await {
+            tracing::error!("Could not save the analytics of import {report_id}: {e:?}");
+        }
+    }
+
+    pub async fn media_import_stats(&self, user_id: i32) -> Result<MediaImportStats> {
+        let reports = self
+            .media_import_reports(user_id)
+            .await?
+            .into_iter()
+            .sorted_unstable_by_key(|r| r.started_on)
+            .collect_vec();
+        let mut sources: Vec<ImportSourceStats> = vec![];
+        let mut success_rate_trend = vec![];
+        for report in reports.into_iter() {
+            let (imported, failed) = report
+                .details
+                .as_ref()
+                .map(|d| (d.import.total, d.failed_items.len()))
+                .unwrap_or_default();
+            // DEV: a running import has the results so far
+            if report
+                .details
+                .as_ref()
+                .map_or(false, |d| d.progress.is_none())
+            {
+                success_rate_trend.push(ImportSuccessRateTrendItem {
+                    report_id: report.id,
+                    source: report.source,
+                    started_on: report.started_on,
+                    success_rate: get_success_rate(imported, failed),
+                });
+            }
+            let stats = match sources.iter_mut().find(|s| s.source == report.source) {
+                Some(s) => s,
+                None => {
+                    sources.push(ImportSourceStats {
+                        source: report.source,
+                        total_imports: 0,
+                        successful_imports: 0,
+                        total_items_imported: 0,
+                        total_items_failed: 0,
+                        last_import: None,
+                    });
+                    sources.last_mut().unwrap()
+                }
+            };
+            stats.total_imports += 1;
+            if report.success == Some(true) {
+                stats.successful_imports += 1;
+            }
+            stats.total_items_imported += imported;
+            stats.total_items_failed += failed;
+            stats.last_import = Some(report);
+        }
+        let total_items_imported = sources.iter().map(|s| s.total_items_imported).sum();
+        let total_items_failed = sources.iter().map(|s| s.total_items_failed).sum();
+        Ok(MediaImportStats {
+            total_items_imported,
+            total_items_failed,
+            success_rate: get_success_rate(total_items_imported, total_items_failed),
+            sources,
+            success_rate_trend,
+        })
+    }
+
+    async fn podcast_episodes(&self, metadata_id: i32) -> Result<Vec<PodcastEpisode>> {
+        let mut metadata = Metadata::find_by_id(metadata_id).one(&self.db).await?;
+        // DEV: a placeholder committed while the details are deferred has no
+        // episodes yet, so its details are fetched right away
+        if let Some(m) = metadata
+            .clone()
+            .filter(|m| m.specifics == MediaSpecifics::Unknown)
+        {
+            if let Err(e) = self.media_service.update_metadata(m).await {
+                tracing::warn!("Could not get the episodes of {metadata_id}: {e:?}");
+            }
+            metadata = Metadata::find_by_id(metadata_id).one(&self.db).await?;
+        }
+        Ok(match metadata.map(|m| m.specifics) {
+            Some(MediaSpecifics::Podcast(p)) => p.episodes,
+            _ => vec![],
+        })
+    }
+
+    /// The titles of the groups that the media is a part of at its provider.
+    async fn group_titles(&self, metadata_id: i32) -> Result<Vec<String>> {
+        let Some(metadata) = Metadata::find_by_id(metadata_id).one(&self.db).await? else {
+            return Ok(vec![]);
+        };
+        let groups = metadata.find_related(MetadataGroup).all(&self.db).await?;
+        Ok(groups.into_iter().map(|g| g.title).collect())
+    }
+
+    /// The names of all the collections of the user.
+    async fn collection_names(&self, user_id: i32) -> Result<HashSet<String>> {
+        let names: Vec<String> = Collection::find()
+            .select_only()
+            .column(collection::Column::Name)
+            .filter(collection::Column::UserId.eq(user_id))
+            .into_tuple()
+            .all(&self.db)
+            .await?;
+        Ok(names.into_iter().collect())
+    }
+
+    /// The names of the user's collections that are not in `existing`.
+    async fn created_collections(
+        &self,
+        user_id: i32,
+        existing: &HashSet<String>,
+    ) -> Result<Vec<String>> {
+        Ok(self
+            .collection_names(user_id)
+            .await?
+            .into_iter()
+            .filter(|n| !existing.contains(n))
+            .sorted()
+            .collect())
+    }
+
+    pub async fn import_from_source(
+        &self,
+        user_id: i32,
+        input: DeployImportJobInput,
+        resume_report_id: Option<i32>,
+        resume_items: Option<Vec<ImportOrExportItem<ImportOrExportItemIdentifier>>>,
+        job: &JobHandle,
+    ) -> Result<()> {
+        let _in_flight = self.shutdown.track();
+        let started_at = Instant::now();
+        let resumed_job = match resume_report_id {
+            Some(id) => MediaImportReport::find_by_id(id).one(&self.db).await?,
+            None => None,
+        };
+        let (db_import_job, previous_details) = match resumed_job {
+            Some(mut job) => {
+                let details = job.details.take();
+                (job, details)
+            }
+            None => (
+                self.media_service
+                    .start_import_job(user_id, input.source)
+                    .await?,
+                None,
+            ),
+        };
+        let (report_id, source, started_on) = (
+            db_import_job.id,
+            db_import_job.source,
+            db_import_job.started_on,
+        );
+        let result = self
+            .import_into_report(
+                user_id,
+                input,
+                db_import_job,
+                previous_details,
+                resume_items,
+                started_at,
+                job,
+            )
+            .await;
+        if let Err(e) = &result {
+            // DEV: imports that could not run, for eg because the source could
+            // not be fetched, count towards the failure trends too
+            let analytics = failed_import_analytics_model(
+                source,
+                started_on,
+                Utc::now(),
+                provider_request_count(),
+            );
+            self.save_import_analytics(report_id, analytics).await;
+            let notification = ImportNotification {
+                source: format!("{:?}", source),
+                imported: 0,
+                failed: 0,
+                lots: vec![],
+                error: Some(e.message.clone()),
+            };
+            self.send_import_notification(user_id, notification).await;
+        }
+        result
+    }
+
+    /// Fetch the items of an import from its source and account for them.
+    async fn fetch_import(
+        &self,
+        user_id: i32,
+        input: DeployImportJobInput,
+        options: &ImportOptions,
+    ) -> Result<FetchedImport> {
+        let strip_patterns = options
+            .title_strip_patterns
+            .clone()
+            .unwrap_or_else(|| self.title_strip_patterns(input.source));
+        let mut matching = TitleMatching::new(options.min_match_confidence, &strip_patterns)?
+            .confirming_below(options.confirm_matches_below);
+        if matches!(
+            input.source,
+            MediaImportSource::Goodreads
+                | MediaImportSource::StoryGraph
+                | MediaImportSource::Movary
+                | MediaImportSource::Xlsx
+        ) {
+            matching = matching.with_known_media(self.db.clone());
+        }
+        let mut import = match input.source {
+            MediaImportSource::MediaTracker => {
+                let mut input = input.media_tracker.unwrap();
+                input.api_key = self.media_service.secrets.open(&input.api_key)?;
+                media_tracker::import(input).await?
+            }
+            MediaImportSource::MediaJson => media_json::import(input.media_json.unwrap()).await?,
+            MediaImportSource::Goodreads => {
+                goodreads::import(
+                    input.goodreads.unwrap(),
+                    &self.media_service.openlibrary_service,
+                    &matching,
+                )
+                .await?
+            }
+            MediaImportSource::Trakt => trakt::import(input.trakt.unwrap()).await?,
+            MediaImportSource::Movary => {
+                let provider = self
+                    .media_service
+                    .get_user_provider(user_id, MetadataLot::Movie, MetadataSource::Tmdb)
+                    .await?;
+                movary::import(input.movary.unwrap(), &*provider, &matching).await?
+            }
+            MediaImportSource::StoryGraph => {
+                story_graph::import(
+                    input.story_graph.unwrap(),
+                    &self.media_service.openlibrary_service,
+                    &matching,
+                )
+                .await?
+            }
+            MediaImportSource::RyotSqlite => {
+                ryot_sqlite::import(input.ryot_sqlite.unwrap(), &self.media_service.file_storage)
+                    .await?
+            }
+            MediaImportSource::JustWatch => {
+                let mut input = input.just_watch.unwrap();
+                input.token = self.media_service.secrets.open(&input.token)?;
+                justwatch::import(input).await?
+            }
+            MediaImportSource::Xlsx => {
+                xlsx::import(
+                    user_id,
+                    input.xlsx.unwrap(),
+                    &self.media_service.file_storage,
+                    &self.media_service,
+                    &matching,
+                )
+                .await?
+            }
+        };
+        if options.keep_unmatched {
+            let (unmatched, failed): (Vec<_>, Vec<_>) = import
+                .failed_items
+                .into_iter()
+                .partition(|f| f.step == ImportFailStep::TitleMatch);
+            import.failed_items = failed;
+            import
+                .media
+                .extend(unmatched.iter().map(|f| unmatched_stub(f, input.source)));
+        }
+        // DEV: Movary lists a movie in each of its files, which would otherwise
+        // have to be confirmed once for each of them
+        import.pending_matches = import
+            .pending_matches
+            .into_iter()
+            .unique_by(|p| (p.lot, p.source, p.title.clone()))
+            .collect();
+        let source_failed = import.failed_items.len();
+        let source_total =
+            import.media.len() + source_failed + import.unsupported + import.pending_matches.len();
+        let (media, filtered): (Vec<_>, Vec<_>) = import
+            .media
+            .into_iter()
+            .partition(|m| options.lots.is_empty() || options.lots.contains(&m.lot));
+        let (media, skipped) = merge_duplicate_items(media);
+        let reconciliation = ImportReconciliation {
+            source_total,
+            imported: 0,
+            failed: source_failed,
+            skipped,
+            filtered: filtered.len() + import.unsupported,
+            pending: import.pending_matches.len(),
+        };
+        let mut lots = vec![];
+        for item in import.failed_items.iter() {
+            let summary = ImportLotSummary::of(&mut lots, item.lot);
+            summary.attempted += 1;
+            summary.failed += 1;
+        }
+        // DEV: the items are prepared as they are imported, so that the items
+        // that fail are kept as they were read from the source
+        import.media = order_import_items(media, options.preserve_source_order);
+        Ok((import, reconciliation, lots))
+    }
+
+    async fn import_into_report(
+        &self,
+        user_id: i32,
+        input: DeployImportJobInput,
+        db_import_job: media_import_report::Model,
+        previous_details: Option<ImportResultResponse>,
+        resume_items: Option<Vec<ImportOrExportItem<ImportOrExportItemIdentifier>>>,
+        started_at: Instant,
+        job: &JobHandle,
+    ) -> Result<()> {
+        let resume_input = input.clone();
+        let mut options = ImportOptions::from(&input);
+        if input.namespace_collections.unwrap_or_default() {
+            // DEV: a resumed job keeps its report, so it adds to the same
+            // collections
+            options.collection_suffix = Some(format!(
+                "{:?} {} #{}",
+                input.source,
+                db_import_job.started_on.date_naive(),
+                db_import_job.id
+            ));
+        }
+        let (mut import, mut reconciliation, mut lots) = resumed_or_fetched(
+            resume_items,
+            self.fetch_import(user_id, input.clone(), &options),
+        )
+        .await?;
+        let mut existing_collections = self.collection_names(user_id).await?;
+        let mut succeeded_items = vec![];
+        let mut unavailable_providers = vec![];
+        let resume_cursor = match previous_details {
+            Some(d) => {
+                succeeded_items = d.succeeded_items;
+                unavailable_providers = d.unavailable_providers;
+                for name in &d.created_collections {
+                    existing_collections.remove(name);
+                }
+                import.failed_items = d.failed_items;
+                import.warnings = d.warnings;
+                import.pending_matches = d.pending_matches;
+                import.unsupported = d.import.unsupported;
+                lots = d.lots;
+                if let Some(r) = d.reconciliation {
+                    reconciliation = r;
                 }
+                d.resume_cursor.unwrap_or_default()
+            }
+            None => 0,
+        };
+        for col_details in import
+            .collections
+            .into_iter()
+            .filter(|c| is_collection_allowed(&c.name, &options.tag_collection_allowlist))
+        {
+            let col_details = CreateOrUpdateCollectionInput {
+                name: options.collection_name(&col_details.name),
+                ..col_details
             };
+            self.media_service
+                .create_or_update_collection(&user_id, col_details)
+                .await?;
+        }
+        let provenance = ImportProvenance {
+            source: input.source,
+            report_id: db_import_job.id,
+        };
+        let enrichment = options.metadata_enrichment;
+        let mut deferred_metadata_ids = vec![];
+        let ignored = self.media_service.ignored_metadata_ids(user_id).await?;
+        let mut seen_batch = options
+            .seen_batch_size
+            .map(|size| SeenBatch::new(user_id, size));
+        let item_timeout =
+            std::time::Duration::from_secs(self.media_service.config.media.import_item_timeout);
+        let mut breaker = ProviderBreaker::new(
+            self.media_service
+                .config
+                .media
+                .import_provider_failure_threshold,
+            unavailable_providers,
+        );
+        let mut timer = ImportTimer::default();
+        let mut progress_saved_at = Instant::now();
+        for (idx, raw_item) in import.media.iter().enumerate().skip(resume_cursor) {
+            timer.start_item();
+            job.set_progress(idx, import.media.len());
+            let estimate = timer.estimate(import.media.len() - idx, Utc::now());
+            if let Some(estimate) = estimate.clone() {
+                job.set_estimate(estimate);
+            }
+            if progress_saved_at.elapsed().as_secs() >= IMPORT_PROGRESS_SAVE_INTERVAL {
+                progress_saved_at = Instant::now();
+                let details = ImportResultResponse {
+                    source: db_import_job.source,
+                    import: ImportDetails {
+                        total: idx,
+                        unsupported: import.unsupported,
+                    },
+                    failed_items: import.failed_items.clone(),
+                    succeeded_items: succeeded_items.clone(),
+                    warnings: import.warnings.clone(),
+                    pending_matches: import.pending_matches.clone(),
+                    resume_cursor: None,
+                    options: Some(options.clone()),
+                    reconciliation: Some(reconciliation.clone()),
+                    summary_warning: None,
+                    created_collections: vec![],
+                    unavailable_providers: breaker.unavailable.clone(),
+                    throughput: Some(timer.throughput()),
+                    progress: Some(ImportProgress {
+                        processed: idx,
+                        total: import.media.len(),
+                        estimate,
+                        saved_on: Utc::now(),
+                    }),
+                    lots: lots.clone(),
+                };
+                self.save_import_progress(db_import_job.id, details).await;
+            }
+            if job.is_cancelled() || self.shutdown.is_requested() {
+                if let Some(batch) = seen_batch.as_mut() {
+                    self.media_service.flush_seen_batch(batch).await?;
+                }
+            }
+            if job.is_cancelled() {
+                tracing::info!(
+                    "Cancelling import job with id = {id} at item {idx}",
+                    id = db_import_job.id
+                );
+                let details = ImportResultResponse {
+                    source: db_import_job.source,
+                    import: ImportDetails {
+                        total: idx,
+                        unsupported: import.unsupported,
+                    },
+                    failed_items: import.failed_items,
+                    succeeded_items,
+                    warnings: import.warnings,
+                    pending_matches: import.pending_matches,
+                    resume_cursor: None,
+                    options: Some(options),
+                    reconciliation: Some(reconciliation),
+                    summary_warning: None,
+                    created_collections: self
+                        .created_collections(user_id, &existing_collections)
+                        .await?,
+                    unavailable_providers: breaker.unavailable.clone(),
+                    throughput: Some(timer.throughput()),
+                    progress: None,
+                    lots: lots.clone(),
+                };
+                let analytics = import_analytics_model(
+                    &details,
+                    db_import_job.started_on,
+                    Utc::now(),
+                    false,
+                    provider_request_count(),
+                );
+                let report = self
+                    .media_service
+                    .cancel_import_job(db_import_job, details)
+                    .await?;
+                self.save_import_analytics(report.id, analytics).await;
+                return Ok(());
+            }
+            if self.shutdown.is_requested() {
+                tracing::info!(
+                    "Interrupting import job with id = {id} at item {idx}",
+                    id = db_import_job.id
+                );
+                let details = ImportResultResponse {
+                    source: db_import_job.source,
+                    import: ImportDetails {
+                        total: idx,
+                        unsupported: import.unsupported,
+                    },
+                    failed_items: import.failed_items,
+                    succeeded_items,
+                    warnings: import.warnings,
+                    pending_matches: import.pending_matches,
+                    resume_cursor: Some(idx),
+                    options: Some(options),
+                    reconciliation: Some(reconciliation),
+                    summary_warning: None,
+                    created_collections: self
+                        .created_collections(user_id, &existing_collections)
+                        .await?,
+                    unavailable_providers: breaker.unavailable.clone(),
+                    throughput: Some(timer.throughput()),
+                    progress: None,
+                    lots: lots.clone(),
+                };
+                let job = self
+                    .media_service
+                    .save_import_job_progress(db_import_job, details)
+                    .await?;
+                let mut storage = self.import_media.clone();
+                storage
+                    .push(ImportMedia {
+                        user_id,
+                        input: resume_input,
+                        resume_report_id: Some(job.id),
+                        resume_items: Some(import.media.clone()),
+                    })
+                    .await
+                    .unwrap();
+                record_job_enqueued(ImportMedia::NAME);
+                return Ok(());
+            }
+            let item = &prepare_item(raw_item.clone(), &options, &mut import.warnings);
+            tracing::debug!(
+                "Importing media with identifier = {iden}",
+                iden = item.source_id
+            );
+            ImportLotSummary::of(&mut lots, item.lot).attempted += 1;
+            let from_provider = matches!(
+                item.identifier,
+                ImportOrExportItemIdentifier::NeedsDetails(_)
+            );
+            if from_provider && breaker.skip(item.lot, item.source) {
+                import.failed_items.push(
+                    ImportFailedItem::new(
+                        item.lot,
+                        ImportFailStep::ProviderUnavailable,
+                        item.source_id.to_owned(),
+                        Some(format!(
+                            "{:?} failed for too many items in a row",
+                            item.source
+                        )),
+                    )
+                    .with_item(raw_item),
+                );
+                reconciliation.failed += 1;
+                ImportLotSummary::of(&mut lots, item.lot).failed += 1;
+                continue;
+            }
+            // DEV: shows and podcasts need their episodes to be known before any
+            // seen history can be attached to them, and the placeholder of an
+            // item needs a title until its details are fetched
+            let defer_details = enrichment != ImportMetadataEnrichment::Immediate
+                && item.title.is_some()
+                && !matches!(item.lot, MetadataLot::Show | MetadataLot::Podcast);
+            let failures_before = provider_failure_count();
+            let data = self
+                .commit_item(user_id, item, defer_details, Some(item_timeout))
+                .await;
+            timer.resolved();
+            if let (Ok(m), true) = (&data, defer_details && from_provider) {
+                deferred_metadata_ids.push(m.id);
+            }
+            // DEV: only the provider failing counts towards skipping it, not eg
+            // an item it does not know about
+            if from_provider {
+                if data.is_ok() {
+                    breaker.record(item.lot, item.source, true);
+                } else if provider_failure_count() > failures_before {
+                    breaker.record(item.lot, item.source, false);
+                }
+            }
             let metadata = match data {
                 Ok(r) => r,
                 Err(e) => {
                     tracing::error!("{e:?}");
-                    import.failed_items.push(ImportFailedItem {
-                        lot: item.lot,
-                        step: ImportFailStep::MediaDetailsFromProvider,
-                        identifier: item.source_id.to_owned(),
-                        error: Some(e.message),
-                    });
+                    import.failed_items.push(
+                        ImportFailedItem::new(
+                            item.lot,
+                            ImportFailStep::MediaDetailsFromProvider,
+                            item.source_id.to_owned(),
+                            Some(e.message),
+                        )
+                        .with_item(raw_item),
+                    );
+                    reconciliation.failed += 1;
+                    ImportLotSummary::of(&mut lots, item.lot).failed += 1;
                     continue;
                 }
             };
-            for seen in item.seen_history.iter() {
+            if ignored.contains(&metadata.id) {
+                tracing::debug!("Skipping ignored media with id = {id}", id = metadata.id);
+                reconciliation.skipped += 1;
+                continue;
+            }
+            reconciliation.imported += 1;
+            ImportLotSummary::of(&mut lots, item.lot).succeeded += 1;
+            let succeeded = self.succeeded_item(metadata.id).await?;
+            if options.log_succeeded_items {
+                tracing::debug!(
+                    target: "ryot::import_audit",
+                    report_id = db_import_job.id,
+                    source_id = %item.source_id,
+                    lot = %succeeded.lot,
+                    source = ?succeeded.source,
+                    identifier = %succeeded.identifier,
+                    metadata_id = metadata.id,
+                    "Imported item"
+                );
+            }
+            succeeded_items.push(succeeded);
+            let written = self
+                .write_item(
+                    user_id,
+                    metadata.id,
+                    item,
+                    &options,
+                    provenance,
+                    seen_batch.as_mut(),
+                )
+                .await?;
+            let summary = ImportLotSummary::of(&mut lots, item.lot);
+            summary.seen_created += written.seen_created;
+            summary.reviews_created += written.reviews_created;
+            summary.seen_skipped += written.seen_skipped;
+            import.failed_items.extend(written.failed_items);
+            tracing::debug!(
+                "Imported item: {idx}/{total}, lot: {lot}, history count: {hist}, review count: {rev}, collection count: {col}",
+                idx = idx,
+                total = import.media.len(),
+                lot = item.lot,
+                hist = item.seen_history.len(),
+                rev = item.reviews.len(),
+                col = item.collections.len(),
+            );
+        }
+        timer.finish_item();
+        if let Some(batch) = seen_batch.as_mut() {
+            self.media_service.flush_seen_batch(batch).await?;
+        }
+        if enrichment == ImportMetadataEnrichment::Deferred {
+            let metadata_ids = deferred_metadata_ids.into_iter().unique().collect_vec();
+            if let Err(e) = self
+                .media_service
+                .deploy_update_metadata_batch_jobs(metadata_ids)
+                .await
+            {
+                tracing::warn!("Could not queue the refresh of the imported media: {e:?}");
+            }
+        }
+        tracing::trace!(
+            "Imported {total} media items from {source}",
+            total = import.media.len(),
+            source = db_import_job.source
+        );
+        let details = ImportResultResponse {
+            source: db_import_job.source,
+            import: ImportDetails {
+                total: reconciliation.imported,
+                unsupported: import.unsupported,
+            },
+            failed_items: import.failed_items,
+            succeeded_items,
+            warnings: import.warnings,
+            pending_matches: import.pending_matches,
+            resume_cursor: None,
+            options: Some(options),
+            reconciliation: Some(reconciliation),
+            summary_warning: None,
+            created_collections: self
+                .created_collections(user_id, &existing_collections)
+                .await?,
+            unavailable_providers: breaker.unavailable,
+            throughput: Some(timer.throughput()),
+            progress: None,
+            lots,
+        };
+        record_import(
+            &format!("{:?}", details.source),
+            details.import.total,
+            details.failed_items.len(),
+            started_at.elapsed(),
+        );
+        let notification = ImportNotification {
+            source: format!("{:?}", details.source),
+            imported: details.import.total,
+            failed: details.failed_items.len(),
+            lots: details.lots.clone(),
+            error: None,
+        };
+        // DEV: a resumed import only counts the requests made since it was
+        // resumed
+        let analytics = import_analytics_model(
+            &details,
+            db_import_job.started_on,
+            Utc::now(),
+            true,
+            provider_request_count(),
+        );
+        let skip_summary = details
+            .options
+            .as_ref()
+            .map_or(false, |o| o.skip_summary_recalculation);
+        let report = if details
+            .options
+            .as_ref()
+            .map_or(false, |o| o.merge_with_last_report)
+        {
+            self.media_service
+                .merge_import_job(db_import_job, details)
+                .await?
+        } else {
+            self.media_service
+                .finish_import_job(db_import_job, details)
+                .await?
+        };
+        self.save_import_analytics(report.id, analytics).await;
+        if !skip_summary {
+            // DEV: the import has been saved by now, so the user is still
+            // notified if the summary can not be recalculated
+            if let Err(e) = self
+                .media_service
+                .deploy_import_summary_job(user_id, report.id)
+                .await
+            {
+                tracing::error!(
+                    "Could not deploy the summary job of import {}: {e:?}",
+                    report.id
+                );
+            }
+        }
+        self.send_import_notification(user_id, notification).await;
+        Ok(())
+    }
+
+    /// Commit the media of an item. If its details do not have to be fetched
+    /// right away and its title is known, only a placeholder is committed for
+    /// it. Fetching the details fails if it takes longer than `fetch_timeout`.
+    async fn commit_item(
+        &self,
+        user_id: i32,
+        item: &ImportOrExportItem<ImportOrExportItemIdentifier>,
+        defer_details: bool,
+        fetch_timeout: Option<std::time::Duration>,
+    ) -> Result<IdObject> {
+        match (&item.identifier, item.title.as_deref()) {
+            (ImportOrExportItemIdentifier::NeedsDetails(i), Some(title)) if defer_details => {
+                self.media_service
+                    .commit_media_placeholder(item.lot, item.source, i, title)
+                    .await
+            }
+            (ImportOrExportItemIdentifier::NeedsDetails(i), _) => {
+                self.media_service
+                    .commit_media_with_timeout(
+                        Some(user_id),
+                        item.lot,
+                        item.source,
+                        i,
+                        fetch_timeout,
+                    )
+                    .await
+            }
+            (ImportOrExportItemIdentifier::AlreadyFilled(a), _) => {
+                // DEV: custom media can not be fetched again, so an item
+                // that was imported before is reused instead of duplicated
                 match self
                     .media_service
-                    .progress_update(
-                        ProgressUpdateInput {
-                            metadata_id: metadata.id,
-                            progress: Some(100),
-                            date: seen.ended_on.map(|d| d.date_naive()),
-                            show_season_number: seen.show_season_number,
-                            show_episode_number: seen.show_episode_number,
-                            podcast_episode_number: seen.podcast_episode_number,
-                            change_state: None,
-                        },
-                        user_id,
-                    )
+                    .media_exists_in_database(a.lot, a.source, &a.identifier)
                     .await
                 {
-                    Ok(_) => {}
-                    Err(e) => import.failed_items.push(ImportFailedItem {
-                        lot: item.lot,
-                        step: ImportFailStep::SeenHistoryConversion,
-                        identifier: item.source_id.to_owned(),
-                        error: Some(e.message),
-                    }),
+                    Ok(Some(m)) => Ok(m),
+                    Ok(None) => self.media_service.commit_media_internal(*a.clone()).await,
+                    Err(e) => Err(e),
+                }
+            }
+        }
+    }
+
+    /// Add the seen history, reviews, state and collections of an item to the
+    /// user's library once its media has been committed.
+    async fn write_item(
+        &self,
+        user_id: i32,
+        metadata_id: i32,
+        item: &ImportOrExportItem<ImportOrExportItemIdentifier>,
+        options: &ImportOptions,
+        provenance: ImportProvenance,
+        mut seen_batch: Option<&mut SeenBatch>,
+    ) -> Result<ItemWrite> {
+        let mut written = ItemWrite::default();
+        let (mut started_history, only_planned) = partition_seen_history(&item.seen_history);
+        let state = match item.state.map(MediaState::normalize) {
+            // DEV: an item that has been started is no longer only planned
+            Some(MediaState::PlanTo) if !started_history.is_empty() => None,
+            Some(s) => Some(s),
+            None if only_planned => Some(MediaState::PlanTo),
+            None => None,
+        };
+        let existing_state = match options.state_overwrite {
+            ImportStateOverwrite::NeverRegress => {
+                let state = self.media_service.media_state(user_id, metadata_id).await?;
+                let history = self
+                    .media_service
+                    .seen_history(metadata_id, user_id)
+                    .await?;
+                [state, seen_history_state(&history)]
+                    .into_iter()
+                    .flatten()
+                    .max_by_key(|s| state_progress(*s))
+            }
+            ImportStateOverwrite::SourceAuthoritative => None,
+        };
+        let state = state.filter(|s| !state_regresses(existing_state, *s));
+        let batched_seen = seen_batch
+            .as_deref()
+            .map_or(false, |b| b.contains_metadata(metadata_id));
+        if options.skip_existing_seen
+            && (batched_seen
+                || !self
+                    .media_service
+                    .seen_history(metadata_id, user_id)
+                    .await?
+                    .is_empty())
+        {
+            started_history.clear();
+        }
+        if only_planned {
+            self.media_service
+                .create_or_update_collection(
+                    &user_id,
+                    CreateOrUpdateCollectionInput {
+                        name: options.collection_name(&options.watchlist_collection),
+                        ..Default::default()
+                    },
+                )
+                .await?;
+            self.media_service
+                .add_media_to_collection(
+                    &user_id,
+                    AddMediaToCollection {
+                        collection_name: options.collection_name(&options.watchlist_collection),
+                        media_id: metadata_id,
+                    },
+                )
+                .await
+                .ok();
+        }
+        let needs_episode_numbers = started_history
+            .iter()
+            .any(|s| s.podcast_episode_number.is_none() && s.podcast_episode_guid.is_some());
+        let podcast_episodes = if item.lot == MetadataLot::Podcast && needs_episode_numbers {
+            self.podcast_episodes(metadata_id).await?
+        } else {
+            vec![]
+        };
+        for seen in started_history {
+            let podcast_episode_number =
+                match (seen.podcast_episode_number, &seen.podcast_episode_guid) {
+                    (None, Some(guid)) => {
+                        match podcast_episode_number_by_guid(&podcast_episodes, guid) {
+                            Some(n) => Some(n),
+                            None => {
+                                written.failed_items.push(ImportFailedItem::new(
+                                    item.lot,
+                                    ImportFailStep::SeenHistoryConversion,
+                                    item.source_id.to_owned(),
+                                    Some(format!("No episode with the GUID {guid:?} in the feed")),
+                                ));
+                                continue;
+                            }
+                        }
+                    }
+                    (number, _) => number,
                 };
+            let progress = seen_progress(seen, options.undated_seen_in_progress);
+            if progress < 100 && state_regresses(existing_state, MediaState::InProgress) {
+                tracing::debug!(
+                    "Not starting {id} again since it has a more advanced state",
+                    id = metadata_id
+                );
+                written.seen_skipped += 1;
+                continue;
             }
-            for review in item.reviews.iter() {
-                if review.review.is_none() && review.rating.is_none() {
-                    tracing::debug!("Skipping review since it has no content");
-                    continue;
-                }
-                let text = review.review.clone().and_then(|r| r.text);
-                let spoiler = review.review.clone().map(|r| r.spoiler.unwrap_or(false));
-                let date = review.review.clone().map(|r| r.date);
-                match self
+            let precision = seen
+                .date_precision
+                .filter(|p| *p != SeenDatePrecision::Day && progress == 100);
+            let input = ProgressUpdateInput {
+                metadata_id,
+                progress: Some(progress),
+                date: seen.ended_on.map(|d| d.date_naive()),
+                show_season_number: seen.show_season_number,
+                show_episode_number: seen.show_episode_number,
+                podcast_episode_number,
+                change_state: None,
+                plays: seen.plays,
+                import: Some(provenance),
+            };
+            let result = match seen_batch.as_deref_mut() {
+                // DEV: vague dates are saved as they are, instead of being
+                // treated as the exact day they are stored as
+                _ if precision.is_some() => self
                     .media_service
-                    .post_review(
-                        &user_id,
-                        PostReviewInput {
-                            rating: review.rating,
-                            text,
-                            spoiler,
-                            date: date.flatten(),
-                            visibility: None,
-                            metadata_id: metadata.id,
-                            review_id: None,
-                            show_season_number: review.show_season_number,
-                            show_episode_number: review.show_episode_number,
-                            podcast_episode_number: review.podcast_episode_number,
+                    .create_seen_entry(
+                        user_id,
+                        CreateSeenEntryInput {
+                            metadata_id,
+                            started_on: seen.started_on.map(|d| d.date_naive()),
+                            finished_on: input.date,
+                            precision,
+                            show_season_number: input.show_season_number,
+                            show_episode_number: input.show_episode_number,
+                            podcast_episode_number,
+                            plays: input.plays,
+                            import: input.import,
                         },
                     )
                     .await
-                {
-                    Ok(_) => {}
-                    Err(e) => import.failed_items.push(ImportFailedItem {
-                        lot: item.lot,
-                        step: ImportFailStep::ReviewConversion,
-                        identifier: item.source_id.to_owned(),
-                        error: Some(e.message),
-                    }),
-                };
-            }
-            for col in item.collections.iter() {
-                self.media_service
-                    .create_or_update_collection(
-                        &user_id,
-                        CreateOrUpdateCollectionInput {
-                            name: col.to_string(),
-                            ..Default::default()
-                        },
-                    )
-                    .await?;
-                self.media_service
-                    .add_media_to_collection(
-                        &user_id,
-                        AddMediaToCollection {
-                            collection_name: col.to_string(),
-                            media_id: metadata.id,
-                        },
-                    )
+                    .map(|_| ()),
+                Some(batch) => self.media_service.batch_progress_update(batch, input).await,
+                None => self
+                    .media_service
+                    .progress_update(input, user_id)
                     .await
-                    .ok();
+                    .map(|_| ()),
+            };
+            match result {
+                Ok(_) => written.seen_created += 1,
+                Err(e) => written.failed_items.push(ImportFailedItem::new(
+                    item.lot,
+                    ImportFailStep::SeenHistoryConversion,
+                    item.source_id.to_owned(),
+                    Some(e.message),
+                )),
+            };
+        }
+        if let Some(state) = state {
+            // DEV: the state depends on the seen history of the item
+            if let Some(batch) = seen_batch.as_deref_mut() {
+                self.media_service.flush_seen_batch(batch).await?;
             }
-            tracing::debug!(
-                "Imported item: {idx}/{total}, lot: {lot}, history count: {hist}, review count: {rev}, collection count: {col}",
-                idx = idx,
-                total = import.media.len(),
-                lot = item.lot,
-                hist = item.seen_history.len(),
-                rev = item.reviews.len(),
-                col = item.collections.len(),
-            );
+            self.media_service
+                .change_media_state(user_id, metadata_id, state)
+                .await
+                .ok();
         }
+        for review in item.reviews.iter() {
+            if review.review.is_none() && review.rating.is_none() {
+                tracing::debug!("Skipping review since it has no content");
+                continue;
+            }
+            let text = review.review.clone().and_then(|r| r.text);
+            let spoiler = review
+                .review
+                .as_ref()
+                .map(|r| r.spoiler.unwrap_or(false) || options.mark_imported_reviews_spoiler);
+            let date = review.review.clone().map(|r| r.date);
+            let visibility = review
+                .review
+                .as_ref()
+                .and_then(|r| r.visibility)
+                .or(options.review_visibility);
+            match self
+                .media_service
+                .post_review(
+                    &user_id,
+                    PostReviewInput {
+                        rating: review.rating,
+                        text,
+                        spoiler,
+                        date: date.flatten(),
+                        visibility,
+                        metadata_id,
+                        review_id: None,
+                        show_season_number: review.show_season_number,
+                        show_episode_number: review.show_episode_number,
+                        podcast_episode_number: review.podcast_episode_number,
+                        import: Some(provenance),
+                    },
+                )
+                .await
+            {
+                Ok(_) => written.reviews_created += 1,
+                Err(e) => written.failed_items.push(ImportFailedItem::new(
+                    item.lot,
+                    ImportFailStep::ReviewConversion,
+                    item.source_id.to_owned(),
+                    Some(e.message),
+                )),
+            };
+        }
+        // DEV: the groups of media whose details were deferred are not
+        // known yet, so they are not added to any group collection
+        let group_titles = match options.group_collections {
+            true => self.group_titles(metadata_id).await,
+            false => Ok(vec![]),
+        };
+        let group_names = match group_titles {
+            Ok(titles) => titles
+                .iter()
+                .map(|t| options.collection_name(t))
+                .collect_vec(),
+            Err(e) => {
+                written.failed_items.push(ImportFailedItem::new(
+                    item.lot,
+                    ImportFailStep::CollectionConversion,
+                    item.source_id.to_owned(),
+                    Some(format!("Could not get the groups: {}", e.message)),
+                ));
+                vec![]
+            }
+        };
+        for name in item.collections.iter().chain(group_names.iter()) {
+            if let Err(e) = self.add_to_collection(user_id, name, metadata_id).await {
+                written.failed_items.push(ImportFailedItem::new(
+                    item.lot,
+                    ImportFailStep::CollectionConversion,
+                    item.source_id.to_owned(),
+                    Some(format!("Could not add it to {name:?}: {}", e.message)),
+                ));
+            }
+        }
+        Ok(written)
+    }
+
+    /// Add the media to a collection of the user, creating the collection if
+    /// it does not exist yet.
+    async fn add_to_collection(&self, user_id: i32, name: &str, metadata_id: i32) -> Result<()> {
         self.media_service
-            .deploy_recalculate_summary_job(user_id)
-            .await
-            .ok();
-        tracing::trace!(
-            "Imported {total} media items from {source}",
-            total = import.media.len(),
-            source = db_import_job.source
+            .create_or_update_collection(
+                &user_id,
+                CreateOrUpdateCollectionInput {
+                    name: name.to_owned(),
+                    ..Default::default()
+                },
+            )
+            .await?;
+        self.media_service
+            .add_media_to_collection(
+                &user_id,
+                AddMediaToCollection {
+                    collection_name: name.to_owned(),
+                    media_id: metadata_id,
+                },
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Post the outcome of an import to the webhook configured by the user.
+    /// Failures are only logged since they do not change the import.
+    async fn send_import_notification(&self, user_id: i32, notification: ImportNotification) {
+        let preferences = match self.media_service.user_preferences(user_id).await {
+            Ok(p) => p.notifications,
+            Err(e) => {
+                tracing::error!("Could not get preferences for user {user_id}: {e:?}");
+                return;
+            }
+        };
+        let url = match preferences.webhook_url {
+            Some(url) if preferences.enabled => url,
+            _ => return,
+        };
+        let payload = notification.payload(preferences.webhook_platform);
+        if let Err(e) = send_webhook(&url, &payload).await {
+            tracing::error!("Could not send import notification to user {user_id}: {e:?}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::iter;
+
+    use super::*;
+    use crate::{
+        entities::user,
+        migrator::{Migrator, Provenance, UserLot},
+        miscellaneous::{SeenOrReviewExtraInformation, SeenShowExtraInformation},
+        models::media::ImportOrExportItemReview,
+        users::{UserPreferences, UserSinkIntegrations},
+    };
+    use chrono::{Datelike, TimeZone};
+    use rstest::rstest;
+    use sea_orm::Database;
+    use sea_orm_migration::MigratorTrait;
+
+    #[test]
+    fn test_import_step_failures() {
+        let failed = |step| ImportFailedItem::new(MetadataLot::Book, step, "1".to_owned(), None);
+        let failures = ImportStepFailures::new(&[
+            failed(ImportFailStep::TitleMatch),
+            failed(ImportFailStep::MediaDetailsFromProvider),
+            failed(ImportFailStep::TitleMatch),
+        ]);
+        assert_eq!(
+            failures,
+            ImportStepFailures {
+                title_match: 2,
+                media_details_from_provider: 1,
+                ..Default::default()
+            }
         );
-        let details = ImportResultResponse {
-            source: db_import_job.source,
+    }
+
+    #[test]
+    fn test_import_timer_estimate() {
+        let now = Utc.with_ymd_and_hms(2023, 8, 1, 12, 0, 0).unwrap();
+        let mut timer = ImportTimer::default();
+        assert_eq!(timer.estimate(10, now), None);
+        for _ in 0..IMPORT_ESTIMATE_MIN_ITEMS - 1 {
+            timer.record(
+                std::time::Duration::from_millis(300),
+                std::time::Duration::from_millis(100),
+            );
+        }
+        let estimate = timer.estimate(10, now).unwrap();
+        assert_eq!(estimate.finish_on, now + Duration::seconds(4));
+        assert!(estimate.low_confidence);
+        timer.record(
+            std::time::Duration::from_millis(300),
+            std::time::Duration::ZERO,
+        );
+        assert!(!timer.estimate(10, now).unwrap().low_confidence);
+    }
+
+    #[test]
+    fn test_import_timer_rolling_average() {
+        let mut timer = ImportTimer::default();
+        for resolution in [1, 2] {
+            for _ in 0..IMPORT_TIMING_WINDOW {
+                timer.record(
+                    std::time::Duration::from_secs(resolution),
+                    std::time::Duration::from_millis(10),
+                );
+            }
+        }
+        assert_eq!(
+            timer.throughput(),
+            ImportThroughput {
+                items: IMPORT_TIMING_WINDOW * 2,
+                resolution_ms: 2000,
+                write_ms: 10,
+            }
+        );
+    }
+
+    #[rstest]
+    #[case(None, None)]
+    #[case(Some(30), Some(90))]
+    #[case(Some(60), Some(60))]
+    #[case(Some(120), None)]
+    #[case(Some(150), None)]
+    fn test_next_import_allowed_on(
+        #[case] finished_minutes_ago: Option<i64>,
+        #[case] allowed_in_minutes: Option<i64>,
+    ) {
+        let now = Utc.with_ymd_and_hms(2023, 8, 1, 12, 0, 0).unwrap();
+        let allowed_on = next_import_allowed_on(
+            finished_minutes_ago.map(|m| now - Duration::minutes(m)),
+            Duration::minutes(120),
+            now,
+        );
+        assert_eq!(
+            allowed_on,
+            allowed_in_minutes.map(|m| now + Duration::minutes(m))
+        );
+    }
+
+    fn episode(season: i32, episode: i32, day: u32) -> ImportOrExportItemSeen {
+        ImportOrExportItemSeen {
+            started_on: None,
+            ended_on: Some(Utc.with_ymd_and_hms(2023, 1, day, 0, 0, 0).unwrap()),
+            show_season_number: Some(season),
+            show_episode_number: Some(episode),
+            podcast_episode_number: None,
+            podcast_episode_guid: None,
+            progress: None,
+            plays: None,
+            date_precision: None,
+        }
+    }
+
+    #[rstest]
+    #[case(vec![episode(1, 1, 1), episode(1, 2, 2), episode(1, 5, 3)], vec![(1, 1), (1, 2), (1, 5)])]
+    #[case(vec![episode(1, 5, 3), episode(1, 1, 1), episode(1, 2, 2)], vec![(1, 1), (1, 2), (1, 5)])]
+    #[case(vec![episode(1, 2, 2), episode(1, 2, 2), episode(2, 4, 4)], vec![(1, 2), (2, 4)])]
+    #[case(vec![episode(2, 3, 1), episode(1, 7, 2)], vec![(2, 3), (1, 7)])]
+    fn test_normalize_seen_history_keeps_gaps(
+        #[case] history: Vec<ImportOrExportItemSeen>,
+        #[case] expected: Vec<(i32, i32)>,
+    ) {
+        let episodes = normalize_seen_history(history)
+            .into_iter()
+            .map(|s| {
+                (
+                    s.show_season_number.unwrap(),
+                    s.show_episode_number.unwrap(),
+                )
+            })
+            .collect_vec();
+        assert_eq!(episodes, expected);
+    }
+
+    #[test]
+    fn test_normalize_seen_history_keeps_undated_rewatches() {
+        let undated = ImportOrExportItemSeen {
+            ended_on: None,
+            ..episode(1, 1, 1)
+        };
+        let history = vec![undated.clone(), undated.clone(), episode(1, 1, 1)];
+        assert_eq!(normalize_seen_history(history).len(), 3);
+    }
+
+    fn seen(progress: Option<i32>) -> ImportOrExportItemSeen {
+        ImportOrExportItemSeen {
+            progress,
+            ..episode(1, 1, 1)
+        }
+    }
+
+    #[rstest]
+    #[case(vec![], 0, false)]
+    #[case(vec![Some(0)], 0, true)]
+    #[case(vec![Some(0), Some(0)], 0, true)]
+    #[case(vec![None], 1, false)]
+    #[case(vec![Some(100)], 1, false)]
+    #[case(vec![Some(0), Some(40)], 1, false)]
+    fn test_partition_seen_history_skips_zero_progress(
+        #[case] progress: Vec<Option<i32>>,
+        #[case] expected_started: usize,
+        #[case] expected_only_planned: bool,
+    ) {
+        let history = progress.into_iter().map(seen).collect_vec();
+        let (started, only_planned) = partition_seen_history(&history);
+        assert_eq!(started.len(), expected_started);
+        assert!(started.iter().all(|s| s.progress != Some(0)));
+        assert_eq!(only_planned, expected_only_planned);
+    }
+
+    #[rstest]
+    #[case(None, true, false, 100)]
+    #[case(None, false, false, 100)]
+    #[case(None, false, true, 0)]
+    #[case(None, true, true, 100)]
+    #[case(Some(40), false, true, 40)]
+    fn test_seen_progress_for_undated_items(
+        #[case] progress: Option<i32>,
+        #[case] dated: bool,
+        #[case] undated_in_progress: bool,
+        #[case] expected: i32,
+    ) {
+        let mut item = seen(progress);
+        if !dated {
+            item.ended_on = None;
+        }
+        assert_eq!(seen_progress(&item, undated_in_progress), expected);
+    }
+
+    fn item(
+        identifier: &str,
+        collection: &str,
+    ) -> ImportOrExportItem<ImportOrExportItemIdentifier> {
+        ImportOrExportItem {
+            source_id: identifier.to_owned(),
+            title: None,
+            lot: MetadataLot::Movie,
+            source: MetadataSource::Tmdb,
+            identifier: ImportOrExportItemIdentifier::NeedsDetails(identifier.to_owned()),
+            seen_history: vec![episode(1, 1, 1)],
+            reviews: vec![],
+            collections: vec![collection.to_owned()],
+            state: None,
+        }
+    }
+
+    #[test]
+    fn test_merge_duplicate_items() {
+        let media = vec![
+            item("27205", "Watchlist"),
+            item("157336", "Watchlist"),
+            item("27205", "Favorites"),
+            item("27205", "Watchlist"),
+        ];
+        let (media, skipped) = merge_duplicate_items(media);
+        assert_eq!(skipped, 2);
+        assert_eq!(media.len(), 2);
+        assert_eq!(media[0].seen_history.len(), 3);
+        assert_eq!(media[0].collections, vec!["Watchlist", "Favorites"]);
+    }
+
+    #[rstest]
+    #[case(false, vec!["157336", "603", "27205"])]
+    #[case(true, vec!["27205", "157336", "603"])]
+    fn test_order_import_items(#[case] preserve_source_order: bool, #[case] expected: Vec<&str>) {
+        let mut busy = item("157336", "Watchlist");
+        busy.collections.push("Favorites".to_owned());
+        let media = vec![item("27205", "Watchlist"), busy, item("603", "Watchlist")];
+        let order = order_import_items(media, preserve_source_order)
+            .into_iter()
+            .map(|m| m.source_id)
+            .collect_vec();
+        assert_eq!(order, expected);
+    }
+
+    fn rating(value: i64, day: Option<u32>) -> ImportOrExportItemRating {
+        ImportOrExportItemRating {
+            review: Some(ImportOrExportItemReview {
+                date: day.map(|d| Utc.with_ymd_and_hms(2023, 1, d, 12, 0, 0).unwrap()),
+                spoiler: None,
+                text: None,
+                visibility: None,
+            }),
+            rating: Some(Decimal::from(value)),
+            show_season_number: None,
+            show_episode_number: None,
+            podcast_episode_number: None,
+        }
+    }
+
+    fn watch(day: u32) -> ImportOrExportItemSeen {
+        ImportOrExportItemSeen {
+            show_season_number: None,
+            show_episode_number: None,
+            ..episode(0, 0, day)
+        }
+    }
+
+    #[test]
+    fn test_tie_ratings_to_seen() {
+        let mut reviews = vec![rating(80, Some(4)), rating(60, Some(12)), rating(70, None)];
+        tie_ratings_to_seen(&mut reviews, &[watch(1), watch(3), watch(10)]);
+        let dates = reviews
+            .iter()
+            .map(|r| rating_date(r).map(|d| d.day()))
+            .collect_vec();
+        assert_eq!(dates, vec![Some(3), Some(10), None]);
+    }
+
+    #[rstest]
+    #[case(None, "Secondary rating (Stars): 4.5")]
+    #[case(Some(" "), "Secondary rating (Stars): 4.5")]
+    #[case(Some("Loved it"), "Loved it\n\nSecondary rating (Stars): 4.5")]
+    fn test_review_text_with_secondary_rating(#[case] text: Option<&str>, #[case] expected: &str) {
+        assert_eq!(
+            review_text_with_secondary_rating(text.map(String::from), "Stars", "4.5"),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_clamp_ratings() {
+        let mut reviews = vec![rating(80, None), rating(120, None), rating(-5, None)];
+        assert_eq!(
+            clamp_ratings(&mut reviews),
+            vec![Decimal::from(120), Decimal::from(-5)]
+        );
+        assert_eq!(
+            reviews.iter().map(|r| r.rating.unwrap()).collect_vec(),
+            vec![Decimal::from(80), MAX_RATING, Decimal::ZERO]
+        );
+    }
+
+    #[test]
+    fn test_keep_latest_rating() {
+        let mut text_only = rating(0, Some(20));
+        text_only.rating = None;
+        let reviews = vec![
+            rating(80, Some(4)),
+            rating(60, Some(12)),
+            rating(70, None),
+            text_only,
+        ];
+        let kept = keep_latest_rating(reviews);
+        assert_eq!(kept.len(), 2);
+        assert_eq!(kept[0].rating, Some(Decimal::from(60)));
+        assert_eq!(kept[1].rating, None);
+    }
+
+    #[rstest]
+    #[case(None, vec!["Watchlist", "Favorites", "Owned", "To Lend"])]
+    #[case(Some(vec!["favorites", "Owned"]), vec!["Watchlist", "Favorites", "Owned"])]
+    #[case(Some(vec![]), vec!["Watchlist"])]
+    fn test_map_collections(#[case] allowlist: Option<Vec<&str>>, #[case] expected: Vec<&str>) {
+        let options = ImportOptions {
+            metadata_enrichment: ImportMetadataEnrichment::Immediate,
+            merge_with_last_report: false,
+            skip_existing_seen: false,
+            review_visibility: None,
+            lots: vec![],
+            watchlist_collection: "Watchlist".to_owned(),
+            undated_seen_in_progress: false,
+            skip_summary_recalculation: false,
+            min_match_confidence: None,
+            tag_collection_allowlist: allowlist.map(|a| a.into_iter().map(String::from).collect()),
+            latest_rating_only: false,
+            collection_suffix: None,
+            seen_batch_size: None,
+            title_strip_patterns: None,
+            keep_unmatched: false,
+            state_overwrite: ImportStateOverwrite::NeverRegress,
+            group_collections: false,
+            log_succeeded_items: false,
+            preserve_source_order: false,
+            mark_imported_reviews_spoiler: false,
+            confirm_matches_below: None,
+        };
+        let collections = ["Watchlist", "Favorites", "Owned", "To Lend"]
+            .map(String::from)
+            .to_vec();
+        assert_eq!(map_collections(collections, &options), expected);
+    }
+
+    #[test]
+    fn test_podcast_episode_number_by_guid() {
+        let episodes = vec![
+            PodcastEpisode {
+                number: 1,
+                id: "1000".to_owned(),
+                guid: Some("urn:episode:a".to_owned()),
+                ..Default::default()
+            },
+            PodcastEpisode {
+                number: 2,
+                id: "urn:episode:b".to_owned(),
+                ..Default::default()
+            },
+        ];
+        assert_eq!(
+            podcast_episode_number_by_guid(&episodes, "urn:episode:a"),
+            Some(1)
+        );
+        assert_eq!(
+            podcast_episode_number_by_guid(&episodes, "urn:episode:b"),
+            Some(2)
+        );
+        assert_eq!(
+            podcast_episode_number_by_guid(&episodes, "urn:episode:c"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_compare_succeeded_items() {
+        let item = |lot, identifier: &str| ImportSucceededItem {
+            lot,
+            source: MetadataSource::Tmdb,
+            identifier: identifier.to_owned(),
+            metadata_id: 1,
+        };
+        let old = vec![
+            item(MetadataLot::Movie, "603"),
+            item(MetadataLot::Movie, "604"),
+        ];
+        let new = vec![
+            item(MetadataLot::Movie, "604"),
+            item(MetadataLot::Movie, "605"),
+            item(MetadataLot::Show, "1399"),
+        ];
+        let compared = compare_succeeded_items(&old, &new);
+        assert_eq!(compared.len(), 2);
+        let movies = compared
+            .iter()
+            .find(|c| c.lot == MetadataLot::Movie)
+            .unwrap();
+        assert_eq!(movies.only_in_new, vec![item(MetadataLot::Movie, "605")]);
+        assert_eq!(movies.only_in_old, vec![item(MetadataLot::Movie, "603")]);
+        assert_eq!(movies.in_both, vec![item(MetadataLot::Movie, "604")]);
+        let shows = compared
+            .iter()
+            .find(|c| c.lot == MetadataLot::Show)
+            .unwrap();
+        assert_eq!(shows.only_in_new.len(), 1);
+        assert!(shows.only_in_old.is_empty());
+    }
+
+    #[test]
+    fn test_resolve_failed_item() {
+        let failed = |identifier: &str| ImportFailedItem {
+            id: identifier.to_lowercase(),
+            lot: MetadataLot::Movie,
+            step: ImportFailStep::TitleMatch,
+            identifier: identifier.to_owned(),
+            error: None,
+            line: None,
+            year: None,
+            item: None,
+        };
+        let mut details = ImportResultResponse {
+            source: MediaImportSource::Movary,
             import: ImportDetails {
-                total: import.media.len() - import.failed_items.len(),
+                total: 3,
+                unsupported: 0,
             },
-            failed_items: import.failed_items,
+            failed_items: vec![failed("Heat"), failed("Alien")],
+            succeeded_items: vec![],
+            warnings: vec![],
+            pending_matches: vec![],
+            resume_cursor: None,
+            options: None,
+            reconciliation: Some(ImportReconciliation {
+                source_total: 3,
+                imported: 1,
+                failed: 2,
+                ..Default::default()
+            }),
+            summary_warning: None,
+            created_collections: vec![],
+            unavailable_providers: vec![],
+            throughput: None,
+            progress: None,
+            lots: vec![],
         };
-        self.media_service
-            .finish_import_job(db_import_job, details)
-            .await?;
-        Ok(())
+        let item = ImportSucceededItem {
+            lot: MetadataLot::Movie,
+            source: MetadataSource::Tmdb,
+            identifier: "348".to_owned(),
+            metadata_id: 1,
+        };
+        assert_eq!(details.resolve_failed_item("heat 2", item.clone()), None);
+        assert_eq!(
+            details.resolve_failed_item("alien", item.clone()),
+            Some(failed("Alien"))
+        );
+        assert_eq!(details.failed_items, vec![failed("Heat")]);
+        assert_eq!(details.succeeded_items, vec![item]);
+        let reconciliation = details.reconciliation.unwrap();
+        assert_eq!((reconciliation.imported, reconciliation.failed), (2, 1));
+    }
+
+    #[test]
+    fn test_item_to_import() {
+        let item = ImportOrExportItem {
+            source_id: "Heat".to_owned(),
+            title: None,
+            lot: MetadataLot::Movie,
+            source: MetadataSource::Tmdb,
+            identifier: ImportOrExportItemIdentifier::NeedsDetails(String::new()),
+            seen_history: vec![seen(Some(100))],
+            reviews: vec![],
+            collections: vec!["Crime".to_owned()],
+            state: None,
+        };
+        let failed = ImportFailedItem::new(
+            MetadataLot::Movie,
+            ImportFailStep::TitleMatch,
+            "Heat".to_owned(),
+            None,
+        )
+        .with_item(&item);
+        assert_eq!(failed.search_title(), Some("Heat".to_owned()));
+        let retried = item_to_import(
+            &failed.item,
+            failed.lot,
+            &failed.identifier,
+            MetadataSource::Tmdb,
+            "949".to_owned(),
+        );
+        assert!(matches!(
+            retried.identifier,
+            ImportOrExportItemIdentifier::NeedsDetails(i) if i == "949"
+        ));
+        assert_eq!(retried.seen_history.len(), 1);
+        assert_eq!(retried.collections, vec!["Crime"]);
+        let bare = item_to_import(
+            &None,
+            MetadataLot::Movie,
+            "Heat",
+            MetadataSource::Tmdb,
+            "949".to_owned(),
+        );
+        assert!(bare.seen_history.is_empty());
+    }
+
+    #[test]
+    fn test_assign_missing_ids() {
+        let mut details: ImportResultResponse = serde_json::from_value(serde_json::json!({
+            "source": "Movary",
+            "import": { "total": 2 },
+            "failed_items": [
+                { "lot": "Movie", "step": "TitleMatch", "identifier": "Heat", "error": null },
+                { "lot": "Movie", "step": "TitleMatch", "identifier": "Alien", "error": null },
+            ],
+        }))
+        .unwrap();
+        assert!(details.assign_missing_ids());
+        let ids = details
+            .failed_items
+            .iter()
+            .map(|f| f.id.clone())
+            .collect_vec();
+        assert!(ids.iter().all(|i| !i.is_empty()) && ids[0] != ids[1]);
+        assert!(!details.assign_missing_ids());
+    }
+
+    #[rstest]
+    #[case("1", Some(1999), Some(1999), ImportMatchLevel::High)]
+    #[case("1", Some(1999), Some(2003), ImportMatchLevel::Medium)]
+    #[case("1", None, Some(1999), ImportMatchLevel::Medium)]
+    #[case("0.9", Some(1999), Some(1999), ImportMatchLevel::Low)]
+    fn test_match_level(
+        #[case] confidence: &str,
+        #[case] expected_year: Option<i32>,
+        #[case] year: Option<i32>,
+        #[case] level: ImportMatchLevel,
+    ) {
+        let confidence = confidence.parse().unwrap();
+        assert_eq!(match_level(confidence, expected_year, year), level);
+    }
+
+    #[test]
+    fn test_pending_match() {
+        let resolution = TitleResolution {
+            chosen: MediaSearchItem {
+                identifier: "603".to_owned(),
+                lot: MetadataLot::Movie,
+                title: "The Matrix".to_owned(),
+                image: None,
+                publish_year: Some(1999),
+                popularity: None,
+            },
+            confidence: Decimal::ONE,
+            alternatives: vec![],
+        };
+        let pending = |matching: &TitleMatching, year| {
+            matching
+                .pending_match(
+                    &resolution,
+                    MetadataLot::Movie,
+                    MetadataSource::Tmdb,
+                    "The Matrix",
+                    year,
+                    Some(2),
+                )
+                .map(|p| p.level)
+        };
+        let matching = TitleMatching::default();
+        assert_eq!(pending(&matching, None), None);
+        let matching = matching.confirming_below(Some(ImportMatchLevel::High));
+        assert_eq!(pending(&matching, Some(1999)), None);
+        assert_eq!(pending(&matching, None), Some(ImportMatchLevel::Medium));
+    }
+
+    #[test]
+    fn test_resolve_pending_match() {
+        let pending = |title: &str| ImportPendingMatch {
+            id: title.to_lowercase(),
+            lot: MetadataLot::Movie,
+            source: MetadataSource::Tmdb,
+            title: title.to_owned(),
+            line: Some(2),
+            level: ImportMatchLevel::Low,
+            confidence: Decimal::new(8, 1),
+            chosen: MediaSearchItem {
+                identifier: "949".to_owned(),
+                lot: MetadataLot::Movie,
+                title: "Heat".to_owned(),
+                image: None,
+                publish_year: Some(1995),
+                popularity: None,
+            },
+            alternatives: vec![],
+            item: None,
+        };
+        let mut details = ImportResultResponse {
+            source: MediaImportSource::Movary,
+            import: ImportDetails {
+                total: 2,
+                unsupported: 0,
+            },
+            failed_items: vec![],
+            succeeded_items: vec![],
+            warnings: vec![],
+            pending_matches: vec![pending("Heat"), pending("Heat 2")],
+            resume_cursor: None,
+            options: None,
+            reconciliation: Some(ImportReconciliation {
+                source_total: 2,
+                pending: 2,
+                ..Default::default()
+            }),
+            summary_warning: None,
+            created_collections: vec![],
+            unavailable_providers: vec![],
+            throughput: None,
+            progress: None,
+            lots: vec![],
+        };
+        let item = ImportSucceededItem {
+            lot: MetadataLot::Movie,
+            source: MetadataSource::Tmdb,
+            identifier: "949".to_owned(),
+            metadata_id: 1,
+        };
+        assert_eq!(details.resolve_pending_match("alien", None), None);
+        assert_eq!(
+            details.resolve_pending_match("heat 2", None),
+            Some(pending("Heat 2"))
+        );
+        assert_eq!(details.failed_items[0].identifier, "Heat 2");
+        assert_eq!(
+            details.resolve_pending_match("heat", Some(item.clone())),
+            Some(pending("Heat"))
+        );
+        assert_eq!(details.succeeded_items, vec![item]);
+        assert!(details.pending_matches.is_empty());
+        let reconciliation = details.reconciliation.unwrap();
+        assert_eq!(
+            (
+                reconciliation.imported,
+                reconciliation.failed,
+                reconciliation.pending
+            ),
+            (1, 1, 0)
+        );
+    }
+
+    #[rstest]
+    #[case(
+        MediaImportSource::Movary,
+        "Blade Runner (1982) [Blu-ray]",
+        "Blade Runner"
+    )]
+    #[case(MediaImportSource::Movary, "Aliens - Special Edition", "Aliens")]
+    #[case(
+        MediaImportSource::Movary,
+        "Apocalypse Now (Director's Cut)",
+        "Apocalypse Now"
+    )]
+    #[case(
+        MediaImportSource::Goodreads,
+        "Leviathan Wakes (The Expanse, #1)",
+        "Leviathan Wakes"
+    )]
+    #[case(MediaImportSource::Goodreads, "1984", "1984")]
+    #[case(MediaImportSource::Goodreads, "(2019)", "(2019)")]
+    fn test_title_normalization(
+        #[case] source: MediaImportSource,
+        #[case] title: &str,
+        #[case] expected: &str,
+    ) {
+        let matching = TitleMatching::new(None, &default_title_strip_patterns(source)).unwrap();
+        assert_eq!(matching.normalize(title), expected);
+    }
+
+    /// A database with anime from AniList, each with its title, year and
+    /// alternative titles.
+    async fn known_media_db(media: &[(&str, &str, Option<i32>, &[&str])]) -> DatabaseConnection {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        Migrator::up(&db, None).await.unwrap();
+        for (identifier, title, publish_year, alternative_titles) in media {
+            let metadata = metadata::ActiveModel {
+                created_on: ActiveValue::Set(Utc::now()),
+                last_updated_on: ActiveValue::Set(Utc::now()),
+                lot: ActiveValue::Set(MetadataLot::Anime),
+                source: ActiveValue::Set(MetadataSource::Anilist),
+                title: ActiveValue::Set(title.to_string()),
+                identifier: ActiveValue::Set(identifier.to_string()),
+                publish_year: ActiveValue::Set(*publish_year),
+                images: ActiveValue::Set(Default::default()),
+                creators: ActiveValue::Set(Default::default()),
+                specifics: ActiveValue::Set(MediaSpecifics::Unknown),
+                ..Default::default()
+            }
+            .insert(&db)
+            .await
+            .unwrap();
+            let titles = iter::once((title, false))
+                .chain(alternative_titles.iter().map(|t| (t, true)))
+                .map(|(t, alias)| metadata_title::ActiveModel {
+                    metadata_id: ActiveValue::Set(metadata.id),
+                    title: ActiveValue::Set(t.to_lowercase()),
+                    lot: ActiveValue::Set(MetadataLot::Anime),
+                    publish_year: ActiveValue::Set(*publish_year),
+                    alias: ActiveValue::Set(alias),
+                });
+            MetadataTitle::insert_many(titles).exec(&db).await.unwrap();
+        }
+        db
+    }
+
+    #[tokio::test]
+    async fn test_known_media_matches_alternative_titles() {
+        let matching = TitleMatching::default().with_known_media(
+            known_media_db(&[
+                (
+                    "199",
+                    "Spirited Away",
+                    None,
+                    &["Sen to Chihiro no Kamikakushi"],
+                ),
+                ("1", "Cowboy Bebop", None, &["Bebop"]),
+                ("5", "Cowboy Bebop: The Movie", None, &["Bebop"]),
+            ])
+            .await,
+        );
+        async fn known(
+            matching: &TitleMatching,
+            lot: MetadataLot,
+            source: MetadataSource,
+            title: &str,
+        ) -> Option<String> {
+            matching
+                .known_media(lot, source, title, None)
+                .await
+                .unwrap()
+                .map(|m| m.identifier)
+        }
+        let anime = |title| {
+            known(
+                &matching,
+                MetadataLot::Anime,
+                MetadataSource::Anilist,
+                title,
+            )
+        };
+        assert_eq!(
+            anime("sen to chihiro no kamikakushi").await.as_deref(),
+            Some("199")
+        );
+        assert_eq!(anime(" SPIRITED AWAY ").await.as_deref(), Some("199"));
+        assert_eq!(
+            known(
+                &matching,
+                MetadataLot::Movie,
+                MetadataSource::Tmdb,
+                "Spirited Away"
+            )
+            .await,
+            None
+        );
+        assert_eq!(anime("Bebop").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_known_media_checks_the_year() {
+        let matching = TitleMatching::default().with_known_media(
+            known_media_db(&[
+                ("1", "Hellsing", Some(2001), &[]),
+                ("2", "Hellsing Ultimate", Some(2006), &["Hellsing"]),
+            ])
+            .await,
+        );
+        async fn known(matching: &TitleMatching, year: Option<i32>) -> Option<String> {
+            matching
+                .known_media(
+                    MetadataLot::Anime,
+                    MetadataSource::Anilist,
+                    "Hellsing",
+                    year,
+                )
+                .await
+                .unwrap()
+                .map(|m| m.identifier)
+        }
+        assert_eq!(known(&matching, None).await, None);
+        assert_eq!(known(&matching, Some(2001)).await.as_deref(), Some("1"));
+        assert_eq!(known(&matching, Some(2006)).await.as_deref(), Some("2"));
+        assert_eq!(known(&matching, Some(2020)).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_known_media_remembers_matches() {
+        let matching = TitleMatching::default();
+        let item = MediaSearchItem {
+            identifier: "20".to_owned(),
+            lot: MetadataLot::Anime,
+            title: "Naruto".to_owned(),
+            image: None,
+            publish_year: Some(2002),
+            popularity: None,
+        };
+        matching.remember_match(MetadataLot::Anime, MetadataSource::Anilist, "Naruto", &item);
+        async fn known(
+            matching: &TitleMatching,
+            title: &str,
+            year: Option<i32>,
+        ) -> Option<MediaSearchItem> {
+            matching
+                .known_media(MetadataLot::Anime, MetadataSource::Anilist, title, year)
+                .await
+                .unwrap()
+        }
+        assert_eq!(known(&matching, "naruto", None).await, Some(item.clone()));
+        assert_eq!(known(&matching, "Naruto", Some(2002)).await, Some(item));
+        assert_eq!(known(&matching, "Naruto", Some(2023)).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_known_resolution_confidence() {
+        let matching = TitleMatching::default()
+            .confirming_below(Some(ImportMatchLevel::Medium))
+            .with_known_media(
+                known_media_db(&[(
+                    "199",
+                    "Spirited Away",
+                    Some(2001),
+                    &["Sen to Chihiro no Kamikakushi"],
+                )])
+                .await,
+            );
+        async fn pending(matching: &TitleMatching, title: &str) -> Option<ImportMatchLevel> {
+            let resolution = matching
+                .known_resolution(MetadataLot::Anime, MetadataSource::Anilist, title, None)
+                .await
+                .unwrap()
+                .unwrap();
+            matching
+                .pending_match(
+                    &resolution,
+                    MetadataLot::Anime,
+                    MetadataSource::Anilist,
+                    title,
+                    None,
+                    None,
+                )
+                .map(|p| p.level)
+        }
+        assert_eq!(pending(&matching, "Spirited Away").await, None);
+        assert_eq!(
+            pending(&matching, "Sen to Chihiro no Kamikakushi").await,
+            Some(ImportMatchLevel::Low)
+        );
+    }
+
+    #[rstest]
+    #[case(None, MediaState::InProgress, false)]
+    #[case(Some(MediaState::Completed), MediaState::InProgress, true)]
+    #[case(Some(MediaState::Completed), MediaState::PlanTo, true)]
+    #[case(Some(MediaState::Completed), MediaState::Dropped, true)]
+    #[case(Some(MediaState::InProgress), MediaState::Completed, false)]
+    #[case(Some(MediaState::InProgress), MediaState::OnHold, false)]
+    #[case(Some(MediaState::Dropped), MediaState::PlanTo, true)]
+    fn test_state_regresses(
+        #[case] existing: Option<MediaState>,
+        #[case] new: MediaState,
+        #[case] expected: bool,
+    ) {
+        assert_eq!(state_regresses(existing, new), expected);
+    }
+
+    #[rstest]
+    #[case(vec![], None)]
+    #[case(vec![(SeenState::InProgress, false)], Some(MediaState::InProgress))]
+    #[case(vec![(SeenState::InProgress, false), (SeenState::Completed, false)], Some(MediaState::Completed))]
+    #[case(vec![(SeenState::Completed, true)], None)]
+    #[case(vec![(SeenState::Dropped, false)], None)]
+    fn test_seen_history_state(
+        #[case] history: Vec<(SeenState, bool)>,
+        #[case] expected: Option<MediaState>,
+    ) {
+        let history = history
+            .into_iter()
+            .map(|(state, episode)| seen::Model {
+                id: 1,
+                progress: 100,
+                started_on: None,
+                finished_on: None,
+                last_updated_on: Utc::now(),
+                user_id: 1,
+                metadata_id: 1,
+                state,
+                plays: 1,
+                provenance: Provenance::Manual,
+                import_source: None,
+                import_report_id: None,
+                date_precision: SeenDatePrecision::Day,
+                deleted_at: None,
+                extra_information: episode.then(|| {
+                    SeenOrReviewExtraInformation::Show(SeenShowExtraInformation {
+                        season: 1,
+                        episode: 1,
+                    })
+                }),
+                show_information: None,
+                podcast_information: None,
+            })
+            .collect_vec();
+        assert_eq!(seen_history_state(&history), expected);
+    }
+
+    #[test]
+    fn test_unmatched_stub() {
+        let failed = ImportFailedItem::new(
+            MetadataLot::Book,
+            ImportFailStep::TitleMatch,
+            "The Unfindable Book".to_owned(),
+            Some("No ISBN found".to_owned()),
+        )
+        .at_line(3);
+        let stub = unmatched_stub(&failed, MediaImportSource::Goodreads);
+        assert_eq!(stub.source, MetadataSource::Custom);
+        assert_eq!(stub.collections, vec![NEEDS_REVIEW_COLLECTION]);
+        match stub.identifier {
+            ImportOrExportItemIdentifier::AlreadyFilled(details) => {
+                assert_eq!(details.title, "The Unfindable Book");
+                assert_eq!(details.identifier, "unmatched-Book-the unfindable book");
+                assert!(matches!(details.specifics, MediaSpecifics::Book(_)));
+            }
+            _ => panic!("the stub should not need details"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_import_analytics_model() {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        Migrator::up(&db, None).await.unwrap();
+        let started_on = Utc.with_ymd_and_hms(2023, 8, 1, 12, 0, 0).unwrap();
+        let model = failed_import_analytics_model(
+            MediaImportSource::Trakt,
+            started_on,
+            started_on + Duration::seconds(3),
+            Some(2),
+        )
+        .insert(&db)
+        .await
+        .unwrap();
+        assert!(!model.success);
+        assert_eq!(model.duration_ms, 3000);
+        assert_eq!(model.source_total, 0);
+        assert_eq!(model.api_calls, Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_trash_import_results_keeps_manual_progress() {
+        let db =
+            known_media_db(&[("1", "Cowboy Bebop", None, &[]), ("2", "Trigun", None, &[])]).await;
+        let user = user::ActiveModel {
+            name: ActiveValue::Set("diana".to_owned()),
+            password: ActiveValue::Set("password".to_owned()),
+            lot: ActiveValue::Set(UserLot::Normal),
+            preferences: ActiveValue::Set(UserPreferences::default()),
+            sink_integrations: ActiveValue::Set(UserSinkIntegrations(vec![])),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+        let report = media_import_report::ActiveModel {
+            user_id: ActiveValue::Set(user.id),
+            source: ActiveValue::Set(MediaImportSource::MediaTracker),
+            started_on: ActiveValue::Set(Utc::now()),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+        let in_progress = collection::ActiveModel {
+            created_on: ActiveValue::Set(Utc::now()),
+            name: ActiveValue::Set(DefaultCollection::InProgress.to_string()),
+            user_id: ActiveValue::Set(user.id),
+            visibility: ActiveValue::Set(Visibility::Private),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+        let started = |metadata_id, report_id: Option<i32>| seen::ActiveModel {
+            progress: ActiveValue::Set(40),
+            last_updated_on: ActiveValue::Set(Utc::now()),
+            user_id: ActiveValue::Set(user.id),
+            metadata_id: ActiveValue::Set(metadata_id),
+            state: ActiveValue::Set(SeenState::InProgress),
+            plays: ActiveValue::Set(1),
+            provenance: ActiveValue::Set(Provenance::Manual),
+            import_report_id: ActiveValue::Set(report_id),
+            date_precision: ActiveValue::Set(SeenDatePrecision::Day),
+            ..Default::default()
+        };
+        // DEV: both are started by the import, and the first one by hand too
+        Seen::insert_many([
+            started(1, Some(report.id)),
+            started(2, Some(report.id)),
+            started(1, None),
+        ])
+        .exec(&db)
+        .await
+        .unwrap();
+        MetadataToCollection::insert_many([1, 2].map(|metadata_id| {
+            metadata_to_collection::ActiveModel {
+                metadata_id: ActiveValue::Set(metadata_id),
+                collection_id: ActiveValue::Set(in_progress.id),
+            }
+        }))
+        .exec(&db)
+        .await
+        .unwrap();
+
+        let txn = db.begin().await.unwrap();
+        let (deleted, metadata_ids) = trash_import_results(&txn, user.id, report.id)
+            .await
+            .unwrap();
+        txn.commit().await.unwrap();
+        assert_eq!(deleted.seen, 2);
+        assert_eq!(deleted.reviews, 0);
+        assert_eq!(metadata_ids, vec![1, 2]);
+        let remaining = MetadataToCollection::find()
+            .filter(metadata_to_collection::Column::CollectionId.eq(in_progress.id))
+            .all(&db)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|m| m.metadata_id)
+            .collect_vec();
+        assert_eq!(remaining, vec![1]);
+        let trashed = Seen::find()
+            .filter(seen::Column::DeletedAt.is_not_null())
+            .all(&db)
+            .await
+            .unwrap();
+        assert!(trashed
+            .iter()
+            .all(|s| s.import_report_id == Some(report.id)));
+
+        // DEV: running it again finds nothing left to delete
+        let (deleted, _) = trash_import_results(&db, user.id, report.id).await.unwrap();
+        assert_eq!(deleted.seen, 0);
+    }
+
+    #[test]
+    fn test_unmatched_stub_keeps_the_item() {
+        let item = ImportOrExportItem {
+            source_id: "Dune".to_owned(),
+            title: None,
+            lot: MetadataLot::Movie,
+            source: MetadataSource::Tmdb,
+            identifier: ImportOrExportItemIdentifier::NeedsDetails(String::new()),
+            seen_history: vec![seen(None)],
+            reviews: vec![],
+            collections: vec!["Favorites".to_owned()],
+            state: Some(MediaState::Completed),
+        };
+        let mut failed = ImportFailedItem::new(
+            MetadataLot::Movie,
+            ImportFailStep::TitleMatch,
+            "Dune".to_owned(),
+            None,
+        )
+        .with_item(&item);
+        failed.year = Some(1984);
+        let stub = unmatched_stub(&failed, MediaImportSource::Movary);
+        assert_eq!(stub.seen_history.len(), 1);
+        assert_eq!(stub.collections, vec!["Favorites", NEEDS_REVIEW_COLLECTION]);
+        assert_eq!(stub.state, Some(MediaState::Completed));
+        match stub.identifier {
+            ImportOrExportItemIdentifier::AlreadyFilled(details) => {
+                assert_eq!(details.identifier, "unmatched-Movie-dune-1984");
+                assert_eq!(details.publish_year, Some(1984));
+            }
+            _ => panic!("the stub should not need details"),
+        }
+    }
+
+    #[test]
+    fn test_map_collections_with_suffix() {
+        let options = ImportOptions {
+            metadata_enrichment: ImportMetadataEnrichment::Immediate,
+            merge_with_last_report: false,
+            skip_existing_seen: false,
+            review_visibility: None,
+            lots: vec![],
+            watchlist_collection: "To Watch".to_owned(),
+            undated_seen_in_progress: false,
+            skip_summary_recalculation: false,
+            min_match_confidence: None,
+            tag_collection_allowlist: None,
+            latest_rating_only: false,
+            collection_suffix: Some("Trakt 2023-08-02 #12".to_owned()),
+            seen_batch_size: None,
+            title_strip_patterns: None,
+            keep_unmatched: false,
+            state_overwrite: ImportStateOverwrite::NeverRegress,
+            group_collections: false,
+            log_succeeded_items: false,
+            preserve_source_order: false,
+            mark_imported_reviews_spoiler: false,
+            confirm_matches_below: None,
+        };
+        let collections = ["Watchlist", "Owned"].map(String::from).to_vec();
+        assert_eq!(
+            map_collections(collections, &options),
+            vec![
+                "To Watch (Trakt 2023-08-02 #12)",
+                "Owned (Trakt 2023-08-02 #12)"
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case("Inception", "Inception", "1")]
+    #[case("The Lord of the Rings", "the lord of the rings!", "1")]
+    #[case("Dune: Part Two", "Dune Part Two", "1")]
+    #[case("Dune", "Dunes", "0.8")]
+    #[case("Inception", "Interstellar", "0.33")]
+    fn test_title_confidence(#[case] expected: &str, #[case] candidate: &str, #[case] score: &str) {
+        assert_eq!(
+            title_confidence(expected, candidate),
+            score.parse().unwrap()
+        );
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Row {
+        title: String,
+    }
+
+    #[test]
+    fn test_csv_records_reports_lines() {
+        let content = "title\nDune\n\"Project\nHail Mary\"\nThe Hobbit\n";
+        let records = csv_records::<Row>(content);
+        let lines = records.iter().map(|(l, _)| *l).collect_vec();
+        assert_eq!(lines, vec![2, 3, 5]);
+        assert_eq!(records[1].1.as_ref().unwrap().title, "Project\nHail Mary");
+    }
+
+    #[test]
+    fn test_provider_breaker_skips_after_consecutive_failures() {
+        let (movie, tmdb) = (MetadataLot::Movie, MetadataSource::Tmdb);
+        let mut breaker = ProviderBreaker::new(2, vec![]);
+        breaker.record(movie, tmdb, false);
+        breaker.record(movie, tmdb, true);
+        breaker.record(movie, tmdb, false);
+        assert!(!breaker.skip(movie, tmdb));
+        breaker.record(MetadataLot::Show, tmdb, false);
+        breaker.record(movie, tmdb, false);
+        assert!(breaker.skip(movie, tmdb));
+        assert!(breaker.skip(movie, tmdb));
+        assert!(!breaker.skip(MetadataLot::Show, tmdb));
+        assert_eq!(
+            breaker.unavailable,
+            vec![ImportUnavailableProvider {
+                lot: movie,
+                source: tmdb,
+                skipped: 2,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_provider_breaker_can_be_disabled() {
+        let mut breaker = ProviderBreaker::new(0, vec![]);
+        for _ in 0..10 {
+            breaker.record(MetadataLot::Book, MetadataSource::Openlibrary, false);
+        }
+        assert!(!breaker.skip(MetadataLot::Book, MetadataSource::Openlibrary));
     }
 }
@@ -1,25 +1,68 @@
 use async_graphql::Result;
-use chrono::NaiveDate;
-use csv::Reader;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize};
 
 use crate::{
     importer::{
-        DeployMovaryImportInput, ImportFailStep, ImportFailedItem, ImportOrExportItem,
-        ImportOrExportItemIdentifier, ImportResult,
+        csv_records, resolve_by_title, DeployMovaryImportInput, ImportFailStep, ImportFailedItem,
+        ImportOrExportItem, ImportOrExportItemIdentifier, ImportResult, ImportWarningItem,
+        ResolvedIdentifier, TitleMatching,
     },
-    migrator::{MetadataLot, MetadataSource},
+    migrator::{MediaState, MetadataLot, MetadataSource},
+    miscellaneous::DefaultCollection,
     models::media::{ImportOrExportItemRating, ImportOrExportItemReview, ImportOrExportItemSeen},
-    utils::convert_naive_to_utc,
+    traits::MediaProvider,
+    utils::{convert_naive_to_utc, convert_string_to_date_with_format},
 };
 
+/// The format of the dates in Movary exports
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// An example of the history file exported by Movary
+pub const HISTORY_TEMPLATE: &str = "title,year,tmdbId,imdbId,watchedAt,plays,comment
+Inception,2010,27205,tt1375666,2023-01-15,2,Watched with friends
+Interstellar,2014,157336,tt0816692,2023-02-03,1,
+";
+
+/// An example of the ratings file exported by Movary
+pub const RATINGS_TEMPLATE: &str = "title,year,tmdbId,imdbId,userRating
+Inception,2010,27205,tt1375666,9
+Interstellar,2014,157336,tt0816692,8
+";
+
+/// An example of the watchlist file exported by Movary
+pub const WATCHLIST_TEMPLATE: &str = "title,year,tmdbId,imdbId,addedAt
+Dune: Part Two,2024,693134,tt15239678,2023-03-01
+";
+
+/// Movary leaves the numeric columns empty for movies that it could not match.
+fn deserialize_optional_number<'de, D>(
+    deserializer: D,
+) -> std::result::Result<Option<i32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Value {
+        Number(i32),
+        Text(String),
+    }
+    Ok(match Value::deserialize(deserializer)? {
+        Value::Number(n) => Some(n),
+        Value::Text(s) => s.trim().parse().ok(),
+    })
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct Common {
     title: String,
-    tmdb_id: i32,
+    #[serde(default, deserialize_with = "deserialize_optional_number")]
+    year: Option<i32>,
+    #[serde(default, deserialize_with = "deserialize_optional_number")]
+    tmdb_id: Option<i32>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -35,120 +78,273 @@ struct Rating {
 struct History {
     #[serde(flatten)]
     common: Common,
-    watched_at: NaiveDate,
+    watched_at: String,
+    /// The number of times the movie was watched on this date
+    #[serde(default, deserialize_with = "deserialize_optional_number")]
+    plays: Option<i32>,
     comment: Option<String>,
 }
 
-pub async fn import(input: DeployMovaryImportInput) -> Result<ImportResult> {
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Watchlist {
+    #[serde(flatten)]
+    common: Common,
+}
+
+/// Get the TMDB id of a movie, searching for its title and year if Movary could
+/// not match it.
+async fn resolve_identifier(
+    provider: &(impl MediaProvider + Sync + ?Sized),
+    common: &Common,
+    matching: &TitleMatching,
+    line: u64,
+) -> std::result::Result<ResolvedIdentifier, String> {
     let lot = MetadataLot::Movie;
-    let source = MetadataSource::Tmdb;
-    let mut media = vec![];
-    let mut failed_items = vec![];
-    let mut ratings_reader = Reader::from_reader(input.ratings.as_bytes());
-    for (idx, result) in ratings_reader.deserialize().enumerate() {
-        let record: Rating = match result {
-            Ok(r) => r,
-            Err(e) => {
-                failed_items.push(ImportFailedItem {
-                    lot,
-                    step: ImportFailStep::InputTransformation,
-                    identifier: idx.to_string(),
-                    error: Some(e.to_string()),
-                });
-                continue;
+    if let Some(tmdb_id) = common.tmdb_id {
+        return Ok(ResolvedIdentifier::Found(tmdb_id.to_string(), None));
+    }
+    let error = "No TMDB id found and could not find it by title";
+    match resolve_by_title(
+        provider,
+        lot,
+        MetadataSource::Tmdb,
+        &common.title,
+        None,
+        common.year,
+        matching,
+    )
+    .await
+    {
+        Ok(Some(mut resolution)) => {
+            if let Some(year) = common.year {
+                if resolution.chosen.publish_year != Some(year) {
+                    if let Some(pos) = resolution.alternatives.iter().position(|a| {
+                        a.publish_year == Some(year)
+                            && matching
+                                .min_confidence
+                                .map_or(true, |m| matching.confidence(&common.title, &a.title) >= m)
+                    }) {
+                        let chosen = resolution.alternatives.remove(pos);
+                        let previous = std::mem::replace(&mut resolution.chosen, chosen);
+                        resolution.alternatives.insert(0, previous);
+                        resolution.confidence =
+                            matching.confidence(&common.title, &resolution.chosen.title);
+                    }
+                }
             }
-        };
-        media.push(ImportOrExportItem {
-            source_id: record.common.title,
-            lot,
-            source,
-            identifier: ImportOrExportItemIdentifier::NeedsDetails(
-                record.common.tmdb_id.to_string(),
+            if let Some(p) = matching.pending_match(
+                &resolution,
+                lot,
+                MetadataSource::Tmdb,
+                &common.title,
+                common.year,
+                Some(line),
+            ) {
+                return Ok(ResolvedIdentifier::Pending(p));
+            }
+            let warning = resolution.warning(lot, &common.title);
+            Ok(ResolvedIdentifier::Found(
+                resolution.chosen.identifier,
+                warning,
+            ))
+        }
+        Err(e) => Err(format!("{error}: {e}")),
+        _ => Err(error.to_owned()),
+    }
+}
+
+/// An item for a movie with none of the data of its records yet.
+fn movie_item(title: String) -> ImportOrExportItem<ImportOrExportItemIdentifier> {
+    ImportOrExportItem {
+        source_id: title.clone(),
+        title: Some(title),
+        lot: MetadataLot::Movie,
+        source: MetadataSource::Tmdb,
+        identifier: ImportOrExportItemIdentifier::NeedsDetails(String::new()),
+        seen_history: vec![],
+        reviews: vec![],
+        collections: vec![],
+        state: None,
+    }
+}
+
+/// Find the item for this movie, creating it if it has not been seen yet.
+/// Movies are told apart by their TMDB id, since titles are not unique.
+fn find_or_insert(
+    media: &mut Vec<ImportOrExportItem<ImportOrExportItemIdentifier>>,
+    title: String,
+    identifier: String,
+) -> &mut ImportOrExportItem<ImportOrExportItemIdentifier> {
+    let existing = media.iter().position(|m| {
+        matches!(&m.identifier, ImportOrExportItemIdentifier::NeedsDetails(i) if *i == identifier)
+    });
+    match existing {
+        Some(pos) => &mut media[pos],
+        None => {
+            let mut item = movie_item(title);
+            item.identifier = ImportOrExportItemIdentifier::NeedsDetails(identifier);
+            media.push(item);
+            media.last_mut().unwrap()
+        }
+    }
+}
+
+/// Parse the records of a file, recording the ones that can not be parsed as
+/// failed items.
+fn parse_records<T: DeserializeOwned>(content: &str, result: &mut ImportResult) -> Vec<(u64, T)> {
+    let mut records = vec![];
+    for (idx, (line, record)) in csv_records(content).into_iter().enumerate() {
+        match record {
+            Ok(r) => records.push((line, r)),
+            Err(e) => result.failed_items.push(
+                ImportFailedItem::new(
+                    MetadataLot::Movie,
+                    ImportFailStep::InputTransformation,
+                    idx.to_string(),
+                    Some(e.to_string()),
+                )
+                .at_line(line),
             ),
-            seen_history: vec![],
-            reviews: vec![ImportOrExportItemRating {
-                // DEV: Rates items out of 10
-                rating: Some(record.user_rating.saturating_mul(dec!(10))),
-                review: None,
-                show_season_number: None,
-                show_episode_number: None,
-                podcast_episode_number: None,
-            }],
-            collections: vec![],
-        })
+        }
     }
-    let mut history_reader = Reader::from_reader(input.history.as_bytes());
-    for (idx, result) in history_reader.deserialize().enumerate() {
-        let record: History = match result {
-            Ok(r) => r,
-            Err(e) => {
-                failed_items.push(ImportFailedItem {
-                    lot,
-                    step: ImportFailStep::InputTransformation,
-                    identifier: idx.to_string(),
-                    error: Some(e.to_string()),
-                });
-                continue;
-            }
-        };
-        let watched_at = Some(convert_naive_to_utc(record.watched_at));
+    records
+}
+
+/// Find the item of the movie of a record. If the movie can not be matched,
+/// `record_item` is kept as a failed item so that the record can be imported
+/// later.
+async fn matched_item<'a>(
+    result: &'a mut ImportResult,
+    provider: &(impl MediaProvider + Sync + ?Sized),
+    matching: &TitleMatching,
+    common: &Common,
+    record_item: &ImportOrExportItem<ImportOrExportItemIdentifier>,
+    line: u64,
+) -> Option<&'a mut ImportOrExportItem<ImportOrExportItemIdentifier>> {
+    let resolved = resolve_identifier(provider, common, matching, line).await;
+    let identifier = result.resolved_identifier(resolved, record_item, common.year, line)?;
+    Some(find_or_insert(
+        &mut result.media,
+        common.title.clone(),
+        identifier,
+    ))
+}
+
+pub async fn import(
+    input: DeployMovaryImportInput,
+    provider: &(impl MediaProvider + Sync + ?Sized),
+    matching: &TitleMatching,
+) -> Result<ImportResult> {
+    let lot = MetadataLot::Movie;
+    let mut result = ImportResult::default();
+    for (line, record) in parse_records::<Rating>(&input.ratings, &mut result) {
+        let mut record_item = movie_item(record.common.title.clone());
+        record_item.reviews.push(ImportOrExportItemRating {
+            // DEV: Rates items out of 10
+            rating: Some(record.user_rating.saturating_mul(dec!(10))),
+            review: None,
+            show_season_number: None,
+            show_episode_number: None,
+            podcast_episode_number: None,
+        });
+        if let Some(item) = matched_item(
+            &mut result,
+            provider,
+            matching,
+            &record.common,
+            &record_item,
+            line,
+        )
+        .await
+        {
+            item.reviews.extend(record_item.reviews);
+        }
+    }
+    let date_format = input.date_format.as_deref().unwrap_or(DATE_FORMAT);
+    for (line, record) in parse_records::<History>(&input.history, &mut result) {
+        let watched_at = convert_string_to_date_with_format(&record.watched_at, date_format)
+            .map(convert_naive_to_utc);
+        if watched_at.is_none() {
+            result.warnings.push(ImportWarningItem {
+                lot,
+                identifier: record.common.title.clone(),
+                warning: format!("Could not parse watched date: {:?}", record.watched_at),
+                alternatives: vec![],
+            });
+        }
+        // DEV: plays on the same date are recorded on a single entry since
+        // identical entries are merged before they are imported
         let seen_item = ImportOrExportItemSeen {
             started_on: None,
             ended_on: watched_at,
             show_season_number: None,
             show_episode_number: None,
             podcast_episode_number: None,
+            podcast_episode_guid: None,
+            progress: None,
+            plays: record.plays.filter(|p| *p > 1),
+            date_precision: None,
         };
         let review = record.comment.map(|c| ImportOrExportItemReview {
             spoiler: Some(false),
             text: Some(c),
+            visibility: None,
             date: watched_at,
         });
-        if let Some(media) = media
-            .iter_mut()
-            .find(|m| m.source_id == record.common.title)
-        {
-            if review.is_some() {
-                if let Some(rating) = media.reviews.last_mut() {
-                    rating.review = review;
-                } else {
-                    media.reviews.push(ImportOrExportItemRating {
-                        review,
-                        rating: None,
-                        show_season_number: None,
-                        show_episode_number: None,
-                        podcast_episode_number: None,
-                    })
-                }
+        let mut record_item = movie_item(record.common.title.clone());
+        record_item.seen_history.push(seen_item);
+        if review.is_some() {
+            record_item.reviews.push(ImportOrExportItemRating {
+                review: review.clone(),
+                rating: None,
+                show_season_number: None,
+                show_episode_number: None,
+                podcast_episode_number: None,
+            });
+        }
+        let Some(item) = matched_item(
+            &mut result,
+            provider,
+            matching,
+            &record.common,
+            &record_item,
+            line,
+        )
+        .await
+        else {
+            continue;
+        };
+        if review.is_some() {
+            if let Some(rating) = item.reviews.last_mut() {
+                rating.review = review;
+            } else {
+                item.reviews.extend(record_item.reviews);
             }
-            media.seen_history.push(seen_item);
-        } else {
-            let mut reviews = vec![];
-            if review.is_some() {
-                reviews.push(ImportOrExportItemRating {
-                    review,
-                    rating: None,
-                    show_season_number: None,
-                    show_episode_number: None,
-                    podcast_episode_number: None,
-                })
+        }
+        item.seen_history.extend(record_item.seen_history);
+    }
+    if let Some(watchlist) = input.watchlist {
+        for (line, record) in parse_records::<Watchlist>(&watchlist, &mut result) {
+            let mut record_item = movie_item(record.common.title.clone());
+            record_item
+                .collections
+                .push(DefaultCollection::Watchlist.to_string());
+            record_item.state = Some(MediaState::PlanTo);
+            if let Some(item) = matched_item(
+                &mut result,
+                provider,
+                matching,
+                &record.common,
+                &record_item,
+                line,
+            )
+            .await
+            {
+                item.collections.extend(record_item.collections);
+                item.state = record_item.state;
             }
-            media.push(ImportOrExportItem {
-                source_id: record.common.title,
-                lot,
-                source,
-                identifier: ImportOrExportItemIdentifier::NeedsDetails(
-                    record.common.tmdb_id.to_string(),
-                ),
-                seen_history: vec![seen_item],
-                reviews,
-                collections: vec![],
-            })
         }
     }
-    Ok(ImportResult {
-        collections: vec![],
-        media,
-        failed_items,
-    })
+    Ok(result)
 }
@@ -0,0 +1,190 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use async_graphql::{Error, Result};
+use sea_orm::{ColumnTrait, ConnectionTrait, Database, EntityTrait, ModelTrait, QueryFilter};
+use sea_orm_migration::{seaql_migrations, MigrationName, MigratorTrait};
+use uuid::Uuid;
+
+use crate::{
+    entities::{
+        collection, metadata,
+        prelude::{Collection, Genre, Metadata, User},
+        user,
+    },
+    file_storage::FileStorageService,
+    importer::{read_limited, DeployRyotSqliteImportInput, ImportResult},
+    migrator::{MetadataSource, Migrator},
+    miscellaneous::resolver::export_user_media,
+    models::media::{
+        CreateOrUpdateCollectionInput, ImportOrExportItem, ImportOrExportItemIdentifier,
+        MediaDetails,
+    },
+};
+
+/// The largest backup that can be imported, in bytes
+pub const MAX_BACKUP_SIZE: usize = 100 * 1024 * 1024;
+
+/// Removes the local copy of the backup once the import is done with it.
+struct TempBackup(PathBuf);
+
+impl Drop for TempBackup {
+    fn drop(&mut self) {
+        std::fs::remove_file(&self.0).ok();
+    }
+}
+
+/// The entities can only read a backup that has the same migrations applied as
+/// this instance. The backup is never migrated here since it is an uploaded file.
+async fn check_schema<C: ConnectionTrait>(db: &C) -> Result<()> {
+    let applied = seaql_migrations::Entity::find()
+        .all(db)
+        .await
+        .map_err(|_| Error::new("The file is not a Ryot backup".to_owned()))?
+        .into_iter()
+        .map(|m| m.version)
+        .collect::<HashSet<_>>();
+    let known = Migrator::migrations()
+        .iter()
+        .map(|m| m.name().to_owned())
+        .collect::<HashSet<_>>();
+    if !known.is_subset(&applied) {
+        return Err(Error::new(
+            "The backup comes from an older version of Ryot. Start the old instance with this version once to upgrade it, then make a new copy.".to_owned(),
+        ));
+    }
+    if !applied.is_subset(&known) {
+        return Err(Error::new(
+            "The backup comes from a newer version of Ryot".to_owned(),
+        ));
+    }
+    Ok(())
+}
+
+/// Custom media can not be fetched from a provider, so its details are copied
+/// over from the backup.
+async fn custom_details<C: ConnectionTrait>(db: &C, meta: metadata::Model) -> Result<MediaDetails> {
+    let genres = meta
+        .find_related(Genre)
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|g| g.name)
+        .collect();
+    Ok(MediaDetails {
+        identifier: meta.identifier,
+        title: meta.title,
+        source: meta.source,
+        description: meta.description,
+        lot: meta.lot,
+        creators: meta.creators.0,
+        genres,
+        images: meta.images.0,
+        publish_year: meta.publish_year,
+        publish_date: meta.publish_date,
+        specifics: meta.specifics,
+        groups: vec![],
+        alternative_titles: vec![],
+    })
+}
+
+pub async fn import(
+    input: DeployRyotSqliteImportInput,
+    file_storage: &FileStorageService,
+) -> Result<ImportResult> {
+    if !file_storage.is_enabled().await {
+        return Err(Error::new(
+            "File storage must be enabled to import a backup".to_owned(),
+        ));
+    }
+    let url = file_storage.get_presigned_url(input.backup_key).await;
+    let response = surf::get(url)
+        .await
+        .map_err(|e| Error::new(format!("Could not download the backup: {e}")))?;
+    let data = read_limited(response, MAX_BACKUP_SIZE, "backup").await?;
+    let backup =
+        TempBackup(std::env::temp_dir().join(format!("ryot-backup-{}.db", Uuid::new_v4())));
+    tokio::fs::write(&backup.0, data).await?;
+    import_backup(&backup.0, &input.username).await
+}
+
+/// Import the media of a user from a SQLite database at `path`.
+pub async fn import_backup(path: &Path, username: &str) -> Result<ImportResult> {
+    let db = Database::connect(format!("sqlite://{}?mode=ro", path.display()))
+        .await
+        .map_err(|e| Error::new(format!("The backup is not a SQLite database: {e}")))?;
+    check_schema(&db).await?;
+    let user = User::find()
+        .filter(user::Column::Name.eq(username))
+        .one(&db)
+        .await?
+        .ok_or_else(|| {
+            Error::new(format!(
+                "The user {:?} does not exist in the backup",
+                username
+            ))
+        })?;
+    let collections = Collection::find()
+        .filter(collection::Column::UserId.eq(user.id))
+        .all(&db)
+        .await?
+        .into_iter()
+        .map(|c| CreateOrUpdateCollectionInput {
+            name: c.name,
+            description: c.description,
+            visibility: Some(c.visibility),
+            update_id: None,
+        })
+        .collect();
+    let exported = export_user_media(&db, user.id).await?;
+    let mut metas: HashMap<String, metadata::Model> = Metadata::find()
+        .filter(
+            metadata::Column::Id.is_in(
+                exported
+                    .iter()
+                    .filter_map(|e| e.source_id.parse::<i32>().ok()),
+            ),
+        )
+        .all(&db)
+        .await?
+        .into_iter()
+        .map(|m| (m.id.to_string(), m))
+        .collect();
+    let mut media = vec![];
+    for item in exported {
+        let meta = metas.remove(&item.source_id);
+        // DEV: the ids of the backup mean nothing to this instance, so the
+        // titles are used in the report instead
+        let source_id = meta
+            .as_ref()
+            .map(|m| m.title.clone())
+            .unwrap_or(item.source_id);
+        let identifier = match meta {
+            Some(m) if item.source == MetadataSource::Custom => {
+                ImportOrExportItemIdentifier::AlreadyFilled(Box::new(custom_details(&db, m).await?))
+            }
+            _ => ImportOrExportItemIdentifier::NeedsDetails(item.identifier),
+        };
+        media.push(ImportOrExportItem {
+            source_id,
+            title: item.title,
+            lot: item.lot,
+            source: item.source,
+            identifier,
+            seen_history: item.seen_history,
+            reviews: item.reviews,
+            collections: item.collections,
+            state: item.state,
+        });
+    }
+    Ok(ImportResult {
+        collections,
+        media,
+        failed_items: vec![],
+        warnings: vec![],
+        pending_matches: vec![],
+        unsupported: 0,
+    })
+}
@@ -1,22 +1,31 @@
 use async_graphql::Result;
-use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
 use convert_case::{Case, Casing};
-use csv::Reader;
-use itertools::Itertools;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 use serde::{Deserialize, Serialize};
 
 use crate::{
     importer::{
-        DeployStoryGraphImportInput, ImportFailStep, ImportFailedItem, ImportOrExportItem,
-        ImportOrExportItemIdentifier, ImportResult,
+        csv_records, resolve_by_title, DeployStoryGraphImportInput, ImportFailStep,
+        ImportFailedItem, ImportOrExportItem, ImportOrExportItemIdentifier, ImportResult,
+        ImportWarningItem, ResolvedIdentifier, TitleMatching,
     },
-    migrator::{MetadataLot, MetadataSource},
+    migrator::{MediaState, MetadataLot, MetadataSource},
     models::media::{ImportOrExportItemRating, ImportOrExportItemReview, ImportOrExportItemSeen},
-    providers::openlibrary::OpenlibraryService,
+    traits::{IsbnProvider, MediaProvider},
+    utils::{convert_naive_to_utc, convert_string_to_date_with_format},
 };
 
+/// The format of the dates in StoryGraph exports
+const DATE_FORMAT: &str = "%Y/%m/%d";
+
+/// An example of the export file generated by StoryGraph
+pub const EXPORT_TEMPLATE: &str = r#"Title,Authors,ISBN/UID,Read Status,Last Date Read,Read Count,Star Rating,Review,Tags
+Dune,Frank Herbert,9780441172719,read,2023/01/15,1,4.5,A classic,"science fiction, favourites"
+Project Hail Mary,Andy Weir,9780593135204,currently-reading,,0,,,
+The Hobbit,J.R.R. Tolkien,9780547928227,to-read,,0,,,
+"#;
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(untagged)]
 enum ReadStatus {
@@ -27,10 +36,28 @@ enum ReadStatus {
     Other(String),
 }
 
+/// The media state that a read status corresponds to, if any.
+fn read_status_state(status: &ReadStatus) -> Option<MediaState> {
+    // DEV: the unit variants of an untagged enum only match empty values, so
+    // the statuses usually end up in `Other`
+    match status {
+        ReadStatus::ToRead => Some(MediaState::PlanTo),
+        ReadStatus::CurrentlyReading => Some(MediaState::InProgress),
+        ReadStatus::Other(s) => match s.as_str() {
+            "to-read" => Some(MediaState::PlanTo),
+            "currently-reading" => Some(MediaState::InProgress),
+            "did-not-finish" => Some(MediaState::Dropped),
+            _ => None,
+        },
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct History {
     #[serde(rename = "Title")]
     title: String,
+    #[serde(rename = "Authors")]
+    authors: Option<String>,
     #[serde(rename = "ISBN/UID")]
     isbn: Option<String>,
     #[serde(rename = "Read Status")]
@@ -47,28 +74,117 @@ struct History {
     tags: Option<String>,
 }
 
+/// The `ISBN/UID` column contains StoryGraph's own identifier for books that do
+/// not have an ISBN.
+fn parse_isbn(isbn: &str) -> Option<String> {
+    let isbn = isbn.replace(['-', ' '], "");
+    let is_valid = match isbn.len() {
+        10 => {
+            isbn[..9].chars().all(|c| c.is_ascii_digit()) && {
+                let last = isbn.chars().last().unwrap();
+                last.is_ascii_digit() || last == 'X' || last == 'x'
+            }
+        }
+        13 => isbn.chars().all(|c| c.is_ascii_digit()),
+        _ => false,
+    };
+    if is_valid {
+        Some(isbn)
+    } else {
+        None
+    }
+}
+
+/// Resolve the identifier of a book by its ISBN, falling back to searching
+/// for its title and first author.
+async fn resolve_identifier(
+    provider: &(impl MediaProvider + IsbnProvider + Sync),
+    record: &History,
+    matching: &TitleMatching,
+    line: u64,
+) -> std::result::Result<ResolvedIdentifier, String> {
+    let lot = MetadataLot::Book;
+    let isbn = record.isbn.as_deref().and_then(parse_isbn);
+    if let Some(isbn) = isbn.as_ref() {
+        if let Some(identifier) = provider.id_from_isbn(isbn).await {
+            return Ok(ResolvedIdentifier::Found(identifier, None));
+        }
+    }
+    let author = record
+        .authors
+        .as_deref()
+        .and_then(|a| a.split(',').next())
+        .map(|a| a.trim())
+        .filter(|a| !a.is_empty());
+    match resolve_by_title(
+        provider,
+        lot,
+        MetadataSource::Openlibrary,
+        &record.title,
+        author,
+        None,
+        matching,
+    )
+    .await
+    {
+        Ok(Some(resolution)) => {
+            // DEV: the export does not have the year the book was published in
+            if let Some(p) = matching.pending_match(
+                &resolution,
+                lot,
+                MetadataSource::Openlibrary,
+                &record.title,
+                None,
+                Some(line),
+            ) {
+                return Ok(ResolvedIdentifier::Pending(p));
+            }
+            let warning = resolution.warning(lot, &record.title);
+            Ok(ResolvedIdentifier::Found(
+                resolution.chosen.identifier,
+                warning,
+            ))
+        }
+        result => {
+            let error = match isbn {
+                Some(isbn) => format!(
+                    "Could not convert ISBN: {} to Openlibrary ID or find it by title",
+                    isbn
+                ),
+                None => "No ISBN found and could not find it by title".to_owned(),
+            };
+            Err(match result {
+                Err(e) => format!("{error}: {e}"),
+                _ => error,
+            })
+        }
+    }
+}
+
 pub async fn import(
     input: DeployStoryGraphImportInput,
-    openlibrary_service: &OpenlibraryService,
+    provider: &(impl MediaProvider + IsbnProvider + Sync),
+    matching: &TitleMatching,
 ) -> Result<ImportResult> {
     let lot = MetadataLot::Book;
     let source = MetadataSource::Openlibrary;
-    let mut media = vec![];
-    let mut failed_items = vec![];
-    let ratings_reader = Reader::from_reader(input.export.as_bytes())
-        .deserialize()
-        .collect_vec();
-    let total = ratings_reader.len();
-    for (idx, result) in ratings_reader.into_iter().enumerate() {
-        let record: History = match result {
+    let mut result = ImportResult::default();
+    let date_format = input.date_format.as_deref().unwrap_or(DATE_FORMAT);
+    let records = csv_records(&input.export);
+    let total = records.len();
+    for (idx, (line, record)) in records.into_iter().enumerate() {
+        let record: History = match record {
             Ok(r) => r,
             Err(e) => {
-                failed_items.push(ImportFailedItem {
-                    lot,
-                    step: ImportFailStep::InputTransformation,
-                    identifier: idx.to_string(),
-                    error: Some(e.to_string()),
-                });
+                result.failed_items.push(
+                    ImportFailedItem::new(
+                        lot,
+                        ImportFailStep::InputTransformation,
+                        idx.to_string(),
+                        Some(e.to_string()),
+                    )
+                    .at_line(line),
+                );
                 continue;
             }
         };
@@ -76,80 +192,135 @@ pub async fn import(
             "Getting details for {title:?} ({idx}/{total})",
             title = record.title
         );
-        if let Some(isbn) = record.isbn {
-            if let Some(identifier) = openlibrary_service.id_from_isbn(&isbn).await {
-                let mut seen_history = vec![
-                    ImportOrExportItemSeen {
-                        started_on: None,
-                        ended_on: None,
-                        show_season_number: None,
-                        show_episode_number: None,
-                        podcast_episode_number: None
-                    };
-                    record.read_count
-                ];
-                if let Some(w) = record.last_date_read {
-                    let w = NaiveDate::parse_from_str(&w, "%Y/%m/%d").unwrap();
-                    let read_at = Some(DateTime::from_utc(
-                        NaiveDateTime::new(w, NaiveTime::from_hms_opt(0, 0, 0).unwrap()),
-                        Utc,
-                    ));
-                    seen_history.first_mut().unwrap().ended_on = read_at;
-                }
-                let mut collections = vec![];
-                collections.push(match record.read_status {
-                    ReadStatus::ToRead => "Watchlist".to_owned(),
-                    ReadStatus::CurrentlyReading => "In Progress".to_owned(),
-                    ReadStatus::Other(s) => s.to_case(Case::Title),
-                });
-                if let Some(t) = record.tags {
-                    collections.extend(t.split(", ").map(|d| d.to_case(Case::Title)))
+        let resolved = resolve_identifier(provider, &record, matching, line).await;
+        // DEV: only the last date is known, so the rest are recorded as plays
+        // instead of creating undated entries
+        let mut seen_history = vec![];
+        if record.read_count > 0 {
+            seen_history.push(ImportOrExportItemSeen {
+                started_on: None,
+                ended_on: None,
+                show_season_number: None,
+                show_episode_number: None,
+                podcast_episode_number: None,
+                podcast_episode_guid: None,
+                progress: None,
+                plays: Some(record.read_count as i32),
+                date_precision: None,
+            });
+        }
+        if let Some(w) = record.last_date_read {
+            match convert_string_to_date_with_format(&w, date_format) {
+                Some(d) => {
+                    if let Some(s) = seen_history.first_mut() {
+                        s.ended_on = Some(convert_naive_to_utc(d));
+                    }
                 }
-                media.push(ImportOrExportItem {
-                    source_id: record.title,
+                None => result.warnings.push(ImportWarningItem {
                     lot,
-                    source,
-                    identifier: ImportOrExportItemIdentifier::NeedsDetails(identifier),
-                    seen_history,
-                    reviews: vec![ImportOrExportItemRating {
-                        rating: record
-                            .rating
-                            // DEV: Rates items out of 10
-                            .map(|d| d.saturating_mul(dec!(10))),
-                        review: record.review.map(|r| ImportOrExportItemReview {
-                            date: None,
-                            spoiler: Some(false),
-                            text: Some(r),
-                        }),
-                        show_season_number: None,
-                        show_episode_number: None,
-                        podcast_episode_number: None,
-                    }],
-                    collections,
-                })
-            } else {
-                failed_items.push(ImportFailedItem {
-                    lot,
-                    step: ImportFailStep::InputTransformation,
-                    identifier: record.title,
-                    error: Some(format!(
-                        "Could not convert ISBN: {} to Openlibrary ID",
-                        isbn
-                    )),
-                })
+                    identifier: record.title.clone(),
+                    warning: format!("Could not parse last read date: {:?}", w),
+                    alternatives: vec![],
+                }),
             }
-        } else {
-            failed_items.push(ImportFailedItem {
-                lot,
-                step: ImportFailStep::InputTransformation,
-                identifier: record.title,
-                error: Some("No ISBN found".to_owned()),
-            })
         }
+        let state = read_status_state(&record.read_status);
+        let mut collections = vec![];
+        if state != Some(MediaState::Dropped) {
+            collections.push(match record.read_status {
+                ReadStatus::ToRead => "Watchlist".to_owned(),
+                ReadStatus::CurrentlyReading => "In Progress".to_owned(),
+                ReadStatus::Other(s) => s.to_case(Case::Title),
+            });
+        }
+        if let Some(t) = record.tags {
+            collections.extend(t.split(", ").map(|d| d.to_case(Case::Title)))
+        }
+        let mut item = ImportOrExportItem {
+            source_id: record.title.clone(),
+            title: Some(record.title),
+            lot,
+            source,
+            identifier: ImportOrExportItemIdentifier::NeedsDetails(String::new()),
+            seen_history,
+            reviews: vec![ImportOrExportItemRating {
+                rating: record
+                    .rating
+                    // DEV: Rates items out of 10
+                    .map(|d| d.saturating_mul(dec!(10))),
+                review: record.review.map(|r| ImportOrExportItemReview {
+                    date: None,
+                    spoiler: Some(false),
+                    text: Some(r),
+                    visibility: None,
+                }),
+                show_season_number: None,
+                show_episode_number: None,
+                podcast_episode_number: None,
+            }],
+            collections,
+            state,
+        };
+        if let Some(identifier) = result.resolved_identifier(resolved, &item, None, line) {
+            item.identifier = ImportOrExportItemIdentifier::NeedsDetails(identifier);
+            result.media.push(item);
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::importer::fake_provider::FakeProvider;
+
+    static FIXTURE: &str = r#"Title,Authors,ISBN/UID,Read Status,Last Date Read,Read Count,Star Rating,Review,Tags
+Dune,Frank Herbert,978-0-441-17271-9,read,2023/01/15,1,4.5,,
+Dune,Frank Herbert,,read,,1,,,
+Emma,Jane Austen,b7a1c2d4-story-graph-uid,read,,1,,,
+Emma,Emma Donoghue,,read,,1,,,
+Unfindable Book,Nobody,,to-read,,0,,,
+"#;
+
+    #[rstest::rstest]
+    #[case("978-0-441-17271-9", Some("9780441172719"))]
+    #[case("0-441-17271-7", Some("0441172717"))]
+    #[case("b7a1c2d4-story-graph-uid", None)]
+    fn test_parse_isbn(#[case] input: &str, #[case] expected: Option<&str>) {
+        assert_eq!(parse_isbn(input).as_deref(), expected);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_identifier_prefers_isbn_then_title_and_author() {
+        let provider = FakeProvider {
+            isbns: HashMap::from([("9780441172719", "OL893415W")]),
+            searches: HashMap::from([
+                ("Dune Frank Herbert", vec![("OL893415W", "Dune")]),
+                ("Emma Jane Austen", vec![("OL66562W", "Emma")]),
+                ("Emma Emma Donoghue", vec![("OL17801239W", "Emma")]),
+            ]),
+        };
+        let mut identifiers = vec![];
+        for (line, record) in csv_records::<History>(FIXTURE) {
+            let resolved =
+                resolve_identifier(&provider, &record.unwrap(), &TitleMatching::default(), line)
+                    .await;
+            identifiers.push(match resolved {
+                Ok(ResolvedIdentifier::Found(i, _)) => Some(i),
+                _ => None,
+            });
+        }
+        assert_eq!(
+            identifiers,
+            vec![
+                Some("OL893415W".to_owned()),
+                Some("OL893415W".to_owned()),
+                Some("OL66562W".to_owned()),
+                Some("OL17801239W".to_owned()),
+                None,
+            ]
+        );
     }
-    Ok(ImportResult {
-        collections: vec![],
-        media,
-        failed_items,
-    })
 }
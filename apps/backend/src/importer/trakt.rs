@@ -1,20 +1,21 @@
-use async_graphql::Result;
+use async_graphql::{Error, Result};
 use convert_case::{Case, Casing};
 use itertools::Itertools;
 use rust_decimal::Decimal;
 use sea_orm::prelude::DateTimeUtc;
-use serde::{Deserialize, Serialize};
-use surf::http::headers::CONTENT_TYPE;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use surf::{http::headers::CONTENT_TYPE, Client};
 
 use crate::{
     importer::{
-        DeployTraktImportInput, ImportFailStep, ImportFailedItem, ImportOrExportItem,
-        ImportOrExportItemIdentifier, ImportResult,
+        DeployTraktImportInput, DeployTraktListImportInput, ImportFailStep, ImportFailedItem,
+        ImportOrExportItem, ImportOrExportItemIdentifier, ImportResult,
     },
-    migrator::{MetadataLot, MetadataSource},
+    migrator::{MediaState, MetadataLot, MetadataSource},
+    miscellaneous::DefaultCollection,
     models::media::{
         CreateOrUpdateCollectionInput, ImportOrExportItemRating, ImportOrExportItemReview,
-        ImportOrExportItemSeen,
+        ImportOrExportItemSeen, Visibility,
     },
     utils::get_base_http_client,
 };
@@ -22,6 +23,8 @@ use crate::{
 const API_URL: &str = "https://api.trakt.tv";
 const CLIENT_ID: &str = "b3d93fd4c53d78d61b18e0f0bf7ad5153de323788dbc0be1a3627205a36e89f5";
 const API_VERSION: &str = "2";
+/// The collection that shows hidden from progress on Trakt are added to
+const HIDDEN_COLLECTION: &str = "Hidden";
 
 #[derive(Debug, Serialize, Deserialize)]
 struct Id {
@@ -31,6 +34,7 @@ struct Id {
 
 #[derive(Debug, Serialize, Deserialize)]
 struct Item {
+    title: Option<String>,
     season: Option<i32>,
     number: Option<i32>,
     ids: Id,
@@ -46,6 +50,20 @@ struct ListItemResponse {
     rating: Option<u8>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+struct Comment {
+    comment: String,
+    spoiler: bool,
+    created_at: Option<DateTimeUtc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CommentItemResponse {
+    #[serde(flatten)]
+    item: ListItemResponse,
+    comment: Comment,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct ListResponse {
     name: String,
@@ -55,18 +73,94 @@ struct ListResponse {
     items: Vec<ListItemResponse>,
 }
 
-pub async fn import(input: DeployTraktImportInput) -> Result<ImportResult> {
-    let mut media_items = vec![];
-    let mut failed_items = vec![];
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct LotStats {
+    #[serde(default)]
+    watched: usize,
+    #[serde(default)]
+    collected: usize,
+    #[serde(default)]
+    ratings: usize,
+}
+
+impl LotStats {
+    /// Every watched, collected or rated item is imported once, so the
+    /// largest of them is the least number of items imported.
+    fn items(&self) -> usize {
+        self.watched.max(self.collected).max(self.ratings)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StatsResponse {
+    #[serde(default)]
+    movies: LotStats,
+    #[serde(default)]
+    shows: LotStats,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ListSummaryResponse {
+    item_count: usize,
+}
 
-    let client = get_base_http_client(
-        &format!("{}/users/{}/", API_URL, input.username),
+fn get_client(api_url: &str, username: &str) -> Client {
+    get_base_http_client(
+        &format!("{}/users/{}/", api_url, username),
         vec![
             (CONTENT_TYPE, "application/json"),
             ("trakt-api-key".into(), CLIENT_ID),
             ("trakt-api-version".into(), API_VERSION),
         ],
-    );
+    )
+}
+
+pub async fn import(input: DeployTraktImportInput) -> Result<ImportResult> {
+    import_from(API_URL, input).await
+}
+
+pub async fn estimate_size(input: &DeployTraktImportInput) -> Result<usize> {
+    estimate_size_from(API_URL, input).await
+}
+
+/// The number of movies and shows on the profile according to its stats, or
+/// the number of items in the list if only a list is imported.
+pub async fn estimate_size_from(api_url: &str, input: &DeployTraktImportInput) -> Result<usize> {
+    if let Some(list) = input.list.as_ref() {
+        let (client, slug) = list_client(api_url, &input.username, &list.slug);
+        let mut rsp = client
+            .get(&format!("lists/{}", slug))
+            .await
+            .map_err(|e| Error::new(e.to_string()))?;
+        let list: ListSummaryResponse = rsp
+            .body_json()
+            .await
+            .map_err(|e| Error::new(format!("Could not find list {:?}: {}", slug, e)))?;
+        return Ok(list.item_count);
+    }
+    let client = get_client(api_url, &input.username);
+    let mut rsp = client
+        .get("stats")
+        .await
+        .map_err(|e| Error::new(e.to_string()))?;
+    let stats: StatsResponse = rsp
+        .body_json()
+        .await
+        .map_err(|e| Error::new(format!("Could not get the Trakt stats: {}", e)))?;
+    Ok(stats.movies.items() + stats.shows.items())
+}
+
+/// Import from a Trakt compatible API hosted at `api_url`.
+pub async fn import_from(api_url: &str, input: DeployTraktImportInput) -> Result<ImportResult> {
+    let mut media_items = vec![];
+    let mut failed_items = vec![];
+
+    if let Some(list) = input.list {
+        let (client, slug) = list_client(api_url, &input.username, &list.slug);
+        return import_list(&client, &slug, list.collection).await;
+    }
+    let client = get_client(api_url, &input.username);
+
     let mut rsp = client.get("lists").await.unwrap();
     let mut lists: Vec<ListResponse> = rsp.body_json().await.unwrap();
 
@@ -91,20 +185,38 @@ pub async fn import(input: DeployTraktImportInput) -> Result<ImportResult> {
             items,
         });
     }
+    let mut owned = vec![];
+    for lot in ["movies", "shows"] {
+        let mut rsp = client.get(&format!("collection/{}", lot)).await.unwrap();
+        let items: Vec<ListItemResponse> = rsp.body_json().await.unwrap();
+        owned.extend(items);
+    }
+    lists.push(ListResponse {
+        name: "owned".to_owned(),
+        description: Some("Media that you have collected on Trakt".to_owned()),
+        ids: Id {
+            trakt: 0,
+            tmdb: None,
+        },
+        items: owned,
+    });
 
     for l in lists.iter() {
         for i in l.items.iter() {
             match process_item(i) {
                 Ok(mut d) => {
                     d.collections.push(l.name.to_case(Case::Title));
-                    media_items.push(d)
+                    if l.name == "watchlist" {
+                        d.state = Some(MediaState::PlanTo);
+                    }
+                    merge_item(&mut media_items, d);
                 }
                 Err(d) => failed_items.push(d),
             }
         }
     }
 
-    let all_collections = lists
+    let mut all_collections = lists
         .iter()
         .map(|l| CreateOrUpdateCollectionInput {
             name: l.name.to_case(Case::Title),
@@ -131,46 +243,39 @@ pub async fn import(input: DeployTraktImportInput) -> Result<ImportResult> {
                     review: Some(ImportOrExportItemReview {
                         spoiler: Some(false),
                         text: Some("".to_owned()),
+                        visibility: None,
                         date: item.rated_at,
                     }),
                     show_season_number: None,
                     show_episode_number: None,
                     podcast_episode_number: None,
                 });
-                if let Some(a) = media_items.iter_mut().find(|i| i.source_id == d.source_id) {
-                    a.reviews = d.reviews;
-                } else {
-                    media_items.push(d)
-                }
+                merge_item(&mut media_items, d);
             }
             Err(d) => failed_items.push(d),
         }
     }
 
     let mut histories = vec![];
-    let rsp = client
-        .head("history")
-        .query(&serde_json::json!({ "limit": 1000 }))
-        .unwrap()
-        .await
-        .unwrap();
-    let total_history = rsp
-        .header("x-pagination-page-count")
-        .expect("pagination to be present")
-        .last()
-        .as_str()
-        .parse::<usize>()
-        .unwrap();
-    for page in 1..total_history + 1 {
-        tracing::trace!("Fetching user history {page:?}/{total_history:?}");
+    let mut page = 1;
+    loop {
         let mut rsp = client
             .get("history")
             .query(&serde_json::json!({ "page": page, "limit": 1000 }))
             .unwrap()
             .await
             .unwrap();
+        let total_pages = rsp
+            .header("x-pagination-page-count")
+            .and_then(|h| h.last().as_str().parse::<usize>().ok())
+            .unwrap_or(1);
+        tracing::trace!("Fetched user history {page:?}/{total_pages:?}");
         let history: Vec<ListItemResponse> = rsp.body_json().await.unwrap();
         histories.extend(history);
+        if page >= total_pages {
+            break;
+        }
+        page += 1;
     }
 
     for item in histories.iter() {
@@ -184,56 +289,437 @@ pub async fn import(input: DeployTraktImportInput) -> Result<ImportResult> {
                 d.seen_history.push(ImportOrExportItemSeen {
                     started_on: None,
                     podcast_episode_number: None,
+                    podcast_episode_guid: None,
+                    progress: None,
+                    plays: None,
+                    date_precision: None,
                     ended_on: item.watched_at,
                     show_season_number,
                     show_episode_number,
                 });
-                if let Some(a) = media_items.iter_mut().find(|i| i.source_id == d.source_id) {
-                    a.seen_history = d.seen_history;
-                } else {
-                    media_items.push(d)
-                }
+                merge_item(&mut media_items, d);
             }
             Err(d) => failed_items.push(d),
         }
     }
+    drop_show_level_history(&mut media_items);
+
+    // DEV: comments of private profiles can not be fetched, in which case the
+    // import carries on without them
+    let path = "comments/all/all";
+    let comments = match get_all_pages::<CommentItemResponse>(&client, path).await {
+        Ok(comments) => comments,
+        Err(e) => {
+            tracing::warn!("Could not fetch the comments on Trakt: {e}");
+            failed_items.push(ImportFailedItem::new(
+                MetadataLot::default(),
+                ImportFailStep::ItemDetailsFromSource,
+                path.to_owned(),
+                Some(e),
+            ));
+            vec![]
+        }
+    };
+    for comment in comments.iter() {
+        match process_item(&comment.item) {
+            Ok(mut d) => {
+                d.reviews.push(comment_review(comment));
+                merge_item(&mut media_items, d);
+            }
+            Err(d) => failed_items.push(d),
+        }
+    }
+
+    // DEV: Trakt only returns the hidden items of users that have made their
+    // profile public, so the import carries on without them otherwise
+    let path = "hidden/progress_watched";
+    let hidden = match get_all_pages::<ListItemResponse>(&client, path).await {
+        Ok(hidden) => hidden,
+        Err(e) => {
+            tracing::warn!("Could not fetch the shows hidden from progress on Trakt: {e}");
+            failed_items.push(ImportFailedItem::new(
+                MetadataLot::Show,
+                ImportFailStep::ItemDetailsFromSource,
+                path.to_owned(),
+                Some(e),
+            ));
+            vec![]
+        }
+    };
+    if mark_hidden_shows(&mut media_items, &hidden) > 0 {
+        all_collections.push(CreateOrUpdateCollectionInput {
+            name: HIDDEN_COLLECTION.to_owned(),
+            description: Some("Shows that you have hidden from progress on Trakt".to_owned()),
+            ..Default::default()
+        });
+    }
     Ok(ImportResult {
         collections: all_collections,
         media: media_items,
         failed_items,
+        warnings: vec![],
+        pending_matches: vec![],
+        unsupported: 0,
+    })
+}
+
+/// Get the items of every page of a paginated endpoint.
+async fn get_all_pages<T: DeserializeOwned>(
+    client: &Client,
+    path: &str,
+) -> std::result::Result<Vec<T>, String> {
+    let mut items = vec![];
+    let mut page = 1;
+    loop {
+        let mut rsp = client
+            .get(path)
+            .query(&serde_json::json!({ "page": page, "limit": 1000 }))
+            .map_err(|e| e.to_string())?
+            .await
+            .map_err(|e| e.to_string())?;
+        if !rsp.status().is_success() {
+            return Err(format!("Trakt responded with {}", rsp.status()));
+        }
+        let total_pages = rsp
+            .header("x-pagination-page-count")
+            .and_then(|h| h.last().as_str().parse::<usize>().ok())
+            .unwrap_or(1);
+        tracing::trace!("Fetched {path} {page:?}/{total_pages:?}");
+        let page_items: Vec<T> = rsp.body_json().await.map_err(|e| e.to_string())?;
+        items.extend(page_items);
+        if page >= total_pages {
+            break;
+        }
+        page += 1;
+    }
+    Ok(items)
+}
+
+/// The owner and the slug of a list given either as a slug, as
+/// `username/lists/slug` or as the url of the list. The owner is `None` when
+/// only the slug is given.
+fn parse_list(list: &str) -> (Option<String>, String) {
+    let path = list.split(['?', '#']).next().unwrap_or_default();
+    let parts = path.trim_matches('/').split('/').collect_vec();
+    match parts.iter().rposition(|p| *p == "lists") {
+        Some(idx) if idx > 0 && idx + 1 < parts.len() => {
+            (Some(parts[idx - 1].to_owned()), parts[idx + 1].to_owned())
+        }
+        _ => (None, parts.last().copied().unwrap_or_default().to_owned()),
+    }
+}
+
+/// A client for the profile that owns the list, which defaults to the profile
+/// being imported, along with the slug of the list.
+fn list_client(api_url: &str, username: &str, list: &str) -> (Client, String) {
+    let (owner, slug) = parse_list(list);
+    (
+        get_client(api_url, owner.as_deref().unwrap_or(username)),
+        slug,
+    )
+}
+
+async fn import_list(
+    client: &Client,
+    slug: &str,
+    collection: Option<String>,
+) -> Result<ImportResult> {
+    let mut rsp = client
+        .get(&format!("lists/{}", slug))
+        .await
+        .map_err(|e| Error::new(e.to_string()))?;
+    let list: ListResponse = rsp
+        .body_json()
+        .await
+        .map_err(|e| Error::new(format!("Could not find list {:?}: {}", slug, e)))?;
+    let mut rsp = client
+        .get(&format!("lists/{}/items", slug))
+        .await
+        .map_err(|e| Error::new(e.to_string()))?;
+    let items: Vec<ListItemResponse> = rsp
+        .body_json()
+        .await
+        .map_err(|e| Error::new(e.to_string()))?;
+    let collection_name = collection.unwrap_or_else(|| list.name.to_case(Case::Title));
+    let mut media_items = vec![];
+    let mut failed_items = vec![];
+    for item in items.iter() {
+        match process_item(item) {
+            Ok(mut d) => {
+                d.collections.push(collection_name.clone());
+                media_items.push(d)
+            }
+            Err(d) => failed_items.push(d),
+        }
+    }
+    Ok(ImportResult {
+        collections: vec![CreateOrUpdateCollectionInput {
+            name: collection_name,
+            description: list.description.filter(|s| !s.is_empty()),
+            ..Default::default()
+        }],
+        media: media_items,
+        failed_items,
+        warnings: vec![],
+        pending_matches: vec![],
+        unsupported: 0,
     })
 }
 
+/// Add the item to the list, or merge it into an existing entry for the same
+/// media so that it keeps all its collections, reviews and history.
+fn merge_item(
+    media_items: &mut Vec<ImportOrExportItem<ImportOrExportItemIdentifier>>,
+    item: ImportOrExportItem<ImportOrExportItemIdentifier>,
+) {
+    match media_items
+        .iter_mut()
+        .find(|i| i.source_id == item.source_id && i.lot == item.lot)
+    {
+        Some(existing) => {
+            for collection in item.collections {
+                if !existing.collections.contains(&collection) {
+                    existing.collections.push(collection);
+                }
+            }
+            existing.reviews.extend(item.reviews);
+            existing.seen_history.extend(item.seen_history);
+            existing.state = existing.state.or(item.state);
+        }
+        None => media_items.push(item),
+    }
+}
+
+/// Comments are imported as reviews without a rating, keeping whether Trakt
+/// flagged them as spoilers.
+fn comment_review(c: &CommentItemResponse) -> ImportOrExportItemRating {
+    let (show_season_number, show_episode_number) = match c.item.episode.as_ref() {
+        Some(e) => (e.season, e.number),
+        None => (None, None),
+    };
+    ImportOrExportItemRating {
+        rating: None,
+        review: Some(ImportOrExportItemReview {
+            spoiler: Some(c.comment.spoiler),
+            text: Some(c.comment.comment.clone()),
+            // DEV: comments are always public on Trakt
+            visibility: Some(Visibility::Public),
+            date: c.comment.created_at,
+        }),
+        show_season_number,
+        show_episode_number,
+        podcast_episode_number: None,
+    }
+}
+
+/// Mark the shows that were hidden from progress as dropped, so that they do
+/// not show up as being watched. Returns the number of shows marked.
+fn mark_hidden_shows(
+    media_items: &mut [ImportOrExportItem<ImportOrExportItemIdentifier>],
+    hidden: &[ListItemResponse],
+) -> usize {
+    let hidden_ids = hidden
+        .iter()
+        .filter_map(|h| h.show.as_ref())
+        .map(|s| s.ids.trakt.to_string())
+        .collect_vec();
+    let mut marked = 0;
+    for item in media_items
+        .iter_mut()
+        .filter(|i| i.lot == MetadataLot::Show && hidden_ids.contains(&i.source_id))
+    {
+        item.state = Some(MediaState::Dropped);
+        item.collections
+            .retain(|c| c != &DefaultCollection::Watchlist.to_string());
+        item.collections.push(HIDDEN_COLLECTION.to_owned());
+        marked += 1;
+    }
+    marked
+}
+
+/// Legacy history entries mark a whole show as watched without any episode
+/// information. Only keep them for shows that have no episode level history.
+fn drop_show_level_history(media_items: &mut [ImportOrExportItem<ImportOrExportItemIdentifier>]) {
+    for item in media_items
+        .iter_mut()
+        .filter(|i| i.lot == MetadataLot::Show)
+    {
+        if item
+            .seen_history
+            .iter()
+            .any(|s| s.show_episode_number.is_some())
+        {
+            item.seen_history
+                .retain(|s| s.show_season_number.is_some() && s.show_episode_number.is_some());
+        }
+    }
+}
+
 fn process_item(
     i: &ListItemResponse,
 ) -> std::result::Result<ImportOrExportItem<ImportOrExportItemIdentifier>, ImportFailedItem> {
-    let (source_id, identifier, lot) = if let Some(d) = i.movie.as_ref() {
-        (d.ids.trakt, d.ids.tmdb, MetadataLot::Movie)
+    let (source_id, title, identifier, lot) = if let Some(d) = i.movie.as_ref() {
+        (d.ids.trakt, d.title.clone(), d.ids.tmdb, MetadataLot::Movie)
     } else if let Some(d) = i.show.as_ref() {
-        (d.ids.trakt, d.ids.tmdb, MetadataLot::Show)
+        (d.ids.trakt, d.title.clone(), d.ids.tmdb, MetadataLot::Show)
     } else {
-        return Err(ImportFailedItem {
-            lot: MetadataLot::Book,
-            step: ImportFailStep::ItemDetailsFromSource,
-            identifier: "".to_owned(),
-            error: Some("Item is neither a movie or a show".to_owned()),
-        });
+        return Err(ImportFailedItem::new(
+            MetadataLot::Book,
+            ImportFailStep::ItemDetailsFromSource,
+            "".to_owned(),
+            Some("Item is neither a movie or a show".to_owned()),
+        ));
     };
     match identifier {
         Some(i) => Ok(ImportOrExportItem {
             source_id: source_id.to_string(),
+            title,
             lot,
             identifier: ImportOrExportItemIdentifier::NeedsDetails(i.to_string()),
             source: MetadataSource::Tmdb,
             seen_history: vec![],
             reviews: vec![],
             collections: vec![],
+            state: None,
         }),
-        None => Err(ImportFailedItem {
-            lot: MetadataLot::Book,
-            step: ImportFailStep::ItemDetailsFromSource,
-            identifier: "".to_owned(),
-            error: Some("Item does not have an associated TMDB id".to_owned()),
-        }),
+        None => Err(ImportFailedItem::new(
+            MetadataLot::Book,
+            ImportFailStep::ItemDetailsFromSource,
+            "".to_owned(),
+            Some("Item does not have an associated TMDB id".to_owned()),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn item(
+        collection: Option<&str>,
+        seen: bool,
+    ) -> ImportOrExportItem<ImportOrExportItemIdentifier> {
+        ImportOrExportItem {
+            source_id: "1".to_owned(),
+            title: None,
+            lot: MetadataLot::Movie,
+            identifier: ImportOrExportItemIdentifier::NeedsDetails("10".to_owned()),
+            source: MetadataSource::Tmdb,
+            seen_history: if seen {
+                vec![ImportOrExportItemSeen {
+                    started_on: None,
+                    ended_on: None,
+                    show_season_number: None,
+                    show_episode_number: None,
+                    podcast_episode_number: None,
+                    podcast_episode_guid: None,
+                    progress: None,
+                    plays: None,
+                    date_precision: None,
+                }]
+            } else {
+                vec![]
+            },
+            reviews: vec![],
+            collections: collection.into_iter().map(|c| c.to_owned()).collect(),
+            state: None,
+        }
+    }
+
+    #[rstest]
+    #[case("watched", None, "watched")]
+    #[case("/watched/", None, "watched")]
+    #[case("alice/lists/watched", Some("alice"), "watched")]
+    #[case(
+        "https://trakt.tv/users/alice/lists/watched?sort=rank,asc",
+        Some("alice"),
+        "watched"
+    )]
+    fn parse_list_keeps_the_owner(
+        #[case] list: &str,
+        #[case] owner: Option<&str>,
+        #[case] slug: &str,
+    ) {
+        assert_eq!(parse_list(list), (owner.map(String::from), slug.to_owned()));
+    }
+
+    #[test]
+    fn test_merge_item_keeps_all_associations() {
+        let mut media_items = vec![];
+        merge_item(&mut media_items, item(Some("Watchlist"), false));
+        merge_item(&mut media_items, item(Some("Owned"), false));
+        merge_item(&mut media_items, item(Some("Watchlist"), false));
+        merge_item(&mut media_items, item(None, true));
+        assert_eq!(media_items.len(), 1);
+        assert_eq!(media_items[0].collections, vec!["Watchlist", "Owned"]);
+        assert_eq!(media_items[0].seen_history.len(), 1);
+    }
+
+    #[test]
+    fn test_mark_hidden_shows_drops_them() {
+        let mut show = item(Some("Watchlist"), true);
+        show.lot = MetadataLot::Show;
+        let mut media_items = vec![show, item(None, true)];
+        let hidden = vec![ListItemResponse {
+            movie: None,
+            show: Some(Item {
+                season: None,
+                number: None,
+                ids: Id {
+                    trakt: 1,
+                    tmdb: Some(10),
+                },
+            }),
+            episode: None,
+            watched_at: None,
+            rated_at: None,
+            rating: None,
+        }];
+        assert_eq!(mark_hidden_shows(&mut media_items, &hidden), 1);
+        assert_eq!(media_items[0].state, Some(MediaState::Dropped));
+        assert_eq!(media_items[0].collections, vec![HIDDEN_COLLECTION]);
+        assert_eq!(media_items[1].state, None);
+    }
+
+    #[test]
+    fn test_comment_review_keeps_spoiler_flag() {
+        let comment: CommentItemResponse = serde_json::from_value(serde_json::json!({
+            "type": "episode",
+            "comment": {
+                "comment": "What a twist at the end!",
+                "spoiler": true,
+                "created_at": "2023-07-30T20:15:00.000Z"
+            },
+            "episode": { "season": 2, "number": 5, "ids": { "trakt": 73482 } },
+            "show": { "ids": { "trakt": 1390, "tmdb": 1399 } }
+        }))
+        .unwrap();
+        let review = comment_review(&comment);
+        assert_eq!(review.rating, None);
+        assert_eq!(review.show_season_number, Some(2));
+        assert_eq!(review.show_episode_number, Some(5));
+        let review = review.review.unwrap();
+        assert_eq!(review.spoiler, Some(true));
+        assert_eq!(review.text.as_deref(), Some("What a twist at the end!"));
+        assert_eq!(review.visibility, Some(Visibility::Public));
+    }
+
+    #[test]
+    fn test_drop_show_level_history_keeps_episodes() {
+        let mut show = item(None, true);
+        show.lot = MetadataLot::Show;
+        let legacy = show.clone();
+        show.seen_history.push(ImportOrExportItemSeen {
+            show_season_number: Some(1),
+            show_episode_number: Some(2),
+            ..show.seen_history[0].clone()
+        });
+        let mut media_items = vec![show, legacy];
+        drop_show_level_history(&mut media_items);
+        assert_eq!(media_items[0].seen_history.len(), 1);
+        assert_eq!(media_items[0].seen_history[0].show_episode_number, Some(2));
+        assert_eq!(media_items[1].seen_history.len(), 1);
     }
 }
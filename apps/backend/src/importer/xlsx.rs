@@ -0,0 +1,481 @@
+use std::{future::Future, io::Cursor};
+
+use async_graphql::{Error, Result};
+use calamine::{open_workbook_from_rs, DataType, Reader, Xlsx};
+use itertools::Itertools;
+use rust_decimal::Decimal;
+use strum::IntoEnumIterator;
+
+use crate::{
+    file_storage::FileStorageService,
+    importer::{
+        read_limited, resolve_by_title, review_text_with_secondary_rating, DeployXlsxImportInput,
+        ImportFailStep, ImportFailedItem, ImportResult, ResolvedIdentifier, TitleMatching,
+    },
+    migrator::{MetadataLot, MetadataSource},
+    miscellaneous::resolver::{MiscellaneousService, Provider},
+    models::media::{
+        ImportOrExportItem, ImportOrExportItemIdentifier, ImportOrExportItemRating,
+        ImportOrExportItemReview, ImportOrExportItemSeen,
+    },
+    utils::{convert_naive_to_utc, convert_string_to_date_with_format, detect_date_format},
+};
+
+/// The largest spreadsheet that can be imported, in bytes
+pub const MAX_SPREADSHEET_SIZE: usize = 20 * 1024 * 1024;
+
+/// A row of the sheet. The first row of the sheet must be a header naming the
+/// columns, in any order and case.
+#[derive(Debug, Default, PartialEq)]
+struct Row {
+    title: String,
+    lot: Option<MetadataLot>,
+    source: Option<MetadataSource>,
+    identifier: Option<String>,
+    watched_on: Option<String>,
+    rating: Option<Decimal>,
+    review: Option<String>,
+    /// The value of the column picked as the secondary rating, as it is
+    secondary_rating: Option<String>,
+    collections: Vec<String>,
+}
+
+/// The provider used to find items whose row does not name one.
+fn default_source(lot: MetadataLot) -> MetadataSource {
+    match lot {
+        MetadataLot::AudioBook => MetadataSource::Audible,
+        MetadataLot::Book => MetadataSource::Openlibrary,
+        MetadataLot::Movie | MetadataLot::Show => MetadataSource::Tmdb,
+        MetadataLot::Podcast => MetadataSource::Itunes,
+        MetadataLot::VideoGame => MetadataSource::Igdb,
+        MetadataLot::Anime | MetadataLot::Manga => MetadataSource::Anilist,
+    }
+}
+
+/// Compare names ignoring case, spaces and underscores so that "Video Game",
+/// "video_game" and "VideoGame" are the same.
+fn same_name(a: &str, b: &str) -> bool {
+    let clean = |s: &str| {
+        s.chars()
+            .filter(|c| c.is_alphanumeric())
+            .collect::<String>()
+            .to_lowercase()
+    };
+    clean(a) == clean(b)
+}
+
+fn cell_to_string(cell: &DataType) -> Option<String> {
+    let value = match cell {
+        DataType::Empty => return None,
+        // DEV: dates are stored as numbers, so they are formatted like the
+        // dates of the CSV importers
+        DataType::DateTime(_) => cell.as_date()?.format("%Y-%m-%d").to_string(),
+        c => c.to_string(),
+    };
+    Some(value.trim().to_owned()).filter(|v| !v.is_empty())
+}
+
+/// The name of a column without its case and any characters that are not
+/// letters or digits.
+fn clean_header(header: &str) -> String {
+    header
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect::<String>()
+        .to_lowercase()
+}
+
+fn parse_row(
+    headers: &[String],
+    cells: &[Option<String>],
+    secondary_rating_column: Option<&str>,
+) -> std::result::Result<Row, String> {
+    let mut row = Row::default();
+    for (header, cell) in headers.iter().zip(cells) {
+        let Some(value) = cell.clone() else {
+            continue;
+        };
+        if Some(header.as_str()) == secondary_rating_column {
+            row.secondary_rating = Some(value);
+            continue;
+        }
+        match header.as_str() {
+            "title" => row.title = value,
+            "type" | "lot" => {
+                row.lot = Some(
+                    MetadataLot::iter()
+                        .find(|l| same_name(&format!("{l:?}"), &value))
+                        .ok_or_else(|| format!("Unknown type: {value:?}"))?,
+                )
+            }
+            "source" | "provider" => {
+                row.source = Some(
+                    MetadataSource::iter()
+                        .find(|s| same_name(&format!("{s:?}"), &value))
+                        .ok_or_else(|| format!("Unknown source: {value:?}"))?,
+                )
+            }
+            "identifier" | "id" => row.identifier = Some(value),
+            "watchedon" | "date" => row.watched_on = Some(value),
+            "rating" => {
+                row.rating = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("Invalid rating: {value:?}"))?,
+                )
+            }
+            "review" => row.review = Some(value),
+            "collections" => {
+                row.collections = value
+                    .split(',')
+                    .map(|c| c.trim().to_owned())
+                    .filter(|c| !c.is_empty())
+                    .collect()
+            }
+            _ => {}
+        }
+    }
+    if row.title.is_empty() {
+        return Err("The title is missing".to_owned());
+    }
+    Ok(row)
+}
+
+/// Parse the rows of a sheet, along with the line each one was read from.
+fn parse_rows(
+    rows: Vec<Vec<Option<String>>>,
+    secondary_rating_column: Option<&str>,
+) -> std::result::Result<Vec<(u64, std::result::Result<Row, String>)>, String> {
+    let mut rows = rows.into_iter();
+    let headers = rows
+        .next()
+        .ok_or_else(|| "The sheet is empty".to_owned())?
+        .into_iter()
+        .map(|h| clean_header(&h.unwrap_or_default()))
+        .collect_vec();
+    if !headers.iter().any(|h| h == "title") {
+        return Err("The sheet does not have a title column".to_owned());
+    }
+    let secondary_rating_column = secondary_rating_column.map(clean_header);
+    if let Some(column) = secondary_rating_column.as_ref() {
+        if !headers.contains(column) {
+            return Err("The sheet does not have the secondary rating column".to_owned());
+        }
+    }
+    Ok(rows
+        .enumerate()
+        .filter(|(_, cells)| cells.iter().any(|c| c.is_some()))
+        .map(|(idx, cells)| {
+            (
+                idx as u64 + 2,
+                parse_row(&headers, &cells, secondary_rating_column.as_deref()),
+            )
+        })
+        .collect())
+}
+
+pub async fn import(
+    user_id: i32,
+    input: DeployXlsxImportInput,
+    file_storage: &FileStorageService,
+    media_service: &MiscellaneousService,
+    matching: &TitleMatching,
+) -> Result<ImportResult> {
+    if !file_storage.is_enabled().await {
+        return Err(Error::new(
+            "File storage must be enabled to import a spreadsheet".to_owned(),
+        ));
+    }
+    let url = file_storage.get_presigned_url(input.file_key).await;
+    let response = surf::get(url)
+        .await
+        .map_err(|e| Error::new(format!("Could not download the spreadsheet: {e}")))?;
+    let data = read_limited(response, MAX_SPREADSHEET_SIZE, "spreadsheet").await?;
+    import_workbook(
+        data,
+        input.sheet_name,
+        input.secondary_rating_column,
+        input.date_format,
+        move |lot, source| media_service.get_user_provider(user_id, lot, source),
+        matching,
+    )
+    .await
+}
+
+/// Import the rows of a sheet of the workbook. Items without an identifier are
+/// searched for using the provider returned by `get_provider`.
+pub async fn import_workbook<F: Future<Output = Result<Provider>>>(
+    data: Vec<u8>,
+    sheet_name: Option<String>,
+    secondary_rating_column: Option<String>,
+    date_format: Option<String>,
+    get_provider: impl Fn(MetadataLot, MetadataSource) -> F,
+    matching: &TitleMatching,
+) -> Result<ImportResult> {
+    let mut workbook: Xlsx<_> = open_workbook_from_rs(Cursor::new(data))
+        .map_err(|e| Error::new(format!("The file is not an XLSX spreadsheet: {e}")))?;
+    let sheet_names = workbook.sheet_names().to_vec();
+    let sheet = match sheet_name {
+        Some(name) => sheet_names
+            .into_iter()
+            .find(|s| *s == name)
+            .ok_or_else(|| Error::new(format!("The spreadsheet has no sheet named {name:?}")))?,
+        None => sheet_names
+            .into_iter()
+            .next()
+            .ok_or_else(|| Error::new("The spreadsheet has no sheets".to_owned()))?,
+    };
+    let range = workbook
+        .worksheet_range(&sheet)
+        .ok_or_else(|| Error::new(format!("Could not read the sheet {sheet:?}")))?
+        .map_err(|e| Error::new(format!("Could not read the sheet {sheet:?}: {e}")))?;
+    let rows = range
+        .rows()
+        .map(|r| r.iter().map(cell_to_string).collect_vec())
+        .collect_vec();
+    let rows = parse_rows(rows, secondary_rating_column.as_deref()).map_err(Error::new)?;
+    let date_format = date_format.or_else(|| {
+        let dates = rows
+            .iter()
+            .filter_map(|(_, r)| r.as_ref().ok()?.watched_on.as_deref());
+        detect_date_format(dates).map(String::from)
+    });
+    let total = rows.len();
+    let mut result = ImportResult::default();
+    for (idx, (line, row)) in rows.into_iter().enumerate() {
+        let row = match row {
+            Ok(r) => r,
+            Err(e) => {
+                result.failed_items.push(
+                    ImportFailedItem::new(
+                        MetadataLot::default(),
+                        ImportFailStep::InputTransformation,
+                        idx.to_string(),
+                        Some(e),
+                    )
+                    .at_line(line),
+                );
+                continue;
+            }
+        };
+        let Some(lot) = row.lot else {
+            result.failed_items.push(
+                ImportFailedItem::new(
+                    MetadataLot::default(),
+                    ImportFailStep::InputTransformation,
+                    row.title,
+                    Some("The type is missing".to_owned()),
+                )
+                .at_line(line),
+            );
+            continue;
+        };
+        tracing::debug!(
+            "Getting details for {title:?} ({idx}/{total})",
+            title = row.title
+        );
+        let source = row.source.unwrap_or_else(|| default_source(lot));
+        let resolved = match row.identifier {
+            Some(i) => Ok(ResolvedIdentifier::Found(i, None)),
+            None => {
+                let resolution = match get_provider(lot, source).await {
+                    Ok(provider) => {
+                        resolve_by_title(&*provider, lot, source, &row.title, None, None, matching)
+                            .await
+                            .map_err(|e| e.to_string())
+                    }
+                    Err(e) => Err(e.message),
+                };
+                match resolution {
+                    Ok(Some(resolution)) => match matching.pending_match(
+                        &resolution,
+                        lot,
+                        source,
+                        &row.title,
+                        None,
+                        Some(line),
+                    ) {
+                        Some(p) => Ok(ResolvedIdentifier::Pending(p)),
+                        None => {
+                            let warning = resolution.warning(lot, &row.title);
+                            Ok(ResolvedIdentifier::Found(
+                                resolution.chosen.identifier,
+                                warning,
+                            ))
+                        }
+                    },
+                    found => {
+                        let error = "No identifier found and could not find it by title";
+                        Err(match found {
+                            Err(e) => format!("{error}: {e}"),
+                            _ => error.to_owned(),
+                        })
+                    }
+                }
+            }
+        };
+        let watched_on = row
+            .watched_on
+            .as_deref()
+            .and_then(|d| convert_string_to_date_with_format(d, date_format.as_deref()?))
+            .map(convert_naive_to_utc);
+        let seen_history = match (row.watched_on, watched_on) {
+            (Some(date), None) => {
+                // DEV: the rest of the row is still imported
+                result.failed_items.push(
+                    ImportFailedItem::new(
+                        lot,
+                        ImportFailStep::SeenHistoryConversion,
+                        row.title.clone(),
+                        Some(format!("Could not parse the date {date:?}")),
+                    )
+                    .at_line(line),
+                );
+                vec![]
+            }
+            (Some(_), Some(_)) => vec![ImportOrExportItemSeen {
+                started_on: None,
+                ended_on: watched_on,
+                show_season_number: None,
+                show_episode_number: None,
+                podcast_episode_number: None,
+                podcast_episode_guid: None,
+                progress: None,
+                plays: None,
+                date_precision: None,
+            }],
+            (None, _) => vec![],
+        };
+        let review = match (row.secondary_rating, secondary_rating_column.as_deref()) {
+            (Some(rating), Some(name)) => {
+                Some(review_text_with_secondary_rating(row.review, name, &rating))
+            }
+            _ => row.review,
+        };
+        let reviews = if row.rating.is_some() || review.is_some() {
+            vec![ImportOrExportItemRating {
+                review: review.map(|text| ImportOrExportItemReview {
+                    date: watched_on,
+                    spoiler: Some(false),
+                    text: Some(text),
+                    visibility: None,
+                }),
+                rating: row.rating,
+                show_season_number: None,
+                show_episode_number: None,
+                podcast_episode_number: None,
+            }]
+        } else {
+            vec![]
+        };
+        let mut item = ImportOrExportItem {
+            source_id: row.title.clone(),
+            title: Some(row.title),
+            lot,
+            source,
+            identifier: ImportOrExportItemIdentifier::NeedsDetails(String::new()),
+            seen_history,
+            reviews,
+            collections: row.collections,
+            state: None,
+        };
+        if let Some(identifier) = result.resolved_identifier(resolved, &item, None, line) {
+            item.identifier = ImportOrExportItemIdentifier::NeedsDetails(identifier);
+            result.media.push(item);
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+
+    use super::*;
+
+    fn cells(values: &[&str]) -> Vec<Option<String>> {
+        values
+            .iter()
+            .map(|v| Some(v.to_string()).filter(|v| !v.is_empty()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_rows_maps_columns_by_header() {
+        let rows = parse_rows(
+            vec![
+                cells(&[
+                    "Title",
+                    "Type",
+                    "Watched On",
+                    "Rating",
+                    "Collections",
+                    "Notes",
+                ]),
+                cells(&[
+                    "Dune",
+                    "Book",
+                    "2023-01-02",
+                    "80",
+                    "Owned, Favorites",
+                    "signed",
+                ]),
+                cells(&["", "", "", "", "", ""]),
+                cells(&["Alien", "video game", "", "", "", ""]),
+                cells(&["Heat", "Movie", "", "great", "", ""]),
+                cells(&["", "Movie", "", "", "", ""]),
+            ],
+            None,
+        )
+        .unwrap();
+        let lines = rows.iter().map(|(l, _)| *l).collect_vec();
+        assert_eq!(lines, vec![2, 4, 5, 6]);
+        assert_eq!(
+            rows[0].1,
+            Ok(Row {
+                title: "Dune".to_owned(),
+                lot: Some(MetadataLot::Book),
+                watched_on: Some("2023-01-02".to_owned()),
+                rating: Some(dec!(80)),
+                collections: vec!["Owned".to_owned(), "Favorites".to_owned()],
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            rows[1].1.as_ref().unwrap().lot,
+            Some(MetadataLot::VideoGame)
+        );
+        assert!(rows[2].1.is_err());
+        assert!(rows[3].1.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_read_limited_rejects_large_files() {
+        let data = read_limited(&b"spreadsheet"[..], 11, "spreadsheet")
+            .await
+            .unwrap();
+        assert_eq!(data, b"spreadsheet");
+        assert!(read_limited(&b"spreadsheet"[..], 10, "spreadsheet")
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_parse_rows_needs_a_title_column() {
+        assert!(parse_rows(vec![cells(&["Name", "Type"])], None).is_err());
+        assert!(parse_rows(vec![], None).is_err());
+    }
+
+    #[test]
+    fn test_parse_rows_keeps_secondary_rating() {
+        let sheet = vec![
+            cells(&["Title", "Type", "Rating", "Stars"]),
+            cells(&["Dune", "Book", "80", "4.5"]),
+        ];
+        let rows = parse_rows(sheet.clone(), Some("stars")).unwrap();
+        let row = rows[0].1.as_ref().unwrap();
+        assert_eq!(row.rating, Some(dec!(80)));
+        assert_eq!(row.secondary_rating.as_deref(), Some("4.5"));
+        assert!(parse_rows(sheet, Some("Score")).is_err());
+    }
+}
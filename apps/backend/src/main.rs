@@ -38,23 +38,27 @@ use tower_http::{
 
 use crate::{
     background::{
-        general_media_cleanup_jobs, general_user_cleanup, import_media,
-        recalculate_user_summary_job, update_exercise_job, update_metadata_job, user_created_job,
-        yank_integrations_data,
+        bulk_media_action_job, general_media_cleanup_jobs, general_user_cleanup, import_media,
+        recalculate_user_summary_job, refresh_instance_statistics, refresh_media_recommendations,
+        refresh_podcasts, send_media_reminders, update_exercise_job, update_metadata_batch_job,
+        update_metadata_job, user_created_job, yank_integrations_data,
     },
     config::get_app_config,
     config::AppConfig,
     graphql::get_schema,
     migrator::Migrator,
+    monitoring::{install_recorder, metrics_handler},
     routes::{
-        config_handler, graphql_handler, graphql_playground, integration_webhook, json_export,
-        static_handler, upload_handler,
+        config_handler, graphql_handler, graphql_playground, health_live, health_ready,
+        integration_webhook, json_export, static_handler, upload_handler,
     },
+    shutdown::{wait_for_signal, ShutdownSignal},
     utils::{create_app_services, MemoryAuthData, BASE_DIR, PROJECT_NAME, VERSION},
 };
 
 mod background;
 mod config;
+mod encryption;
 mod entities;
 mod file_storage;
 mod fitness;
@@ -64,8 +68,12 @@ mod integrations;
 mod migrator;
 mod miscellaneous;
 mod models;
+mod monitoring;
+mod notification;
 mod providers;
+mod rate_limiter;
 mod routes;
+mod shutdown;
 mod traits;
 mod users;
 mod utils;
@@ -90,6 +98,12 @@ async fn main() -> Result<()> {
     )?;
     let config = Arc::new(config);
 
+    let metrics_handle = if config.metrics.enabled {
+        Some(install_recorder())
+    } else {
+        None
+    };
+
     let mut aws_conf = aws_sdk_s3::Config::builder()
         .region(Region::new(config.file_storage.s3_region.clone()))
         .force_path_style(true);
@@ -144,7 +158,19 @@ async fn main() -> Result<()> {
     let user_created_job_storage = create_storage(pool.clone()).await;
     let recalculate_user_summary_job_storage = create_storage(pool.clone()).await;
     let update_metadata_job_storage = create_storage(pool.clone()).await;
+    let update_metadata_batch_job_storage = create_storage(pool.clone()).await;
     let update_exercise_job_storage = create_storage(pool.clone()).await;
+    let bulk_media_action_job_storage = create_storage(pool.clone()).await;
+
+    let shutdown = Arc::new(ShutdownSignal::default());
+    tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move {
+            wait_for_signal().await;
+            tracing::info!("Received shutdown signal");
+            shutdown.request();
+        }
+    });
 
     let app_services = create_app_services(
         db.clone(),
@@ -155,9 +181,26 @@ async fn main() -> Result<()> {
         &user_created_job_storage,
         &update_exercise_job_storage,
         &update_metadata_job_storage,
+        &update_metadata_batch_job_storage,
         &recalculate_user_summary_job_storage,
+        &bulk_media_action_job_storage,
+        shutdown.clone(),
     )
     .await;
+    app_services.media_service.check_secrets_key(&pool).await?;
+    app_services.media_service.encrypt_stored_secrets().await?;
+    if config.server.public_statistics {
+        // DEV: the scheduled job only runs at the start of the next hour, so the
+        // statistics are computed once in the background when the server starts
+        tokio::spawn({
+            let media_service = app_services.media_service.clone();
+            async move {
+                if let Err(e) = media_service.refresh_instance_statistics().await {
+                    tracing::error!("Could not compute the statistics of the instance: {e:?}");
+                }
+            }
+        });
+    }
 
     if cfg!(debug_assertions) {
         use specta::export;
@@ -210,11 +253,16 @@ async fn main() -> Result<()> {
         .route("/upload", post(upload_handler))
         .route("/graphql", get(graphql_playground).post(graphql_handler))
         .route("/export", get(json_export))
+        .route("/health/live", get(health_live))
+        .route("/health/ready", get(health_ready))
+        .route("/metrics", get(metrics_handler))
         .fallback(static_handler)
         .layer(Extension(app_services.media_service.clone()))
         .layer(Extension(app_services.file_storage_service.clone()))
         .layer(Extension(schema))
         .layer(Extension(config.clone()))
+        .layer(Extension(pool.clone()))
+        .layer(Extension(metrics_handle))
         .layer(TowerTraceLayer::new_for_http())
         .layer(TowerCatchPanicLayer::new())
         .layer(CookieManagerLayer::new())
@@ -237,10 +285,21 @@ async fn main() -> Result<()> {
     let media_service_4 = app_services.media_service.clone();
     let media_service_6 = app_services.media_service.clone();
     let media_service_7 = app_services.media_service.clone();
+    let media_service_8 = app_services.media_service.clone();
+    let media_service_9 = app_services.media_service.clone();
+    let media_service_10 = app_services.media_service.clone();
+    let media_service_11 = app_services.media_service.clone();
+    let media_service_12 = app_services.media_service.clone();
+    let media_service_13 = app_services.media_service.clone();
+    let media_service_14 = app_services.media_service.clone();
+    let media_service_15 = app_services.media_service.clone();
     let exercise_service_1 = app_services.exercise_service.clone();
 
     let user_cleanup_every = config.scheduler.user_cleanup_every;
     let pull_every = config.integration.pull_every;
+    let podcast_refresh_every = config.podcasts.refresh_every;
+    let shutdown_grace_period =
+        Duration::from_secs(config.server.shutdown_grace_period.try_into().unwrap());
 
     let monitor = async {
         let mn = Monitor::new()
@@ -285,11 +344,63 @@ async fn main() -> Result<()> {
                     .layer(ApalisExtension(media_service_3.clone()))
                     .build_fn(yank_integrations_data)
             })
+            .register_with_count(1, move |c| {
+                WorkerBuilder::new(format!("send_media_reminders-{c}"))
+                    .stream(
+                        // every day
+                        CronStream::new(Schedule::from_str("0 0 9 * * *").unwrap())
+                            .timer(SleepTimer)
+                            .to_stream(),
+                    )
+                    .layer(ApalisTraceLayer::new())
+                    .layer(ApalisExtension(media_service_8.clone()))
+                    .build_fn(send_media_reminders)
+            })
+            .register_with_count(1, move |c| {
+                WorkerBuilder::new(format!("refresh_media_recommendations-{c}"))
+                    .stream(
+                        // every day
+                        CronStream::new(Schedule::from_str("0 0 3 * * *").unwrap())
+                            .timer(SleepTimer)
+                            .to_stream(),
+                    )
+                    .layer(ApalisTraceLayer::new())
+                    .layer(ApalisExtension(media_service_9.clone()))
+                    .build_fn(refresh_media_recommendations)
+            })
+            .register_with_count(1, move |c| {
+                WorkerBuilder::new(format!("refresh_instance_statistics-{c}"))
+                    .stream(
+                        // every hour
+                        CronStream::new(Schedule::from_str("0 0 * * * *").unwrap())
+                            .timer(SleepTimer)
+                            .to_stream(),
+                    )
+                    .layer(ApalisTraceLayer::new())
+                    .layer(ApalisExtension(media_service_14.clone()))
+                    .build_fn(refresh_instance_statistics)
+            })
+            .register_with_count(1, move |c| {
+                WorkerBuilder::new(format!("refresh_podcasts-{c}"))
+                    .stream(
+                        CronStream::new(
+                            Schedule::from_str(&format!("0 0 */{} ? * *", podcast_refresh_every))
+                                .unwrap(),
+                        )
+                        .timer(SleepTimer)
+                        .to_stream(),
+                    )
+                    .layer(ApalisTraceLayer::new())
+                    .layer(ApalisExtension(media_service_11.clone()))
+                    .build_fn(refresh_podcasts)
+            })
             // application jobs
             .register_with_count(1, move |c| {
                 WorkerBuilder::new(format!("import_media-{c}"))
                     .layer(ApalisTraceLayer::new())
                     .layer(ApalisExtension(importer_service_1.clone()))
+                    .layer(ApalisExtension(media_service_12.clone()))
+                    .layer(ApalisExtension(import_media_storage.clone()))
                     .with_storage(import_media_storage.clone())
                     .build_fn(import_media)
             })
@@ -297,6 +408,7 @@ async fn main() -> Result<()> {
                 WorkerBuilder::new(format!("user_created_job-{c}"))
                     .layer(ApalisTraceLayer::new())
                     .layer(ApalisExtension(media_service_4.clone()))
+                    .layer(ApalisExtension(user_created_job_storage.clone()))
                     .with_storage(user_created_job_storage.clone())
                     .build_fn(user_created_job)
             })
@@ -304,6 +416,9 @@ async fn main() -> Result<()> {
                 WorkerBuilder::new(format!("recalculate_user_summary_job-{c}"))
                     .layer(ApalisTraceLayer::new())
                     .layer(ApalisExtension(media_service_6.clone()))
+                    .layer(ApalisExtension(
+                        recalculate_user_summary_job_storage.clone(),
+                    ))
                     .with_storage(recalculate_user_summary_job_storage.clone())
                     .build_fn(recalculate_user_summary_job)
             })
@@ -315,18 +430,44 @@ async fn main() -> Result<()> {
                         Duration::new(5, 0),
                     ))
                     .layer(ApalisExtension(media_service_7.clone()))
+                    .layer(ApalisExtension(update_metadata_job_storage.clone()))
                     .with_storage(update_metadata_job_storage.clone())
                     .build_fn(update_metadata_job)
             })
+            .register_with_count(1, move |c| {
+                WorkerBuilder::new(format!("update_metadata_batch_job-{c}"))
+                    .layer(ApalisTraceLayer::new())
+                    .layer(ApalisExtension(media_service_15.clone()))
+                    .layer(ApalisExtension(update_metadata_batch_job_storage.clone()))
+                    .with_storage(update_metadata_batch_job_storage.clone())
+                    .build_fn(update_metadata_batch_job)
+            })
+            .register_with_count(1, move |c| {
+                WorkerBuilder::new(format!("bulk_media_action_job-{c}"))
+                    .layer(ApalisTraceLayer::new())
+                    .layer(ApalisExtension(media_service_10.clone()))
+                    .layer(ApalisExtension(bulk_media_action_job_storage.clone()))
+                    .with_storage(bulk_media_action_job_storage.clone())
+                    .build_fn(bulk_media_action_job)
+            })
             .register_with_count(1, move |c| {
                 WorkerBuilder::new(format!("update_exercise_job-{c}"))
                     .layer(ApalisTraceLayer::new())
                     .layer(ApalisRateLimitLayer::new(50, Duration::new(5, 0)))
                     .layer(ApalisExtension(exercise_service_1.clone()))
+                    .layer(ApalisExtension(media_service_13.clone()))
+                    .layer(ApalisExtension(update_exercise_job_storage.clone()))
                     .with_storage(update_exercise_job_storage.clone())
                     .build_fn(update_exercise_job)
             })
-            .run()
+            .run_with_signal(async {
+                shutdown.requested().await;
+                tracing::info!("Waiting for running jobs to finish");
+                if !shutdown.drain(shutdown_grace_period).await {
+                    tracing::warn!("Running jobs did not finish within the grace period");
+                }
+                Ok(())
+            })
             .await;
         Ok(mn)
     };
@@ -334,6 +475,7 @@ async fn main() -> Result<()> {
     let http = async {
         Server::bind(&addr)
             .serve(app_routes.into_make_service())
+            .with_graceful_shutdown(shutdown.requested())
             .await
             .map_err(|e| IoError::new(IoErrorKind::Interrupted, e))
     };
@@ -50,6 +50,7 @@ pub enum MetadataImageLot {
     Enum,
     Default,
     Type,
+    Hash,
 )]
 #[sea_orm(rs_type = "String", db_type = "String(None)")]
 pub enum MetadataSource {
@@ -95,6 +96,7 @@ pub enum MetadataImage {
     Enum,
     Default,
     Type,
+    Hash,
 )]
 #[sea_orm(rs_type = "String", db_type = "String(None)")]
 pub enum MetadataLot {
@@ -143,6 +145,10 @@ pub enum Metadata {
     Source,
     // details about the media
     Specifics,
+    // the number of refreshes that have failed in a row
+    RefreshFailures,
+    // other titles the media is known by, embedded as json
+    AlternativeTitles,
 }
 
 #[async_trait::async_trait]
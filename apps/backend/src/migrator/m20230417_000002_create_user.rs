@@ -24,6 +24,10 @@ pub enum UserToMetadata {
     UserId,
     MetadataId,
     LastUpdatedOn,
+    Monitored,
+    Ignored,
+    ShowProgress,
+    State,
 }
 
 #[derive(
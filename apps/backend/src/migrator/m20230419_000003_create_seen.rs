@@ -27,6 +27,8 @@ pub enum SeenState {
     InProgress,
     #[sea_orm(string_value = "OH")]
     OnAHold,
+    #[sea_orm(string_value = "ST")]
+    Stale,
 }
 
 #[derive(Iden)]
@@ -40,6 +42,12 @@ pub enum Seen {
     UserId,
     MetadataId,
     LastUpdatedOn,
+    Plays,
+    Provenance,
+    ImportSource,
+    ImportReportId,
+    DatePrecision,
+    DeletedAt,
     // for the time being this stores the `season` and `episode` numbers
     ExtraInformation,
 }
@@ -26,6 +26,10 @@ pub enum Review {
     UserId,
     MetadataId,
     Spoiler,
+    Provenance,
+    ImportSource,
+    ImportReportId,
+    DeletedAt,
 }
 
 #[async_trait::async_trait]
@@ -30,6 +30,12 @@ pub enum MediaImportSource {
     Movary,
     #[sea_orm(string_value = "ST")]
     StoryGraph,
+    #[sea_orm(string_value = "RS")]
+    RyotSqlite,
+    #[sea_orm(string_value = "XL")]
+    Xlsx,
+    #[sea_orm(string_value = "JW")]
+    JustWatch,
 }
 
 #[derive(Iden)]
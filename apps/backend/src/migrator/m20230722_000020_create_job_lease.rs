@@ -0,0 +1,55 @@
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20230722_000020_create_job_lease"
+    }
+}
+
+/// A lease on a cron job that makes sure only one instance runs it at a time.
+#[derive(Iden)]
+pub enum JobLease {
+    Table,
+    Name,
+    Holder,
+    AcquiredOn,
+    ExpiresOn,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(JobLease::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(JobLease::Name)
+                            .string()
+                            .primary_key()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(JobLease::Holder).string().not_null())
+                    .col(
+                        ColumnDef::new(JobLease::AcquiredOn)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(JobLease::ExpiresOn)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+        Ok(())
+    }
+}
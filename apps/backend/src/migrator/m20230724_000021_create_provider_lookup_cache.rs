@@ -0,0 +1,88 @@
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20230724_000021_create_provider_lookup_cache"
+    }
+}
+
+pub static UNIQUE_INDEX: &str = "provider_lookup_cache__lot-source-identifier__index";
+
+/// Details of media resolved from a provider, shared between all users so that
+/// the same item is not looked up again by every import.
+#[derive(Iden)]
+pub enum ProviderLookupCache {
+    Table,
+    Id,
+    Lot,
+    Source,
+    Identifier,
+    Details,
+    CachedOn,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ProviderLookupCache::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ProviderLookupCache::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(ProviderLookupCache::Lot)
+                            .string_len(2)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ProviderLookupCache::Source)
+                            .string_len(2)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ProviderLookupCache::Identifier)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ProviderLookupCache::Details)
+                            .json()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ProviderLookupCache::CachedOn)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .unique()
+                    .name(UNIQUE_INDEX)
+                    .table(ProviderLookupCache::Table)
+                    .col(ProviderLookupCache::Lot)
+                    .col(ProviderLookupCache::Source)
+                    .col(ProviderLookupCache::Identifier)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+        Ok(())
+    }
+}
@@ -0,0 +1,34 @@
+use sea_orm_migration::prelude::*;
+
+use crate::migrator::m20230419_000003_create_seen::Seen;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20230725_000022_add_plays_field"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if !manager.has_column("seen", "plays").await? {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Seen::Table)
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Seen::Plays).integer().not_null().default(1),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+        Ok(())
+    }
+}
@@ -0,0 +1,79 @@
+use sea_orm_migration::prelude::*;
+
+use crate::migrator::{m20230417_000002_create_user::User, Metadata};
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20230726_000023_create_media_reminder"
+    }
+}
+
+/// A date on which a user wants to be reminded about a media item
+#[derive(Iden)]
+pub enum MediaReminder {
+    Table,
+    Id,
+    UserId,
+    MetadataId,
+    RemindOn,
+    Note,
+    CreatedOn,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(MediaReminder::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(MediaReminder::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(MediaReminder::UserId).integer().not_null())
+                    .col(
+                        ColumnDef::new(MediaReminder::MetadataId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(MediaReminder::RemindOn).date().not_null())
+                    .col(ColumnDef::new(MediaReminder::Note).text())
+                    .col(
+                        ColumnDef::new(MediaReminder::CreatedOn)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("media_reminder_to_user_foreign_key")
+                            .from(MediaReminder::Table, MediaReminder::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("media_reminder_to_metadata_foreign_key")
+                            .from(MediaReminder::Table, MediaReminder::MetadataId)
+                            .to(Metadata::Table, Metadata::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+        Ok(())
+    }
+}
@@ -0,0 +1,37 @@
+use sea_orm_migration::prelude::*;
+
+use crate::migrator::UserToMetadata;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20230727_000025_add_ignored_field"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if !manager.has_column("user_to_metadata", "ignored").await? {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(UserToMetadata::Table)
+                        .add_column_if_not_exists(
+                            ColumnDef::new(UserToMetadata::Ignored)
+                                .boolean()
+                                .not_null()
+                                .default(false),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+        Ok(())
+    }
+}
@@ -0,0 +1,137 @@
+use sea_orm_migration::prelude::*;
+
+use crate::migrator::Metadata;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20230728_000026_create_metadata_group"
+    }
+}
+
+/// A group of related media from a provider, like a movie collection or a
+/// video game franchise
+#[derive(Iden)]
+pub enum MetadataGroup {
+    Table,
+    Id,
+    Identifier,
+    Title,
+    Lot,
+    Source,
+    // all the media in the group in order, embedded as json
+    Parts,
+    LastUpdatedOn,
+}
+
+#[derive(Iden)]
+pub enum MetadataToMetadataGroup {
+    Table,
+    MetadataId,
+    MetadataGroupId,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(MetadataGroup::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(MetadataGroup::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(MetadataGroup::Identifier)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(MetadataGroup::Title).string().not_null())
+                    .col(ColumnDef::new(MetadataGroup::Lot).string_len(2).not_null())
+                    .col(
+                        ColumnDef::new(MetadataGroup::Source)
+                            .string_len(2)
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(MetadataGroup::Parts).json().not_null())
+                    .col(
+                        ColumnDef::new(MetadataGroup::LastUpdatedOn)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .unique()
+                    .name("metadata_group-identifier-source-lot__unique_index")
+                    .table(MetadataGroup::Table)
+                    .col(MetadataGroup::Identifier)
+                    .col(MetadataGroup::Source)
+                    .col(MetadataGroup::Lot)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_table(
+                Table::create()
+                    .table(MetadataToMetadataGroup::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(MetadataToMetadataGroup::MetadataId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(MetadataToMetadataGroup::MetadataGroupId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .name("pk-metadata_metadata_group")
+                            .col(MetadataToMetadataGroup::MetadataId)
+                            .col(MetadataToMetadataGroup::MetadataGroupId),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-metadata_id-metadata_group_id")
+                            .from(
+                                MetadataToMetadataGroup::Table,
+                                MetadataToMetadataGroup::MetadataId,
+                            )
+                            .to(Metadata::Table, Metadata::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-metadata_group_id-metadata_id")
+                            .from(
+                                MetadataToMetadataGroup::Table,
+                                MetadataToMetadataGroup::MetadataGroupId,
+                            )
+                            .to(MetadataGroup::Table, MetadataGroup::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+        Ok(())
+    }
+}
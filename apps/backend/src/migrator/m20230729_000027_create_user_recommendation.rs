@@ -0,0 +1,85 @@
+use sea_orm_migration::prelude::*;
+
+use crate::migrator::m20230417_000002_create_user::User;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20230729_000027_create_user_recommendation"
+    }
+}
+
+/// The media recommended to a user for a particular lot, recalculated daily
+#[derive(Iden)]
+pub enum UserRecommendation {
+    Table,
+    Id,
+    UserId,
+    Lot,
+    ComputedOn,
+    // the recommended items in order, embedded as json
+    Items,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(UserRecommendation::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(UserRecommendation::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(UserRecommendation::UserId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(UserRecommendation::Lot)
+                            .string_len(2)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(UserRecommendation::ComputedOn)
+                            .date()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(UserRecommendation::Items).json().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("user_recommendation_to_user_foreign_key")
+                            .from(UserRecommendation::Table, UserRecommendation::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .unique()
+                    .name("user_recommendation-user_id-lot__unique_index")
+                    .table(UserRecommendation::Table)
+                    .col(UserRecommendation::UserId)
+                    .col(UserRecommendation::Lot)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+        Ok(())
+    }
+}
@@ -0,0 +1,98 @@
+use async_graphql::Enum;
+use sea_orm::{DeriveActiveEnum, EnumIter};
+use sea_orm_migration::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::m20230417_000002_create_user::User;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20230730_000028_create_bulk_media_action_report"
+    }
+}
+
+/// The actions that can be applied to many media items at once
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, EnumIter, DeriveActiveEnum, Deserialize, Serialize, Enum,
+)]
+#[sea_orm(rs_type = "String", db_type = "String(None)")]
+pub enum BulkMediaAction {
+    #[sea_orm(string_value = "AC")]
+    AddToCollection,
+    #[sea_orm(string_value = "RC")]
+    RemoveFromCollection,
+    #[sea_orm(string_value = "MO")]
+    MarkMonitored,
+    #[sea_orm(string_value = "IG")]
+    MarkIgnored,
+}
+
+#[derive(Iden)]
+pub enum BulkMediaActionReport {
+    Table,
+    Id,
+    UserId,
+    Action,
+    StartedOn,
+    FinishedOn,
+    Details,
+    Success,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(BulkMediaActionReport::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(BulkMediaActionReport::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(BulkMediaActionReport::UserId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(BulkMediaActionReport::Action)
+                            .string_len(2)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(BulkMediaActionReport::StartedOn)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(
+                        ColumnDef::new(BulkMediaActionReport::FinishedOn)
+                            .timestamp_with_time_zone(),
+                    )
+                    .col(ColumnDef::new(BulkMediaActionReport::Details).json())
+                    .col(ColumnDef::new(BulkMediaActionReport::Success).boolean())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("bulk_media_action_report_to_user_foreign_key")
+                            .from(BulkMediaActionReport::Table, BulkMediaActionReport::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+        Ok(())
+    }
+}
@@ -0,0 +1,37 @@
+use sea_orm_migration::prelude::*;
+
+use crate::migrator::Metadata;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20230801_000030_add_refresh_failures_field"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if !manager.has_column("metadata", "refresh_failures").await? {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Metadata::Table)
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Metadata::RefreshFailures)
+                                .integer()
+                                .not_null()
+                                .default(0),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+        Ok(())
+    }
+}
@@ -0,0 +1,104 @@
+use async_graphql::Enum;
+use sea_orm::{DeriveActiveEnum, EnumIter};
+use sea_orm_migration::prelude::*;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::migrator::{SeenState, UserToMetadata};
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20230802_000031_add_media_state_field"
+    }
+}
+
+/// The state a user has put a media item in
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    EnumIter,
+    DeriveActiveEnum,
+    Deserialize,
+    Serialize,
+    Enum,
+    Type,
+)]
+#[sea_orm(rs_type = "String", db_type = "String(None)")]
+pub enum MediaState {
+    #[sea_orm(string_value = "PT")]
+    PlanTo,
+    #[sea_orm(string_value = "IP")]
+    InProgress,
+    #[sea_orm(string_value = "CO")]
+    Completed,
+    #[sea_orm(string_value = "DR")]
+    Dropped,
+    #[sea_orm(string_value = "OH")]
+    OnHold,
+    /// The name this state had when it was set through the state of seen items
+    #[graphql(deprecation = "Use `ON_HOLD` instead")]
+    #[sea_orm(string_value = "OA")]
+    OnAHold,
+}
+
+impl MediaState {
+    /// The state with the deprecated names replaced, which is what gets stored.
+    pub fn normalize(self) -> Self {
+        match self {
+            Self::OnAHold => Self::OnHold,
+            s => s,
+        }
+    }
+
+    /// The state a media item is in after a seen item is saved. Stale seen items
+    /// leave it unchanged.
+    pub fn from_seen_state(state: SeenState) -> Option<Self> {
+        match state {
+            SeenState::Completed => Some(Self::Completed),
+            SeenState::Dropped => Some(Self::Dropped),
+            SeenState::InProgress => Some(Self::InProgress),
+            SeenState::OnAHold => Some(Self::OnHold),
+            SeenState::Stale => None,
+        }
+    }
+
+    /// The state given to the latest seen item when the media is put in this
+    /// state. Planned media has no seen item to change.
+    pub fn seen_state(self) -> Option<SeenState> {
+        match self {
+            Self::PlanTo => None,
+            Self::InProgress => Some(SeenState::InProgress),
+            Self::Completed => Some(SeenState::Completed),
+            Self::Dropped => Some(SeenState::Dropped),
+            Self::OnHold | Self::OnAHold => Some(SeenState::OnAHold),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if !manager.has_column("user_to_metadata", "state").await? {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(UserToMetadata::Table)
+                        .add_column_if_not_exists(ColumnDef::new(UserToMetadata::State).string())
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+        Ok(())
+    }
+}
@@ -0,0 +1,95 @@
+use async_graphql::Enum;
+use sea_orm::{DeriveActiveEnum, EnumIter};
+use sea_orm_migration::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::migrator::{m20230419_000003_create_seen::Seen, m20230505_000006_create_review::Review};
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20230803_000032_add_provenance_fields"
+    }
+}
+
+/// How a seen item or review was created
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Default,
+    EnumIter,
+    DeriveActiveEnum,
+    Deserialize,
+    Serialize,
+    Enum,
+)]
+#[sea_orm(rs_type = "String", db_type = "String(None)")]
+pub enum Provenance {
+    #[default]
+    #[sea_orm(string_value = "MA")]
+    Manual,
+    #[sea_orm(string_value = "IM")]
+    Import,
+}
+
+fn provenance_columns<T: Iden + 'static>(
+    provenance: T,
+    import_source: T,
+    import_report_id: T,
+) -> [ColumnDef; 3] {
+    [
+        ColumnDef::new(provenance)
+            .string()
+            .not_null()
+            .default(Provenance::Manual)
+            .to_owned(),
+        ColumnDef::new(import_source).string().to_owned(),
+        ColumnDef::new(import_report_id).integer().to_owned(),
+    ]
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // DEV: SQLite can only add one column at a time
+        if !manager.has_column("seen", "provenance").await? {
+            for column in
+                provenance_columns(Seen::Provenance, Seen::ImportSource, Seen::ImportReportId)
+            {
+                manager
+                    .alter_table(
+                        Table::alter()
+                            .table(Seen::Table)
+                            .add_column_if_not_exists(column)
+                            .to_owned(),
+                    )
+                    .await?;
+            }
+        }
+        if !manager.has_column("review", "provenance").await? {
+            for column in provenance_columns(
+                Review::Provenance,
+                Review::ImportSource,
+                Review::ImportReportId,
+            ) {
+                manager
+                    .alter_table(
+                        Table::alter()
+                            .table(Review::Table)
+                            .add_column_if_not_exists(column)
+                            .to_owned(),
+                    )
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+        Ok(())
+    }
+}
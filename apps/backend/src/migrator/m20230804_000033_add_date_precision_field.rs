@@ -0,0 +1,90 @@
+use async_graphql::Enum;
+use chrono::{Datelike, NaiveDate};
+use sea_orm::{DeriveActiveEnum, EnumIter};
+use sea_orm_migration::prelude::*;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::migrator::Seen;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20230804_000033_add_date_precision_field"
+    }
+}
+
+/// How precisely the dates of a seen item are known
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Default,
+    EnumIter,
+    DeriveActiveEnum,
+    Deserialize,
+    Serialize,
+    Enum,
+    Type,
+)]
+#[sea_orm(rs_type = "String", db_type = "String(None)")]
+pub enum SeenDatePrecision {
+    #[default]
+    #[sea_orm(string_value = "D")]
+    Day,
+    #[sea_orm(string_value = "M")]
+    Month,
+    #[sea_orm(string_value = "Y")]
+    Year,
+}
+
+impl SeenDatePrecision {
+    /// The first day of the period the date is in. Dates are stored this way
+    /// when only their month or year is known.
+    pub fn truncate(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Self::Day => date,
+            Self::Month => date.with_day(1).unwrap(),
+            Self::Year => NaiveDate::from_ymd_opt(date.year(), 1, 1).unwrap(),
+        }
+    }
+
+    /// Format the date without the parts that are not known.
+    pub fn format(self, date: NaiveDate) -> String {
+        match self {
+            Self::Day => date.format("%Y-%m-%d"),
+            Self::Month => date.format("%Y-%m"),
+            Self::Year => date.format("%Y"),
+        }
+        .to_string()
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if !manager.has_column("seen", "date_precision").await? {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Seen::Table)
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Seen::DatePrecision)
+                                .string()
+                                .not_null()
+                                .default(SeenDatePrecision::Day),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+        Ok(())
+    }
+}
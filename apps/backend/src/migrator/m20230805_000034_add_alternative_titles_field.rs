@@ -0,0 +1,37 @@
+use sea_orm_migration::prelude::*;
+
+use crate::migrator::Metadata;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20230805_000034_add_alternative_titles_field"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if !manager.has_column("metadata", "alternative_titles").await? {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Metadata::Table)
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Metadata::AlternativeTitles)
+                                .json()
+                                .not_null()
+                                .default("[]"),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+use sea_orm::ConnectionTrait;
+use sea_orm_migration::prelude::*;
+
+use crate::migrator::Metadata;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20230806_000035_create_metadata_title"
+    }
+}
+
+/// The titles a media item is known by, so that media with the same title can
+/// be found without scanning the metadata table
+#[derive(Iden)]
+pub enum MetadataTitle {
+    Table,
+    MetadataId,
+    // the title in lowercase and without surrounding whitespace
+    Title,
+    Lot,
+    PublishYear,
+    // whether this is one of the alternative titles of the media
+    Alias,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if manager.has_table("metadata_title").await? {
+            return Ok(());
+        }
+        manager
+            .create_table(
+                Table::create()
+                    .table(MetadataTitle::Table)
+                    .col(
+                        ColumnDef::new(MetadataTitle::MetadataId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(MetadataTitle::Title).string().not_null())
+                    .col(ColumnDef::new(MetadataTitle::Lot).string_len(2).not_null())
+                    .col(ColumnDef::new(MetadataTitle::PublishYear).integer())
+                    .col(
+                        ColumnDef::new(MetadataTitle::Alias)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .name("pk-metadata_title")
+                            .col(MetadataTitle::MetadataId)
+                            .col(MetadataTitle::Title),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-metadata_title-metadata_id")
+                            .from(MetadataTitle::Table, MetadataTitle::MetadataId)
+                            .to(Metadata::Table, Metadata::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("metadata_title-lot-title__index")
+                    .table(MetadataTitle::Table)
+                    .col(MetadataTitle::Lot)
+                    .col(MetadataTitle::Title)
+                    .to_owned(),
+            )
+            .await?;
+        // DEV: the main titles of the existing media are copied over. `LOWER`
+        // only changes ASCII letters on SQLite, the rest are fixed the next
+        // time the media is refreshed.
+        let insert = Query::insert()
+            .into_table(MetadataTitle::Table)
+            .columns([
+                MetadataTitle::MetadataId,
+                MetadataTitle::Title,
+                MetadataTitle::Lot,
+                MetadataTitle::PublishYear,
+            ])
+            .select_from(
+                Query::select()
+                    .column(Metadata::Id)
+                    .expr(Func::lower(
+                        Func::cust(Alias::new("TRIM")).arg(Expr::col(Metadata::Title)),
+                    ))
+                    .column(Metadata::Lot)
+                    .column(Metadata::PublishYear)
+                    .from(Metadata::Table)
+                    .to_owned(),
+            )
+            .map_err(|e| DbErr::Migration(e.to_string()))?
+            .to_owned();
+        let db = manager.get_connection();
+        db.execute(db.get_database_backend().build(&insert)).await?;
+        Ok(())
+    }
+
+    async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+        Ok(())
+    }
+}
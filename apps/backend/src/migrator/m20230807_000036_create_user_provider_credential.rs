@@ -0,0 +1,80 @@
+use sea_orm_migration::prelude::*;
+
+use crate::migrator::m20230417_000002_create_user::User;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20230807_000036_create_user_provider_credential"
+    }
+}
+
+/// The credentials a user has configured for a provider, which are used
+/// instead of the ones of the instance
+#[derive(Iden)]
+pub enum UserProviderCredential {
+    Table,
+    UserId,
+    Source,
+    // the encrypted JSON of the credentials
+    Credentials,
+    UpdatedOn,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(UserProviderCredential::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(UserProviderCredential::UserId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(UserProviderCredential::Source)
+                            .string_len(2)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(UserProviderCredential::Credentials)
+                            .text()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(UserProviderCredential::UpdatedOn)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .name("pk-user_provider_credential")
+                            .col(UserProviderCredential::UserId)
+                            .col(UserProviderCredential::Source),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("user_provider_credential_to_user_foreign_key")
+                            .from(
+                                UserProviderCredential::Table,
+                                UserProviderCredential::UserId,
+                            )
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+        Ok(())
+    }
+}
@@ -0,0 +1,136 @@
+use sea_orm_migration::prelude::*;
+
+use crate::migrator::m20230509_000008_create_media_import_report::MediaImportReport;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20230809_000038_create_import_analytics"
+    }
+}
+
+/// The aggregate metrics of each finished import. They are kept when the
+/// report of the import is deleted, so that trends can be followed over time.
+#[derive(Iden)]
+pub enum ImportAnalytics {
+    Table,
+    Id,
+    ReportId,
+    Source,
+    StartedOn,
+    FinishedOn,
+    DurationMs,
+    Success,
+    SourceTotal,
+    Imported,
+    Failed,
+    Skipped,
+    Filtered,
+    Pending,
+    // the number of failed items for each step
+    FailuresByStep,
+    ApiCalls,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ImportAnalytics::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ImportAnalytics::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(ImportAnalytics::ReportId).integer())
+                    .col(
+                        ColumnDef::new(ImportAnalytics::Source)
+                            .string_len(2)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ImportAnalytics::StartedOn)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ImportAnalytics::FinishedOn)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ImportAnalytics::DurationMs)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ImportAnalytics::Success)
+                            .boolean()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ImportAnalytics::SourceTotal)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ImportAnalytics::Imported)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(ImportAnalytics::Failed).integer().not_null())
+                    .col(
+                        ColumnDef::new(ImportAnalytics::Skipped)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ImportAnalytics::Filtered)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ImportAnalytics::Pending)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ImportAnalytics::FailuresByStep)
+                            .json()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(ImportAnalytics::ApiCalls).integer())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("import_analytics_to_media_import_report_foreign_key")
+                            .from(ImportAnalytics::Table, ImportAnalytics::ReportId)
+                            .to(MediaImportReport::Table, MediaImportReport::Id)
+                            .on_delete(ForeignKeyAction::SetNull)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("import_analytics__source__started_on__index")
+                    .table(ImportAnalytics::Table)
+                    .col(ImportAnalytics::Source)
+                    .col(ImportAnalytics::StartedOn)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+        Ok(())
+    }
+}
@@ -0,0 +1,46 @@
+use sea_orm_migration::prelude::*;
+
+use crate::migrator::{m20230419_000003_create_seen::Seen, m20230505_000006_create_review::Review};
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20230810_000039_add_deleted_at_fields"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if !manager.has_column("seen", "deleted_at").await? {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Seen::Table)
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Seen::DeletedAt).timestamp_with_time_zone(),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+        }
+        if !manager.has_column("review", "deleted_at").await? {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Review::Table)
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Review::DeletedAt).timestamp_with_time_zone(),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+        Ok(())
+    }
+}
@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+use sea_orm_migration::prelude::*;
+
+use crate::{
+    entities::{
+        metadata,
+        prelude::{Seen, UserToMetadata},
+        seen, user_to_metadata,
+    },
+    migrator::{MediaState, MetadataLot, SeenState},
+};
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20230811_000040_backfill_media_state"
+    }
+}
+
+/// The state of media whose latest seen item is in the given state. A seen item
+/// of a show or podcast is for one episode, so it does not complete the media.
+fn backfilled_state(state: SeenState, lot: MetadataLot) -> Option<MediaState> {
+    match MediaState::from_seen_state(state)? {
+        MediaState::Completed if matches!(lot, MetadataLot::Show | MetadataLot::Podcast) => {
+            Some(MediaState::InProgress)
+        }
+        s => Some(s),
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        let seen: Vec<(i32, i32, SeenState, MetadataLot)> = Seen::find()
+            .select_only()
+            .column(seen::Column::UserId)
+            .column(seen::Column::MetadataId)
+            .column(seen::Column::State)
+            .column(metadata::Column::Lot)
+            .inner_join(metadata::Entity)
+            .filter(seen::Column::DeletedAt.is_null())
+            .filter(seen::Column::State.ne(SeenState::Stale))
+            .order_by_asc(seen::Column::LastUpdatedOn)
+            .into_tuple()
+            .all(db)
+            .await?;
+        // DEV: ordered by when they were updated, so the latest seen item wins
+        let mut states = HashMap::new();
+        for (user_id, metadata_id, state, lot) in seen {
+            if let Some(state) = backfilled_state(state, lot) {
+                states.insert((user_id, metadata_id), state);
+            }
+        }
+        for ((user_id, metadata_id), state) in states {
+            UserToMetadata::update_many()
+                .filter(user_to_metadata::Column::UserId.eq(user_id))
+                .filter(user_to_metadata::Column::MetadataId.eq(metadata_id))
+                .filter(user_to_metadata::Column::State.is_null())
+                .col_expr(user_to_metadata::Column::State, Expr::value(state))
+                .exec(db)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+        Ok(())
+    }
+}
@@ -0,0 +1,37 @@
+use sea_orm::{ActiveModelTrait, ActiveValue, ColumnTrait, EntityTrait, QueryFilter};
+use sea_orm_migration::prelude::*;
+
+use crate::entities::{media_import_report, prelude::MediaImportReport};
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20230812_000041_add_import_item_ids"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        let reports = MediaImportReport::find()
+            .filter(media_import_report::Column::Details.is_not_null())
+            .all(db)
+            .await?;
+        for report in reports {
+            let mut details = report.details.clone().unwrap();
+            if !details.assign_missing_ids() {
+                continue;
+            }
+            let mut report: media_import_report::ActiveModel = report.into();
+            report.details = ActiveValue::Set(Some(details));
+            report.update(db).await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+        Ok(())
+    }
+}
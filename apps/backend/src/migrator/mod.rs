@@ -20,6 +20,27 @@ mod m20230712_000016_remove_identifier_fields;
 mod m20230717_000017_change_rating_value;
 mod m20230717_000018_add_user_sink_integrations_field;
 mod m20230722_000019_add_state_field;
+mod m20230722_000020_create_job_lease;
+mod m20230724_000021_create_provider_lookup_cache;
+mod m20230725_000022_add_plays_field;
+mod m20230726_000023_create_media_reminder;
+mod m20230727_000024_add_monitored_field;
+mod m20230727_000025_add_ignored_field;
+mod m20230728_000026_create_metadata_group;
+mod m20230729_000027_create_user_recommendation;
+mod m20230730_000028_create_bulk_media_action_report;
+mod m20230731_000029_add_show_progress_field;
+mod m20230801_000030_add_refresh_failures_field;
+mod m20230802_000031_add_media_state_field;
+mod m20230803_000032_add_provenance_fields;
+mod m20230804_000033_add_date_precision_field;
+mod m20230805_000034_add_alternative_titles_field;
+mod m20230806_000035_create_metadata_title;
+mod m20230807_000036_create_user_provider_credential;
+mod m20230809_000038_create_import_analytics;
+mod m20230810_000039_add_deleted_at_fields;
+mod m20230811_000040_backfill_media_state;
+mod m20230812_000041_add_import_item_ids;
 
 pub use m20230410_000001_create_metadata::{
     Metadata, MetadataImageLot, MetadataLot, MetadataSource,
@@ -28,6 +49,11 @@ pub use m20230417_000002_create_user::{UserLot, UserToMetadata};
 pub use m20230419_000003_create_seen::{Seen, SeenState};
 pub use m20230505_000006_create_review::Review;
 pub use m20230509_000008_create_media_import_report::MediaImportSource;
+pub use m20230730_000028_create_bulk_media_action_report::BulkMediaAction;
+pub use m20230802_000031_add_media_state_field::MediaState;
+pub use m20230803_000032_add_provenance_fields::Provenance;
+pub use m20230804_000033_add_date_precision_field::SeenDatePrecision;
+pub use m20230806_000035_create_metadata_title::MetadataTitle;
 
 pub struct Migrator;
 
@@ -54,6 +80,27 @@ impl MigratorTrait for Migrator {
             Box::new(m20230717_000017_change_rating_value::Migration),
             Box::new(m20230717_000018_add_user_sink_integrations_field::Migration),
             Box::new(m20230722_000019_add_state_field::Migration),
+            Box::new(m20230722_000020_create_job_lease::Migration),
+            Box::new(m20230724_000021_create_provider_lookup_cache::Migration),
+            Box::new(m20230725_000022_add_plays_field::Migration),
+            Box::new(m20230726_000023_create_media_reminder::Migration),
+            Box::new(m20230727_000024_add_monitored_field::Migration),
+            Box::new(m20230727_000025_add_ignored_field::Migration),
+            Box::new(m20230728_000026_create_metadata_group::Migration),
+            Box::new(m20230729_000027_create_user_recommendation::Migration),
+            Box::new(m20230730_000028_create_bulk_media_action_report::Migration),
+            Box::new(m20230731_000029_add_show_progress_field::Migration),
+            Box::new(m20230801_000030_add_refresh_failures_field::Migration),
+            Box::new(m20230802_000031_add_media_state_field::Migration),
+            Box::new(m20230803_000032_add_provenance_fields::Migration),
+            Box::new(m20230804_000033_add_date_precision_field::Migration),
+            Box::new(m20230805_000034_add_alternative_titles_field::Migration),
+            Box::new(m20230806_000035_create_metadata_title::Migration),
+            Box::new(m20230807_000036_create_user_provider_credential::Migration),
+            Box::new(m20230809_000038_create_import_analytics::Migration),
+            Box::new(m20230810_000039_add_deleted_at_fields::Migration),
+            Box::new(m20230811_000040_backfill_media_state::Migration),
+            Box::new(m20230812_000041_add_import_item_ids::Migration),
         ]
     }
 }
@@ -7,8 +7,8 @@ use strum::{Display, EnumIter};
 use crate::{
     migrator::MetadataImageLot,
     models::media::{
-        AnimeSpecifics, AudioBookSpecifics, BookSpecifics, MangaSpecifics, MovieSpecifics,
-        PodcastSpecifics, ShowSpecifics, VideoGameSpecifics,
+        AnimeSpecifics, AudioBookSpecifics, BookSpecifics, MangaSpecifics, MediaRecommendation,
+        MediaSearchItem, MovieSpecifics, PodcastSpecifics, ShowSpecifics, VideoGameSpecifics,
     },
     traits::MediaProviderLanguages,
 };
@@ -53,6 +53,70 @@ pub struct MetadataImage {
 #[derive(Clone, Debug, PartialEq, FromJsonQueryResult, Eq, Serialize, Deserialize, Default)]
 pub struct MetadataImages(pub Vec<MetadataImage>);
 
+/// Other titles a media item is known by, for eg in other languages
+#[derive(Clone, Debug, PartialEq, FromJsonQueryResult, Eq, Serialize, Deserialize, Default)]
+pub struct MetadataAlternativeTitles(pub Vec<String>);
+
+#[derive(Clone, Debug, PartialEq, FromJsonQueryResult, Eq, Serialize, Deserialize, Default)]
+pub struct MetadataGroupParts(pub Vec<MediaSearchItem>);
+
+/// The outcome of a bulk action on media
+#[derive(
+    Clone, Debug, PartialEq, FromJsonQueryResult, Eq, Serialize, Deserialize, SimpleObject, Default,
+)]
+pub struct BulkMediaActionResult {
+    /// The number of media that matched the filter
+    pub matched: usize,
+    /// The number of media that were changed by the action
+    pub affected: usize,
+}
+
+/// How far a user has got through a show
+#[derive(
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    FromJsonQueryResult,
+    Serialize,
+    Deserialize,
+    SimpleObject,
+    Default,
+)]
+pub struct ShowProgress {
+    pub seasons: Vec<ShowSeasonProgress>,
+    /// The percentage of aired episodes that have been watched
+    pub percentage: i32,
+    /// The first aired episode after the last one watched
+    pub next_episode: Option<ShowNextEpisode>,
+}
+
+#[derive(
+    Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, SimpleObject, Default,
+)]
+pub struct ShowSeasonProgress {
+    pub season_number: i32,
+    pub name: String,
+    /// The number of aired episodes of this season that have been watched
+    pub watched: usize,
+    /// The number of episodes of this season that have aired
+    pub total: usize,
+}
+
+#[derive(
+    Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, SimpleObject, Default,
+)]
+pub struct ShowNextEpisode {
+    pub season_number: i32,
+    pub episode_number: i32,
+    pub name: String,
+}
+
+#[derive(Clone, Debug, PartialEq, FromJsonQueryResult, Eq, Serialize, Deserialize, Default)]
+pub struct MediaRecommendations(pub Vec<MediaRecommendation>);
+
 #[derive(
     Clone,
     Debug,
@@ -1,10 +1,20 @@
-use std::{collections::HashSet, sync::Arc};
+use std::{
+    collections::HashSet,
+    env,
+    sync::{Arc, Mutex},
+};
 
 use anyhow::anyhow;
-use apalis::{prelude::Storage as ApalisStorage, sqlite::SqliteStorage};
+use apalis::{
+    prelude::{Job, Storage as ApalisStorage},
+    sqlite::SqliteStorage,
+};
 use argon2::{Argon2, PasswordHash, PasswordVerifier};
-use async_graphql::{Context, Enum, Error, InputObject, Object, Result, SimpleObject, Union};
-use chrono::{Duration as ChronoDuration, NaiveDate, Utc};
+use async_graphql::{
+    ComplexObject, Context, Enum, Error, InputObject, Object, Result, SimpleObject, Union,
+};
+use chrono::{Datelike, Duration as ChronoDuration, NaiveDate, Utc};
+use chrono_tz::Tz;
 use cookie::{time::Duration as CookieDuration, time::OffsetDateTime, Cookie};
 use enum_meta::{HashMap, Meta};
 use futures::TryStreamExt;
@@ -21,53 +31,71 @@ use rust_decimal::Decimal;
 use sea_orm::{
     prelude::DateTimeUtc, ActiveModelTrait, ActiveValue, ColumnTrait, ConnectionTrait,
     DatabaseBackend, DatabaseConnection, EntityTrait, FromQueryResult, Iden, JoinType, ModelTrait,
-    Order, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, Statement,
+    Order, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, Select, Statement,
+    TransactionTrait,
 };
 use sea_orm::{Iterable, QueryTrait};
 use sea_query::{
     Alias, Cond, Expr, Func, Keyword, MySqlQueryBuilder, NullOrdering, OrderedStatement,
-    PostgresQueryBuilder, Query, SelectStatement, SqliteQueryBuilder, UnionType, Values,
+    PostgresQueryBuilder, Query, SelectStatement, SimpleExpr, SqliteQueryBuilder, UnionType,
+    Values,
 };
 use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
 use strum::IntoEnumIterator;
 use uuid::Uuid;
 
 use crate::{
-    background::{RecalculateUserSummaryJob, UpdateMetadataJob, UserCreatedJob},
+    background::{
+        queued_imports_with_secrets, BulkMediaActionJob, JobHandle, JobRegistry, JobStatus,
+        RecalculateUserSummaryJob, UpdateMetadataBatchJob, UpdateMetadataJob, UserCreatedJob,
+        WorkerStatus,
+    },
     config::AppConfig,
+    encryption::SecretsCipher,
     entities::{
-        collection, genre, media_import_report, metadata, metadata_to_collection,
-        metadata_to_genre,
+        bulk_media_action_report, collection, genre, job_lease, media_import_report,
+        media_reminder, metadata, metadata_group, metadata_title, metadata_to_collection,
+        metadata_to_genre, metadata_to_metadata_group,
         prelude::{
-            Collection, Genre, MediaImportReport, Metadata, MetadataToCollection, Review, Seen,
-            Summary, User, UserToMetadata,
+            BulkMediaActionReport, Collection, Genre, JobLease, MediaImportReport, MediaReminder,
+            Metadata, MetadataGroup, MetadataTitle, MetadataToCollection, MetadataToGenre,
+            MetadataToMetadataGroup, ProviderLookupCache, Review, Seen, Summary, User,
+            UserProviderCredential, UserRecommendation, UserToMetadata,
         },
-        review, seen, summary, user, user_to_metadata,
+        provider_lookup_cache, review, seen, summary, user, user_provider_credential,
+        user_recommendation, user_to_metadata,
     },
     file_storage::FileStorageService,
     importer::ImportResultResponse,
     integrations::{IntegrationMedia, IntegrationService},
     migrator::{
-        MediaImportSource, Metadata as TempMetadata, MetadataImageLot, MetadataLot, MetadataSource,
-        Review as TempReview, Seen as TempSeen, SeenState, UserLot,
+        BulkMediaAction, MediaImportSource, MediaState, Metadata as TempMetadata, MetadataImageLot,
+        MetadataLot, MetadataSource, MetadataTitle as TempMetadataTitle, Provenance,
+        Review as TempReview, Seen as TempSeen, SeenDatePrecision, SeenState, UserLot,
         UserToMetadata as TempUserToMetadata,
     },
     miscellaneous::{
-        CustomService, DefaultCollection, MediaSpecifics, MetadataCreator, MetadataCreators,
-        MetadataImage, MetadataImageUrl, MetadataImages, SeenOrReviewExtraInformation,
-        SeenPodcastExtraInformation, SeenShowExtraInformation,
+        BulkMediaActionResult, CustomService, DefaultCollection, MediaRecommendations,
+        MediaSpecifics, MetadataAlternativeTitles, MetadataCreator, MetadataCreators,
+        MetadataGroupParts, MetadataImage, MetadataImageUrl, MetadataImages,
+        SeenOrReviewExtraInformation, SeenPodcastExtraInformation, SeenShowExtraInformation,
+        ShowNextEpisode, ShowProgress, ShowSeasonProgress,
     },
     models::{
         media::{
             AddMediaToCollection, AnimeSpecifics, AudioBookSpecifics, BookSpecifics,
-            CreateOrUpdateCollectionInput, ImportOrExportItem, ImportOrExportItemRating,
-            ImportOrExportItemReview, ImportOrExportItemSeen, MangaSpecifics, MediaDetails,
-            MediaListItem, MediaSearchItem, MovieSpecifics, PodcastSpecifics, PostReviewInput,
+            CreateOrUpdateCollectionInput, CreateSeenEntryInput, ImportOrExportItem,
+            ImportOrExportItemRating, ImportOrExportItemReview, ImportOrExportItemSeen,
+            MangaSpecifics, MediaDetails, MediaListItem, MediaRecommendation, MediaSearchItem,
+            MetadataGroupDetails, MovieSpecifics, PodcastSpecifics, PostReviewInput,
             ProgressUpdateError, ProgressUpdateErrorVariant, ProgressUpdateInput,
             ProgressUpdateResultUnion, ShowSpecifics, VideoGameSpecifics, Visibility,
         },
         IdObject, SearchInput, SearchResults,
     },
+    monitoring::{record_job_enqueued, record_provider_failure},
+    notification::{send_webhook, MonitorNotification, ReminderNotification},
     providers::{
         anilist::{AnilistAnimeService, AnilistMangaService, AnilistService},
         audible::AudibleService,
@@ -78,18 +106,67 @@ use crate::{
         openlibrary::OpenlibraryService,
         tmdb::{TmdbMovieService, TmdbService, TmdbShowService},
     },
+    rate_limiter::{rate_limited_error, RateLimitedOperation, RateLimiter},
+    shutdown::ShutdownSignal,
     traits::{AuthProvider, IsFeatureEnabled, MediaProvider, MediaProviderLanguages},
     users::{
-        UserPreferences, UserSinkIntegration, UserSinkIntegrationSetting, UserSinkIntegrations,
-        UserYankIntegration, UserYankIntegrationSetting, UserYankIntegrations,
+        UserPreferences, UserProviderCredentials, UserSinkIntegration, UserSinkIntegrationSetting,
+        UserSinkIntegrations, UserYankIntegration, UserYankIntegrationSetting,
+        UserYankIntegrations,
     },
     utils::{
-        convert_naive_to_utc, get_case_insensitive_like_query, user_id_from_token, MemoryAuthData,
-        MemoryDatabase, AUTHOR, COOKIE_NAME, PAGE_LIMIT, REPOSITORY_LINK, VERSION,
+        associate_user_with_metadata, convert_naive_to_utc, get_case_insensitive_like_query,
+        user_id_from_token, MemoryAuthData, MemoryDatabase, AUTHOR, COOKIE_NAME, MAX_RATING,
+        MAX_SUMMARY_JOB_ATTEMPTS, PAGE_LIMIT, PROJECT_NAME, REPOSITORY_LINK, VERSION,
     },
 };
 
-type Provider = Box<(dyn MediaProvider + Send + Sync)>;
+pub type Provider = Box<(dyn MediaProvider + Send + Sync)>;
+
+/// A provider client built with the credentials of a user. It is kept for as
+/// long as the credentials do not change so that its rate limits and access
+/// tokens are shared by all the requests of the user.
+#[derive(Clone)]
+enum UserProviderClient {
+    Audible(AudibleService),
+    Listennotes(ListennotesService),
+    Igdb(IgdbService),
+    TmdbShows(TmdbShowService),
+    TmdbMovies(TmdbMovieService),
+}
+
+impl UserProviderClient {
+    fn provider(&self) -> Provider {
+        match self {
+            Self::Audible(s) => Box::new(s.clone()),
+            Self::Listennotes(s) => Box::new(s.clone()),
+            Self::Igdb(s) => Box::new(s.clone()),
+            Self::TmdbShows(s) => Box::new(s.clone()),
+            Self::TmdbMovies(s) => Box::new(s.clone()),
+        }
+    }
+}
+
+/// The number of top rated media that recommendations are seeded from
+const RECOMMENDATION_SEEDS: usize = 5;
+/// Completions older than this are not used to seed recommendations
+const RECOMMENDATION_SEED_DAYS: i64 = 365;
+/// The number of days deleted seen items and reviews can be restored for
+pub const TRASH_RETENTION_DAYS: i64 = 30;
+/// The number of recommendations stored for each user and lot
+const MAX_RECOMMENDATIONS: usize = 50;
+/// The score given to an item each time a provider considers it similar to a
+/// seed. Items found through a shared genre score one per genre.
+const PROVIDER_RECOMMENDATION_SCORE: usize = 2;
+/// The most times the wait between refreshes of a failing podcast is doubled
+const MAX_REFRESH_BACKOFF: i32 = 5;
+/// The most media that are refreshed by one batch job
+const METADATA_BATCH_SIZE: usize = 100;
+/// The most seen items that are inserted in one statement
+pub const MAX_SEEN_BATCH_SIZE: usize = 500;
+/// How long a queued refresh of a media item keeps it from being queued again,
+/// in case its update job is lost
+const METADATA_REFRESH_LEASE_DAYS: i64 = 1;
 
 #[derive(Debug, Serialize, Deserialize, InputObject, Clone)]
 struct CreateCustomMediaInput {
@@ -247,6 +324,27 @@ struct UpdateUserFeaturePreferenceInput {
     value: bool,
 }
 
+#[derive(Debug, InputObject)]
+struct CreateMediaReminderInput {
+    metadata_id: i32,
+    remind_on: NaiveDate,
+    note: Option<String>,
+}
+
+#[derive(Debug, InputObject)]
+struct SetProviderCredentialsInput {
+    source: MetadataSource,
+    credentials: UserProviderCredentials,
+}
+
+/// A provider that the user has configured credentials for. The credentials
+/// themselves are never returned.
+#[derive(Debug, SimpleObject)]
+struct UserProviderCredentialsSummary {
+    source: MetadataSource,
+    updated_on: DateTimeUtc,
+}
+
 #[derive(Debug, InputObject)]
 struct CollectionContentsInput {
     collection_id: i32,
@@ -278,6 +376,27 @@ struct ReviewItem {
     show_season: Option<i32>,
     show_episode: Option<i32>,
     podcast_episode: Option<i32>,
+    provenance: Provenance,
+    import_source: Option<MediaImportSource>,
+    import_report_id: Option<i32>,
+}
+
+/// A deleted review that can still be restored
+#[derive(Debug, SimpleObject)]
+struct TrashedReview {
+    id: i32,
+    metadata_id: i32,
+    posted_on: DateTimeUtc,
+    rating: Option<Decimal>,
+    text: Option<String>,
+    deleted_at: DateTimeUtc,
+}
+
+/// The seen items and reviews deleted by a user that can still be restored
+#[derive(Debug, SimpleObject)]
+struct UserTrash {
+    seen: Vec<seen::Model>,
+    reviews: Vec<TrashedReview>,
 }
 
 #[derive(Debug, SimpleObject)]
@@ -287,6 +406,9 @@ struct CollectionItem {
     num_items: u64,
     description: Option<String>,
     visibility: Visibility,
+    /// When the media in this collection that was updated the longest time ago
+    /// was last updated
+    oldest_media_update: Option<DateTimeUtc>,
 }
 
 #[derive(SimpleObject)]
@@ -318,6 +440,7 @@ struct DetailedMediaSearchResults {
 }
 
 #[derive(Debug, Serialize, Deserialize, SimpleObject, Clone)]
+#[graphql(complex)]
 struct GraphqlMediaDetails {
     id: i32,
     title: String,
@@ -342,6 +465,37 @@ struct GraphqlMediaDetails {
     source_url: Option<String>,
     /// The number of users who have seen this media
     seen_by: i32,
+    /// The groups of related media that this media is a part of
+    groups: Vec<metadata_group::Model>,
+}
+
+#[ComplexObject]
+impl GraphqlMediaDetails {
+    /// How far the logged in user has got through this show. Empty for other
+    /// kinds of media.
+    async fn show_progress(&self, gql_ctx: &Context<'_>) -> Result<Option<ShowProgress>> {
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.show_progress(user_id, self.id).await
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, SimpleObject, Clone)]
+struct MediaGroupPart {
+    item: MediaSearchItem,
+    /// The id of the media if it is present in the database
+    metadata_id: Option<i32>,
+    /// The highest progress the user has made on this media
+    progress: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, SimpleObject, Clone)]
+struct MediaGroupDetails {
+    details: metadata_group::Model,
+    parts: Vec<MediaGroupPart>,
+    /// The index of the part that follows the last one the user has completed,
+    /// if they have not completed it yet
+    next_part: Option<usize>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Enum, Clone, PartialEq, Eq, Copy, Default)]
@@ -386,6 +540,7 @@ enum MediaGeneralFilter {
     InProgress,
     Dropped,
     OnAHold,
+    Stale,
     Completed,
     Unseen,
 }
@@ -394,6 +549,29 @@ enum MediaGeneralFilter {
 struct MediaFilter {
     general: Option<MediaGeneralFilter>,
     collection: Option<i32>,
+    state: Option<MediaState>,
+    /// Only media from this provider
+    source: Option<MetadataSource>,
+    /// Only media with this identifier at its provider
+    identifier: Option<String>,
+}
+
+/// What the user has done with a media item
+#[derive(Debug, SimpleObject)]
+struct UserMediaAssociation {
+    last_updated_on: DateTimeUtc,
+    monitored: bool,
+    ignored: bool,
+    state: Option<MediaState>,
+    seen_count: usize,
+    collections: Vec<String>,
+}
+
+#[derive(Debug, SimpleObject)]
+struct MediaByIdentifier {
+    details: GraphqlMediaDetails,
+    /// Not set if the media is not associated with the user
+    association: Option<UserMediaAssociation>,
 }
 
 #[derive(Debug, Serialize, Deserialize, InputObject, Clone)]
@@ -405,6 +583,26 @@ struct MediaListInput {
     filter: Option<MediaFilter>,
 }
 
+/// The media in the user's library that a bulk action is applied to. Ignored
+/// media never matches.
+#[derive(Debug, Serialize, Deserialize, InputObject, Clone)]
+pub struct BulkMediaFilter {
+    lot: Option<MetadataLot>,
+    /// The name of a collection the media must be in
+    collection: Option<String>,
+    /// Only media with a seen item in this state
+    seen_state: Option<SeenState>,
+    min_rating: Option<Decimal>,
+    max_rating: Option<Decimal>,
+}
+
+#[derive(Debug, Serialize, Deserialize, InputObject, Clone)]
+pub struct BulkMediaActionInput {
+    action: BulkMediaAction,
+    /// The collection to add the media to or remove it from
+    collection_name: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, InputObject, Clone)]
 struct CollectionInput {
     name: Option<String>,
@@ -431,6 +629,91 @@ struct CoreDetails {
     default_credentials: bool,
 }
 
+/// Whether imports from a source can work with the providers configured on
+/// this instance
+#[derive(Debug, SimpleObject)]
+pub struct ImportSourceAvailability {
+    source: MediaImportSource,
+    enabled: bool,
+    /// Why the source can not be used, or which of its media can not be
+    /// imported
+    reason: Option<String>,
+}
+
+#[derive(Debug, SimpleObject, Clone, PartialEq, Eq)]
+pub struct MetadataDuplicateCandidate {
+    metadata_id: i32,
+    source: MetadataSource,
+    identifier: String,
+    title: String,
+    publish_year: Option<i32>,
+    seen_count: u64,
+    review_count: u64,
+}
+
+/// Media items that look like the same thing fetched from different providers.
+/// They share a title, either in the same year or through one of their
+/// alternative titles.
+#[derive(Debug, SimpleObject, Clone)]
+pub struct MetadataDuplicateGroup {
+    lot: MetadataLot,
+    /// The shared title, in lowercase
+    title: String,
+    /// The item the others would be merged into, which is the one with the
+    /// most activity
+    merge_into: i32,
+    candidates: Vec<MetadataDuplicateCandidate>,
+}
+
+/// The details that identify a media item
+#[derive(Debug, SimpleObject, Clone, FromQueryResult)]
+pub struct MetadataOverview {
+    id: i32,
+    lot: MetadataLot,
+    source: MetadataSource,
+    identifier: String,
+    title: String,
+    last_updated_on: DateTimeUtc,
+}
+
+#[derive(Debug, FromQueryResult)]
+struct PossibleDuplicatePair {
+    first_id: i32,
+    second_id: i32,
+    title: String,
+}
+
+#[derive(Debug, SimpleObject, Clone)]
+pub struct InstanceLotStatistics {
+    lot: MetadataLot,
+    media: u64,
+}
+
+/// Counts about the whole instance, without any data of individual users
+#[derive(Debug, SimpleObject, Clone)]
+pub struct InstanceStatistics {
+    version: String,
+    /// The number of seconds since the server started
+    uptime: i64,
+    users: u64,
+    seen_events: u64,
+    reviews: u64,
+    lots: Vec<InstanceLotStatistics>,
+    /// When the counts were last computed
+    computed_on: DateTimeUtc,
+}
+
+/// The media a user finished in a year
+#[derive(Debug, SimpleObject, Clone, PartialEq, Eq)]
+pub struct YearlySeenSummary {
+    year: i32,
+    /// The number of seen items finished in the year
+    total: usize,
+    /// The number of seen items finished in each month, starting with January.
+    /// Items that are only known to be from the year are not in any month.
+    months: Vec<usize>,
+}
+
 #[derive(Debug, Ord, PartialEq, Eq, PartialOrd, Clone)]
 struct ProgressUpdateCache {
     user_id: i32,
@@ -440,6 +723,42 @@ struct ProgressUpdateCache {
     podcast_episode_number: Option<i32>,
 }
 
+/// Completed seen items that are saved together, so that large imports need
+/// fewer round trips to the database.
+pub struct SeenBatch {
+    user_id: i32,
+    size: usize,
+    items: Vec<(ProgressUpdateCache, seen::ActiveModel)>,
+    /// The media of the last item, since imports add items of the same media
+    /// one after the other
+    metadata: Option<metadata::Model>,
+}
+
+impl SeenBatch {
+    pub fn new(user_id: i32, size: usize) -> Self {
+        Self {
+            user_id,
+            size: size.clamp(1, MAX_SEEN_BATCH_SIZE),
+            items: vec![],
+            metadata: None,
+        }
+    }
+
+    pub fn contains_metadata(&self, metadata_id: i32) -> bool {
+        self.items.iter().any(|(k, _)| k.metadata_id == metadata_id)
+    }
+
+    fn contains(&self, key: &ProgressUpdateCache) -> bool {
+        self.items.iter().any(|(k, _)| k == key)
+    }
+
+    /// Add an item to the batch and return whether it is full.
+    fn push(&mut self, key: ProgressUpdateCache, row: seen::ActiveModel) -> bool {
+        self.items.push((key, row));
+        self.items.len() >= self.size
+    }
+}
+
 fn create_cookie(
     ctx: &Context<'_>,
     api_key: &str,
@@ -480,6 +799,23 @@ impl MiscellaneousQuery {
             .await
     }
 
+    /// Get anonymous statistics about the instance. Only available if they
+    /// have been made public in the config.
+    async fn instance_statistics(&self, gql_ctx: &Context<'_>) -> Result<InstanceStatistics> {
+        gql_ctx
+            .data_unchecked::<Arc<MiscellaneousService>>()
+            .instance_statistics()
+            .await
+    }
+
+    /// Get the seen items and reviews that the currently logged in user deleted
+    /// and that can still be restored.
+    async fn user_trash(&self, gql_ctx: &Context<'_>) -> Result<UserTrash> {
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.user_trash(user_id).await
+    }
+
     /// Get a review by its ID.
     async fn review_by_id(&self, gql_ctx: &Context<'_>, review_id: i32) -> Result<ReviewItem> {
         gql_ctx
@@ -544,6 +880,29 @@ impl MiscellaneousQuery {
             .await
     }
 
+    /// Get all the media in a group in order, along with the progress of the
+    /// currently logged in user on each of them.
+    async fn media_group_details(
+        &self,
+        gql_ctx: &Context<'_>,
+        group_id: i32,
+    ) -> Result<MediaGroupDetails> {
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.media_group_details(user_id, group_id).await
+    }
+
+    /// Get the number of media items the user finished in a year, by month.
+    async fn yearly_seen_summary(
+        &self,
+        gql_ctx: &Context<'_>,
+        year: i32,
+    ) -> Result<YearlySeenSummary> {
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.yearly_seen_summary(user_id, year).await
+    }
+
     /// Get the user's seen history for a particular media item.
     async fn seen_history(
         &self,
@@ -583,6 +942,18 @@ impl MiscellaneousQuery {
             .await
     }
 
+    /// Get the import sources along with whether they can be used on this
+    /// instance, taking the provider credentials of the logged in user into
+    /// account.
+    async fn import_source_availability(
+        &self,
+        gql_ctx: &Context<'_>,
+    ) -> Result<Vec<ImportSourceAvailability>> {
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await.ok();
+        service.import_source_availability(user_id).await
+    }
+
     /// Get a user's preferences.
     async fn user_preferences(&self, gql_ctx: &Context<'_>) -> Result<UserPreferences> {
         let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
@@ -598,10 +969,14 @@ impl MiscellaneousQuery {
         source: MetadataSource,
         input: SearchInput,
     ) -> Result<DetailedMediaSearchResults> {
-        gql_ctx
-            .data_unchecked::<Arc<MiscellaneousService>>()
-            .media_search(lot, source, input)
-            .await
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        // DEV: searching does not need an account, but the results of a user
+        // use their provider credentials and leave out the media they ignored
+        let user_id = service.user_id_from_ctx(gql_ctx).await.ok();
+        service
+            .rate_limit_guard(user_id, RateLimitedOperation::Search)
+            .await?;
+        service.media_search(user_id, lot, source, input).await
     }
 
     /// Check if a media with the given metadata and identifier exists in the database.
@@ -618,6 +993,22 @@ impl MiscellaneousQuery {
             .await
     }
 
+    /// Get a media item using its identifier at a provider, along with what the
+    /// currently logged in user has done with it.
+    async fn media_by_identifier(
+        &self,
+        gql_ctx: &Context<'_>,
+        lot: MetadataLot,
+        source: MetadataSource,
+        identifier: String,
+    ) -> Result<Option<MediaByIdentifier>> {
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service
+            .media_by_identifier(user_id, lot, source, &identifier)
+            .await
+    }
+
     /// Get all the metadata sources possible for a lot.
     async fn media_sources_for_lot(
         &self,
@@ -648,6 +1039,60 @@ impl MiscellaneousQuery {
         service.users().await
     }
 
+    /// Get the jobs running on this instance. Admins can see the jobs of all
+    /// users.
+    async fn background_jobs(&self, gql_ctx: &Context<'_>) -> Result<Vec<JobStatus>> {
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.background_jobs(user_id).await
+    }
+
+    /// Get whether the background workers of this instance are paused.
+    async fn background_worker_status(&self, gql_ctx: &Context<'_>) -> Result<WorkerStatus> {
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.admin_account_guard(user_id).await?;
+        Ok(service.jobs.worker_status())
+    }
+
+    /// Get the media items of a type that look like duplicates of each other,
+    /// along with what `mergeMetadataDuplicates` would do with them. Nothing is
+    /// changed.
+    async fn metadata_duplicates_preview(
+        &self,
+        gql_ctx: &Context<'_>,
+        lot: MetadataLot,
+        page: Option<i32>,
+    ) -> Result<Vec<MetadataDuplicateGroup>> {
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.admin_account_guard(user_id).await?;
+        service
+            .metadata_duplicates_preview(lot, page.unwrap_or(1))
+            .await
+    }
+
+    /// Get the media items that no user has any activity for. These are the
+    /// ones that the next cleanup job would remove.
+    async fn orphaned_metadata(
+        &self,
+        gql_ctx: &Context<'_>,
+        page: Option<i32>,
+    ) -> Result<Vec<MetadataOverview>> {
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.admin_account_guard(user_id).await?;
+        service.orphaned_metadata(page.unwrap_or(1)).await
+    }
+
+    /// Get the instances that last ran each of the cron jobs.
+    async fn job_leases(&self, gql_ctx: &Context<'_>) -> Result<Vec<job_lease::Model>> {
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.admin_account_guard(user_id).await?;
+        service.job_leases().await
+    }
+
     /// Get details about the currently logged in user.
     async fn user_details(&self, gql_ctx: &Context<'_>) -> Result<UserDetailsResult> {
         let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
@@ -678,6 +1123,55 @@ impl MiscellaneousQuery {
         let user_id = service.user_id_from_ctx(gql_ctx).await?;
         service.user_auth_tokens(user_id).await
     }
+
+    /// Get the reminders of the currently logged in user that have not been
+    /// sent yet.
+    async fn media_reminders(&self, gql_ctx: &Context<'_>) -> Result<Vec<media_reminder::Model>> {
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.media_reminders(user_id).await
+    }
+
+    /// Get the providers that the currently logged in user has configured
+    /// credentials for.
+    async fn user_provider_credentials(
+        &self,
+        gql_ctx: &Context<'_>,
+    ) -> Result<Vec<UserProviderCredentialsSummary>> {
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.user_provider_credentials_summary(user_id).await
+    }
+
+    /// Get the media that the currently logged in user has ignored.
+    async fn ignored_media(&self, gql_ctx: &Context<'_>) -> Result<Vec<MediaSearchItem>> {
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.ignored_media(user_id).await
+    }
+
+    /// Get all the bulk actions performed by the user
+    async fn bulk_media_action_reports(
+        &self,
+        gql_ctx: &Context<'_>,
+    ) -> Result<Vec<bulk_media_action_report::Model>> {
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.bulk_media_action_reports(user_id).await
+    }
+
+    /// Get the media recommended to the user based on their library. These are
+    /// recalculated daily.
+    async fn media_recommendations(
+        &self,
+        gql_ctx: &Context<'_>,
+        lot: MetadataLot,
+        count: Option<usize>,
+    ) -> Result<Vec<MediaRecommendation>> {
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.media_recommendations(user_id, lot, count).await
+    }
 }
 
 #[derive(Default)]
@@ -692,13 +1186,21 @@ impl MiscellaneousMutation {
         service.post_review(&user_id, input).await
     }
 
-    /// Delete a review if it belongs to the currently logged in user.
+    /// Delete a review if it belongs to the currently logged in user. It can be
+    /// restored until it is purged from the trash.
     async fn delete_review(&self, gql_ctx: &Context<'_>, review_id: i32) -> Result<bool> {
         let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
         let user_id = service.user_id_from_ctx(gql_ctx).await?;
         service.delete_review(&user_id, review_id).await
     }
 
+    /// Restore a review that the currently logged in user deleted.
+    async fn restore_review(&self, gql_ctx: &Context<'_>, review_id: i32) -> Result<bool> {
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.restore_review(&user_id, review_id).await
+    }
+
     /// Create a new collection for the logged in user or edit details of an existing one.
     async fn create_or_update_collection(
         &self,
@@ -746,66 +1248,264 @@ impl MiscellaneousMutation {
         service.delete_collection(&user_id, &collection_name).await
     }
 
-    /// Delete a seen item from a user's history.
+    /// Delete a seen item from a user's history. It can be restored until it is
+    /// purged from the trash.
     async fn delete_seen_item(&self, gql_ctx: &Context<'_>, seen_id: i32) -> Result<IdObject> {
         let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
         let user_id = service.user_id_from_ctx(gql_ctx).await?;
         service.delete_seen_item(seen_id, user_id).await
     }
 
-    /// Deploy jobs to update all media item's metadata.
-    async fn update_all_metadata(&self, gql_ctx: &Context<'_>) -> Result<bool> {
-        gql_ctx
-            .data_unchecked::<Arc<MiscellaneousService>>()
-            .update_all_metadata()
-            .await
+    /// Restore a seen item that was deleted from a user's history.
+    async fn restore_seen_item(&self, gql_ctx: &Context<'_>, seen_id: i32) -> Result<IdObject> {
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.restore_seen_item(seen_id, user_id).await
     }
 
-    /// Create a custom media item.
-    async fn create_custom_media(
-        &self,
-        gql_ctx: &Context<'_>,
-        input: CreateCustomMediaInput,
-    ) -> Result<CreateCustomMediaResult> {
+    /// Toggle whether the currently logged in user is notified when a media
+    /// item changes. Returns whether the media is now monitored.
+    async fn toggle_media_monitor(&self, gql_ctx: &Context<'_>, metadata_id: i32) -> Result<bool> {
         let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
         let user_id = service.user_id_from_ctx(gql_ctx).await?;
-        service.create_custom_media(input, &user_id).await
+        service.toggle_media_monitor(user_id, metadata_id).await
     }
 
-    /// Mark a user's progress on a specific media item.
-    async fn progress_update(
-        &self,
-        gql_ctx: &Context<'_>,
-        input: ProgressUpdateInput,
-    ) -> Result<ProgressUpdateResultUnion> {
+    /// Hide a media item from the library, search results and summary of the
+    /// currently logged in user. Imports do not add seen history to it.
+    async fn ignore_media(&self, gql_ctx: &Context<'_>, metadata_id: i32) -> Result<bool> {
         let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
         let user_id = service.user_id_from_ctx(gql_ctx).await?;
-        service.progress_update(input, user_id).await
+        service.set_media_ignored(user_id, metadata_id, true).await
     }
 
-    /// Deploy a job to update a media item's metadata.
-    async fn deploy_update_metadata_job(
+    /// Stop ignoring a media item for the currently logged in user.
+    async fn unignore_media(&self, gql_ctx: &Context<'_>, metadata_id: i32) -> Result<bool> {
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.set_media_ignored(user_id, metadata_id, false).await
+    }
+
+    /// Apply an action to all the media in the user's library matching a
+    /// filter. This runs in the background and can be cancelled like other
+    /// jobs. Returns the id of the report.
+    async fn bulk_media_action(
         &self,
         gql_ctx: &Context<'_>,
-        metadata_id: i32,
-    ) -> Result<String> {
-        gql_ctx
-            .data_unchecked::<Arc<MiscellaneousService>>()
-            .deploy_update_metadata_job(metadata_id)
+        action: BulkMediaActionInput,
+        filter: BulkMediaFilter,
+    ) -> Result<IdObject> {
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service
+            .deploy_bulk_media_action(user_id, action, filter)
             .await
     }
 
-    /// Merge a media item into another. This will move all `seen` and `review`
-    /// items with the new user and then delete the old media item completely.
-    async fn merge_metadata(
+    /// Recalculate the media recommendations of the user for a lot.
+    async fn refresh_media_recommendations(
         &self,
         gql_ctx: &Context<'_>,
-        merge_from: i32,
-        merge_into: i32,
+        lot: MetadataLot,
     ) -> Result<bool> {
-        gql_ctx
-            .data_unchecked::<Arc<MiscellaneousService>>()
-            .merge_metadata(merge_from, merge_into)
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.refresh_media_recommendations(user_id, lot).await
+    }
+
+    /// Create a reminder on a media item in the user's library.
+    async fn create_media_reminder(
+        &self,
+        gql_ctx: &Context<'_>,
+        input: CreateMediaReminderInput,
+    ) -> Result<IdObject> {
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.create_media_reminder(user_id, input).await
+    }
+
+    /// Delete a reminder of the currently logged in user.
+    async fn delete_media_reminder(&self, gql_ctx: &Context<'_>, reminder_id: i32) -> Result<bool> {
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.delete_media_reminder(user_id, reminder_id).await
+    }
+
+    /// Set the credentials the currently logged in user wants to use for a
+    /// provider instead of the ones of the instance.
+    async fn set_provider_credentials(
+        &self,
+        gql_ctx: &Context<'_>,
+        input: SetProviderCredentialsInput,
+    ) -> Result<bool> {
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.set_provider_credentials(user_id, input).await
+    }
+
+    /// Remove the credentials of the currently logged in user for a provider,
+    /// so that the ones of the instance are used again.
+    async fn clear_provider_credentials(
+        &self,
+        gql_ctx: &Context<'_>,
+        source: MetadataSource,
+    ) -> Result<bool> {
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.clear_provider_credentials(user_id, source).await
+    }
+
+    /// Check that the credentials of the currently logged in user for a
+    /// provider are accepted by it.
+    async fn test_provider_credentials(
+        &self,
+        gql_ctx: &Context<'_>,
+        source: MetadataSource,
+    ) -> Result<bool> {
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.test_provider_credentials(user_id, source).await
+    }
+
+    /// Move a stale seen item back to being in progress.
+    async fn unstale_seen_item(&self, gql_ctx: &Context<'_>, seen_id: i32) -> Result<IdObject> {
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.unstale_seen_item(seen_id, user_id).await
+    }
+
+    /// Deploy jobs to update all media item's metadata.
+    async fn update_all_metadata(&self, gql_ctx: &Context<'_>) -> Result<bool> {
+        gql_ctx
+            .data_unchecked::<Arc<MiscellaneousService>>()
+            .update_all_metadata()
+            .await
+    }
+
+    /// Create a custom media item.
+    async fn create_custom_media(
+        &self,
+        gql_ctx: &Context<'_>,
+        input: CreateCustomMediaInput,
+    ) -> Result<CreateCustomMediaResult> {
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.create_custom_media(input, &user_id).await
+    }
+
+    /// Mark a user's progress on a specific media item.
+    async fn progress_update(
+        &self,
+        gql_ctx: &Context<'_>,
+        input: ProgressUpdateInput,
+    ) -> Result<ProgressUpdateResultUnion> {
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.progress_update(input, user_id).await
+    }
+
+    /// Add a completed seen item with dates that might only be known to their
+    /// month or year.
+    async fn create_seen_entry(
+        &self,
+        gql_ctx: &Context<'_>,
+        input: CreateSeenEntryInput,
+    ) -> Result<IdObject> {
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.create_seen_entry(user_id, input).await
+    }
+
+    /// Change the state a user has put a media item in.
+    async fn change_media_state(
+        &self,
+        gql_ctx: &Context<'_>,
+        metadata_id: i32,
+        state: MediaState,
+    ) -> Result<bool> {
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service
+            .change_media_state(user_id, metadata_id, state)
+            .await
+    }
+
+    /// Deploy a job to update a media item's metadata.
+    async fn deploy_update_metadata_job(
+        &self,
+        gql_ctx: &Context<'_>,
+        metadata_id: i32,
+    ) -> Result<String> {
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service
+            .rate_limit_guard(Some(user_id), RateLimitedOperation::MetadataRefresh)
+            .await?;
+        service.deploy_update_metadata_job(metadata_id).await
+    }
+
+    /// Queue a refresh of the media in a collection of the currently logged in
+    /// user, starting with the ones updated the longest time ago. Returns the
+    /// number of media queued.
+    async fn refresh_collection_metadata(
+        &self,
+        gql_ctx: &Context<'_>,
+        collection_id: i32,
+    ) -> Result<usize> {
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service
+            .rate_limit_guard(Some(user_id), RateLimitedOperation::CollectionRefresh)
+            .await?;
+        service
+            .refresh_collection_metadata(user_id, collection_id)
+            .await
+    }
+
+    /// Merge a media item into another. This will move all `seen` and `review`
+    /// items with the new user and then delete the old media item completely.
+    async fn merge_metadata(
+        &self,
+        gql_ctx: &Context<'_>,
+        merge_from: i32,
+        merge_into: i32,
+    ) -> Result<bool> {
+        gql_ctx
+            .data_unchecked::<Arc<MiscellaneousService>>()
+            .merge_metadata(merge_from, merge_into)
+            .await
+    }
+
+    /// Merge media items that were listed together by
+    /// `metadataDuplicatesPreview`. Each of them must still look like a
+    /// duplicate of `mergeInto`, so items that do not anymore are left alone.
+    async fn merge_metadata_duplicates(
+        &self,
+        gql_ctx: &Context<'_>,
+        merge_into: i32,
+        merge_from: Vec<i32>,
+    ) -> Result<bool> {
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.admin_account_guard(user_id).await?;
+        service
+            .merge_metadata_duplicates(user_id, merge_into, merge_from)
+            .await
+    }
+
+    /// Delete media items listed by `orphanedMetadata` right away. Items that a
+    /// user has started using since are left alone. Returns the number of
+    /// items deleted.
+    async fn delete_orphaned_metadata(
+        &self,
+        gql_ctx: &Context<'_>,
+        metadata_ids: Vec<i32>,
+    ) -> Result<u64> {
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.admin_account_guard(user_id).await?;
+        service
+            .delete_orphaned_metadata(user_id, metadata_ids)
             .await
     }
 
@@ -817,9 +1517,10 @@ impl MiscellaneousMutation {
         source: MetadataSource,
         identifier: String,
     ) -> Result<IdObject> {
-        gql_ctx
-            .data_unchecked::<Arc<MiscellaneousService>>()
-            .commit_media(lot, source, &identifier)
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await.ok();
+        service
+            .commit_media(user_id, lot, source, &identifier)
             .await
     }
 
@@ -858,6 +1559,50 @@ impl MiscellaneousMutation {
         service.update_user(&user_id, input).await
     }
 
+    /// Request a running job to be cancelled. Users can only cancel their own
+    /// jobs unless they are an admin. Fails if the job is not running or can
+    /// not be cancelled, see `cancellable` of `backgroundJobs`.
+    async fn cancel_background_job(&self, gql_ctx: &Context<'_>, job_id: String) -> Result<bool> {
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.cancel_background_job(user_id, job_id).await
+    }
+
+    /// Stop the background workers of this instance from starting new jobs.
+    /// Running jobs are allowed to finish, queued jobs stay pending and cron
+    /// jobs are skipped until the workers are resumed. Returns `false` if they
+    /// were already paused.
+    async fn pause_background_workers(&self, gql_ctx: &Context<'_>) -> Result<bool> {
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.admin_account_guard(user_id).await?;
+        tracing::info!("Background workers paused by user {user_id}");
+        Ok(service.jobs.pause())
+    }
+
+    /// Let the background workers of this instance start new jobs again.
+    /// Returns `false` if they were not paused.
+    async fn resume_background_workers(&self, gql_ctx: &Context<'_>) -> Result<bool> {
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.admin_account_guard(user_id).await?;
+        tracing::info!("Background workers resumed by user {user_id}");
+        Ok(service.jobs.resume())
+    }
+
+    /// Remove all the seen history, reviews and collection memberships of the
+    /// currently logged in user. The confirmation token must be the username of
+    /// the user.
+    async fn clear_user_media(
+        &self,
+        gql_ctx: &Context<'_>,
+        confirmation_token: String,
+    ) -> Result<bool> {
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.clear_user_media(user_id, confirmation_token).await
+    }
+
     /// Delete all summaries for the currently logged in user and then generate one from scratch.
     pub async fn regenerate_user_summary(&self, gql_ctx: &Context<'_>) -> Result<bool> {
         let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
@@ -865,6 +1610,20 @@ impl MiscellaneousMutation {
         service.regenerate_user_summary(user_id).await
     }
 
+    /// Change a single preference of the user. The path is dot separated (for
+    /// eg: `general.timezone`) and the value is parsed as JSON, falling back to a
+    /// plain string.
+    async fn update_user_preference(
+        &self,
+        gql_ctx: &Context<'_>,
+        path: String,
+        value: String,
+    ) -> Result<bool> {
+        let service = gql_ctx.data_unchecked::<Arc<MiscellaneousService>>();
+        let user_id = service.user_id_from_ctx(gql_ctx).await?;
+        service.update_user_preference(path, value, user_id).await
+    }
+
     /// Change a user's feature preferences.
     async fn update_user_feature_preference(
         &self,
@@ -958,10 +1717,20 @@ pub struct MiscellaneousService {
     pub anilist_anime_service: AnilistAnimeService,
     pub anilist_manga_service: AnilistMangaService,
     pub integration_service: IntegrationService,
+    pub secrets: SecretsCipher,
     pub update_metadata: SqliteStorage<UpdateMetadataJob>,
+    pub update_metadata_batch: SqliteStorage<UpdateMetadataBatchJob>,
     pub recalculate_user_summary: SqliteStorage<RecalculateUserSummaryJob>,
     pub user_created: SqliteStorage<UserCreatedJob>,
+    pub bulk_media_action: SqliteStorage<BulkMediaActionJob>,
     seen_progress_cache: Arc<Cache<ProgressUpdateCache, ()>>,
+    rate_limiter: Arc<RateLimiter>,
+    instance_id: String,
+    pub jobs: Arc<JobRegistry>,
+    pub shutdown: Arc<ShutdownSignal>,
+    started_on: DateTimeUtc,
+    instance_statistics: Mutex<Option<InstanceStatistics>>,
+    user_providers: Mutex<HashMap<(i32, MetadataLot, MetadataSource), UserProviderClient>>,
 }
 
 impl AuthProvider for MiscellaneousService {
@@ -978,8 +1747,11 @@ impl MiscellaneousService {
         config: Arc<AppConfig>,
         file_storage: Arc<FileStorageService>,
         update_metadata: &SqliteStorage<UpdateMetadataJob>,
+        update_metadata_batch: &SqliteStorage<UpdateMetadataBatchJob>,
         recalculate_user_summary: &SqliteStorage<RecalculateUserSummaryJob>,
         user_created: &SqliteStorage<UserCreatedJob>,
+        bulk_media_action: &SqliteStorage<BulkMediaActionJob>,
+        shutdown: Arc<ShutdownSignal>,
     ) -> Self {
         let openlibrary_service = OpenlibraryService::new(&config.books.openlibrary).await;
         let google_books_service = GoogleBooksService::new(&config.books.google_books).await;
@@ -992,6 +1764,13 @@ impl MiscellaneousService {
         let anilist_anime_service = AnilistAnimeService::new(&config.anime.anilist).await;
         let anilist_manga_service = AnilistMangaService::new(&config.manga.anilist).await;
         let integration_service = IntegrationService::new().await;
+        let secrets = SecretsCipher::new(&config.server.secrets_key);
+        let rate_limiter = Arc::new(RateLimiter::new(&config.rate_limit));
+        let instance_id = format!(
+            "{}-{}",
+            env::var("HOSTNAME").unwrap_or_else(|_| PROJECT_NAME.to_owned()),
+            nanoid!(6)
+        );
 
         let seen_progress_cache = Arc::new(Cache::new());
         let cache_clone = seen_progress_cache.clone();
@@ -1007,6 +1786,13 @@ impl MiscellaneousService {
             auth_db: auth_db.clone(),
             config,
             seen_progress_cache,
+            rate_limiter,
+            instance_id,
+            jobs: Arc::new(JobRegistry::default()),
+            shutdown,
+            started_on: Utc::now(),
+            instance_statistics: Mutex::new(None),
+            user_providers: Mutex::new(HashMap::new()),
             file_storage,
             audible_service,
             google_books_service,
@@ -1019,9 +1805,12 @@ impl MiscellaneousService {
             anilist_anime_service,
             anilist_manga_service,
             integration_service,
+            secrets,
             update_metadata: update_metadata.clone(),
+            update_metadata_batch: update_metadata_batch.clone(),
             recalculate_user_summary: recalculate_user_summary.clone(),
             user_created: user_created.clone(),
+            bulk_media_action: bulk_media_action.clone(),
         }
     }
 }
@@ -1037,6 +1826,60 @@ impl MiscellaneousService {
         }
     }
 
+    async fn instance_statistics(&self) -> Result<InstanceStatistics> {
+        if !self.config.server.public_statistics {
+            return Err(Error::new("The statistics of this instance are not public"));
+        }
+        // DEV: the counts are only computed in the background, the last ones are
+        // served until they are computed again
+        let mut statistics = self
+            .instance_statistics
+            .lock()
+            .unwrap()
+            .clone()
+            .ok_or_else(|| {
+                Error::new("The statistics of this instance have not been computed yet")
+            })?;
+        statistics.uptime = (Utc::now() - self.started_on).num_seconds();
+        Ok(statistics)
+    }
+
+    /// Count everything on the instance again. This runs on a schedule so that
+    /// public requests never aggregate the database themselves.
+    pub async fn refresh_instance_statistics(&self) -> Result<InstanceStatistics> {
+        let lots = Metadata::find()
+            .select_only()
+            .column(metadata::Column::Lot)
+            .column_as(Expr::col(metadata::Column::Id).count(), "count")
+            .group_by(metadata::Column::Lot)
+            .into_tuple::<(MetadataLot, i64)>()
+            .all(&self.db)
+            .await?
+            .into_iter()
+            .map(|(lot, count)| InstanceLotStatistics {
+                lot,
+                media: count as u64,
+            })
+            .collect();
+        let statistics = InstanceStatistics {
+            version: VERSION.to_owned(),
+            uptime: (Utc::now() - self.started_on).num_seconds(),
+            users: User::find().count(&self.db).await?,
+            seen_events: Seen::find()
+                .filter(seen::Column::DeletedAt.is_null())
+                .count(&self.db)
+                .await?,
+            reviews: Review::find()
+                .filter(review::Column::DeletedAt.is_null())
+                .count(&self.db)
+                .await?,
+            lots,
+            computed_on: Utc::now(),
+        };
+        *self.instance_statistics.lock().unwrap() = Some(statistics.clone());
+        Ok(statistics)
+    }
+
     async fn metadata_images(&self, meta: &metadata::Model) -> Result<(Vec<String>, Vec<String>)> {
         let mut poster_images = vec![];
         let mut backdrop_images = vec![];
@@ -1109,6 +1952,7 @@ impl MiscellaneousService {
             backdrop_images,
             genres,
         } = self.generic_metadata(metadata_id).await?;
+        let groups = model.find_related(MetadataGroup).all(&self.db).await?;
         let slug = slug::slugify(&model.title);
         let identifier = &model.identifier;
         let source_url = match model.source {
@@ -1167,7 +2011,8 @@ impl MiscellaneousService {
                 TempSeen::Table,
                 seen_alias.clone(),
                 Expr::col((metadata_alias.clone(), TempMetadata::Id))
-                    .equals((seen_alias.clone(), TempSeen::MetadataId)),
+                    .equals((seen_alias.clone(), TempSeen::MetadataId))
+                    .and(Expr::col((seen_alias.clone(), TempSeen::DeletedAt)).is_null()),
             )
             .and_where(Expr::col((metadata_alias.clone(), TempMetadata::Id)).eq(metadata_id))
             .group_by_col((metadata_alias.clone(), TempMetadata::Id))
@@ -1205,6 +2050,7 @@ impl MiscellaneousService {
             anime_specifics: None,
             source_url,
             seen_by,
+            groups,
         };
         match model.specifics {
             MediaSpecifics::AudioBook(a) => {
@@ -1236,8 +2082,9 @@ impl MiscellaneousService {
         Ok(resp)
     }
 
-    async fn seen_history(&self, metadata_id: i32, user_id: i32) -> Result<Vec<seen::Model>> {
+    pub async fn seen_history(&self, metadata_id: i32, user_id: i32) -> Result<Vec<seen::Model>> {
         let mut seen = Seen::find()
+            .filter(seen::Column::DeletedAt.is_null())
             .filter(seen::Column::UserId.eq(user_id))
             .filter(seen::Column::MetadataId.eq(metadata_id))
             .order_by_desc(seen::Column::LastUpdatedOn)
@@ -1255,6 +2102,7 @@ impl MiscellaneousService {
     ) -> Result<SearchResults<MediaListItem>> {
         let meta = UserToMetadata::find()
             .filter(user_to_metadata::Column::UserId.eq(user_id))
+            .filter(user_to_metadata::Column::Ignored.eq(false))
             .all(&self.db)
             .await
             .unwrap();
@@ -1333,6 +2181,7 @@ impl MiscellaneousService {
                             )
                             .from(TempSeen::Table)
                             .and_where(Expr::col(TempSeen::UserId).eq(user_id))
+                            .and_where(Expr::col(TempSeen::DeletedAt).is_null())
                             .group_by_col(TempSeen::MetadataId)
                             .to_owned();
                         main_select = main_select
@@ -1385,6 +2234,10 @@ impl MiscellaneousService {
                                     .and(
                                         Expr::col((review_alias.clone(), TempReview::UserId))
                                             .eq(user_id),
+                                    )
+                                    .and(
+                                        Expr::col((review_alias.clone(), TempReview::DeletedAt))
+                                            .is_null(),
                                     ),
                             )
                             .group_by_col((metadata_alias.clone(), TempMetadata::Id))
@@ -1412,11 +2265,37 @@ impl MiscellaneousService {
                     )
                     .to_owned();
             }
+            if let Some(s) = f.source {
+                main_select = main_select
+                    .and_where(Expr::col((metadata_alias.clone(), TempMetadata::Source)).eq(s))
+                    .to_owned();
+            }
+            if let Some(s) = f.identifier {
+                main_select = main_select
+                    .and_where(Expr::col((metadata_alias.clone(), TempMetadata::Identifier)).eq(s))
+                    .to_owned();
+            }
+            if let Some(s) = f.state {
+                let in_state: Vec<i32> = UserToMetadata::find()
+                    .select_only()
+                    .column(user_to_metadata::Column::MetadataId)
+                    .filter(user_to_metadata::Column::UserId.eq(user_id))
+                    .filter(user_to_metadata::Column::State.eq(s.normalize()))
+                    .into_tuple()
+                    .all(&self.db)
+                    .await?;
+                main_select = main_select
+                    .and_where(
+                        Expr::col((metadata_alias.clone(), TempMetadata::Id)).is_in(in_state),
+                    )
+                    .to_owned();
+            }
             if let Some(s) = f.general {
                 let reviews = if matches!(s, MediaGeneralFilter::All) {
                     vec![]
                 } else {
                     Review::find()
+                        .filter(review::Column::DeletedAt.is_null())
                         .filter(review::Column::UserId.eq(user_id))
                         .all(&self.db)
                         .await?
@@ -1445,15 +2324,18 @@ impl MiscellaneousService {
                     MediaGeneralFilter::Dropped
                     | MediaGeneralFilter::InProgress
                     | MediaGeneralFilter::Completed
-                    | MediaGeneralFilter::OnAHold => {
+                    | MediaGeneralFilter::OnAHold
+                    | MediaGeneralFilter::Stale => {
                         let state = match s {
                             MediaGeneralFilter::Dropped => SeenState::Dropped,
                             MediaGeneralFilter::InProgress => SeenState::InProgress,
                             MediaGeneralFilter::Completed => SeenState::Completed,
                             MediaGeneralFilter::OnAHold => SeenState::OnAHold,
+                            MediaGeneralFilter::Stale => SeenState::Stale,
                             _ => unreachable!(),
                         };
                         let filtered_ids = Seen::find()
+                            .filter(seen::Column::DeletedAt.is_null())
                             .filter(seen::Column::UserId.eq(user_id))
                             .filter(seen::Column::State.eq(state))
                             .all(&self.db)
@@ -1520,6 +2402,12 @@ impl MiscellaneousService {
             .into_iter()
             .map(|qr| InnerMediaSearchItem::from_query_result(&qr, "").unwrap())
             .collect();
+        let podcast_ids = metas
+            .iter()
+            .filter(|m| m.lot == MetadataLot::Podcast)
+            .map(|m| m.id)
+            .collect::<Vec<_>>();
+        let unplayed = unplayed_podcast_episodes(&self.db, user_id, podcast_ids).await?;
         let mut items = vec![];
         for m in metas {
             let avg_select = Query::select()
@@ -1531,7 +2419,8 @@ impl MiscellaneousService {
                 .cond_where(
                     Cond::all()
                         .add(Expr::col((TempReview::Table, TempReview::UserId)).eq(user_id))
-                        .add(Expr::col((TempReview::Table, TempReview::MetadataId)).eq(m.id)),
+                        .add(Expr::col((TempReview::Table, TempReview::MetadataId)).eq(m.id))
+                        .add(Expr::col((TempReview::Table, TempReview::DeletedAt)).is_null()),
                 )
                 .to_owned();
             let stmt = self.get_db_stmt(avg_select);
@@ -1548,6 +2437,10 @@ impl MiscellaneousService {
                     ..Default::default()
                 })
                 .await?;
+            let unplayed_episodes = match m.lot {
+                MetadataLot::Podcast => Some(unplayed.get(&m.id).copied().unwrap_or_default()),
+                _ => None,
+            };
             let m_small = MediaListItem {
                 data: MediaSearchItem {
                     identifier: m.id.to_string(),
@@ -1555,8 +2448,10 @@ impl MiscellaneousService {
                     title: m.title,
                     image: poster_images.get(0).cloned(),
                     publish_year: m.publish_year,
+                    popularity: None,
                 },
                 average_rating: avg,
+                unplayed_episodes,
             };
             items.push(m_small);
         }
@@ -1572,50 +2467,172 @@ impl MiscellaneousService {
         })
     }
 
-    // DEV: First we update progress only if media has not been consumed for
-    // this user in the last `n` duration.
-    pub async fn progress_update(
-        &self,
-        input: ProgressUpdateInput,
-        user_id: i32,
-    ) -> Result<ProgressUpdateResultUnion> {
-        let cache = ProgressUpdateCache {
-            user_id,
-            metadata_id: input.metadata_id,
-            show_season_number: input.show_season_number,
-            show_episode_number: input.show_episode_number,
-            podcast_episode_number: input.podcast_episode_number,
-        };
-
-        if self.seen_progress_cache.get(&cache).await.is_some() {
-            return Ok(ProgressUpdateResultUnion::Error(ProgressUpdateError {
-                error: ProgressUpdateErrorVariant::AlreadySeen,
-            }));
+    async fn show_progress(&self, user_id: i32, metadata_id: i32) -> Result<Option<ShowProgress>> {
+        let association = UserToMetadata::find_by_id((user_id, metadata_id))
+            .one(&self.db)
+            .await?;
+        if let Some(progress) = association.as_ref().and_then(|a| a.show_progress.clone()) {
+            return Ok(Some(progress));
         }
-
-        let prev_seen = Seen::find()
-            .filter(seen::Column::Progress.lt(100))
+        let specifics = match Metadata::find_by_id(metadata_id).one(&self.db).await? {
+            Some(metadata::Model {
+                specifics: MediaSpecifics::Show(s),
+                ..
+            }) => s,
+            _ => return Ok(None),
+        };
+        let watched: HashSet<(i32, i32)> = Seen::find()
+            .filter(seen::Column::DeletedAt.is_null())
+            .select_only()
+            .column(seen::Column::ExtraInformation)
             .filter(seen::Column::UserId.eq(user_id))
-            .filter(seen::Column::State.ne(SeenState::Dropped))
-            .filter(seen::Column::MetadataId.eq(input.metadata_id))
-            .order_by_desc(seen::Column::LastUpdatedOn)
+            .filter(seen::Column::MetadataId.eq(metadata_id))
+            .filter(seen::Column::State.eq(SeenState::Completed))
+            .distinct()
+            .into_tuple::<Option<SeenOrReviewExtraInformation>>()
             .all(&self.db)
-            .await
-            .unwrap();
-        #[derive(Debug, Serialize, Deserialize, Enum, Clone, PartialEq, Eq, Copy)]
-        pub enum ProgressUpdateAction {
-            Update,
-            Now,
-            InThePast,
-            JustStarted,
-            ChangeState,
+            .await?
+            .into_iter()
+            .filter_map(|e| match e {
+                Some(SeenOrReviewExtraInformation::Show(s)) => Some((s.season, s.episode)),
+                _ => None,
+            })
+            .collect();
+        let progress = compute_show_progress(&specifics, &watched, Utc::now().date_naive());
+        if let Some(association) = association {
+            let mut association: user_to_metadata::ActiveModel = association.into();
+            association.show_progress = ActiveValue::Set(Some(progress.clone()));
+            association.update(&self.db).await?;
         }
-        let action = match input.change_state {
-            None => match input.progress {
-                None => ProgressUpdateAction::ChangeState,
-                Some(p) => {
-                    if p == 100 {
-                        match input.date {
+        Ok(Some(progress))
+    }
+
+    /// The state the user has put a media item in, if any.
+    pub async fn media_state(&self, user_id: i32, metadata_id: i32) -> Result<Option<MediaState>> {
+        Ok(UserToMetadata::find()
+            .filter(user_to_metadata::Column::UserId.eq(user_id))
+            .filter(user_to_metadata::Column::MetadataId.eq(metadata_id))
+            .one(&self.db)
+            .await?
+            .and_then(|u| u.state))
+    }
+
+    async fn set_media_state(
+        &self,
+        user_id: i32,
+        metadata_id: i32,
+        state: MediaState,
+    ) -> Result<()> {
+        associate_user_with_metadata(&user_id, &metadata_id, &self.db).await?;
+        UserToMetadata::update_many()
+            .filter(user_to_metadata::Column::UserId.eq(user_id))
+            .filter(user_to_metadata::Column::MetadataId.eq(metadata_id))
+            .col_expr(user_to_metadata::Column::State, Expr::value(state))
+            .exec(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn change_media_state(
+        &self,
+        user_id: i32,
+        metadata_id: i32,
+        state: MediaState,
+    ) -> Result<bool> {
+        let state = state.normalize();
+        // DEV: dropping or holding media also changes its latest seen item, the
+        // same way a progress update does
+        if matches!(state, MediaState::Dropped | MediaState::OnHold)
+            && !self.seen_history(metadata_id, user_id).await?.is_empty()
+        {
+            self.progress_update(
+                ProgressUpdateInput {
+                    metadata_id,
+                    progress: None,
+                    date: None,
+                    show_season_number: None,
+                    show_episode_number: None,
+                    podcast_episode_number: None,
+                    change_state: Some(state),
+                    plays: None,
+                    import: None,
+                },
+                user_id,
+            )
+            .await?;
+        }
+        self.set_media_state(user_id, metadata_id, state).await?;
+        Ok(true)
+    }
+
+    /// Drop the cached show progress of a media item, for one user or for everyone.
+    pub async fn invalidate_show_progress(
+        &self,
+        metadata_id: i32,
+        user_id: Option<i32>,
+    ) -> Result<()> {
+        let mut update = UserToMetadata::update_many()
+            .filter(user_to_metadata::Column::MetadataId.eq(metadata_id))
+            .col_expr(
+                user_to_metadata::Column::ShowProgress,
+                Expr::value(Option::<ShowProgress>::None),
+            );
+        if let Some(user_id) = user_id {
+            update = update.filter(user_to_metadata::Column::UserId.eq(user_id));
+        }
+        update.exec(&self.db).await?;
+        Ok(())
+    }
+
+    // DEV: First we update progress only if media has not been consumed for
+    // this user in the last `n` duration.
+    pub async fn progress_update(
+        &self,
+        mut input: ProgressUpdateInput,
+        user_id: i32,
+    ) -> Result<ProgressUpdateResultUnion> {
+        if let Some(progress) = input.progress.filter(|p| *p < 100) {
+            let threshold = self.completion_threshold(user_id).await?;
+            input.progress = Some(apply_completion_threshold(progress, threshold));
+        }
+        let cache = ProgressUpdateCache {
+            user_id,
+            metadata_id: input.metadata_id,
+            show_season_number: input.show_season_number,
+            show_episode_number: input.show_episode_number,
+            podcast_episode_number: input.podcast_episode_number,
+        };
+
+        if self.seen_progress_cache.get(&cache).await.is_some() {
+            return Ok(ProgressUpdateResultUnion::Error(ProgressUpdateError {
+                error: ProgressUpdateErrorVariant::AlreadySeen,
+            }));
+        }
+
+        let prev_seen = Seen::find()
+            .filter(seen::Column::DeletedAt.is_null())
+            .filter(seen::Column::Progress.lt(100))
+            .filter(seen::Column::UserId.eq(user_id))
+            .filter(seen::Column::State.ne(SeenState::Dropped))
+            .filter(seen::Column::MetadataId.eq(input.metadata_id))
+            .order_by_desc(seen::Column::LastUpdatedOn)
+            .all(&self.db)
+            .await
+            .unwrap();
+        #[derive(Debug, Serialize, Deserialize, Enum, Clone, PartialEq, Eq, Copy)]
+        pub enum ProgressUpdateAction {
+            Update,
+            Now,
+            InThePast,
+            JustStarted,
+            ChangeState,
+        }
+        let action = match input.change_state {
+            None => match input.progress {
+                None => ProgressUpdateAction::ChangeState,
+                Some(p) => {
+                    if p == 100 {
+                        match input.date {
                             None => ProgressUpdateAction::InThePast,
                             Some(u) => {
                                 if Utc::now().date_naive() == u {
@@ -1656,8 +2673,20 @@ impl MiscellaneousService {
                 last_seen.update(&self.db).await.unwrap()
             }
             ProgressUpdateAction::ChangeState => {
-                let new_state = input.change_state.unwrap_or(SeenState::Dropped);
+                let new_state = match input
+                    .change_state
+                    .unwrap_or(MediaState::Dropped)
+                    .seen_state()
+                {
+                    Some(s) => s,
+                    None => {
+                        return Ok(ProgressUpdateResultUnion::Error(ProgressUpdateError {
+                            error: ProgressUpdateErrorVariant::InvalidUpdate,
+                        }))
+                    }
+                };
                 let last_seen = Seen::find()
+                    .filter(seen::Column::DeletedAt.is_null())
                     .filter(seen::Column::UserId.eq(user_id))
                     .filter(seen::Column::MetadataId.eq(input.metadata_id))
                     .order_by_desc(seen::Column::LastUpdatedOn)
@@ -1684,73 +2713,31 @@ impl MiscellaneousService {
                     .await
                     .unwrap()
                     .unwrap();
-                let extra_infomation = match meta.lot {
-                    MetadataLot::Show => {
-                        if let (Some(season), Some(episode), MediaSpecifics::Show(spec)) = (
-                            input.show_season_number,
-                            input.show_episode_number,
-                            meta.specifics,
-                        ) {
-                            let mut is_there = false;
-                            for s in spec.seasons.iter() {
-                                for e in s.episodes.iter() {
-                                    if s.season_number == season && e.episode_number == episode {
-                                        is_there = true;
-                                        break;
-                                    }
-                                }
-                            }
-                            if !is_there {
-                                return Ok(ProgressUpdateResultUnion::Error(ProgressUpdateError {
-                                    error: ProgressUpdateErrorVariant::InvalidUpdate,
-                                }));
-                            }
-                            Some(SeenOrReviewExtraInformation::Show(
-                                SeenShowExtraInformation { season, episode },
-                            ))
-                        } else {
-                            return Ok(ProgressUpdateResultUnion::Error(ProgressUpdateError {
-                                error: ProgressUpdateErrorVariant::InvalidUpdate,
-                            }));
-                        }
-                    }
-                    MetadataLot::Podcast => {
-                        if let (Some(episode), MediaSpecifics::Podcast(spec)) =
-                            (input.podcast_episode_number, meta.specifics)
-                        {
-                            let mut is_there = false;
-                            for e in spec.episodes.iter() {
-                                if e.number == episode {
-                                    is_there = true;
-                                    break;
-                                }
-                            }
-                            if !is_there {
-                                return Ok(ProgressUpdateResultUnion::Error(ProgressUpdateError {
-                                    error: ProgressUpdateErrorVariant::InvalidUpdate,
-                                }));
-                            }
-                            Some(SeenOrReviewExtraInformation::Podcast(
-                                SeenPodcastExtraInformation { episode },
-                            ))
-                        } else {
-                            return Ok(ProgressUpdateResultUnion::Error(ProgressUpdateError {
-                                error: ProgressUpdateErrorVariant::InvalidUpdate,
-                            }));
-                        }
+                let extra_infomation = match seen_extra_information(&meta, &input) {
+                    Ok(e) => e,
+                    Err(error) => {
+                        return Ok(ProgressUpdateResultUnion::Error(ProgressUpdateError {
+                            error,
+                        }))
                     }
-                    _ => None,
                 };
                 let finished_on = if action == ProgressUpdateAction::JustStarted {
                     None
                 } else {
                     input.date
                 };
-                let (progress, started_on) = if matches!(action, ProgressUpdateAction::JustStarted)
-                {
-                    (0, Some(Utc::now().date_naive()))
-                } else {
-                    (100, None)
+                let (progress, started_on) = match (action, input.progress) {
+                    // DEV: imports can start an item with the progress the source
+                    // had recorded for it
+                    (ProgressUpdateAction::JustStarted, Some(p)) => {
+                        (p, Some(Utc::now().date_naive()))
+                    }
+                    (ProgressUpdateAction::JustStarted, None) => {
+                        return Ok(ProgressUpdateResultUnion::Error(ProgressUpdateError {
+                            error: ProgressUpdateErrorVariant::InvalidUpdate,
+                        }))
+                    }
+                    _ => (100, None),
                 };
                 let seen_insert = seen::ActiveModel {
                     progress: ActiveValue::Set(progress),
@@ -1761,6 +2748,14 @@ impl MiscellaneousService {
                     last_updated_on: ActiveValue::Set(Utc::now()),
                     extra_information: ActiveValue::Set(extra_infomation),
                     state: ActiveValue::Set(SeenState::InProgress),
+                    plays: ActiveValue::Set(input.plays.unwrap_or(1).max(1)),
+                    provenance: ActiveValue::Set(
+                        input
+                            .import
+                            .map_or(Provenance::Manual, |_| Provenance::Import),
+                    ),
+                    import_source: ActiveValue::Set(input.import.map(|i| i.source)),
+                    import_report_id: ActiveValue::Set(input.import.map(|i| i.report_id)),
                     ..Default::default()
                 };
                 seen_insert.insert(&self.db).await.unwrap()
@@ -1782,9 +2777,254 @@ impl MiscellaneousService {
         Ok(ProgressUpdateResultUnion::Ok(IdObject { id }))
     }
 
+    /// Save a completed seen item with the dates and precision given, without
+    /// assuming that anything happened today.
+    pub async fn create_seen_entry(
+        &self,
+        user_id: i32,
+        input: CreateSeenEntryInput,
+    ) -> Result<IdObject> {
+        let precision = input.precision.unwrap_or_default();
+        let started_on = input.started_on.map(|d| precision.truncate(d));
+        let finished_on = input.finished_on.map(|d| precision.truncate(d));
+        if matches!((started_on, finished_on), (Some(s), Some(f)) if s > f) {
+            return Err(Error::new(
+                "The media can not be finished before it was started",
+            ));
+        }
+        let meta = Metadata::find_by_id(input.metadata_id)
+            .one(&self.db)
+            .await?
+            .ok_or_else(|| Error::new("The media does not exist"))?;
+        let progress_input = ProgressUpdateInput {
+            metadata_id: input.metadata_id,
+            progress: Some(100),
+            date: finished_on,
+            show_season_number: input.show_season_number,
+            show_episode_number: input.show_episode_number,
+            podcast_episode_number: input.podcast_episode_number,
+            change_state: None,
+            plays: input.plays,
+            import: input.import,
+        };
+        let extra_information = seen_extra_information(&meta, &progress_input)
+            .map_err(|_| Error::new("The episode does not exist in this media"))?;
+        let seen_insert = seen::ActiveModel {
+            progress: ActiveValue::Set(100),
+            user_id: ActiveValue::Set(user_id),
+            metadata_id: ActiveValue::Set(input.metadata_id),
+            started_on: ActiveValue::Set(started_on),
+            finished_on: ActiveValue::Set(finished_on),
+            last_updated_on: ActiveValue::Set(Utc::now()),
+            extra_information: ActiveValue::Set(extra_information),
+            state: ActiveValue::Set(SeenState::Completed),
+            plays: ActiveValue::Set(input.plays.unwrap_or(1).max(1)),
+            date_precision: ActiveValue::Set(precision),
+            provenance: ActiveValue::Set(
+                input
+                    .import
+                    .map_or(Provenance::Manual, |_| Provenance::Import),
+            ),
+            import_source: ActiveValue::Set(input.import.map(|i| i.source)),
+            import_report_id: ActiveValue::Set(input.import.map(|i| i.report_id)),
+            ..Default::default()
+        };
+        let seen_item = seen_insert.insert(&self.db).await?;
+        let id = seen_item.id;
+        self.after_media_seen_tasks(seen_item).await?;
+        Ok(IdObject { id })
+    }
+
+    pub async fn yearly_seen_summary(&self, user_id: i32, year: i32) -> Result<YearlySeenSummary> {
+        let (Some(start), Some(end)) = (
+            NaiveDate::from_ymd_opt(year, 1, 1),
+            NaiveDate::from_ymd_opt(year, 12, 31),
+        ) else {
+            return Err(Error::new("The year is not valid"));
+        };
+        let finished: Vec<(NaiveDate, SeenDatePrecision)> = Seen::find()
+            .filter(seen::Column::DeletedAt.is_null())
+            .select_only()
+            .column(seen::Column::FinishedOn)
+            .column(seen::Column::DatePrecision)
+            .filter(seen::Column::UserId.eq(user_id))
+            .filter(seen::Column::State.eq(SeenState::Completed))
+            .filter(seen::Column::FinishedOn.between(start, end))
+            .into_tuple()
+            .all(&self.db)
+            .await?;
+        Ok(summarize_year(year, &finished))
+    }
+
+    /// Add a progress update to the batch instead of saving it. Only completed
+    /// items from the past are batched. Any other update flushes the batch and
+    /// is saved right away, so that updates are applied in order.
+    pub async fn batch_progress_update(
+        &self,
+        batch: &mut SeenBatch,
+        input: ProgressUpdateInput,
+    ) -> Result<()> {
+        let batchable = input.change_state.is_none()
+            && input.progress == Some(100)
+            && input.date != Some(Utc::now().date_naive());
+        if !batchable {
+            self.flush_seen_batch(batch).await?;
+            return self.progress_update(input, batch.user_id).await.map(|_| ());
+        }
+        let cache = ProgressUpdateCache {
+            user_id: batch.user_id,
+            metadata_id: input.metadata_id,
+            show_season_number: input.show_season_number,
+            show_episode_number: input.show_episode_number,
+            podcast_episode_number: input.podcast_episode_number,
+        };
+        if batch.contains(&cache) || self.seen_progress_cache.get(&cache).await.is_some() {
+            return Ok(());
+        }
+        let meta = match batch.metadata.take() {
+            Some(m) if m.id == input.metadata_id => m,
+            _ => Metadata::find_by_id(input.metadata_id)
+                .one(&self.db)
+                .await?
+                .ok_or_else(|| Error::new("The media does not exist"))?,
+        };
+        let extra_information = seen_extra_information(&meta, &input);
+        batch.metadata = Some(meta);
+        let extra_information = match extra_information {
+            Ok(e) => e,
+            Err(e) => {
+                tracing::debug!("Skipping seen item of media {}: {e:?}", input.metadata_id);
+                return Ok(());
+            }
+        };
+        let seen_insert = seen::ActiveModel {
+            progress: ActiveValue::Set(100),
+            user_id: ActiveValue::Set(batch.user_id),
+            metadata_id: ActiveValue::Set(input.metadata_id),
+            started_on: ActiveValue::Set(None),
+            finished_on: ActiveValue::Set(input.date),
+            last_updated_on: ActiveValue::Set(Utc::now()),
+            extra_information: ActiveValue::Set(extra_information),
+            state: ActiveValue::Set(SeenState::Completed),
+            plays: ActiveValue::Set(input.plays.unwrap_or(1).max(1)),
+            provenance: ActiveValue::Set(
+                input
+                    .import
+                    .map_or(Provenance::Manual, |_| Provenance::Import),
+            ),
+            import_source: ActiveValue::Set(input.import.map(|i| i.source)),
+            import_report_id: ActiveValue::Set(input.import.map(|i| i.report_id)),
+            ..Default::default()
+        };
+        if batch.push(cache, seen_insert) {
+            self.flush_seen_batch(batch).await?;
+        }
+        Ok(())
+    }
+
+    /// Save the items of the batch in one transaction.
+    pub async fn flush_seen_batch(&self, batch: &mut SeenBatch) -> Result<()> {
+        if batch.items.is_empty() {
+            return Ok(());
+        }
+        let (keys, rows): (Vec<_>, Vec<_>) = std::mem::take(&mut batch.items).into_iter().unzip();
+        let metadata_ids = keys.iter().map(|k| k.metadata_id).unique().collect_vec();
+        let txn = self.db.begin().await?;
+        insert_seen_batch(&txn, batch.user_id, rows, &metadata_ids).await?;
+        txn.commit().await?;
+        // DEV: the items are only marked as seen once they are saved, so that a
+        // failed batch can be imported again
+        for key in keys {
+            self.seen_progress_cache
+                .insert(
+                    key,
+                    (),
+                    ChronoDuration::hours(self.config.server.progress_update_threshold)
+                        .to_std()
+                        .unwrap(),
+                )
+                .await;
+        }
+        for metadata_id in metadata_ids {
+            let last_seen = Seen::find()
+                .filter(seen::Column::DeletedAt.is_null())
+                .filter(seen::Column::UserId.eq(batch.user_id))
+                .filter(seen::Column::MetadataId.eq(metadata_id))
+                .order_by_desc(seen::Column::Id)
+                .one(&self.db)
+                .await?;
+            if let Some(seen) = last_seen {
+                self.after_media_seen_tasks(seen).await?;
+            }
+        }
+        Ok(())
+    }
+
     pub async fn deploy_recalculate_summary_job(&self, user_id: i32) -> Result<()> {
         let mut storage = self.recalculate_user_summary.clone();
-        storage.push(RecalculateUserSummaryJob { user_id }).await?;
+        storage
+            .push(RecalculateUserSummaryJob {
+                user_id,
+                import_report_id: None,
+            })
+            .await?;
+        record_job_enqueued(RecalculateUserSummaryJob::NAME);
+        Ok(())
+    }
+
+    /// Recalculate the summary of a user after an import. The report is
+    /// flagged if the job can not be queued or fails later on.
+    pub async fn deploy_import_summary_job(&self, user_id: i32, report_id: i32) -> Result<()> {
+        let mut storage = self.recalculate_user_summary.clone();
+        let mut attempt = 1;
+        loop {
+            let job = RecalculateUserSummaryJob {
+                user_id,
+                import_report_id: Some(report_id),
+            };
+            match storage.push(job).await {
+                Ok(_) => {
+                    record_job_enqueued(RecalculateUserSummaryJob::NAME);
+                    return Ok(());
+                }
+                Err(e) if attempt < MAX_SUMMARY_JOB_ATTEMPTS => {
+                    tracing::warn!(
+                        "Could not queue summary recalculation for user {user_id} (attempt {attempt}): {e}"
+                    );
+                    tokio::time::sleep(std::time::Duration::from_secs(attempt)).await;
+                    attempt += 1;
+                }
+                Err(e) => {
+                    return self
+                        .flag_import_summary_stale(
+                            report_id,
+                            format!("The summary recalculation could not be queued: {e}"),
+                        )
+                        .await;
+                }
+            }
+        }
+    }
+
+    /// Record on an import report that the user's summary might not reflect
+    /// the imported data.
+    pub async fn flag_import_summary_stale(&self, report_id: i32, reason: String) -> Result<()> {
+        tracing::warn!("Import report {report_id}: {reason}");
+        let report = match MediaImportReport::find_by_id(report_id)
+            .one(&self.db)
+            .await?
+        {
+            Some(r) => r,
+            None => return Ok(()),
+        };
+        let mut details = match report.details.clone() {
+            Some(d) => d,
+            None => return Ok(()),
+        };
+        details.summary_warning = Some(reason);
+        let mut model: media_import_report::ActiveModel = report.into();
+        model.details = ActiveValue::Set(Some(details));
+        model.update(&self.db).await?;
         Ok(())
     }
 
@@ -1793,6 +3033,7 @@ impl MiscellaneousService {
         for u in user_to_metadatas {
             // check if there is any seen item
             let seen_count = Seen::find()
+                .filter(seen::Column::DeletedAt.is_null())
                 .filter(seen::Column::UserId.eq(u.user_id))
                 .filter(seen::Column::MetadataId.eq(u.metadata_id))
                 .count(&self.db)
@@ -1800,6 +3041,7 @@ impl MiscellaneousService {
                 .unwrap();
             // check if it has been reviewed
             let reviewed_count = Review::find()
+                .filter(review::Column::DeletedAt.is_null())
                 .filter(review::Column::UserId.eq(u.user_id))
                 .filter(review::Column::MetadataId.eq(u.metadata_id))
                 .count(&self.db)
@@ -1823,7 +3065,7 @@ impl MiscellaneousService {
                 .await
                 .unwrap();
             let is_in_collection = meta_ids.contains(&u.metadata_id);
-            if seen_count + reviewed_count == 0 && !is_in_collection {
+            if seen_count + reviewed_count == 0 && !is_in_collection && !u.monitored && !u.ignored {
                 tracing::debug!(
                     "Removing user_to_metadata = {id:?}",
                     id = (u.user_id, u.metadata_id)
@@ -1831,6 +3073,7 @@ impl MiscellaneousService {
                 u.delete(&self.db).await.ok();
             }
         }
+        self.cleanup_media_reminders().await?;
         Ok(())
     }
 
@@ -1844,12 +3087,24 @@ impl MiscellaneousService {
         creators: Vec<MetadataCreator>,
         specifics: MediaSpecifics,
         genres: Vec<String>,
+        publish_year: Option<i32>,
+        publish_date: Option<NaiveDate>,
+        alternative_titles: Vec<String>,
     ) -> Result<()> {
         let meta = Metadata::find_by_id(metadata_id)
             .one(&self.db)
             .await
             .unwrap()
             .unwrap();
+        self.save_metadata_titles(
+            metadata_id,
+            meta.lot,
+            publish_year,
+            &title,
+            &alternative_titles,
+        )
+        .await
+        .ok();
         let mut meta: metadata::ActiveModel = meta.into();
         meta.title = ActiveValue::Set(title);
         meta.description = ActiveValue::Set(description);
@@ -1857,6 +3112,9 @@ impl MiscellaneousService {
         meta.last_updated_on = ActiveValue::Set(Utc::now());
         meta.creators = ActiveValue::Set(MetadataCreators(creators));
         meta.specifics = ActiveValue::Set(specifics);
+        meta.publish_year = ActiveValue::Set(publish_year);
+        meta.publish_date = ActiveValue::Set(publish_date);
+        meta.alternative_titles = ActiveValue::Set(MetadataAlternativeTitles(alternative_titles));
         meta.save(&self.db).await.ok();
         for genre in genres {
             self.associate_genre_with_metadata(genre, metadata_id)
@@ -1901,29 +3159,154 @@ impl MiscellaneousService {
             identifier: ActiveValue::Set(details.identifier),
             creators: ActiveValue::Set(MetadataCreators(details.creators)),
             specifics: ActiveValue::Set(details.specifics),
+            alternative_titles: ActiveValue::Set(MetadataAlternativeTitles(
+                details.alternative_titles,
+            )),
             ..Default::default()
         };
         let metadata = metadata.insert(&self.db).await.unwrap();
+        self.save_metadata_titles(
+            metadata.id,
+            metadata.lot,
+            metadata.publish_year,
+            &metadata.title,
+            &metadata.alternative_titles.0,
+        )
+        .await
+        .ok();
         for genre in details.genres {
             self.associate_genre_with_metadata(genre, metadata.id)
                 .await
                 .ok();
         }
+        self.associate_groups_with_metadata(&metadata, details.groups)
+            .await
+            .ok();
         Ok(IdObject { id: metadata.id })
     }
 
+    /// Replace the groups a media item is part of. Groups are shared by all the
+    /// media in them, so their parts are updated with the latest ones from the
+    /// provider.
+    async fn associate_groups_with_metadata(
+        &self,
+        metadata: &metadata::Model,
+        groups: Vec<MetadataGroupDetails>,
+    ) -> Result<()> {
+        MetadataToMetadataGroup::delete_many()
+            .filter(metadata_to_metadata_group::Column::MetadataId.eq(metadata.id))
+            .exec(&self.db)
+            .await?;
+        for group in groups {
+            let existing = MetadataGroup::find()
+                .filter(metadata_group::Column::Identifier.eq(&group.identifier))
+                .filter(metadata_group::Column::Lot.eq(metadata.lot))
+                .filter(metadata_group::Column::Source.eq(metadata.source))
+                .one(&self.db)
+                .await?;
+            let db_group = match existing {
+                Some(g) => {
+                    let mut g: metadata_group::ActiveModel = g.into();
+                    g.title = ActiveValue::Set(group.title);
+                    g.parts = ActiveValue::Set(MetadataGroupParts(group.parts));
+                    g.last_updated_on = ActiveValue::Set(Utc::now());
+                    g.update(&self.db).await?
+                }
+                None => {
+                    let g = metadata_group::ActiveModel {
+                        identifier: ActiveValue::Set(group.identifier),
+                        title: ActiveValue::Set(group.title),
+                        lot: ActiveValue::Set(metadata.lot),
+                        source: ActiveValue::Set(metadata.source),
+                        parts: ActiveValue::Set(MetadataGroupParts(group.parts)),
+                        last_updated_on: ActiveValue::Set(Utc::now()),
+                        ..Default::default()
+                    };
+                    g.insert(&self.db).await?
+                }
+            };
+            let intermediate = metadata_to_metadata_group::ActiveModel {
+                metadata_id: ActiveValue::Set(metadata.id),
+                metadata_group_id: ActiveValue::Set(db_group.id),
+            };
+            intermediate.insert(&self.db).await.ok();
+        }
+        Ok(())
+    }
+
+    async fn media_group_details(&self, user_id: i32, group_id: i32) -> Result<MediaGroupDetails> {
+        let group = MetadataGroup::find_by_id(group_id)
+            .one(&self.db)
+            .await?
+            .ok_or_else(|| Error::new("This group does not exist"))?;
+        let identifiers = group
+            .parts
+            .0
+            .iter()
+            .map(|p| p.identifier.clone())
+            .collect_vec();
+        let metas = Metadata::find()
+            .filter(metadata::Column::Identifier.is_in(identifiers))
+            .filter(metadata::Column::Lot.eq(group.lot))
+            .filter(metadata::Column::Source.eq(group.source))
+            .all(&self.db)
+            .await?;
+        let seen = Seen::find()
+            .filter(seen::Column::DeletedAt.is_null())
+            .filter(seen::Column::UserId.eq(user_id))
+            .filter(seen::Column::MetadataId.is_in(metas.iter().map(|m| m.id)))
+            .all(&self.db)
+            .await?;
+        let parts = group
+            .parts
+            .0
+            .iter()
+            .map(|p| {
+                let metadata_id = metas
+                    .iter()
+                    .find(|m| m.identifier == p.identifier)
+                    .map(|m| m.id);
+                let progress = seen
+                    .iter()
+                    .filter(|s| Some(s.metadata_id) == metadata_id)
+                    .map(|s| s.progress)
+                    .max();
+                MediaGroupPart {
+                    item: p.clone(),
+                    metadata_id,
+                    progress,
+                }
+            })
+            .collect_vec();
+        let next_part = next_group_part(&parts);
+        Ok(MediaGroupDetails {
+            details: group,
+            parts,
+            next_part,
+        })
+    }
+
     pub async fn cleanup_metadata_with_associated_user_activities(&self) -> Result<()> {
-        let all_metadata = Metadata::find().all(&self.db).await.unwrap();
-        for metadata in all_metadata {
-            let num_associations = UserToMetadata::find()
-                .filter(user_to_metadata::Column::MetadataId.eq(metadata.id))
-                .count(&self.db)
-                .await
-                .unwrap();
-            if num_associations == 0 {
-                metadata.delete(&self.db).await.ok();
-            }
+        let ids = unused_metadata_ids(&self.db).await?;
+        if !ids.is_empty() {
+            tracing::debug!("Removing metadata = {ids:?}");
+            Metadata::delete_many()
+                .filter(metadata::Column::Id.is_in(ids))
+                .exec(&self.db)
+                .await?;
         }
+        let groups = MetadataGroup::delete_many()
+            .filter(
+                metadata_group::Column::Id.not_in_subquery(
+                    Query::select()
+                        .column(metadata_to_metadata_group::Column::MetadataGroupId)
+                        .from(MetadataToMetadataGroup)
+                        .to_owned(),
+                ),
+            )
+            .exec(&self.db)
+            .await?;
+        tracing::debug!("Removed {} empty metadata groups", groups.rows_affected);
         Ok(())
     }
 
@@ -1935,46 +3318,334 @@ impl MiscellaneousService {
             .unwrap();
         let mut storage = self.update_metadata.clone();
         let job_id = storage.push(UpdateMetadataJob { metadata }).await?;
+        record_job_enqueued(UpdateMetadataJob::NAME);
         Ok(job_id.to_string())
     }
 
-    pub async fn merge_metadata(&self, merge_from: i32, merge_into: i32) -> Result<bool> {
-        for old_seen in Seen::find()
-            .filter(seen::Column::MetadataId.eq(merge_from))
-            .all(&self.db)
-            .await
-            .unwrap()
-        {
-            let old_seen_active: seen::ActiveModel = old_seen.clone().into();
-            let new_seen = seen::ActiveModel {
-                id: ActiveValue::NotSet,
-                metadata_id: ActiveValue::Set(merge_into),
-                ..old_seen_active
-            };
-            new_seen.insert(&self.db).await?;
-            old_seen.delete(&self.db).await?;
+    /// Queue the refresh of many media, in jobs of at most
+    /// `METADATA_BATCH_SIZE` media each.
+    pub async fn deploy_update_metadata_batch_jobs(&self, metadata_ids: Vec<i32>) -> Result<()> {
+        let mut storage = self.update_metadata_batch.clone();
+        for chunk in metadata_ids.chunks(METADATA_BATCH_SIZE) {
+            storage
+                .push(UpdateMetadataBatchJob {
+                    metadata_ids: chunk.to_vec(),
+                })
+                .await?;
+            record_job_enqueued(UpdateMetadataBatchJob::NAME);
         }
-        for old_review in Review::find()
-            .filter(review::Column::MetadataId.eq(merge_from))
-            .all(&self.db)
-            .await
-            .unwrap()
-        {
-            let old_review_active: review::ActiveModel = old_review.clone().into();
-            let new_review = review::ActiveModel {
-                id: ActiveValue::NotSet,
-                metadata_id: ActiveValue::Set(merge_into),
-                ..old_review_active
+        Ok(())
+    }
+
+    async fn refresh_collection_metadata(&self, user_id: i32, collection_id: i32) -> Result<usize> {
+        let collection = Collection::find_by_id(collection_id)
+            .filter(collection::Column::UserId.eq(user_id))
+            .one(&self.db)
+            .await?
+            .ok_or_else(|| Error::new("This collection does not exist"))?;
+        let metas = collection
+            .find_related(Metadata)
+            .order_by_asc(metadata::Column::LastUpdatedOn)
+            .all(&self.db)
+            .await?;
+        let mut storage = self.update_metadata.clone();
+        let mut queued = 0;
+        for metadata in metas {
+            if queued >= self.config.rate_limit.collection_refresh_max_items {
+                break;
+            }
+            // DEV: media whose refresh is still queued is skipped, so refreshing
+            // the same collection again does not queue the same jobs twice
+            if !lease_metadata_refresh(&self.db, metadata.id, &self.instance_id).await? {
+                continue;
+            }
+            storage.push(UpdateMetadataJob { metadata }).await?;
+            record_job_enqueued(UpdateMetadataJob::NAME);
+            queued += 1;
+        }
+        tracing::debug!("Queued {queued} refreshes for collection {collection_id}");
+        Ok(queued as usize)
+    }
+
+    /// Allow the media to be queued for a refresh again, once its update job
+    /// has run.
+    pub async fn release_metadata_refresh(&self, metadata_id: i32) -> Result<()> {
+        JobLease::delete_by_id(metadata_refresh_lease(metadata_id))
+            .exec(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn merge_metadata(&self, merge_from: i32, merge_into: i32) -> Result<bool> {
+        let txn = self.db.begin().await?;
+        merge_metadata_rows(&txn, merge_from, merge_into).await?;
+        txn.commit().await?;
+        Ok(true)
+    }
+
+    async fn metadata_duplicates_preview(
+        &self,
+        lot: MetadataLot,
+        page: i32,
+    ) -> Result<Vec<MetadataDuplicateGroup>> {
+        let query = Self::possible_duplicate_pairs_query(lot)
+            .order_by(Alias::new("first_id"), Order::Asc)
+            .order_by(Alias::new("second_id"), Order::Asc)
+            .limit(PAGE_LIMIT as u64)
+            .offset(((page - 1) * PAGE_LIMIT) as u64)
+            .to_owned();
+        let pairs = PossibleDuplicatePair::find_by_statement(self.get_db_stmt(query))
+            .all(&self.db)
+            .await?;
+        let ids = pairs
+            .iter()
+            .flat_map(|p| [p.first_id, p.second_id])
+            .unique()
+            .collect_vec();
+        let metas: HashMap<i32, metadata::Model> = Metadata::find()
+            .filter(metadata::Column::Id.is_in(ids))
+            .all(&self.db)
+            .await?
+            .into_iter()
+            .map(|m| (m.id, m))
+            .collect();
+        let sources = metas.iter().map(|(id, m)| (*id, m.source)).collect();
+        let activity = activity_counts(&self.db, metas.keys().copied().collect()).await?;
+        let mut groups = vec![];
+        for (title, ids) in group_duplicate_pairs(pairs, &sources) {
+            let candidates = ids
+                .into_iter()
+                .filter_map(|id| metas.get(&id))
+                .map(|m| {
+                    let (seen_count, review_count) =
+                        activity.get(&m.id).copied().unwrap_or_default();
+                    MetadataDuplicateCandidate {
+                        metadata_id: m.id,
+                        source: m.source,
+                        identifier: m.identifier.clone(),
+                        title: m.title.clone(),
+                        publish_year: m.publish_year,
+                        seen_count,
+                        review_count,
+                    }
+                })
+                .collect_vec();
+            let Some(merge_into) = candidates
+                .iter()
+                .max_by_key(|c| {
+                    (
+                        c.seen_count + c.review_count,
+                        std::cmp::Reverse(c.metadata_id),
+                    )
+                })
+                .map(|c| c.metadata_id)
+            else {
+                continue;
             };
-            new_review.insert(&self.db).await?;
-            old_review.delete(&self.db).await?;
+            groups.push(MetadataDuplicateGroup {
+                lot,
+                title,
+                merge_into,
+                candidates,
+            });
+        }
+        Ok(groups)
+    }
+
+    /// Whether two media items of a type share a title, as listed by
+    /// `metadata_duplicates_preview`.
+    async fn is_possible_duplicate(&self, lot: MetadataLot, a: i32, b: i32) -> Result<bool> {
+        let query = Self::possible_duplicate_pairs_query(lot)
+            .and_where(Expr::col((Alias::new("x"), TempMetadataTitle::MetadataId)).eq(a.min(b)))
+            .and_where(Expr::col((Alias::new("y"), TempMetadataTitle::MetadataId)).eq(a.max(b)))
+            .to_owned();
+        let found = PossibleDuplicatePair::find_by_statement(self.get_db_stmt(query))
+            .one(&self.db)
+            .await?;
+        Ok(found.is_some())
+    }
+
+    async fn merge_metadata_duplicates(
+        &self,
+        user_id: i32,
+        merge_into: i32,
+        merge_from: Vec<i32>,
+    ) -> Result<bool> {
+        if merge_from.is_empty() || merge_from.contains(&merge_into) {
+            return Err(Error::new("Nothing to merge"));
+        }
+        let metas = Metadata::find()
+            .filter(metadata::Column::Id.is_in(merge_from.iter().copied().chain([merge_into])))
+            .all(&self.db)
+            .await?;
+        let target = metas
+            .iter()
+            .find(|m| m.id == merge_into)
+            .ok_or_else(|| Error::new("This media is not listed as a duplicate"))?;
+        // DEV: a provider lists distinct items that happen to have the same
+        // title, so the items to merge must all come from different ones
+        if !metas.iter().map(|m| m.source).all_unique() {
+            return Err(Error::new(
+                "Media from the same provider can not be merged".to_owned(),
+            ));
         }
-        Metadata::delete_by_id(merge_from).exec(&self.db).await?;
+        for id in merge_from.iter() {
+            if !metas.iter().any(|m| m.id == *id)
+                || !self
+                    .is_possible_duplicate(target.lot, *id, merge_into)
+                    .await?
+            {
+                return Err(Error::new(format!(
+                    "Media with id = {id} is not a duplicate of {merge_into}"
+                )));
+            }
+        }
+        // DEV: either all of them are merged or none, so that the preview still
+        // lists the group if something goes wrong
+        let txn = self.db.begin().await?;
+        for id in merge_from.iter() {
+            merge_metadata_rows(&txn, *id, merge_into).await?;
+        }
+        txn.commit().await?;
+        tracing::info!("User {user_id} merged media with ids = {merge_from:?} into {merge_into}");
         Ok(true)
     }
 
-    async fn user_preferences(&self, user_id: i32) -> Result<UserPreferences> {
+    async fn orphaned_metadata(&self, page: i32) -> Result<Vec<MetadataOverview>> {
+        let items = unused_metadata()
+            .select_only()
+            .columns([
+                metadata::Column::Id,
+                metadata::Column::Lot,
+                metadata::Column::Source,
+                metadata::Column::Identifier,
+                metadata::Column::Title,
+                metadata::Column::LastUpdatedOn,
+            ])
+            .order_by_asc(metadata::Column::Id)
+            .limit(PAGE_LIMIT as u64)
+            .offset(((page - 1) * PAGE_LIMIT) as u64)
+            .into_model::<MetadataOverview>()
+            .all(&self.db)
+            .await?;
+        Ok(items)
+    }
+
+    async fn delete_orphaned_metadata(&self, user_id: i32, metadata_ids: Vec<i32>) -> Result<u64> {
+        if metadata_ids.is_empty() {
+            return Ok(0);
+        }
+        // DEV: the ids are checked again since a user might have started using
+        // the media after it was listed
+        let ids: Vec<i32> = unused_metadata()
+            .select_only()
+            .column(metadata::Column::Id)
+            .filter(metadata::Column::Id.is_in(metadata_ids))
+            .into_tuple()
+            .all(&self.db)
+            .await?;
+        if ids.is_empty() {
+            return Ok(0);
+        }
+        let deleted = Metadata::delete_many()
+            .filter(metadata::Column::Id.is_in(ids.clone()))
+            .exec(&self.db)
+            .await?;
+        tracing::info!("User {user_id} deleted orphaned media = {ids:?}");
+        Ok(deleted.rows_affected)
+    }
+
+    /// The query that pairs up media of the same type sharing a title. Each pair
+    /// is listed once, with the lower id first.
+    fn possible_duplicate_pairs_query(lot: MetadataLot) -> SelectStatement {
+        let first_alias = Alias::new("x");
+        let second_alias = Alias::new("y");
+        let first_col = |c: TempMetadataTitle| Expr::col((first_alias.clone(), c));
+        let second_col = |c: TempMetadataTitle| Expr::col((second_alias.clone(), c));
+        Query::select()
+            .expr_as(
+                first_col(TempMetadataTitle::MetadataId),
+                Alias::new("first_id"),
+            )
+            .expr_as(
+                second_col(TempMetadataTitle::MetadataId),
+                Alias::new("second_id"),
+            )
+            .expr_as(
+                Func::min(first_col(TempMetadataTitle::Title)),
+                Alias::new("title"),
+            )
+            .from_as(TempMetadataTitle::Table, first_alias.clone())
+            .join_as(
+                JoinType::InnerJoin,
+                TempMetadataTitle::Table,
+                second_alias.clone(),
+                Cond::all()
+                    .add(
+                        first_col(TempMetadataTitle::Lot)
+                            .equals((second_alias.clone(), TempMetadataTitle::Lot)),
+                    )
+                    .add(
+                        first_col(TempMetadataTitle::Title)
+                            .equals((second_alias.clone(), TempMetadataTitle::Title)),
+                    )
+                    .add(
+                        first_col(TempMetadataTitle::MetadataId)
+                            .lt(second_col(TempMetadataTitle::MetadataId)),
+                    )
+                    .add(
+                        Cond::any()
+                            .add(first_col(TempMetadataTitle::Alias).eq(true))
+                            .add(second_col(TempMetadataTitle::Alias).eq(true))
+                            .add(
+                                first_col(TempMetadataTitle::PublishYear)
+                                    .equals((second_alias.clone(), TempMetadataTitle::PublishYear)),
+                            ),
+                    ),
+            )
+            .and_where(first_col(TempMetadataTitle::Lot).eq(lot))
+            .group_by_columns([
+                (first_alias.clone(), TempMetadataTitle::MetadataId),
+                (second_alias.clone(), TempMetadataTitle::MetadataId),
+            ])
+            .to_owned()
+    }
+
+    /// Store the normalized titles of a media item so that it can be matched
+    /// with others sharing one of them.
+    async fn save_metadata_titles(
+        &self,
+        metadata_id: i32,
+        lot: MetadataLot,
+        publish_year: Option<i32>,
+        title: &str,
+        alternative_titles: &[String],
+    ) -> Result<()> {
+        MetadataTitle::delete_many()
+            .filter(metadata_title::Column::MetadataId.eq(metadata_id))
+            .exec(&self.db)
+            .await?;
+        let titles = metadata_titles(title, alternative_titles)
+            .into_iter()
+            .map(|(title, alias)| metadata_title::ActiveModel {
+                metadata_id: ActiveValue::Set(metadata_id),
+                title: ActiveValue::Set(title),
+                lot: ActiveValue::Set(lot),
+                publish_year: ActiveValue::Set(publish_year),
+                alias: ActiveValue::Set(alias),
+            })
+            .collect_vec();
+        if !titles.is_empty() {
+            MetadataTitle::insert_many(titles).exec(&self.db).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn user_preferences(&self, user_id: i32) -> Result<UserPreferences> {
         let mut prefs = self.user_by_id(user_id).await?.preferences;
+        for secret in prefs.secrets_mut() {
+            *secret = self.secrets.open(secret)?;
+        }
         prefs.features_enabled.anime =
             self.config.anime.is_enabled() && prefs.features_enabled.anime;
         prefs.features_enabled.audio_books =
@@ -2006,13 +3677,87 @@ impl MiscellaneousService {
         Ok(general)
     }
 
+    async fn import_source_availability(
+        &self,
+        user_id: Option<i32>,
+    ) -> Result<Vec<ImportSourceAvailability>> {
+        let file_storage = self.core_enabled_features().await?.file_storage;
+        let user_sources: Vec<MetadataSource> = match user_id {
+            Some(user_id) => {
+                UserProviderCredential::find()
+                    .select_only()
+                    .column(user_provider_credential::Column::Source)
+                    .filter(user_provider_credential::Column::UserId.eq(user_id))
+                    .into_tuple()
+                    .all(&self.db)
+                    .await?
+            }
+            None => vec![],
+        };
+        let configured = |lot: MetadataLot, source: MetadataSource| {
+            user_sources.contains(&source)
+                || match (lot, source) {
+                    (MetadataLot::Movie, MetadataSource::Tmdb) => {
+                        !self.config.movies.tmdb.access_token.is_empty()
+                    }
+                    (MetadataLot::Show, MetadataSource::Tmdb) => {
+                        !self.config.shows.tmdb.access_token.is_empty()
+                    }
+                    (_, MetadataSource::Igdb) => self.config.video_games.is_enabled(),
+                    _ => true,
+                }
+        };
+        Ok(MediaImportSource::iter()
+            .map(|source| {
+                let needs_file_storage = matches!(
+                    source,
+                    MediaImportSource::RyotSqlite | MediaImportSource::Xlsx
+                );
+                let providers = import_source_providers(source);
+                let missing = providers
+                    .iter()
+                    .filter(|(lot, source)| !configured(*lot, *source))
+                    .collect_vec();
+                let (enabled, reason) = if needs_file_storage && !file_storage {
+                    (false, Some("File storage is not enabled".to_owned()))
+                } else if missing.is_empty() {
+                    (true, None)
+                } else {
+                    let names = missing
+                        .iter()
+                        .map(|(lot, source)| format!("{source:?} ({lot:?})"))
+                        .join(", ");
+                    let enabled = missing.len() < providers.len();
+                    let reason = if enabled {
+                        format!("The media from {names} can not be imported until it is configured")
+                    } else {
+                        format!("{names} must be configured")
+                    };
+                    (enabled, Some(reason))
+                };
+                ImportSourceAvailability {
+                    source,
+                    enabled,
+                    reason,
+                }
+            })
+            .collect())
+    }
+
     async fn media_search(
         &self,
+        user_id: Option<i32>,
         lot: MetadataLot,
         source: MetadataSource,
         input: SearchInput,
     ) -> Result<DetailedMediaSearchResults> {
-        let provider = self.get_provider(lot, source)?;
+        let (provider, ignored) = match user_id {
+            Some(user_id) => (
+                self.get_user_provider(user_id, lot, source).await?,
+                self.ignored_metadata_ids(user_id).await?,
+            ),
+            None => (self.get_provider(lot, source)?, vec![]),
+        };
         let results = provider.search(&input.query, input.page).await?;
         let mut all_idens = results
             .items
@@ -2095,6 +3840,7 @@ impl MiscellaneousService {
                         .and_then(|i| i.id),
                     item: i,
                 })
+                .filter(|i| i.database_id.map_or(true, |id| !ignored.contains(&id)))
                 .collect()
         };
         let results = DetailedMediaSearchResults {
@@ -2114,13 +3860,16 @@ impl MiscellaneousService {
             .await
             .unwrap()
             .unwrap();
+        let user_id = self
+            .credentials_user_for_metadata(metadata.id, metadata.source)
+            .await?;
         let results = self
-            .details_from_provider(metadata.lot, metadata.source, &metadata.identifier)
+            .details_from_provider(user_id, metadata.lot, metadata.source, &metadata.identifier)
             .await?;
         Ok(results)
     }
 
-    fn get_provider(&self, lot: MetadataLot, source: MetadataSource) -> Result<Provider> {
+    pub fn get_provider(&self, lot: MetadataLot, source: MetadataSource) -> Result<Provider> {
         let service: Provider = match source {
             MetadataSource::Openlibrary => Box::new(self.openlibrary_service.clone()),
             MetadataSource::Itunes => Box::new(self.itunes_service.clone()),
@@ -2145,22 +3894,300 @@ impl MiscellaneousService {
         Ok(service)
     }
 
+    /// The provider built with the credentials the user has configured for it,
+    /// or the one of the instance if there are none or they can not be read.
+    pub async fn get_user_provider(
+        &self,
+        user_id: i32,
+        lot: MetadataLot,
+        source: MetadataSource,
+    ) -> Result<Provider> {
+        let key = (user_id, lot, source);
+        if let Some(client) = self.user_providers.lock().unwrap().get(&key) {
+            return Ok(client.provider());
+        }
+        let credentials = match self.provider_credentials(user_id, source).await {
+            Ok(Some(credentials)) => credentials,
+            Ok(None) => return self.get_provider(lot, source),
+            Err(e) => {
+                tracing::warn!(
+                    "Could not read the {source:?} credentials of user {user_id}, using the \
+                    ones of the instance: {e:?}"
+                );
+                return self.get_provider(lot, source);
+            }
+        };
+        match self.user_provider_client(lot, source, credentials).await {
+            Some(client) => {
+                let provider = client.provider();
+                self.user_providers.lock().unwrap().insert(key, client);
+                Ok(provider)
+            }
+            None => self.get_provider(lot, source),
+        }
+    }
+
+    /// Forget the clients built with the credentials of a user for a source,
+    /// so that the next requests use the new ones.
+    fn forget_user_providers(&self, user_id: i32, source: MetadataSource) {
+        self.user_providers
+            .lock()
+            .unwrap()
+            .retain(|(u, _, s), _| *u != user_id || *s != source);
+    }
+
+    /// Build a client with the credentials of a user, for the sources that use
+    /// credentials.
+    async fn user_provider_client(
+        &self,
+        lot: MetadataLot,
+        source: MetadataSource,
+        credentials: UserProviderCredentials,
+    ) -> Option<UserProviderClient> {
+        let client = match source {
+            MetadataSource::Audible => {
+                let mut config = self.config.audio_books.audible.clone();
+                let service = match credentials.region {
+                    Some(region) => {
+                        config.locale = region;
+                        AudibleService::in_region(&config).await
+                    }
+                    None => AudibleService::new(&config).await,
+                };
+                UserProviderClient::Audible(service)
+            }
+            MetadataSource::Listennotes => {
+                let mut config = self.config.podcasts.clone();
+                if let Some(token) = credentials.api_token {
+                    config.listennotes.api_token = token;
+                }
+                UserProviderClient::Listennotes(ListennotesService::new(&config).await)
+            }
+            MetadataSource::Igdb => {
+                let mut config = self.config.video_games.clone();
+                if let (Some(id), Some(secret)) = (credentials.client_id, credentials.client_secret)
+                {
+                    config.twitch.client_id = id;
+                    config.twitch.client_secret = secret;
+                }
+                UserProviderClient::Igdb(IgdbService::new(&config).await)
+            }
+            MetadataSource::Tmdb => match lot {
+                MetadataLot::Show => {
+                    let mut config = self.config.shows.tmdb.clone();
+                    if let Some(token) = credentials.api_token {
+                        config.access_token = token;
+                    }
+                    if let Some(region) = credentials.region {
+                        config.locale = region;
+                    }
+                    UserProviderClient::TmdbShows(TmdbShowService::new(&config).await)
+                }
+                MetadataLot::Movie => {
+                    let mut config = self.config.movies.tmdb.clone();
+                    if let Some(token) = credentials.api_token {
+                        config.access_token = token;
+                    }
+                    if let Some(region) = credentials.region {
+                        config.locale = region;
+                    }
+                    UserProviderClient::TmdbMovies(TmdbMovieService::new(&config).await)
+                }
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some(client)
+    }
+
+    /// The user whose credentials are used to get the details of media that is
+    /// shared by all users. It is one of the users that have it in their library.
+    async fn credentials_user_for_metadata(
+        &self,
+        metadata_id: i32,
+        source: MetadataSource,
+    ) -> Result<Option<i32>> {
+        let user_id = UserProviderCredential::find()
+            .select_only()
+            .column(user_provider_credential::Column::UserId)
+            .filter(user_provider_credential::Column::Source.eq(source))
+            .filter(
+                user_provider_credential::Column::UserId.in_subquery(
+                    Query::select()
+                        .column(user_to_metadata::Column::UserId)
+                        .from(UserToMetadata)
+                        .and_where(user_to_metadata::Column::MetadataId.eq(metadata_id))
+                        .to_owned(),
+                ),
+            )
+            .order_by_asc(user_provider_credential::Column::UserId)
+            .into_tuple()
+            .one(&self.db)
+            .await?;
+        Ok(user_id)
+    }
+
+    /// Get the details from the provider, with the credentials of the user if
+    /// there is one.
     async fn details_from_provider(
         &self,
+        user_id: Option<i32>,
         lot: MetadataLot,
         source: MetadataSource,
         identifier: &str,
     ) -> Result<MediaDetails> {
-        let provider = self.get_provider(lot, source)?;
+        let provider = match user_id {
+            Some(user_id) => self.get_user_provider(user_id, lot, source).await?,
+            None => self.get_provider(lot, source)?,
+        };
         let results = provider.details(identifier).await?;
         Ok(results)
     }
 
+    /// Create the media if it is not in the database yet, getting its details
+    /// with the credentials of the user if there is one.
     pub async fn commit_media(
+        &self,
+        user_id: Option<i32>,
+        lot: MetadataLot,
+        source: MetadataSource,
+        identifier: &str,
+    ) -> Result<IdObject> {
+        self.commit_media_with_timeout(user_id, lot, source, identifier, None)
+            .await
+    }
+
+    /// Same as `commit_media`, but getting the details fails if it takes longer
+    /// than `fetch_timeout`.
+    pub async fn commit_media_with_timeout(
+        &self,
+        user_id: Option<i32>,
+        lot: MetadataLot,
+        source: MetadataSource,
+        identifier: &str,
+        fetch_timeout: Option<std::time::Duration>,
+    ) -> Result<IdObject> {
+        if let Some(m) = self
+            .media_exists_in_database(lot, source, identifier)
+            .await?
+        {
+            return Ok(m);
+        }
+        let details = self.cached_details_from_provider(user_id, lot, source, identifier);
+        // DEV: only the provider is timed, so that the media is never left half
+        // written
+        let details = match fetch_timeout {
+            Some(t) => tokio::time::timeout(t, details).await.unwrap_or_else(|_| {
+                record_provider_failure();
+                Err(Error::new(format!(
+                    "Timed out after {} seconds",
+                    t.as_secs()
+                )))
+            })?,
+            None => details.await?,
+        };
+        self.commit_media_internal(details).await
+    }
+
+    /// Commit the best of the candidates found by searching a provider for a
+    /// title, as ordered by `sort_media_candidates`. Returns the candidate that
+    /// was committed, if there was any.
+    pub async fn commit_media_candidate(
+        &self,
+        user_id: Option<i32>,
+        lot: MetadataLot,
+        source: MetadataSource,
+        title: &str,
+        candidates: Vec<MediaSearchItem>,
+    ) -> Result<Option<(MediaSearchItem, IdObject)>> {
+        let Some(chosen) = sort_media_candidates(candidates, lot, title)
+            .into_iter()
+            .next()
+        else {
+            return Ok(None);
+        };
+        let media = self
+            .commit_media(user_id, chosen.lot, source, &chosen.identifier)
+            .await?;
+        Ok(Some((chosen, media)))
+    }
+
+    /// Get the details from the provider, reusing the ones that were fetched
+    /// recently for the same media, possibly by another user.
+    pub async fn cached_details_from_provider(
+        &self,
+        user_id: Option<i32>,
+        lot: MetadataLot,
+        source: MetadataSource,
+        identifier: &str,
+    ) -> Result<MediaDetails> {
+        let ttl = self.config.media.provider_cache_ttl_hours;
+        if ttl <= 0 {
+            return self
+                .details_from_provider(user_id, lot, source, identifier)
+                .await;
+        }
+        let cached = ProviderLookupCache::find()
+            .filter(provider_lookup_cache::Column::Lot.eq(lot))
+            .filter(provider_lookup_cache::Column::Source.eq(source))
+            .filter(provider_lookup_cache::Column::Identifier.eq(identifier))
+            .one(&self.db)
+            .await?;
+        if let Some(c) = cached.as_ref() {
+            if c.cached_on + ChronoDuration::hours(ttl) > Utc::now() {
+                if let Ok(details) = serde_json::from_value::<MediaDetails>(c.details.clone()) {
+                    tracing::trace!("Using cached details for {identifier:?}");
+                    return Ok(details);
+                }
+            }
+        }
+        let details = self
+            .details_from_provider(user_id, lot, source, identifier)
+            .await?;
+        let value = serde_json::to_value(&details)?;
+        match cached {
+            Some(c) => {
+                let mut c: provider_lookup_cache::ActiveModel = c.into();
+                c.details = ActiveValue::Set(value);
+                c.cached_on = ActiveValue::Set(Utc::now());
+                c.update(&self.db).await.ok();
+            }
+            None => {
+                let c = provider_lookup_cache::ActiveModel {
+                    lot: ActiveValue::Set(lot),
+                    source: ActiveValue::Set(source),
+                    identifier: ActiveValue::Set(identifier.to_owned()),
+                    details: ActiveValue::Set(value),
+                    cached_on: ActiveValue::Set(Utc::now()),
+                    ..Default::default()
+                };
+                // DEV: another job might have inserted the same item in the meantime
+                c.insert(&self.db).await.ok();
+            }
+        }
+        Ok(details)
+    }
+
+    /// Remove the cached provider details that are older than the configured TTL.
+    pub async fn cleanup_provider_lookup_cache(&self) -> Result<()> {
+        let ttl = self.config.media.provider_cache_ttl_hours.max(0);
+        ProviderLookupCache::delete_many()
+            .filter(
+                provider_lookup_cache::Column::CachedOn.lt(Utc::now() - ChronoDuration::hours(ttl)),
+            )
+            .exec(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    /// Commit media with only the details that are already known. The rest of them
+    /// are expected to be filled in later by an `UpdateMetadataJob`.
+    pub async fn commit_media_placeholder(
         &self,
         lot: MetadataLot,
         source: MetadataSource,
         identifier: &str,
+        title: &str,
     ) -> Result<IdObject> {
         if let Some(m) = self
             .media_exists_in_database(lot, source, identifier)
@@ -2168,14 +4195,30 @@ impl MiscellaneousService {
         {
             Ok(m)
         } else {
-            let details = self.details_from_provider(lot, source, identifier).await?;
-            let media_id = self.commit_media_internal(details).await?;
-            Ok(media_id)
+            let details = MediaDetails {
+                identifier: identifier.to_owned(),
+                title: title.to_owned(),
+                source,
+                lot,
+                description: None,
+                creators: vec![],
+                genres: vec![],
+                images: vec![],
+                publish_year: None,
+                publish_date: None,
+                specifics: MediaSpecifics::Unknown,
+                groups: vec![],
+                alternative_titles: vec![],
+            };
+            self.commit_media_internal(details).await
         }
     }
 
     async fn review_by_id(&self, review_id: i32) -> Result<ReviewItem> {
-        let review = Review::find_by_id(review_id).one(&self.db).await?;
+        let review = Review::find_by_id(review_id)
+            .filter(review::Column::DeletedAt.is_null())
+            .one(&self.db)
+            .await?;
         match review {
             Some(r) => {
                 let user = r.find_related(User).one(&self.db).await.unwrap().unwrap();
@@ -2198,6 +4241,9 @@ impl MiscellaneousService {
                     show_season: show_se,
                     show_episode: show_ep,
                     podcast_episode: podcast_ep,
+                    provenance: r.provenance,
+                    import_source: r.import_source,
+                    import_report_id: r.import_report_id,
                     posted_by: ReviewPostedBy {
                         id: user.id,
                         name: user.name,
@@ -2214,6 +4260,7 @@ impl MiscellaneousService {
         metadata_id: &i32,
     ) -> Result<Vec<ReviewItem>> {
         let all_reviews = Review::find()
+            .filter(review::Column::DeletedAt.is_null())
             .order_by_desc(review::Column::PostedOn)
             .filter(review::Column::MetadataId.eq(metadata_id.to_owned()))
             .all(&self.db)
@@ -2254,12 +4301,21 @@ impl MiscellaneousService {
         let mut data = vec![];
         for collection in collections.into_iter() {
             let num_items = collection.find_related(Metadata).count(&self.db).await?;
+            let oldest_media_update = collection
+                .find_related(Metadata)
+                .select_only()
+                .column_as(Expr::col(metadata::Column::LastUpdatedOn).min(), "oldest")
+                .into_tuple::<Option<DateTimeUtc>>()
+                .one(&self.db)
+                .await?
+                .flatten();
             data.push(CollectionItem {
                 id: collection.id,
                 name: collection.name,
                 description: collection.description,
                 visibility: collection.visibility,
                 num_items,
+                oldest_media_update,
             });
         }
         Ok(data)
@@ -2338,6 +4394,7 @@ impl MiscellaneousService {
                     title: m.model.title,
                     image: m.poster_images.get(0).cloned(),
                     publish_year: m.model.publish_year,
+                    popularity: None,
                 },
                 u_t_m.map(|d| d.last_updated_on).unwrap_or_default(),
             ));
@@ -2353,7 +4410,14 @@ impl MiscellaneousService {
     }
 
     pub async fn post_review(&self, user_id: &i32, input: PostReviewInput) -> Result<IdObject> {
-        let review_id = match input.review_id {
+        if let Some(rating) = input.rating {
+            if rating < Decimal::ZERO || rating > MAX_RATING {
+                return Err(Error::new(format!(
+                    "The rating must be between 0 and {MAX_RATING}"
+                )));
+            }
+        }
+        let review_id = match input.review_id {
             Some(i) => ActiveValue::Set(i),
             None => ActiveValue::NotSet,
         };
@@ -2378,6 +4442,16 @@ impl MiscellaneousService {
             extra_information: ActiveValue::Set(extra_infomation),
             ..Default::default()
         };
+        // DEV: an update keeps the provenance of the review it changes
+        if input.review_id.is_none() {
+            review_obj.provenance = ActiveValue::Set(
+                input
+                    .import
+                    .map_or(Provenance::Manual, |_| Provenance::Import),
+            );
+            review_obj.import_source = ActiveValue::Set(input.import.map(|i| i.source));
+            review_obj.import_report_id = ActiveValue::Set(input.import.map(|i| i.report_id));
+        }
         if let Some(s) = input.spoiler {
             review_obj.spoiler = ActiveValue::Set(s);
         }
@@ -2395,6 +4469,7 @@ impl MiscellaneousService {
 
     pub async fn delete_review(&self, user_id: &i32, review_id: i32) -> Result<bool> {
         let review = Review::find()
+            .filter(review::Column::DeletedAt.is_null())
             .filter(review::Column::Id.eq(review_id))
             .one(&self.db)
             .await
@@ -2402,7 +4477,9 @@ impl MiscellaneousService {
         match review {
             Some(r) => {
                 if r.user_id == *user_id {
-                    r.delete(&self.db).await?;
+                    let mut r: review::ActiveModel = r.into();
+                    r.deleted_at = ActiveValue::Set(Some(Utc::now()));
+                    r.update(&self.db).await?;
                     Ok(true)
                 } else {
                     Err(Error::new("This review does not belong to you".to_owned()))
@@ -2412,6 +4489,57 @@ impl MiscellaneousService {
         }
     }
 
+    pub async fn restore_review(&self, user_id: &i32, review_id: i32) -> Result<bool> {
+        let review = Review::find_by_id(review_id)
+            .filter(review::Column::UserId.eq(*user_id))
+            .filter(review::Column::DeletedAt.is_not_null())
+            .one(&self.db)
+            .await?
+            .ok_or_else(|| Error::new("This review is not in the trash".to_owned()))?;
+        let metadata_id = review.metadata_id;
+        let mut review: review::ActiveModel = review.into();
+        review.deleted_at = ActiveValue::Set(None);
+        review.update(&self.db).await?;
+        // DEV: the association might have been cleaned up while it was deleted
+        associate_user_with_metadata(user_id, &metadata_id, &self.db).await?;
+        Ok(true)
+    }
+
+    pub async fn user_trash(&self, user_id: i32) -> Result<UserTrash> {
+        let seen = Seen::find()
+            .filter(seen::Column::UserId.eq(user_id))
+            .filter(seen::Column::DeletedAt.is_not_null())
+            .order_by_desc(seen::Column::DeletedAt)
+            .all(&self.db)
+            .await?;
+        let reviews = Review::find()
+            .filter(review::Column::UserId.eq(user_id))
+            .filter(review::Column::DeletedAt.is_not_null())
+            .order_by_desc(review::Column::DeletedAt)
+            .all(&self.db)
+            .await?
+            .into_iter()
+            .map(|r| TrashedReview {
+                id: r.id,
+                metadata_id: r.metadata_id,
+                posted_on: r.posted_on,
+                rating: r.rating,
+                text: r.text,
+                deleted_at: r.deleted_at.unwrap(),
+            })
+            .collect();
+        Ok(UserTrash { seen, reviews })
+    }
+
+    /// Permanently remove the seen items and reviews that have been in the trash
+    /// for longer than `TRASH_RETENTION_DAYS`.
+    pub async fn purge_trash(&self) -> Result<()> {
+        let before = Utc::now() - ChronoDuration::days(TRASH_RETENTION_DAYS);
+        let (seen, reviews) = purge_trash_before(&self.db, before).await?;
+        tracing::debug!("Purged {seen} seen items and {reviews} reviews from the trash");
+        Ok(())
+    }
+
     pub async fn create_or_update_collection(
         &self,
         user_id: &i32,
@@ -2536,6 +4664,62 @@ impl MiscellaneousService {
         Ok(model)
     }
 
+    pub async fn cancel_import_job(
+        &self,
+        job: media_import_report::Model,
+        details: ImportResultResponse,
+    ) -> Result<media_import_report::Model> {
+        let mut model: media_import_report::ActiveModel = job.into();
+        model.finished_on = ActiveValue::Set(Some(Utc::now()));
+        model.details = ActiveValue::Set(Some(details));
+        model.success = ActiveValue::Set(Some(false));
+        let model = model.update(&self.db).await.unwrap();
+        Ok(model)
+    }
+
+    /// Append the results of an import to the last successful report of the
+    /// same source and remove the report of the import itself.
+    pub async fn merge_import_job(
+        &self,
+        job: media_import_report::Model,
+        details: ImportResultResponse,
+    ) -> Result<media_import_report::Model> {
+        let previous = match MediaImportReport::find()
+            .filter(media_import_report::Column::UserId.eq(job.user_id))
+            .filter(media_import_report::Column::Source.eq(job.source))
+            .filter(media_import_report::Column::Id.ne(job.id))
+            .filter(media_import_report::Column::Success.eq(true))
+            .order_by_desc(media_import_report::Column::FinishedOn)
+            .one(&self.db)
+            .await?
+        {
+            Some(p) => p,
+            None => return self.finish_import_job(job, details).await,
+        };
+        let merged = match previous.details.clone() {
+            Some(mut d) => {
+                d.merge(details);
+                d
+            }
+            None => details,
+        };
+        job.delete(&self.db).await?;
+        let model = self.finish_import_job(previous, merged).await?;
+        tracing::trace!("Merged import job into job with id = {id}", id = model.id);
+        Ok(model)
+    }
+
+    pub async fn save_import_job_progress(
+        &self,
+        job: media_import_report::Model,
+        details: ImportResultResponse,
+    ) -> Result<media_import_report::Model> {
+        let mut model: media_import_report::ActiveModel = job.into();
+        model.details = ActiveValue::Set(Some(details));
+        let model = model.update(&self.db).await.unwrap();
+        Ok(model)
+    }
+
     pub async fn media_import_reports(
         &self,
         user_id: i32,
@@ -2548,8 +4732,289 @@ impl MiscellaneousService {
         Ok(reports)
     }
 
+    async fn media_reminders(&self, user_id: i32) -> Result<Vec<media_reminder::Model>> {
+        let reminders = MediaReminder::find()
+            .filter(media_reminder::Column::UserId.eq(user_id))
+            .order_by_asc(media_reminder::Column::RemindOn)
+            .all(&self.db)
+            .await?;
+        Ok(reminders)
+    }
+
+    async fn create_media_reminder(
+        &self,
+        user_id: i32,
+        input: CreateMediaReminderInput,
+    ) -> Result<IdObject> {
+        let in_library = UserToMetadata::find()
+            .filter(user_to_metadata::Column::UserId.eq(user_id))
+            .filter(user_to_metadata::Column::MetadataId.eq(input.metadata_id))
+            .count(&self.db)
+            .await?;
+        if in_library == 0 {
+            return Err(Error::new("This media is not in your library".to_owned()));
+        }
+        let reminder = media_reminder::ActiveModel {
+            user_id: ActiveValue::Set(user_id),
+            metadata_id: ActiveValue::Set(input.metadata_id),
+            remind_on: ActiveValue::Set(input.remind_on),
+            note: ActiveValue::Set(input.note),
+            created_on: ActiveValue::Set(Utc::now()),
+            ..Default::default()
+        }
+        .insert(&self.db)
+        .await?;
+        Ok(IdObject { id: reminder.id })
+    }
+
+    async fn delete_media_reminder(&self, user_id: i32, reminder_id: i32) -> Result<bool> {
+        let result = MediaReminder::delete_many()
+            .filter(media_reminder::Column::Id.eq(reminder_id))
+            .filter(media_reminder::Column::UserId.eq(user_id))
+            .exec(&self.db)
+            .await?;
+        Ok(result.rows_affected > 0)
+    }
+
+    /// Make sure that the secrets stored in the database and in the inputs of
+    /// queued imports can be decrypted, so that a missing `server.secrets_key`
+    /// is noticed when the server starts instead of when the secrets are used.
+    pub async fn check_secrets_key(&self, scheduler_pool: &SqlitePool) -> anyhow::Result<()> {
+        if self.secrets.is_enabled() {
+            return Ok(());
+        }
+        let credentials = UserProviderCredential::find().count(&self.db).await?;
+        let users = User::find()
+            .all(&self.db)
+            .await?
+            .iter_mut()
+            .map(|u| {
+                user_secrets_mut(u)
+                    .iter()
+                    .any(|s| SecretsCipher::is_encrypted(s))
+            })
+            .filter(|encrypted| *encrypted)
+            .count();
+        let imports = queued_imports_with_secrets(scheduler_pool).await?;
+        if credentials > 0 || users > 0 || imports > 0 {
+            return Err(anyhow!(
+                "The database has encrypted secrets ({credentials} provider credentials, \
+                the integrations or notifications of {users} users and {imports} queued \
+                imports), but `server.secrets_key` is not set"
+            ));
+        }
+        Ok(())
+    }
+
+    /// Encrypt the secrets of the users that were stored without a key. This
+    /// runs whenever the server starts, since the key can be set at any time.
+    pub async fn encrypt_stored_secrets(&self) -> anyhow::Result<()> {
+        if !self.secrets.is_enabled() {
+            return Ok(());
+        }
+        let mut encrypted = 0;
+        for mut user in User::find().all(&self.db).await? {
+            let mut changed = false;
+            for secret in user_secrets_mut(&mut user) {
+                if !SecretsCipher::is_encrypted(secret) {
+                    *secret = self.secrets.seal(secret).map_err(|e| anyhow!(e.message))?;
+                    changed = true;
+                }
+            }
+            if !changed {
+                continue;
+            }
+            let mut model: user::ActiveModel = user.clone().into();
+            model.yank_integrations = ActiveValue::Set(user.yank_integrations);
+            model.sink_integrations = ActiveValue::Set(user.sink_integrations);
+            model.preferences = ActiveValue::Set(user.preferences);
+            model.update(&self.db).await?;
+            encrypted += 1;
+        }
+        if encrypted > 0 {
+            tracing::info!("Encrypted the stored secrets of {encrypted} users");
+        }
+        Ok(())
+    }
+
+    async fn provider_credentials(
+        &self,
+        user_id: i32,
+        source: MetadataSource,
+    ) -> Result<Option<UserProviderCredentials>> {
+        let Some(stored) = UserProviderCredential::find_by_id((user_id, source))
+            .one(&self.db)
+            .await?
+        else {
+            return Ok(None);
+        };
+        let credentials = self.secrets.decrypt(&stored.credentials)?;
+        Ok(Some(serde_json::from_str(&credentials)?))
+    }
+
+    async fn user_provider_credentials_summary(
+        &self,
+        user_id: i32,
+    ) -> Result<Vec<UserProviderCredentialsSummary>> {
+        let stored = UserProviderCredential::find()
+            .filter(user_provider_credential::Column::UserId.eq(user_id))
+            .order_by_asc(user_provider_credential::Column::Source)
+            .all(&self.db)
+            .await?;
+        Ok(stored
+            .into_iter()
+            .map(|c| UserProviderCredentialsSummary {
+                source: c.source,
+                updated_on: c.updated_on,
+            })
+            .collect())
+    }
+
+    async fn set_provider_credentials(
+        &self,
+        user_id: i32,
+        input: SetProviderCredentialsInput,
+    ) -> Result<bool> {
+        input
+            .credentials
+            .validate(input.source)
+            .map_err(|e| Error::new(e.to_string()))?;
+        let encrypted = self
+            .secrets
+            .encrypt(&serde_json::to_string(&input.credentials)?)?;
+        let credentials = user_provider_credential::ActiveModel {
+            user_id: ActiveValue::Set(user_id),
+            source: ActiveValue::Set(input.source),
+            credentials: ActiveValue::Set(encrypted),
+            updated_on: ActiveValue::Set(Utc::now()),
+        };
+        if UserProviderCredential::find_by_id((user_id, input.source))
+            .one(&self.db)
+            .await?
+            .is_some()
+        {
+            credentials.update(&self.db).await?;
+        } else {
+            credentials.insert(&self.db).await?;
+        }
+        self.forget_user_providers(user_id, input.source);
+        Ok(true)
+    }
+
+    async fn clear_provider_credentials(
+        &self,
+        user_id: i32,
+        source: MetadataSource,
+    ) -> Result<bool> {
+        let result = UserProviderCredential::delete_by_id((user_id, source))
+            .exec(&self.db)
+            .await?;
+        self.forget_user_providers(user_id, source);
+        Ok(result.rows_affected > 0)
+    }
+
+    async fn test_provider_credentials(
+        &self,
+        user_id: i32,
+        source: MetadataSource,
+    ) -> Result<bool> {
+        let credentials = self
+            .provider_credentials(user_id, source)
+            .await?
+            .ok_or_else(|| Error::new(format!("No credentials are set for {source:?}")))?;
+        let lot = match source {
+            MetadataSource::Audible => MetadataLot::AudioBook,
+            MetadataSource::Igdb => MetadataLot::VideoGame,
+            MetadataSource::Listennotes => MetadataLot::Podcast,
+            _ => MetadataLot::Movie,
+        };
+        let provider = match self.user_provider_client(lot, source, credentials).await {
+            Some(client) => client.provider(),
+            None => self.get_provider(lot, source)?,
+        };
+        provider
+            .search("the", None)
+            .await
+            .map_err(|e| Error::new(format!("{source:?} did not accept the credentials: {e}")))?;
+        Ok(true)
+    }
+
+    /// Notify users about the reminders that are due and remove them. A
+    /// reminder that could not be sent is tried again on the next run.
+    pub async fn send_due_media_reminders(&self) -> Result<()> {
+        // DEV: the day of a reminder can have started for users that are ahead
+        // of UTC, so the ones of the next day are looked at too
+        let due = MediaReminder::find()
+            .filter(
+                media_reminder::Column::RemindOn
+                    .lte(Utc::now().date_naive() + ChronoDuration::days(1)),
+            )
+            .find_also_related(Metadata)
+            .all(&self.db)
+            .await?;
+        for (reminder, metadata) in due {
+            let id = reminder.id;
+            if let Err(e) = self.send_media_reminder(reminder, metadata).await {
+                tracing::error!("Could not send reminder {id}: {e:?}");
+            }
+        }
+        Ok(())
+    }
+
+    /// Send a reminder if its day has started in the timezone of the user. It
+    /// is removed once it has been sent, or if it can not be sent at all.
+    async fn send_media_reminder(
+        &self,
+        reminder: media_reminder::Model,
+        metadata: Option<metadata::Model>,
+    ) -> Result<()> {
+        let preferences = self.user_preferences(reminder.user_id).await?;
+        if reminder.remind_on > date_in_timezone(Utc::now(), &preferences.general.timezone) {
+            return Ok(());
+        }
+        let notifications = preferences.notifications;
+        match (notifications.webhook_url, metadata) {
+            (Some(url), Some(metadata)) if notifications.enabled => {
+                let notification = ReminderNotification {
+                    metadata_id: reminder.metadata_id,
+                    media_title: metadata.title,
+                    remind_on: reminder.remind_on,
+                    note: reminder.note.clone(),
+                };
+                let payload = notification.payload(notifications.webhook_platform);
+                send_webhook(&url, &payload)
+                    .await
+                    .map_err(|e| Error::new(e.to_string()))?;
+            }
+            _ => tracing::debug!("Not sending reminder {id}", id = reminder.id),
+        }
+        reminder.delete(&self.db).await?;
+        Ok(())
+    }
+
+    /// Remove the reminders on media that is no longer in the user's library.
+    async fn cleanup_media_reminders(&self) -> Result<()> {
+        let reminders = MediaReminder::find().all(&self.db).await?;
+        for reminder in reminders {
+            let in_library = UserToMetadata::find()
+                .filter(user_to_metadata::Column::UserId.eq(reminder.user_id))
+                .filter(user_to_metadata::Column::MetadataId.eq(reminder.metadata_id))
+                .count(&self.db)
+                .await?;
+            if in_library == 0 {
+                tracing::debug!("Removing media_reminder = {id}", id = reminder.id);
+                reminder.delete(&self.db).await?;
+            }
+        }
+        Ok(())
+    }
+
     pub async fn delete_seen_item(&self, seen_id: i32, user_id: i32) -> Result<IdObject> {
-        let seen_item = Seen::find_by_id(seen_id).one(&self.db).await.unwrap();
+        let seen_item = Seen::find_by_id(seen_id)
+            .filter(seen::Column::DeletedAt.is_null())
+            .one(&self.db)
+            .await
+            .unwrap();
         if let Some(si) = seen_item {
             let seen_id = si.id;
             let progress = si.progress;
@@ -2559,7 +5024,12 @@ impl MiscellaneousService {
                     "This seen item does not belong to this user".to_owned(),
                 ));
             }
-            si.delete(&self.db).await.ok();
+            let mut si: seen::ActiveModel = si.into();
+            si.deleted_at = ActiveValue::Set(Some(Utc::now()));
+            si.update(&self.db).await?;
+            self.invalidate_show_progress(metadata_id, Some(user_id))
+                .await
+                .ok();
             if progress < 100 {
                 self.remove_media_item_from_collection(
                     &user_id,
@@ -2575,6 +5045,80 @@ impl MiscellaneousService {
         }
     }
 
+    pub async fn restore_seen_item(&self, seen_id: i32, user_id: i32) -> Result<IdObject> {
+        let seen_item = Seen::find_by_id(seen_id)
+            .filter(seen::Column::UserId.eq(user_id))
+            .filter(seen::Column::DeletedAt.is_not_null())
+            .one(&self.db)
+            .await?
+            .ok_or_else(|| Error::new("This seen item is not in the trash".to_owned()))?;
+        let mut seen_item: seen::ActiveModel = seen_item.into();
+        seen_item.deleted_at = ActiveValue::Set(None);
+        let seen_item = seen_item.update(&self.db).await?;
+        let id = seen_item.id;
+        associate_user_with_metadata(&user_id, &seen_item.metadata_id, &self.db).await?;
+        self.invalidate_show_progress(seen_item.metadata_id, Some(user_id))
+            .await
+            .ok();
+        self.after_media_seen_tasks(seen_item).await?;
+        Ok(IdObject { id })
+    }
+
+    pub async fn unstale_seen_item(&self, seen_id: i32, user_id: i32) -> Result<IdObject> {
+        let seen_item = match Seen::find_by_id(seen_id)
+            .filter(seen::Column::DeletedAt.is_null())
+            .one(&self.db)
+            .await?
+        {
+            Some(si) if si.user_id == user_id => si,
+            _ => return Err(Error::new("This seen item does not exist".to_owned())),
+        };
+        if seen_item.state != SeenState::Stale {
+            return Err(Error::new("This seen item is not stale".to_owned()));
+        }
+        let mut seen_item: seen::ActiveModel = seen_item.into();
+        seen_item.state = ActiveValue::Set(SeenState::InProgress);
+        seen_item.last_updated_on = ActiveValue::Set(Utc::now());
+        let seen_item = seen_item.update(&self.db).await?;
+        let id = seen_item.id;
+        self.after_media_seen_tasks(seen_item).await?;
+        Ok(IdObject { id })
+    }
+
+    /// Mark the in progress seen items that have not been updated in the number
+    /// of days set in each user's preferences as stale.
+    pub async fn mark_stale_seen_items(&self) -> Result<()> {
+        let all_users = User::find().all(&self.db).await?;
+        for user in all_users {
+            let days = match user.preferences.general.stale_in_progress_after_days {
+                Some(d) if d > 0 => d,
+                _ => continue,
+            };
+            let stale_seen_items = Seen::find()
+                .filter(seen::Column::DeletedAt.is_null())
+                .filter(seen::Column::UserId.eq(user.id))
+                .filter(seen::Column::State.eq(SeenState::InProgress))
+                .filter(seen::Column::Progress.lt(100))
+                .filter(
+                    seen::Column::LastUpdatedOn.lt(Utc::now() - ChronoDuration::days(days.into())),
+                )
+                .all(&self.db)
+                .await?;
+            tracing::trace!(
+                "Marking {} seen items as stale for user {}",
+                stale_seen_items.len(),
+                user.id
+            );
+            for seen_item in stale_seen_items {
+                let mut seen_item: seen::ActiveModel = seen_item.into();
+                seen_item.state = ActiveValue::Set(SeenState::Stale);
+                let seen_item = seen_item.update(&self.db).await?;
+                self.after_media_seen_tasks(seen_item).await.ok();
+            }
+        }
+        Ok(())
+    }
+
     pub async fn cleanup_summaries_for_user(&self, user_id: &i32) -> Result<()> {
         let summaries = Summary::delete_many()
             .filter(summary::Column::UserId.eq(user_id.to_owned()))
@@ -2589,60 +5133,661 @@ impl MiscellaneousService {
         Ok(())
     }
 
+    /// Refresh the media one after the other. A media that can not be refreshed
+    /// is logged and skipped, so that it does not hold back the others.
+    pub async fn update_metadata_batch(&self, metadata_ids: Vec<i32>) -> Result<()> {
+        let metas = Metadata::find()
+            .filter(metadata::Column::Id.is_in(metadata_ids))
+            .all(&self.db)
+            .await?;
+        for metadata in metas {
+            let metadata_id = metadata.id;
+            if let Err(e) = self.update_metadata(metadata).await {
+                tracing::warn!("Could not update metadata {metadata_id}: {e:?}");
+            }
+        }
+        Ok(())
+    }
+
     pub async fn update_metadata(&self, metadata: metadata::Model) -> Result<()> {
         let metadata_id = metadata.id;
         tracing::trace!("Updating metadata for {:?}", metadata_id);
+        // DEV: the job might have been queued a while ago, so compare against the
+        // latest version of the metadata
+        let current = Metadata::find_by_id(metadata_id)
+            .one(&self.db)
+            .await?
+            .unwrap_or(metadata);
         let maybe_details = self
             .details_from_provider_for_existing_media(metadata_id)
             .await;
         match maybe_details {
             Ok(details) => {
+                let changes = metadata_changes(&current, &details, Utc::now().date_naive());
                 self.update_media(
                     metadata_id,
-                    details.title,
+                    details.title.clone(),
                     details.description,
                     details.images,
                     details.creators,
                     details.specifics,
                     details.genres,
+                    details.publish_year,
+                    details.publish_date,
+                    details.alternative_titles,
                 )
                 .await
                 .ok();
+                self.associate_groups_with_metadata(&current, details.groups)
+                    .await
+                    .ok();
+                // DEV: episodes might have aired or been renumbered
+                self.invalidate_show_progress(metadata_id, None).await.ok();
+                self.set_refresh_failures(metadata_id, Expr::value(0))
+                    .await?;
+                if !changes.is_empty() {
+                    self.notify_monitoring_users(metadata_id, details.title, changes)
+                        .await?;
+                }
             }
             Err(e) => {
                 tracing::error!("Error while updating: {:?}", e);
+                self.set_refresh_failures(
+                    metadata_id,
+                    Expr::col(metadata::Column::RefreshFailures).add(1),
+                )
+                .await?;
             }
         }
         tracing::trace!("Updated metadata for {:?}", metadata_id);
         Ok(())
     }
 
+    async fn set_refresh_failures(&self, metadata_id: i32, failures: SimpleExpr) -> Result<()> {
+        Metadata::update_many()
+            .filter(metadata::Column::Id.eq(metadata_id))
+            .col_expr(metadata::Column::RefreshFailures, failures)
+            .exec(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    /// Queue a refresh of every podcast that is in at least one library. Podcasts
+    /// whose feed keeps failing are skipped until their backoff runs out.
+    pub async fn refresh_library_podcasts(&self) -> Result<()> {
+        let every = ChronoDuration::hours(self.config.podcasts.refresh_every.into());
+        let now = Utc::now();
+        let podcasts = Metadata::find()
+            .filter(metadata::Column::Lot.eq(MetadataLot::Podcast))
+            .filter(
+                metadata::Column::Id.in_subquery(
+                    Query::select()
+                        .column(user_to_metadata::Column::MetadataId)
+                        .from(UserToMetadata)
+                        .to_owned(),
+                ),
+            )
+            .all(&self.db)
+            .await?;
+        for podcast in podcasts {
+            if is_refresh_due(
+                podcast.last_updated_on,
+                podcast.refresh_failures,
+                every,
+                now,
+            ) {
+                self.deploy_update_metadata_job(podcast.id).await?;
+            } else {
+                tracing::debug!(
+                    "Skipping refresh of podcast {} after {} failures",
+                    podcast.id,
+                    podcast.refresh_failures
+                );
+            }
+        }
+        Ok(())
+    }
+
     pub async fn update_all_metadata(&self) -> Result<bool> {
+        let monitored: Vec<i32> = UserToMetadata::find()
+            .select_only()
+            .column(user_to_metadata::Column::MetadataId)
+            .filter(user_to_metadata::Column::Monitored.eq(true))
+            .distinct()
+            .into_tuple()
+            .all(&self.db)
+            .await?;
         let metadatas = Metadata::find()
             .order_by_asc(metadata::Column::Id)
             .all(&self.db)
             .await
             .unwrap();
-        for metadata in metadatas {
+        // DEV: monitored media is refreshed first so that changes are noticed
+        // as soon as possible
+        let (first, rest): (Vec<_>, Vec<_>) = metadatas
+            .into_iter()
+            .partition(|m| monitored.contains(&m.id));
+        for metadata in first.into_iter().chain(rest) {
             self.deploy_update_metadata_job(metadata.id).await?;
         }
         Ok(true)
     }
 
-    async fn user_details(&self, token: &str) -> Result<UserDetailsResult> {
-        let found_token = user_id_from_token(token.to_owned(), &self.auth_db).await;
-        if let Ok(user_id) = found_token {
-            let user = self.user_by_id(user_id).await?;
-            Ok(UserDetailsResult::Ok(user))
-        } else {
-            Ok(UserDetailsResult::Error(UserDetailsError {
-                error: UserDetailsErrorVariant::AuthTokenInvalid,
-            }))
-        }
-    }
-
-    async fn user_by_id(&self, user_id: i32) -> Result<user::Model> {
-        User::find_by_id(user_id)
+    /// Send the changes of a media item to every user monitoring it.
+    async fn notify_monitoring_users(
+        &self,
+        metadata_id: i32,
+        media_title: String,
+        changes: Vec<String>,
+    ) -> Result<()> {
+        let monitoring = UserToMetadata::find()
+            .filter(user_to_metadata::Column::MetadataId.eq(metadata_id))
+            .filter(user_to_metadata::Column::Monitored.eq(true))
+            .all(&self.db)
+            .await?;
+        let notification = MonitorNotification {
+            metadata_id,
+            media_title,
+            changes,
+        };
+        for association in monitoring {
+            let preferences = self
+                .user_preferences(association.user_id)
+                .await?
+                .notifications;
+            let url = match preferences.webhook_url {
+                Some(url) if preferences.enabled => url,
+                _ => continue,
+            };
+            let payload = notification.payload(preferences.webhook_platform);
+            if let Err(e) = send_webhook(&url, &payload).await {
+                tracing::error!(
+                    "Could not notify user {id} about changes to {metadata_id}: {e:?}",
+                    id = association.user_id
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// The ids of the media that the user has ignored.
+    pub async fn ignored_metadata_ids(&self, user_id: i32) -> Result<Vec<i32>> {
+        let ids = UserToMetadata::find()
+            .select_only()
+            .column(user_to_metadata::Column::MetadataId)
+            .filter(user_to_metadata::Column::UserId.eq(user_id))
+            .filter(user_to_metadata::Column::Ignored.eq(true))
+            .into_tuple()
+            .all(&self.db)
+            .await?;
+        Ok(ids)
+    }
+
+    async fn ignored_media(&self, user_id: i32) -> Result<Vec<MediaSearchItem>> {
+        let ignored = self.ignored_metadata_ids(user_id).await?;
+        let metas = Metadata::find()
+            .filter(metadata::Column::Id.is_in(ignored))
+            .order_by_asc(metadata::Column::Title)
+            .all(&self.db)
+            .await?;
+        let mut items = vec![];
+        for m in metas {
+            let (poster_images, _) = self.metadata_images(&m).await?;
+            items.push(MediaSearchItem {
+                identifier: m.id.to_string(),
+                lot: m.lot,
+                title: m.title,
+                image: poster_images.get(0).cloned(),
+                publish_year: m.publish_year,
+                popularity: None,
+            });
+        }
+        Ok(items)
+    }
+
+    async fn set_media_ignored(
+        &self,
+        user_id: i32,
+        metadata_id: i32,
+        ignored: bool,
+    ) -> Result<bool> {
+        associate_user_with_metadata(&user_id, &metadata_id, &self.db).await?;
+        let association = UserToMetadata::find_by_id((user_id, metadata_id))
+            .one(&self.db)
+            .await?
+            .ok_or_else(|| Error::new("This media does not exist"))?;
+        let mut association: user_to_metadata::ActiveModel = association.into();
+        association.ignored = ActiveValue::Set(ignored);
+        association.update(&self.db).await?;
+        Ok(true)
+    }
+
+    /// All the media of a lot in the user's library, including the ignored ones.
+    async fn library_metadata(
+        &self,
+        user_id: i32,
+        lot: MetadataLot,
+    ) -> Result<Vec<metadata::Model>> {
+        let metas = Metadata::find()
+            .filter(metadata::Column::Lot.eq(lot))
+            .filter(
+                metadata::Column::Id.in_subquery(
+                    Query::select()
+                        .column(user_to_metadata::Column::MetadataId)
+                        .from(UserToMetadata)
+                        .and_where(user_to_metadata::Column::UserId.eq(user_id))
+                        .to_owned(),
+                ),
+            )
+            .all(&self.db)
+            .await?;
+        Ok(metas)
+    }
+
+    async fn media_recommendations(
+        &self,
+        user_id: i32,
+        lot: MetadataLot,
+        count: Option<usize>,
+    ) -> Result<Vec<MediaRecommendation>> {
+        let recommendations = match UserRecommendation::find()
+            .filter(user_recommendation::Column::UserId.eq(user_id))
+            .filter(user_recommendation::Column::Lot.eq(lot))
+            .one(&self.db)
+            .await?
+        {
+            Some(r) => r.items.0,
+            None => return Ok(vec![]),
+        };
+        // DEV: media might have been added to the library since the
+        // recommendations were calculated
+        let library: HashSet<_> = self
+            .library_metadata(user_id, lot)
+            .await?
+            .into_iter()
+            .map(|m| (m.source, m.identifier))
+            .collect();
+        Ok(recommendations
+            .into_iter()
+            .filter(|r| !library.contains(&(r.source, r.item.identifier.clone())))
+            .take(count.unwrap_or(PAGE_LIMIT as usize))
+            .collect())
+    }
+
+    /// Calculate the recommendations of a user for a lot and store them. They
+    /// are seeded by the user's highest rated recent completions, using the
+    /// similar items of the providers and the genres of the seeds.
+    pub async fn refresh_media_recommendations(
+        &self,
+        user_id: i32,
+        lot: MetadataLot,
+    ) -> Result<bool> {
+        let library = self.library_metadata(user_id, lot).await?;
+        let library_ids = library.iter().map(|m| m.id).collect_vec();
+        let since = Utc::now().date_naive() - ChronoDuration::days(RECOMMENDATION_SEED_DAYS);
+        let completed: HashSet<i32> = Seen::find()
+            .filter(seen::Column::DeletedAt.is_null())
+            .select_only()
+            .column(seen::Column::MetadataId)
+            .filter(seen::Column::UserId.eq(user_id))
+            .filter(seen::Column::State.eq(SeenState::Completed))
+            .filter(seen::Column::FinishedOn.gte(since))
+            .filter(seen::Column::MetadataId.is_in(library_ids.clone()))
+            .into_tuple::<i32>()
+            .all(&self.db)
+            .await?
+            .into_iter()
+            .collect();
+        let ratings: HashMap<i32, Decimal> = Review::find()
+            .filter(review::Column::DeletedAt.is_null())
+            .filter(review::Column::UserId.eq(user_id))
+            .filter(review::Column::MetadataId.is_in(completed.iter().copied()))
+            .all(&self.db)
+            .await?
+            .into_iter()
+            .filter_map(|r| r.rating.map(|rating| (r.metadata_id, rating)))
+            .collect();
+        let seeds = library
+            .iter()
+            .filter(|m| completed.contains(&m.id))
+            .sorted_by_key(|m| std::cmp::Reverse(ratings.get(&m.id).copied()))
+            .take(RECOMMENDATION_SEEDS)
+            .collect_vec();
+        let mut candidates = vec![];
+        for seed in seeds.iter() {
+            let provider = match self.get_provider(seed.lot, seed.source) {
+                Ok(p) => p,
+                Err(_) => continue,
+            };
+            match provider.similar(&seed.identifier).await {
+                Ok(items) => candidates.extend(items.into_iter().map(|item| {
+                    let recommendation = MediaRecommendation {
+                        item,
+                        source: seed.source,
+                        because_of: vec![seed.title.clone()],
+                    };
+                    (recommendation, PROVIDER_RECOMMENDATION_SCORE)
+                })),
+                Err(e) => tracing::warn!("Could not get media similar to {:?}: {e:?}", seed.title),
+            }
+        }
+        let mut genre_seeds: HashMap<i32, Vec<String>> = HashMap::new();
+        for link in MetadataToGenre::find()
+            .filter(metadata_to_genre::Column::MetadataId.is_in(seeds.iter().map(|s| s.id)))
+            .all(&self.db)
+            .await?
+        {
+            let seed = seeds.iter().find(|s| s.id == link.metadata_id).unwrap();
+            genre_seeds
+                .entry(link.genre_id)
+                .or_default()
+                .push(seed.title.clone());
+        }
+        let mut shared_genres: HashMap<i32, Vec<i32>> = HashMap::new();
+        for link in MetadataToGenre::find()
+            .filter(metadata_to_genre::Column::GenreId.is_in(genre_seeds.keys().copied()))
+            .filter(metadata_to_genre::Column::MetadataId.is_not_in(library_ids))
+            .all(&self.db)
+            .await?
+        {
+            shared_genres
+                .entry(link.metadata_id)
+                .or_default()
+                .push(link.genre_id);
+        }
+        let best_ids = shared_genres
+            .iter()
+            .sorted_by_key(|(_, genres)| std::cmp::Reverse(genres.len()))
+            .take(MAX_RECOMMENDATIONS)
+            .map(|(id, _)| *id)
+            .collect_vec();
+        for m in Metadata::find()
+            .filter(metadata::Column::Id.is_in(best_ids))
+            .filter(metadata::Column::Lot.eq(lot))
+            .all(&self.db)
+            .await?
+        {
+            let genres = &shared_genres[&m.id];
+            let because_of = genres
+                .iter()
+                .flat_map(|g| genre_seeds[g].iter().cloned())
+                .unique()
+                .collect();
+            let (poster_images, _) = self.metadata_images(&m).await?;
+            let recommendation = MediaRecommendation {
+                item: MediaSearchItem {
+                    identifier: m.identifier,
+                    lot: m.lot,
+                    title: m.title,
+                    image: poster_images.get(0).cloned(),
+                    publish_year: m.publish_year,
+                    popularity: None,
+                },
+                source: m.source,
+                because_of,
+            };
+            candidates.push((recommendation, genres.len()));
+        }
+        let library_keys = library
+            .into_iter()
+            .map(|m| (m.source, m.identifier))
+            .collect();
+        let items = MediaRecommendations(rank_recommendations(candidates, &library_keys));
+        let today = Utc::now().date_naive();
+        match UserRecommendation::find()
+            .filter(user_recommendation::Column::UserId.eq(user_id))
+            .filter(user_recommendation::Column::Lot.eq(lot))
+            .one(&self.db)
+            .await?
+        {
+            Some(r) => {
+                let mut r: user_recommendation::ActiveModel = r.into();
+                r.computed_on = ActiveValue::Set(today);
+                r.items = ActiveValue::Set(items);
+                r.update(&self.db).await?;
+            }
+            None => {
+                let r = user_recommendation::ActiveModel {
+                    user_id: ActiveValue::Set(user_id),
+                    lot: ActiveValue::Set(lot),
+                    computed_on: ActiveValue::Set(today),
+                    items: ActiveValue::Set(items),
+                    ..Default::default()
+                };
+                r.insert(&self.db).await?;
+            }
+        }
+        Ok(true)
+    }
+
+    /// Recalculate the recommendations of all users for the lots in their
+    /// library, unless that was already done today.
+    pub async fn refresh_all_media_recommendations(&self) -> Result<()> {
+        let today = Utc::now().date_naive();
+        for user in User::find().all(&self.db).await? {
+            let lots: Vec<MetadataLot> = Metadata::find()
+                .select_only()
+                .column(metadata::Column::Lot)
+                .filter(
+                    metadata::Column::Id.in_subquery(
+                        Query::select()
+                            .column(user_to_metadata::Column::MetadataId)
+                            .from(UserToMetadata)
+                            .and_where(user_to_metadata::Column::UserId.eq(user.id))
+                            .to_owned(),
+                    ),
+                )
+                .distinct()
+                .into_tuple()
+                .all(&self.db)
+                .await?;
+            let fresh: Vec<MetadataLot> = UserRecommendation::find()
+                .select_only()
+                .column(user_recommendation::Column::Lot)
+                .filter(user_recommendation::Column::UserId.eq(user.id))
+                .filter(user_recommendation::Column::ComputedOn.eq(today))
+                .into_tuple()
+                .all(&self.db)
+                .await?;
+            for lot in lots.into_iter().filter(|l| !fresh.contains(l)) {
+                if let Err(e) = self.refresh_media_recommendations(user.id, lot).await {
+                    tracing::error!(
+                        "Could not refresh {lot:?} recommendations for user {id}: {e:?}",
+                        id = user.id
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn bulk_media_action_reports(
+        &self,
+        user_id: i32,
+    ) -> Result<Vec<bulk_media_action_report::Model>> {
+        let reports = BulkMediaActionReport::find()
+            .filter(bulk_media_action_report::Column::UserId.eq(user_id))
+            .order_by_desc(bulk_media_action_report::Column::StartedOn)
+            .all(&self.db)
+            .await?;
+        Ok(reports)
+    }
+
+    /// Get the id of one of the user's collections by its name.
+    async fn collection_id_by_name(&self, user_id: i32, name: &str) -> Result<i32> {
+        let collection = Collection::find()
+            .filter(collection::Column::UserId.eq(user_id))
+            .filter(collection::Column::Name.eq(name))
+            .one(&self.db)
+            .await?
+            .ok_or_else(|| Error::new(format!("The collection {name:?} does not exist")))?;
+        Ok(collection.id)
+    }
+
+    async fn deploy_bulk_media_action(
+        &self,
+        user_id: i32,
+        action: BulkMediaActionInput,
+        filter: BulkMediaFilter,
+    ) -> Result<IdObject> {
+        if matches!(
+            action.action,
+            BulkMediaAction::AddToCollection | BulkMediaAction::RemoveFromCollection
+        ) {
+            let name = action
+                .collection_name
+                .as_ref()
+                .ok_or_else(|| Error::new("A collection is required for this action"))?;
+            self.collection_id_by_name(user_id, name).await?;
+        }
+        if let Some(name) = filter.collection.as_ref() {
+            self.collection_id_by_name(user_id, name).await?;
+        }
+        let report = bulk_media_action_report::ActiveModel {
+            user_id: ActiveValue::Set(user_id),
+            action: ActiveValue::Set(action.action),
+            ..Default::default()
+        };
+        let report = report.insert(&self.db).await?;
+        let mut storage = self.bulk_media_action.clone();
+        storage
+            .push(BulkMediaActionJob {
+                user_id,
+                report_id: report.id,
+                action,
+                filter,
+            })
+            .await?;
+        record_job_enqueued(BulkMediaActionJob::NAME);
+        Ok(IdObject { id: report.id })
+    }
+
+    /// The ids of the media in the user's library matching a bulk filter.
+    async fn bulk_media_filter_ids(
+        &self,
+        user_id: i32,
+        filter: &BulkMediaFilter,
+    ) -> Result<Vec<i32>> {
+        let collection_id = match filter.collection.as_ref() {
+            Some(name) => Some(self.collection_id_by_name(user_id, name).await?),
+            None => None,
+        };
+        let ids = bulk_media_filter(user_id, filter, collection_id)
+            .order_by_asc(user_to_metadata::Column::MetadataId)
+            .into_tuple()
+            .all(&self.db)
+            .await?;
+        Ok(ids)
+    }
+
+    /// Apply a bulk action to every matching media item and record the outcome
+    /// on its report.
+    pub async fn perform_bulk_media_action(
+        &self,
+        user_id: i32,
+        report_id: i32,
+        action: BulkMediaActionInput,
+        filter: BulkMediaFilter,
+        job: &JobHandle,
+    ) -> Result<()> {
+        let report = BulkMediaActionReport::find_by_id(report_id)
+            .one(&self.db)
+            .await?
+            .ok_or_else(|| Error::new("The bulk action report does not exist"))?;
+        let collection_id = match action.collection_name.as_ref() {
+            Some(name) => Some(self.collection_id_by_name(user_id, name).await?),
+            None => None,
+        };
+        let ids = self.bulk_media_filter_ids(user_id, &filter).await?;
+        let mut result = BulkMediaActionResult {
+            matched: ids.len(),
+            affected: 0,
+        };
+        let mut cancelled = false;
+        for (idx, metadata_id) in ids.iter().enumerate() {
+            job.set_progress(idx, ids.len());
+            if job.is_cancelled() {
+                tracing::info!("Cancelling bulk action {report_id} at item {idx}");
+                cancelled = true;
+                break;
+            }
+            let affected = match (action.action, collection_id) {
+                (BulkMediaAction::AddToCollection, Some(collection_id)) => {
+                    metadata_to_collection::ActiveModel {
+                        metadata_id: ActiveValue::Set(*metadata_id),
+                        collection_id: ActiveValue::Set(collection_id),
+                    }
+                    .insert(&self.db)
+                    .await
+                    .is_ok()
+                }
+                (BulkMediaAction::RemoveFromCollection, Some(collection_id)) => {
+                    MetadataToCollection::delete_many()
+                        .filter(metadata_to_collection::Column::MetadataId.eq(*metadata_id))
+                        .filter(metadata_to_collection::Column::CollectionId.eq(collection_id))
+                        .exec(&self.db)
+                        .await?
+                        .rows_affected
+                        > 0
+                }
+                (BulkMediaAction::MarkMonitored, _) | (BulkMediaAction::MarkIgnored, _) => {
+                    let column = match action.action {
+                        BulkMediaAction::MarkMonitored => user_to_metadata::Column::Monitored,
+                        _ => user_to_metadata::Column::Ignored,
+                    };
+                    UserToMetadata::update_many()
+                        .col_expr(column, Expr::value(true))
+                        .filter(user_to_metadata::Column::UserId.eq(user_id))
+                        .filter(user_to_metadata::Column::MetadataId.eq(*metadata_id))
+                        .filter(column.eq(false))
+                        .exec(&self.db)
+                        .await?
+                        .rows_affected
+                        > 0
+                }
+                _ => false,
+            };
+            if affected {
+                result.affected += 1;
+            }
+        }
+        let mut model: bulk_media_action_report::ActiveModel = report.into();
+        model.finished_on = ActiveValue::Set(Some(Utc::now()));
+        model.details = ActiveValue::Set(Some(result));
+        model.success = ActiveValue::Set(Some(!cancelled));
+        model.update(&self.db).await?;
+        Ok(())
+    }
+
+    /// Toggle whether the user is notified about changes to a media item.
+    /// Returns whether the media is now monitored.
+    async fn toggle_media_monitor(&self, user_id: i32, metadata_id: i32) -> Result<bool> {
+        associate_user_with_metadata(&user_id, &metadata_id, &self.db).await?;
+        let association = UserToMetadata::find_by_id((user_id, metadata_id))
+            .one(&self.db)
+            .await?
+            .ok_or_else(|| Error::new("This media does not exist"))?;
+        let monitored = !association.monitored;
+        let mut association: user_to_metadata::ActiveModel = association.into();
+        association.monitored = ActiveValue::Set(monitored);
+        association.update(&self.db).await?;
+        Ok(monitored)
+    }
+
+    async fn user_details(&self, token: &str) -> Result<UserDetailsResult> {
+        let found_token = user_id_from_token(token.to_owned(), &self.auth_db).await;
+        if let Ok(user_id) = found_token {
+            let user = self.user_by_id(user_id).await?;
+            Ok(UserDetailsResult::Ok(user))
+        } else {
+            Ok(UserDetailsResult::Error(UserDetailsError {
+                error: UserDetailsErrorVariant::AuthTokenInvalid,
+            }))
+        }
+    }
+
+    async fn user_by_id(&self, user_id: i32) -> Result<user::Model> {
+        User::find_by_id(user_id)
             .one(&self.db)
             .await
             .unwrap()
@@ -2664,16 +5809,27 @@ impl MiscellaneousService {
         let mut ls = summary::Model::default();
 
         let num_reviews = Review::find()
+            .filter(review::Column::DeletedAt.is_null())
             .filter(review::Column::UserId.eq(user_id.to_owned()))
             .count(&self.db)
             .await?;
 
         ls.data.media.reviews_posted = num_reviews;
 
+        ls.data.media.dropped = UserToMetadata::find()
+            .filter(user_to_metadata::Column::UserId.eq(*user_id))
+            .filter(user_to_metadata::Column::State.eq(MediaState::Dropped))
+            .filter(user_to_metadata::Column::Ignored.eq(false))
+            .count(&self.db)
+            .await?;
+
+        let ignored = self.ignored_metadata_ids(*user_id).await?;
         let mut seen_items = Seen::find()
+            .filter(seen::Column::DeletedAt.is_null())
             .filter(seen::Column::UserId.eq(user_id.to_owned()))
             .filter(seen::Column::UserId.eq(user_id.to_owned()))
             .filter(seen::Column::Progress.eq(100))
+            .filter(seen::Column::MetadataId.is_not_in(ignored))
             .find_also_related(Metadata)
             .stream(&self.db)
             .await?;
@@ -2684,29 +5840,30 @@ impl MiscellaneousService {
         let mut unique_podcast_episodes = HashSet::new();
         while let Some((seen, metadata)) = seen_items.try_next().await.unwrap() {
             let meta = metadata.to_owned().unwrap();
+            let plays = seen.plays.max(1);
             match meta.specifics {
                 MediaSpecifics::AudioBook(item) => {
-                    ls.data.media.audio_books.played += 1;
+                    ls.data.media.audio_books.played += plays;
                     if let Some(r) = item.runtime {
-                        ls.data.media.audio_books.runtime += r;
+                        ls.data.media.audio_books.runtime += r * plays;
                     }
                 }
                 MediaSpecifics::Anime(item) => {
-                    ls.data.media.anime.watched += 1;
+                    ls.data.media.anime.watched += plays;
                     if let Some(r) = item.episodes {
-                        ls.data.media.anime.episodes += r;
+                        ls.data.media.anime.episodes += r * plays;
                     }
                 }
                 MediaSpecifics::Manga(item) => {
-                    ls.data.media.manga.read += 1;
+                    ls.data.media.manga.read += plays;
                     if let Some(r) = item.chapters {
-                        ls.data.media.manga.chapters += r;
+                        ls.data.media.manga.chapters += r * plays;
                     }
                 }
                 MediaSpecifics::Book(item) => {
-                    ls.data.media.books.read += 1;
+                    ls.data.media.books.read += plays;
                     if let Some(pg) = item.pages {
-                        ls.data.media.books.pages += pg;
+                        ls.data.media.books.pages += pg * plays;
                     }
                 }
                 MediaSpecifics::Podcast(item) => {
@@ -2719,7 +5876,7 @@ impl MiscellaneousService {
                                 SeenOrReviewExtraInformation::Podcast(s) => {
                                     if s.episode == episode.number {
                                         if let Some(r) = episode.runtime {
-                                            ls.data.media.podcasts.runtime += r;
+                                            ls.data.media.podcasts.runtime += r * plays;
                                         }
                                         unique_podcast_episodes.insert((s.episode, episode.id));
                                     }
@@ -2729,9 +5886,9 @@ impl MiscellaneousService {
                     }
                 }
                 MediaSpecifics::Movie(item) => {
-                    ls.data.media.movies.watched += 1;
+                    ls.data.media.movies.watched += plays;
                     if let Some(r) = item.runtime {
-                        ls.data.media.movies.runtime += r;
+                        ls.data.media.movies.runtime += r * plays;
                     }
                 }
                 MediaSpecifics::Show(item) => {
@@ -2745,9 +5902,9 @@ impl MiscellaneousService {
                                         && s.episode == episode.episode_number
                                     {
                                         if let Some(r) = episode.runtime {
-                                            ls.data.media.shows.runtime += r;
+                                            ls.data.media.shows.runtime += r * plays;
                                         }
-                                        ls.data.media.shows.watched_episodes += 1;
+                                        ls.data.media.shows.watched_episodes += plays;
                                         unique_show_seasons.insert((s.season, season.id));
                                     }
                                 }
@@ -2756,7 +5913,7 @@ impl MiscellaneousService {
                     }
                 }
                 MediaSpecifics::VideoGame(_item) => {
-                    ls.data.media.video_games.played += 1;
+                    ls.data.media.video_games.played += plays;
                 }
                 MediaSpecifics::Unknown => {}
             }
@@ -2812,6 +5969,7 @@ impl MiscellaneousService {
         };
         let user = user.insert(&self.db).await.unwrap();
         storage.push(UserCreatedJob { user_id: user.id }).await?;
+        record_job_enqueued(UserCreatedJob::NAME);
         Ok(RegisterResult::Ok(IdObject { id: user.id }))
     }
 
@@ -3006,6 +6164,8 @@ impl MiscellaneousService {
             publish_year: input.publish_year,
             publish_date: None,
             specifics,
+            groups: vec![],
+            alternative_titles: vec![],
         };
         let media = self.commit_media_internal(details).await?;
         self.add_media_to_collection(
@@ -3020,88 +6180,7 @@ impl MiscellaneousService {
     }
 
     pub async fn export(&self, user_id: i32) -> Result<Vec<ImportOrExportItem<String>>> {
-        let related_metadata = UserToMetadata::find()
-            .filter(user_to_metadata::Column::UserId.eq(user_id))
-            .all(&self.db)
-            .await
-            .unwrap();
-        let distinct_meta_ids = related_metadata
-            .into_iter()
-            .map(|m| m.metadata_id)
-            .collect_vec();
-        let metas = Metadata::find()
-            .filter(metadata::Column::Id.is_in(distinct_meta_ids))
-            .order_by(metadata::Column::Id, Order::Asc)
-            .all(&self.db)
-            .await?;
-
-        let mut resp = vec![];
-
-        for m in metas {
-            let mut seen_history = m
-                .find_related(Seen)
-                .filter(seen::Column::UserId.eq(user_id))
-                .all(&self.db)
-                .await
-                .unwrap();
-            modify_seen_elements(&mut seen_history);
-            let seen_history = seen_history
-                .into_iter()
-                .map(|s| {
-                    let (show_season_number, show_episode_number) = match s.show_information {
-                        Some(d) => (Some(d.season), Some(d.episode)),
-                        None => (None, None),
-                    };
-                    let podcast_episode_number = s.podcast_information.map(|d| d.episode);
-                    ImportOrExportItemSeen {
-                        started_on: s.started_on.map(convert_naive_to_utc),
-                        ended_on: s.finished_on.map(convert_naive_to_utc),
-                        show_season_number,
-                        show_episode_number,
-                        podcast_episode_number,
-                    }
-                })
-                .collect();
-            let db_reviews = m
-                .find_related(Review)
-                .filter(review::Column::UserId.eq(user_id))
-                .all(&self.db)
-                .await
-                .unwrap();
-            let mut reviews = vec![];
-            for r in db_reviews {
-                let rev = self.review_by_id(r.id).await.unwrap();
-                reviews.push(ImportOrExportItemRating {
-                    review: Some(ImportOrExportItemReview {
-                        date: Some(rev.posted_on),
-                        spoiler: Some(rev.spoiler),
-                        text: rev.text,
-                    }),
-                    rating: rev.rating,
-                    show_season_number: rev.show_season,
-                    show_episode_number: rev.show_episode,
-                    podcast_episode_number: rev.podcast_episode,
-                });
-            }
-            let collections = self
-                .media_in_collections(user_id, m.id)
-                .await?
-                .into_iter()
-                .map(|c| c.name)
-                .collect();
-            let exp = ImportOrExportItem {
-                source_id: m.id.to_string(),
-                lot: m.lot,
-                source: m.source,
-                identifier: m.identifier,
-                seen_history,
-                reviews,
-                collections,
-            };
-            resp.push(exp);
-        }
-
-        Ok(resp)
+        export_user_media(&self.db, user_id).await
     }
 
     fn get_sql_and_values(&self, stmt: SelectStatement) -> (String, Values) {
@@ -3118,15 +6197,53 @@ impl MiscellaneousService {
         Statement::from_sql_and_values(self.db.get_database_backend(), &sql, values)
     }
 
-    async fn update_user_feature_preference(
+    async fn update_user_preference(
         &self,
-        input: UpdateUserFeaturePreferenceInput,
+        path: String,
+        value: String,
         user_id: i32,
     ) -> Result<bool> {
+        let value = serde_json::from_str(&value).unwrap_or(serde_json::Value::String(value));
         let user_model = self.user_by_id(user_id).await?;
-        let mut preferences = user_model.preferences.clone();
-        match input.property {
-            MetadataLot::AudioBook => preferences.features_enabled.audio_books = input.value,
+        let preferences = user_model
+            .preferences
+            .update_path(&path, value)
+            .map_err(|e| Error::new(e.to_string()))?;
+        if preferences
+            .general
+            .stale_in_progress_after_days
+            .map_or(false, |d| d <= 0)
+        {
+            return Err(Error::new("The number of days must be positive".to_owned()));
+        }
+        if preferences
+            .import_defaults
+            .min_match_confidence
+            .map_or(false, |c| c < Decimal::ZERO || c > Decimal::ONE)
+        {
+            return Err(Error::new(
+                "The match confidence must be between 0 and 1".to_owned(),
+            ));
+        }
+        let mut preferences = preferences;
+        for secret in preferences.secrets_mut() {
+            *secret = self.secrets.seal(secret)?;
+        }
+        let mut user_model: user::ActiveModel = user_model.into();
+        user_model.preferences = ActiveValue::Set(preferences);
+        user_model.update(&self.db).await?;
+        Ok(true)
+    }
+
+    async fn update_user_feature_preference(
+        &self,
+        input: UpdateUserFeaturePreferenceInput,
+        user_id: i32,
+    ) -> Result<bool> {
+        let user_model = self.user_by_id(user_id).await?;
+        let mut preferences = user_model.preferences.clone();
+        match input.property {
+            MetadataLot::AudioBook => preferences.features_enabled.audio_books = input.value,
             MetadataLot::Book => preferences.features_enabled.books = input.value,
             MetadataLot::Movie => preferences.features_enabled.movies = input.value,
             MetadataLot::Podcast => preferences.features_enabled.podcasts = input.value,
@@ -3170,11 +6287,10 @@ impl MiscellaneousService {
                 timestamp: i.timestamp,
             })
         });
-        let sink_integrations = user.sink_integrations.0;
-        sink_integrations.into_iter().for_each(|i| {
+        for i in user.sink_integrations.0 {
             let description = match i.settings {
                 UserSinkIntegrationSetting::Jellyfin { slug } => {
-                    format!("Jellyfin slug: {}", slug)
+                    format!("Jellyfin slug: {}", self.secrets.open(&slug)?)
                 }
             };
             all_integrations.push(GraphqlUserIntegration {
@@ -3183,7 +6299,7 @@ impl MiscellaneousService {
                 description,
                 timestamp: i.timestamp,
             })
-        });
+        }
         Ok(all_integrations)
     }
 
@@ -3203,7 +6319,9 @@ impl MiscellaneousService {
                     let slug = get_id_hasher(&self.config.integration.hasher_salt)
                         .encode(&[user_id.try_into().unwrap()]);
                     let slug = format!("{}--{}", slug, nanoid!(5));
-                    UserSinkIntegrationSetting::Jellyfin { slug }
+                    UserSinkIntegrationSetting::Jellyfin {
+                        slug: self.secrets.seal(&slug)?,
+                    }
                 }
             },
         };
@@ -3233,7 +6351,7 @@ impl MiscellaneousService {
                 UserYankIntegrationLot::Audiobookshelf => {
                     UserYankIntegrationSetting::Audiobookshelf {
                         base_url: input.base_url,
-                        token: input.token,
+                        token: self.secrets.seal(&input.token)?,
                     }
                 }
             },
@@ -3299,21 +6417,68 @@ impl MiscellaneousService {
         Ok(())
     }
 
-    async fn media_exists_in_database(
+    pub async fn media_exists_in_database(
         &self,
         lot: MetadataLot,
         source: MetadataSource,
         identifier: &str,
     ) -> Result<Option<IdObject>> {
+        // DEV: there can be duplicates, so always pick the oldest one to make sure
+        // that the same item is resolved every time
         let media = Metadata::find()
             .filter(metadata::Column::Lot.eq(lot))
             .filter(metadata::Column::Source.eq(source))
             .filter(metadata::Column::Identifier.eq(identifier))
+            .order_by_asc(metadata::Column::Id)
             .one(&self.db)
             .await?;
         Ok(media.map(|m| IdObject { id: m.id }))
     }
 
+    async fn media_by_identifier(
+        &self,
+        user_id: i32,
+        lot: MetadataLot,
+        source: MetadataSource,
+        identifier: &str,
+    ) -> Result<Option<MediaByIdentifier>> {
+        let metadata_id = match self
+            .media_exists_in_database(lot, source, identifier)
+            .await?
+        {
+            Some(m) => m.id,
+            None => return Ok(None),
+        };
+        let details = self.media_details(metadata_id).await?;
+        let association = match UserToMetadata::find_by_id((user_id, metadata_id))
+            .one(&self.db)
+            .await?
+        {
+            Some(utm) => {
+                let seen_count = self.seen_history(metadata_id, user_id).await?.len();
+                let collections = self
+                    .media_in_collections(user_id, metadata_id)
+                    .await?
+                    .into_iter()
+                    .map(|c| c.name)
+                    .collect();
+                Some(UserMediaAssociation {
+                    last_updated_on: utm.last_updated_on,
+                    monitored: utm.monitored,
+                    ignored: utm.ignored,
+                    state: utm.state,
+                    seen_count,
+                    collections,
+                })
+            }
+            None => None,
+        };
+        Ok(Some(MediaByIdentifier {
+            details,
+            association,
+        }))
+    }
+
     async fn media_sources_for_lot(&self, lot: MetadataLot) -> Vec<MetadataSource> {
         match lot {
             MetadataLot::AudioBook => vec![MetadataSource::Audible],
@@ -3382,7 +6547,7 @@ impl MiscellaneousService {
                 let response = match &integration.settings {
                     UserYankIntegrationSetting::Audiobookshelf { base_url, token } => {
                         self.integration_service
-                            .audiobookshelf_progress(base_url, token)
+                            .audiobookshelf_progress(base_url, &self.secrets.open(token)?)
                             .await
                     }
                 };
@@ -3473,7 +6638,7 @@ impl MiscellaneousService {
         Ok(resp)
     }
 
-    async fn admin_account_guard(&self, user_id: i32) -> Result<()> {
+    pub async fn admin_account_guard(&self, user_id: i32) -> Result<()> {
         let main_user = self.user_by_id(user_id).await?;
         if main_user.lot != UserLot::Admin {
             return Err(Error::new("Only admins can perform this operation."));
@@ -3481,6 +6646,92 @@ impl MiscellaneousService {
         Ok(())
     }
 
+    /// Take a token for the operation. Users that are not logged in share
+    /// their limits.
+    pub async fn rate_limit_guard(
+        &self,
+        user_id: Option<i32>,
+        operation: RateLimitedOperation,
+    ) -> Result<()> {
+        if let Some(user_id) = user_id {
+            if self.config.rate_limit.exempt_admins
+                && self.user_by_id(user_id).await?.lot == UserLot::Admin
+            {
+                return Ok(());
+            }
+        }
+        self.rate_limiter
+            .check(user_id, operation)
+            .map_err(|retry_after| rate_limited_error(operation, retry_after))
+    }
+
+    /// Try to take the lease on a cron job. Returns `false` if another instance
+    /// is currently holding it.
+    pub async fn acquire_job_lease(&self, name: &str, duration: ChronoDuration) -> Result<bool> {
+        let now = Utc::now();
+        let renewed = JobLease::update_many()
+            .col_expr(
+                job_lease::Column::Holder,
+                Expr::value(self.instance_id.clone()),
+            )
+            .col_expr(job_lease::Column::AcquiredOn, Expr::value(now))
+            .col_expr(job_lease::Column::ExpiresOn, Expr::value(now + duration))
+            .filter(job_lease::Column::Name.eq(name))
+            .filter(
+                Cond::any()
+                    .add(job_lease::Column::ExpiresOn.lt(now))
+                    .add(job_lease::Column::Holder.eq(self.instance_id.clone())),
+            )
+            .exec(&self.db)
+            .await?;
+        if renewed.rows_affected > 0 {
+            return Ok(true);
+        }
+        // DEV: the primary key makes sure only one instance can create the lease
+        let lease = job_lease::ActiveModel {
+            name: ActiveValue::Set(name.to_owned()),
+            holder: ActiveValue::Set(self.instance_id.clone()),
+            acquired_on: ActiveValue::Set(now),
+            expires_on: ActiveValue::Set(now + duration),
+        };
+        Ok(lease.insert(&self.db).await.is_ok())
+    }
+
+    async fn background_jobs(&self, user_id: i32) -> Result<Vec<JobStatus>> {
+        let is_admin = self.user_by_id(user_id).await?.lot == UserLot::Admin;
+        Ok(self
+            .jobs
+            .statuses()
+            .into_iter()
+            .filter(|j| is_admin || j.user_id == Some(user_id))
+            .collect())
+    }
+
+    async fn cancel_background_job(&self, user_id: i32, job_id: String) -> Result<bool> {
+        let job = self
+            .jobs
+            .get(&job_id)
+            .ok_or_else(|| Error::new(format!("No job with id = {job_id} is running")))?;
+        if job.user_id != Some(user_id) {
+            self.admin_account_guard(user_id).await?;
+        }
+        if !job.cancellable {
+            return Err(Error::new(format!(
+                "The {} job can not be cancelled",
+                job.name
+            )));
+        }
+        // DEV: the job might have finished since it was looked up
+        Ok(self.jobs.cancel(&job_id))
+    }
+
+    async fn job_leases(&self) -> Result<Vec<job_lease::Model>> {
+        Ok(JobLease::find()
+            .order_by_asc(job_lease::Column::Name)
+            .all(&self.db)
+            .await?)
+    }
+
     async fn users(&self) -> Result<Vec<user::Model>> {
         Ok(User::find()
             .order_by_asc(user::Column::Id)
@@ -3488,6 +6739,58 @@ impl MiscellaneousService {
             .await?)
     }
 
+    async fn clear_user_media(&self, user_id: i32, confirmation_token: String) -> Result<bool> {
+        let user = self.user_by_id(user_id).await?;
+        if confirmation_token != user.name {
+            return Err(Error::new(
+                "The confirmation token does not match the username".to_owned(),
+            ));
+        }
+        let txn = self.db.begin().await?;
+        let collection_ids: Vec<i32> = Collection::find()
+            .select_only()
+            .column(collection::Column::Id)
+            .filter(collection::Column::UserId.eq(user_id))
+            .into_tuple()
+            .all(&txn)
+            .await?;
+        MetadataToCollection::delete_many()
+            .filter(metadata_to_collection::Column::CollectionId.is_in(collection_ids))
+            .exec(&txn)
+            .await?;
+        Seen::delete_many()
+            .filter(seen::Column::UserId.eq(user_id))
+            .exec(&txn)
+            .await?;
+        Review::delete_many()
+            .filter(review::Column::UserId.eq(user_id))
+            .exec(&txn)
+            .await?;
+        // DEV: ignored and monitored media are not part of the library, so
+        // only the state of their association is reset
+        UserToMetadata::delete_many()
+            .filter(user_to_metadata::Column::UserId.eq(user_id))
+            .filter(user_to_metadata::Column::Ignored.eq(false))
+            .filter(user_to_metadata::Column::Monitored.eq(false))
+            .exec(&txn)
+            .await?;
+        UserToMetadata::update_many()
+            .filter(user_to_metadata::Column::UserId.eq(user_id))
+            .col_expr(
+                user_to_metadata::Column::State,
+                Expr::value(Option::<MediaState>::None),
+            )
+            .col_expr(
+                user_to_metadata::Column::ShowProgress,
+                Expr::value(Option::<ShowProgress>::None),
+            )
+            .exec(&txn)
+            .await?;
+        txn.commit().await?;
+        tracing::info!("Cleared all media for user {user_id}");
+        self.regenerate_user_summary(user_id).await
+    }
+
     async fn delete_user(&self, to_delete_user_id: i32) -> Result<bool> {
         let maybe_user = User::find_by_id(to_delete_user_id).one(&self.db).await?;
         if let Some(u) = maybe_user {
@@ -3533,7 +6836,11 @@ impl MiscellaneousService {
         for db_integration in user.sink_integrations.0.into_iter() {
             let progress = match db_integration.settings {
                 UserSinkIntegrationSetting::Jellyfin { slug } => {
-                    if slug == user_hash_id && integration == UserSinkIntegrationLot::Jellyfin {
+                    let matches = self
+                        .secrets
+                        .open(&slug)
+                        .map_or(false, |slug| slug == user_hash_id);
+                    if matches && integration == UserSinkIntegrationLot::Jellyfin {
                         self.integration_service
                             .jellyfin_progress(&payload)
                             .await
@@ -3550,16 +6857,28 @@ impl MiscellaneousService {
         Ok(())
     }
 
+    /// The progress at or above which media is recorded as completed for this
+    /// user.
+    async fn completion_threshold(&self, user_id: i32) -> Result<i32> {
+        Ok(self
+            .user_by_id(user_id)
+            .await?
+            .preferences
+            .general
+            .completion_threshold_percent
+            .unwrap_or(self.config.integration.maximum_progress_limit))
+    }
+
     async fn integration_progress_update(&self, pu: IntegrationMedia, user_id: i32) -> Result<()> {
         if pu.progress < self.config.integration.minimum_progress_limit {
             return Err(Error::new("Progress outside bound"));
         }
-        let progress = if pu.progress > self.config.integration.maximum_progress_limit {
-            100
-        } else {
-            pu.progress
-        };
-        let IdObject { id } = self.commit_media(pu.lot, pu.source, &pu.identifier).await?;
+        // DEV: `progress_update` decides whether this counts as completed using
+        // the user's threshold
+        let progress = pu.progress.min(100);
+        let IdObject { id } = self
+            .commit_media(Some(user_id), pu.lot, pu.source, &pu.identifier)
+            .await?;
         self.progress_update(
             ProgressUpdateInput {
                 metadata_id: id,
@@ -3569,6 +6888,8 @@ impl MiscellaneousService {
                 show_episode_number: pu.show_episode_number,
                 podcast_episode_number: pu.podcast_episode_number,
                 change_state: None,
+                plays: None,
+                import: None,
             },
             user_id,
         )
@@ -3585,7 +6906,7 @@ impl MiscellaneousService {
         )
         .await
         .ok();
-        match seen.state {
+        let state = match seen.state {
             SeenState::InProgress => {
                 self.add_media_to_collection(
                     &seen.user_id,
@@ -3596,8 +6917,9 @@ impl MiscellaneousService {
                 )
                 .await
                 .ok();
+                Some(MediaState::InProgress)
             }
-            SeenState::Dropped | SeenState::OnAHold => {
+            SeenState::Dropped | SeenState::OnAHold | SeenState::Stale => {
                 self.remove_media_item_from_collection(
                     &seen.user_id,
                     &seen.metadata_id,
@@ -3605,6 +6927,7 @@ impl MiscellaneousService {
                 )
                 .await
                 .ok();
+                MediaState::from_seen_state(seen.state)
             }
             SeenState::Completed => {
                 let metadata = self.generic_metadata(seen.metadata_id).await?;
@@ -3660,6 +6983,7 @@ impl MiscellaneousService {
                         )
                         .await
                         .ok();
+                        Some(MediaState::Completed)
                     } else {
                         self.add_media_to_collection(
                             &seen.user_id,
@@ -3670,6 +6994,7 @@ impl MiscellaneousService {
                         )
                         .await
                         .ok();
+                        Some(MediaState::InProgress)
                     }
                 } else {
                     self.remove_media_item_from_collection(
@@ -3679,13 +7004,848 @@ impl MiscellaneousService {
                     )
                     .await
                     .ok();
-                };
+                    Some(MediaState::Completed)
+                }
             }
         };
+        if let Some(state) = state {
+            self.set_media_state(seen.user_id, seen.metadata_id, state)
+                .await?;
+        }
         Ok(())
     }
 }
 
+/// All the media of a user along with their seen history, reviews and
+/// collections. This works with any database that has the Ryot schema, which
+/// allows reading backups of other instances.
+pub async fn export_user_media<C: ConnectionTrait>(
+    db: &C,
+    user_id: i32,
+) -> Result<Vec<ImportOrExportItem<String>>> {
+    let related_metadata = UserToMetadata::find()
+        .filter(user_to_metadata::Column::UserId.eq(user_id))
+        .all(db)
+        .await
+        .unwrap();
+    let distinct_meta_ids = related_metadata.iter().map(|m| m.metadata_id).collect_vec();
+    let metas = Metadata::find()
+        .filter(metadata::Column::Id.is_in(distinct_meta_ids))
+        .order_by(metadata::Column::Id, Order::Asc)
+        .all(db)
+        .await?;
+    let user_collections = Collection::find()
+        .filter(collection::Column::UserId.eq(user_id))
+        .all(db)
+        .await?;
+
+    let mut resp = vec![];
+
+    for m in metas {
+        let mut seen_history = m
+            .find_related(Seen)
+            .filter(seen::Column::UserId.eq(user_id))
+            .filter(seen::Column::DeletedAt.is_null())
+            .all(db)
+            .await
+            .unwrap();
+        modify_seen_elements(&mut seen_history);
+        let seen_history = seen_history
+            .into_iter()
+            .map(|s| {
+                let (show_season_number, show_episode_number) = match s.show_information {
+                    Some(d) => (Some(d.season), Some(d.episode)),
+                    None => (None, None),
+                };
+                let podcast_episode_number = s.podcast_information.map(|d| d.episode);
+                ImportOrExportItemSeen {
+                    started_on: s.started_on.map(convert_naive_to_utc),
+                    ended_on: s.finished_on.map(convert_naive_to_utc),
+                    show_season_number,
+                    show_episode_number,
+                    podcast_episode_number,
+                    podcast_episode_guid: None,
+                    progress: Some(s.progress),
+                    plays: Some(s.plays),
+                    date_precision: Some(s.date_precision),
+                }
+            })
+            .collect();
+        let reviews = m
+            .find_related(Review)
+            .filter(review::Column::UserId.eq(user_id))
+            .filter(review::Column::DeletedAt.is_null())
+            .all(db)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|r| {
+                let (show_season_number, show_episode_number, podcast_episode_number) = match r
+                    .extra_information
+                {
+                    Some(SeenOrReviewExtraInformation::Show(d)) => {
+                        (Some(d.season), Some(d.episode), None)
+                    }
+                    Some(SeenOrReviewExtraInformation::Podcast(d)) => (None, None, Some(d.episode)),
+                    None => (None, None, None),
+                };
+                ImportOrExportItemRating {
+                    review: Some(ImportOrExportItemReview {
+                        date: Some(r.posted_on),
+                        spoiler: Some(r.spoiler),
+                        text: r.text,
+                        visibility: Some(r.visibility),
+                    }),
+                    rating: r.rating,
+                    show_season_number,
+                    show_episode_number,
+                    podcast_episode_number,
+                }
+            })
+            .collect();
+        let collection_ids = MetadataToCollection::find()
+            .filter(metadata_to_collection::Column::MetadataId.eq(m.id))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|mtc| mtc.collection_id)
+            .collect_vec();
+        let collections = user_collections
+            .iter()
+            .filter(|c| collection_ids.contains(&c.id))
+            .map(|c| c.name.clone())
+            .collect();
+        let state = related_metadata
+            .iter()
+            .find(|r| r.metadata_id == m.id)
+            .and_then(|r| r.state);
+        let exp = ImportOrExportItem {
+            source_id: m.id.to_string(),
+            title: Some(m.title),
+            lot: m.lot,
+            source: m.source,
+            identifier: m.identifier,
+            seen_history,
+            reviews,
+            collections,
+            state,
+        };
+        resp.push(exp);
+    }
+
+    Ok(resp)
+}
+
+/// Order the candidates found by searching a provider so that the same one is
+/// committed every time. Candidates with the expected lot are preferred, then
+/// exact (case insensitive) title matches, then the most popular ones and then
+/// the lowest identifier. Duplicate identifiers are removed.
+pub fn sort_media_candidates(
+    candidates: Vec<MediaSearchItem>,
+    lot: MetadataLot,
+    title: &str,
+) -> Vec<MediaSearchItem> {
+    let title = title.to_lowercase();
+    candidates
+        .into_iter()
+        .unique_by(|c| c.identifier.clone())
+        .sorted_by_cached_key(|c| {
+            // DEV: numeric identifiers are compared as numbers so that "9" comes
+            // before "10"
+            let numeric = c.identifier.parse::<u64>().ok();
+            (
+                c.lot != lot,
+                c.title.to_lowercase() != title,
+                std::cmp::Reverse(c.popularity),
+                numeric.is_none(),
+                numeric,
+                c.identifier.clone(),
+            )
+        })
+        .collect()
+}
+
+/// Insert the seen items of a batch and associate their media with the user.
+async fn insert_seen_batch<C: ConnectionTrait>(
+    db: &C,
+    user_id: i32,
+    rows: Vec<seen::ActiveModel>,
+    metadata_ids: &[i32],
+) -> Result<()> {
+    Seen::insert_many(rows).exec(db).await?;
+    // DEV: `insert_many` does not run the hooks of the seen entity, so their
+    // work is repeated here
+    for metadata_id in metadata_ids.iter() {
+        associate_user_with_metadata(&user_id, metadata_id, db)
+            .await
+            .ok();
+    }
+    UserToMetadata::update_many()
+        .filter(user_to_metadata::Column::UserId.eq(user_id))
+        .filter(user_to_metadata::Column::MetadataId.is_in(metadata_ids.to_vec()))
+        .col_expr(
+            user_to_metadata::Column::LastUpdatedOn,
+            Expr::value(Utc::now()),
+        )
+        .col_expr(
+            user_to_metadata::Column::ShowProgress,
+            Expr::value(Option::<ShowProgress>::None),
+        )
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+/// The episode that a progress update is for. Updates of shows and podcasts
+/// must point to an episode that exists.
+fn seen_extra_information(
+    meta: &metadata::Model,
+    input: &ProgressUpdateInput,
+) -> Result<Option<SeenOrReviewExtraInformation>, ProgressUpdateErrorVariant> {
+    match (meta.lot, &meta.specifics) {
+        (MetadataLot::Show, MediaSpecifics::Show(spec)) => {
+            let (season, episode) = match (input.show_season_number, input.show_episode_number) {
+                (Some(s), Some(e)) => (s, e),
+                _ => return Err(ProgressUpdateErrorVariant::InvalidUpdate),
+            };
+            let is_there = spec.seasons.iter().any(|s| {
+                s.season_number == season && s.episodes.iter().any(|e| e.episode_number == episode)
+            });
+            if !is_there {
+                return Err(ProgressUpdateErrorVariant::InvalidUpdate);
+            }
+            Ok(Some(SeenOrReviewExtraInformation::Show(
+                SeenShowExtraInformation { season, episode },
+            )))
+        }
+        (MetadataLot::Podcast, MediaSpecifics::Podcast(spec)) => {
+            let episode = input
+                .podcast_episode_number
+                .ok_or(ProgressUpdateErrorVariant::InvalidUpdate)?;
+            if !spec.episodes.iter().any(|e| e.number == episode) {
+                return Err(ProgressUpdateErrorVariant::InvalidUpdate);
+            }
+            Ok(Some(SeenOrReviewExtraInformation::Podcast(
+                SeenPodcastExtraInformation { episode },
+            )))
+        }
+        (MetadataLot::Show | MetadataLot::Podcast, _) => {
+            Err(ProgressUpdateErrorVariant::InvalidUpdate)
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Count the watched episodes of every season except the specials. Episodes
+/// without a date are assumed to have aired.
+fn compute_show_progress(
+    specifics: &ShowSpecifics,
+    watched: &HashSet<(i32, i32)>,
+    today: NaiveDate,
+) -> ShowProgress {
+    let aired = specifics
+        .seasons
+        .iter()
+        .filter(|s| s.name != "Specials")
+        .map(|s| {
+            let episodes = s
+                .episodes
+                .iter()
+                .filter(|e| e.publish_date.map_or(true, |d| d <= today))
+                .sorted_by_key(|e| e.episode_number)
+                .collect_vec();
+            (s, episodes)
+        })
+        .sorted_by_key(|(s, _)| s.season_number)
+        .collect_vec();
+    let seasons = aired
+        .iter()
+        .map(|(s, episodes)| ShowSeasonProgress {
+            season_number: s.season_number,
+            name: s.name.clone(),
+            watched: episodes
+                .iter()
+                .filter(|e| watched.contains(&(s.season_number, e.episode_number)))
+                .count(),
+            total: episodes.len(),
+        })
+        .collect_vec();
+    let total: usize = seasons.iter().map(|s| s.total).sum();
+    let seen: usize = seasons.iter().map(|s| s.watched).sum();
+    let percentage = if total == 0 { 0 } else { seen * 100 / total };
+    let furthest = aired
+        .iter()
+        .flat_map(|(s, episodes)| {
+            episodes
+                .iter()
+                .map(move |e| (s.season_number, e.episode_number))
+        })
+        .filter(|key| watched.contains(key))
+        .max();
+    let next_episode = aired
+        .iter()
+        .flat_map(|(s, episodes)| episodes.iter().map(move |e| (s.season_number, *e)))
+        .find(|(season, e)| {
+            let key = (*season, e.episode_number);
+            !watched.contains(&key) && furthest.map_or(true, |f| key > f)
+        })
+        .map(|(season_number, e)| ShowNextEpisode {
+            season_number,
+            episode_number: e.episode_number,
+            name: e.name.clone(),
+        });
+    ShowProgress {
+        seasons,
+        percentage: percentage.try_into().unwrap(),
+        next_episode,
+    }
+}
+
+/// Count the seen items finished on each date. A date that is only known to its
+/// year counts for the year but not for any of its months.
+fn summarize_year(year: i32, finished: &[(NaiveDate, SeenDatePrecision)]) -> YearlySeenSummary {
+    let mut months = vec![0; 12];
+    for (date, precision) in finished {
+        if *precision != SeenDatePrecision::Year {
+            months[date.month0() as usize] += 1;
+        }
+    }
+    YearlySeenSummary {
+        year,
+        total: finished.len(),
+        months,
+    }
+}
+
+/// Round the progress up to completed if it is at or above the threshold.
+fn apply_completion_threshold(progress: i32, threshold: i32) -> i32 {
+    if progress >= threshold.clamp(1, 100) {
+        100
+    } else {
+        progress
+    }
+}
+
+/// Merge the candidates that were recommended more than once, drop the ones
+/// already in the library and order the rest by their total score.
+fn rank_recommendations(
+    candidates: Vec<(MediaRecommendation, usize)>,
+    library: &HashSet<(MetadataSource, String)>,
+) -> Vec<MediaRecommendation> {
+    let mut merged: Vec<(MediaRecommendation, usize)> = vec![];
+    for (recommendation, score) in candidates {
+        let key = (
+            recommendation.source,
+            recommendation.item.identifier.clone(),
+        );
+        if library.contains(&key) {
+            continue;
+        }
+        match merged
+            .iter_mut()
+            .find(|(r, _)| r.source == key.0 && r.item.identifier == key.1)
+        {
+            Some((existing, total)) => {
+                *total += score;
+                for title in recommendation.because_of {
+                    if !existing.because_of.contains(&title) {
+                        existing.because_of.push(title);
+                    }
+                }
+            }
+            None => merged.push((recommendation, score)),
+        }
+    }
+    merged.sort_by(|a, b| b.1.cmp(&a.1));
+    merged
+        .into_iter()
+        .map(|(r, _)| r)
+        .take(MAX_RECOMMENDATIONS)
+        .collect()
+}
+
+/// Move the seen items and reviews of a media item to another one and delete it.
+async fn merge_metadata_rows<C: ConnectionTrait>(
+    db: &C,
+    merge_from: i32,
+    merge_into: i32,
+) -> Result<()> {
+    for old_seen in Seen::find()
+        .filter(seen::Column::MetadataId.eq(merge_from))
+        .all(db)
+        .await?
+    {
+        let old_seen_active: seen::ActiveModel = old_seen.clone().into();
+        let new_seen = seen::ActiveModel {
+            id: ActiveValue::NotSet,
+            metadata_id: ActiveValue::Set(merge_into),
+            ..old_seen_active
+        };
+        new_seen.insert(db).await?;
+        old_seen.delete(db).await?;
+    }
+    for old_review in Review::find()
+        .filter(review::Column::MetadataId.eq(merge_from))
+        .all(db)
+        .await?
+    {
+        let old_review_active: review::ActiveModel = old_review.clone().into();
+        let new_review = review::ActiveModel {
+            id: ActiveValue::NotSet,
+            metadata_id: ActiveValue::Set(merge_into),
+            ..old_review_active
+        };
+        new_review.insert(db).await?;
+        old_review.delete(db).await?;
+    }
+    Metadata::delete_by_id(merge_from).exec(db).await?;
+    Ok(())
+}
+
+/// The number of episodes of each podcast that the user has not listened to,
+/// fetched for all of them at once.
+async fn unplayed_podcast_episodes<C: ConnectionTrait>(
+    db: &C,
+    user_id: i32,
+    metadata_ids: Vec<i32>,
+) -> Result<HashMap<i32, usize>> {
+    if metadata_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let podcasts = Metadata::find()
+        .filter(metadata::Column::Id.is_in(metadata_ids.clone()))
+        .all(db)
+        .await?;
+    let seen: Vec<(i32, Option<SeenOrReviewExtraInformation>)> = Seen::find()
+        .select_only()
+        .column(seen::Column::MetadataId)
+        .column(seen::Column::ExtraInformation)
+        .filter(seen::Column::DeletedAt.is_null())
+        .filter(seen::Column::UserId.eq(user_id))
+        .filter(seen::Column::MetadataId.is_in(metadata_ids))
+        .filter(seen::Column::State.eq(SeenState::Completed))
+        .into_tuple()
+        .all(db)
+        .await?;
+    let mut played: HashMap<i32, HashSet<i32>> = HashMap::new();
+    for (metadata_id, extra_information) in seen {
+        if let Some(SeenOrReviewExtraInformation::Podcast(p)) = extra_information {
+            played.entry(metadata_id).or_default().insert(p.episode);
+        }
+    }
+    Ok(podcasts
+        .into_iter()
+        .filter_map(|m| match m.specifics {
+            MediaSpecifics::Podcast(p) => {
+                let played = played.remove(&m.id).unwrap_or_default();
+                let unplayed = p
+                    .episodes
+                    .iter()
+                    .filter(|e| !played.contains(&e.number))
+                    .count();
+                Some((m.id, unplayed))
+            }
+            _ => None,
+        })
+        .collect())
+}
+
+/// The number of seen items and reviews of each media item, leaving out the
+/// ones in the trash.
+async fn activity_counts<C: ConnectionTrait>(
+    db: &C,
+    metadata_ids: Vec<i32>,
+) -> Result<HashMap<i32, (u64, u64)>> {
+    let seen: Vec<(i32, i64)> = Seen::find()
+        .select_only()
+        .column(seen::Column::MetadataId)
+        .column_as(Expr::col(seen::Column::Id).count(), "count")
+        .filter(seen::Column::DeletedAt.is_null())
+        .filter(seen::Column::MetadataId.is_in(metadata_ids.clone()))
+        .group_by(seen::Column::MetadataId)
+        .into_tuple()
+        .all(db)
+        .await?;
+    let reviews: Vec<(i32, i64)> = Review::find()
+        .select_only()
+        .column(review::Column::MetadataId)
+        .column_as(Expr::col(review::Column::Id).count(), "count")
+        .filter(review::Column::DeletedAt.is_null())
+        .filter(review::Column::MetadataId.is_in(metadata_ids))
+        .group_by(review::Column::MetadataId)
+        .into_tuple()
+        .all(db)
+        .await?;
+    let mut counts: HashMap<i32, (u64, u64)> = HashMap::new();
+    for (metadata_id, count) in seen {
+        counts.entry(metadata_id).or_default().0 = count as u64;
+    }
+    for (metadata_id, count) in reviews {
+        counts.entry(metadata_id).or_default().1 = count as u64;
+    }
+    Ok(counts)
+}
+
+/// Join the pairs of media sharing a title into groups, along with the title.
+/// A group where two items come from the same provider is left out, since a
+/// provider lists distinct items that happen to have the same title.
+fn group_duplicate_pairs(
+    pairs: Vec<PossibleDuplicatePair>,
+    sources: &HashMap<i32, MetadataSource>,
+) -> Vec<(String, Vec<i32>)> {
+    let mut groups: Vec<(String, Vec<i32>)> = vec![];
+    for pair in pairs {
+        let ids = [pair.first_id, pair.second_id];
+        match groups
+            .iter()
+            .position(|(_, g)| ids.iter().any(|id| g.contains(id)))
+        {
+            Some(idx) => {
+                let group = &mut groups[idx].1;
+                for id in ids {
+                    if !group.contains(&id) {
+                        group.push(id);
+                    }
+                }
+            }
+            None => groups.push((pair.title, ids.to_vec())),
+        }
+    }
+    groups
+        .into_iter()
+        .filter(|(_, ids)| ids.iter().map(|id| sources.get(id)).all_unique())
+        .map(|(title, ids)| (title, ids.into_iter().sorted().collect()))
+        .collect()
+}
+
+/// The metadata that no user has any activity for. Being part of a collection
+/// counts as activity, so that items which are only on a watchlist are kept.
+fn unused_metadata() -> Select<Metadata> {
+    Metadata::find()
+        .filter(
+            metadata::Column::Id.not_in_subquery(
+                Query::select()
+                    .column(user_to_metadata::Column::MetadataId)
+                    .from(UserToMetadata)
+                    .to_owned(),
+            ),
+        )
+        .filter(
+            metadata::Column::Id.not_in_subquery(
+                Query::select()
+                    .column(metadata_to_collection::Column::MetadataId)
+                    .from(MetadataToCollection)
+                    .to_owned(),
+            ),
+        )
+        .filter(
+            metadata::Column::Id.not_in_subquery(
+                Query::select()
+                    .column(seen::Column::MetadataId)
+                    .from(Seen)
+                    .to_owned(),
+            ),
+        )
+        .filter(
+            metadata::Column::Id.not_in_subquery(
+                Query::select()
+                    .column(review::Column::MetadataId)
+                    .from(Review)
+                    .to_owned(),
+            ),
+        )
+        .filter(
+            metadata::Column::Id.not_in_subquery(
+                Query::select()
+                    .column(media_reminder::Column::MetadataId)
+                    .from(MediaReminder)
+                    .to_owned(),
+            ),
+        )
+}
+
+/// The normalized titles a media item is known by, starting with its main title.
+/// Each one is paired with whether it is an alternative title.
+fn metadata_titles(title: &str, alternative_titles: &[String]) -> Vec<(String, bool)> {
+    let normalize = |t: &str| t.trim().to_lowercase();
+    let main = normalize(title);
+    let mut titles = vec![];
+    if !main.is_empty() {
+        titles.push((main.clone(), false));
+    }
+    titles.extend(
+        alternative_titles
+            .iter()
+            .map(|t| normalize(t))
+            .filter(|t| !t.is_empty() && *t != main)
+            .unique()
+            .map(|t| (t, true)),
+    );
+    titles
+}
+
+/// The providers that the media of an import source is committed with. The
+/// sources that can have media from any provider are not listed.
+fn import_source_providers(source: MediaImportSource) -> Vec<(MetadataLot, MetadataSource)> {
+    match source {
+        MediaImportSource::MediaTracker => vec![
+            (MetadataLot::Book, MetadataSource::Openlibrary),
+            (MetadataLot::Movie, MetadataSource::Tmdb),
+            (MetadataLot::Show, MetadataSource::Tmdb),
+            (MetadataLot::VideoGame, MetadataSource::Igdb),
+            (MetadataLot::AudioBook, MetadataSource::Audible),
+        ],
+        MediaImportSource::Trakt | MediaImportSource::JustWatch => vec![
+            (MetadataLot::Movie, MetadataSource::Tmdb),
+            (MetadataLot::Show, MetadataSource::Tmdb),
+        ],
+        MediaImportSource::Movary => vec![(MetadataLot::Movie, MetadataSource::Tmdb)],
+        MediaImportSource::Goodreads | MediaImportSource::StoryGraph => {
+            vec![(MetadataLot::Book, MetadataSource::Openlibrary)]
+        }
+        MediaImportSource::MediaJson | MediaImportSource::RyotSqlite | MediaImportSource::Xlsx => {
+            vec![]
+        }
+    }
+}
+
+/// The date at the given time in a timezone, or in UTC if the timezone is not
+/// known.
+fn date_in_timezone(at: DateTimeUtc, timezone: &str) -> NaiveDate {
+    match timezone.parse::<Tz>() {
+        Ok(tz) => at.with_timezone(&tz).date_naive(),
+        Err(_) => {
+            tracing::warn!("Unknown timezone {timezone:?}, using UTC");
+            at.date_naive()
+        }
+    }
+}
+
+/// The secrets stored on a user, which are encrypted when a key is configured.
+fn user_secrets_mut(user: &mut user::Model) -> Vec<&mut String> {
+    let mut secrets = vec![];
+    if let Some(integrations) = user.yank_integrations.as_mut() {
+        for integration in integrations.0.iter_mut() {
+            secrets.extend(integration.settings.secrets_mut());
+        }
+    }
+    for integration in user.sink_integrations.0.iter_mut() {
+        secrets.extend(integration.settings.secrets_mut());
+    }
+    secrets.extend(user.preferences.secrets_mut());
+    secrets
+}
+
+/// The media in the user's library matching a bulk filter, with the collection
+/// of the filter already resolved to its id.
+fn bulk_media_filter(
+    user_id: i32,
+    filter: &BulkMediaFilter,
+    collection_id: Option<i32>,
+) -> Select<UserToMetadata> {
+    let mut query = UserToMetadata::find()
+        .select_only()
+        .column(user_to_metadata::Column::MetadataId)
+        .filter(user_to_metadata::Column::UserId.eq(user_id))
+        .filter(user_to_metadata::Column::Ignored.eq(false));
+    if let Some(lot) = filter.lot {
+        query = query.filter(
+            user_to_metadata::Column::MetadataId.in_subquery(
+                Query::select()
+                    .column(metadata::Column::Id)
+                    .from(Metadata)
+                    .and_where(metadata::Column::Lot.eq(lot))
+                    .to_owned(),
+            ),
+        );
+    }
+    if let Some(collection_id) = collection_id {
+        query = query.filter(
+            user_to_metadata::Column::MetadataId.in_subquery(
+                Query::select()
+                    .column(metadata_to_collection::Column::MetadataId)
+                    .from(MetadataToCollection)
+                    .and_where(metadata_to_collection::Column::CollectionId.eq(collection_id))
+                    .to_owned(),
+            ),
+        );
+    }
+    if let Some(state) = filter.seen_state {
+        query = query.filter(
+            user_to_metadata::Column::MetadataId.in_subquery(
+                Query::select()
+                    .column(seen::Column::MetadataId)
+                    .from(Seen)
+                    .and_where(seen::Column::UserId.eq(user_id))
+                    .and_where(seen::Column::State.eq(state))
+                    .and_where(seen::Column::DeletedAt.is_null())
+                    .to_owned(),
+            ),
+        );
+    }
+    if filter.min_rating.is_some() || filter.max_rating.is_some() {
+        let mut reviews = Query::select()
+            .column(review::Column::MetadataId)
+            .from(Review)
+            .and_where(review::Column::UserId.eq(user_id))
+            .and_where(review::Column::Rating.is_not_null())
+            .and_where(review::Column::DeletedAt.is_null())
+            .to_owned();
+        if let Some(min) = filter.min_rating {
+            reviews.and_where(review::Column::Rating.gte(min));
+        }
+        if let Some(max) = filter.max_rating {
+            reviews.and_where(review::Column::Rating.lte(max));
+        }
+        query = query.filter(user_to_metadata::Column::MetadataId.in_subquery(reviews));
+    }
+    query
+}
+
+/// Permanently remove the seen items and reviews that were deleted before the
+/// given time, returning how many of each were removed.
+async fn purge_trash_before<C: ConnectionTrait>(db: &C, before: DateTimeUtc) -> Result<(u64, u64)> {
+    let seen = Seen::delete_many()
+        .filter(seen::Column::DeletedAt.lt(before))
+        .exec(db)
+        .await?
+        .rows_affected;
+    let reviews = Review::delete_many()
+        .filter(review::Column::DeletedAt.lt(before))
+        .exec(db)
+        .await?
+        .rows_affected;
+    Ok((seen, reviews))
+}
+
+/// Get the ids of the metadata that the cleanup job removes.
+fn metadata_refresh_lease(metadata_id: i32) -> String {
+    format!("refresh-metadata-{metadata_id}")
+}
+
+/// Take the lease on refreshing a media item. Returns `false` if a refresh of
+/// it is already queued. The lease is released when the update job has run, and
+/// expires in case the job was lost.
+async fn lease_metadata_refresh<C: ConnectionTrait>(
+    db: &C,
+    metadata_id: i32,
+    holder: &str,
+) -> Result<bool> {
+    let name = metadata_refresh_lease(metadata_id);
+    let now = Utc::now();
+    JobLease::delete_many()
+        .filter(job_lease::Column::Name.eq(&name))
+        .filter(job_lease::Column::ExpiresOn.lt(now))
+        .exec(db)
+        .await?;
+    // DEV: the primary key makes sure a refresh is only queued once
+    let lease = job_lease::ActiveModel {
+        name: ActiveValue::Set(name),
+        holder: ActiveValue::Set(holder.to_owned()),
+        acquired_on: ActiveValue::Set(now),
+        expires_on: ActiveValue::Set(now + ChronoDuration::days(METADATA_REFRESH_LEASE_DAYS)),
+    };
+    Ok(lease.insert(db).await.is_ok())
+}
+
+async fn unused_metadata_ids<C: ConnectionTrait>(db: &C) -> Result<Vec<i32>> {
+    let ids = unused_metadata()
+        .select_only()
+        .column(metadata::Column::Id)
+        .into_tuple()
+        .all(db)
+        .await?;
+    Ok(ids)
+}
+
+/// The part that follows the last one the user has completed, if they have not
+/// completed it yet.
+fn next_group_part(parts: &[MediaGroupPart]) -> Option<usize> {
+    parts
+        .iter()
+        .rposition(|p| p.progress == Some(100))
+        .map(|idx| idx + 1)
+        .filter(|idx| *idx < parts.len())
+}
+
+/// Describe what changed between the stored metadata and the details that were
+/// just fetched from the provider.
+fn metadata_changes(old: &metadata::Model, new: &MediaDetails, today: NaiveDate) -> Vec<String> {
+    let mut changes = vec![];
+    match (&old.specifics, &new.specifics) {
+        (MediaSpecifics::Show(old_spec), MediaSpecifics::Show(new_spec)) => {
+            for season in new_spec.seasons.iter() {
+                let previous = old_spec
+                    .seasons
+                    .iter()
+                    .find(|s| s.season_number == season.season_number);
+                match previous {
+                    None => changes.push(format!(
+                        "S{:02} added, {} episodes",
+                        season.season_number,
+                        season.episodes.len()
+                    )),
+                    Some(p) if season.episodes.len() > p.episodes.len() => changes.push(format!(
+                        "S{:02}: {} episodes added",
+                        season.season_number,
+                        season.episodes.len() - p.episodes.len()
+                    )),
+                    _ => {}
+                }
+            }
+        }
+        (MediaSpecifics::Podcast(old_spec), MediaSpecifics::Podcast(new_spec)) => {
+            let added = new_spec
+                .episodes
+                .iter()
+                .filter(|e| !old_spec.episodes.iter().any(|o| o.id == e.id))
+                .map(|e| e.title.as_str())
+                .collect_vec();
+            if !added.is_empty() {
+                changes.push(format!(
+                    "{} episodes added: {}",
+                    added.len(),
+                    added.join(", ")
+                ));
+            }
+        }
+        _ => {}
+    }
+    if let Some(date) = new.publish_date {
+        match old.publish_date {
+            Some(previous) if previous != date => {
+                changes.push(format!("Release date changed from {previous} to {date}"))
+            }
+            None => changes.push(format!("Release date set to {date}")),
+            _ => {}
+        }
+        let was_announced = old
+            .publish_date
+            .map_or(false, |d| d > old.last_updated_on.date_naive());
+        if was_announced && date <= today {
+            changes.push(format!("Released on {date}"));
+        }
+    }
+    changes
+}
+
+/// Whether a podcast should be refreshed. Every failure in a row doubles the
+/// time to wait since the last successful refresh.
+fn is_refresh_due(
+    last_updated_on: DateTimeUtc,
+    failures: i32,
+    every: ChronoDuration,
+    now: DateTimeUtc,
+) -> bool {
+    if failures <= 0 {
+        return true;
+    }
+    let backoff = every * 2_i32.pow(failures.min(MAX_REFRESH_BACKOFF) as u32);
+    // DEV: the last refresh finished a little after the cron fired, so half an
+    // interval of slack keeps it from missing its slot
+    now - last_updated_on + every / 2 >= backoff
+}
+
 fn modify_seen_elements(all_seen: &mut [seen::Model]) {
     all_seen.iter_mut().for_each(|s| {
         if let Some(i) = s.extra_information.as_ref() {
@@ -3700,3 +7860,821 @@ fn modify_seen_elements(all_seen: &mut [seen::Model]) {
         }
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use rstest::rstest;
+    use sea_orm::Database;
+    use sea_orm_migration::MigratorTrait;
+
+    use super::*;
+    use crate::{
+        migrator::Migrator,
+        miscellaneous::{MetadataCreators, MetadataImages},
+        models::media::{PodcastEpisode, ShowEpisode, ShowSeason},
+        users::{UserPreferences, UserSinkIntegrations},
+    };
+
+    async fn insert_metadata(db: &DatabaseConnection, identifier: &str) -> i32 {
+        metadata::ActiveModel {
+            created_on: ActiveValue::Set(Utc::now()),
+            last_updated_on: ActiveValue::Set(Utc::now()),
+            lot: ActiveValue::Set(MetadataLot::Movie),
+            source: ActiveValue::Set(MetadataSource::Tmdb),
+            title: ActiveValue::Set(identifier.to_owned()),
+            identifier: ActiveValue::Set(identifier.to_owned()),
+            images: ActiveValue::Set(MetadataImages::default()),
+            creators: ActiveValue::Set(MetadataCreators::default()),
+            specifics: ActiveValue::Set(MediaSpecifics::Unknown),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .unwrap()
+        .id
+    }
+
+    #[tokio::test]
+    async fn test_metadata_refresh_is_leased_once() {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        Migrator::up(&db, None).await.unwrap();
+        assert!(lease_metadata_refresh(&db, 1, "a").await.unwrap());
+        assert!(!lease_metadata_refresh(&db, 1, "a").await.unwrap());
+        assert!(!lease_metadata_refresh(&db, 1, "b").await.unwrap());
+        assert!(lease_metadata_refresh(&db, 2, "a").await.unwrap());
+        JobLease::update_many()
+            .col_expr(
+                job_lease::Column::ExpiresOn,
+                Expr::value(Utc::now() - ChronoDuration::minutes(1)),
+            )
+            .filter(job_lease::Column::Name.eq(metadata_refresh_lease(1)))
+            .exec(&db)
+            .await
+            .unwrap();
+        assert!(lease_metadata_refresh(&db, 1, "b").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_unused_metadata_ids_keeps_collection_items() {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        Migrator::up(&db, None).await.unwrap();
+        let user = user::ActiveModel {
+            name: ActiveValue::Set("diana".to_owned()),
+            password: ActiveValue::Set("password".to_owned()),
+            lot: ActiveValue::Set(UserLot::Normal),
+            preferences: ActiveValue::Set(UserPreferences::default()),
+            sink_integrations: ActiveValue::Set(UserSinkIntegrations(vec![])),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+        let collection = collection::ActiveModel {
+            created_on: ActiveValue::Set(Utc::now()),
+            name: ActiveValue::Set("Watchlist".to_owned()),
+            user_id: ActiveValue::Set(user.id),
+            visibility: ActiveValue::Set(Visibility::Private),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+        let in_collection = insert_metadata(&db, "27205").await;
+        let unused = insert_metadata(&db, "157336").await;
+        metadata_to_collection::ActiveModel {
+            metadata_id: ActiveValue::Set(in_collection),
+            collection_id: ActiveValue::Set(collection.id),
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+        assert_eq!(unused_metadata_ids(&db).await.unwrap(), vec![unused]);
+    }
+
+    #[tokio::test]
+    async fn test_unplayed_podcast_episodes() {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        Migrator::up(&db, None).await.unwrap();
+        let user = user::ActiveModel {
+            name: ActiveValue::Set("diana".to_owned()),
+            password: ActiveValue::Set("password".to_owned()),
+            lot: ActiveValue::Set(UserLot::Normal),
+            preferences: ActiveValue::Set(UserPreferences::default()),
+            sink_integrations: ActiveValue::Set(UserSinkIntegrations(vec![])),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+        let mut podcasts = vec![];
+        for identifier in ["first", "second"] {
+            let mut podcast: metadata::ActiveModel =
+                Metadata::find_by_id(insert_metadata(&db, identifier).await)
+                    .one(&db)
+                    .await
+                    .unwrap()
+                    .unwrap()
+                    .into();
+            podcast.lot = ActiveValue::Set(MetadataLot::Podcast);
+            podcast.specifics = ActiveValue::Set(MediaSpecifics::Podcast(PodcastSpecifics {
+                episodes: (1..=3)
+                    .map(|number| PodcastEpisode {
+                        number,
+                        ..Default::default()
+                    })
+                    .collect(),
+                total_episodes: 3,
+            }));
+            podcasts.push(podcast.update(&db).await.unwrap().id);
+        }
+        for (episode, state) in [
+            (1, SeenState::Completed),
+            (1, SeenState::Completed),
+            (2, SeenState::InProgress),
+        ] {
+            seen::ActiveModel {
+                progress: ActiveValue::Set(100),
+                last_updated_on: ActiveValue::Set(Utc::now()),
+                user_id: ActiveValue::Set(user.id),
+                metadata_id: ActiveValue::Set(podcasts[0]),
+                state: ActiveValue::Set(state),
+                plays: ActiveValue::Set(1),
+                provenance: ActiveValue::Set(Provenance::Manual),
+                date_precision: ActiveValue::Set(SeenDatePrecision::Day),
+                extra_information: ActiveValue::Set(Some(SeenOrReviewExtraInformation::Podcast(
+                    SeenPodcastExtraInformation { episode },
+                ))),
+                ..Default::default()
+            }
+            .insert(&db)
+            .await
+            .unwrap();
+        }
+        let unplayed = unplayed_podcast_episodes(&db, user.id, podcasts.clone())
+            .await
+            .unwrap();
+        assert_eq!(unplayed.get(&podcasts[0]), Some(&2));
+        assert_eq!(unplayed.get(&podcasts[1]), Some(&3));
+    }
+
+    #[tokio::test]
+    async fn test_merge_duplicate_activity() {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        Migrator::up(&db, None).await.unwrap();
+        let user = user::ActiveModel {
+            name: ActiveValue::Set("diana".to_owned()),
+            password: ActiveValue::Set("password".to_owned()),
+            lot: ActiveValue::Set(UserLot::Normal),
+            preferences: ActiveValue::Set(UserPreferences::default()),
+            sink_integrations: ActiveValue::Set(UserSinkIntegrations(vec![])),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+        let kept = insert_metadata(&db, "27205").await;
+        let merged = insert_metadata(&db, "tt1375666").await;
+        for deleted_at in [None, Some(Utc::now())] {
+            seen::ActiveModel {
+                progress: ActiveValue::Set(100),
+                last_updated_on: ActiveValue::Set(Utc::now()),
+                user_id: ActiveValue::Set(user.id),
+                metadata_id: ActiveValue::Set(kept),
+                state: ActiveValue::Set(SeenState::Completed),
+                plays: ActiveValue::Set(1),
+                provenance: ActiveValue::Set(Provenance::Manual),
+                date_precision: ActiveValue::Set(SeenDatePrecision::Day),
+                deleted_at: ActiveValue::Set(deleted_at),
+                ..Default::default()
+            }
+            .insert(&db)
+            .await
+            .unwrap();
+        }
+        review::ActiveModel {
+            posted_on: ActiveValue::Set(Utc::now()),
+            rating: ActiveValue::Set(Some(Decimal::from(80))),
+            visibility: ActiveValue::Set(Visibility::Private),
+            spoiler: ActiveValue::Set(false),
+            user_id: ActiveValue::Set(user.id),
+            metadata_id: ActiveValue::Set(merged),
+            provenance: ActiveValue::Set(Provenance::Manual),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+        let counts = activity_counts(&db, vec![kept, merged]).await.unwrap();
+        assert_eq!(counts.get(&kept), Some(&(1, 0)));
+        assert_eq!(counts.get(&merged), Some(&(0, 1)));
+
+        let txn = db.begin().await.unwrap();
+        merge_metadata_rows(&txn, merged, kept).await.unwrap();
+        txn.commit().await.unwrap();
+        let counts = activity_counts(&db, vec![kept, merged]).await.unwrap();
+        assert_eq!(counts.get(&kept), Some(&(1, 1)));
+        assert_eq!(counts.get(&merged), None);
+        assert!(Metadata::find_by_id(merged)
+            .one(&db)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_trashed_seen_and_reviews_do_not_match_bulk_filters() {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        Migrator::up(&db, None).await.unwrap();
+        let user = user::ActiveModel {
+            name: ActiveValue::Set("diana".to_owned()),
+            password: ActiveValue::Set("password".to_owned()),
+            lot: ActiveValue::Set(UserLot::Normal),
+            preferences: ActiveValue::Set(UserPreferences::default()),
+            sink_integrations: ActiveValue::Set(UserSinkIntegrations(vec![])),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+        let metadata_id = insert_metadata(&db, "27205").await;
+        associate_user_with_metadata(&user.id, &metadata_id, &db)
+            .await
+            .unwrap();
+        let seen = seen::ActiveModel {
+            progress: ActiveValue::Set(100),
+            last_updated_on: ActiveValue::Set(Utc::now()),
+            user_id: ActiveValue::Set(user.id),
+            metadata_id: ActiveValue::Set(metadata_id),
+            state: ActiveValue::Set(SeenState::Completed),
+            plays: ActiveValue::Set(1),
+            provenance: ActiveValue::Set(Provenance::Manual),
+            date_precision: ActiveValue::Set(SeenDatePrecision::Day),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+        let review = review::ActiveModel {
+            posted_on: ActiveValue::Set(Utc::now()),
+            rating: ActiveValue::Set(Some(Decimal::from(80))),
+            visibility: ActiveValue::Set(Visibility::Private),
+            spoiler: ActiveValue::Set(false),
+            user_id: ActiveValue::Set(user.id),
+            metadata_id: ActiveValue::Set(metadata_id),
+            provenance: ActiveValue::Set(Provenance::Manual),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+        let filter = BulkMediaFilter {
+            lot: None,
+            collection: None,
+            seen_state: Some(SeenState::Completed),
+            min_rating: Some(Decimal::from(50)),
+            max_rating: None,
+        };
+        async fn matching(
+            db: &DatabaseConnection,
+            user_id: i32,
+            filter: &BulkMediaFilter,
+        ) -> Vec<i32> {
+            bulk_media_filter(user_id, filter, None)
+                .into_tuple()
+                .all(db)
+                .await
+                .unwrap()
+        }
+        async fn set_deleted_at(
+            db: &DatabaseConnection,
+            seen: &seen::Model,
+            review: &review::Model,
+            deleted_at: Option<DateTimeUtc>,
+        ) {
+            let mut seen: seen::ActiveModel = seen.clone().into();
+            seen.deleted_at = ActiveValue::Set(deleted_at);
+            seen.update(db).await.unwrap();
+            let mut review: review::ActiveModel = review.clone().into();
+            review.deleted_at = ActiveValue::Set(deleted_at);
+            review.update(db).await.unwrap();
+        }
+        assert_eq!(matching(&db, user.id, &filter).await, vec![metadata_id]);
+
+        set_deleted_at(&db, &seen, &review, Some(Utc::now())).await;
+        assert_eq!(matching(&db, user.id, &filter).await, vec![]);
+
+        set_deleted_at(&db, &seen, &review, None).await;
+        assert_eq!(matching(&db, user.id, &filter).await, vec![metadata_id]);
+
+        let purge_before = Utc::now() - ChronoDuration::days(TRASH_RETENTION_DAYS);
+        assert_eq!(purge_trash_before(&db, purge_before).await.unwrap(), (0, 0));
+        set_deleted_at(
+            &db,
+            &seen,
+            &review,
+            Some(purge_before - ChronoDuration::days(1)),
+        )
+        .await;
+        assert_eq!(purge_trash_before(&db, purge_before).await.unwrap(), (1, 1));
+        assert!(Seen::find_by_id(seen.id).one(&db).await.unwrap().is_none());
+        assert!(Review::find_by_id(review.id)
+            .one(&db)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[rstest]
+    #[case("UTC", 1)]
+    #[case("Asia/Kolkata", 2)]
+    #[case("America/New_York", 1)]
+    #[case("Nowhere/Unknown", 1)]
+    fn test_date_in_timezone(#[case] timezone: &str, #[case] day: u32) {
+        let at = Utc.with_ymd_and_hms(2023, 1, 1, 22, 0, 0).unwrap();
+        assert_eq!(
+            date_in_timezone(at, timezone),
+            NaiveDate::from_ymd_opt(2023, 1, day).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_user_secrets() {
+        let mut preferences = UserPreferences::default();
+        preferences.notifications.webhook_url = Some("https://ntfy.sh/ryot".to_owned());
+        let mut user = user::Model {
+            id: 1,
+            name: "diana".to_owned(),
+            email: None,
+            password: "password".to_owned(),
+            lot: UserLot::Normal,
+            preferences,
+            yank_integrations: Some(UserYankIntegrations(vec![UserYankIntegration {
+                id: 1,
+                settings: UserYankIntegrationSetting::Audiobookshelf {
+                    base_url: "http://abs".to_owned(),
+                    token: "token".to_owned(),
+                },
+                timestamp: Utc::now(),
+            }])),
+            sink_integrations: UserSinkIntegrations(vec![UserSinkIntegration {
+                id: 1,
+                settings: UserSinkIntegrationSetting::Jellyfin {
+                    slug: "slug".to_owned(),
+                },
+                timestamp: Utc::now(),
+            }]),
+        };
+        let secrets = user_secrets_mut(&mut user)
+            .into_iter()
+            .map(|s| s.clone())
+            .collect_vec();
+        assert_eq!(secrets, vec!["token", "slug", "https://ntfy.sh/ryot"]);
+    }
+
+    #[test]
+    fn test_metadata_titles() {
+        let alternative_titles = vec![
+            "Spirited Away ".to_owned(),
+            "Sen to Chihiro".to_owned(),
+            "sen to chihiro".to_owned(),
+            " ".to_owned(),
+        ];
+        assert_eq!(
+            metadata_titles(" Spirited Away", &alternative_titles),
+            vec![
+                ("spirited away".to_owned(), false),
+                ("sen to chihiro".to_owned(), true),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_possible_duplicate_pairs_query() {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        Migrator::up(&db, None).await.unwrap();
+        let mut ids = vec![];
+        for (identifier, title, year, alias) in [
+            ("1", "dune", Some(2021), false),
+            ("2", "dune", Some(2021), false),
+            ("3", "dune", Some(1984), false),
+            ("4", "dune", None, true),
+        ] {
+            let id = insert_metadata(&db, identifier).await;
+            metadata_title::ActiveModel {
+                metadata_id: ActiveValue::Set(id),
+                title: ActiveValue::Set(title.to_owned()),
+                lot: ActiveValue::Set(MetadataLot::Movie),
+                publish_year: ActiveValue::Set(year),
+                alias: ActiveValue::Set(alias),
+            }
+            .insert(&db)
+            .await
+            .unwrap();
+            ids.push(id);
+        }
+        let query = MiscellaneousService::possible_duplicate_pairs_query(MetadataLot::Movie)
+            .order_by(Alias::new("first_id"), Order::Asc)
+            .order_by(Alias::new("second_id"), Order::Asc)
+            .to_owned();
+        let pairs =
+            PossibleDuplicatePair::find_by_statement(db.get_database_backend().build(&query))
+                .all(&db)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|p| (p.first_id, p.second_id))
+                .collect_vec();
+        assert_eq!(
+            pairs,
+            vec![
+                (ids[0], ids[1]),
+                (ids[0], ids[3]),
+                (ids[1], ids[3]),
+                (ids[2], ids[3])
+            ]
+        );
+    }
+
+    fn show(seasons: &[(i32, usize)]) -> MediaSpecifics {
+        MediaSpecifics::Show(ShowSpecifics {
+            seasons: seasons
+                .iter()
+                .map(|(number, episodes)| ShowSeason {
+                    season_number: *number,
+                    episodes: vec![ShowEpisode::default(); *episodes],
+                    ..Default::default()
+                })
+                .collect(),
+        })
+    }
+
+    fn details(specifics: MediaSpecifics, publish_date: Option<NaiveDate>) -> MediaDetails {
+        MediaDetails {
+            identifier: "1399".to_owned(),
+            title: "Game of Thrones".to_owned(),
+            source: MetadataSource::Tmdb,
+            description: None,
+            lot: MetadataLot::Show,
+            creators: vec![],
+            genres: vec![],
+            images: vec![],
+            publish_year: None,
+            publish_date,
+            specifics,
+            groups: vec![],
+            alternative_titles: vec![],
+        }
+    }
+
+    #[test]
+    fn test_metadata_changes() {
+        let date = |d| NaiveDate::from_ymd_opt(2023, 7, d).unwrap();
+        let old = metadata::Model {
+            last_updated_on: convert_naive_to_utc(date(1)),
+            publish_date: Some(date(20)),
+            specifics: show(&[(1, 10), (2, 8)]),
+            ..Default::default()
+        };
+        let new = details(show(&[(1, 10), (2, 10), (3, 8)]), Some(date(25)));
+        assert_eq!(
+            metadata_changes(&old, &new, date(27)),
+            vec![
+                "S02: 2 episodes added",
+                "S03 added, 8 episodes",
+                "Release date changed from 2023-07-20 to 2023-07-25",
+                "Released on 2023-07-25",
+            ]
+        );
+        let unchanged = details(show(&[(1, 10), (2, 8)]), Some(date(20)));
+        assert!(metadata_changes(&old, &unchanged, date(5)).is_empty());
+    }
+
+    #[rstest]
+    #[case(vec![], None)]
+    #[case(vec![Some(100), None, None], Some(1))]
+    #[case(vec![Some(100), Some(40), Some(100), None], Some(3))]
+    #[case(vec![None, Some(100)], None)]
+    #[case(vec![None, Some(50)], None)]
+    fn test_next_group_part(#[case] progress: Vec<Option<i32>>, #[case] expected: Option<usize>) {
+        let parts = progress
+            .into_iter()
+            .enumerate()
+            .map(|(idx, progress)| MediaGroupPart {
+                item: MediaSearchItem {
+                    identifier: idx.to_string(),
+                    lot: MetadataLot::Movie,
+                    title: format!("Part {idx}"),
+                    image: None,
+                    publish_year: None,
+                    popularity: None,
+                },
+                metadata_id: None,
+                progress,
+            })
+            .collect_vec();
+        assert_eq!(next_group_part(&parts), expected);
+    }
+
+    #[test]
+    fn test_rank_recommendations() {
+        let rec = |identifier: &str, seed: &str| MediaRecommendation {
+            item: MediaSearchItem {
+                identifier: identifier.to_owned(),
+                lot: MetadataLot::Movie,
+                title: identifier.to_owned(),
+                image: None,
+                publish_year: None,
+                popularity: None,
+            },
+            source: MetadataSource::Tmdb,
+            because_of: vec![seed.to_owned()],
+        };
+        let library = HashSet::from([(MetadataSource::Tmdb, "owned".to_owned())]);
+        let ranked = rank_recommendations(
+            vec![
+                (rec("a", "Dune"), 2),
+                (rec("owned", "Dune"), 5),
+                (rec("b", "Dune"), 2),
+                (rec("b", "Arrival"), 2),
+                (rec("c", "Arrival"), 1),
+            ],
+            &library,
+        );
+        let identifiers = ranked
+            .iter()
+            .map(|r| r.item.identifier.as_str())
+            .collect_vec();
+        assert_eq!(identifiers, vec!["b", "a", "c"]);
+        assert_eq!(ranked[0].because_of, vec!["Dune", "Arrival"]);
+    }
+
+    #[rstest]
+    #[case(Some(2), true)]
+    #[case(Some(3), false)]
+    #[case(None, false)]
+    fn test_seen_extra_information_podcast(#[case] episode: Option<i32>, #[case] valid: bool) {
+        let meta = metadata::Model {
+            lot: MetadataLot::Podcast,
+            specifics: MediaSpecifics::Podcast(PodcastSpecifics {
+                episodes: (1..=2)
+                    .map(|number| PodcastEpisode {
+                        number,
+                        ..Default::default()
+                    })
+                    .collect(),
+                total_episodes: 2,
+            }),
+            ..Default::default()
+        };
+        let input = ProgressUpdateInput {
+            metadata_id: 1,
+            progress: Some(100),
+            date: None,
+            show_season_number: None,
+            show_episode_number: None,
+            podcast_episode_number: episode,
+            change_state: None,
+            plays: None,
+            import: None,
+        };
+        assert_eq!(seen_extra_information(&meta, &input).is_ok(), valid);
+    }
+
+    #[test]
+    fn test_seen_batch_dedup() {
+        let key = |episode| ProgressUpdateCache {
+            user_id: 1,
+            metadata_id: 1,
+            show_season_number: None,
+            show_episode_number: None,
+            podcast_episode_number: Some(episode),
+        };
+        let mut batch = SeenBatch::new(1, 2);
+        assert!(!batch.push(key(1), seen::ActiveModel::default()));
+        assert!(batch.contains(&key(1)));
+        assert!(!batch.contains(&key(2)));
+        assert!(batch.contains_metadata(1));
+        assert!(batch.push(key(2), seen::ActiveModel::default()));
+        assert_eq!(SeenBatch::new(1, 0).size, 1);
+    }
+
+    #[tokio::test]
+    async fn test_insert_seen_batch() {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        Migrator::up(&db, None).await.unwrap();
+        let user = user::ActiveModel {
+            name: ActiveValue::Set("diana".to_owned()),
+            password: ActiveValue::Set("password".to_owned()),
+            lot: ActiveValue::Set(UserLot::Normal),
+            preferences: ActiveValue::Set(UserPreferences::default()),
+            sink_integrations: ActiveValue::Set(UserSinkIntegrations(vec![])),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+        let metadata_ids = vec![
+            insert_metadata(&db, "27205").await,
+            insert_metadata(&db, "603").await,
+        ];
+        let rows = [metadata_ids[0], metadata_ids[0], metadata_ids[1]]
+            .map(|metadata_id| seen::ActiveModel {
+                progress: ActiveValue::Set(100),
+                last_updated_on: ActiveValue::Set(Utc::now()),
+                user_id: ActiveValue::Set(user.id),
+                metadata_id: ActiveValue::Set(metadata_id),
+                state: ActiveValue::Set(SeenState::Completed),
+                plays: ActiveValue::Set(1),
+                provenance: ActiveValue::Set(Provenance::Import),
+                date_precision: ActiveValue::Set(SeenDatePrecision::Day),
+                ..Default::default()
+            })
+            .to_vec();
+        let txn = db.begin().await.unwrap();
+        insert_seen_batch(&txn, user.id, rows, &metadata_ids)
+            .await
+            .unwrap();
+        txn.commit().await.unwrap();
+        assert_eq!(Seen::find().count(&db).await.unwrap(), 3);
+        let associated = UserToMetadata::find()
+            .filter(user_to_metadata::Column::UserId.eq(user.id))
+            .count(&db)
+            .await
+            .unwrap();
+        assert_eq!(associated, 2);
+    }
+
+    #[test]
+    fn test_metadata_changes_podcast_episodes() {
+        let podcast = |ids: &[&str]| {
+            MediaSpecifics::Podcast(PodcastSpecifics {
+                episodes: ids
+                    .iter()
+                    .map(|id| PodcastEpisode {
+                        id: id.to_string(),
+                        title: format!("Episode {id}"),
+                        ..Default::default()
+                    })
+                    .collect(),
+                total_episodes: ids.len() as i32,
+            })
+        };
+        let old = metadata::Model {
+            specifics: podcast(&["a", "b"]),
+            ..Default::default()
+        };
+        let today = NaiveDate::from_ymd_opt(2023, 8, 1).unwrap();
+        let new = details(podcast(&["b", "c", "d"]), None);
+        assert_eq!(
+            metadata_changes(&old, &new, today),
+            vec!["2 episodes added: Episode c, Episode d"]
+        );
+        let new = details(podcast(&["a", "b"]), None);
+        assert!(metadata_changes(&old, &new, today).is_empty());
+    }
+
+    #[rstest]
+    #[case(0, 1, true)]
+    #[case(1, 2, true)]
+    #[case(2, 2, false)]
+    #[case(2, 4, true)]
+    #[case(9, 16, false)]
+    #[case(9, 32, true)]
+    fn test_is_refresh_due(#[case] failures: i32, #[case] intervals: i32, #[case] due: bool) {
+        let every = ChronoDuration::hours(6);
+        let last_updated_on = Utc::now();
+        // DEV: the cron fires a little before the previous refresh is an
+        // exact number of intervals old
+        let now = last_updated_on + every * intervals - ChronoDuration::minutes(5);
+        assert_eq!(is_refresh_due(last_updated_on, failures, every, now), due);
+    }
+
+    #[rstest]
+    #[case(92, 95, 92)]
+    #[case(95, 95, 100)]
+    #[case(97, 95, 100)]
+    #[case(92, 90, 100)]
+    #[case(50, 0, 100)]
+    #[case(99, 150, 99)]
+    fn test_apply_completion_threshold(
+        #[case] progress: i32,
+        #[case] threshold: i32,
+        #[case] expected: i32,
+    ) {
+        assert_eq!(apply_completion_threshold(progress, threshold), expected);
+    }
+
+    #[test]
+    fn test_group_duplicate_pairs() {
+        let pair = |first_id: i32, second_id: i32, title: &str| PossibleDuplicatePair {
+            first_id,
+            second_id,
+            title: title.to_owned(),
+        };
+        let sources = HashMap::from_iter([
+            (1, MetadataSource::Openlibrary),
+            (2, MetadataSource::GoogleBooks),
+            (3, MetadataSource::Audible),
+            (4, MetadataSource::Openlibrary),
+            (5, MetadataSource::Openlibrary),
+        ]);
+        let groups = group_duplicate_pairs(
+            vec![
+                pair(1, 2, "dune"),
+                pair(1, 3, "dune"),
+                pair(2, 3, "dune"),
+                pair(4, 5, "emma"),
+            ],
+            &sources,
+        );
+        assert_eq!(groups, vec![("dune".to_owned(), vec![1, 2, 3])]);
+    }
+
+    #[test]
+    fn test_compute_show_progress() {
+        let today = NaiveDate::from_ymd_opt(2023, 7, 31).unwrap();
+        let season = |number: i32, name: &str, dates: &[Option<NaiveDate>]| ShowSeason {
+            season_number: number,
+            name: name.to_owned(),
+            episodes: dates
+                .iter()
+                .enumerate()
+                .map(|(idx, date)| ShowEpisode {
+                    episode_number: idx as i32 + 1,
+                    publish_date: *date,
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let aired = Some(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap());
+        let upcoming = Some(NaiveDate::from_ymd_opt(2023, 9, 1).unwrap());
+        let specifics = ShowSpecifics {
+            seasons: vec![
+                season(0, "Specials", &[aired, aired]),
+                season(1, "Season 1", &[aired, aired, None]),
+                season(2, "Season 2", &[aired, upcoming]),
+            ],
+        };
+        let watched = HashSet::from([(0, 1), (1, 1), (1, 3)]);
+        let progress = compute_show_progress(&specifics, &watched, today);
+        let counts = progress
+            .seasons
+            .iter()
+            .map(|s| (s.season_number, s.watched, s.total))
+            .collect_vec();
+        assert_eq!(counts, vec![(1, 2, 3), (2, 0, 1)]);
+        assert_eq!(progress.percentage, 50);
+        let next = progress.next_episode.unwrap();
+        assert_eq!((next.season_number, next.episode_number), (2, 1));
+
+        let progress = compute_show_progress(&specifics, &HashSet::new(), today);
+        assert_eq!(progress.percentage, 0);
+        let next = progress.next_episode.unwrap();
+        assert_eq!((next.season_number, next.episode_number), (1, 1));
+    }
+
+    #[test]
+    fn test_yearly_summary_counts_vague_dates_for_the_year_only() {
+        let date = |m| NaiveDate::from_ymd_opt(2014, m, 1).unwrap();
+        let summary = summarize_year(
+            2014,
+            &[
+                (date(1), SeenDatePrecision::Day),
+                (date(1), SeenDatePrecision::Month),
+                (date(3), SeenDatePrecision::Day),
+                (date(1), SeenDatePrecision::Year),
+            ],
+        );
+        assert_eq!(summary.total, 4);
+        assert_eq!(summary.months, vec![2, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_sort_media_candidates() {
+        let candidate =
+            |identifier: &str, lot, title: &str, popularity: Option<i64>| MediaSearchItem {
+                identifier: identifier.to_owned(),
+                lot,
+                title: title.to_owned(),
+                image: None,
+                publish_year: None,
+                popularity: popularity.map(Decimal::from),
+            };
+        let candidates = vec![
+            candidate("10", MetadataLot::Show, "Dune", Some(900)),
+            candidate("20", MetadataLot::Movie, "Dune: Part Two", Some(800)),
+            candidate("300", MetadataLot::Movie, "dune", None),
+            candidate("20", MetadataLot::Movie, "Dune: Part Two", Some(800)),
+            candidate("40", MetadataLot::Movie, "Dune", None),
+            candidate("500", MetadataLot::Movie, "Dune", Some(10)),
+            candidate("60", MetadataLot::Movie, "Dune: Part Two", None),
+        ];
+        let sorted = sort_media_candidates(candidates, MetadataLot::Movie, "Dune")
+            .into_iter()
+            .map(|c| c.identifier)
+            .collect_vec();
+        assert_eq!(sorted, vec!["500", "40", "300", "20", "60", "10"]);
+    }
+}
@@ -7,7 +7,7 @@ use specta::Type;
 
 use crate::{
     entities::exercise::Model as ExerciseModel,
-    migrator::{MetadataLot, MetadataSource, SeenState},
+    migrator::{MediaImportSource, MediaState, MetadataLot, MetadataSource, SeenDatePrecision},
     miscellaneous::{MediaSpecifics, MetadataCreator, MetadataImage},
 };
 
@@ -53,6 +53,8 @@ pub mod media {
     pub struct MediaListItem {
         pub data: MediaSearchItem,
         pub average_rating: Option<Decimal>,
+        /// The number of episodes of a podcast that have not been listened to
+        pub unplayed_episodes: Option<usize>,
     }
 
     #[derive(
@@ -61,6 +63,9 @@ pub mod media {
     #[graphql(input_name = "AudioBookSpecificsInput")]
     pub struct AudioBookSpecifics {
         pub runtime: Option<i32>,
+        /// The Audible region the details were fetched from.
+        #[serde(default)]
+        pub region: Option<String>,
     }
 
     #[derive(
@@ -114,6 +119,9 @@ pub mod media {
         #[serde(default)]
         pub number: i32,
         pub id: String,
+        /// The GUID of the episode in the podcast's feed
+        #[serde(default, alias = "guid_from_rss")]
+        pub guid: Option<String>,
         #[serde(rename = "audio_length_sec")]
         pub runtime: Option<i32>,
         #[serde(rename = "description")]
@@ -242,17 +250,42 @@ pub mod media {
         pub volumes: Option<i32>,
     }
 
-    #[derive(Debug, Serialize, Deserialize, SimpleObject, Clone)]
+    #[derive(Debug, Serialize, Deserialize, SimpleObject, Clone, PartialEq, Eq)]
     pub struct MediaSearchItem {
         pub identifier: String,
         pub lot: MetadataLot,
         pub title: String,
         pub image: Option<String>,
         pub publish_year: Option<i32>,
+        /// How popular the media is at the provider, if it says so. Only used to
+        /// pick between candidates.
+        #[graphql(skip)]
+        #[serde(default)]
+        pub popularity: Option<Decimal>,
+    }
+
+    /// A media item that is not in the user's library but might interest them
+    #[derive(Debug, Serialize, Deserialize, SimpleObject, Clone, PartialEq, Eq)]
+    pub struct MediaRecommendation {
+        pub item: MediaSearchItem,
+        pub source: MetadataSource,
+        /// The titles of the media in the user's library this was recommended
+        /// because of
+        pub because_of: Vec<String>,
     }
 
     #[derive(
-        Debug, Clone, Copy, PartialEq, Eq, EnumIter, DeriveActiveEnum, Deserialize, Serialize, Enum,
+        Debug,
+        Clone,
+        Copy,
+        PartialEq,
+        Eq,
+        EnumIter,
+        DeriveActiveEnum,
+        Deserialize,
+        Serialize,
+        Enum,
+        Type,
     )]
     #[sea_orm(rs_type = "String", db_type = "String(None)")]
     pub enum Visibility {
@@ -422,6 +455,9 @@ pub mod media {
         pub manga: MangaSummary,
         #[serde(default)]
         pub reviews_posted: u64,
+        /// The number of media that have been dropped
+        #[serde(default)]
+        pub dropped: u64,
     }
 
     #[derive(
@@ -446,6 +482,13 @@ pub mod media {
         pub media_id: i32,
     }
 
+    /// The import that created a seen item or review
+    #[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+    pub struct ImportProvenance {
+        pub source: MediaImportSource,
+        pub report_id: i32,
+    }
+
     #[derive(Debug, InputObject)]
     pub struct PostReviewInput {
         pub rating: Option<Decimal>,
@@ -459,6 +502,8 @@ pub mod media {
         pub show_season_number: Option<i32>,
         pub show_episode_number: Option<i32>,
         pub podcast_episode_number: Option<i32>,
+        #[graphql(skip)]
+        pub import: Option<ImportProvenance>,
     }
 
     #[derive(Debug, Serialize, Deserialize, InputObject, Clone)]
@@ -469,7 +514,32 @@ pub mod media {
         pub show_season_number: Option<i32>,
         pub show_episode_number: Option<i32>,
         pub podcast_episode_number: Option<i32>,
-        pub change_state: Option<SeenState>,
+        /// Drop the media or put it on hold, along with its latest seen item.
+        pub change_state: Option<MediaState>,
+        /// The number of times the media was consumed. Defaults to 1.
+        pub plays: Option<i32>,
+        #[graphql(skip)]
+        #[serde(default)]
+        pub import: Option<ImportProvenance>,
+    }
+
+    /// A completed seen item whose dates might only be known to their month or
+    /// year, for eg "sometime in 2014".
+    #[derive(Debug, Serialize, Deserialize, InputObject, Clone)]
+    pub struct CreateSeenEntryInput {
+        pub metadata_id: i32,
+        pub started_on: Option<NaiveDate>,
+        pub finished_on: Option<NaiveDate>,
+        /// How precisely the dates are known. Defaults to the day.
+        pub precision: Option<SeenDatePrecision>,
+        pub show_season_number: Option<i32>,
+        pub show_episode_number: Option<i32>,
+        pub podcast_episode_number: Option<i32>,
+        /// The number of times the media was consumed. Defaults to 1.
+        pub plays: Option<i32>,
+        #[graphql(skip)]
+        #[serde(default)]
+        pub import: Option<ImportProvenance>,
     }
 
     #[derive(Enum, Clone, Debug, Copy, PartialEq, Eq)]
@@ -503,6 +573,21 @@ pub mod media {
         pub publish_year: Option<i32>,
         pub publish_date: Option<NaiveDate>,
         pub specifics: MediaSpecifics,
+        #[serde(default)]
+        pub groups: Vec<MetadataGroupDetails>,
+        /// Other titles the media is known by, for eg in other languages.
+        #[serde(default)]
+        pub alternative_titles: Vec<String>,
+    }
+
+    /// A group of related media (for eg: a movie collection or a video game
+    /// franchise) as returned by the provider
+    #[derive(Debug, Serialize, Deserialize, Clone)]
+    pub struct MetadataGroupDetails {
+        pub identifier: String,
+        pub title: String,
+        /// All the media in the group in the order they were released
+        pub parts: Vec<MediaSearchItem>,
     }
 
     #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -526,6 +611,20 @@ pub mod media {
         pub show_episode_number: Option<i32>,
         /// If for a podcast, the episode which was seen.
         pub podcast_episode_number: Option<i32>,
+        /// If for a podcast, the GUID of the episode which was seen. Used to find
+        /// the episode number when it is not known.
+        #[serde(default)]
+        pub podcast_episode_guid: Option<String>,
+        /// The progress of the media. It is considered completed if not set.
+        #[serde(default)]
+        pub progress: Option<i32>,
+        /// The number of times the media was consumed, if only the date of the
+        /// last time is known. Defaults to 1.
+        #[serde(default)]
+        pub plays: Option<i32>,
+        /// How precisely the dates are known. Defaults to the day.
+        #[serde(default)]
+        pub date_precision: Option<SeenDatePrecision>,
     }
 
     #[derive(Debug, Serialize, Deserialize, Clone, Type)]
@@ -536,6 +635,9 @@ pub mod media {
         pub spoiler: Option<bool>,
         /// Actual text for the review.
         pub text: Option<String>,
+        /// The visibility of the review. Uses the import default if not set.
+        #[serde(default)]
+        pub visibility: Option<Visibility>,
     }
 
     #[derive(Debug, Serialize, Deserialize, Clone, Type)]
@@ -557,6 +659,9 @@ pub mod media {
     pub struct ImportOrExportItem<T> {
         /// An string to help identify it in the original source.
         pub source_id: String,
+        /// The title of the media in the source, if it is known.
+        #[serde(default)]
+        pub title: Option<String>,
         /// The type of media.
         pub lot: MetadataLot,
         /// The source of media.
@@ -569,6 +674,9 @@ pub mod media {
         pub reviews: Vec<ImportOrExportItemRating>,
         /// The collections to add this media to.
         pub collections: Vec<String>,
+        /// The state the user has put this media in.
+        #[serde(default)]
+        pub state: Option<MediaState>,
     }
 }
 
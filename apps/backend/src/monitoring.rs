@@ -0,0 +1,149 @@
+use std::{cell::Cell, future::Future, sync::Arc, time::Duration};
+
+use axum::{
+    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+    Extension,
+};
+use metrics::{counter, decrement_gauge, gauge, histogram, increment_gauge};
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+
+use crate::config::AppConfig;
+
+tokio::task_local! {
+    /// The number of provider requests made by the task since it started
+    /// counting them
+    static PROVIDER_REQUESTS: Cell<usize>;
+    /// The number of provider requests made by the task that could not be
+    /// sent, got a server error or took too long
+    static PROVIDER_FAILURES: Cell<usize>;
+}
+
+/// Count the provider requests made while running the future. The counts are
+/// available to it through `provider_request_count` and
+/// `provider_failure_count`.
+pub async fn count_provider_requests<F: Future>(future: F) -> F::Output {
+    PROVIDER_REQUESTS
+        .scope(Cell::new(0), PROVIDER_FAILURES.scope(Cell::new(0), future))
+        .await
+}
+
+/// The number of provider requests made so far by the current task, if it is
+/// counting them.
+pub fn provider_request_count() -> Option<usize> {
+    PROVIDER_REQUESTS.try_with(|c| c.get()).ok()
+}
+
+/// The number of provider requests that failed so far in the current task, if
+/// it is counting them.
+pub fn provider_failure_count() -> Option<usize> {
+    PROVIDER_FAILURES.try_with(|c| c.get()).ok()
+}
+
+/// Count a provider request of the current task that failed because of the
+/// provider, as opposed to eg the media not being found.
+pub fn record_provider_failure() {
+    PROVIDER_FAILURES.try_with(|c| c.set(c.get() + 1)).ok();
+}
+
+/// Install the global metrics recorder. All `metrics` macros are no-ops until
+/// this is called.
+pub fn install_recorder() -> PrometheusHandle {
+    PrometheusBuilder::new()
+        .install_recorder()
+        .expect("Could not install the metrics recorder")
+}
+
+/// Record a GraphQL request. The operation should be one of a bounded set of
+/// names, see `graphql::operation_label`.
+pub fn record_graphql_request(operation: String, success: bool, duration: Duration) {
+    let status = if success { "ok" } else { "error" };
+    counter!(
+        "graphql_requests_total",
+        1,
+        "operation" => operation.clone(),
+        "status" => status
+    );
+    histogram!(
+        "graphql_request_duration_seconds",
+        duration.as_secs_f64(),
+        "operation" => operation
+    );
+}
+
+pub fn record_job_enqueued(job: &'static str) {
+    counter!("background_jobs_total", 1, "job" => job, "status" => "enqueued");
+    increment_gauge!("background_jobs_queued", 1.0, "job" => job);
+}
+
+pub fn record_job_finished(job: &'static str, success: bool) {
+    let status = if success { "completed" } else { "failed" };
+    counter!("background_jobs_total", 1, "job" => job, "status" => status);
+    decrement_gauge!("background_jobs_queued", 1.0, "job" => job);
+}
+
+pub fn record_oldest_pending_job(age: i64) {
+    gauge!("background_jobs_oldest_pending_seconds", age as f64);
+}
+
+pub fn record_provider_request(host: &str, duration: Duration) {
+    PROVIDER_REQUESTS.try_with(|c| c.set(c.get() + 1)).ok();
+    histogram!(
+        "provider_request_duration_seconds",
+        duration.as_secs_f64(),
+        "host" => host.to_owned()
+    );
+}
+
+pub fn record_import(source: &str, imported: usize, failed: usize, duration: Duration) {
+    let source = source.to_owned();
+    counter!(
+        "import_items_total",
+        imported as u64,
+        "source" => source.clone(),
+        "status" => "imported"
+    );
+    counter!(
+        "import_items_total",
+        failed as u64,
+        "source" => source.clone(),
+        "status" => "failed"
+    );
+    histogram!(
+        "import_duration_seconds",
+        duration.as_secs_f64(),
+        "source" => source
+    );
+}
+
+pub async fn metrics_handler(
+    Extension(config): Extension<Arc<AppConfig>>,
+    Extension(handle): Extension<Option<PrometheusHandle>>,
+    headers: HeaderMap,
+) -> Result<String, StatusCode> {
+    let handle = handle.ok_or(StatusCode::NOT_FOUND)?;
+    if !config.metrics.token.is_empty() {
+        let expected = format!("Bearer {}", config.metrics.token);
+        let provided = headers.get(AUTHORIZATION).and_then(|h| h.to_str().ok());
+        if provided != Some(expected.as_str()) {
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+    }
+    Ok(handle.render())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_provider_failures_are_counted_per_task() {
+        assert_eq!(provider_failure_count(), None);
+        let count = count_provider_requests(async {
+            record_provider_failure();
+            record_provider_failure();
+            provider_failure_count()
+        })
+        .await;
+        assert_eq!(count, Some(2));
+    }
+}
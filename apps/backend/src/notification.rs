@@ -0,0 +1,471 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use anyhow::{anyhow, bail, Result};
+use async_std::net::TcpStream;
+use async_tls::TlsConnector;
+use chrono::NaiveDate;
+use http_types::{headers::HOST, Body, Method, Request};
+use serde_json::{json, Value};
+use surf::{http::url::Host, Url};
+
+use crate::{importer::ImportLotSummary, users::UserNotificationPlatform};
+
+/// The outcome of an import job that users are notified about
+#[derive(Debug, Clone)]
+pub struct ImportNotification {
+    pub source: String,
+    pub imported: usize,
+    pub failed: usize,
+    pub lots: Vec<ImportLotSummary>,
+    /// Why the import failed, if it did not run to completion
+    pub error: Option<String>,
+}
+
+impl ImportNotification {
+    fn title(&self) -> String {
+        match self.error {
+            Some(_) => format!("Import from {} failed", self.source),
+            None => format!("Import from {} complete", self.source),
+        }
+    }
+
+    fn summary(&self) -> String {
+        if let Some(error) = &self.error {
+            return error.clone();
+        }
+        let mut summary = format!(
+            "{} items imported, {} items failed",
+            self.imported, self.failed
+        );
+        for (lot, outcome) in self.lot_outcomes() {
+            summary.push_str(&format!("\n{lot}: {outcome}"));
+        }
+        summary
+    }
+
+    /// The name of each lot with how its items fared.
+    fn lot_outcomes(&self) -> Vec<(String, String)> {
+        self.lots
+            .iter()
+            .map(|l| {
+                let outcome = format!(
+                    "{} of {} imported, {} failed",
+                    l.succeeded, l.attempted, l.failed
+                );
+                (format!("{:?}", l.lot), outcome)
+            })
+            .collect()
+    }
+
+    /// The body of the webhook request in the shape the platform expects.
+    pub fn payload(&self, platform: UserNotificationPlatform) -> Value {
+        let generic = json!({
+            "event": if self.error.is_some() { "import_failed" } else { "import_complete" },
+            "title": self.title(),
+            "source": self.source,
+            "imported": self.imported,
+            "failed": self.failed,
+            "lots": self.lots,
+            "error": self.error,
+        });
+        let lots = self.lot_outcomes();
+        let mut fields = vec![
+            ("Imported", self.imported.to_string()),
+            ("Failed", self.failed.to_string()),
+        ];
+        fields.extend(
+            lots.iter()
+                .map(|(lot, outcome)| (lot.as_str(), outcome.clone())),
+        );
+        render(
+            platform,
+            &self.title(),
+            &self.summary(),
+            &fields,
+            self.failed == 0 && self.error.is_none(),
+            generic,
+        )
+    }
+}
+
+/// A reminder that a user set on a media item
+#[derive(Debug, Clone)]
+pub struct ReminderNotification {
+    pub metadata_id: i32,
+    pub media_title: String,
+    pub remind_on: NaiveDate,
+    pub note: Option<String>,
+}
+
+impl ReminderNotification {
+    fn title(&self) -> String {
+        format!("Reminder for {}", self.media_title)
+    }
+
+    /// The body of the webhook request in the shape the platform expects.
+    pub fn payload(&self, platform: UserNotificationPlatform) -> Value {
+        let generic = json!({
+            "event": "media_reminder",
+            "title": self.title(),
+            "metadata_id": self.metadata_id,
+            "remind_on": self.remind_on,
+            "note": self.note,
+        });
+        let fields = [("Date", self.remind_on.to_string())];
+        render(
+            platform,
+            &self.title(),
+            self.note.as_deref().unwrap_or_default(),
+            &fields,
+            true,
+            generic,
+        )
+    }
+}
+
+/// The changes that were found in a monitored media item
+#[derive(Debug, Clone)]
+pub struct MonitorNotification {
+    pub metadata_id: i32,
+    pub media_title: String,
+    pub changes: Vec<String>,
+}
+
+impl MonitorNotification {
+    fn title(&self) -> String {
+        format!("{} has changed", self.media_title)
+    }
+
+    /// The body of the webhook request in the shape the platform expects.
+    pub fn payload(&self, platform: UserNotificationPlatform) -> Value {
+        let generic = json!({
+            "event": "media_changed",
+            "title": self.title(),
+            "metadata_id": self.metadata_id,
+            "changes": self.changes,
+        });
+        render(
+            platform,
+            &self.title(),
+            &self.changes.join("\n"),
+            &[],
+            true,
+            generic,
+        )
+    }
+}
+
+/// Render a message as a Discord embed or a Slack attachment. The generic
+/// payload is sent as is.
+fn render(
+    platform: UserNotificationPlatform,
+    title: &str,
+    summary: &str,
+    fields: &[(&str, String)],
+    succeeded: bool,
+    generic: Value,
+) -> Value {
+    match platform {
+        UserNotificationPlatform::Generic => generic,
+        UserNotificationPlatform::Discord => {
+            // DEV: green when everything went well, red otherwise
+            let color = if succeeded { 0x2ecc71 } else { 0xe74c3c };
+            let fields = fields
+                .iter()
+                .map(|(name, value)| json!({ "name": name, "value": value, "inline": true }))
+                .collect::<Vec<_>>();
+            json!({
+                "embeds": [{
+                    "title": title,
+                    "description": summary,
+                    "color": color,
+                    "fields": fields,
+                }],
+            })
+        }
+        UserNotificationPlatform::Slack => {
+            let color = if succeeded { "good" } else { "danger" };
+            let fields = fields
+                .iter()
+                .map(|(name, value)| json!({ "title": name, "value": value, "short": true }))
+                .collect::<Vec<_>>();
+            json!({
+                "text": title,
+                "attachments": [{
+                    "fallback": summary,
+                    "color": color,
+                    "fields": fields,
+                }],
+            })
+        }
+    }
+}
+
+/// Whether the address can be reached from the public internet.
+fn is_public_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            // DEV: `100.64.0.0/10` is shared by carrier-grade NATs and
+            // `0.0.0.0/8` reaches the host itself on most systems
+            let shared = a == 100 && (b & 0xc0) == 64;
+            !(a == 0
+                || ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_multicast()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || shared)
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_public_address(IpAddr::V4(ip));
+            }
+            // DEV: 6to4 and NAT64 addresses are routed to the IPv4 address
+            // that they embed
+            let embedded = match ip.segments() {
+                [0x2002, high, low, ..] | [0x64, 0xff9b, 0, 0, 0, 0, high, low] => {
+                    Some(Ipv4Addr::from((u32::from(high) << 16) | u32::from(low)))
+                }
+                _ => None,
+            };
+            if let Some(ip) = embedded {
+                return is_public_address(IpAddr::V4(ip));
+            }
+            let first = ip.segments()[0];
+            let unique_local = (first & 0xfe00) == 0xfc00;
+            let link_local = (first & 0xffc0) == 0xfe80;
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                || unique_local
+                || link_local)
+        }
+    }
+}
+
+/// Parse the url of a webhook and make sure that it points to a public host.
+/// The url is set by the user, so this prevents them from making the server
+/// send requests to its own network. Returns the address that was checked,
+/// which is the one the webhook must be sent to.
+async fn check_webhook_url(url: &str) -> Result<(Url, SocketAddr)> {
+    let url = Url::parse(url)?;
+    if !matches!(url.scheme(), "http" | "https") {
+        bail!("Webhooks can only be sent over http or https");
+    }
+    let port = url.port_or_known_default().unwrap_or(80);
+    let addresses: Vec<_> = match url.host() {
+        Some(Host::Ipv4(ip)) => vec![IpAddr::V4(ip)],
+        Some(Host::Ipv6(ip)) => vec![IpAddr::V6(ip)],
+        Some(Host::Domain(domain)) => tokio::net::lookup_host((domain, port))
+            .await?
+            .map(|a| a.ip())
+            .collect(),
+        None => bail!("The webhook url has no host"),
+    };
+    match addresses.iter().find(|ip| !is_public_address(**ip)) {
+        Some(ip) => Err(anyhow!(
+            "Webhooks can not be sent to the private address {ip}"
+        )),
+        None if addresses.is_empty() => bail!("The host of the webhook could not be resolved"),
+        None => Ok((url, SocketAddr::new(addresses[0], port))),
+    }
+}
+
+/// Post the payload to the url over a connection to `address`. The host is
+/// not resolved again, since it could then point somewhere else than the
+/// address that was checked.
+async fn post_pinned(url: Url, address: SocketAddr, payload: &Value) -> Result<()> {
+    let Some(host) = url.host_str().map(ToOwned::to_owned) else {
+        bail!("The webhook url has no host");
+    };
+    let mut req = Request::new(Method::Post, url.clone());
+    let header = match url.port() {
+        Some(port) => format!("{host}:{port}"),
+        None => host.clone(),
+    };
+    req.insert_header(HOST, header);
+    req.set_body(Body::from_json(payload).map_err(|e| anyhow!(e.to_string()))?);
+    let stream = TcpStream::connect(address).await?;
+    let rsp = match url.scheme() {
+        "https" => {
+            let stream = TlsConnector::default().connect(&host, stream).await?;
+            async_h1::connect(stream, req).await
+        }
+        _ => async_h1::connect(stream, req).await,
+    };
+    match rsp {
+        Ok(r) if r.status().is_success() => Ok(()),
+        Ok(r) => bail!("Webhook responded with status {}", r.status()),
+        Err(e) => bail!(e.to_string()),
+    }
+}
+
+/// Send a notification to the webhook configured by the user.
+pub async fn send_webhook(url: &str, payload: &Value) -> Result<()> {
+    let (url, address) = check_webhook_url(url).await?;
+    post_pinned(url, address, payload).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use axum::{
+        http::{HeaderMap, StatusCode},
+        routing::post,
+        Router, Server,
+    };
+    use rstest::rstest;
+
+    use super::*;
+    use crate::migrator::MetadataLot;
+
+    fn notification(failed: usize) -> ImportNotification {
+        ImportNotification {
+            source: "Trakt".to_owned(),
+            imported: 10,
+            failed,
+            lots: vec![],
+            error: None,
+        }
+    }
+
+    #[rstest]
+    #[case(0, 0x2ecc71)]
+    #[case(2, 0xe74c3c)]
+    fn test_discord_payload(#[case] failed: usize, #[case] color: i64) {
+        let payload = notification(failed).payload(UserNotificationPlatform::Discord);
+        let embed = &payload["embeds"][0];
+        assert_eq!(embed["title"], "Import from Trakt complete");
+        assert_eq!(embed["color"], color);
+        assert_eq!(embed["fields"][1]["value"], failed.to_string());
+    }
+
+    #[rstest]
+    #[case(0, "good")]
+    #[case(2, "danger")]
+    fn test_slack_payload(#[case] failed: usize, #[case] color: &str) {
+        let payload = notification(failed).payload(UserNotificationPlatform::Slack);
+        let attachment = &payload["attachments"][0];
+        assert_eq!(payload["text"], "Import from Trakt complete");
+        assert_eq!(attachment["color"], color);
+        assert_eq!(attachment["fields"][0]["value"], "10");
+    }
+
+    #[test]
+    fn test_lot_summaries() {
+        let mut notification = notification(50);
+        notification.lots = vec![ImportLotSummary {
+            lot: MetadataLot::Book,
+            attempted: 50,
+            succeeded: 0,
+            failed: 50,
+            seen_created: 0,
+            reviews_created: 0,
+            seen_skipped: 0,
+        }];
+        let payload = notification.payload(UserNotificationPlatform::Discord);
+        let embed = &payload["embeds"][0];
+        assert_eq!(
+            embed["description"],
+            "10 items imported, 50 items failed\nBook: 0 of 50 imported, 50 failed"
+        );
+        assert_eq!(embed["fields"][2]["name"], "Book");
+        let payload = notification.payload(UserNotificationPlatform::Generic);
+        assert_eq!(payload["lots"][0]["failed"], 50);
+    }
+
+    #[test]
+    fn test_reminder_payload() {
+        let reminder = ReminderNotification {
+            metadata_id: 1,
+            media_title: "Dune".to_owned(),
+            remind_on: NaiveDate::from_ymd_opt(2023, 8, 4).unwrap(),
+            note: Some("Return to the library".to_owned()),
+        };
+        let payload = reminder.payload(UserNotificationPlatform::Discord);
+        assert_eq!(payload["embeds"][0]["title"], "Reminder for Dune");
+        assert_eq!(payload["embeds"][0]["description"], "Return to the library");
+        assert_eq!(payload["embeds"][0]["fields"][0]["value"], "2023-08-04");
+    }
+
+    #[test]
+    fn test_failed_import_payload() {
+        let mut notification = notification(0);
+        notification.error = Some("Could not fetch the history".to_owned());
+        let payload = notification.payload(UserNotificationPlatform::Discord);
+        assert_eq!(payload["embeds"][0]["title"], "Import from Trakt failed");
+        assert_eq!(payload["embeds"][0]["color"], 0xe74c3c);
+        let payload = notification.payload(UserNotificationPlatform::Generic);
+        assert_eq!(payload["event"], "import_failed");
+        assert_eq!(payload["error"], "Could not fetch the history");
+    }
+
+    #[rstest]
+    #[case("93.184.216.34", true)]
+    #[case("2606:2800:220:1::", true)]
+    #[case("127.0.0.1", false)]
+    #[case("10.0.0.5", false)]
+    #[case("172.16.4.1", false)]
+    #[case("192.168.1.10", false)]
+    #[case("169.254.169.254", false)]
+    #[case("100.64.0.1", false)]
+    #[case("0.0.0.0", false)]
+    #[case("0.1.2.3", false)]
+    #[case("224.0.0.1", false)]
+    #[case("::1", false)]
+    #[case("fd00::1", false)]
+    #[case("fe80::1", false)]
+    #[case("::ffff:127.0.0.1", false)]
+    #[case("ff02::1", false)]
+    #[case("2002:c0a8:10a::", false)]
+    #[case("2002:5db8:d822::", true)]
+    #[case("64:ff9b::a00:5", false)]
+    #[case("64:ff9b::5db8:d822", true)]
+    fn test_is_public_address(#[case] ip: &str, #[case] public: bool) {
+        assert_eq!(is_public_address(ip.parse().unwrap()), public);
+    }
+
+    #[rstest]
+    #[case("https://93.184.216.34/hook", true)]
+    #[case("http://127.0.0.1:8000/hook", false)]
+    #[case("http://[::1]/hook", false)]
+    #[case("http://localhost/hook", false)]
+    #[case("file:///etc/passwd", false)]
+    #[tokio::test]
+    async fn test_check_webhook_url(#[case] url: &str, #[case] allowed: bool) {
+        assert_eq!(check_webhook_url(url).await.is_ok(), allowed);
+    }
+
+    #[tokio::test]
+    async fn test_post_pinned_keeps_the_host() {
+        let app = Router::new().route(
+            "/hook",
+            post(|headers: HeaderMap| async move {
+                match headers.get("host").and_then(|h| h.to_str().ok()) {
+                    Some(h) if h.starts_with("hooks.example.com:") => StatusCode::OK,
+                    _ => StatusCode::BAD_REQUEST,
+                }
+            }),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(
+            Server::from_tcp(listener)
+                .unwrap()
+                .serve(app.into_make_service()),
+        );
+        // DEV: the host is never resolved, the request goes to the address
+        let url = Url::parse(&format!("http://hooks.example.com:{}/hook", address.port())).unwrap();
+        post_pinned(url, address, &json!({})).await.unwrap();
+    }
+
+    #[test]
+    fn test_generic_payload() {
+        let payload = notification(1).payload(UserNotificationPlatform::Generic);
+        assert_eq!(payload["imported"], 10);
+        assert_eq!(payload["failed"], 1);
+    }
+}
@@ -1,6 +1,7 @@
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use graphql_client::{GraphQLQuery, Response};
+use rust_decimal::Decimal;
 use surf::Client;
 
 use crate::{
@@ -135,7 +136,7 @@ mod utils {
         migrator::{MetadataImageLot, MetadataSource},
         miscellaneous::{MediaSpecifics, MetadataCreator, MetadataImage, MetadataImageUrl},
         models::media::{AnimeSpecifics, MangaSpecifics},
-        utils::get_base_http_client,
+        utils::{alternative_titles, get_base_http_client},
     };
 
     use super::*;
@@ -220,12 +221,21 @@ mod utils {
             details_query::MediaType::Other(_) => unreachable!(),
         };
 
+        let title = details.title.unwrap();
+        let user_preferred = title.user_preferred.unwrap();
+        let alternative_titles = alternative_titles(
+            &user_preferred,
+            [title.romaji, title.english, title.native]
+                .into_iter()
+                .flatten()
+                .chain(details.synonyms.into_iter().flatten().flatten()),
+        );
         let year = details
             .start_date
             .and_then(|b| b.year.map(|y| y.try_into().unwrap()));
         Ok(MediaDetails {
             identifier: details.id.to_string(),
-            title: details.title.unwrap().user_preferred.unwrap(),
+            title: user_preferred,
             source: MetadataSource::Anilist,
             description: details.description,
             lot,
@@ -235,6 +245,8 @@ mod utils {
             publish_year: year,
             publish_date: None,
             specifics,
+            groups: vec![],
+            alternative_titles,
         })
     }
 
@@ -285,6 +297,7 @@ mod utils {
                 publish_year: b
                     .start_date
                     .and_then(|b| b.year.map(|y| y.try_into().unwrap())),
+                popularity: b.popularity.map(Decimal::from),
             })
             .collect();
         Ok((media, total, next_page))
@@ -2,6 +2,7 @@ use anyhow::{anyhow, Result};
 use async_graphql::SimpleObject;
 use async_trait::async_trait;
 use itertools::Itertools;
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use surf::{http::headers::ACCEPT, Client};
 
@@ -10,7 +11,7 @@ use crate::{
     migrator::{MetadataImageLot, MetadataLot, MetadataSource},
     miscellaneous::{MediaSpecifics, MetadataCreator, MetadataImage, MetadataImageUrl},
     models::{
-        media::{AudioBookSpecifics, MediaDetails, MediaSearchItem},
+        media::{AudioBookSpecifics, MediaDetails, MediaSearchItem, MetadataGroupDetails},
         NamedObject, SearchResults,
     },
     traits::{MediaProvider, MediaProviderLanguages},
@@ -34,6 +35,7 @@ impl Default for PrimaryQuery {
                 "media",
                 "product_attrs",
                 "product_extended_attrs",
+                "series",
             ]
             .join(","),
             image_sizes: ["2400"].join(","),
@@ -62,6 +64,22 @@ pub struct AudibleCategoryLadderCollection {
     ladder: Vec<NamedObject>,
 }
 
+#[derive(Debug, Serialize, Deserialize, SimpleObject)]
+pub struct AudibleSeries {
+    asin: String,
+    title: String,
+    sequence: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, SimpleObject)]
+pub struct AudibleRelationship {
+    asin: String,
+    relationship_to_product: String,
+    relationship_type: String,
+    sort: Option<String>,
+    title: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, SimpleObject)]
 pub struct AudibleItem {
     asin: String,
@@ -74,11 +92,16 @@ pub struct AudibleItem {
     release_date: Option<String>,
     runtime_length_min: Option<i32>,
     category_ladders: Option<Vec<AudibleCategoryLadderCollection>>,
+    series: Option<Vec<AudibleSeries>>,
+    relationships: Option<Vec<AudibleRelationship>>,
 }
 
 #[derive(Debug, Clone)]
 pub struct AudibleService {
     client: Client,
+    locale: String,
+    /// Used when an item can not be found in the configured region.
+    fallback_client: Option<Client>,
 }
 
 impl MediaProviderLanguages for AudibleService {
@@ -96,7 +119,7 @@ impl AudibleService {
         let suffix = match locale {
             "us" => "com",
             "ca" => "ca",
-            "uk" => "co.uk",
+            "gb" | "uk" => "co.uk",
             "au" => "co.au",
             "fr" => "fr",
             "de" => "de",
@@ -109,32 +132,171 @@ impl AudibleService {
         format!("https://api.audible.{}/1.0/catalog/products/", suffix)
     }
 
+    fn client_for_locale(locale: &str) -> Client {
+        let url = Self::url_from_locale(locale);
+        get_base_http_client(&url, vec![(ACCEPT, "application/json")])
+    }
+
+    /// The client of the instance, which looks for the items that are not in
+    /// the configured region in the US store.
     pub async fn new(config: &AudibleConfig) -> Self {
-        let url = Self::url_from_locale(&config.locale);
-        let client = get_base_http_client(&url, vec![(ACCEPT, "application/json")]);
-        Self { client }
+        let default_locale = Self::default_language();
+        let fallback_client = if config.locale == default_locale {
+            None
+        } else {
+            Some(Self::client_for_locale(&default_locale))
+        };
+        Self {
+            client: Self::client_for_locale(&config.locale),
+            locale: config.locale.clone(),
+            fallback_client,
+        }
     }
-}
 
-#[async_trait]
-impl MediaProvider for AudibleService {
-    async fn details(&self, identifier: &str) -> Result<MediaDetails> {
+    /// A client that only looks for items in the region that was chosen by a
+    /// user.
+    pub async fn in_region(config: &AudibleConfig) -> Self {
+        Self {
+            client: Self::client_for_locale(&config.locale),
+            locale: config.locale.clone(),
+            fallback_client: None,
+        }
+    }
+
+    async fn details_in_region(
+        &self,
+        client: &Client,
+        locale: &str,
+        identifier: &str,
+    ) -> Result<MediaDetails> {
         #[derive(Serialize, Deserialize, Debug)]
         struct AudibleItemResponse {
             product: AudibleItem,
         }
-        let mut rsp = self
-            .client
+        let mut rsp = client
             .get(identifier)
             .query(&PrimaryQuery::default())
             .unwrap()
             .await
             .map_err(|e| anyhow!(e))?;
         let data: AudibleItemResponse = rsp.body_json().await.map_err(|e| anyhow!(e))?;
-        let d = self.audible_response_to_search_response(data.product);
+        let series = data.product.series.clone().unwrap_or_default();
+        let mut d = self.audible_response_to_search_response(data.product, Some(locale));
+        for s in series {
+            // DEV: a missing series listing should not stop the book from being
+            // added, so it degrades to a group containing only this book
+            let parts = match self.series_parts(client, &s.asin).await {
+                Ok(parts) if !parts.is_empty() => parts,
+                result => {
+                    if let Err(e) = result {
+                        tracing::warn!("Could not get the books of the series {:?}: {e}", s.asin);
+                    }
+                    vec![MediaSearchItem {
+                        identifier: d.identifier.clone(),
+                        lot: d.lot,
+                        title: d.title.clone(),
+                        image: None,
+                        publish_year: d.publish_year,
+                        popularity: None,
+                    }]
+                }
+            };
+            d.groups.push(MetadataGroupDetails {
+                identifier: s.asin,
+                title: s.title,
+                parts,
+            });
+        }
         Ok(d)
     }
 
+    /// Get all the books in a series ordered by their position in it.
+    async fn series_parts(
+        &self,
+        client: &Client,
+        series_asin: &str,
+    ) -> Result<Vec<MediaSearchItem>> {
+        #[derive(Serialize, Deserialize, Debug)]
+        struct AudibleSeriesResponse {
+            product: AudibleItem,
+        }
+        let mut rsp = client
+            .get(series_asin)
+            .query(&PrimaryQuery {
+                response_groups: "relationships".to_owned(),
+                image_sizes: "".to_owned(),
+            })
+            .unwrap()
+            .await
+            .map_err(|e| anyhow!(e))?;
+        let data: AudibleSeriesResponse = rsp.body_json().await.map_err(|e| anyhow!(e))?;
+        Ok(series_parts_from_relationships(
+            data.product.relationships.unwrap_or_default(),
+        ))
+    }
+}
+
+/// The position of a book in its series, which can be fractional for the
+/// novellas that are set between two books.
+fn series_position(relationship: &AudibleRelationship) -> Option<Decimal> {
+    let sort = relationship.sort.as_deref()?;
+    match sort.parse() {
+        Ok(position) => Some(position),
+        Err(e) => {
+            tracing::warn!(
+                "Could not parse the position {sort:?} of {:?} in its series: {e}",
+                relationship.asin
+            );
+            None
+        }
+    }
+}
+
+fn series_parts_from_relationships(
+    relationships: Vec<AudibleRelationship>,
+) -> Vec<MediaSearchItem> {
+    relationships
+        .into_iter()
+        .filter(|r| r.relationship_to_product == "child" && r.relationship_type == "series")
+        .map(|r| (series_position(&r), r))
+        // DEV: the books without a position are put at the end
+        .sorted_by_key(|(position, _)| (position.is_none(), *position))
+        .map(|(_, r)| MediaSearchItem {
+            title: r.title.unwrap_or_else(|| r.asin.clone()),
+            identifier: r.asin,
+            lot: MetadataLot::AudioBook,
+            image: None,
+            publish_year: None,
+            popularity: None,
+        })
+        .collect()
+}
+
+#[async_trait]
+impl MediaProvider for AudibleService {
+    async fn details(&self, identifier: &str) -> Result<MediaDetails> {
+        let err = match self
+            .details_in_region(&self.client, &self.locale, identifier)
+            .await
+        {
+            Ok(d) => return Ok(d),
+            Err(e) => e,
+        };
+        // DEV: not every title is sold in every region, so lookups by ASIN get
+        // another try in the US store before failing
+        match &self.fallback_client {
+            Some(client) => {
+                tracing::debug!(
+                    "Could not find {identifier:?} in the {:?} region: {err}",
+                    self.locale
+                );
+                self.details_in_region(client, &Self::default_language(), identifier)
+                    .await
+            }
+            None => Err(err),
+        }
+    }
+
     async fn search(
         &self,
         query: &str,
@@ -164,7 +326,7 @@ impl MediaProvider for AudibleService {
             .products
             .into_iter()
             .map(|d| {
-                let a = self.audible_response_to_search_response(d);
+                let a = self.audible_response_to_search_response(d, None);
                 MediaSearchItem {
                     identifier: a.identifier,
                     lot: MetadataLot::AudioBook,
@@ -180,6 +342,7 @@ impl MediaProvider for AudibleService {
                         .get(0)
                         .cloned(),
                     publish_year: a.publish_year,
+                    popularity: None,
                 }
             })
             .collect_vec();
@@ -197,7 +360,11 @@ impl MediaProvider for AudibleService {
 }
 
 impl AudibleService {
-    fn audible_response_to_search_response(&self, item: AudibleItem) -> MediaDetails {
+    fn audible_response_to_search_response(
+        &self,
+        item: AudibleItem,
+        region: Option<&str>,
+    ) -> MediaDetails {
         let images = Vec::from_iter(item.product_images.image.map(|a| MetadataImage {
             url: MetadataImageUrl::Url(a),
             lot: MetadataImageLot::Poster,
@@ -242,8 +409,40 @@ impl AudibleService {
             publish_date: convert_string_to_date(&release_date),
             specifics: MediaSpecifics::AudioBook(AudioBookSpecifics {
                 runtime: item.runtime_length_min,
+                region: region.map(String::from),
             }),
             images,
+            groups: vec![],
+            alternative_titles: vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn relationship(asin: &str, kind: &str, sort: Option<&str>) -> AudibleRelationship {
+        AudibleRelationship {
+            asin: asin.to_owned(),
+            relationship_to_product: "child".to_owned(),
+            relationship_type: kind.to_owned(),
+            sort: sort.map(String::from),
+            title: Some(asin.to_owned()),
         }
     }
+
+    #[test]
+    fn test_series_parts_are_ordered_by_position() {
+        let parts = series_parts_from_relationships(vec![
+            relationship("B", "series", Some("2")),
+            relationship("X", "season", Some("1")),
+            relationship("C", "series", None),
+            relationship("D", "series", Some("unknown")),
+            relationship("N", "series", Some("1.5")),
+            relationship("A", "series", Some("1")),
+        ]);
+        let identifiers = parts.into_iter().map(|p| p.identifier).collect_vec();
+        assert_eq!(identifiers, vec!["A", "N", "B", "C", "D"]);
+    }
 }
@@ -137,6 +137,7 @@ impl MediaProvider for GoogleBooksService {
                     title,
                     image,
                     publish_year,
+                    popularity: None,
                 }
             })
             .collect();
@@ -223,6 +224,8 @@ impl GoogleBooksService {
                 pages: item.page_count,
             }),
             images: images.unique().collect(),
+            groups: vec![],
+            alternative_titles: vec![],
         }
     }
 }
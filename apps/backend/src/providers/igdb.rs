@@ -1,26 +1,33 @@
+use std::sync::Arc;
+
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use chrono::Datelike;
 use itertools::Itertools;
 use sea_orm::prelude::DateTimeUtc;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_with::{formats::Flexible, serde_as, TimestampSeconds};
+use surf::{http::headers::AUTHORIZATION, Client, StatusCode};
+use surf_governor::GovernorMiddleware;
+use tokio::sync::Mutex;
 
 use crate::{
     config::VideoGameConfig,
     migrator::{MetadataImageLot, MetadataLot, MetadataSource},
     miscellaneous::{MediaSpecifics, MetadataCreator, MetadataImage, MetadataImageUrl},
     models::{
-        media::{MediaDetails, MediaSearchItem, VideoGameSpecifics},
+        media::{MediaDetails, MediaSearchItem, MetadataGroupDetails, VideoGameSpecifics},
         NamedObject, SearchResults,
     },
     traits::{MediaProvider, MediaProviderLanguages},
-    utils::PAGE_LIMIT,
+    utils::{alternative_titles, get_base_http_client, PAGE_LIMIT},
 };
 
 pub static URL: &str = "https://api.igdb.com/v4/";
 pub static IMAGE_URL: &str = "https://images.igdb.com/igdb/image/upload/";
 pub static AUTH_URL: &str = "https://id.twitch.tv/oauth2/token";
+/// IGDB allows 4 requests per second for each client id.
+static REQUESTS_PER_SECOND: u32 = 4;
 
 static FIELDS: &str = "
 fields
@@ -34,7 +41,9 @@ fields
     involved_companies.*,
     artworks.*,
     platforms.name,
-    genres.*;
+    genres.*,
+    alternative_names.name,
+    collection;
 where version_parent = null;
 ";
 
@@ -72,13 +81,39 @@ struct IgdbSearchResponse {
     artworks: Option<Vec<IgdbImage>>,
     genres: Option<Vec<NamedObject>>,
     platforms: Option<Vec<NamedObject>>,
+    collection: Option<i32>,
+    alternative_names: Option<Vec<NamedObject>>,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize, Debug)]
+struct IgdbCollectionGame {
+    id: i32,
+    name: String,
+    cover: Option<IgdbImage>,
+    #[serde_as(as = "Option<TimestampSeconds<i64, Flexible>>")]
+    first_release_date: Option<DateTimeUtc>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct IgdbCollection {
+    id: i32,
+    name: String,
+    games: Option<Vec<IgdbCollectionGame>>,
 }
 
 #[derive(Debug, Clone)]
 pub struct IgdbService {
     image_url: String,
     image_size: String,
+    auth_url: String,
     config: VideoGameConfig,
+    /// The access token that is currently in use. The lock is held while it is
+    /// refreshed so that concurrent lookups wait for a single refresh.
+    credentials: Arc<Mutex<Option<utils::Credentials>>>,
+    /// Shared by all the clients so that the limit applies to the whole
+    /// instance.
+    governor: GovernorMiddleware,
 }
 
 impl MediaProviderLanguages for IgdbService {
@@ -93,18 +128,74 @@ impl MediaProviderLanguages for IgdbService {
 
 impl IgdbService {
     pub async fn new(config: &VideoGameConfig) -> Self {
+        Self::with_auth_url(config, AUTH_URL)
+    }
+
+    fn with_auth_url(config: &VideoGameConfig, auth_url: &str) -> Self {
         Self {
             image_url: IMAGE_URL.to_owned(),
             image_size: config.igdb.image_size.to_string(),
+            auth_url: auth_url.to_owned(),
             config: config.clone(),
+            credentials: Arc::new(Mutex::new(None)),
+            governor: GovernorMiddleware::per_second(REQUESTS_PER_SECOND).unwrap(),
         }
     }
+
+    /// Get a valid access token, refreshing it if it has expired or if it is the
+    /// `rejected` one.
+    async fn access_token(&self, rejected: Option<&str>) -> Result<String> {
+        let mut credentials = self.credentials.lock().await;
+        if let Some(c) = credentials.as_ref() {
+            // DEV: when a token was rejected by several requests at once, only
+            // the first one to get here refreshes it
+            if rejected != Some(c.access_token.as_str()) && !c.has_expired() {
+                return Ok(c.access_token.clone());
+            }
+        }
+        tracing::debug!("Refreshing the IGDB access token");
+        let fresh = utils::get_access_token(&self.auth_url, &self.config).await?;
+        let token = fresh.access_token.clone();
+        *credentials = Some(fresh);
+        Ok(token)
+    }
+
+    fn client(&self, access_token: &str) -> Client {
+        get_base_http_client(
+            URL,
+            vec![
+                ("Client-ID".into(), self.config.twitch.client_id.to_owned()),
+                (AUTHORIZATION, access_token.to_owned()),
+            ],
+        )
+        .with(self.governor.clone())
+    }
+
+    /// Query an endpoint, refreshing the access token once if it is rejected.
+    async fn post<T: DeserializeOwned>(&self, endpoint: &str, body: String) -> Result<T> {
+        let access_token = self.access_token(None).await?;
+        let mut rsp = self
+            .client(&access_token)
+            .post(endpoint)
+            .body_string(body.clone())
+            .await
+            .map_err(|e| anyhow!(e))?;
+        if rsp.status() == StatusCode::Unauthorized {
+            let access_token = self.access_token(Some(&access_token)).await?;
+            rsp = self
+                .client(&access_token)
+                .post(endpoint)
+                .body_string(body)
+                .await
+                .map_err(|e| anyhow!(e))?;
+        }
+        rsp.body_json().await.map_err(|e| anyhow!(e))
+    }
 }
 
 #[async_trait]
 impl MediaProvider for IgdbService {
     async fn details(&self, identifier: &str) -> Result<MediaDetails> {
-        let client = utils::get_client(&self.config).await;
         let req_body = format!(
             r#"
 {field}
@@ -113,15 +204,19 @@ where id = {id};
             field = FIELDS,
             id = identifier
         );
-        let mut rsp = client
-            .post("games")
-            .body_string(req_body)
-            .await
-            .map_err(|e| anyhow!(e))?;
-
-        let mut details: Vec<IgdbSearchResponse> = rsp.body_json().await.map_err(|e| anyhow!(e))?;
+        let mut details: Vec<IgdbSearchResponse> = self.post("games", req_body).await?;
         let detail = details.pop().unwrap();
-        let d = self.igdb_response_to_search_response(detail);
+        let collection = detail.collection;
+        let mut d = self.igdb_response_to_search_response(detail);
+        // DEV: the game is still added when its collection can not be fetched
+        if let Some(id) = collection {
+            match self.collection_details(id).await {
+                Ok(groups) => d.groups = groups,
+                Err(e) => {
+                    tracing::warn!("Could not get the collection {id} of {identifier:?}: {e}")
+                }
+            }
+        }
         Ok(d)
     }
 
@@ -131,7 +226,6 @@ where id = {id};
         page: Option<i32>,
     ) -> Result<SearchResults<MediaSearchItem>> {
         let page = page.unwrap_or(1);
-        let client = utils::get_client(&self.config).await;
         let req_body = format!(
             r#"
 {field}
@@ -143,13 +237,7 @@ offset: {offset};
             limit = PAGE_LIMIT,
             offset = (page - 1) * PAGE_LIMIT
         );
-        let mut rsp = client
-            .post("games")
-            .body_string(req_body)
-            .await
-            .map_err(|e| anyhow!(e))?;
-
-        let search: Vec<IgdbSearchResponse> = rsp.body_json().await.map_err(|e| anyhow!(e))?;
+        let search: Vec<IgdbSearchResponse> = self.post("games", req_body).await?;
 
         // DEV: API does not return total count
         let total = 100;
@@ -173,6 +261,7 @@ offset: {offset};
                         .get(0)
                         .cloned(),
                     publish_year: a.publish_year,
+                    popularity: None,
                 }
             })
             .collect_vec();
@@ -185,6 +274,45 @@ offset: {offset};
 }
 
 impl IgdbService {
+    /// Get all the games in a collection ordered by their release date.
+    async fn collection_details(&self, collection_id: i32) -> Result<Vec<MetadataGroupDetails>> {
+        let req_body = format!(
+            r#"
+fields
+    id,
+    name,
+    games.name,
+    games.cover.*,
+    games.first_release_date;
+where id = {id};
+            "#,
+            id = collection_id
+        );
+        let collections: Vec<IgdbCollection> = self.post("collections", req_body).await?;
+        Ok(collections
+            .into_iter()
+            .map(|c| MetadataGroupDetails {
+                identifier: c.id.to_string(),
+                title: c.name,
+                parts: c
+                    .games
+                    .unwrap_or_default()
+                    .into_iter()
+                    // DEV: unreleased games come last
+                    .sorted_by_key(|g| (g.first_release_date.is_none(), g.first_release_date))
+                    .map(|g| MediaSearchItem {
+                        identifier: g.id.to_string(),
+                        lot: MetadataLot::VideoGame,
+                        title: g.name,
+                        image: g.cover.map(|c| self.get_cover_image_url(c.image_id)),
+                        publish_year: g.first_release_date.map(|d| d.year()),
+                        popularity: None,
+                    })
+                    .collect(),
+            })
+            .collect())
+    }
+
     fn igdb_response_to_search_response(&self, item: IgdbSearchResponse) -> MediaDetails {
         let mut images = Vec::from_iter(item.cover.map(|a| MetadataImage {
             url: MetadataImageUrl::Url(self.get_cover_image_url(a.image_id)),
@@ -227,6 +355,13 @@ impl IgdbService {
             })
             .unique()
             .collect();
+        let alternative_titles = alternative_titles(
+            &item.name,
+            item.alternative_names
+                .unwrap_or_default()
+                .into_iter()
+                .map(|n| n.name),
+        );
         MediaDetails {
             identifier: item.id.to_string(),
             lot: MetadataLot::VideoGame,
@@ -252,6 +387,8 @@ impl IgdbService {
                     .map(|p| p.name)
                     .collect(),
             }),
+            groups: vec![],
+            alternative_titles,
         }
     }
 
@@ -261,25 +398,25 @@ impl IgdbService {
 }
 
 mod utils {
-    use std::{env, fs};
-
     use serde_json::json;
-    use surf::{http::headers::AUTHORIZATION, Client};
 
     use super::*;
-    use crate::{
-        config::VideoGameConfig,
-        utils::{get_base_http_client, get_now_timestamp, read_file_to_json},
-    };
+    use crate::{config::VideoGameConfig, utils::get_now_timestamp};
 
-    #[derive(Deserialize, Debug, Serialize)]
-    struct Credentials {
-        access_token: String,
+    #[derive(Deserialize, Debug, Serialize, Clone)]
+    pub struct Credentials {
+        pub access_token: String,
         expires_at: u128,
     }
 
-    async fn get_access_token(config: &VideoGameConfig) -> Credentials {
-        let mut access_res = surf::post(AUTH_URL)
+    impl Credentials {
+        pub fn has_expired(&self) -> bool {
+            self.expires_at < get_now_timestamp()
+        }
+    }
+
+    pub async fn get_access_token(auth_url: &str, config: &VideoGameConfig) -> Result<Credentials> {
+        let mut access_res = surf::post(auth_url)
             .query(&json!({
                 "client_id": config.twitch.client_id.to_owned(),
                 "client_secret": config.twitch.client_secret.to_owned(),
@@ -287,7 +424,7 @@ mod utils {
             }))
             .unwrap()
             .await
-            .unwrap();
+            .map_err(|e| anyhow!(e))?;
         #[derive(Deserialize, Serialize, Default, Debug)]
         struct AccessResponse {
             access_token: String,
@@ -297,38 +434,83 @@ mod utils {
         let access = access_res
             .body_json::<AccessResponse>()
             .await
-            .unwrap_or_default();
-        let expires_at = get_now_timestamp() + (access.expires_in * 1000);
+            .map_err(|e| anyhow!("Could not get an access token from Twitch: {e}"))?;
+        // DEV: refresh a minute early so that a token does not expire in the
+        // middle of a request
+        let expires_at = get_now_timestamp() + (access.expires_in.saturating_sub(60) * 1000);
         let access_token = format!("{} {}", access.token_type, access.access_token);
-        Credentials {
+        Ok(Credentials {
             access_token,
             expires_at,
-        }
+        })
     }
+}
 
-    // Ideally, I want this to use a mutex to store the client and expiry time.
-    // However for the time being we will read and write to a file.
-    pub async fn get_client(config: &VideoGameConfig) -> Client {
-        let path = env::temp_dir().join("igdb-credentials.json");
-        let access_token =
-            if let Some(mut credential_details) = read_file_to_json::<Credentials>(&path) {
-                if credential_details.expires_at < get_now_timestamp() {
-                    tracing::debug!("Access token has expired, refreshing...");
-                    credential_details = get_access_token(config).await;
-                    fs::write(path, serde_json::to_string(&credential_details).unwrap()).ok();
-                }
-                credential_details.access_token
-            } else {
-                let creds = get_access_token(config).await;
-                fs::write(path, serde_json::to_string(&creds).unwrap()).ok();
-                creds.access_token
-            };
-        get_base_http_client(
-            URL,
-            vec![
-                ("Client-ID".into(), config.twitch.client_id.to_owned()),
-                (AUTHORIZATION, access_token),
-            ],
-        )
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::TcpListener,
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use axum::{routing::post, Json, Router, Server};
+    use futures::future::join_all;
+    use serde_json::json;
+
+    use super::*;
+    use crate::config::{IgdbConfig, IgdbImageSize, TwitchConfig};
+
+    /// Start a server that hands out a new token on every request to it.
+    fn mock_twitch(refreshes: Arc<AtomicUsize>) -> String {
+        let app = Router::new().route(
+            "/token",
+            post(move || async move {
+                let count = refreshes.fetch_add(1, Ordering::SeqCst) + 1;
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                Json(json!({
+                    "access_token": format!("token-{count}"),
+                    "token_type": "Bearer",
+                    "expires_in": 3600,
+                }))
+            }),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/token", listener.local_addr().unwrap());
+        tokio::spawn(
+            Server::from_tcp(listener)
+                .unwrap()
+                .serve(app.into_make_service()),
+        );
+        url
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_lookups_refresh_the_token_once() {
+        let refreshes = Arc::new(AtomicUsize::new(0));
+        let auth_url = mock_twitch(refreshes.clone());
+        let config = VideoGameConfig {
+            igdb: IgdbConfig {
+                image_size: IgdbImageSize::Original,
+            },
+            twitch: TwitchConfig {
+                client_id: "id".to_owned(),
+                client_secret: "secret".to_owned(),
+            },
+        };
+        let service = IgdbService::with_auth_url(&config, &auth_url);
+
+        let tokens = join_all((0..10).map(|_| service.access_token(None))).await;
+        assert!(tokens
+            .iter()
+            .all(|t| t.as_ref().unwrap() == "Bearer token-1"));
+        assert_eq!(refreshes.load(Ordering::SeqCst), 1);
+
+        // every request that got a 401 with the old token asks for a new one
+        let tokens = join_all((0..10).map(|_| service.access_token(Some("Bearer token-1")))).await;
+        assert!(tokens
+            .iter()
+            .all(|t| t.as_ref().unwrap() == "Bearer token-2"));
+        assert_eq!(refreshes.load(Ordering::SeqCst), 2);
     }
 }
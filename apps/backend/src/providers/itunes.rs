@@ -70,6 +70,7 @@ struct ITunesItem {
     artwork_url_60: Option<String>,
     artwork_url_600: Option<String>,
     track_time_millis: Option<i32>,
+    episode_guid: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -150,6 +151,7 @@ impl MediaProvider for ITunesService {
             .map(|(idx, e)| PodcastEpisode {
                 number: i32::try_from(idx).unwrap() + 1,
                 id: e.track_id.unwrap().to_string(),
+                guid: e.episode_guid,
                 runtime: e.track_time_millis.map(|t| t / 1000 / 60),
                 overview: e.description,
                 title: e.track_name.unwrap(),
@@ -172,6 +174,8 @@ impl MediaProvider for ITunesService {
                 episodes,
                 total_episodes,
             }),
+            groups: vec![],
+            alternative_titles: vec![],
         })
     }
 
@@ -235,5 +239,6 @@ fn get_search_response(item: ITunesItem) -> MediaSearchItem {
         title: item.collection_name,
         image: images.get(0).cloned(),
         publish_year,
+        popularity: None,
     }
 }
@@ -128,6 +128,7 @@ impl MediaProvider for ListennotesService {
                 title: r.title_original,
                 image: r.image,
                 publish_year: r.publish_date.map(|r| r.year()),
+                popularity: None,
             })
             .collect_vec();
         Ok(SearchResults {
@@ -210,6 +211,8 @@ impl ListennotesService {
                     .collect(),
                 total_episodes: d.total_episodes,
             }),
+            groups: vec![],
+            alternative_titles: vec![],
         })
     }
 }
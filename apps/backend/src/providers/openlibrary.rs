@@ -18,7 +18,7 @@ use crate::{
         media::{BookSpecifics, MediaDetails, MediaSearchItem},
         SearchResults,
     },
-    traits::{MediaProvider, MediaProviderLanguages},
+    traits::{IsbnProvider, MediaProvider, MediaProviderLanguages},
     utils::{get_base_http_client, get_data_parallelly_from_sources, PAGE_LIMIT},
 };
 
@@ -254,6 +254,8 @@ impl MediaProvider for OpenlibraryService {
             specifics: MediaSpecifics::Book(BookSpecifics {
                 pages: Some(num_pages),
             }),
+            groups: vec![],
+            alternative_titles: vec![],
         })
     }
 
@@ -341,6 +343,7 @@ impl MediaProvider for OpenlibraryService {
                     title: b.title,
                     image: b.images.get(0).cloned(),
                     publish_year: b.publish_year,
+                    popularity: None,
                 })
                 .collect(),
         })
@@ -364,9 +367,11 @@ impl OpenlibraryService {
         }
         None
     }
+}
 
-    /// Get a book's ID from its ISBN
-    pub async fn id_from_isbn(&self, isbn: &str) -> Option<String> {
+#[async_trait]
+impl IsbnProvider for OpenlibraryService {
+    async fn id_from_isbn(&self, isbn: &str) -> Option<String> {
         let mut resp = self
             .client
             .clone()
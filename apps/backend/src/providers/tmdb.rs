@@ -2,6 +2,7 @@ use anyhow::{anyhow, Result};
 use async_graphql::SimpleObject;
 use async_trait::async_trait;
 use itertools::Itertools;
+use rust_decimal::{prelude::FromPrimitive, Decimal};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use surf::Client;
@@ -12,12 +13,13 @@ use crate::{
     miscellaneous::{MediaSpecifics, MetadataCreator, MetadataImage, MetadataImageUrl},
     models::{
         media::{
-            MediaDetails, MediaSearchItem, MovieSpecifics, ShowEpisode, ShowSeason, ShowSpecifics,
+            MediaDetails, MediaSearchItem, MetadataGroupDetails, MovieSpecifics, ShowEpisode,
+            ShowSeason, ShowSpecifics,
         },
         NamedObject, SearchResults,
     },
     traits::{MediaProvider, MediaProviderLanguages},
-    utils::{convert_date_to_year, convert_string_to_date},
+    utils::{alternative_titles, convert_date_to_year, convert_string_to_date},
 };
 
 pub static URL: &str = "https://api.themoviedb.org/3/";
@@ -78,12 +80,21 @@ impl MediaProvider for TmdbMovieService {
             release_date: String,
             runtime: i32,
             genres: Vec<NamedObject>,
+            belongs_to_collection: Option<TmdbCollectionRef>,
+            original_title: Option<String>,
+            #[serde(default)]
+            alternative_titles: utils::TmdbAlternativeTitles,
+        }
+        #[derive(Debug, Serialize, Deserialize, Clone)]
+        struct TmdbCollectionRef {
+            id: i32,
         }
         let mut rsp = self
             .client
             .get(format!("movie/{}", &identifier))
             .query(&json!({
                 "language": self.base.language,
+                "append_to_response": "alternative_titles",
             }))
             .unwrap()
             .await
@@ -141,6 +152,26 @@ impl MediaProvider for TmdbMovieService {
             image_ids.push(u);
         }
         utils::save_all_images(&self.client, "movie", identifier, &mut image_ids).await?;
+        // DEV: the movie is still added when its collection can not be fetched
+        let groups = match data.belongs_to_collection {
+            Some(c) => match self.collection_details(c.id).await {
+                Ok(group) => vec![group],
+                Err(e) => {
+                    tracing::warn!(
+                        "Could not get the collection {id} of {identifier:?}: {e}",
+                        id = c.id
+                    );
+                    vec![]
+                }
+            },
+            None => vec![],
+        };
+        let alternative_titles = alternative_titles(
+            &data.title,
+            data.original_title
+                .into_iter()
+                .chain(data.alternative_titles.titles.into_iter().map(|t| t.title)),
+        );
 
         Ok(MediaDetails {
             identifier: data.id.to_string(),
@@ -163,6 +194,8 @@ impl MediaProvider for TmdbMovieService {
             specifics: MediaSpecifics::Movie(MovieSpecifics {
                 runtime: Some(data.runtime),
             }),
+            groups,
+            alternative_titles,
         })
     }
 
@@ -180,6 +213,7 @@ impl MediaProvider for TmdbMovieService {
             overview: Option<String>,
             title: String,
             release_date: String,
+            popularity: Option<f64>,
         }
         #[derive(Serialize, Deserialize, Debug)]
         struct TmdbSearchResponse {
@@ -209,6 +243,7 @@ impl MediaProvider for TmdbMovieService {
                 title: d.title,
                 publish_year: convert_date_to_year(&d.release_date),
                 image: d.poster_path.map(|p| self.base.get_cover_image_url(p)),
+                popularity: d.popularity.and_then(Decimal::from_f64),
             })
             .collect_vec();
         let next_page = if page < search.total_pages {
@@ -222,6 +257,90 @@ impl MediaProvider for TmdbMovieService {
             items: resp.to_vec(),
         })
     }
+
+    async fn similar(&self, identifier: &str) -> Result<Vec<MediaSearchItem>> {
+        #[derive(Debug, Serialize, Deserialize)]
+        struct TmdbMovie {
+            id: i32,
+            poster_path: Option<String>,
+            title: String,
+            #[serde(default)]
+            release_date: String,
+        }
+        #[derive(Serialize, Deserialize, Debug)]
+        struct TmdbRecommendationsResponse {
+            results: Vec<TmdbMovie>,
+        }
+        let mut rsp = self
+            .client
+            .get(format!("movie/{}/recommendations", identifier))
+            .query(&json!({ "language": self.base.language }))
+            .unwrap()
+            .await
+            .map_err(|e| anyhow!(e))?;
+        let data: TmdbRecommendationsResponse = rsp.body_json().await.map_err(|e| anyhow!(e))?;
+        Ok(data
+            .results
+            .into_iter()
+            .map(|d| MediaSearchItem {
+                identifier: d.id.to_string(),
+                lot: MetadataLot::Movie,
+                title: d.title,
+                publish_year: convert_date_to_year(&d.release_date),
+                image: d.poster_path.map(|p| self.base.get_cover_image_url(p)),
+                popularity: None,
+            })
+            .collect())
+    }
+}
+
+impl TmdbMovieService {
+    /// Get all the movies in a collection ordered by their release date.
+    async fn collection_details(&self, collection_id: i32) -> Result<MetadataGroupDetails> {
+        #[derive(Debug, Serialize, Deserialize, Clone)]
+        struct TmdbCollectionPart {
+            id: i32,
+            title: String,
+            poster_path: Option<String>,
+            release_date: Option<String>,
+        }
+        #[derive(Debug, Serialize, Deserialize, Clone)]
+        struct TmdbCollection {
+            id: i32,
+            name: String,
+            parts: Vec<TmdbCollectionPart>,
+        }
+        let mut rsp = self
+            .client
+            .get(format!("collection/{}", collection_id))
+            .query(&json!({
+                "language": self.base.language,
+            }))
+            .unwrap()
+            .await
+            .map_err(|e| anyhow!(e))?;
+        let data: TmdbCollection = rsp.body_json().await.map_err(|e| anyhow!(e))?;
+        // DEV: unreleased movies do not have a release date yet and come last
+        let parts = data
+            .parts
+            .into_iter()
+            .map(|p| (p.release_date.unwrap_or_default(), p))
+            .sorted_by(|(a, _), (b, _)| (a.is_empty(), a).cmp(&(b.is_empty(), b)))
+            .map(|(date, p)| MediaSearchItem {
+                identifier: p.id.to_string(),
+                lot: MetadataLot::Movie,
+                title: p.title,
+                image: p.poster_path.map(|p| self.base.get_cover_image_url(p)),
+                publish_year: convert_date_to_year(&date),
+                popularity: None,
+            })
+            .collect();
+        Ok(MetadataGroupDetails {
+            identifier: data.id.to_string(),
+            title: data.name,
+            parts,
+        })
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -260,17 +379,30 @@ impl MediaProvider for TmdbShowService {
             first_air_date: Option<String>,
             seasons: Vec<TmdbSeasonNumber>,
             genres: Vec<NamedObject>,
+            original_name: Option<String>,
+            #[serde(default)]
+            alternative_titles: utils::TmdbAlternativeTitles,
         }
         let mut rsp = self
             .client
             .get(format!("tv/{}", &identifier))
             .query(&json!({
                 "language": self.base.language,
+                "append_to_response": "alternative_titles",
             }))
             .unwrap()
             .await
             .map_err(|e| anyhow!(e))?;
         let data: TmdbShow = rsp.body_json().await.map_err(|e| anyhow!(e))?;
+        let alternative_titles = alternative_titles(
+            &data.name,
+            data.original_name.clone().into_iter().chain(
+                data.alternative_titles
+                    .titles
+                    .iter()
+                    .map(|t| t.title.clone()),
+            ),
+        );
         let mut image_ids = Vec::from_iter(data.poster_path);
         if let Some(u) = data.backdrop_path {
             image_ids.push(u);
@@ -428,6 +560,8 @@ impl MediaProvider for TmdbShowService {
                     })
                     .collect(),
             }),
+            groups: vec![],
+            alternative_titles,
         })
     }
 
@@ -445,6 +579,7 @@ impl MediaProvider for TmdbShowService {
             overview: Option<String>,
             name: String,
             first_air_date: String,
+            popularity: Option<f64>,
         }
         #[derive(Serialize, Deserialize, Debug)]
         struct TmdbSearchResponse {
@@ -474,6 +609,7 @@ impl MediaProvider for TmdbShowService {
                 title: d.name,
                 publish_year: convert_date_to_year(&d.first_air_date),
                 image: d.poster_path.map(|p| self.base.get_cover_image_url(p)),
+                popularity: d.popularity.and_then(Decimal::from_f64),
             })
             .collect_vec();
         let next_page = if page < search.total_pages {
@@ -487,6 +623,41 @@ impl MediaProvider for TmdbShowService {
             items: resp.to_vec(),
         })
     }
+
+    async fn similar(&self, identifier: &str) -> Result<Vec<MediaSearchItem>> {
+        #[derive(Debug, Serialize, Deserialize)]
+        struct TmdbShow {
+            id: i32,
+            poster_path: Option<String>,
+            name: String,
+            #[serde(default)]
+            first_air_date: String,
+        }
+        #[derive(Serialize, Deserialize, Debug)]
+        struct TmdbRecommendationsResponse {
+            results: Vec<TmdbShow>,
+        }
+        let mut rsp = self
+            .client
+            .get(format!("tv/{}/recommendations", identifier))
+            .query(&json!({ "language": self.base.language }))
+            .unwrap()
+            .await
+            .map_err(|e| anyhow!(e))?;
+        let data: TmdbRecommendationsResponse = rsp.body_json().await.map_err(|e| anyhow!(e))?;
+        Ok(data
+            .results
+            .into_iter()
+            .map(|d| MediaSearchItem {
+                identifier: d.id.to_string(),
+                lot: MetadataLot::Show,
+                title: d.name,
+                publish_year: convert_date_to_year(&d.first_air_date),
+                image: d.poster_path.map(|p| self.base.get_cover_image_url(p)),
+                popularity: None,
+            })
+            .collect())
+    }
 }
 
 mod utils {
@@ -505,6 +676,18 @@ mod utils {
         pub profile_path: Option<String>,
     }
 
+    #[derive(Debug, Serialize, Deserialize, Clone)]
+    pub struct TmdbAlternativeTitle {
+        pub title: String,
+    }
+
+    /// Movies list their alternative titles in `titles` and shows in `results`
+    #[derive(Debug, Serialize, Deserialize, Clone, Default)]
+    pub struct TmdbAlternativeTitles {
+        #[serde(default, alias = "results")]
+        pub titles: Vec<TmdbAlternativeTitle>,
+    }
+
     #[derive(Debug, Serialize, Deserialize, Clone)]
     pub struct TmdbImage {
         pub file_path: String,
@@ -0,0 +1,180 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use async_graphql::{Error, ErrorExtensions};
+use strum::Display;
+
+use crate::config::RateLimitConfig;
+
+/// The error code returned when an operation has been rate limited
+pub const RATE_LIMITED: &str = "RATE_LIMITED";
+
+/// The operations that are expensive enough to be rate limited
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Display)]
+pub enum RateLimitedOperation {
+    CollectionRefresh,
+    Export,
+    Import,
+    MetadataRefresh,
+    Search,
+}
+
+/// How often buckets that have refilled completely are removed
+const EVICT_EVERY: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// The buckets of a user, or of everyone who is not logged in, for each
+/// operation
+type BucketKey = (Option<i32>, RateLimitedOperation);
+
+#[derive(Debug)]
+struct Buckets {
+    buckets: HashMap<BucketKey, Bucket>,
+    evicted_on: Instant,
+}
+
+/// An in-memory token bucket rate limiter with one bucket per user and
+/// operation. Users that are not logged in share one bucket per operation.
+#[derive(Debug)]
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimiter {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        Self {
+            config: config.clone(),
+            buckets: Mutex::new(Buckets {
+                buckets: HashMap::new(),
+                evicted_on: Instant::now(),
+            }),
+        }
+    }
+
+    fn limit_per_minute(&self, operation: RateLimitedOperation) -> i32 {
+        match operation {
+            RateLimitedOperation::CollectionRefresh => self.config.collection_refresh_per_minute,
+            RateLimitedOperation::Export => self.config.export_per_minute,
+            RateLimitedOperation::Import => self.config.import_per_minute,
+            RateLimitedOperation::MetadataRefresh => self.config.metadata_refresh_per_minute,
+            RateLimitedOperation::Search => self.config.search_per_minute,
+        }
+    }
+
+    /// Take a token from the user's bucket for this operation. If the bucket is
+    /// empty, returns the duration after which a token will be available.
+    pub fn check(
+        &self,
+        user_id: Option<i32>,
+        operation: RateLimitedOperation,
+    ) -> Result<(), Duration> {
+        self.check_at(user_id, operation, Instant::now())
+    }
+
+    fn check_at(
+        &self,
+        user_id: Option<i32>,
+        operation: RateLimitedOperation,
+        now: Instant,
+    ) -> Result<(), Duration> {
+        let limit = self.limit_per_minute(operation);
+        if limit <= 0 {
+            return Ok(());
+        }
+        let capacity = limit as f64;
+        let refill_per_second = capacity / 60.0;
+        let mut buckets = self.buckets.lock().unwrap();
+        if now.duration_since(buckets.evicted_on) >= EVICT_EVERY {
+            // DEV: every bucket refills completely within a minute, so the ones
+            // that were not used since then are the same as new ones
+            buckets
+                .buckets
+                .retain(|_, b| now.duration_since(b.last_refill) < Duration::from_secs(60));
+            buckets.evicted_on = now;
+        }
+        let bucket = buckets
+            .buckets
+            .entry((user_id, operation))
+            .or_insert(Bucket {
+                tokens: capacity,
+                last_refill: now,
+            });
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * refill_per_second).min(capacity);
+        bucket.last_refill = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / refill_per_second,
+            ))
+        }
+    }
+}
+
+pub fn rate_limited_error(operation: RateLimitedOperation, retry_after: Duration) -> Error {
+    let retry_after = retry_after.as_secs() + 1;
+    Error::new(format!(
+        "Too many requests for {operation}, try again in {retry_after} seconds"
+    ))
+    .extend_with(|_, e| {
+        e.set("code", RATE_LIMITED);
+        e.set("retryAfter", retry_after);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter() -> RateLimiter {
+        RateLimiter::new(&RateLimitConfig {
+            collection_refresh_max_items: 100,
+            collection_refresh_per_minute: 1,
+            exempt_admins: true,
+            export_per_minute: 2,
+            import_per_minute: 2,
+            metadata_refresh_per_minute: 10,
+            search_per_minute: 2,
+        })
+    }
+
+    #[test]
+    fn test_anonymous_users_share_a_bucket() {
+        let limiter = limiter();
+        let now = Instant::now();
+        let search = RateLimitedOperation::Search;
+        assert!(limiter.check_at(None, search, now).is_ok());
+        assert!(limiter.check_at(None, search, now).is_ok());
+        assert!(limiter.check_at(None, search, now).is_err());
+        assert!(limiter.check_at(Some(1), search, now).is_ok());
+    }
+
+    #[test]
+    fn test_unused_buckets_are_evicted() {
+        let limiter = limiter();
+        let now = Instant::now();
+        let search = RateLimitedOperation::Search;
+        limiter.check_at(Some(1), search, now).unwrap();
+        limiter
+            .check_at(Some(2), search, now + Duration::from_secs(30))
+            .unwrap();
+        limiter
+            .check_at(Some(3), search, now + Duration::from_secs(61))
+            .unwrap();
+        let buckets = limiter.buckets.lock().unwrap();
+        assert!(!buckets.buckets.contains_key(&(Some(1), search)));
+        assert!(buckets.buckets.contains_key(&(Some(2), search)));
+        assert!(buckets.buckets.contains_key(&(Some(3), search)));
+    }
+}
@@ -1,6 +1,10 @@
-use std::sync::Arc;
+use std::{
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use async_graphql::http::GraphiQLSource;
 use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
 use axum::{
@@ -13,19 +17,28 @@ use axum::{
 };
 use http::header::AUTHORIZATION;
 use rust_embed::RustEmbed;
+use sea_orm::{ConnectionTrait, Statement};
+use serde::Serialize;
 use serde_json::json;
+use sqlx::SqlitePool;
+use tokio::time::timeout;
 use tower_cookies::Cookies;
 use uuid::Uuid;
 
 use crate::{
+    background::{oldest_pending_job, pending_job_count},
     config::AppConfig,
     file_storage::FileStorageService,
-    graphql::GraphqlSchema,
+    graphql::{operation_label, GraphqlSchema},
     miscellaneous::resolver::MiscellaneousService,
+    monitoring::{record_graphql_request, record_oldest_pending_job},
+    providers::tmdb::URL as TMDB_URL,
+    rate_limiter::RateLimitedOperation,
     utils::{user_id_from_token, GqlCtx, COOKIE_NAME},
 };
 
 static INDEX_HTML: &str = "index.html";
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(3);
 
 #[derive(RustEmbed)]
 #[folder = "../frontend/out/"]
@@ -93,7 +106,11 @@ pub async fn graphql_handler(
         ctx.auth_token = h.to_str().map(strip).ok();
     }
     req = req.data(ctx);
-    schema.execute(req).await.into()
+    let operation = operation_label(&schema, &req);
+    let started_at = Instant::now();
+    let resp = schema.execute(req).await;
+    record_graphql_request(operation, resp.is_ok(), started_at.elapsed());
+    resp.into()
 }
 
 pub async fn graphql_playground() -> impl IntoResponse {
@@ -138,6 +155,15 @@ pub async fn json_export(
     let user_id = user_id_from_token(authorization.token().to_owned(), &media_service.auth_db)
         .await
         .map_err(|e| (StatusCode::FORBIDDEN, Json(json!({"err": e.message}))))?;
+    media_service
+        .rate_limit_guard(Some(user_id), RateLimitedOperation::Export)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                Json(json!({"err": e.message})),
+            )
+        })?;
     let resp = media_service.export(user_id).await.unwrap();
     Ok(Json(json!(resp)))
 }
@@ -156,3 +182,122 @@ pub async fn integration_webhook(
         })?;
     Ok(StatusCode::OK)
 }
+
+#[derive(Debug, Serialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum HealthStatus {
+    Ok,
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Serialize)]
+struct HealthCheck {
+    name: &'static str,
+    status: HealthStatus,
+    latency_ms: u128,
+    error: Option<String>,
+}
+
+async fn run_health_check<F>(name: &'static str, required: bool, check: F) -> HealthCheck
+where
+    F: Future<Output = Result<()>>,
+{
+    let start = Instant::now();
+    let result = match timeout(HEALTH_CHECK_TIMEOUT, check).await {
+        Ok(r) => r,
+        Err(_) => Err(anyhow!("Timed out after {:?}", HEALTH_CHECK_TIMEOUT)),
+    };
+    let (status, error) = match result {
+        Ok(_) => (HealthStatus::Ok, None),
+        Err(e) if required => (HealthStatus::Error, Some(e.to_string())),
+        Err(e) => (HealthStatus::Warning, Some(e.to_string())),
+    };
+    HealthCheck {
+        name,
+        status,
+        latency_ms: start.elapsed().as_millis(),
+        error,
+    }
+}
+
+pub async fn health_live() -> impl IntoResponse {
+    Json(json!({ "status": HealthStatus::Ok }))
+}
+
+pub async fn health_ready(
+    Extension(media_service): Extension<Arc<MiscellaneousService>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    Extension(scheduler_pool): Extension<SqlitePool>,
+) -> impl IntoResponse {
+    let db = &media_service.db;
+    let mut checks = vec![
+        run_health_check("database", true, async {
+            db.execute(Statement::from_string(
+                db.get_database_backend(),
+                "SELECT 1".to_owned(),
+            ))
+            .await?;
+            Ok(())
+        })
+        .await,
+        run_health_check("scheduler", true, async {
+            sqlx::query("SELECT 1").execute(&scheduler_pool).await?;
+            Ok(())
+        })
+        .await,
+    ];
+    if config.server.readiness_probe_provider {
+        checks.push(
+            run_health_check(
+                "provider",
+                config.server.readiness_requires_provider,
+                async {
+                    surf::get(TMDB_URL).await.map_err(|e| anyhow!(e))?;
+                    Ok(())
+                },
+            )
+            .await,
+        );
+    }
+    let max_job_wait = config.server.readiness_max_job_wait;
+    // DEV: jobs are put back in the queue while the workers are paused, so the
+    // wait of the oldest one says nothing about whether they are picked up
+    if media_service.jobs.is_paused() {
+        checks.push(
+            run_health_check("worker", false, async {
+                let pending = pending_job_count(&scheduler_pool).await?;
+                Err(anyhow!(
+                    "The workers are paused with {pending} jobs waiting in the queue"
+                ))
+            })
+            .await,
+        );
+    } else if max_job_wait > 0 {
+        checks.push(
+            run_health_check("worker", true, async {
+                let oldest = oldest_pending_job(&scheduler_pool).await?;
+                record_oldest_pending_job(oldest.as_ref().map_or(0, |(_, age)| *age));
+                match oldest {
+                    Some((job, age)) if age > max_job_wait => Err(anyhow!(
+                        "A {job} job has been waiting for {age} seconds to be picked up"
+                    )),
+                    _ => Ok(()),
+                }
+            })
+            .await,
+        );
+    }
+    let status = if checks.iter().any(|c| c.status == HealthStatus::Error) {
+        HealthStatus::Error
+    } else if checks.iter().any(|c| c.status == HealthStatus::Warning) {
+        HealthStatus::Warning
+    } else {
+        HealthStatus::Ok
+    };
+    let code = match status {
+        HealthStatus::Error => StatusCode::SERVICE_UNAVAILABLE,
+        _ => StatusCode::OK,
+    };
+    (code, Json(json!({ "status": status, "checks": checks })))
+}
@@ -0,0 +1,113 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use tokio::{sync::Notify, time::timeout};
+
+/// Coordinates a graceful shutdown between the HTTP server and the background
+/// workers. Long running jobs check `is_requested` between items and stop
+/// early once a shutdown has been requested, and the workers stop starting new
+/// jobs.
+#[derive(Debug, Default)]
+pub struct ShutdownSignal {
+    requested: AtomicBool,
+    in_flight: AtomicUsize,
+    notify: Notify,
+}
+
+/// Marks a job as running until it is dropped.
+pub struct InFlightGuard(Arc<ShutdownSignal>);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::SeqCst);
+        self.0.notify.notify_waiters();
+    }
+}
+
+impl ShutdownSignal {
+    pub fn is_requested(&self) -> bool {
+        self.requested.load(Ordering::SeqCst)
+    }
+
+    pub fn request(&self) {
+        self.requested.store(true, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    pub fn track(self: &Arc<Self>) -> InFlightGuard {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        InFlightGuard(self.clone())
+    }
+
+    /// Resolves once a shutdown has been requested.
+    pub async fn requested(&self) {
+        loop {
+            let notified = self.notify.notified();
+            if self.is_requested() {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    /// Waits for all tracked jobs to finish. Returns `false` if some of them
+    /// were still running after the grace period.
+    pub async fn drain(&self, grace_period: Duration) -> bool {
+        let drained = async {
+            loop {
+                let notified = self.notify.notified();
+                if self.in_flight.load(Ordering::SeqCst) == 0 {
+                    return;
+                }
+                notified.await;
+            }
+        };
+        timeout(grace_period, drained).await.is_ok()
+    }
+}
+
+/// Resolves when the process receives `SIGINT` or `SIGTERM`.
+pub async fn wait_for_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Could not install the Ctrl+C handler");
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Could not install the SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_drain_waits_for_tracked_jobs() {
+        let signal = Arc::new(ShutdownSignal::default());
+        let guard = signal.track();
+        signal.request();
+        signal.requested().await;
+        assert!(!signal.drain(Duration::from_millis(10)).await);
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            drop(guard);
+        });
+        assert!(signal.drain(Duration::from_secs(1)).await);
+    }
+}
@@ -21,6 +21,17 @@ pub trait MediaProvider {
 
     /// Get details about a media item for the particular identifier.
     async fn details(&self, identifier: &str) -> Result<MediaDetails>;
+
+    /// Get media items similar to the one with the particular identifier.
+    async fn similar(&self, _identifier: &str) -> Result<Vec<MediaSearchItem>> {
+        Ok(vec![])
+    }
+}
+
+#[async_trait]
+pub trait IsbnProvider {
+    /// Get the identifier of a book from its ISBN.
+    async fn id_from_isbn(&self, isbn: &str) -> Option<String>;
 }
 
 pub trait MediaProviderLanguages {
@@ -1,10 +1,22 @@
-use async_graphql::SimpleObject;
+use anyhow::{anyhow, bail, Result};
+use async_graphql::{Enum, InputObject, SimpleObject};
+use rust_decimal::Decimal;
 use sea_orm::{prelude::DateTimeUtc, FromJsonQueryResult};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    importer::ImportMetadataEnrichment,
+    migrator::{MetadataLot, MetadataSource},
+    models::media::Visibility,
+    providers::{audible::AudibleService, tmdb::TmdbService},
+    traits::MediaProviderLanguages,
+};
 
 #[derive(
     Debug, Serialize, Deserialize, SimpleObject, Clone, Eq, PartialEq, FromJsonQueryResult,
 )]
+#[serde(default)]
 pub struct UserFeaturesEnabledPreferences {
     pub anime: bool,
     pub audio_books: bool,
@@ -31,12 +43,131 @@ impl Default for UserFeaturesEnabledPreferences {
     }
 }
 
+#[derive(Debug, Serialize, Deserialize, SimpleObject, Clone, Eq, PartialEq)]
+#[serde(default)]
+pub struct UserGeneralPreferences {
+    /// The IANA name of the timezone the user is in.
+    pub timezone: String,
+    /// The number of days after which untouched in progress seen items are
+    /// marked as stale. Disabled if not set.
+    pub stale_in_progress_after_days: Option<i32>,
+    /// Progress at or above this percentage is recorded as completed. Uses the
+    /// `integration.maximum_progress_limit` of the instance if not set.
+    pub completion_threshold_percent: Option<i32>,
+}
+
+impl Default for UserGeneralPreferences {
+    fn default() -> Self {
+        Self {
+            timezone: "UTC".to_owned(),
+            stale_in_progress_after_days: None,
+            completion_threshold_percent: None,
+        }
+    }
+}
+
+/// The service that a notification webhook belongs to, which decides the shape
+/// of the payload sent to it
+#[derive(Debug, Serialize, Deserialize, Enum, Clone, Copy, Eq, PartialEq, Default)]
+pub enum UserNotificationPlatform {
+    #[default]
+    Generic,
+    Discord,
+    Slack,
+}
+
+#[derive(Debug, Serialize, Deserialize, SimpleObject, Clone, Eq, PartialEq)]
+#[serde(default)]
+pub struct UserNotificationPreferences {
+    /// Whether notifications will be sent to the user.
+    pub enabled: bool,
+    /// The url that notifications are posted to. It must point to a public
+    /// host.
+    pub webhook_url: Option<String>,
+    pub webhook_platform: UserNotificationPlatform,
+}
+
+impl Default for UserNotificationPreferences {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            webhook_url: None,
+            webhook_platform: UserNotificationPlatform::default(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Enum, Clone, Copy, Eq, PartialEq, Default)]
+pub enum UserUnitSystem {
+    #[default]
+    Metric,
+    Imperial,
+}
+
+#[derive(Debug, Serialize, Deserialize, SimpleObject, Clone, Eq, PartialEq, Default)]
+#[serde(default)]
+pub struct UserFitnessPreferences {
+    pub unit_system: UserUnitSystem,
+}
+
+/// The options used when an import job does not specify them
+#[derive(Debug, Serialize, Deserialize, SimpleObject, Clone, Eq, PartialEq, Default)]
+#[serde(default)]
+pub struct UserImportDefaultsPreferences {
+    pub metadata_enrichment: ImportMetadataEnrichment,
+    /// Do not import seen history for media that already has some.
+    pub skip_existing_seen: bool,
+    pub review_visibility: Option<Visibility>,
+    /// Mark all imported reviews as containing spoilers.
+    pub mark_imported_reviews_spoiler: bool,
+    /// Only import media of these types. All types are imported if empty.
+    pub lots: Vec<MetadataLot>,
+    /// The collection that items from watchlist-like shelves are added to.
+    pub watchlist_collection: Option<String>,
+    /// Record seen history without an end date as in progress.
+    pub undated_seen_in_progress: bool,
+    /// Items matched by their title below this confidence are treated as
+    /// unmatched.
+    pub min_match_confidence: Option<Decimal>,
+}
+
 #[derive(
     Debug, Serialize, Deserialize, SimpleObject, Clone, Eq, PartialEq, Default, FromJsonQueryResult,
 )]
+#[serde(default)]
 pub struct UserPreferences {
-    #[serde(default)]
     pub features_enabled: UserFeaturesEnabledPreferences,
+    pub general: UserGeneralPreferences,
+    pub notifications: UserNotificationPreferences,
+    pub fitness: UserFitnessPreferences,
+    pub import_defaults: UserImportDefaultsPreferences,
+}
+
+impl UserPreferences {
+    /// The preferences that are stored encrypted.
+    pub fn secrets_mut(&mut self) -> Vec<&mut String> {
+        self.notifications.webhook_url.iter_mut().collect()
+    }
+
+    /// Set a single preference using its dot separated path (for eg:
+    /// `general.timezone`). The value must be valid for the type of the
+    /// preference.
+    pub fn update_path(&self, path: &str, value: Value) -> Result<Self> {
+        let mut preferences = serde_json::to_value(self)?;
+        let mut current = &mut preferences;
+        for key in path.split('.') {
+            current = current
+                .as_object_mut()
+                .and_then(|o| o.get_mut(key))
+                .ok_or_else(|| anyhow!("Preference `{path}` does not exist"))?;
+        }
+        if current.is_object() {
+            bail!("Preference `{path}` is a section and can not be set directly");
+        }
+        *current = value;
+        serde_json::from_value(preferences)
+            .map_err(|e| anyhow!("Invalid value for preference `{path}`: {e}"))
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq, FromJsonQueryResult)]
@@ -45,6 +176,15 @@ pub enum UserYankIntegrationSetting {
     Audiobookshelf { base_url: String, token: String },
 }
 
+impl UserYankIntegrationSetting {
+    /// The secrets of the integration, which are stored encrypted.
+    pub fn secrets_mut(&mut self) -> Vec<&mut String> {
+        match self {
+            Self::Audiobookshelf { token, .. } => vec![token],
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq, FromJsonQueryResult)]
 pub struct UserYankIntegration {
     pub id: usize,
@@ -62,6 +202,15 @@ pub enum UserSinkIntegrationSetting {
     Jellyfin { slug: String },
 }
 
+impl UserSinkIntegrationSetting {
+    /// The secrets of the integration, which are stored encrypted.
+    pub fn secrets_mut(&mut self) -> Vec<&mut String> {
+        match self {
+            Self::Jellyfin { slug } => vec![slug],
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq, FromJsonQueryResult)]
 pub struct UserSinkIntegration {
     pub id: usize,
@@ -72,3 +221,124 @@ pub struct UserSinkIntegration {
 
 #[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq, FromJsonQueryResult)]
 pub struct UserSinkIntegrations(pub Vec<UserSinkIntegration>);
+
+/// The credentials of a provider that a user has configured, which are used
+/// instead of the ones of the instance.
+#[derive(Debug, Serialize, Deserialize, InputObject, Clone, Default, Eq, PartialEq)]
+pub struct UserProviderCredentials {
+    /// The API token, used by Listennotes and TMDB.
+    pub api_token: Option<String>,
+    /// The client ID, used by IGDB.
+    pub client_id: Option<String>,
+    /// The client secret, used by IGDB.
+    pub client_secret: Option<String>,
+    /// The region of the Audible catalog, or the language of TMDB.
+    pub region: Option<String>,
+}
+
+impl UserProviderCredentials {
+    /// Check that the credentials have the fields that the provider needs.
+    pub fn validate(&self, source: MetadataSource) -> Result<()> {
+        let required = |field: &Option<String>, name: &str| match field {
+            Some(v) if !v.trim().is_empty() => Ok(()),
+            _ => Err(anyhow!("{source:?} credentials need a `{name}`")),
+        };
+        let region = |supported: Vec<String>| match &self.region {
+            Some(r) if !supported.contains(r) => {
+                Err(anyhow!("{source:?} does not support the region {r:?}"))
+            }
+            _ => Ok(()),
+        };
+        match source {
+            MetadataSource::Audible => {
+                required(&self.region, "region")?;
+                region(AudibleService::supported_languages())
+            }
+            MetadataSource::Igdb => {
+                required(&self.client_id, "client_id")?;
+                required(&self.client_secret, "client_secret")
+            }
+            MetadataSource::Listennotes => required(&self.api_token, "api_token"),
+            MetadataSource::Tmdb => {
+                required(&self.api_token, "api_token")?;
+                region(TmdbService::supported_languages())
+            }
+            _ => bail!("{source:?} does not use credentials"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use serde_json::json;
+
+    #[rstest]
+    #[case(json!({}))]
+    #[case(json!({ "features_enabled": { "anime": true } }))]
+    #[case(json!({ "general": {}, "notifications": {}, "fitness": {} }))]
+    #[case(json!({ "general": { "a_preference_from_the_future": 1 }, "unknown_section": {} }))]
+    fn test_preferences_apply_defaults(#[case] stored: Value) {
+        let preferences: UserPreferences = serde_json::from_value(stored).unwrap();
+        assert_eq!(preferences, UserPreferences::default());
+    }
+
+    #[test]
+    fn test_preferences_keep_stored_values() {
+        let stored = json!({
+            "features_enabled": { "books": false },
+            "general": { "timezone": "Asia/Kolkata" }
+        });
+        let preferences: UserPreferences = serde_json::from_value(stored).unwrap();
+        assert!(!preferences.features_enabled.books);
+        assert!(preferences.features_enabled.anime);
+        assert_eq!(preferences.general.timezone, "Asia/Kolkata");
+    }
+
+    #[rstest]
+    #[case("general.timezone", json!("Europe/Berlin"), true)]
+    #[case("general.stale_in_progress_after_days", json!(30), true)]
+    #[case("general.stale_in_progress_after_days", json!(null), true)]
+    #[case("general.completion_threshold_percent", json!(90), true)]
+    #[case("fitness.unit_system", json!("Imperial"), true)]
+    #[case("features_enabled.anime", json!(false), true)]
+    #[case("features_enabled.anime", json!("no"), false)]
+    #[case("fitness.unit_system", json!("Parsecs"), false)]
+    #[case("general", json!("UTC"), false)]
+    #[case("general.does_not_exist", json!(1), false)]
+    #[case("", json!(1), false)]
+    fn test_preferences_update_path(
+        #[case] path: &str,
+        #[case] value: Value,
+        #[case] is_valid: bool,
+    ) {
+        let updated = UserPreferences::default().update_path(path, value);
+        assert_eq!(updated.is_ok(), is_valid);
+    }
+
+    #[rstest]
+    #[case(MetadataSource::Audible, None, None, Some("gb"), true)]
+    #[case(MetadataSource::Audible, None, None, Some("mars"), false)]
+    #[case(MetadataSource::Audible, Some("token"), None, None, false)]
+    #[case(MetadataSource::Igdb, None, Some("id"), None, false)]
+    #[case(MetadataSource::Listennotes, Some("token"), None, None, true)]
+    #[case(MetadataSource::Listennotes, Some(" "), None, None, false)]
+    #[case(MetadataSource::Tmdb, Some("token"), None, Some("de"), true)]
+    #[case(MetadataSource::Openlibrary, Some("token"), None, None, false)]
+    fn test_provider_credentials_validate(
+        #[case] source: MetadataSource,
+        #[case] api_token: Option<&str>,
+        #[case] client_id: Option<&str>,
+        #[case] region: Option<&str>,
+        #[case] is_valid: bool,
+    ) {
+        let credentials = UserProviderCredentials {
+            api_token: api_token.map(String::from),
+            client_id: client_id.map(String::from),
+            client_secret: None,
+            region: region.map(String::from),
+        };
+        assert_eq!(credentials.validate(source).is_ok(), is_valid);
+    }
+}
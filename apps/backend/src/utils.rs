@@ -3,7 +3,7 @@ use std::{
     io::Read,
     path::PathBuf,
     sync::Arc,
-    time::{SystemTime, UNIX_EPOCH},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use apalis::sqlite::SqliteStorage;
@@ -14,6 +14,7 @@ use darkbird::{
     Storage,
 };
 use http_types::headers::HeaderName;
+use rust_decimal::Decimal;
 use sea_orm::{
     prelude::DateTimeUtc, ActiveModelTrait, ActiveValue, ConnectionTrait, DatabaseConnection,
 };
@@ -23,22 +24,26 @@ use serde::{
     Deserialize, Serialize,
 };
 use surf::{
-    http::headers::{ToHeaderValues, USER_AGENT},
-    Client, Config, Url,
+    http::headers::{ToHeaderValues, RETRY_AFTER, USER_AGENT},
+    middleware::{Middleware, Next},
+    Client, Config, Request, Response, StatusCode, Url,
 };
 use tokio::task::JoinSet;
 
 use crate::{
     background::{
-        ImportMedia, RecalculateUserSummaryJob, UpdateExerciseJob, UpdateMetadataJob,
-        UserCreatedJob,
+        BulkMediaActionJob, ImportMedia, RecalculateUserSummaryJob, UpdateExerciseJob,
+        UpdateMetadataBatchJob, UpdateMetadataJob, UserCreatedJob,
     },
     config::AppConfig,
     entities::user_to_metadata,
     file_storage::FileStorageService,
     fitness::exercise::resolver::ExerciseService,
     importer::ImporterService,
+    migrator::SeenDatePrecision,
     miscellaneous::resolver::MiscellaneousService,
+    monitoring::{record_provider_failure, record_provider_request},
+    shutdown::ShutdownSignal,
 };
 
 pub type MemoryDatabase = Arc<Storage<String, MemoryAuthData>>;
@@ -51,6 +56,17 @@ pub const AUTHOR: &str = "ignisda";
 pub const PROJECT_NAME: &str = env!("CARGO_PKG_NAME");
 pub const REPOSITORY_LINK: &str = "https://github.com/ignisda/ryot";
 pub const USER_AGENT_STR: &str = const_str::concat!(AUTHOR, "/", PROJECT_NAME);
+/// The maximum number of times a request will be retried when a provider
+/// responds with `429 Too Many Requests`.
+pub const MAX_RETRY_AFTER_ATTEMPTS: usize = 5;
+/// The longest `Retry-After` that is waited for. A provider asking for a longer
+/// wait is not retried, so that a job is not stuck for hours.
+pub const MAX_RETRY_AFTER_WAIT: Duration = Duration::from_secs(120);
+/// The maximum number of times queueing the summary recalculation after an
+/// import will be attempted.
+pub const MAX_SUMMARY_JOB_ATTEMPTS: u64 = 3;
+/// The highest rating a review can have. The lowest is zero.
+pub const MAX_RATING: Decimal = Decimal::ONE_HUNDRED;
 
 /// All the services that are used by the app
 pub struct AppServices {
@@ -70,7 +86,10 @@ pub async fn create_app_services(
     user_created_job: &SqliteStorage<UserCreatedJob>,
     update_exercise_job: &SqliteStorage<UpdateExerciseJob>,
     update_metadata_job: &SqliteStorage<UpdateMetadataJob>,
+    update_metadata_batch_job: &SqliteStorage<UpdateMetadataBatchJob>,
     recalculate_user_summary_job: &SqliteStorage<RecalculateUserSummaryJob>,
+    bulk_media_action_job: &SqliteStorage<BulkMediaActionJob>,
+    shutdown: Arc<ShutdownSignal>,
 ) -> AppServices {
     let file_storage_service = Arc::new(FileStorageService::new(
         s3_client,
@@ -91,8 +110,11 @@ pub async fn create_app_services(
             config,
             file_storage_service.clone(),
             update_metadata_job,
+            update_metadata_batch_job,
             recalculate_user_summary_job,
             user_created_job,
+            bulk_media_action_job,
+            shutdown.clone(),
         )
         .await,
     );
@@ -100,6 +122,7 @@ pub async fn create_app_services(
         &db,
         media_service.clone(),
         import_media_job,
+        shutdown,
     ));
     AppServices {
         media_service,
@@ -143,6 +166,97 @@ pub fn convert_string_to_date(d: &str) -> Option<NaiveDate> {
     NaiveDate::parse_from_str(d, "%Y-%m-%d").ok()
 }
 
+/// The formats that are tried (in order) when the format of the dates of a file
+/// is not known
+// DEV: `%Y` also accepts years with less than four digits, so the formats with two
+// digit years need to be tried first.
+pub const AUTO_DETECT_DATE_FORMATS: [&str; 8] = [
+    "%m-%d-%y", "%m/%d/%y", "%Y-%m-%d", "%Y/%m/%d", "%d/%m/%Y", "%d-%m-%Y", "%d.%m.%Y", "%m/%d/%Y",
+];
+
+/// Parse a date using the given format. The format can either be a `chrono`
+/// format string (`%d/%m/%Y`) or a human readable one (`DD/MM/YYYY`).
+pub fn convert_string_to_date_with_format(d: &str, format: &str) -> Option<NaiveDate> {
+    let d = d.trim();
+    // DEV: some exports include the time along with the date
+    let d = d.split(&[' ', 'T'][..]).next().unwrap_or(d);
+    let format = if format.contains('%') {
+        format.to_owned()
+    } else {
+        format
+            .replace("YYYY", "%Y")
+            .replace("YY", "%y")
+            .replace("MM", "%m")
+            .replace("DD", "%d")
+    };
+    NaiveDate::parse_from_str(d, &format).ok()
+}
+
+/// The format of `AUTO_DETECT_DATE_FORMATS` that parses the most of the dates.
+/// It is used for all the dates of a file, so that "03/04/2023" is not read
+/// with a different day and month order than "25/04/2023". Ties go to the
+/// format that comes first.
+pub fn detect_date_format<'a>(dates: impl IntoIterator<Item = &'a str>) -> Option<&'static str> {
+    let dates = dates.into_iter().collect::<Vec<_>>();
+    AUTO_DETECT_DATE_FORMATS
+        .iter()
+        .map(|f| {
+            let parsed = dates
+                .iter()
+                .filter(|d| convert_string_to_date_with_format(d, f).is_some())
+                .count();
+            (*f, parsed)
+        })
+        .rev()
+        .max_by_key(|(_, parsed)| *parsed)
+        .filter(|(_, parsed)| *parsed > 0)
+        .map(|(f, _)| f)
+}
+
+/// Parse a date that might only name its month or year, for eg "Jan 2015" or
+/// "2015", after trying the format. Such dates are returned as the first day of
+/// the period they name.
+pub fn convert_string_to_fuzzy_date(
+    d: &str,
+    format: &str,
+) -> Option<(NaiveDate, SeenDatePrecision)> {
+    if let Some(date) = convert_string_to_date_with_format(d, format) {
+        return Some((date, SeenDatePrecision::Day));
+    }
+    let d = d.trim();
+    let month = ["%b %Y", "%B %Y", "%Y/%m", "%Y-%m", "%m/%Y"]
+        .iter()
+        .find_map(|f| NaiveDate::parse_from_str(&format!("1 {d}"), &format!("%d {f}")).ok());
+    if let Some(date) = month {
+        return Some((date, SeenDatePrecision::Month));
+    }
+    d.parse::<i32>()
+        .ok()
+        .filter(|y| (1000..=9999).contains(y))
+        .and_then(|y| NaiveDate::from_ymd_opt(y, 1, 1))
+        .map(|date| (date, SeenDatePrecision::Year))
+}
+
+/// The other titles a provider lists for a media item, without blanks,
+/// repetitions and the main title itself.
+pub fn alternative_titles(
+    title: &str,
+    candidates: impl IntoIterator<Item = String>,
+) -> Vec<String> {
+    let mut seen = vec![title.trim().to_lowercase()];
+    let mut titles = vec![];
+    for candidate in candidates {
+        let candidate = candidate.trim();
+        let key = candidate.to_lowercase();
+        if candidate.is_empty() || seen.contains(&key) {
+            continue;
+        }
+        seen.push(key);
+        titles.push(candidate.to_owned());
+    }
+    titles
+}
+
 pub fn convert_date_to_year(d: &str) -> Option<i32> {
     convert_string_to_date(d).map(|d| d.format("%Y").to_string().parse::<i32>().unwrap())
 }
@@ -205,10 +319,93 @@ pub fn get_base_http_client(
     for (header, value) in headers.into_iter() {
         config = config.add_header(header, value).unwrap();
     }
-    config
+    let client: Client = config
         .set_base_url(Url::parse(url).unwrap())
         .try_into()
-        .unwrap()
+        .unwrap();
+    client
+        .with(RetryAfterMiddleware)
+        .with(ProviderMetricsMiddleware)
+}
+
+/// A middleware that records the latency of all requests made to providers, and
+/// the ones that failed because of the provider.
+#[derive(Debug)]
+pub struct ProviderMetricsMiddleware;
+
+#[surf::utils::async_trait]
+impl Middleware for ProviderMetricsMiddleware {
+    async fn handle(&self, req: Request, client: Client, next: Next<'_>) -> surf::Result<Response> {
+        let host = req.url().host_str().unwrap_or_default().to_owned();
+        let start = Instant::now();
+        let res = next.run(req, client).await;
+        record_provider_request(&host, start.elapsed());
+        if res.as_ref().map_or(true, |r| r.status().is_server_error()) {
+            record_provider_failure();
+        }
+        res
+    }
+}
+
+/// Parse the value of a `Retry-After` header. It can either be a number of
+/// seconds or a HTTP date.
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    let wait = date.with_timezone(&Utc) - Utc::now();
+    Some(wait.to_std().unwrap_or_default())
+}
+
+/// The time to wait before retrying a rate limited request, if it should be
+/// retried at all.
+fn retry_after_wait(value: &str) -> Option<Duration> {
+    parse_retry_after(value).filter(|w| *w <= MAX_RETRY_AFTER_WAIT)
+}
+
+/// A middleware that sleeps for the duration specified by the `Retry-After`
+/// header when a provider responds with `429 Too Many Requests` and then
+/// retries the request, unless the wait is longer than `MAX_RETRY_AFTER_WAIT`.
+#[derive(Debug)]
+pub struct RetryAfterMiddleware;
+
+#[surf::utils::async_trait]
+impl Middleware for RetryAfterMiddleware {
+    async fn handle(
+        &self,
+        mut req: Request,
+        client: Client,
+        next: Next<'_>,
+    ) -> surf::Result<Response> {
+        // the body is not preserved when cloning a request
+        let body = req.take_body().into_bytes().await?;
+        let mut attempt = 0;
+        loop {
+            let mut new_req = req.clone();
+            new_req.set_body(body.clone());
+            let res = next.run(new_req, client.clone()).await?;
+            if res.status() != StatusCode::TooManyRequests || attempt >= MAX_RETRY_AFTER_ATTEMPTS {
+                return Ok(res);
+            }
+            let wait = match res
+                .header(RETRY_AFTER)
+                .and_then(|h| retry_after_wait(h.last().as_str()))
+            {
+                Some(w) => w,
+                None => return Ok(res),
+            };
+            attempt += 1;
+            tracing::warn!(
+                "Rate limited by {}, retrying in {:?} (attempt {})",
+                req.url(),
+                wait,
+                attempt
+            );
+            tokio::time::sleep(wait).await;
+        }
+    }
 }
 
 pub fn get_case_insensitive_like_query<E>(expr: E, v: &str) -> SimpleExpr
@@ -264,3 +461,78 @@ impl FullText for MemoryAuthData {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("30", Some(Duration::from_secs(30)))]
+    #[case("120", Some(Duration::from_secs(120)))]
+    #[case("3600", None)]
+    #[case("soon", None)]
+    fn test_retry_after_wait(#[case] value: &str, #[case] expected: Option<Duration>) {
+        assert_eq!(retry_after_wait(value), expected);
+    }
+
+    #[rstest]
+    #[case("2023-05-01", "%Y-%m-%d", Some((2023, 5, 1)))]
+    #[case("2023-05-01 10:20:30", "YYYY-MM-DD", Some((2023, 5, 1)))]
+    #[case("05/01/2023", "MM/DD/YYYY", Some((2023, 5, 1)))]
+    #[case("05-01-23", "MM-DD-YY", Some((2023, 5, 1)))]
+    #[case("01.05.2023", "%d.%m.%Y", Some((2023, 5, 1)))]
+    #[case("2023-05-01", "DD/MM/YYYY", None)]
+    #[case("not a date", "%Y-%m-%d", None)]
+    fn test_convert_string_to_date_with_format(
+        #[case] input: &str,
+        #[case] format: &str,
+        #[case] expected: Option<(i32, u32, u32)>,
+    ) {
+        let expected = expected.map(|(y, m, d)| NaiveDate::from_ymd_opt(y, m, d).unwrap());
+        assert_eq!(convert_string_to_date_with_format(input, format), expected);
+    }
+
+    #[rstest]
+    #[case("2015/01/12", Some((2015, 1, 12, SeenDatePrecision::Day)))]
+    #[case("Jan 2015", Some((2015, 1, 1, SeenDatePrecision::Month)))]
+    #[case("January 2015", Some((2015, 1, 1, SeenDatePrecision::Month)))]
+    #[case("2015/03", Some((2015, 3, 1, SeenDatePrecision::Month)))]
+    #[case("2015", Some((2015, 1, 1, SeenDatePrecision::Year)))]
+    #[case("15", None)]
+    #[case("sometime", None)]
+    fn test_convert_string_to_fuzzy_date(
+        #[case] input: &str,
+        #[case] expected: Option<(i32, u32, u32, SeenDatePrecision)>,
+    ) {
+        let expected = expected.map(|(y, m, d, p)| (NaiveDate::from_ymd_opt(y, m, d).unwrap(), p));
+        assert_eq!(convert_string_to_fuzzy_date(input, "%Y/%m/%d"), expected);
+    }
+
+    #[rstest]
+    #[case(&["2023-05-01", "2023-05-13"], Some("%Y-%m-%d"))]
+    #[case(&["03/04/2023", "25/04/2023"], Some("%d/%m/%Y"))]
+    #[case(&["03/04/2023", "04/25/2023"], Some("%m/%d/%Y"))]
+    #[case(&["03/04/2023", "04/25/2023", "05/26/2023"], Some("%m/%d/%Y"))]
+    #[case(&["someday"], None)]
+    #[case(&[], None)]
+    fn test_detect_date_format(#[case] dates: &[&str], #[case] expected: Option<&str>) {
+        assert_eq!(detect_date_format(dates.iter().copied()), expected);
+    }
+
+    #[test]
+    fn test_alternative_titles() {
+        let titles = alternative_titles(
+            "Spirited Away",
+            [
+                "千と千尋の神隠し",
+                "spirited away",
+                " ",
+                "Sen to Chihiro",
+                "千と千尋の神隠し",
+            ]
+            .map(String::from),
+        );
+        assert_eq!(titles, vec!["千と千尋の神隠し", "Sen to Chihiro"]);
+    }
+}
@@ -45,6 +45,7 @@ import {
 	DeployUpdateMetadataJobDocument,
 	type DeployUpdateMetadataJobMutationVariables,
 	MediaDetailsDocument,
+	MediaState,
 	MediaInCollectionsDocument,
 	MediaItemReviewsDocument,
 	type MediaItemReviewsQuery,
@@ -400,7 +401,7 @@ const ReviewItem = ({
 };
 
 const Page: NextPageWithLayout = () => {
-	const [changeState, setChangeState] = useState<SeenState>();
+	const [changeState, setChangeState] = useState<MediaState>();
 
 	const [
 		progressModalOpened,
@@ -935,8 +936,8 @@ const Page: NextPageWithLayout = () => {
 														onChange={(v) => {
 															if (v) {
 																const state = match(v)
-																	.with("Drop", () => SeenState.Dropped)
-																	.with("Put on hold", () => SeenState.OnAHold)
+																	.with("Drop", () => MediaState.Dropped)
+																	.with("Put on hold", () => MediaState.OnHold)
 																	.otherwise(() => undefined);
 																if (state) setChangeState(state);
 															}
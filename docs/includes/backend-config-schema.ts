@@ -96,6 +96,7 @@ export interface IntegrationConfig {
 	hasher_salt: string;
 	/**
 	 * The maximum progress limit after which a media is considered to be completed.
+	 * Users can change this with their `completion_threshold_percent` preference.
 	 * @default 95
 	 */
 	maximum_progress_limit: number;
@@ -122,7 +123,61 @@ export interface MangaConfig {
 }
 
 export interface MediaConfig {
+	/**
+	 * The number of minutes after a successful import before the user can
+	 * import from the same source again. Set to `0` to disable the cooldown.
+	 * @default 0
+	 */
+	import_cooldown_minutes: number;
+	/**
+	 * The number of seconds an imported item can take to be fetched from its
+	 * provider before it fails.
+	 * @default 30
+	 */
+	import_item_timeout: number;
+	/**
+	 * The number of items in a row that can fail to be fetched from the same
+	 * provider, because it could not be reached, responded with a server error
+	 * or timed out, before the remaining items from it are skipped. Set to `0`
+	 * to never skip them.
+	 * @default 5
+	 */
+	import_provider_failure_threshold: number;
+	/**
+	 * The number of days for which import reports are kept. They are also the
+	 * source of the import audit log. Set to `0` to keep them forever.
+	 * @default 0
+	 */
+	import_report_retention_days: number;
+	/**
+	 * The cooldown of specific sources, for eg `{ "Trakt": 60 }`, used
+	 * instead of `import_cooldown_minutes`. Can only be set in the config
+	 * file.
+	 */
+	import_source_cooldown_minutes: Record<string, number>;
+	/**
+	 * Regular expressions removed from titles before imported items are
+	 * matched by their title, in addition to the defaults of each source. They
+	 * are separated by semicolons when set using an environment variable.
+	 */
+	import_title_strip_patterns: string[];
+	/**
+	 * The number of hours for which details fetched from a provider are
+	 * reused when the same media is committed again. Set to `0` to disable
+	 * the cache.
+	 * @default 168
+	 */
+	provider_cache_ttl_hours: number;
+}
 
+export interface MetricsConfig {
+	/**
+	 * Whether the Prometheus metrics will be exposed at `/metrics`.
+	 * @default true
+	 */
+	enabled: boolean;
+	/** If set, requests to `/metrics` must include this as a bearer token. */
+	token: string;
 }
 
 export interface MoviesTmdbConfig {
@@ -152,6 +207,56 @@ export interface PodcastConfig {
 	itunes: ITunesConfig;
 	/** Settings related to Listennotes. */
 	listennotes: ListenNotesConfig;
+	/**
+	 * Refresh the episodes of podcasts that are in a library every `n` hours.
+	 * Must be between `1` and `23`.
+	 * @default 6
+	 */
+	refresh_every: number;
+}
+
+export interface RateLimitConfig {
+	/**
+	 * The maximum number of media a single collection refresh will queue. The
+	 * ones that were updated the longest time ago are picked first.
+	 * @default 100
+	 */
+	collection_refresh_max_items: number;
+	/**
+	 * The number of collection refreshes a user can perform every minute. Set
+	 * to `0` to disable the limit.
+	 * @default 1
+	 */
+	collection_refresh_per_minute: number;
+	/**
+	 * Whether admins are exempt from all rate limits.
+	 * @default true
+	 */
+	exempt_admins: boolean;
+	/**
+	 * The number of exports a user can perform every minute. Set to `0` to
+	 * disable the limit.
+	 * @default 2
+	 */
+	export_per_minute: number;
+	/**
+	 * The number of import jobs a user can deploy every minute. Set to `0` to
+	 * disable the limit.
+	 * @default 2
+	 */
+	import_per_minute: number;
+	/**
+	 * The number of metadata refresh jobs a user can deploy every minute. Set
+	 * to `0` to disable the limit.
+	 * @default 10
+	 */
+	metadata_refresh_per_minute: number;
+	/**
+	 * The number of searches a user can perform every minute. Users that are
+	 * not logged in share this limit. Set to `0` to disable the limit.
+	 * @default 30
+	 */
+	search_per_minute: number;
 }
 
 export interface SchedulerConfig {
@@ -184,12 +289,23 @@ export interface ServerConfig {
 	 * instance.
 	 */
 	default_credentials: boolean;
+	/**
+	 * Whether introspection queries will be rejected. Introspection is always
+	 * allowed when running in development mode.
+	 */
+	disable_introspection: boolean;
 	/**
 	 * This will make auth cookies insecure and should be set to `true` if you
 	 * are running the server on `localhost`.
 	 * [More information](https://github.com/IgnisDa/ryot/issues/23)
 	 */
 	insecure_cookie: boolean;
+	/**
+	 * The path to a JSON file containing an array of SHA-256 hashes of the
+	 * GraphQL operations that are allowed to be executed. All operations are
+	 * allowed if this is empty or when running in development mode.
+	 */
+	persisted_queries_path: string;
 	/**
 	 * The hours in which a media can be marked as seen again for a user. This
 	 * is used so that the same media can not be used marked as started when
@@ -197,6 +313,41 @@ export interface ServerConfig {
 	 * @default 2
 	 */
 	progress_update_threshold: number;
+	/**
+	 * Whether anonymous statistics about the instance can be queried without
+	 * logging in.
+	 */
+	public_statistics: boolean;
+	/**
+	 * The number of seconds that a background job can wait to be picked up
+	 * by a worker before the instance is marked as not ready. Set to `0` to
+	 * disable this check.
+	 * @default 3600
+	 */
+	readiness_max_job_wait: number;
+	/**
+	 * Whether the readiness check will also make sure that the providers
+	 * (TMDB) are reachable.
+	 */
+	readiness_probe_provider: boolean;
+	/**
+	 * Whether a failing provider probe will mark the instance as not ready.
+	 * Otherwise it is only reported as a warning.
+	 */
+	readiness_requires_provider: boolean;
+	/**
+	 * The key used to encrypt the secrets that are stored in the database,
+	 * for eg the tokens of integrations and the provider credentials of
+	 * users. Secrets are stored unencrypted and provider credentials can not
+	 * be stored if this is empty.
+	 */
+	secrets_key: string;
+	/**
+	 * The number of seconds to wait for running background jobs to finish
+	 * their current item when the server is shutting down.
+	 * @default 30
+	 */
+	shutdown_grace_period: number;
 }
 
 export interface ShowsTmdbConfig {
@@ -276,10 +427,14 @@ export interface AppConfig {
 	manga: MangaConfig;
 	/** Settings related to media. */
 	media: MediaConfig;
+	/** Settings related to the metrics endpoint. */
+	metrics: MetricsConfig;
 	/** Settings related to movies. */
 	movies: MovieConfig;
 	/** Settings related to podcasts. */
 	podcasts: PodcastConfig;
+	/** Settings related to rate limiting expensive operations. */
+	rate_limit: RateLimitConfig;
 	/** Settings related to scheduler. */
 	scheduler: SchedulerConfig;
 	/** Settings related to server. */
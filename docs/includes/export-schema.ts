@@ -4,8 +4,11 @@ export type ImportOrExportItemReview = {
 	date: string | null;
 	spoiler: boolean | null;
 	text: string | null;
+	visibility: Visibility | null;
 };
 
+export type Visibility = "Public" | "Private";
+
 export type ImportOrExportItemRating = {
 	review: ImportOrExportItemReview | null;
 	rating: string | null;
@@ -20,6 +23,9 @@ export type ImportOrExportItemSeen = {
 	show_season_number: number | null;
 	show_episode_number: number | null;
 	podcast_episode_number: number | null;
+	podcast_episode_guid: string | null;
+	progress: number | null;
+	plays: number | null;
 };
 
 export type MetadataSource =
@@ -38,6 +44,7 @@ export type MetadataSource =
  */
 export type ImportOrExportItem<T> = {
 	source_id: string;
+	title: string | null;
 	lot: MetadataLot;
 	source: MetadataSource;
 	identifier: T;
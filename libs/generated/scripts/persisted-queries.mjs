@@ -0,0 +1,26 @@
+// Emits the SHA-256 hashes of all backend operations so that the server can
+// use them as an allow-list (see `server.persisted_queries_path`).
+import { createHash } from "node:crypto";
+import { readFileSync, readdirSync, writeFileSync } from "node:fs";
+import { dirname, join, resolve } from "node:path";
+import { fileURLToPath } from "node:url";
+import { parse, print } from "graphql";
+
+const rootPath = resolve(dirname(fileURLToPath(import.meta.url)), "../../");
+const definitionsPath = join(rootPath, "graphql", "src", "backend");
+const outputPath =
+	process.argv[2] ?? join(rootPath, "generated", "persisted-queries.json");
+
+const hashes = ["queries", "mutations"].flatMap((kind) =>
+	readdirSync(join(definitionsPath, kind))
+		.filter((file) => file.endsWith(".gql"))
+		.map((file) => {
+			const source = readFileSync(join(definitionsPath, kind, file), "utf-8");
+			// the client sends the printed document, so that is what gets hashed
+			const query = print(parse(source));
+			return createHash("sha256").update(query).digest("hex");
+		}),
+);
+
+writeFileSync(outputPath, JSON.stringify(hashes.sort(), null, "\t"));
+console.log(`Wrote ${hashes.length} hashes to ${outputPath}`);
@@ -358,6 +358,7 @@ export enum ImportFailStep {
 
 export type ImportFailedItem = {
   error?: Maybe<Scalars['String']>;
+  id: Scalars['String'];
   identifier: Scalars['String'];
   lot: MetadataLot;
   step: ImportFailStep;
@@ -403,6 +404,7 @@ export type MangaSummary = {
 export type MediaFilter = {
   collection?: InputMaybe<Scalars['Int']>;
   general?: InputMaybe<MediaGeneralFilter>;
+  state?: InputMaybe<MediaState>;
 };
 
 export enum MediaGeneralFilter {
@@ -485,6 +487,20 @@ export enum MediaSortOrder {
   Desc = 'DESC'
 }
 
+/** The state a user has put a media item in */
+export enum MediaState {
+  Completed = 'COMPLETED',
+  Dropped = 'DROPPED',
+  InProgress = 'IN_PROGRESS',
+  /**
+   * The name this state had when it was set through the state of seen items
+   * @deprecated Use `ON_HOLD` instead
+   */
+  OnAHold = 'ON_A_HOLD',
+  OnHold = 'ON_HOLD',
+  PlanTo = 'PLAN_TO'
+}
+
 export type MetadataCreator = {
   imageUrls: Array<Scalars['String']>;
   name: Scalars['String'];
@@ -530,6 +546,8 @@ export type MoviesSummary = {
 export type MutationRoot = {
   /** Add a media item to a collection if it is not there, otherwise do nothing. */
   addMediaToCollection: Scalars['Boolean'];
+  /** Change the state a user has put a media item in. */
+  changeMediaState: Scalars['Boolean'];
   /** Fetch details about a media and create a media item in the database. */
   commitMedia: IdObject;
   /** Create a custom media item. */
@@ -598,6 +616,12 @@ export type MutationRootAddMediaToCollectionArgs = {
 };
 
 
+export type MutationRootChangeMediaStateArgs = {
+  metadataId: Scalars['Int'];
+  state: MediaState;
+};
+
+
 export type MutationRootCommitMediaArgs = {
   identifier: Scalars['String'];
   lot: MetadataLot;
@@ -768,7 +792,8 @@ export enum ProgressUpdateErrorVariant {
 }
 
 export type ProgressUpdateInput = {
-  changeState?: InputMaybe<SeenState>;
+  /** Drop the media or put it on hold, along with its latest seen item. */
+  changeState?: InputMaybe<MediaState>;
   date?: InputMaybe<Scalars['NaiveDate']>;
   metadataId: Scalars['Int'];
   podcastEpisodeNumber?: InputMaybe<Scalars['Int']>;
@@ -1093,6 +1118,8 @@ export type UserMediaSummary = {
   anime: AnimeSummary;
   audioBooks: AudioBooksSummary;
   books: BooksSummary;
+  /** The number of media that have been dropped */
+  dropped: Scalars['Int'];
   manga: MangaSummary;
   movies: MoviesSummary;
   podcasts: PodcastsSummary;